@@ -0,0 +1,85 @@
+//! Off-chain cost of the state and instruction codecs every handler goes
+//! through. Run with `cargo bench --bench pack`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use test_token_solana::{
+    instruction::{initialize_mint, TokenInstruction},
+    state::{Account, AccountState, Mint},
+};
+
+fn account() -> Account {
+    Account {
+        mint: Pubkey::new_from_array([1; 32]),
+        owner: Pubkey::new_from_array([2; 32]),
+        amount: 1_000,
+        delegate: COption::Some(Pubkey::new_from_array([3; 32])),
+        state: AccountState::Initialized,
+        delegated_amount: 100,
+        ..Account::default()
+    }
+}
+
+fn bench_account(c: &mut Criterion) {
+    let account = account();
+    let mut data = vec![0; Account::LEN];
+    c.bench_function("Account::pack", |b| {
+        b.iter(|| Account::pack(black_box(account), &mut data).unwrap())
+    });
+    Account::pack(account, &mut data).unwrap();
+    c.bench_function("Account::unpack", |b| {
+        b.iter(|| Account::unpack(black_box(&data)).unwrap())
+    });
+}
+
+fn bench_mint(c: &mut Criterion) {
+    let mint = Mint {
+        mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+        supply: 1_000,
+        decimals: 2,
+        is_initialized: true,
+        ..Mint::default()
+    };
+    let mut data = vec![0; Mint::LEN];
+    c.bench_function("Mint::pack", |b| {
+        b.iter(|| Mint::pack(black_box(mint), &mut data).unwrap())
+    });
+    Mint::pack(mint, &mut data).unwrap();
+    c.bench_function("Mint::unpack", |b| {
+        b.iter(|| Mint::unpack(black_box(&data)).unwrap())
+    });
+}
+
+fn bench_instruction(c: &mut Criterion) {
+    let instructions = [
+        ("Transfer", TokenInstruction::Transfer { amount: 100 }.pack()),
+        (
+            "TransferChecked",
+            TokenInstruction::TransferChecked {
+                amount: 100,
+                decimals: 2,
+            }
+            .pack(),
+        ),
+        (
+            "InitializeMint",
+            initialize_mint(
+                &test_token_solana::id(),
+                &Pubkey::new_from_array([1; 32]),
+                &Pubkey::new_from_array([2; 32]),
+                Some(&Pubkey::new_from_array([3; 32])),
+                2,
+            )
+            .unwrap()
+            .data,
+        ),
+    ];
+    for (name, data) in instructions.iter() {
+        c.bench_function(&format!("TokenInstruction::unpack {}", name), |b| {
+            b.iter(|| TokenInstruction::unpack(black_box(data)).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, bench_account, bench_mint, bench_instruction);
+criterion_main!(benches);
@@ -0,0 +1,328 @@
+//! Feeds arbitrary instruction data over a fixed universe of mints, token
+//! accounts and signers, with arbitrary byte mutations applied to their
+//! buffers first, into `Processor::process`.
+//!
+//! The process must never panic, and an instruction that succeeds must leave
+//! every program account it wrote unpackable and must not break the supply
+//! invariant of a mint where it held before.
+//!
+//! Run with `cargo fuzz run process`. A crashing input is written to
+//! `artifacts/process/`; `cargo fuzz tmin process <artifact>` shrinks it and
+//! `cargo fuzz fmt process <artifact>` prints it as a `FuzzInput`.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use solana_sdk::account::{
+    create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
+};
+use std::sync::Once;
+use test_token_solana::{
+    instruction::TokenInstruction,
+    processor::Processor,
+    state::{
+        Account, AccountState, FlashMintConfig, Mint, Multisig, TransferFeeConfig,
+        TransferPolicy, LEGACY_ACCOUNT_LEN, LEGACY_MINT_LEN,
+    },
+};
+
+#[derive(Arbitrary, Debug)]
+enum Mutation {
+    /// Overwrites one byte of an account's data
+    Set { account: u8, offset: u16, value: u8 },
+    /// Shrinks or zero-extends an account's data
+    Resize { account: u8, len: u16 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzInput {
+    /// Picks the balances the universe starts from
+    seed: u64,
+    mutations: Vec<Mutation>,
+    /// Universe index and signer flag of each instruction account
+    accounts: Vec<(u8, bool)>,
+    data: Vec<u8>,
+}
+
+/// Quiet logs, default sysvars, and no cross-program invocation
+struct FuzzStubs;
+
+impl SyscallStubs for FuzzStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _data: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Clock) = Clock::default();
+        }
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        _instruction: &Instruction,
+        _account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        Err(ProgramError::IncorrectProgramId)
+    }
+}
+
+fn key(index: u8) -> Pubkey {
+    Pubkey::new_from_array([index + 1; 32])
+}
+
+fn packed<T: Pack>(value: T) -> SolanaAccount {
+    let mut account = SolanaAccount::new(
+        Rent::default().minimum_balance(T::LEN),
+        T::LEN,
+        &test_token_solana::id(),
+    );
+    T::pack(value, &mut account.data).unwrap();
+    account
+}
+
+/// The same universe for the same seed: the authority, two owners and a
+/// delegate, two mints with two accounts each, a multisig, blank mint and
+/// account buffers, and the rent sysvar. Universe index `i` has key `key(i)`,
+/// except for the sysvar.
+fn universe(seed: u64) -> Vec<(Pubkey, SolanaAccount)> {
+    let mut state = seed | 1;
+    let mut next_amount = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % 1_000_000
+    };
+    let (a1, a2, b1, b2, withheld) = (
+        next_amount(),
+        next_amount(),
+        next_amount(),
+        next_amount(),
+        next_amount(),
+    );
+    let token_account = |mint: u8, owner: u8, amount: u64| Account {
+        mint: key(mint),
+        owner: key(owner),
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    };
+    let mut signers = [Pubkey::default(); 11];
+    signers[0] = key(1);
+    signers[1] = key(2);
+
+    let accounts = vec![
+        SolanaAccount::default(),
+        SolanaAccount::default(),
+        SolanaAccount::default(),
+        SolanaAccount::default(),
+        packed(Mint {
+            mint_authority: COption::Some(key(0)),
+            supply: a1 + a2,
+            decimals: 2,
+            is_initialized: true,
+            freeze_authority: COption::Some(key(0)),
+            ..Mint::default()
+        }),
+        packed(Mint {
+            mint_authority: COption::Some(key(10)),
+            supply: b1 + b2 + withheld,
+            decimals: 6,
+            is_initialized: true,
+            permanent_delegate: COption::Some(key(3)),
+            transfer_fee_config: TransferFeeConfig {
+                transfer_fee_authority: COption::Some(key(0)),
+                transfer_fee_basis_points: 100,
+                maximum_fee: 1_000,
+            },
+            ..Mint::default()
+        }),
+        packed(token_account(4, 1, a1)),
+        packed(Account {
+            delegate: COption::Some(key(3)),
+            delegated_amount: a2 / 2,
+            ..token_account(4, 2, a2)
+        }),
+        packed(Account {
+            withheld_amount: withheld,
+            ..token_account(5, 1, b1)
+        }),
+        packed(Account {
+            state: AccountState::Frozen,
+            ..token_account(5, 2, b2)
+        }),
+        packed(Multisig {
+            m: 1,
+            n: 2,
+            is_initialized: true,
+            signers,
+        }),
+        SolanaAccount::new(0, Mint::LEN, &test_token_solana::id()),
+        SolanaAccount::new(0, Account::LEN, &test_token_solana::id()),
+    ];
+    let mut universe: Vec<(Pubkey, SolanaAccount)> = accounts
+        .into_iter()
+        .enumerate()
+        .map(|(index, account)| (key(index as u8), account))
+        .collect();
+    universe.push((
+        sysvar::rent::id(),
+        create_account_for_test(&Rent::default()),
+    ));
+    universe
+}
+
+/// Each mint whose supply equals what its non-native accounts hold
+fn balanced_mints(universe: &[(Pubkey, SolanaAccount)]) -> Vec<Pubkey> {
+    let accounts: Vec<Account> = universe
+        .iter()
+        .filter(|(_, account)| account.owner == test_token_solana::id())
+        .filter_map(|(_, account)| Account::unpack(&account.data).ok())
+        .collect();
+    universe
+        .iter()
+        .filter(|(_, account)| account.owner == test_token_solana::id())
+        .filter_map(|(key, account)| Some((key, Mint::unpack(&account.data).ok()?)))
+        .filter(|(key, mint)| {
+            let of_mint = || accounts.iter().filter(|account| account.mint == **key);
+            if of_mint().any(|account| account.is_native()) {
+                return false;
+            }
+            let held = of_mint()
+                .map(|account| {
+                    account.amount as u128
+                        + account.withheld_amount as u128
+                        + account.vesting_amount as u128
+                })
+                .sum::<u128>();
+            held + mint.withheld_amount as u128 == mint.supply as u128
+        })
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// Whether data of this length, if written, must unpack as its layout
+fn unpacks(data: &[u8]) -> bool {
+    match data.len() {
+        Mint::LEN | LEGACY_MINT_LEN => Mint::unpack_unchecked(data).is_ok(),
+        Account::LEN | LEGACY_ACCOUNT_LEN => Account::unpack_unchecked(data).is_ok(),
+        Multisig::LEN => Multisig::unpack_unchecked(data).is_ok(),
+        FlashMintConfig::LEN => FlashMintConfig::unpack_unchecked(data).is_ok(),
+        TransferPolicy::LEN => TransferPolicy::unpack_unchecked(data).is_ok(),
+        _ => true,
+    }
+}
+
+/// Whether the instruction, or any instruction of a batch, resizes an
+/// account. `AccountInfo::realloc` needs the runtime's serialized layout,
+/// which the unit-test account infos do not have.
+fn resizes(data: &[u8]) -> bool {
+    match TokenInstruction::unpack(data) {
+        Ok(TokenInstruction::Reallocate)
+        | Ok(TokenInstruction::ReallocateMint)
+        | Ok(TokenInstruction::InitializeTokenMetadata { .. })
+        | Ok(TokenInstruction::UpdateTokenMetadataField { .. }) => true,
+        Ok(TokenInstruction::Batch { instructions }) => instructions
+            .iter()
+            .any(|entry| resizes(entry.get(1..).unwrap_or_default())),
+        _ => false,
+    }
+}
+
+fuzz_target!(|input: FuzzInput| {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(FuzzStubs));
+    });
+
+    if resizes(&input.data) {
+        return;
+    }
+
+    let mut universe = universe(input.seed);
+    let len = universe.len();
+    for mutation in input.mutations.iter() {
+        match *mutation {
+            Mutation::Set {
+                account,
+                offset,
+                value,
+            } => {
+                let data = &mut universe[account as usize % len].1.data;
+                if !data.is_empty() {
+                    let offset = offset as usize % data.len();
+                    data[offset] = value;
+                }
+            }
+            Mutation::Resize { account, len: new_len } => {
+                universe[account as usize % len]
+                    .1
+                    .data
+                    .resize(new_len as usize % 1024, 0);
+            }
+        }
+    }
+
+    // The unit-test machinery gives every meta its own account, so a key
+    // listed twice would be two diverging copies
+    let indices: Vec<(usize, bool)> = input
+        .accounts
+        .iter()
+        .map(|&(index, is_signer)| (index as usize % len, is_signer))
+        .collect();
+    if (1..indices.len()).any(|i| indices[..i].iter().any(|(index, _)| *index == indices[i].0)) {
+        return;
+    }
+
+    let balanced = balanced_mints(&universe);
+    let mut slots: Vec<(Pubkey, bool, SolanaAccount)> = indices
+        .iter()
+        .map(|&(index, is_signer)| (universe[index].0, is_signer, universe[index].1.clone()))
+        .collect();
+    let result = {
+        let mut metas: Vec<(&Pubkey, bool, &mut SolanaAccount)> = slots
+            .iter_mut()
+            .map(|(key, is_signer, account)| (&*key, *is_signer, account))
+            .collect();
+        let account_infos = create_is_signer_account_infos(&mut metas);
+        Processor::process(&test_token_solana::id(), &account_infos, &input.data)
+    };
+    if result.is_err() {
+        return;
+    }
+
+    for (&(index, _), (_, _, account)) in indices.iter().zip(slots) {
+        let before = &universe[index].1;
+        if account.owner == test_token_solana::id() && account.data != before.data {
+            assert!(unpacks(&account.data), "wrote unpackable data to {}", index);
+        }
+        universe[index].1 = account;
+    }
+    let still_balanced = balanced_mints(&universe);
+    for mint in balanced {
+        assert!(still_balanced.contains(&mint), "supply of {} drifted", mint);
+    }
+});
@@ -0,0 +1,224 @@
+//! Conversions between raw token amounts and UI amounts, the raw amount
+//! scaled down by `10^decimals`.
+//!
+//! The `AmountToUiAmount` and `UiAmountToAmount` instructions call these
+//! functions, so client code that uses them formats and parses amounts exactly
+//! as the program does. The conversions work on decimal strings and need
+//! `alloc` but no floating-point routines from `std`. The `f64` functions are
+//! for display and input only: an `f64` holds fewer significant digits than a
+//! `u64`.
+
+use solana_program::program_error::ProgramError;
+
+/// The UI amount as a decimal string with exactly `decimals` fractional
+/// digits
+pub fn amount_to_ui_amount_string(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    if decimals > 0 {
+        // Left-pad zeros to decimals + 1, so we at least have an integer zero
+        let mut s = format!("{:01$}", amount, decimals + 1);
+        s.insert(s.len() - decimals, '.');
+        s
+    } else {
+        amount.to_string()
+    }
+}
+
+/// Like `amount_to_ui_amount_string`, without trailing fractional zeros or
+/// a trailing point. This is what `AmountToUiAmount` returns.
+pub fn amount_to_ui_amount_string_trimmed(amount: u64, decimals: u8) -> String {
+    let mut s = amount_to_ui_amount_string(amount, decimals);
+    if decimals > 0 {
+        let zeros_trimmed = s.trim_end_matches('0');
+        s = zeros_trimmed.trim_end_matches('.').to_string();
+    }
+    s
+}
+
+/// Parses a decimal UI amount exactly, failing with `InvalidArgument` on an
+/// empty or malformed string, more fractional digits than `decimals` other
+/// than trailing zeros, or a value past `u64::MAX`. This is what
+/// `UiAmountToAmount` parses.
+pub fn try_ui_amount_into_amount(ui_amount: &str, decimals: u8) -> Result<u64, ProgramError> {
+    let decimals = decimals as usize;
+    let mut parts = ui_amount.split('.');
+    // Splitting always yields at least one part, even for an empty string
+    let mut amount_str = parts.next().unwrap().to_string();
+    let after_decimal = parts.next().unwrap_or("");
+    let after_decimal = after_decimal.trim_end_matches('0');
+    if (amount_str.is_empty() && after_decimal.is_empty())
+        || parts.next().is_some()
+        || after_decimal.len() > decimals
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    amount_str.push_str(after_decimal);
+    for _ in 0..decimals.saturating_sub(after_decimal.len()) {
+        amount_str.push('0');
+    }
+    amount_str
+        .parse::<u64>()
+        .map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// The UI amount as the `f64` nearest to it
+pub fn amount_to_ui_amount(amount: u64, decimals: u8) -> f64 {
+    // Always a decimal number, so the parse cannot fail
+    amount_to_ui_amount_string(amount, decimals)
+        .parse()
+        .unwrap()
+}
+
+/// The raw amount of a UI amount given as an `f64`, dropping the digits
+/// past `decimals`. The shortest decimal form of `ui_amount` is converted,
+/// so `1.15` with two decimals is 115 even though the nearest `f64` is
+/// slightly smaller. Negative amounts and NaN give zero, and amounts past
+/// `u64::MAX` saturate.
+pub fn ui_amount_to_amount(ui_amount: f64, decimals: u8) -> u64 {
+    if ui_amount.is_nan() || ui_amount <= 0.0 {
+        return 0;
+    }
+    // `Display` never uses an exponent, and infinity fails to parse below
+    let ui_amount = ui_amount.to_string();
+    let (integer, fraction) = match ui_amount.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (ui_amount.as_str(), ""),
+    };
+    let fraction = &fraction[..fraction.len().min(decimals as usize)];
+    try_ui_amount_into_amount(&format!("{}.{}", integer, fraction), decimals).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_to_ui_amount_string() {
+        assert_eq!(amount_to_ui_amount_string(0, 0), "0");
+        assert_eq!(amount_to_ui_amount_string(0, 2), "0.00");
+        assert_eq!(amount_to_ui_amount_string(1, 2), "0.01");
+        assert_eq!(amount_to_ui_amount_string(1_500_000, 6), "1.500000");
+        assert_eq!(amount_to_ui_amount_string(u64::MAX, 0), "18446744073709551615");
+        assert_eq!(amount_to_ui_amount_string(u64::MAX, 2), "184467440737095516.15");
+        assert_eq!(amount_to_ui_amount_string(u64::MAX, 20), "0.18446744073709551615");
+    }
+
+    #[test]
+    fn test_amount_to_ui_amount_string_trimmed() {
+        assert_eq!(amount_to_ui_amount_string_trimmed(0, 0), "0");
+        assert_eq!(amount_to_ui_amount_string_trimmed(0, 9), "0");
+        assert_eq!(amount_to_ui_amount_string_trimmed(1_500_000, 6), "1.5");
+        assert_eq!(amount_to_ui_amount_string_trimmed(1_000_000, 6), "1");
+        assert_eq!(amount_to_ui_amount_string_trimmed(100, 0), "100");
+        assert_eq!(amount_to_ui_amount_string_trimmed(1, 9), "0.000000001");
+        assert_eq!(
+            amount_to_ui_amount_string_trimmed(u64::MAX, 2),
+            "184467440737095516.15"
+        );
+    }
+
+    #[test]
+    fn test_try_ui_amount_into_amount() {
+        assert_eq!(try_ui_amount_into_amount("0", 0), Ok(0));
+        assert_eq!(try_ui_amount_into_amount("0", 6), Ok(0));
+        assert_eq!(try_ui_amount_into_amount("1.5", 6), Ok(1_500_000));
+        assert_eq!(try_ui_amount_into_amount("1", 6), Ok(1_000_000));
+        assert_eq!(try_ui_amount_into_amount("1.", 6), Ok(1_000_000));
+        assert_eq!(try_ui_amount_into_amount(".5", 1), Ok(5));
+        assert_eq!(try_ui_amount_into_amount("0.000000001", 9), Ok(1));
+        assert_eq!(try_ui_amount_into_amount("1.50", 1), Ok(15));
+        assert_eq!(
+            try_ui_amount_into_amount("18446744073709551615", 0),
+            Ok(u64::MAX)
+        );
+        assert_eq!(
+            try_ui_amount_into_amount("184467440737095516.15", 2),
+            Ok(u64::MAX)
+        );
+
+        // too many fractional digits
+        assert_eq!(
+            try_ui_amount_into_amount("0.001", 2),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            try_ui_amount_into_amount("1.5", 0),
+            Err(ProgramError::InvalidArgument)
+        );
+        // overflow
+        assert_eq!(
+            try_ui_amount_into_amount("18446744073709551616", 0),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            try_ui_amount_into_amount("184467440737095516.16", 2),
+            Err(ProgramError::InvalidArgument)
+        );
+        // malformed
+        assert_eq!(try_ui_amount_into_amount("", 2), Err(ProgramError::InvalidArgument));
+        assert_eq!(try_ui_amount_into_amount(".", 2), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            try_ui_amount_into_amount("1.2.3", 2),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            try_ui_amount_into_amount("1a", 2),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_amount_to_ui_amount() {
+        assert_eq!(amount_to_ui_amount(0, 0), 0.0);
+        assert_eq!(amount_to_ui_amount(0, 9), 0.0);
+        assert_eq!(amount_to_ui_amount(100, 0), 100.0);
+        assert_eq!(amount_to_ui_amount(1, 9), 0.000000001);
+        assert_eq!(amount_to_ui_amount(1_500_000, 6), 1.5);
+        assert_eq!(amount_to_ui_amount(115, 2), 1.15);
+        assert_eq!(amount_to_ui_amount(u64::MAX, 0), u64::MAX as f64);
+        assert_eq!(amount_to_ui_amount(u64::MAX, 2), 184467440737095516.15);
+        assert_eq!(
+            amount_to_ui_amount(u64::MAX, 20),
+            "0.18446744073709551615".parse::<f64>().unwrap()
+        );
+        assert_eq!(amount_to_ui_amount(1, u8::MAX), 1e-255);
+    }
+
+    #[test]
+    fn test_ui_amount_to_amount() {
+        assert_eq!(ui_amount_to_amount(0.0, 0), 0);
+        assert_eq!(ui_amount_to_amount(0.0, 9), 0);
+        assert_eq!(ui_amount_to_amount(100.0, 0), 100);
+        assert_eq!(ui_amount_to_amount(0.000000001, 9), 1);
+        assert_eq!(ui_amount_to_amount(1.5, 6), 1_500_000);
+        // the nearest f64 to 1.15 is below it, which scaling would truncate
+        assert_eq!((1.15f64 * 100.0) as u64, 114);
+        assert_eq!(ui_amount_to_amount(1.15, 2), 115);
+        // digits past the decimals are dropped, not rounded
+        assert_eq!(ui_amount_to_amount(1.999, 2), 199);
+        assert_eq!(ui_amount_to_amount(0.000000001, 8), 0);
+        assert_eq!(ui_amount_to_amount(1.5, 0), 1);
+        assert_eq!(ui_amount_to_amount(1e20, 0), u64::MAX);
+        assert_eq!(ui_amount_to_amount(u64::MAX as f64, 0), u64::MAX);
+        assert_eq!(ui_amount_to_amount(1e9, 9), 1_000_000_000_000_000_000);
+        assert_eq!(ui_amount_to_amount(f64::INFINITY, 2), u64::MAX);
+        assert_eq!(ui_amount_to_amount(-1.0, 2), 0);
+        assert_eq!(ui_amount_to_amount(f64::NEG_INFINITY, 2), 0);
+        assert_eq!(ui_amount_to_amount(f64::NAN, 2), 0);
+
+        // amounts of up to 15 significant digits survive the round trip
+        for (amount, decimals) in [
+            (1, 9),
+            (123_456_789, 6),
+            (999_999_999_999_999, 0),
+            (999_999_999_999_999, 9),
+            (100_000_000_000_000_000, 18),
+        ] {
+            assert_eq!(
+                ui_amount_to_amount(amount_to_ui_amount(amount, decimals), decimals),
+                amount
+            );
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! `proptest` strategies for the state and instruction types, so property
+//! tests can draw them with `any::<T>()`. Built with the `proptest` feature.
+
+use crate::{
+    instruction::{AuthorityType, TokenInstruction, MAX_BATCH_SIZE},
+    state::{Account, AccountState, InterestBearingConfig, Mint, PolicyMode, TransferFeeConfig},
+};
+use proptest::prelude::*;
+use solana_program::{program_option::COption, pubkey::Pubkey};
+
+fn arb_pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn arb_coption<T: std::fmt::Debug>(
+    value: impl Strategy<Value = T>,
+) -> impl Strategy<Value = COption<T>> {
+    prop::option::of(value).prop_map(COption::from)
+}
+
+impl Arbitrary for AuthorityType {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(AuthorityType::MintTokens),
+            Just(AuthorityType::AccountOwner),
+            Just(AuthorityType::CloseAccount),
+            Just(AuthorityType::FreezeAccount),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for AccountState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(AccountState::Uninitialized),
+            Just(AccountState::Initialized),
+            Just(AccountState::Frozen),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for PolicyMode {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![Just(PolicyMode::Allowlist), Just(PolicyMode::Blocklist)].boxed()
+    }
+}
+
+impl Arbitrary for TransferFeeConfig {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (arb_coption(arb_pubkey()), any::<u16>(), any::<u64>())
+            .prop_map(
+                |(transfer_fee_authority, transfer_fee_basis_points, maximum_fee)| {
+                    TransferFeeConfig {
+                        transfer_fee_authority,
+                        transfer_fee_basis_points,
+                        maximum_fee,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for InterestBearingConfig {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            arb_coption(arb_pubkey()),
+            any::<i64>(),
+            any::<i16>(),
+            any::<i64>(),
+            any::<i16>(),
+        )
+            .prop_map(
+                |(
+                    rate_authority,
+                    initialization_timestamp,
+                    pre_update_average_rate,
+                    last_update_timestamp,
+                    current_rate,
+                )| InterestBearingConfig {
+                    rate_authority,
+                    initialization_timestamp,
+                    pre_update_average_rate,
+                    last_update_timestamp,
+                    current_rate,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for Mint {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            arb_coption(arb_pubkey()),
+            any::<u64>(),
+            any::<u8>(),
+            any::<bool>(),
+            arb_coption(arb_pubkey()),
+            arb_coption(any::<u64>()),
+            arb_coption(arb_pubkey()),
+            any::<AccountState>(),
+            any::<TransferFeeConfig>(),
+            any::<u64>(),
+            any::<InterestBearingConfig>(),
+            (
+                arb_coption(arb_pubkey()),
+                any::<bool>(),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+            ),
+        )
+            .prop_map(
+                |(
+                    mint_authority,
+                    supply,
+                    decimals,
+                    is_initialized,
+                    freeze_authority,
+                    max_supply,
+                    permanent_delegate,
+                    default_account_state,
+                    transfer_fee_config,
+                    withheld_amount,
+                    interest_bearing_config,
+                    (
+                        pause_authority,
+                        paused,
+                        transfer_hook_authority,
+                        transfer_hook_program_id,
+                        metadata_pointer,
+                        transfer_policy,
+                    ),
+                )| Mint {
+                    mint_authority,
+                    supply,
+                    decimals,
+                    is_initialized,
+                    freeze_authority,
+                    max_supply,
+                    permanent_delegate,
+                    default_account_state,
+                    transfer_fee_config,
+                    withheld_amount,
+                    interest_bearing_config,
+                    pause_authority,
+                    paused,
+                    transfer_hook_authority,
+                    transfer_hook_program_id,
+                    metadata_pointer,
+                    transfer_policy,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for Account {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            arb_pubkey(),
+            arb_pubkey(),
+            any::<u64>(),
+            arb_coption(arb_pubkey()),
+            any::<u64>(),
+            any::<AccountState>(),
+            arb_coption(any::<u64>()),
+            arb_coption(arb_pubkey()),
+            arb_coption(any::<u64>()),
+            arb_coption(arb_pubkey()),
+            (
+                any::<bool>(),
+                any::<u64>(),
+                any::<bool>(),
+                any::<bool>(),
+                any::<bool>(),
+                arb_coption(any::<u64>()),
+                any::<bool>(),
+            ),
+            (
+                arb_coption(any::<u64>()),
+                any::<u64>(),
+                any::<u64>(),
+                any::<u64>(),
+                any::<u64>(),
+                any::<u64>(),
+                any::<u64>(),
+            ),
+        )
+            .prop_map(
+                |(
+                    mint,
+                    owner,
+                    amount,
+                    delegate,
+                    delegated_amount,
+                    state,
+                    is_native,
+                    close_authority,
+                    expiry_slot,
+                    pending_owner,
+                    (
+                        memo_required,
+                        withheld_amount,
+                        mint_required,
+                        immutable_owner,
+                        cpi_guard,
+                        lock_until,
+                        escrowed,
+                    ),
+                    (
+                        limit_amount,
+                        window_start_epoch,
+                        spent_in_window,
+                        vesting_amount,
+                        vesting_total,
+                        vesting_start_slot,
+                        vesting_end_slot,
+                    ),
+                )| Account {
+                    mint,
+                    owner,
+                    amount,
+                    delegate,
+                    delegated_amount,
+                    state,
+                    is_native,
+                    close_authority,
+                    expiry_slot,
+                    pending_owner,
+                    memo_required,
+                    withheld_amount,
+                    mint_required,
+                    immutable_owner,
+                    cpi_guard,
+                    lock_until,
+                    escrowed,
+                    limit_amount,
+                    window_start_epoch,
+                    spent_in_window,
+                    vesting_amount,
+                    vesting_total,
+                    vesting_start_slot,
+                    vesting_end_slot,
+                },
+            )
+            .boxed()
+    }
+}
+
+/// Every instruction except those that borrow strings, which a `'static`
+/// instruction could only hold as literals
+impl Arbitrary for TokenInstruction<'static> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            (
+                any::<u8>(),
+                arb_pubkey(),
+                arb_coption(arb_pubkey()),
+                arb_coption(any::<u64>()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey())
+            )
+                .prop_map(
+                    |(
+                        decimals,
+                        mint_authority,
+                        freeze_authority,
+                        max_supply,
+                        permanent_delegate,
+                        transfer_fee_authority,
+                        rate_authority,
+                        pause_authority,
+                    )| {
+                        TokenInstruction::InitializeMint {
+                            decimals,
+                            mint_authority,
+                            freeze_authority,
+                            max_supply,
+                            permanent_delegate,
+                            transfer_fee_authority,
+                            rate_authority,
+                            pause_authority,
+                        }
+                    }
+                ),
+            Just(TokenInstruction::InitializeAccount),
+            any::<u64>().prop_map(|amount| TokenInstruction::Transfer { amount }),
+            any::<u64>().prop_map(|amount| TokenInstruction::Approve { amount }),
+            any::<u64>().prop_map(|amount| TokenInstruction::MintTo { amount }),
+            any::<u64>().prop_map(|amount| TokenInstruction::Burn { amount }),
+            Just(TokenInstruction::Revoke),
+            (any::<AuthorityType>(), arb_coption(arb_pubkey())).prop_map(
+                |(authority_type, new_authority)| TokenInstruction::SetAuthority {
+                    authority_type,
+                    new_authority,
+                }
+            ),
+            Just(TokenInstruction::FreezeAccount),
+            Just(TokenInstruction::ThawAccount),
+            (any::<u64>(), any::<u8>()).prop_map(|(amount, decimals)| {
+                TokenInstruction::TransferChecked { amount, decimals }
+            }),
+            (any::<u64>(), any::<u8>()).prop_map(|(amount, decimals)| {
+                TokenInstruction::ApproveChecked { amount, decimals }
+            }),
+            (any::<u64>(), any::<u8>())
+                .prop_map(|(amount, decimals)| TokenInstruction::BurnChecked { amount, decimals }),
+            any::<u8>().prop_map(|m| TokenInstruction::InitializeMultisig { m }),
+            (
+                any::<u8>(),
+                arb_pubkey(),
+                arb_coption(arb_pubkey()),
+                arb_coption(any::<u64>()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey()),
+                arb_coption(arb_pubkey())
+            )
+                .prop_map(
+                    |(
+                        decimals,
+                        mint_authority,
+                        freeze_authority,
+                        max_supply,
+                        permanent_delegate,
+                        transfer_fee_authority,
+                        rate_authority,
+                        pause_authority,
+                    )| {
+                        TokenInstruction::InitializeMint2 {
+                            decimals,
+                            mint_authority,
+                            freeze_authority,
+                            max_supply,
+                            permanent_delegate,
+                            transfer_fee_authority,
+                            rate_authority,
+                            pause_authority,
+                        }
+                    }
+                ),
+            arb_pubkey().prop_map(|owner| TokenInstruction::InitializeAccount2 { owner }),
+            arb_pubkey().prop_map(|owner| TokenInstruction::InitializeAccount3 { owner }),
+            Just(TokenInstruction::SyncNative),
+            Just(TokenInstruction::GetAccountDataSize),
+            any::<u64>().prop_map(|amount| TokenInstruction::AmountToUiAmount { amount }),
+            Just(TokenInstruction::CloseAccount),
+            any::<u64>().prop_map(|amount| TokenInstruction::IncreaseDelegatedAmount { amount }),
+            any::<u64>().prop_map(|amount| TokenInstruction::DecreaseDelegatedAmount { amount }),
+            (any::<u64>(), any::<u64>()).prop_map(|(amount, expiry_slot)| {
+                TokenInstruction::ApproveWithExpiry {
+                    amount,
+                    expiry_slot,
+                }
+            }),
+            prop::collection::vec(any::<u64>(), 1..=MAX_BATCH_SIZE)
+                .prop_map(|amounts| TokenInstruction::TransferBatch { amounts }),
+            prop::collection::vec(any::<u64>(), 1..=MAX_BATCH_SIZE)
+                .prop_map(|amounts| TokenInstruction::MintToBatch { amounts }),
+            arb_pubkey().prop_map(|new_owner| TokenInstruction::ProposeOwner { new_owner }),
+            Just(TokenInstruction::AcceptOwnership),
+            any::<AccountState>()
+                .prop_map(|state| TokenInstruction::SetDefaultAccountState { state }),
+            any::<bool>().prop_map(|enable| TokenInstruction::RequireMemoTransfers { enable }),
+            (any::<u64>(), any::<u8>(), any::<u64>()).prop_map(|(amount, decimals, fee)| {
+                TokenInstruction::TransferCheckedWithFee {
+                    amount,
+                    decimals,
+                    fee,
+                }
+            }),
+            (any::<u16>(), any::<u64>()).prop_map(|(transfer_fee_basis_points, maximum_fee)| {
+                TokenInstruction::SetTransferFee {
+                    transfer_fee_basis_points,
+                    maximum_fee,
+                }
+            }),
+            any::<u8>().prop_map(|num_token_accounts| {
+                TokenInstruction::WithdrawWithheldTokensFromAccounts { num_token_accounts }
+            }),
+            Just(TokenInstruction::HarvestWithheldTokensToMint),
+            any::<i16>().prop_map(|rate| TokenInstruction::UpdateRateInterestBearingMint { rate }),
+            Just(TokenInstruction::Pause),
+            Just(TokenInstruction::Resume),
+            Just(TokenInstruction::Reallocate),
+            Just(TokenInstruction::ReallocateMint),
+            Just(TokenInstruction::InitializeImmutableOwner),
+            Just(TokenInstruction::EnableCpiGuard),
+            Just(TokenInstruction::DisableCpiGuard),
+            (arb_coption(arb_pubkey()), arb_coption(arb_pubkey())).prop_map(
+                |(authority, program_id)| TokenInstruction::InitializeTransferHook {
+                    authority,
+                    program_id,
+                }
+            ),
+            arb_coption(arb_pubkey())
+                .prop_map(|program_id| TokenInstruction::UpdateTransferHook { program_id }),
+            arb_coption(arb_pubkey()).prop_map(|metadata_address| {
+                TokenInstruction::InitializeMetadataPointer { metadata_address }
+            }),
+            Just(TokenInstruction::InitializeAssociatedAccount),
+            Just(TokenInstruction::RecoverNested),
+            any::<u64>().prop_map(|lamports| TokenInstruction::WrapSol { lamports }),
+            any::<u64>().prop_map(|lamports| TokenInstruction::UnwrapSol { lamports }),
+            Just(TokenInstruction::RevokeAll),
+            (any::<u64>(), any::<u64>()).prop_map(|(minimum, maximum)| {
+                TokenInstruction::AssertBalance { minimum, maximum }
+            }),
+            (any::<u64>(), any::<u64>())
+                .prop_map(|(minimum, maximum)| TokenInstruction::AssertSupply { minimum, maximum }),
+            Just(TokenInstruction::GetAccountBalance),
+            Just(TokenInstruction::GetMintSupply),
+            any::<u64>().prop_map(|amount| TokenInstruction::SplitAccount { amount }),
+            (1..=MAX_BATCH_SIZE as u8, any::<bool>()).prop_map(
+                |(num_source_accounts, close_sources)| TokenInstruction::MergeAccounts {
+                    num_source_accounts,
+                    close_sources,
+                }
+            ),
+            any::<u64>().prop_map(|slot| TokenInstruction::LockUntilSlot { slot }),
+            any::<PolicyMode>().prop_map(|mode| TokenInstruction::InitializePolicy { mode }),
+            arb_pubkey().prop_map(|entry| TokenInstruction::AddPolicyEntry { entry }),
+            arb_pubkey().prop_map(|entry| TokenInstruction::RemovePolicyEntry { entry }),
+            any::<u64>().prop_map(|amount| TokenInstruction::ApproveEscrowed { amount }),
+            any::<u8>().prop_map(|decimals| TokenInstruction::UpdateDecimals { decimals }),
+            any::<u64>().prop_map(|fee| TokenInstruction::InitializeFlashMint { fee }),
+            any::<u64>().prop_map(|amount| TokenInstruction::FlashMintTo { amount }),
+            any::<u64>().prop_map(|amount| TokenInstruction::FlashBurnRepay { amount }),
+            arb_coption(any::<u64>())
+                .prop_map(|amount| TokenInstruction::SetSpendingLimit { amount }),
+            (any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+                |(total_amount, start_slot, end_slot)| TokenInstruction::TransferVested {
+                    total_amount,
+                    start_slot,
+                    end_slot,
+                }
+            ),
+            Just(TokenInstruction::ClaimVested),
+            (any::<u64>(), any::<u64>())
+                .prop_map(|(amount_a, amount_b)| TokenInstruction::Swap { amount_a, amount_b }),
+            prop::collection::vec(
+                prop::collection::vec(any::<u8>(), 2..40),
+                1..=MAX_BATCH_SIZE
+            )
+            .prop_map(|instructions| TokenInstruction::Batch { instructions }),
+            (1..=u8::MAX).prop_map(|count| TokenInstruction::FreezeAccounts { count }),
+            (1..=u8::MAX).prop_map(|count| TokenInstruction::ThawAccounts { count }),
+            any::<u64>().prop_map(|amount| TokenInstruction::BurnFrom { amount }),
+        ]
+        .boxed()
+    }
+}
@@ -0,0 +1,26 @@
+//! Canonical token account addresses, one per wallet and mint, derived from
+//! the token program itself so no separate program is needed to create them
+
+use solana_program::pubkey::Pubkey;
+
+/// Address of the associated token account of `wallet_address` for
+/// `mint_address`, and the canonical bump seed that derives it
+pub fn get_associated_token_address(
+    wallet_address: &Pubkey,
+    mint_address: &Pubkey,
+) -> (Pubkey, u8) {
+    get_associated_token_address_with_program_id(wallet_address, mint_address, &crate::id())
+}
+
+/// Like `get_associated_token_address`, for the token program deployed at
+/// `token_program_id`
+pub fn get_associated_token_address_with_program_id(
+    wallet_address: &Pubkey,
+    mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[wallet_address.as_ref(), mint_address.as_ref()],
+        token_program_id,
+    )
+}
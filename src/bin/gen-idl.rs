@@ -0,0 +1,9 @@
+//! Prints the program's IDL as Anchor JSON:
+//! `cargo run --features idl --bin gen-idl > idl.json`
+
+fn main() {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&test_token_solana::idl::idl()).unwrap()
+    );
+}
@@ -0,0 +1,308 @@
+//! An async client for off-chain tools and integration tests.
+//!
+//! A `Token` drives one mint through any `ProgramClient`, implemented here
+//! for `BanksClient` and the nonblocking `RpcClient`. Each method builds its
+//! instructions with the builders in `instruction`, funds new accounts to be
+//! rent-exempt, and sends one transaction paid for and signed by the payer,
+//! plus whichever authority the method takes.
+
+use crate::{
+    id, instruction,
+    state::{Account, Mint},
+};
+use async_trait::async_trait;
+use solana_banks_client::{BanksClient, BanksClientError};
+use solana_program::{
+    hash::Hash,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use thiserror::Error;
+
+/// Errors a `Token` method returns
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// The transaction was sent and failed
+    #[error("transaction failed: {0}")]
+    Transaction(#[from] TransactionError),
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    /// An instruction could not be built, or an account not unpacked
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+    /// The client could not reach the cluster or bank
+    #[error("client error: {0}")]
+    Client(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// What a `Token` needs from a connection to a cluster or bank
+#[async_trait]
+pub trait ProgramClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash, ClientError>;
+
+    async fn get_minimum_balance_for_rent_exemption(&self, len: usize) -> Result<u64, ClientError>;
+
+    /// Sends the transaction and waits until it is processed
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<(), ClientError>;
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<SolanaAccount>, ClientError>;
+}
+
+impl From<BanksClientError> for ClientError {
+    fn from(error: BanksClientError) -> Self {
+        match error {
+            BanksClientError::TransactionError(error)
+            | BanksClientError::SimulationError { err: error, .. } => Self::Transaction(error),
+            error => Self::Client(error.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl ProgramClient for BanksClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash, ClientError> {
+        Ok(BanksClient::get_latest_blockhash(&mut self.clone()).await?)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, len: usize) -> Result<u64, ClientError> {
+        Ok(self.clone().get_rent().await?.minimum_balance(len))
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<(), ClientError> {
+        Ok(self
+            .clone()
+            .process_transaction(transaction.clone())
+            .await?)
+    }
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<SolanaAccount>, ClientError> {
+        Ok(BanksClient::get_account(&mut self.clone(), *address).await?)
+    }
+}
+
+impl From<solana_rpc_client_api::client_error::Error> for ClientError {
+    fn from(error: solana_rpc_client_api::client_error::Error) -> Self {
+        match error.get_transaction_error() {
+            Some(error) => Self::Transaction(error),
+            None => Self::Client(error.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl ProgramClient for RpcClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash, ClientError> {
+        Ok(RpcClient::get_latest_blockhash(self).await?)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, len: usize) -> Result<u64, ClientError> {
+        Ok(RpcClient::get_minimum_balance_for_rent_exemption(self, len).await?)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<(), ClientError> {
+        self.send_and_confirm_transaction(transaction).await?;
+        Ok(())
+    }
+
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<SolanaAccount>, ClientError> {
+        Ok(self
+            .get_account_with_commitment(address, self.commitment())
+            .await?
+            .value)
+    }
+}
+
+/// One mint of the program, and the payer that funds its transactions
+pub struct Token<C> {
+    client: C,
+    payer: Keypair,
+    mint: Pubkey,
+}
+
+impl<C: ProgramClient> Token<C> {
+    /// A client for an existing mint
+    pub fn new(client: C, payer: Keypair, mint: Pubkey) -> Self {
+        Self {
+            client,
+            payer,
+            mint,
+        }
+    }
+
+    /// Creates and initializes the mint at `mint`'s address
+    pub async fn create_mint(
+        client: C,
+        payer: Keypair,
+        mint: &Keypair,
+        mint_authority: &Pubkey,
+        freeze_authority: Option<&Pubkey>,
+        decimals: u8,
+    ) -> Result<Self, ClientError> {
+        let token = Self::new(client, payer, mint.pubkey());
+        let create = token
+            .create_program_account(&mint.pubkey(), Mint::LEN)
+            .await?;
+        let initialize = instruction::initialize_mint(
+            &id(),
+            &mint.pubkey(),
+            mint_authority,
+            freeze_authority,
+            decimals,
+        )?;
+        token.process(&[create, initialize], &[mint]).await?;
+        Ok(token)
+    }
+
+    pub fn mint(&self) -> &Pubkey {
+        &self.mint
+    }
+
+    pub fn payer(&self) -> &Keypair {
+        &self.payer
+    }
+
+    /// Creates and initializes a token account of the mint at `account`'s
+    /// address
+    pub async fn create_account(
+        &self,
+        account: &Keypair,
+        owner: &Pubkey,
+    ) -> Result<(), ClientError> {
+        let create = self
+            .create_program_account(&account.pubkey(), Account::LEN)
+            .await?;
+        let initialize =
+            instruction::initialize_account(&id(), &account.pubkey(), &self.mint, owner)?;
+        self.process(&[create, initialize], &[account]).await
+    }
+
+    pub async fn mint_to(
+        &self,
+        destination: &Pubkey,
+        authority: &Keypair,
+        amount: u64,
+    ) -> Result<(), ClientError> {
+        let mint_to = instruction::mint_to(
+            &id(),
+            &self.mint,
+            destination,
+            &authority.pubkey(),
+            &[],
+            amount,
+        )?;
+        self.process(&[mint_to], &[authority]).await
+    }
+
+    pub async fn transfer(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Keypair,
+        amount: u64,
+    ) -> Result<(), ClientError> {
+        let transfer =
+            instruction::transfer(&id(), source, destination, &authority.pubkey(), &[], amount)?;
+        self.process(&[transfer], &[authority]).await
+    }
+
+    pub async fn approve(
+        &self,
+        source: &Pubkey,
+        delegate: &Pubkey,
+        owner: &Keypair,
+        amount: u64,
+    ) -> Result<(), ClientError> {
+        let approve = instruction::approve(&id(), source, delegate, &owner.pubkey(), &[], amount)?;
+        self.process(&[approve], &[owner]).await
+    }
+
+    pub async fn burn(
+        &self,
+        account: &Pubkey,
+        authority: &Keypair,
+        amount: u64,
+    ) -> Result<(), ClientError> {
+        let burn = instruction::burn(&id(), account, &self.mint, &authority.pubkey(), &[], amount)?;
+        self.process(&[burn], &[authority]).await
+    }
+
+    /// The token account at `account`, unpacked
+    pub async fn get_account_info(&self, account: &Pubkey) -> Result<Account, ClientError> {
+        self.get_packed(account).await
+    }
+
+    /// The mint, unpacked
+    pub async fn get_mint_info(&self) -> Result<Mint, ClientError> {
+        self.get_packed(&self.mint).await
+    }
+
+    async fn get_packed<T: Pack + IsInitialized>(
+        &self,
+        address: &Pubkey,
+    ) -> Result<T, ClientError> {
+        let account = self
+            .client
+            .get_account(address)
+            .await?
+            .ok_or(ClientError::AccountNotFound(*address))?;
+        if account.owner != id() {
+            return Err(ProgramError::IncorrectProgramId.into());
+        }
+        Ok(T::unpack(&account.data)?)
+    }
+
+    /// A system instruction creating a rent-exempt account of `len` bytes
+    /// owned by the program
+    async fn create_program_account(
+        &self,
+        address: &Pubkey,
+        len: usize,
+    ) -> Result<Instruction, ClientError> {
+        let lamports = self
+            .client
+            .get_minimum_balance_for_rent_exemption(len)
+            .await?;
+        Ok(system_instruction::create_account(
+            &self.payer.pubkey(),
+            address,
+            lamports,
+            len as u64,
+            &id(),
+        ))
+    }
+
+    /// Sends `instructions` in one transaction, signed by the payer and
+    /// `signers`
+    async fn process(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), ClientError> {
+        let mut keypairs = vec![&self.payer];
+        for signer in signers {
+            if keypairs
+                .iter()
+                .all(|keypair| keypair.pubkey() != signer.pubkey())
+            {
+                keypairs.push(signer);
+            }
+        }
+        let recent_blockhash = self.client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &keypairs,
+            recent_blockhash,
+        );
+        self.client.send_transaction(&transaction).await
+    }
+}
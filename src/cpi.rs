@@ -0,0 +1,168 @@
+//! Helpers for invoking this program from other on-chain programs.
+//!
+//! Each helper builds the instruction with the matching builder in
+//! `instruction`, which fails with `IncorrectProgramId` if `token_program` is
+//! not this program, and then calls `invoke_signed`. Pass an empty
+//! `signer_seeds` when the authority signed the outer transaction.
+//!
+//! An account owned by a program-derived address is spent by the program
+//! that derives it, passing the address's seeds and bump, for which the
+//! runtime marks the address as a signer:
+//!
+//! ```
+//! use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+//! use test_token_solana::cpi;
+//!
+//! /// Accounts: token program, vault token account, destination, vault
+//! /// authority, the program's address derived from `b"vault"`
+//! fn withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+//!     let (_, bump) = Pubkey::find_program_address(&[b"vault"], program_id);
+//!     cpi::transfer(
+//!         &accounts[0],
+//!         &accounts[1],
+//!         &accounts[2],
+//!         &accounts[3],
+//!         amount,
+//!         &[&[b"vault", &[bump]]],
+//!     )
+//! }
+//! ```
+
+use crate::instruction;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+};
+
+pub fn initialize_account<'a>(
+    token_program: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    rent: &AccountInfo<'a>,
+) -> ProgramResult {
+    let ix = instruction::initialize_account(token_program.key, account.key, mint.key, owner.key)?;
+    invoke(
+        &ix,
+        &[
+            account.clone(),
+            mint.clone(),
+            owner.clone(),
+            rent.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+pub fn transfer<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = instruction::transfer(
+        token_program.key,
+        source.key,
+        destination.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            source.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn approve<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    delegate: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = instruction::approve(
+        token_program.key,
+        source.key,
+        delegate.key,
+        owner.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            source.clone(),
+            delegate.clone(),
+            owner.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn mint_to<'a>(
+    token_program: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    mint_authority: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = instruction::mint_to(
+        token_program.key,
+        mint.key,
+        destination.key,
+        mint_authority.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            mint.clone(),
+            destination.clone(),
+            mint_authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn burn<'a>(
+    token_program: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = instruction::burn(
+        token_program.key,
+        account.key,
+        mint.key,
+        authority.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            account.clone(),
+            mint.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
@@ -0,0 +1,58 @@
+//! Program entrypoint, left out by the `no-entrypoint` feature so that the
+//! crate can be a dependency of another program or of off-chain code.
+
+use crate::processor::Processor;
+use solana_program::{
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
+};
+
+entrypoint!(process_instruction);
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let result = Processor::process(program_id, accounts, instruction_data);
+    #[cfg(not(feature = "no-log"))]
+    if let Err(error) = &result {
+        use solana_program::program_error::PrintProgramError;
+        error.print::<crate::error::TokenError>();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::TokenError, id, instruction, test_support::LOGS};
+    use solana_program::program_error::ProgramError;
+
+    #[test]
+    fn test_process_instruction_logs() {
+        crate::test_support::set_stubs();
+        let logs = || LOGS.with(|logs| logs.replace(vec![]));
+        logs();
+
+        // the error code is returned whether or not it is printed
+        assert_eq!(
+            process_instruction(&id(), &[], &[u8::MAX]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        if cfg!(feature = "no-log") {
+            assert!(logs().is_empty());
+        } else {
+            assert_eq!(logs(), vec!["Error: Invalid instruction".to_string()]);
+        }
+
+        assert_eq!(
+            process_instruction(&id(), &[], &instruction::TokenInstruction::Revoke.pack()),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        let logs = logs();
+        assert_eq!(
+            logs.contains(&"Instruction: Revoke".to_string()),
+            !cfg!(feature = "no-log")
+        );
+        assert_eq!(logs.is_empty(), cfg!(feature = "no-log"));
+    }
+}
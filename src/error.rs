@@ -1,34 +1,123 @@
 use solana_program::{
     decode_error::DecodeError,
     program_error::{ProgramError, PrintProgramError},
-    msg,
 };
+use crate::processor::log;
 use thiserror::Error;
 use num_traits::FromPrimitive;
 use num_derive::FromPrimitive;
 
+/// Error codes match the SPL Token program where a variant has an SPL
+/// equivalent; codes from 100 up are specific to this program. Never reuse or
+/// renumber a code.
 #[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
 pub enum TokenError {
-    #[error("Invalid instruction")]
-    InvalidInstruction,
-    #[error("Already in use")]
-    AlreadyInUse,
     #[error("Lamport balance below rent-exempt threshold")]
-    NotRentExempt,
+    NotRentExempt = 0,
+    #[error("Insufficient funds")]
+    InsufficientFunds = 1,
     #[error("Invalid mint")]
-    InvalidMint,
+    InvalidMint = 2,
     #[error("Mint mismatch")]
-    MintMismatch,
-    #[error("Self transfer")]
-    SelfTransfer,
-    #[error("Insufficient funds")]
-    InsufficientFunds,
-    #[error("Overflow")]
-    Overflow,
-    #[error("Fixed supply")]
-    FixedSupply,
+    MintMismatch = 3,
     #[error("Owner mismatch")]
-    OwnerMismatch,
+    OwnerMismatch = 4,
+    #[error("Fixed supply")]
+    FixedSupply = 5,
+    #[error("Already in use")]
+    AlreadyInUse = 6,
+    #[error("Invalid number of provided signers")]
+    InvalidNumberOfProvidedSigners = 7,
+    #[error("Invalid number of required signers")]
+    InvalidNumberOfRequiredSigners = 8,
+    #[error("State is uninitialized")]
+    UninitializedState = 9,
+    #[error("Instruction does not support native tokens")]
+    NativeNotSupported = 10,
+    #[error("Non-native account can only be closed if its balance is zero")]
+    NonNativeHasBalance = 11,
+    #[error("Invalid instruction")]
+    InvalidInstruction = 12,
+    #[error("State is invalid for requested operation")]
+    InvalidState = 13,
+    #[error("Overflow")]
+    Overflow = 14,
+    #[error("Authority type not supported for this account")]
+    AuthorityTypeNotSupported = 15,
+    #[error("This token mint cannot freeze accounts")]
+    MintCannotFreeze = 16,
+    #[error("Account is frozen")]
+    AccountFrozen = 17,
+    #[error("The provided decimals value different from the Mint decimals")]
+    MintDecimalsMismatch = 18,
+    #[error("Instruction does not support non-native tokens")]
+    NonNativeNotSupported = 19,
+    #[error("Self transfer")]
+    SelfTransfer = 100,
+    #[error("Insufficient delegated funds")]
+    InsufficientDelegatedFunds = 101,
+    #[error("Delegate expired")]
+    DelegateExpired = 102,
+    #[error("Minting would exceed the maximum supply")]
+    SupplyCapExceeded = 103,
+    #[error("Transfers to this account require a memo")]
+    MissingMemo = 104,
+    #[error("Calculated fee does not match expected fee")]
+    FeeMismatch = 105,
+    #[error("Transfer fee exceeds maximum of 10,000 basis points")]
+    TransferFeeExceedsMaximum = 106,
+    #[error("This token mint has no transfer fee authority")]
+    NoTransferFeeAuthority = 107,
+    #[error("Transfers of the mint must pass the mint account")]
+    MintRequiredForTransfer = 108,
+    #[error("Account has withheld transfer fees")]
+    AccountHasWithheldTransferFees = 109,
+    #[error("This token mint has no rate authority")]
+    NoRateAuthority = 110,
+    #[error("Mint is paused")]
+    MintPaused = 111,
+    #[error("This token mint has no pause authority")]
+    NoPauseAuthority = 112,
+    #[error("The account owner cannot be changed")]
+    ImmutableOwner = 113,
+    #[error("The account's CPI guard blocks this owner action in a cross-program invocation")]
+    CpiGuarded = 114,
+    #[error("This token mint has no transfer hook authority")]
+    NoTransferHookAuthority = 115,
+    #[error("Token metadata field exceeds its maximum length")]
+    MetadataFieldTooLong = 116,
+    #[error("Account is not the mint's metadata pointer")]
+    MetadataPointerMismatch = 117,
+    #[error("Balance or supply is outside the asserted bounds")]
+    BalanceAssertionFailed = 118,
+    #[error("Account is locked")]
+    AccountLocked = 119,
+    #[error("Transfer policy does not permit the destination owner")]
+    TransferNotPermitted = 120,
+    #[error("Transfer policy is full")]
+    PolicyFull = 121,
+    #[error("Transfers of the mint must pass its transfer policy")]
+    MissingTransferPolicy = 122,
+    #[error("Insufficient funds outside the escrowed amount")]
+    InsufficientUnlockedFunds = 123,
+    #[error("Mint decimals exceed the maximum")]
+    InvalidDecimals = 124,
+    #[error("Mint has a supply")]
+    MintHasSupply = 125,
+    #[error("The default pubkey cannot be an authority")]
+    InvalidAuthority = 126,
+    #[error("Flash mint is not repaid later in the transaction")]
+    FlashRepayMissing = 127,
+    #[error("A flash mint is already outstanding")]
+    FlashMintOutstanding = 128,
+    #[error("Repayment does not match the outstanding flash mint")]
+    FlashRepayMismatch = 129,
+    #[error("Spending limit for the epoch exceeded")]
+    SpendingLimitExceeded = 130,
+    #[error("Account holds tokens still vesting")]
+    VestingInProgress = 131,
+    #[error("The owner or the account itself cannot be its delegate")]
+    InvalidDelegate = 132,
 }
 
 impl From<TokenError> for ProgramError {
@@ -37,6 +126,17 @@ impl From<TokenError> for ProgramError {
     }
 }
 
+impl TokenError {
+    /// The token error a failed instruction returned, or `None` for any
+    /// other program error
+    pub fn from_program_error(error: &ProgramError) -> Option<Self> {
+        match error {
+            ProgramError::Custom(code) => Self::from_u32(*code),
+            _ => None,
+        }
+    }
+}
+
 impl<T> DecodeError<T> for TokenError {
     fn type_of() -> &'static str {
         "TokenError"
@@ -48,17 +148,134 @@ impl PrintProgramError for TokenError {
     where
         E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
     {
-        match self {
-            TokenError::NotRentExempt => msg!("Error: Lamport balance below rent-exempt threshold"),
-            TokenError::InvalidInstruction => msg!("Error: Invalid instruction"),
-            TokenError::AlreadyInUse => msg!("Error: Already in use"),
-            TokenError::InvalidMint => msg!("Error: Invalid mint"),
-            TokenError::MintMismatch => msg!("Error: Mint mismatch"),
-            TokenError::SelfTransfer => msg!("Error: Self transfer"),
-            TokenError::InsufficientFunds => msg!("Error: Insufficient funds"),
-            TokenError::Overflow => msg!("Error: Overflow"),
-            TokenError::FixedSupply => msg!("Error: Fixed supply"),
-            TokenError::OwnerMismatch => msg!("Error: Owner mismatch"),
+        // The `Display` message, so a new variant cannot be left unprinted
+        log!("Error: {}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::LOGS;
+
+    // Frozen table of error codes; clients decode `ProgramError::Custom`
+    // against these values, so an entry must never change
+    const ERROR_CODES: &[(TokenError, u32)] = &[
+        (TokenError::NotRentExempt, 0),
+        (TokenError::InsufficientFunds, 1),
+        (TokenError::InvalidMint, 2),
+        (TokenError::MintMismatch, 3),
+        (TokenError::OwnerMismatch, 4),
+        (TokenError::FixedSupply, 5),
+        (TokenError::AlreadyInUse, 6),
+        (TokenError::InvalidNumberOfProvidedSigners, 7),
+        (TokenError::InvalidNumberOfRequiredSigners, 8),
+        (TokenError::UninitializedState, 9),
+        (TokenError::NativeNotSupported, 10),
+        (TokenError::NonNativeHasBalance, 11),
+        (TokenError::InvalidInstruction, 12),
+        (TokenError::InvalidState, 13),
+        (TokenError::Overflow, 14),
+        (TokenError::AuthorityTypeNotSupported, 15),
+        (TokenError::MintCannotFreeze, 16),
+        (TokenError::AccountFrozen, 17),
+        (TokenError::MintDecimalsMismatch, 18),
+        (TokenError::NonNativeNotSupported, 19),
+        (TokenError::SelfTransfer, 100),
+        (TokenError::InsufficientDelegatedFunds, 101),
+        (TokenError::DelegateExpired, 102),
+        (TokenError::SupplyCapExceeded, 103),
+        (TokenError::MissingMemo, 104),
+        (TokenError::FeeMismatch, 105),
+        (TokenError::TransferFeeExceedsMaximum, 106),
+        (TokenError::NoTransferFeeAuthority, 107),
+        (TokenError::MintRequiredForTransfer, 108),
+        (TokenError::AccountHasWithheldTransferFees, 109),
+        (TokenError::NoRateAuthority, 110),
+        (TokenError::MintPaused, 111),
+        (TokenError::NoPauseAuthority, 112),
+        (TokenError::ImmutableOwner, 113),
+        (TokenError::CpiGuarded, 114),
+        (TokenError::NoTransferHookAuthority, 115),
+        (TokenError::MetadataFieldTooLong, 116),
+        (TokenError::MetadataPointerMismatch, 117),
+        (TokenError::BalanceAssertionFailed, 118),
+        (TokenError::AccountLocked, 119),
+        (TokenError::TransferNotPermitted, 120),
+        (TokenError::PolicyFull, 121),
+        (TokenError::MissingTransferPolicy, 122),
+        (TokenError::InsufficientUnlockedFunds, 123),
+        (TokenError::InvalidDecimals, 124),
+        (TokenError::MintHasSupply, 125),
+        (TokenError::InvalidAuthority, 126),
+        (TokenError::FlashRepayMissing, 127),
+        (TokenError::FlashMintOutstanding, 128),
+        (TokenError::FlashRepayMismatch, 129),
+        (TokenError::SpendingLimitExceeded, 130),
+        (TokenError::VestingInProgress, 131),
+        (TokenError::InvalidDelegate, 132),
+    ];
+
+    #[test]
+    fn test_error_codes() {
+        for (error, code) in ERROR_CODES {
+            assert_eq!(error.clone() as u32, *code, "{:?}", error);
+            assert_eq!(ProgramError::from(error.clone()), ProgramError::Custom(*code));
+        }
+    }
+
+    #[test]
+    fn test_from_u32() {
+        for (error, code) in ERROR_CODES {
+            assert_eq!(TokenError::from_u32(*code), Some(error.clone()));
         }
+        // codes without a variant do not decode
+        for code in [20, 99, 133] {
+            assert_eq!(TokenError::from_u32(code), None);
+        }
+    }
+
+    #[test]
+    fn test_every_error_round_trips_and_prints() {
+        crate::test_support::set_stubs();
+        let logs = || LOGS.with(|logs| logs.replace(vec![]));
+        logs();
+
+        let mut codes = vec![];
+        for code in 0..=u32::from(u16::MAX) {
+            let error = match TokenError::from_u32(code) {
+                Some(error) => error,
+                None => continue,
+            };
+            codes.push(code);
+
+            let program_error = ProgramError::from(error.clone());
+            assert_eq!(program_error, ProgramError::Custom(code));
+            assert_eq!(
+                TokenError::from_program_error(&program_error),
+                Some(error.clone())
+            );
+            assert!(!error.to_string().is_empty(), "{:?}", error);
+
+            error.print::<TokenError>();
+            if cfg!(feature = "no-log") {
+                assert!(logs().is_empty());
+            } else {
+                assert_eq!(logs(), vec![format!("Error: {}", error)]);
+            }
+        }
+        // and the table above names every variant
+        let mut table: Vec<u32> = ERROR_CODES.iter().map(|(_, code)| *code).collect();
+        table.sort_unstable();
+        assert_eq!(codes, table);
+
+        assert_eq!(
+            TokenError::from_program_error(&ProgramError::Custom(20)),
+            None
+        );
+        assert_eq!(
+            TokenError::from_program_error(&ProgramError::InvalidArgument),
+            None
+        );
     }
 }
@@ -0,0 +1,282 @@
+//! Events the processor logs with `sol_log_data`, so indexers learn what a
+//! transfer, mint, burn or approval did without diffing account states.
+//!
+//! Each event is logged as one data field: a one-byte discriminator followed
+//! by three keys and a little-endian amount, 105 bytes in all. The `events`
+//! feature, on by default, turns the logging on; the types and the decoder
+//! are always available, for off-chain use. A `Program data:` log line
+//! carries the field base64-encoded; decode it and pass the bytes to
+//! `parse_event`.
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::pubkey::Pubkey;
+
+/// Length of every packed event
+pub const EVENT_LEN: usize = 1 + 32 * 3 + 8;
+
+/// Discriminator of a `TransferEvent`
+pub const TRANSFER_EVENT_TAG: u8 = 0;
+/// Discriminator of a `MintEvent`
+pub const MINT_EVENT_TAG: u8 = 1;
+/// Discriminator of a `BurnEvent`
+pub const BURN_EVENT_TAG: u8 = 2;
+/// Discriminator of an `ApproveEvent`
+pub const APPROVE_EVENT_TAG: u8 = 3;
+
+/// Tokens moved between two accounts. `amount` is what left the source,
+/// including any fee withheld at the destination.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferEvent {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+/// Tokens minted into an account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MintEvent {
+    pub mint: Pubkey,
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+/// Tokens burned from an account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BurnEvent {
+    pub account: Pubkey,
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+/// A delegation set on an account; an amount of zero revokes it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ApproveEvent {
+    pub source: Pubkey,
+    pub delegate: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Any event the program logs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenEvent {
+    Transfer(TransferEvent),
+    Mint(MintEvent),
+    Burn(BurnEvent),
+    Approve(ApproveEvent),
+}
+
+fn pack_fields(tag: u8, keys: [&Pubkey; 3], amount: u64) -> [u8; EVENT_LEN] {
+    let mut dst = [0; EVENT_LEN];
+    {
+        let dst = array_mut_ref![dst, 0, EVENT_LEN];
+        let (tag_dst, first_dst, second_dst, third_dst, amount_dst) =
+            mut_array_refs![dst, 1, 32, 32, 32, 8];
+        tag_dst[0] = tag;
+        first_dst.copy_from_slice(keys[0].as_ref());
+        second_dst.copy_from_slice(keys[1].as_ref());
+        third_dst.copy_from_slice(keys[2].as_ref());
+        *amount_dst = amount.to_le_bytes();
+    }
+    dst
+}
+
+/// The keys and amount of an event with the given tag, or `None` for any
+/// other tag or length
+fn unpack_fields(tag: u8, src: &[u8]) -> Option<([Pubkey; 3], u64)> {
+    if src.len() != EVENT_LEN || src[0] != tag {
+        return None;
+    }
+    let src = array_ref![src, 0, EVENT_LEN];
+    let (_, first, second, third, amount) = array_refs![src, 1, 32, 32, 32, 8];
+    Some((
+        [
+            Pubkey::new_from_array(*first),
+            Pubkey::new_from_array(*second),
+            Pubkey::new_from_array(*third),
+        ],
+        u64::from_le_bytes(*amount),
+    ))
+}
+
+impl TransferEvent {
+    pub fn pack(&self) -> [u8; EVENT_LEN] {
+        pack_fields(
+            TRANSFER_EVENT_TAG,
+            [&self.source, &self.destination, &self.authority],
+            self.amount,
+        )
+    }
+
+    pub fn unpack(src: &[u8]) -> Option<Self> {
+        let ([source, destination, authority], amount) = unpack_fields(TRANSFER_EVENT_TAG, src)?;
+        Some(Self {
+            source,
+            destination,
+            authority,
+            amount,
+        })
+    }
+}
+
+impl MintEvent {
+    pub fn pack(&self) -> [u8; EVENT_LEN] {
+        pack_fields(
+            MINT_EVENT_TAG,
+            [&self.mint, &self.account, &self.authority],
+            self.amount,
+        )
+    }
+
+    pub fn unpack(src: &[u8]) -> Option<Self> {
+        let ([mint, account, authority], amount) = unpack_fields(MINT_EVENT_TAG, src)?;
+        Some(Self {
+            mint,
+            account,
+            authority,
+            amount,
+        })
+    }
+}
+
+impl BurnEvent {
+    pub fn pack(&self) -> [u8; EVENT_LEN] {
+        pack_fields(
+            BURN_EVENT_TAG,
+            [&self.account, &self.mint, &self.authority],
+            self.amount,
+        )
+    }
+
+    pub fn unpack(src: &[u8]) -> Option<Self> {
+        let ([account, mint, authority], amount) = unpack_fields(BURN_EVENT_TAG, src)?;
+        Some(Self {
+            account,
+            mint,
+            authority,
+            amount,
+        })
+    }
+}
+
+impl ApproveEvent {
+    pub fn pack(&self) -> [u8; EVENT_LEN] {
+        pack_fields(
+            APPROVE_EVENT_TAG,
+            [&self.source, &self.delegate, &self.owner],
+            self.amount,
+        )
+    }
+
+    pub fn unpack(src: &[u8]) -> Option<Self> {
+        let ([source, delegate, owner], amount) = unpack_fields(APPROVE_EVENT_TAG, src)?;
+        Some(Self {
+            source,
+            delegate,
+            owner,
+            amount,
+        })
+    }
+}
+
+impl TokenEvent {
+    pub fn pack(&self) -> [u8; EVENT_LEN] {
+        match self {
+            Self::Transfer(event) => event.pack(),
+            Self::Mint(event) => event.pack(),
+            Self::Burn(event) => event.pack(),
+            Self::Approve(event) => event.pack(),
+        }
+    }
+
+    /// Logs the event as program data, unless the `events` feature is off
+    pub(crate) fn emit(&self) {
+        #[cfg(feature = "events")]
+        solana_program::log::sol_log_data(&[&self.pack()]);
+    }
+}
+
+/// Decodes one logged event, or `None` if the data is not one
+pub fn parse_event(data: &[u8]) -> Option<TokenEvent> {
+    match *data.first()? {
+        TRANSFER_EVENT_TAG => TransferEvent::unpack(data).map(TokenEvent::Transfer),
+        MINT_EVENT_TAG => MintEvent::unpack(data).map(TokenEvent::Mint),
+        BURN_EVENT_TAG => BurnEvent::unpack(data).map(TokenEvent::Burn),
+        APPROVE_EVENT_TAG => ApproveEvent::unpack(data).map(TokenEvent::Approve),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let events = [
+            TokenEvent::Transfer(TransferEvent {
+                source: key(1),
+                destination: key(2),
+                authority: key(3),
+                amount: 42,
+            }),
+            TokenEvent::Mint(MintEvent {
+                mint: key(1),
+                account: key(2),
+                authority: key(3),
+                amount: u64::MAX,
+            }),
+            TokenEvent::Burn(BurnEvent {
+                account: key(1),
+                mint: key(2),
+                authority: key(3),
+                amount: 0,
+            }),
+            TokenEvent::Approve(ApproveEvent {
+                source: key(1),
+                delegate: key(2),
+                owner: key(3),
+                amount: 7,
+            }),
+        ];
+        for (tag, event) in events.iter().enumerate() {
+            let packed = event.pack();
+            assert_eq!(packed[0], tag as u8);
+            assert_eq!(&packed[1..33], key(1).as_ref());
+            assert_eq!(&packed[65..97], key(3).as_ref());
+            assert_eq!(parse_event(&packed), Some(*event));
+
+            // a truncated or extended event is not one
+            assert_eq!(parse_event(&packed[..EVENT_LEN - 1]), None);
+            let mut extended = packed.to_vec();
+            extended.push(0);
+            assert_eq!(parse_event(&extended), None);
+        }
+
+        let transfer = TransferEvent {
+            source: key(1),
+            destination: key(2),
+            authority: key(3),
+            amount: 42,
+        };
+        assert_eq!(
+            transfer.pack()[97..],
+            42u64.to_le_bytes(),
+            "the amount closes the event"
+        );
+        // each event only unpacks its own tag
+        assert_eq!(MintEvent::unpack(&transfer.pack()), None);
+
+        let mut unknown = transfer.pack();
+        unknown[0] = APPROVE_EVENT_TAG + 1;
+        assert_eq!(parse_event(&unknown), None);
+        assert_eq!(parse_event(&[]), None);
+    }
+}
@@ -0,0 +1,1117 @@
+//! An Anchor-compatible IDL of the program, for generating clients.
+//!
+//! Instructions are listed by their tag constants, each with the accounts in
+//! the order the processor reads them; the state layouts take their field
+//! names from the packed field tables, and the errors and enums come from
+//! their definitions. Packed state is a borsh-compatible sequence of fields, so
+//! Anchor's coder decodes it, except that a `COption` is a four-byte tag and a
+//! value, described by the `COptionPubkey` and `COptionU64` types.
+//!
+//! `cargo run --features idl --bin gen-idl` prints the IDL, and
+//! `tests/fixtures/idl.json` holds the copy clients are generated from.
+
+use crate::{
+    error::TokenError,
+    instruction::*,
+    state::{AccountState, PolicyMode, ACCOUNT_FIELDS, MINT_FIELDS},
+};
+use num_enum::TryFromPrimitive;
+use num_traits::FromPrimitive;
+use serde_json::{json, Map, Value};
+use solana_program::{pubkey::Pubkey, system_program, sysvar};
+use std::fmt::Debug;
+
+/// Type of an instruction argument or a state field, as Anchor names it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdlType {
+    U8,
+    U16,
+    I16,
+    U32,
+    U64,
+    I64,
+    Bool,
+    Pubkey,
+    String,
+    Bytes,
+    Option(&'static IdlType),
+    Vec(&'static IdlType),
+    Array(&'static IdlType, usize),
+    Defined(&'static str),
+}
+
+impl IdlType {
+    pub fn to_json(self) -> Value {
+        match self {
+            Self::U8 => json!("u8"),
+            Self::U16 => json!("u16"),
+            Self::I16 => json!("i16"),
+            Self::U32 => json!("u32"),
+            Self::U64 => json!("u64"),
+            Self::I64 => json!("i64"),
+            Self::Bool => json!("bool"),
+            Self::Pubkey => json!("pubkey"),
+            Self::String => json!("string"),
+            Self::Bytes => json!("bytes"),
+            Self::Option(inner) => json!({ "option": inner.to_json() }),
+            Self::Vec(inner) => json!({ "vec": inner.to_json() }),
+            Self::Array(inner, len) => json!({ "array": [inner.to_json(), len] }),
+            Self::Defined(name) => json!({ "defined": { "name": name } }),
+        }
+    }
+}
+
+/// An account an instruction expects
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdlAccount {
+    pub name: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+    /// Signs, or is a multisig whose signers follow the listed accounts
+    pub multisig: bool,
+    /// The sysvar or program the account must be
+    pub address: Option<Pubkey>,
+}
+
+impl IdlAccount {
+    pub const fn new(name: &'static str, writable: bool, signer: bool) -> Self {
+        Self {
+            name,
+            writable,
+            signer,
+            multisig: false,
+            address: None,
+        }
+    }
+
+    /// An authority, which may be a multisig
+    pub const fn authority(name: &'static str) -> Self {
+        Self {
+            multisig: true,
+            ..Self::new(name, false, true)
+        }
+    }
+
+    pub const fn address(name: &'static str, address: Pubkey) -> Self {
+        Self {
+            address: Some(address),
+            ..Self::new(name, false, false)
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut account = Map::new();
+        account.insert("name".into(), json!(self.name));
+        if self.multisig {
+            account.insert(
+                "docs".into(),
+                json!(["Or a multisig, whose signers follow the listed accounts"]),
+            );
+        }
+        if self.writable {
+            account.insert("writable".into(), json!(true));
+        }
+        if self.signer {
+            account.insert("signer".into(), json!(true));
+        }
+        if let Some(address) = self.address {
+            account.insert("address".into(), json!(address.to_string()));
+        }
+        Value::Object(account)
+    }
+}
+
+/// An instruction, discriminated by its one-byte tag
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdlInstruction {
+    pub name: &'static str,
+    /// Accounts past the listed ones, and data Anchor cannot encode
+    pub docs: &'static [&'static str],
+    pub tag: u8,
+    pub accounts: &'static [IdlAccount],
+    pub args: &'static [(&'static str, IdlType)],
+}
+
+impl IdlInstruction {
+    pub fn to_json(&self) -> Value {
+        let mut instruction = Map::new();
+        instruction.insert("name".into(), json!(self.name));
+        if !self.docs.is_empty() {
+            instruction.insert("docs".into(), json!(self.docs));
+        }
+        instruction.insert("discriminator".into(), json!([self.tag]));
+        instruction.insert(
+            "accounts".into(),
+            self.accounts.iter().map(IdlAccount::to_json).collect(),
+        );
+        instruction.insert(
+            "args".into(),
+            self.args
+                .iter()
+                .map(|(name, ty)| json!({ "name": name, "type": ty.to_json() }))
+                .collect(),
+        );
+        Value::Object(instruction)
+    }
+}
+
+/// Every instruction, in tag order
+pub const INSTRUCTIONS: [IdlInstruction; BURN_FROM_TAG as usize + 1] = [
+    IdlInstruction {
+        name: "initialize_mint",
+        docs: &[],
+        tag: INITIALIZE_MINT_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::address("rent", sysvar::rent::ID),
+        ],
+        args: &[
+            ("decimals", IdlType::U8),
+            ("mint_authority", IdlType::Pubkey),
+            ("freeze_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("max_supply", IdlType::Option(&IdlType::U64)),
+            ("permanent_delegate", IdlType::Option(&IdlType::Pubkey)),
+            ("transfer_fee_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("rate_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("pause_authority", IdlType::Option(&IdlType::Pubkey)),
+        ],
+    },
+    IdlInstruction {
+        name: "initialize_account",
+        docs: &[],
+        tag: INITIALIZE_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::new("owner", false, false),
+            IdlAccount::address("rent", sysvar::rent::ID),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "transfer",
+        docs: &[],
+        tag: TRANSFER_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "approve",
+        docs: &[],
+        tag: APPROVE_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("delegate", false, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "mint_to",
+        docs: &[],
+        tag: MINT_TO_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "burn",
+        docs: &[],
+        tag: BURN_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "revoke",
+        docs: &[],
+        tag: REVOKE_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "set_authority",
+        docs: &[],
+        tag: SET_AUTHORITY_TAG,
+        accounts: &[
+            IdlAccount::new("owned", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[
+            ("authority_type", IdlType::Defined("AuthorityType")),
+            ("new_authority", IdlType::Option(&IdlType::Pubkey)),
+        ],
+    },
+    IdlInstruction {
+        name: "freeze_account",
+        docs: &[],
+        tag: FREEZE_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "thaw_account",
+        docs: &[],
+        tag: THAW_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "transfer_checked",
+        docs: &[],
+        tag: TRANSFER_CHECKED_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64), ("decimals", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "approve_checked",
+        docs: &[],
+        tag: APPROVE_CHECKED_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::new("delegate", false, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64), ("decimals", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "burn_checked",
+        docs: &[],
+        tag: BURN_CHECKED_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64), ("decimals", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "initialize_multisig",
+        docs: &["The N signers follow the listed accounts."],
+        tag: INITIALIZE_MULTISIG_TAG,
+        accounts: &[
+            IdlAccount::new("multisig", true, false),
+            IdlAccount::address("rent", sysvar::rent::ID),
+        ],
+        args: &[("m", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "initialize_mint2",
+        docs: &[],
+        tag: INITIALIZE_MINT2_TAG,
+        accounts: &[IdlAccount::new("mint", true, false)],
+        args: &[
+            ("decimals", IdlType::U8),
+            ("mint_authority", IdlType::Pubkey),
+            ("freeze_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("max_supply", IdlType::Option(&IdlType::U64)),
+            ("permanent_delegate", IdlType::Option(&IdlType::Pubkey)),
+            ("transfer_fee_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("rate_authority", IdlType::Option(&IdlType::Pubkey)),
+            ("pause_authority", IdlType::Option(&IdlType::Pubkey)),
+        ],
+    },
+    IdlInstruction {
+        name: "initialize_account2",
+        docs: &[],
+        tag: INITIALIZE_ACCOUNT2_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::address("rent", sysvar::rent::ID),
+        ],
+        args: &[("owner", IdlType::Pubkey)],
+    },
+    IdlInstruction {
+        name: "initialize_account3",
+        docs: &[],
+        tag: INITIALIZE_ACCOUNT3_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", false, false),
+        ],
+        args: &[("owner", IdlType::Pubkey)],
+    },
+    IdlInstruction {
+        name: "sync_native",
+        docs: &[],
+        tag: SYNC_NATIVE_TAG,
+        accounts: &[IdlAccount::new("account", true, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "get_account_data_size",
+        docs: &[],
+        tag: GET_ACCOUNT_DATA_SIZE_TAG,
+        accounts: &[IdlAccount::new("mint", false, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "amount_to_ui_amount",
+        docs: &[],
+        tag: AMOUNT_TO_UI_AMOUNT_TAG,
+        accounts: &[IdlAccount::new("mint", false, false)],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "ui_amount_to_amount",
+        docs: &["The UI amount takes up the rest of the data, with no length prefix."],
+        tag: UI_AMOUNT_TO_AMOUNT_TAG,
+        accounts: &[IdlAccount::new("mint", false, false)],
+        args: &[("ui_amount", IdlType::String)],
+    },
+    IdlInstruction {
+        name: "close_account",
+        docs: &[],
+        tag: CLOSE_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "increase_delegated_amount",
+        docs: &[],
+        tag: INCREASE_DELEGATED_AMOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "decrease_delegated_amount",
+        docs: &[],
+        tag: DECREASE_DELEGATED_AMOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "approve_with_expiry",
+        docs: &[],
+        tag: APPROVE_WITH_EXPIRY_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("delegate", false, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64), ("expiry_slot", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "transfer_batch",
+        docs: &[
+            "A writable destination per amount follows the listed accounts.",
+            "The amounts are a one-byte count and the amounts, not a borsh vector.",
+        ],
+        tag: TRANSFER_BATCH_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amounts", IdlType::Vec(&IdlType::U64))],
+    },
+    IdlInstruction {
+        name: "mint_to_batch",
+        docs: &[
+            "A writable destination per amount follows the listed accounts.",
+            "The amounts are a one-byte count and the amounts, not a borsh vector.",
+        ],
+        tag: MINT_TO_BATCH_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amounts", IdlType::Vec(&IdlType::U64))],
+    },
+    IdlInstruction {
+        name: "propose_owner",
+        docs: &[],
+        tag: PROPOSE_OWNER_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("new_owner", IdlType::Pubkey)],
+    },
+    IdlInstruction {
+        name: "accept_ownership",
+        docs: &[],
+        tag: ACCEPT_OWNERSHIP_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("pending_owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "set_default_account_state",
+        docs: &[],
+        tag: SET_DEFAULT_ACCOUNT_STATE_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[("state", IdlType::Defined("AccountState"))],
+    },
+    IdlInstruction {
+        name: "require_memo_transfers",
+        docs: &[],
+        tag: REQUIRE_MEMO_TRANSFERS_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("enable", IdlType::Bool)],
+    },
+    IdlInstruction {
+        name: "transfer_checked_with_fee",
+        docs: &[],
+        tag: TRANSFER_CHECKED_WITH_FEE_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64), ("decimals", IdlType::U8), ("fee", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "set_transfer_fee",
+        docs: &[],
+        tag: SET_TRANSFER_FEE_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("transfer_fee_authority"),
+        ],
+        args: &[("transfer_fee_basis_points", IdlType::U16), ("maximum_fee", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "withdraw_withheld_tokens_from_accounts",
+        docs: &["The writable sources follow the listed accounts and any multisig signers."],
+        tag: WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("num_token_accounts", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "harvest_withheld_tokens_to_mint",
+        docs: &["The writable sources follow the mint."],
+        tag: HARVEST_WITHHELD_TOKENS_TO_MINT_TAG,
+        accounts: &[IdlAccount::new("mint", true, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "update_rate_interest_bearing_mint",
+        docs: &[],
+        tag: UPDATE_RATE_INTEREST_BEARING_MINT_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("rate_authority"),
+        ],
+        args: &[("rate", IdlType::I16)],
+    },
+    IdlInstruction {
+        name: "pause",
+        docs: &[],
+        tag: PAUSE_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("pause_authority"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "resume",
+        docs: &[],
+        tag: RESUME_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("pause_authority"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "reallocate",
+        docs: &[],
+        tag: REALLOCATE_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("payer", true, true),
+            IdlAccount::address("system_program", system_program::ID),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "reallocate_mint",
+        docs: &[],
+        tag: REALLOCATE_MINT_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::new("payer", true, true),
+            IdlAccount::address("system_program", system_program::ID),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "initialize_immutable_owner",
+        docs: &[],
+        tag: INITIALIZE_IMMUTABLE_OWNER_TAG,
+        accounts: &[IdlAccount::new("account", true, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "enable_cpi_guard",
+        docs: &[],
+        tag: ENABLE_CPI_GUARD_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "disable_cpi_guard",
+        docs: &[],
+        tag: DISABLE_CPI_GUARD_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "initialize_transfer_hook",
+        docs: &[],
+        tag: INITIALIZE_TRANSFER_HOOK_TAG,
+        accounts: &[IdlAccount::new("mint", true, false)],
+        args: &[
+            ("authority", IdlType::Option(&IdlType::Pubkey)),
+            ("program_id", IdlType::Option(&IdlType::Pubkey)),
+        ],
+    },
+    IdlInstruction {
+        name: "update_transfer_hook",
+        docs: &[],
+        tag: UPDATE_TRANSFER_HOOK_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("program_id", IdlType::Option(&IdlType::Pubkey))],
+    },
+    IdlInstruction {
+        name: "initialize_metadata_pointer",
+        docs: &[],
+        tag: INITIALIZE_METADATA_POINTER_TAG,
+        accounts: &[IdlAccount::new("mint", true, false)],
+        args: &[("metadata_address", IdlType::Option(&IdlType::Pubkey))],
+    },
+    IdlInstruction {
+        name: "initialize_token_metadata",
+        docs: &[],
+        tag: INITIALIZE_TOKEN_METADATA_TAG,
+        accounts: &[
+            IdlAccount::new("metadata", true, false),
+            IdlAccount::new("payer", true, true),
+            IdlAccount::address("system_program", system_program::ID),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::new("update_authority", false, false),
+            IdlAccount::authority("mint_authority"),
+        ],
+        args: &[("name", IdlType::String), ("symbol", IdlType::String), ("uri", IdlType::String)],
+    },
+    IdlInstruction {
+        name: "update_token_metadata_field",
+        docs: &[],
+        tag: UPDATE_TOKEN_METADATA_FIELD_TAG,
+        accounts: &[
+            IdlAccount::new("metadata", true, false),
+            IdlAccount::new("payer", true, true),
+            IdlAccount::address("system_program", system_program::ID),
+            IdlAccount::authority("update_authority"),
+        ],
+        args: &[("field", IdlType::Defined("MetadataField")), ("value", IdlType::String)],
+    },
+    IdlInstruction {
+        name: "initialize_associated_account",
+        docs: &[],
+        tag: INITIALIZE_ASSOCIATED_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("payer", true, true),
+            IdlAccount::new("associated_account", true, false),
+            IdlAccount::new("wallet", false, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::address("system_program", system_program::ID),
+            IdlAccount::address("rent", sysvar::rent::ID),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "recover_nested",
+        docs: &[],
+        tag: RECOVER_NESTED_TAG,
+        accounts: &[
+            IdlAccount::new("nested_account", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::new("owner_account", false, false),
+            IdlAccount {
+                writable: true,
+                ..IdlAccount::authority("wallet")
+            },
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "wrap_sol",
+        docs: &[],
+        tag: WRAP_SOL_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("funding", true, true),
+            IdlAccount::address("system_program", system_program::ID),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("lamports", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "unwrap_sol",
+        docs: &[],
+        tag: UNWRAP_SOL_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("lamports", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "revoke_all",
+        docs: &[],
+        tag: REVOKE_ALL_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "assert_balance",
+        docs: &[],
+        tag: ASSERT_BALANCE_TAG,
+        accounts: &[IdlAccount::new("account", false, false)],
+        args: &[("minimum", IdlType::U64), ("maximum", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "assert_supply",
+        docs: &[],
+        tag: ASSERT_SUPPLY_TAG,
+        accounts: &[IdlAccount::new("mint", false, false)],
+        args: &[("minimum", IdlType::U64), ("maximum", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "get_account_balance",
+        docs: &[],
+        tag: GET_ACCOUNT_BALANCE_TAG,
+        accounts: &[IdlAccount::new("account", false, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "get_mint_supply",
+        docs: &[],
+        tag: GET_MINT_SUPPLY_TAG,
+        accounts: &[IdlAccount::new("mint", false, false)],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "split_account",
+        docs: &[],
+        tag: SPLIT_ACCOUNT_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("new_account", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "merge_accounts",
+        docs: &["The writable sources follow the listed accounts."],
+        tag: MERGE_ACCOUNTS_TAG,
+        accounts: &[
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("num_source_accounts", IdlType::U8), ("close_sources", IdlType::Bool)],
+    },
+    IdlInstruction {
+        name: "lock_until_slot",
+        docs: &[],
+        tag: LOCK_UNTIL_SLOT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("slot", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "initialize_policy",
+        docs: &[],
+        tag: INITIALIZE_POLICY_TAG,
+        accounts: &[
+            IdlAccount::new("policy", true, false),
+            IdlAccount::new("mint", true, false),
+        ],
+        args: &[("mode", IdlType::Defined("PolicyMode"))],
+    },
+    IdlInstruction {
+        name: "add_policy_entry",
+        docs: &[],
+        tag: ADD_POLICY_ENTRY_TAG,
+        accounts: &[
+            IdlAccount::new("policy", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[("entry", IdlType::Pubkey)],
+    },
+    IdlInstruction {
+        name: "remove_policy_entry",
+        docs: &[],
+        tag: REMOVE_POLICY_ENTRY_TAG,
+        accounts: &[
+            IdlAccount::new("policy", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[("entry", IdlType::Pubkey)],
+    },
+    IdlInstruction {
+        name: "approve_escrowed",
+        docs: &[],
+        tag: APPROVE_ESCROWED_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("delegate", false, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "update_decimals",
+        docs: &[],
+        tag: UPDATE_DECIMALS_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("mint_authority"),
+        ],
+        args: &[("decimals", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "initialize_flash_mint",
+        docs: &[],
+        tag: INITIALIZE_FLASH_MINT_TAG,
+        accounts: &[
+            IdlAccount::new("config", true, false),
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("mint_authority"),
+        ],
+        args: &[("fee", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "flash_mint_to",
+        docs: &[],
+        tag: FLASH_MINT_TO_TAG,
+        accounts: &[
+            IdlAccount::new("mint", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::new("config", true, false),
+            IdlAccount::address("instructions", sysvar::instructions::ID),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "flash_burn_repay",
+        docs: &[],
+        tag: FLASH_BURN_REPAY_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("mint", true, false),
+            IdlAccount::new("config", true, false),
+            IdlAccount::new("fee_destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "set_spending_limit",
+        docs: &[],
+        tag: SET_SPENDING_LIMIT_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[("amount", IdlType::Option(&IdlType::U64))],
+    },
+    IdlInstruction {
+        name: "transfer_vested",
+        docs: &[],
+        tag: TRANSFER_VESTED_TAG,
+        accounts: &[
+            IdlAccount::new("source", true, false),
+            IdlAccount::new("destination", true, false),
+            IdlAccount::authority("authority"),
+        ],
+        args: &[
+            ("total_amount", IdlType::U64),
+            ("start_slot", IdlType::U64),
+            ("end_slot", IdlType::U64),
+        ],
+    },
+    IdlInstruction {
+        name: "claim_vested",
+        docs: &[],
+        tag: CLAIM_VESTED_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::authority("owner"),
+        ],
+        args: &[],
+    },
+    IdlInstruction {
+        name: "swap",
+        docs: &[],
+        tag: SWAP_TAG,
+        accounts: &[
+            IdlAccount::new("source_a", true, false),
+            IdlAccount::new("destination_a", true, false),
+            IdlAccount::new("source_b", true, false),
+            IdlAccount::new("destination_b", true, false),
+            IdlAccount::new("authority_a", false, true),
+            IdlAccount::new("authority_b", false, true),
+        ],
+        args: &[("amount_a", IdlType::U64), ("amount_b", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "batch",
+        docs: &[
+            "The entries are a one-byte count, not a borsh vector, each a two-byte length",
+            "and its data: the entry's account count and packed instruction.",
+            "The accounts of each entry follow one another.",
+        ],
+        tag: BATCH_TAG,
+        accounts: &[],
+        args: &[("instructions", IdlType::Vec(&IdlType::Bytes))],
+    },
+    IdlInstruction {
+        name: "freeze_accounts",
+        docs: &["The `count` writable accounts follow the listed accounts."],
+        tag: FREEZE_ACCOUNTS_TAG,
+        accounts: &[
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[("count", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "thaw_accounts",
+        docs: &["The `count` writable accounts follow the listed accounts."],
+        tag: THAW_ACCOUNTS_TAG,
+        accounts: &[
+            IdlAccount::new("mint", false, false),
+            IdlAccount::authority("freeze_authority"),
+        ],
+        args: &[("count", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "burn_from",
+        docs: &[],
+        tag: BURN_FROM_TAG,
+        accounts: &[
+            IdlAccount::new("account", true, false),
+            IdlAccount::new("mint", true, false),
+            IdlAccount::authority("mint_authority"),
+        ],
+        args: &[("amount", IdlType::U64)],
+    },
+];
+
+/// Types of the packed `Mint` fields, in the order of `MINT_FIELDS`
+pub const MINT_TYPES: [IdlType; MINT_FIELDS.len()] = [
+    IdlType::Defined("COptionPubkey"),
+    IdlType::U64,
+    IdlType::U8,
+    IdlType::Bool,
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("COptionU64"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("AccountState"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::U16,
+    IdlType::U64,
+    IdlType::U64,
+    IdlType::Defined("COptionPubkey"),
+    IdlType::I64,
+    IdlType::I16,
+    IdlType::I64,
+    IdlType::I16,
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Bool,
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("COptionPubkey"),
+];
+
+/// Types of the packed `Account` fields, in the order of `ACCOUNT_FIELDS`
+pub const ACCOUNT_TYPES: [IdlType; ACCOUNT_FIELDS.len()] = [
+    IdlType::Pubkey,
+    IdlType::Pubkey,
+    IdlType::U64,
+    IdlType::Defined("COptionPubkey"),
+    IdlType::U64,
+    IdlType::Defined("AccountState"),
+    IdlType::Defined("COptionU64"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Defined("COptionU64"),
+    IdlType::Defined("COptionPubkey"),
+    IdlType::Bool,
+    IdlType::U64,
+    IdlType::Bool,
+    IdlType::Bool,
+    IdlType::Bool,
+    IdlType::Defined("COptionU64"),
+    IdlType::Bool,
+    IdlType::Defined("COptionU64"),
+    IdlType::U64,
+    IdlType::U64,
+    IdlType::U64,
+    IdlType::U64,
+    IdlType::U64,
+    IdlType::U64,
+];
+
+fn struct_type(name: &str, fields: &[(&str, IdlType)]) -> Value {
+    json!({
+        "name": name,
+        "type": {
+            "kind": "struct",
+            "fields": fields
+                .iter()
+                .map(|(name, ty)| json!({ "name": name, "type": ty.to_json() }))
+                .collect::<Vec<_>>(),
+        },
+    })
+}
+
+/// The variants of a fieldless enum, named by their `Debug` output
+fn enum_type<T: TryFromPrimitive<Primitive = u8> + Debug>(name: &str) -> Value {
+    let variants: Vec<Value> = (0..=u8::MAX)
+        .map_while(|value| T::try_from_primitive(value).ok())
+        .map(|variant| json!({ "name": format!("{:?}", variant) }))
+        .collect();
+    json!({
+        "name": name,
+        "type": { "kind": "enum", "variants": variants },
+    })
+}
+
+fn packed_struct_type(
+    name: &str,
+    fields: &[(&'static str, usize, usize)],
+    types: &[IdlType],
+) -> Value {
+    let fields: Vec<(&str, IdlType)> = fields
+        .iter()
+        .zip(types)
+        .map(|((name, _, _), ty)| (*name, *ty))
+        .collect();
+    struct_type(name, &fields)
+}
+
+fn types() -> Vec<Value> {
+    vec![
+        struct_type(
+            "COptionPubkey",
+            &[("tag", IdlType::U32), ("value", IdlType::Pubkey)],
+        ),
+        struct_type("COptionU64", &[("tag", IdlType::U32), ("value", IdlType::U64)]),
+        enum_type::<AccountState>("AccountState"),
+        enum_type::<AuthorityType>("AuthorityType"),
+        enum_type::<MetadataField>("MetadataField"),
+        enum_type::<PolicyMode>("PolicyMode"),
+        packed_struct_type("Mint", &MINT_FIELDS, &MINT_TYPES),
+        packed_struct_type("Account", &ACCOUNT_FIELDS, &ACCOUNT_TYPES),
+        struct_type(
+            "Multisig",
+            &[
+                ("m", IdlType::U8),
+                ("n", IdlType::U8),
+                ("is_initialized", IdlType::Bool),
+                ("signers", IdlType::Array(&IdlType::Pubkey, MAX_SIGNERS)),
+            ],
+        ),
+    ]
+}
+
+fn errors() -> Vec<Value> {
+    (0..=u32::from(u16::MAX))
+        .filter_map(|code| Some((code, TokenError::from_u32(code)?)))
+        .map(|(code, error)| {
+            json!({ "code": code, "name": format!("{:?}", error), "msg": error.to_string() })
+        })
+        .collect()
+}
+
+/// The IDL as Anchor's JSON format
+pub fn idl() -> Value {
+    json!({
+        "address": crate::id().to_string(),
+        "metadata": {
+            "name": "test_token_solana",
+            "version": env!("CARGO_PKG_VERSION"),
+            "spec": "0.1.0",
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+        },
+        "instructions": INSTRUCTIONS.iter().map(IdlInstruction::to_json).collect::<Vec<_>>(),
+        "accounts": [],
+        "errors": errors(),
+        "types": types(),
+    })
+}
@@ -1,148 +1,6569 @@
-use crate::{error::TokenError};
+use crate::{
+    associated_account::get_associated_token_address_with_program_id, check_program_account,
+    error::TokenError,
+    state::{AccountState, PolicyMode},
+};
 use solana_program::{
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
+    program_option::COption,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
-use std::convert::TryInto;
+use num_enum::TryFromPrimitive;
+use std::convert::{TryFrom, TryInto};
 use std::mem::size_of;
 
+pub const MIN_SIGNERS: usize = 1;
+pub const MAX_SIGNERS: usize = 11;
+/// Transfer amount that moves the whole balance of the source, or for a
+/// delegate the lesser of the balance and the delegated amount
+pub const TRANSFER_ALL: u64 = u64::MAX;
+/// Most amounts a batch instruction may carry
+pub const MAX_BATCH_SIZE: usize = 20;
+
+// The tag byte each packed instruction starts with
+pub const INITIALIZE_MINT_TAG: u8 = 0;
+pub const INITIALIZE_ACCOUNT_TAG: u8 = 1;
+pub const TRANSFER_TAG: u8 = 2;
+pub const APPROVE_TAG: u8 = 3;
+pub const MINT_TO_TAG: u8 = 4;
+pub const BURN_TAG: u8 = 5;
+pub const REVOKE_TAG: u8 = 6;
+pub const SET_AUTHORITY_TAG: u8 = 7;
+pub const FREEZE_ACCOUNT_TAG: u8 = 8;
+pub const THAW_ACCOUNT_TAG: u8 = 9;
+pub const TRANSFER_CHECKED_TAG: u8 = 10;
+pub const APPROVE_CHECKED_TAG: u8 = 11;
+pub const BURN_CHECKED_TAG: u8 = 12;
+pub const INITIALIZE_MULTISIG_TAG: u8 = 13;
+pub const INITIALIZE_MINT2_TAG: u8 = 14;
+pub const INITIALIZE_ACCOUNT2_TAG: u8 = 15;
+pub const INITIALIZE_ACCOUNT3_TAG: u8 = 16;
+pub const SYNC_NATIVE_TAG: u8 = 17;
+pub const GET_ACCOUNT_DATA_SIZE_TAG: u8 = 18;
+pub const AMOUNT_TO_UI_AMOUNT_TAG: u8 = 19;
+pub const UI_AMOUNT_TO_AMOUNT_TAG: u8 = 20;
+pub const CLOSE_ACCOUNT_TAG: u8 = 21;
+pub const INCREASE_DELEGATED_AMOUNT_TAG: u8 = 22;
+pub const DECREASE_DELEGATED_AMOUNT_TAG: u8 = 23;
+pub const APPROVE_WITH_EXPIRY_TAG: u8 = 24;
+pub const TRANSFER_BATCH_TAG: u8 = 25;
+pub const MINT_TO_BATCH_TAG: u8 = 26;
+pub const PROPOSE_OWNER_TAG: u8 = 27;
+pub const ACCEPT_OWNERSHIP_TAG: u8 = 28;
+pub const SET_DEFAULT_ACCOUNT_STATE_TAG: u8 = 29;
+pub const REQUIRE_MEMO_TRANSFERS_TAG: u8 = 30;
+pub const TRANSFER_CHECKED_WITH_FEE_TAG: u8 = 31;
+pub const SET_TRANSFER_FEE_TAG: u8 = 32;
+pub const WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG: u8 = 33;
+pub const HARVEST_WITHHELD_TOKENS_TO_MINT_TAG: u8 = 34;
+pub const UPDATE_RATE_INTEREST_BEARING_MINT_TAG: u8 = 35;
+pub const PAUSE_TAG: u8 = 36;
+pub const RESUME_TAG: u8 = 37;
+pub const REALLOCATE_TAG: u8 = 38;
+pub const REALLOCATE_MINT_TAG: u8 = 39;
+pub const INITIALIZE_IMMUTABLE_OWNER_TAG: u8 = 40;
+pub const ENABLE_CPI_GUARD_TAG: u8 = 41;
+pub const DISABLE_CPI_GUARD_TAG: u8 = 42;
+pub const INITIALIZE_TRANSFER_HOOK_TAG: u8 = 43;
+pub const UPDATE_TRANSFER_HOOK_TAG: u8 = 44;
+pub const INITIALIZE_METADATA_POINTER_TAG: u8 = 45;
+pub const INITIALIZE_TOKEN_METADATA_TAG: u8 = 46;
+pub const UPDATE_TOKEN_METADATA_FIELD_TAG: u8 = 47;
+pub const INITIALIZE_ASSOCIATED_ACCOUNT_TAG: u8 = 48;
+pub const RECOVER_NESTED_TAG: u8 = 49;
+pub const WRAP_SOL_TAG: u8 = 50;
+pub const UNWRAP_SOL_TAG: u8 = 51;
+pub const REVOKE_ALL_TAG: u8 = 52;
+pub const ASSERT_BALANCE_TAG: u8 = 53;
+pub const ASSERT_SUPPLY_TAG: u8 = 54;
+pub const GET_ACCOUNT_BALANCE_TAG: u8 = 55;
+pub const GET_MINT_SUPPLY_TAG: u8 = 56;
+pub const SPLIT_ACCOUNT_TAG: u8 = 57;
+pub const MERGE_ACCOUNTS_TAG: u8 = 58;
+pub const LOCK_UNTIL_SLOT_TAG: u8 = 59;
+pub const INITIALIZE_POLICY_TAG: u8 = 60;
+pub const ADD_POLICY_ENTRY_TAG: u8 = 61;
+pub const REMOVE_POLICY_ENTRY_TAG: u8 = 62;
+pub const APPROVE_ESCROWED_TAG: u8 = 63;
+pub const UPDATE_DECIMALS_TAG: u8 = 64;
+pub const INITIALIZE_FLASH_MINT_TAG: u8 = 65;
+pub const FLASH_MINT_TO_TAG: u8 = 66;
+pub const FLASH_BURN_REPAY_TAG: u8 = 67;
+pub const SET_SPENDING_LIMIT_TAG: u8 = 68;
+pub const TRANSFER_VESTED_TAG: u8 = 69;
+pub const CLAIM_VESTED_TAG: u8 = 70;
+pub const SWAP_TAG: u8 = 71;
+pub const BATCH_TAG: u8 = 72;
+pub const FREEZE_ACCOUNTS_TAG: u8 = 73;
+pub const THAW_ACCOUNTS_TAG: u8 = 74;
+pub const BURN_FROM_TAG: u8 = 75;
+
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
-pub enum TokenInstruction {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenInstruction<'a> {
     InitializeMint {
         decimals: u8,
-        mint_authority: Pubkey
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        mint_authority: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        freeze_authority: COption<Pubkey>,
+        /// Hard cap on the supply, fixed for the life of the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+        max_supply: COption<u64>,
+        /// May transfer or burn from any account of the mint, fixed for the
+        /// life of the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        permanent_delegate: COption<Pubkey>,
+        /// May set the transfer fee, which starts at zero
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        transfer_fee_authority: COption<Pubkey>,
+        /// May set the interest rate, which starts at zero. Only a mint with a
+        /// rate authority accrues interest.
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        rate_authority: COption<Pubkey>,
+        /// May pause and resume the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        pause_authority: COption<Pubkey>,
     },
+    /// Accounts: account (writable), mint, owner, rent sysvar.
+    ///
+    /// The owner does not sign, so it can be a program-derived address that
+    /// has no private key. Its program then spends from the account by
+    /// signing for the address with `invoke_signed`.
     InitializeAccount,
+    /// Transfers tokens. An amount of `TRANSFER_ALL` sweeps the source. A
+    /// destination that requires memos also needs the instructions sysvar
+    /// after the signers. Accounts of a mint that may charge a fee, run a
+    /// transfer hook or check a transfer policy fail with
+    /// `MintRequiredForTransfer`; they transfer with `TransferChecked`.
     Transfer { amount: u64, },
     Approve { amount: u64, },
     MintTo { amount: u64, },
     Burn { amount: u64, },
+    /// Clears the delegate of the source account. Accounts: source (writable),
+    /// owner or current delegate (signer). Succeeds as a no-op if no delegate
+    /// is set.
+    Revoke,
+    /// Sets a new authority of a mint or account. Accounts: mint or account
+    /// (writable), current authority (signer). Fails with
+    /// `AuthorityTypeNotSupported` for an authority the target does not have,
+    /// and with `MintCannotFreeze` for a mint created without a freeze
+    /// authority or that renounced it.
+    SetAuthority {
+        authority_type: AuthorityType,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        new_authority: COption<Pubkey>,
+    },
+    /// Freezes an initialized account. Accounts: account (writable), mint,
+    /// freeze authority (signer).
+    FreezeAccount,
+    /// Thaws a frozen account. Accounts: account (writable), mint,
+    /// freeze authority (signer).
+    ThawAccount,
+    /// Transfers tokens after checking the mint and its decimals. Accounts:
+    /// source (writable), mint, destination (writable), authority (signer).
+    /// An amount of `TRANSFER_ALL` sweeps the source. The mint's permanent
+    /// delegate may act as the authority, and the mint's transfer fee is
+    /// withheld in the destination. A mint with a `TransferPolicy` needs the
+    /// policy after the authority and its signers, and it must permit the
+    /// destination's owner; transfers that leave it out fail with
+    /// `MissingTransferPolicy`.
+    TransferChecked { amount: u64, decimals: u8 },
+    /// Approves a delegate after checking the mint and its decimals. Accounts:
+    /// source (writable), mint, delegate, owner (signer).
+    ApproveChecked { amount: u64, decimals: u8 },
+    /// Burns tokens after checking the mint decimals. Accounts: source
+    /// (writable), mint (writable), authority (signer). The mint's permanent
+    /// delegate may act as the authority.
+    BurnChecked { amount: u64, decimals: u8 },
+    /// Initializes an M-of-N multisig that can be used as any authority.
+    /// Accounts: multisig (writable), rent sysvar, N signers.
+    InitializeMultisig { m: u8 },
+    /// Like `InitializeMint`, but reads rent from the sysvar cache instead of
+    /// requiring the rent sysvar account. Accounts: mint (writable).
+    InitializeMint2 {
+        decimals: u8,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        mint_authority: Pubkey,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        freeze_authority: COption<Pubkey>,
+        /// Hard cap on the supply, fixed for the life of the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+        max_supply: COption<u64>,
+        /// May transfer or burn from any account of the mint, fixed for the
+        /// life of the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        permanent_delegate: COption<Pubkey>,
+        /// May set the transfer fee, which starts at zero
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        transfer_fee_authority: COption<Pubkey>,
+        /// May set the interest rate, which starts at zero. Only a mint with a
+        /// rate authority accrues interest.
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        rate_authority: COption<Pubkey>,
+        /// May pause and resume the mint
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        pause_authority: COption<Pubkey>,
+    },
+    /// Like `InitializeAccount`, but the owner is passed in instruction data.
+    /// Accounts: account (writable), mint, rent sysvar.
+    InitializeAccount2 {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        owner: Pubkey,
+    },
+    /// Like `InitializeAccount2`, but reads rent from the sysvar cache.
+    /// Accounts: account (writable), mint.
+    InitializeAccount3 {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        owner: Pubkey,
+    },
+    /// Updates the amount of a native account to match its lamports minus
+    /// the rent-exempt reserve. Accounts: native account (writable).
+    SyncNative,
+    /// Returns the data size of a token account for the given mint as a
+    /// little-endian `u64` in the return data. Accounts: mint.
+    GetAccountDataSize,
+    /// Converts an amount to its UI representation using the mint decimals,
+    /// returned as a UTF-8 string in the return data. Accounts: mint.
+    AmountToUiAmount { amount: u64 },
+    /// Converts a UI amount to a raw amount using the mint decimals, returned
+    /// as a little-endian `u64` in the return data. The string takes the rest
+    /// of the instruction data. Accounts: mint.
+    UiAmountToAmount { ui_amount: &'a str },
+    /// Closes an account with a zero token balance and moves all of its
+    /// lamports to the destination. Native accounts may hold a balance.
+    /// Accounts: account (writable), destination (writable), owner or close
+    /// authority (signer).
+    CloseAccount,
+    /// Adds to the amount the current delegate may transfer, without
+    /// replacing it. Fails if no delegate is set. Accounts: source (writable),
+    /// owner (signer).
+    IncreaseDelegatedAmount { amount: u64 },
+    /// Subtracts from the amount the current delegate may transfer, clearing
+    /// the delegate when nothing is left. Fails if no delegate is set.
+    /// Accounts: source (writable), owner (signer).
+    DecreaseDelegatedAmount { amount: u64 },
+    /// Like `Approve`, but the delegate can no longer spend once the clock is
+    /// past `expiry_slot`. Accounts: source (writable), delegate, owner
+    /// (signer).
+    ApproveWithExpiry { amount: u64, expiry_slot: u64 },
+    /// Transfers from one source to several destinations at once, all or
+    /// nothing. Carries 1 to `MAX_BATCH_SIZE` amounts, one per destination.
+    /// Accounts: source (writable), authority (signer), a destination
+    /// (writable) for each amount. Fails for accounts that require the mint,
+    /// as `Transfer` does.
+    TransferBatch { amounts: Vec<u64> },
+    /// Mints to several destinations at once, all or nothing. Carries 1 to
+    /// `MAX_BATCH_SIZE` amounts, one per destination. Accounts: mint
+    /// (writable), mint authority (signer), a destination (writable) for
+    /// each amount.
+    MintToBatch { amounts: Vec<u64> },
+    /// Proposes a new owner, who takes over once they accept. Proposing
+    /// `Pubkey::default()` cancels a pending proposal, which the pending owner
+    /// may also do. Accounts: account (writable), owner or pending owner
+    /// (signer).
+    ProposeOwner {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        new_owner: Pubkey,
+    },
+    /// Makes the pending owner the owner of the account. Accounts: account
+    /// (writable), pending owner (signer).
+    AcceptOwnership,
+    /// Sets the state new accounts of the mint start in, either `Initialized`
+    /// or `Frozen`. Accounts: mint (writable), freeze authority (signer).
+    SetDefaultAccountState { state: AccountState },
+    /// Requires, or stops requiring, a memo instruction in every transaction
+    /// that transfers into the account. Accounts: account (writable), owner
+    /// (signer).
+    RequireMemoTransfers { enable: bool },
+    /// Like `TransferChecked`, but also checks the fee the mint withholds
+    /// from the transfer against `fee`. Accounts: source (writable), mint,
+    /// destination (writable), authority (signer).
+    TransferCheckedWithFee { amount: u64, decimals: u8, fee: u64 },
+    /// Sets the fee withheld from checked transfers, in basis points of the
+    /// amount and capped at `maximum_fee`. Accounts: mint (writable),
+    /// transfer fee authority (signer).
+    SetTransferFee {
+        transfer_fee_basis_points: u16,
+        maximum_fee: u64,
+    },
+    /// Moves the fees withheld in token accounts, and those already harvested
+    /// to the mint, into a destination account of the mint. Accounts: mint
+    /// (writable), destination (writable), transfer fee authority (signer),
+    /// then `num_token_accounts` source accounts (writable).
+    WithdrawWithheldTokensFromAccounts { num_token_accounts: u8 },
+    /// Moves the fees withheld in token accounts into the mint, so the
+    /// accounts can be closed. Anyone may call it. Accounts: mint
+    /// (writable), source accounts (writable).
+    HarvestWithheldTokensToMint,
+    /// Sets the interest rate of the mint, in basis points per year. Interest
+    /// accrued at the old rate is kept. Accounts: mint (writable), rate
+    /// authority (signer).
+    UpdateRateInterestBearingMint { rate: i16 },
+    /// Halts minting, burning and checked transfers of the mint's tokens.
+    /// Accounts: mint (writable), pause authority (signer).
+    Pause,
+    /// Lets a paused mint's tokens move again. Accounts: mint (writable),
+    /// pause authority (signer).
+    Resume,
+    /// Grows an account created under an older, shorter layout to the current
+    /// `Account::LEN`, with the payer funding the larger rent-exempt reserve.
+    /// Accounts: account (writable), payer (writable, signer), system
+    /// program, owner (signer).
+    Reallocate,
+    /// Grows a mint created under the `V1` or `Spl` layout to the current
+    /// `Mint::LEN`, with the payer funding the larger rent-exempt reserve.
+    /// Anyone may call it. Accounts: mint (writable), payer (writable,
+    /// signer), system program.
+    ReallocateMint,
+    /// Fixes the owner of an uninitialized account, so that `SetAuthority`
+    /// and `ProposeOwner` can never change it once the account is
+    /// initialized. Must precede `InitializeAccount`, in the same transaction.
+    /// Accounts: account (writable).
+    InitializeImmutableOwner,
+    /// Stops the owner from spending, approving or handing over the account
+    /// through a cross-program invocation. Delegates are unaffected. Must be
+    /// a top-level instruction. Accounts: account (writable), owner (signer).
+    EnableCpiGuard,
+    /// Lets the owner act on the account through cross-program invocations
+    /// again. Must be a top-level instruction. Accounts: account (writable),
+    /// owner (signer).
+    DisableCpiGuard,
+    /// Sets the transfer hook of an uninitialized mint. Must precede
+    /// `InitializeMint`, in the same transaction. Accounts: mint (writable).
+    InitializeTransferHook {
+        /// May change the hook program later
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        authority: COption<Pubkey>,
+        /// Invoked after every checked transfer, see `transfer_hook::execute`
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        program_id: COption<Pubkey>,
+    },
+    /// Replaces or removes the mint's transfer hook program. Accounts: mint
+    /// (writable), transfer hook authority (signer).
+    UpdateTransferHook {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        program_id: COption<Pubkey>,
+    },
+    /// Names the account holding the mint's `TokenMetadata`, which may be
+    /// the mint itself. Must precede `InitializeMint`, in the same
+    /// transaction. Accounts: mint (writable).
+    InitializeMetadataPointer {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+        metadata_address: COption<Pubkey>,
+    },
+    /// Writes the mint's metadata into the account its metadata pointer
+    /// names, sized to fit, with the payer covering any rent shortfall. Each
+    /// string is packed as a little-endian `u32` length and its bytes.
+    /// Accounts: metadata (writable), payer (writable, signer), system
+    /// program, mint, update authority, mint authority (signer).
+    InitializeTokenMetadata {
+        name: &'a str,
+        symbol: &'a str,
+        uri: &'a str,
+    },
+    /// Replaces one metadata field, resizing the account to fit, with the
+    /// payer covering any rent shortfall. Accounts: metadata (writable),
+    /// payer (writable, signer), system program, update authority (signer).
+    UpdateTokenMetadataField { field: MetadataField, value: &'a str },
+    /// Creates and initializes the wallet's associated token account for the
+    /// mint, at the address `get_associated_token_address` derives. The
+    /// payer funds the rent-exempt reserve. Accounts: payer (writable,
+    /// signer), associated account (writable), wallet, mint, system program,
+    /// rent sysvar.
+    InitializeAssociatedAccount,
+    /// Rescues tokens sent to a token account owned by another token account
+    /// rather than a wallet. Moves the nested account's whole balance to an
+    /// account of the wallet that owns the owning account, then closes the
+    /// nested account, its lamports going to the wallet. Accounts: nested
+    /// account (writable), destination (writable), owner token account,
+    /// wallet (writable, signer).
+    RecoverNested,
+    /// Moves lamports from a funding account into a native account and adds
+    /// them to its balance, in place of a lamport transfer and `SyncNative`.
+    /// Accounts: native account (writable), funding account (writable,
+    /// signer), system program, owner (signer).
+    WrapSol { lamports: u64 },
+    /// Takes lamports out of a native account's balance and moves them to a
+    /// destination, never touching the rent-exempt reserve. Accounts: native
+    /// account (writable), destination (writable), owner (signer).
+    UnwrapSol { lamports: u64 },
+    /// Clears every permission granted over an account: the delegate and its
+    /// allowance, the close authority and any pending owner proposal.
+    /// Accounts: account (writable), owner (signer). Succeeds as a no-op if
+    /// none are set.
+    RevokeAll,
+    /// Fails with `BalanceAssertionFailed` unless the account's balance is
+    /// within `minimum..=maximum`; pass `u64::MIN` or `u64::MAX` to leave a
+    /// bound open. Writes nothing. Accounts: account.
+    AssertBalance { minimum: u64, maximum: u64 },
+    /// As `AssertBalance`, for a mint's supply. Accounts: mint.
+    AssertSupply { minimum: u64, maximum: u64 },
+    /// Returns the account's balance as a little-endian `u64` in the return
+    /// data. Accounts: account.
+    GetAccountBalance,
+    /// Returns the mint's supply as a little-endian `u64` in the return data.
+    /// Accounts: mint.
+    GetMintSupply,
+    /// Initializes a new account of the source's mint and owner and moves
+    /// `amount` into it. The new account must already be rent-exempt.
+    /// Accounts: source (writable), new account (writable), mint, owner
+    /// (signer). A mint with a `TransferPolicy` needs the policy after the
+    /// owner and its signers, as for `TransferChecked`.
+    SplitAccount { amount: u64 },
+    /// Moves the whole balance of 1 to `MAX_BATCH_SIZE` sources into a
+    /// destination of the same mint and owner, all or nothing. With
+    /// `close_sources`, the emptied sources are closed and their lamports go
+    /// to the destination. Accounts: destination (writable), owner (signer),
+    /// `num_source_accounts` sources (writable). Fails for accounts that
+    /// require the mint, as `Transfer` does.
+    MergeAccounts { num_source_accounts: u8, close_sources: bool },
+    /// Stops anything being spent from or approved on the account before
+    /// `slot`. A lock can be extended but not shortened. Accounts: account
+    /// (writable), owner (signer).
+    LockUntilSlot { slot: u64 },
+    /// Like `Approve`, but also reserves the delegated amount: until the
+    /// delegation ends, the owner can only spend the rest of the balance.
+    /// `amount` may not exceed the balance. Accounts: source (writable),
+    /// delegate, owner (signer).
+    ApproveEscrowed { amount: u64 },
+    /// Changes the mint's decimals, up to `MAX_DECIMALS`, while its supply
+    /// is zero. Accounts: mint (writable), mint authority (signer).
+    UpdateDecimals { decimals: u8 },
+    /// Initializes a `TransferPolicy` with no entries and records it on an
+    /// uninitialized mint, whose transfers must then all pass it. Must
+    /// precede `InitializeMint`, in the same transaction. The policy account
+    /// must already be rent-exempt. Accounts: policy (writable), mint
+    /// (writable).
+    InitializePolicy { mode: PolicyMode },
+    /// Lists `entry` in the policy. Accounts: policy (writable), mint, freeze
+    /// authority (signer).
+    AddPolicyEntry {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        entry: Pubkey,
+    },
+    /// Removes `entry` from the policy. Accounts: policy (writable), mint,
+    /// freeze authority (signer).
+    RemovePolicyEntry {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+        entry: Pubkey,
+    },
+    /// Initializes a `FlashMintConfig` letting anyone flash mint the mint's
+    /// tokens for a flat `fee` each time. The config account must already be
+    /// rent-exempt. Accounts: config (writable), mint, mint authority
+    /// (signer).
+    InitializeFlashMint { fee: u64 },
+    /// Mints `amount` to the destination without the mint authority, provided
+    /// a `FlashBurnRepay` of the same mint, config and amount follows as a
+    /// later top-level instruction of the transaction. Accounts: mint
+    /// (writable), destination (writable), config (writable), instructions
+    /// sysvar.
+    FlashMintTo { amount: u64 },
+    /// Burns the outstanding flash mint `amount` from the source and moves
+    /// the config's fee from it to the fee destination, an account owned by
+    /// the mint authority and not read when the fee is zero. Accounts: source
+    /// (writable), mint (writable), config (writable), fee destination
+    /// (writable), owner or delegate (signer).
+    FlashBurnRepay { amount: u64 },
+    /// Caps what the owner and its delegate together may move out of the
+    /// account per epoch at `amount`, or lifts the cap with `None`. Split,
+    /// merged, unwrapped and closed-out amounts count along with transfers
+    /// and burns.
+    /// Accounts: account (writable), owner (signer).
+    SetSpendingLimit {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+        amount: COption<u64>,
+    },
+    /// Moves `total_amount` out of the source into the destination's vesting
+    /// schedule, released linearly from `start_slot` to `end_slot`. The
+    /// destination's previous schedule must be fully claimed. Accounts:
+    /// source (writable), destination (writable), owner or delegate (signer).
+    /// Fails for accounts that require the mint, as `Transfer` does.
+    TransferVested {
+        total_amount: u64,
+        start_slot: u64,
+        end_slot: u64,
+    },
+    /// Moves the part of the vesting schedule released so far into the
+    /// account's balance. Accounts: account (writable), owner (signer).
+    ClaimVested,
+    /// Moves `amount_a` from source A to destination A and `amount_b` from
+    /// source B to destination B, each leg validated as a `Transfer`; if
+    /// either fails, neither happens. Accounts: source A (writable),
+    /// destination A (writable), source B (writable), destination B
+    /// (writable), authority A (signer), authority B (signer), then any
+    /// accounts both legs see after their authority.
+    Swap { amount_a: u64, amount_b: u64 },
+    /// Runs 1 to `MAX_BATCH_SIZE` inner instructions in order, all or
+    /// nothing. Each entry is the number of accounts the inner instruction
+    /// consumes followed by its packed data; the accounts are split among
+    /// the entries in that order. A batch may not contain another batch.
+    Batch { instructions: Vec<Vec<u8>> },
+    /// Freezes `count` initialized accounts of one mint, all or nothing.
+    /// Accounts: mint, freeze authority (signer), `count` accounts
+    /// (writable).
+    FreezeAccounts { count: u8 },
+    /// Thaws `count` frozen accounts of one mint, all or nothing. Accounts:
+    /// mint, freeze authority (signer), `count` accounts (writable).
+    ThawAccounts { count: u8 },
+    /// Burns from any account of the mint on the mint authority's signature
+    /// alone, without the account owner's consent. Accounts: account
+    /// (writable), mint (writable), mint authority (signer).
+    BurnFrom { amount: u64 },
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize, borsh::BorshSchema)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuthorityType {
+    /// Mints new tokens. Set on a mint.
+    MintTokens,
+    /// Owns a token account. Set on an account.
+    AccountOwner,
+    /// May close a token account in addition to its owner. Set on an account.
+    CloseAccount,
+    /// Freezes and thaws the accounts of a mint. Set on a mint.
+    FreezeAccount,
 }
 
-impl TokenInstruction {
+/// Field of `TokenMetadata` that `UpdateTokenMetadataField` replaces
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize, borsh::BorshSchema)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MetadataField {
+    Name,
+    Symbol,
+    Uri,
+}
+
+impl<'a> TokenInstruction<'a> {
     pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
         match self {
             Self::InitializeMint {
                 mint_authority,
                 decimals,
+                freeze_authority,
+                max_supply,
+                permanent_delegate,
+                transfer_fee_authority,
+                rate_authority,
+                pause_authority,
             } => {
-                buf.push(0);
+                buf.push(INITIALIZE_MINT_TAG);
                 buf.push(*decimals);
                 buf.extend_from_slice(mint_authority.as_ref());
+                Self::pack_pubkey_option(freeze_authority, &mut buf);
+                Self::pack_u64_option(max_supply, &mut buf);
+                Self::pack_pubkey_option(permanent_delegate, &mut buf);
+                Self::pack_pubkey_option(transfer_fee_authority, &mut buf);
+                Self::pack_pubkey_option(rate_authority, &mut buf);
+                Self::pack_pubkey_option(pause_authority, &mut buf);
             }
-            Self::InitializeAccount => buf.push(1),
+            Self::InitializeAccount => buf.push(INITIALIZE_ACCOUNT_TAG),
             Self::Transfer { amount } => {
-                buf.push(2);
+                buf.push(TRANSFER_TAG);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
             Self::Approve { amount } => {
-                buf.push(3);
+                buf.push(APPROVE_TAG);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
             Self::MintTo { amount } => {
-                buf.push(4);
+                buf.push(MINT_TO_TAG);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
             Self::Burn { amount } => {
-                buf.push(5);
+                buf.push(BURN_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Revoke => buf.push(REVOKE_TAG),
+            Self::SetAuthority {
+                authority_type,
+                new_authority,
+            } => {
+                buf.push(SET_AUTHORITY_TAG);
+                buf.push(*authority_type as u8);
+                Self::pack_pubkey_option(new_authority, &mut buf);
+            }
+            Self::FreezeAccount => buf.push(FREEZE_ACCOUNT_TAG),
+            Self::ThawAccount => buf.push(THAW_ACCOUNT_TAG),
+            Self::TransferChecked { amount, decimals } => {
+                buf.push(TRANSFER_CHECKED_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*decimals);
+            }
+            Self::ApproveChecked { amount, decimals } => {
+                buf.push(APPROVE_CHECKED_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*decimals);
+            }
+            Self::BurnChecked { amount, decimals } => {
+                buf.push(BURN_CHECKED_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*decimals);
+            }
+            Self::InitializeMultisig { m } => {
+                buf.push(INITIALIZE_MULTISIG_TAG);
+                buf.push(*m);
+            }
+            Self::InitializeMint2 {
+                mint_authority,
+                decimals,
+                freeze_authority,
+                max_supply,
+                permanent_delegate,
+                transfer_fee_authority,
+                rate_authority,
+                pause_authority,
+            } => {
+                buf.push(INITIALIZE_MINT2_TAG);
+                buf.push(*decimals);
+                buf.extend_from_slice(mint_authority.as_ref());
+                Self::pack_pubkey_option(freeze_authority, &mut buf);
+                Self::pack_u64_option(max_supply, &mut buf);
+                Self::pack_pubkey_option(permanent_delegate, &mut buf);
+                Self::pack_pubkey_option(transfer_fee_authority, &mut buf);
+                Self::pack_pubkey_option(rate_authority, &mut buf);
+                Self::pack_pubkey_option(pause_authority, &mut buf);
+            }
+            Self::InitializeAccount2 { owner } => {
+                buf.push(INITIALIZE_ACCOUNT2_TAG);
+                buf.extend_from_slice(owner.as_ref());
+            }
+            Self::InitializeAccount3 { owner } => {
+                buf.push(INITIALIZE_ACCOUNT3_TAG);
+                buf.extend_from_slice(owner.as_ref());
+            }
+            Self::SyncNative => buf.push(SYNC_NATIVE_TAG),
+            Self::GetAccountDataSize => buf.push(GET_ACCOUNT_DATA_SIZE_TAG),
+            Self::AmountToUiAmount { amount } => {
+                buf.push(AMOUNT_TO_UI_AMOUNT_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::UiAmountToAmount { ui_amount } => {
+                buf.push(UI_AMOUNT_TO_AMOUNT_TAG);
+                buf.extend_from_slice(ui_amount.as_bytes());
+            }
+            Self::CloseAccount => buf.push(CLOSE_ACCOUNT_TAG),
+            Self::IncreaseDelegatedAmount { amount } => {
+                buf.push(INCREASE_DELEGATED_AMOUNT_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::DecreaseDelegatedAmount { amount } => {
+                buf.push(DECREASE_DELEGATED_AMOUNT_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ApproveWithExpiry {
+                amount,
+                expiry_slot,
+            } => {
+                buf.push(APPROVE_WITH_EXPIRY_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&expiry_slot.to_le_bytes());
+            }
+            Self::TransferBatch { amounts } => {
+                buf.push(TRANSFER_BATCH_TAG);
+                Self::pack_amounts(amounts, &mut buf);
+            }
+            Self::MintToBatch { amounts } => {
+                buf.push(MINT_TO_BATCH_TAG);
+                Self::pack_amounts(amounts, &mut buf);
+            }
+            Self::ProposeOwner { new_owner } => {
+                buf.push(PROPOSE_OWNER_TAG);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::AcceptOwnership => buf.push(ACCEPT_OWNERSHIP_TAG),
+            Self::SetDefaultAccountState { state } => {
+                buf.push(SET_DEFAULT_ACCOUNT_STATE_TAG);
+                buf.push(*state as u8);
+            }
+            Self::RequireMemoTransfers { enable } => {
+                buf.push(REQUIRE_MEMO_TRANSFERS_TAG);
+                buf.push(*enable as u8);
+            }
+            Self::TransferCheckedWithFee {
+                amount,
+                decimals,
+                fee,
+            } => {
+                buf.push(TRANSFER_CHECKED_WITH_FEE_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*decimals);
+                buf.extend_from_slice(&fee.to_le_bytes());
+            }
+            Self::SetTransferFee {
+                transfer_fee_basis_points,
+                maximum_fee,
+            } => {
+                buf.push(SET_TRANSFER_FEE_TAG);
+                buf.extend_from_slice(&transfer_fee_basis_points.to_le_bytes());
+                buf.extend_from_slice(&maximum_fee.to_le_bytes());
+            }
+            Self::WithdrawWithheldTokensFromAccounts { num_token_accounts } => {
+                buf.push(WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG);
+                buf.push(*num_token_accounts);
+            }
+            Self::HarvestWithheldTokensToMint => buf.push(HARVEST_WITHHELD_TOKENS_TO_MINT_TAG),
+            Self::UpdateRateInterestBearingMint { rate } => {
+                buf.push(UPDATE_RATE_INTEREST_BEARING_MINT_TAG);
+                buf.extend_from_slice(&rate.to_le_bytes());
+            }
+            Self::Pause => buf.push(PAUSE_TAG),
+            Self::Resume => buf.push(RESUME_TAG),
+            Self::Reallocate => buf.push(REALLOCATE_TAG),
+            Self::ReallocateMint => buf.push(REALLOCATE_MINT_TAG),
+            Self::InitializeImmutableOwner => buf.push(INITIALIZE_IMMUTABLE_OWNER_TAG),
+            Self::EnableCpiGuard => buf.push(ENABLE_CPI_GUARD_TAG),
+            Self::DisableCpiGuard => buf.push(DISABLE_CPI_GUARD_TAG),
+            Self::InitializeTransferHook {
+                authority,
+                program_id,
+            } => {
+                buf.push(INITIALIZE_TRANSFER_HOOK_TAG);
+                Self::pack_pubkey_option(authority, &mut buf);
+                Self::pack_pubkey_option(program_id, &mut buf);
+            }
+            Self::UpdateTransferHook { program_id } => {
+                buf.push(UPDATE_TRANSFER_HOOK_TAG);
+                Self::pack_pubkey_option(program_id, &mut buf);
+            }
+            Self::InitializeMetadataPointer { metadata_address } => {
+                buf.push(INITIALIZE_METADATA_POINTER_TAG);
+                Self::pack_pubkey_option(metadata_address, &mut buf);
+            }
+            Self::InitializeTokenMetadata { name, symbol, uri } => {
+                buf.push(INITIALIZE_TOKEN_METADATA_TAG);
+                Self::pack_str(name, &mut buf);
+                Self::pack_str(symbol, &mut buf);
+                Self::pack_str(uri, &mut buf);
+            }
+            Self::UpdateTokenMetadataField { field, value } => {
+                buf.push(UPDATE_TOKEN_METADATA_FIELD_TAG);
+                buf.push(*field as u8);
+                Self::pack_str(value, &mut buf);
+            }
+            Self::InitializeAssociatedAccount => buf.push(INITIALIZE_ASSOCIATED_ACCOUNT_TAG),
+            Self::RecoverNested => buf.push(RECOVER_NESTED_TAG),
+            Self::WrapSol { lamports } => {
+                buf.push(WRAP_SOL_TAG);
+                buf.extend_from_slice(&lamports.to_le_bytes());
+            }
+            Self::UnwrapSol { lamports } => {
+                buf.push(UNWRAP_SOL_TAG);
+                buf.extend_from_slice(&lamports.to_le_bytes());
+            }
+            Self::RevokeAll => buf.push(REVOKE_ALL_TAG),
+            Self::AssertBalance { minimum, maximum } => {
+                buf.push(ASSERT_BALANCE_TAG);
+                buf.extend_from_slice(&minimum.to_le_bytes());
+                buf.extend_from_slice(&maximum.to_le_bytes());
+            }
+            Self::AssertSupply { minimum, maximum } => {
+                buf.push(ASSERT_SUPPLY_TAG);
+                buf.extend_from_slice(&minimum.to_le_bytes());
+                buf.extend_from_slice(&maximum.to_le_bytes());
+            }
+            Self::GetAccountBalance => buf.push(GET_ACCOUNT_BALANCE_TAG),
+            Self::GetMintSupply => buf.push(GET_MINT_SUPPLY_TAG),
+            Self::SplitAccount { amount } => {
+                buf.push(SPLIT_ACCOUNT_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::MergeAccounts {
+                num_source_accounts,
+                close_sources,
+            } => {
+                buf.push(MERGE_ACCOUNTS_TAG);
+                buf.push(*num_source_accounts);
+                buf.push(*close_sources as u8);
+            }
+            Self::LockUntilSlot { slot } => {
+                buf.push(LOCK_UNTIL_SLOT_TAG);
+                buf.extend_from_slice(&slot.to_le_bytes());
+            }
+            Self::InitializePolicy { mode } => {
+                buf.push(INITIALIZE_POLICY_TAG);
+                buf.push(*mode as u8);
+            }
+            Self::AddPolicyEntry { entry } => {
+                buf.push(ADD_POLICY_ENTRY_TAG);
+                buf.extend_from_slice(entry.as_ref());
+            }
+            Self::RemovePolicyEntry { entry } => {
+                buf.push(REMOVE_POLICY_ENTRY_TAG);
+                buf.extend_from_slice(entry.as_ref());
+            }
+            Self::ApproveEscrowed { amount } => {
+                buf.push(APPROVE_ESCROWED_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::UpdateDecimals { decimals } => {
+                buf.push(UPDATE_DECIMALS_TAG);
+                buf.push(*decimals);
+            }
+            Self::InitializeFlashMint { fee } => {
+                buf.push(INITIALIZE_FLASH_MINT_TAG);
+                buf.extend_from_slice(&fee.to_le_bytes());
+            }
+            Self::FlashMintTo { amount } => {
+                buf.push(FLASH_MINT_TO_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::FlashBurnRepay { amount } => {
+                buf.push(FLASH_BURN_REPAY_TAG);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SetSpendingLimit { amount } => {
+                buf.push(SET_SPENDING_LIMIT_TAG);
+                Self::pack_u64_option(amount, &mut buf);
+            }
+            Self::TransferVested {
+                total_amount,
+                start_slot,
+                end_slot,
+            } => {
+                buf.push(TRANSFER_VESTED_TAG);
+                buf.extend_from_slice(&total_amount.to_le_bytes());
+                buf.extend_from_slice(&start_slot.to_le_bytes());
+                buf.extend_from_slice(&end_slot.to_le_bytes());
+            }
+            Self::ClaimVested => buf.push(CLAIM_VESTED_TAG),
+            Self::Swap { amount_a, amount_b } => {
+                buf.push(SWAP_TAG);
+                buf.extend_from_slice(&amount_a.to_le_bytes());
+                buf.extend_from_slice(&amount_b.to_le_bytes());
+            }
+            Self::Batch { instructions } => {
+                buf.push(BATCH_TAG);
+                buf.push(instructions.len() as u8);
+                for entry in instructions {
+                    buf.extend_from_slice(&(entry.len() as u16).to_le_bytes());
+                    buf.extend_from_slice(entry);
+                }
+            }
+            Self::FreezeAccounts { count } => {
+                buf.push(FREEZE_ACCOUNTS_TAG);
+                buf.push(*count);
+            }
+            Self::ThawAccounts { count } => {
+                buf.push(THAW_ACCOUNTS_TAG);
+                buf.push(*count);
+            }
+            Self::BurnFrom { amount } => {
+                buf.push(BURN_FROM_TAG);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
         };
         buf
     }
 
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+    /// Unpacks a byte buffer, rejecting any data past what the tag expects
+    pub fn unpack(input: &'a [u8]) -> Result<Self, ProgramError> {
         use TokenError::InvalidInstruction;
 
         let (&tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
-        Ok(match tag {
-            0 => {
+        let (instruction, rest) = match tag {
+            INITIALIZE_MINT_TAG | INITIALIZE_MINT2_TAG => {
                 let (&decimals, rest) = rest.split_first().ok_or(InvalidInstruction)?;
-                let (mint_authority, _rest) = Self::unpack_pubkey(rest)?;
-                Self::InitializeMint {
-                    decimals,
-                    mint_authority,
-                }
+                let (mint_authority, rest) = Self::unpack_pubkey(rest)?;
+                // Mints packed before freeze support carry no trailing option
+                let (freeze_authority, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_pubkey_option(rest)?
+                };
+                // and those packed before supply caps carry no cap
+                let (max_supply, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_u64_option(rest)?
+                };
+                let (permanent_delegate, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_pubkey_option(rest)?
+                };
+                let (transfer_fee_authority, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_pubkey_option(rest)?
+                };
+                let (rate_authority, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_pubkey_option(rest)?
+                };
+                let (pause_authority, rest) = if rest.is_empty() {
+                    (COption::None, rest)
+                } else {
+                    Self::unpack_pubkey_option(rest)?
+                };
+                let instruction = match tag {
+                    INITIALIZE_MINT_TAG => Self::InitializeMint {
+                        decimals,
+                        mint_authority,
+                        freeze_authority,
+                        max_supply,
+                        permanent_delegate,
+                        transfer_fee_authority,
+                        rate_authority,
+                        pause_authority,
+                    },
+                    INITIALIZE_MINT2_TAG => Self::InitializeMint2 {
+                        decimals,
+                        mint_authority,
+                        freeze_authority,
+                        max_supply,
+                        permanent_delegate,
+                        transfer_fee_authority,
+                        rate_authority,
+                        pause_authority,
+                    },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            INITIALIZE_ACCOUNT_TAG => (Self::InitializeAccount, rest),
+            TRANSFER_TAG..=BURN_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let instruction = match tag {
+                    TRANSFER_TAG => Self::Transfer { amount },
+                    APPROVE_TAG => Self::Approve { amount },
+                    MINT_TO_TAG => Self::MintTo { amount },
+                    BURN_TAG => Self::Burn { amount },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            REVOKE_TAG => (Self::Revoke, rest),
+            SET_AUTHORITY_TAG => {
+                let (&authority_type, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let authority_type = AuthorityType::try_from_primitive(authority_type)
+                    .or(Err(TokenError::AuthorityTypeNotSupported))?;
+                let (new_authority, rest) = Self::unpack_pubkey_option(rest)?;
+                (
+                    Self::SetAuthority {
+                        authority_type,
+                        new_authority,
+                    },
+                    rest,
+                )
+            }
+            FREEZE_ACCOUNT_TAG => (Self::FreezeAccount, rest),
+            THAW_ACCOUNT_TAG => (Self::ThawAccount, rest),
+            TRANSFER_CHECKED_TAG..=BURN_CHECKED_TAG => {
+                let (amount, decimals, rest) = Self::unpack_amount_decimals(rest)?;
+                let instruction = match tag {
+                    TRANSFER_CHECKED_TAG => Self::TransferChecked { amount, decimals },
+                    APPROVE_CHECKED_TAG => Self::ApproveChecked { amount, decimals },
+                    BURN_CHECKED_TAG => Self::BurnChecked { amount, decimals },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            INITIALIZE_MULTISIG_TAG => {
+                let (&m, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                (Self::InitializeMultisig { m }, rest)
+            }
+            INITIALIZE_ACCOUNT2_TAG | INITIALIZE_ACCOUNT3_TAG => {
+                let (owner, rest) = Self::unpack_pubkey(rest)?;
+                let instruction = match tag {
+                    INITIALIZE_ACCOUNT2_TAG => Self::InitializeAccount2 { owner },
+                    INITIALIZE_ACCOUNT3_TAG => Self::InitializeAccount3 { owner },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            SYNC_NATIVE_TAG => (Self::SyncNative, rest),
+            GET_ACCOUNT_DATA_SIZE_TAG => (Self::GetAccountDataSize, rest),
+            AMOUNT_TO_UI_AMOUNT_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::AmountToUiAmount { amount }, rest)
+            }
+            UI_AMOUNT_TO_AMOUNT_TAG => {
+                // The UI amount takes up the rest of the instruction data
+                let ui_amount = std::str::from_utf8(rest).map_err(|_| InvalidInstruction)?;
+                (Self::UiAmountToAmount { ui_amount }, &rest[rest.len()..])
+            }
+            CLOSE_ACCOUNT_TAG => (Self::CloseAccount, rest),
+            INCREASE_DELEGATED_AMOUNT_TAG | DECREASE_DELEGATED_AMOUNT_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let instruction = match tag {
+                    INCREASE_DELEGATED_AMOUNT_TAG => Self::IncreaseDelegatedAmount { amount },
+                    DECREASE_DELEGATED_AMOUNT_TAG => Self::DecreaseDelegatedAmount { amount },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            APPROVE_WITH_EXPIRY_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (expiry_slot, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::ApproveWithExpiry {
+                        amount,
+                        expiry_slot,
+                    },
+                    rest,
+                )
+            }
+            TRANSFER_BATCH_TAG => {
+                let (amounts, rest) = Self::unpack_amounts(rest)?;
+                (Self::TransferBatch { amounts }, rest)
+            }
+            MINT_TO_BATCH_TAG => {
+                let (amounts, rest) = Self::unpack_amounts(rest)?;
+                (Self::MintToBatch { amounts }, rest)
+            }
+            PROPOSE_OWNER_TAG => {
+                let (new_owner, rest) = Self::unpack_pubkey(rest)?;
+                (Self::ProposeOwner { new_owner }, rest)
+            }
+            ACCEPT_OWNERSHIP_TAG => (Self::AcceptOwnership, rest),
+            SET_DEFAULT_ACCOUNT_STATE_TAG => {
+                let (&state, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let state = AccountState::try_from_primitive(state).or(Err(InvalidInstruction))?;
+                (Self::SetDefaultAccountState { state }, rest)
+            }
+            REQUIRE_MEMO_TRANSFERS_TAG => {
+                let (&enable, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let enable = match enable {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                (Self::RequireMemoTransfers { enable }, rest)
+            }
+            TRANSFER_CHECKED_WITH_FEE_TAG => {
+                let (amount, decimals, rest) = Self::unpack_amount_decimals(rest)?;
+                let (fee, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::TransferCheckedWithFee {
+                        amount,
+                        decimals,
+                        fee,
+                    },
+                    rest,
+                )
+            }
+            SET_TRANSFER_FEE_TAG => {
+                let (transfer_fee_basis_points, rest) = Self::unpack_u16(rest)?;
+                let (maximum_fee, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::SetTransferFee {
+                        transfer_fee_basis_points,
+                        maximum_fee,
+                    },
+                    rest,
+                )
+            }
+            WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG => {
+                let (&num_token_accounts, rest) =
+                    rest.split_first().ok_or(InvalidInstruction)?;
+                (
+                    Self::WithdrawWithheldTokensFromAccounts { num_token_accounts },
+                    rest,
+                )
+            }
+            HARVEST_WITHHELD_TOKENS_TO_MINT_TAG => (Self::HarvestWithheldTokensToMint, rest),
+            UPDATE_RATE_INTEREST_BEARING_MINT_TAG => {
+                let (rate, rest) = Self::unpack_u16(rest)?;
+                (
+                    Self::UpdateRateInterestBearingMint { rate: rate as i16 },
+                    rest,
+                )
+            }
+            PAUSE_TAG => (Self::Pause, rest),
+            RESUME_TAG => (Self::Resume, rest),
+            REALLOCATE_TAG => (Self::Reallocate, rest),
+            REALLOCATE_MINT_TAG => (Self::ReallocateMint, rest),
+            INITIALIZE_IMMUTABLE_OWNER_TAG => (Self::InitializeImmutableOwner, rest),
+            ENABLE_CPI_GUARD_TAG => (Self::EnableCpiGuard, rest),
+            DISABLE_CPI_GUARD_TAG => (Self::DisableCpiGuard, rest),
+            INITIALIZE_TRANSFER_HOOK_TAG => {
+                let (authority, rest) = Self::unpack_pubkey_option(rest)?;
+                let (program_id, rest) = Self::unpack_pubkey_option(rest)?;
+                (
+                    Self::InitializeTransferHook {
+                        authority,
+                        program_id,
+                    },
+                    rest,
+                )
             }
-            1 => Self::InitializeAccount,
-            2 | 3 | 4 | 5 => {
-                let amount = rest
-                    .get(..8)
-                    .and_then(|slice| slice.try_into().ok())
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                match tag {
-                    2 => Self::Transfer { amount },
-                    3 => Self::Approve { amount },
-                    4 => Self::MintTo { amount },
-                    5 => Self::Burn { amount },
+            UPDATE_TRANSFER_HOOK_TAG => {
+                let (program_id, rest) = Self::unpack_pubkey_option(rest)?;
+                (Self::UpdateTransferHook { program_id }, rest)
+            }
+            INITIALIZE_METADATA_POINTER_TAG => {
+                let (metadata_address, rest) = Self::unpack_pubkey_option(rest)?;
+                (Self::InitializeMetadataPointer { metadata_address }, rest)
+            }
+            INITIALIZE_TOKEN_METADATA_TAG => {
+                let (name, rest) = Self::unpack_str(rest)?;
+                let (symbol, rest) = Self::unpack_str(rest)?;
+                let (uri, rest) = Self::unpack_str(rest)?;
+                (Self::InitializeTokenMetadata { name, symbol, uri }, rest)
+            }
+            UPDATE_TOKEN_METADATA_FIELD_TAG => {
+                let (&field, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let field = MetadataField::try_from_primitive(field).or(Err(InvalidInstruction))?;
+                let (value, rest) = Self::unpack_str(rest)?;
+                (Self::UpdateTokenMetadataField { field, value }, rest)
+            }
+            INITIALIZE_ASSOCIATED_ACCOUNT_TAG => (Self::InitializeAssociatedAccount, rest),
+            RECOVER_NESTED_TAG => (Self::RecoverNested, rest),
+            WRAP_SOL_TAG | UNWRAP_SOL_TAG => {
+                let (lamports, rest) = Self::unpack_u64(rest)?;
+                let instruction = match tag {
+                    WRAP_SOL_TAG => Self::WrapSol { lamports },
+                    UNWRAP_SOL_TAG => Self::UnwrapSol { lamports },
                     _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            REVOKE_ALL_TAG => (Self::RevokeAll, rest),
+            ASSERT_BALANCE_TAG | ASSERT_SUPPLY_TAG => {
+                let (minimum, rest) = Self::unpack_u64(rest)?;
+                let (maximum, rest) = Self::unpack_u64(rest)?;
+                let instruction = match tag {
+                    ASSERT_BALANCE_TAG => Self::AssertBalance { minimum, maximum },
+                    ASSERT_SUPPLY_TAG => Self::AssertSupply { minimum, maximum },
+                    _ => unreachable!(),
+                };
+                (instruction, rest)
+            }
+            GET_ACCOUNT_BALANCE_TAG => (Self::GetAccountBalance, rest),
+            GET_MINT_SUPPLY_TAG => (Self::GetMintSupply, rest),
+            SPLIT_ACCOUNT_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::SplitAccount { amount }, rest)
+            }
+            MERGE_ACCOUNTS_TAG => {
+                let (&num_source_accounts, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                if num_source_accounts == 0 || num_source_accounts as usize > MAX_BATCH_SIZE {
+                    return Err(InvalidInstruction.into());
+                }
+                let (&close_sources, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let close_sources = match close_sources {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                (
+                    Self::MergeAccounts {
+                        num_source_accounts,
+                        close_sources,
+                    },
+                    rest,
+                )
+            }
+            LOCK_UNTIL_SLOT_TAG => {
+                let (slot, rest) = Self::unpack_u64(rest)?;
+                (Self::LockUntilSlot { slot }, rest)
+            }
+            INITIALIZE_POLICY_TAG => {
+                let (&mode, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let mode = PolicyMode::try_from_primitive(mode).or(Err(InvalidInstruction))?;
+                (Self::InitializePolicy { mode }, rest)
+            }
+            ADD_POLICY_ENTRY_TAG => {
+                let (entry, rest) = Self::unpack_pubkey(rest)?;
+                (Self::AddPolicyEntry { entry }, rest)
+            }
+            REMOVE_POLICY_ENTRY_TAG => {
+                let (entry, rest) = Self::unpack_pubkey(rest)?;
+                (Self::RemovePolicyEntry { entry }, rest)
+            }
+            APPROVE_ESCROWED_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::ApproveEscrowed { amount }, rest)
+            }
+            UPDATE_DECIMALS_TAG => {
+                let (&decimals, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                (Self::UpdateDecimals { decimals }, rest)
+            }
+            INITIALIZE_FLASH_MINT_TAG => {
+                let (fee, rest) = Self::unpack_u64(rest)?;
+                (Self::InitializeFlashMint { fee }, rest)
+            }
+            FLASH_MINT_TO_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::FlashMintTo { amount }, rest)
+            }
+            FLASH_BURN_REPAY_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::FlashBurnRepay { amount }, rest)
+            }
+            SET_SPENDING_LIMIT_TAG => {
+                let (amount, rest) = Self::unpack_u64_option(rest)?;
+                (Self::SetSpendingLimit { amount }, rest)
+            }
+            TRANSFER_VESTED_TAG => {
+                let (total_amount, rest) = Self::unpack_u64(rest)?;
+                let (start_slot, rest) = Self::unpack_u64(rest)?;
+                let (end_slot, rest) = Self::unpack_u64(rest)?;
+                (
+                    Self::TransferVested {
+                        total_amount,
+                        start_slot,
+                        end_slot,
+                    },
+                    rest,
+                )
+            }
+            CLAIM_VESTED_TAG => (Self::ClaimVested, rest),
+            SWAP_TAG => {
+                let (amount_a, rest) = Self::unpack_u64(rest)?;
+                let (amount_b, rest) = Self::unpack_u64(rest)?;
+                (Self::Swap { amount_a, amount_b }, rest)
+            }
+            BATCH_TAG => {
+                let (&count, mut rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                if count == 0 || count as usize > MAX_BATCH_SIZE {
+                    return Err(InvalidInstruction.into());
+                }
+                let mut instructions = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (len, next) = Self::unpack_u16(rest)?;
+                    let entry = next.get(..len as usize).ok_or(InvalidInstruction)?;
+                    // an entry needs its account count and at least a tag
+                    if entry.len() < 2 {
+                        return Err(InvalidInstruction.into());
+                    }
+                    instructions.push(entry.to_vec());
+                    rest = &next[len as usize..];
                 }
+                (Self::Batch { instructions }, rest)
+            }
+            FREEZE_ACCOUNTS_TAG | THAW_ACCOUNTS_TAG => {
+                let (&count, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                if count == 0 {
+                    return Err(InvalidInstruction.into());
+                }
+                let instruction = if tag == FREEZE_ACCOUNTS_TAG {
+                    Self::FreezeAccounts { count }
+                } else {
+                    Self::ThawAccounts { count }
+                };
+                (instruction, rest)
+            }
+            BURN_FROM_TAG => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                (Self::BurnFrom { amount }, rest)
             }
             _ => return Err(TokenError::InvalidInstruction.into()),
-        })
+        };
+
+        if !rest.is_empty() {
+            return Err(TokenError::InvalidInstruction.into());
+        }
+        Ok(instruction)
+    }
+
+    /// A batch is a one-byte count followed by that many little-endian amounts
+    fn unpack_amounts(input: &[u8]) -> Result<(Vec<u64>, &[u8]), ProgramError> {
+        let (&count, mut rest) = input.split_first().ok_or(TokenError::InvalidInstruction)?;
+        if count == 0 || count as usize > MAX_BATCH_SIZE {
+            return Err(TokenError::InvalidInstruction.into());
+        }
+        let mut amounts = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (amount, next) = Self::unpack_u64(rest)?;
+            amounts.push(amount);
+            rest = next;
+        }
+        Ok((amounts, rest))
+    }
+
+    fn pack_amounts(amounts: &[u64], buf: &mut Vec<u8>) {
+        buf.push(amounts.len() as u8);
+        for amount in amounts {
+            buf.extend_from_slice(&amount.to_le_bytes());
+        }
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        let value = input
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(TokenError::InvalidInstruction)?;
+        Ok((value, &input[2..]))
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        let amount = input
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(TokenError::InvalidInstruction)?;
+        Ok((amount, &input[8..]))
+    }
+
+    /// The amount of a checked instruction followed by the decimals it expects
+    fn unpack_amount_decimals(input: &[u8]) -> Result<(u64, u8, &[u8]), ProgramError> {
+        let (amount, rest) = Self::unpack_u64(input)?;
+        let (&decimals, rest) = rest
+            .split_first()
+            .ok_or(TokenError::InvalidInstruction)?;
+        Ok((amount, decimals, rest))
     }
 
     fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
         if input.len() >= 32 {
             let (key, rest) = input.split_at(32);
-            let pk = Pubkey::new(key);
+            let pk = Pubkey::try_from(key).map_err(|_| TokenError::InvalidInstruction)?;
             Ok((pk, rest))
         } else {
             Err(TokenError::InvalidInstruction.into())
         }
     }
+
+    fn unpack_pubkey_option(input: &[u8]) -> Result<(COption<Pubkey>, &[u8]), ProgramError> {
+        match input.split_first() {
+            Some((&0, rest)) => Ok((COption::None, rest)),
+            Some((&1, rest)) => {
+                let (pk, rest) = Self::unpack_pubkey(rest)?;
+                Ok((COption::Some(pk), rest))
+            }
+            _ => Err(TokenError::InvalidInstruction.into()),
+        }
+    }
+
+    fn pack_pubkey_option(value: &COption<Pubkey>, buf: &mut Vec<u8>) {
+        match *value {
+            COption::Some(ref key) => {
+                buf.push(1);
+                buf.extend_from_slice(&key.to_bytes());
+            }
+            COption::None => buf.push(0),
+        }
+    }
+
+    fn unpack_str(input: &[u8]) -> Result<(&str, &[u8]), ProgramError> {
+        let len = input
+            .get(..4)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u32::from_le_bytes)
+            .ok_or(TokenError::InvalidInstruction)? as usize;
+        let bytes = input
+            .get(4..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(TokenError::InvalidInstruction)?;
+        let value = std::str::from_utf8(bytes).map_err(|_| TokenError::InvalidInstruction)?;
+        Ok((value, &input[4 + len..]))
+    }
+
+    fn pack_str(value: &str, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn unpack_u64_option(input: &[u8]) -> Result<(COption<u64>, &[u8]), ProgramError> {
+        match input.split_first() {
+            Some((&0, rest)) => Ok((COption::None, rest)),
+            Some((&1, rest)) => {
+                let (value, rest) = Self::unpack_u64(rest)?;
+                Ok((COption::Some(value), rest))
+            }
+            _ => Err(TokenError::InvalidInstruction.into()),
+        }
+    }
+
+    fn pack_u64_option(value: &COption<u64>, buf: &mut Vec<u8>) {
+        match *value {
+            COption::Some(value) => {
+                buf.push(1);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            COption::None => buf.push(0),
+        }
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+pub fn initialize_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    initialize_mint_with_max_supply(
+        token_program_id,
+        mint_pubkey,
+        mint_authority_pubkey,
+        freeze_authority_pubkey,
+        decimals,
+        None,
+    )
+}
 
-    #[test]
-    fn test_mint1() {
-        let mint = TokenInstruction::InitializeMint {
-            decimals: 2,
-            mint_authority: Pubkey::new(&[1u8; 32]),
-        };
+/// Builds an `InitializeMint` with a permanent delegate, who may transfer or
+/// burn from any account of the mint
+pub fn initialize_mint_with_permanent_delegate(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    permanent_delegate_pubkey: &Pubkey,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: COption::None,
+        permanent_delegate: COption::Some(*permanent_delegate_pubkey),
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::None,
+    }
+    .pack();
 
-        let mut packed = Vec::from([0u8, 2]);
-        packed.extend_from_slice(&[1u8; 32]);
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
 
-        assert_eq!(mint.pack(), packed);
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
 
-        let unpacked = TokenInstruction::unpack(&packed).unwrap();
-        assert_eq!(unpacked, mint);
+/// Builds an `InitializeMint` with a transfer fee authority, who may then set
+/// the fee withheld from checked transfers
+pub fn initialize_mint_with_transfer_fee_authority(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    transfer_fee_authority_pubkey: &Pubkey,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::Some(*transfer_fee_authority_pubkey),
+        rate_authority: COption::None,
+        pause_authority: COption::None,
     }
+    .pack();
 
-    #[test]
-    fn test_mint2() {
-        let mint = TokenInstruction::InitializeMint {
-            decimals: 2,
-            mint_authority: Pubkey::new(&[2u8; 32]),
-        };
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
 
-        let mut packed = Vec::from([0u8, 2]);
-        packed.extend_from_slice(&[2u8; 32]);
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
 
-        assert_eq!(mint.pack(), packed);
+/// Builds an `InitializeMint` with a rate authority, whose mint reports
+/// amounts with interest accrued at the rate it sets
+pub fn initialize_mint_with_rate_authority(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    rate_authority_pubkey: &Pubkey,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::Some(*rate_authority_pubkey),
+        pause_authority: COption::None,
+    }
+    .pack();
 
-        let unpacked = TokenInstruction::unpack(&packed).unwrap();
-        assert_eq!(unpacked, mint);
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds an `InitializeMint` with a pause authority, who may then halt all
+/// minting, burning and checked transfers of the mint's tokens
+pub fn initialize_mint_with_pause_authority(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    pause_authority_pubkey: &Pubkey,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::Some(*pause_authority_pubkey),
     }
+    .pack();
 
-    #[test]
-    fn test_init_account1() {
-        let init_account = TokenInstruction::InitializeAccount;
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
 
-        let packed = Vec::from([1u8]);
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
 
-        assert_eq!(init_account.pack(), packed);
+/// Builds an `InitializeMint` whose supply can never exceed `max_supply`
+pub fn initialize_mint_with_max_supply(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    decimals: u8,
+    max_supply: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: max_supply.into(),
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::None,
+    }
+    .pack();
 
-        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
 
-        assert_eq!(unpacked, init_account);
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_mint2(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    freeze_authority_pubkey: Option<&Pubkey>,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let freeze_authority = freeze_authority_pubkey.cloned().into();
+    let data = TokenInstruction::InitializeMint2 {
+        mint_authority: *mint_authority_pubkey,
+        freeze_authority,
+        decimals,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::None,
+    }
+    .pack();
+
+    let accounts = vec![AccountMeta::new(*mint_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// The owner need not sign, so it may be off the curve, such as a
+/// program-derived address
+pub fn initialize_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeAccount.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_account2(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeAccount2 {
+        owner: *owner_pubkey,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_account3(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeAccount3 {
+        owner: *owner_pubkey,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_multisig(
+    token_program_id: &Pubkey,
+    multisig_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    m: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeMultisig { m }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*multisig_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn is_valid_signer_index(index: usize) -> bool {
+    (MIN_SIGNERS..=MAX_SIGNERS).contains(&index)
+}
+
+pub fn transfer(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Transfer { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `Batch` running `instructions` in order, passing each the accounts
+/// of its own metas
+pub fn batch(
+    token_program_id: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    if instructions.is_empty() || instructions.len() > MAX_BATCH_SIZE {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut entries = Vec::with_capacity(instructions.len());
+    let mut accounts = vec![];
+    for instruction in instructions {
+        check_program_account(&instruction.program_id)?;
+        let account_count =
+            u8::try_from(instruction.accounts.len()).map_err(|_| ProgramError::InvalidArgument)?;
+        if instruction.data.len() >= u16::MAX as usize {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let mut entry = Vec::with_capacity(1 + instruction.data.len());
+        entry.push(account_count);
+        entry.extend_from_slice(&instruction.data);
+        entries.push(entry);
+        accounts.extend_from_slice(&instruction.accounts);
+    }
+    let data = TokenInstruction::Batch {
+        instructions: entries,
+    }
+    .pack();
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Swaps between two parties, A sending `amount_a` to B's destination and B
+/// sending `amount_b` to A's, both signing
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    token_program_id: &Pubkey,
+    source_a_pubkey: &Pubkey,
+    destination_a_pubkey: &Pubkey,
+    source_b_pubkey: &Pubkey,
+    destination_b_pubkey: &Pubkey,
+    authority_a_pubkey: &Pubkey,
+    authority_b_pubkey: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Swap { amount_a, amount_b }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*source_a_pubkey, false),
+        AccountMeta::new(*destination_a_pubkey, false),
+        AccountMeta::new(*source_b_pubkey, false),
+        AccountMeta::new(*destination_b_pubkey, false),
+        AccountMeta::new_readonly(*authority_a_pubkey, true),
+        AccountMeta::new_readonly(*authority_b_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::TransferChecked { amount, decimals }.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn approve(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Approve { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*delegate_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn approve_escrowed(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ApproveEscrowed { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*delegate_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn approve_checked(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ApproveChecked { amount, decimals }.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*delegate_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn revoke(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Revoke.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn revoke_all(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::RevokeAll.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_authority(
+    token_program_id: &Pubkey,
+    owned_pubkey: &Pubkey,
+    new_authority_pubkey: Option<&Pubkey>,
+    authority_type: AuthorityType,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let new_authority = new_authority_pubkey.cloned().into();
+    let data = TokenInstruction::SetAuthority {
+        authority_type,
+        new_authority,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*owned_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn mint_to(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::MintTo { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn burn(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Burn { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `BurnFrom` for the mint authority to burn from `account_pubkey`
+pub fn burn_from(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::BurnFrom { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn freeze_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::FreezeAccount.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*freeze_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `FreezeAccounts` freezing each of `account_pubkeys`
+pub fn freeze_accounts(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    account_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    toggle_freeze_accounts(
+        token_program_id,
+        mint_pubkey,
+        freeze_authority_pubkey,
+        signer_pubkeys,
+        account_pubkeys,
+        true,
+    )
+}
+
+/// Builds a `ThawAccounts` thawing each of `account_pubkeys`
+pub fn thaw_accounts(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    account_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    toggle_freeze_accounts(
+        token_program_id,
+        mint_pubkey,
+        freeze_authority_pubkey,
+        signer_pubkeys,
+        account_pubkeys,
+        false,
+    )
+}
+
+fn toggle_freeze_accounts(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    account_pubkeys: &[&Pubkey],
+    freeze: bool,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let count = u8::try_from(account_pubkeys.len())
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let data = if freeze {
+        TokenInstruction::FreezeAccounts { count }
+    } else {
+        TokenInstruction::ThawAccounts { count }
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + account_pubkeys.len() + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*freeze_authority_pubkey, signer_pubkeys.is_empty()));
+    for account_pubkey in account_pubkeys.iter() {
+        accounts.push(AccountMeta::new(**account_pubkey, false));
+    }
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn thaw_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ThawAccount.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*freeze_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn burn_checked(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::BurnChecked { amount, decimals }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn sync_native(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new(*account_pubkey, false)],
+        data: TokenInstruction::SyncNative.pack(),
+    })
+}
+
+pub fn get_account_data_size(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*mint_pubkey, false)],
+        data: TokenInstruction::GetAccountDataSize.pack(),
+    })
+}
+
+pub fn split_account(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    new_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::SplitAccount { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new(*new_account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn merge_accounts(
+    token_program_id: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    source_pubkeys: &[&Pubkey],
+    close_sources: bool,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    if source_pubkeys.is_empty() || source_pubkeys.len() > MAX_BATCH_SIZE {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = TokenInstruction::MergeAccounts {
+        num_source_accounts: source_pubkeys.len() as u8,
+        close_sources,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + source_pubkeys.len() + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for source_pubkey in source_pubkeys.iter() {
+        accounts.push(AccountMeta::new(**source_pubkey, false));
+    }
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn lock_until_slot(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    slot: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::LockUntilSlot { slot }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_spending_limit(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: COption<u64>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::SetSpendingLimit { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_vested(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    total_amount: u64,
+    start_slot: u64,
+    end_slot: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::TransferVested {
+        total_amount,
+        start_slot,
+        end_slot,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn claim_vested(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ClaimVested.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_decimals(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::UpdateDecimals { decimals }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_policy(
+    token_program_id: &Pubkey,
+    policy_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mode: PolicyMode,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializePolicy { mode }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*policy_pubkey, false),
+        AccountMeta::new(*mint_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn add_policy_entry(
+    token_program_id: &Pubkey,
+    policy_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    entry: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::AddPolicyEntry { entry: *entry }.pack();
+    policy_instruction(
+        token_program_id,
+        policy_pubkey,
+        mint_pubkey,
+        freeze_authority_pubkey,
+        signer_pubkeys,
+        data,
+    )
+}
+
+pub fn remove_policy_entry(
+    token_program_id: &Pubkey,
+    policy_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    entry: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::RemovePolicyEntry { entry: *entry }.pack();
+    policy_instruction(
+        token_program_id,
+        policy_pubkey,
+        mint_pubkey,
+        freeze_authority_pubkey,
+        signer_pubkeys,
+        data,
+    )
+}
+
+/// The policy entry instructions both take the policy, the mint and its
+/// freeze authority
+fn policy_instruction(
+    token_program_id: &Pubkey,
+    policy_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    data: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*policy_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*freeze_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_flash_mint(
+    token_program_id: &Pubkey,
+    config_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    fee: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeFlashMint { fee }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*config_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn flash_mint_to(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::FlashMintTo { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*config_pubkey, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn flash_burn_repay(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    fee_destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::FlashBurnRepay { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(5 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*config_pubkey, false));
+    accounts.push(AccountMeta::new(*fee_destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn get_account_balance(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*account_pubkey, false)],
+        data: TokenInstruction::GetAccountBalance.pack(),
+    })
+}
+
+pub fn get_mint_supply(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*mint_pubkey, false)],
+        data: TokenInstruction::GetMintSupply.pack(),
+    })
+}
+
+pub fn amount_to_ui_amount(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*mint_pubkey, false)],
+        data: TokenInstruction::AmountToUiAmount { amount }.pack(),
+    })
+}
+
+pub fn assert_balance(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    minimum: u64,
+    maximum: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*account_pubkey, false)],
+        data: TokenInstruction::AssertBalance { minimum, maximum }.pack(),
+    })
+}
+
+pub fn assert_supply(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    minimum: u64,
+    maximum: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*mint_pubkey, false)],
+        data: TokenInstruction::AssertSupply { minimum, maximum }.pack(),
+    })
+}
+
+pub fn ui_amount_to_amount(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    ui_amount: &str,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new_readonly(*mint_pubkey, false)],
+        data: TokenInstruction::UiAmountToAmount { ui_amount }.pack(),
+    })
+}
+
+pub fn close_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::CloseAccount.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn increase_delegated_amount(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::IncreaseDelegatedAmount { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn decrease_delegated_amount(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::DecreaseDelegatedAmount { amount }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn approve_with_expiry(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    expiry_slot: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ApproveWithExpiry {
+        amount,
+        expiry_slot,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*delegate_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `TransferBatch` paying each `(destination, amount)` pair
+pub fn transfer_batch(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    transfers: &[(&Pubkey, u64)],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    if transfers.is_empty() || transfers.len() > MAX_BATCH_SIZE {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = TokenInstruction::TransferBatch {
+        amounts: transfers.iter().map(|(_, amount)| *amount).collect(),
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + transfers.len() + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for (destination_pubkey, _) in transfers.iter() {
+        accounts.push(AccountMeta::new(**destination_pubkey, false));
+    }
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `MintToBatch` minting each `(destination, amount)` pair
+pub fn mint_to_batch(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    mints: &[(&Pubkey, u64)],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    if mints.is_empty() || mints.len() > MAX_BATCH_SIZE {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = TokenInstruction::MintToBatch {
+        amounts: mints.iter().map(|(_, amount)| *amount).collect(),
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + mints.len() + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for (destination_pubkey, _) in mints.iter() {
+        accounts.push(AccountMeta::new(**destination_pubkey, false));
+    }
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Builds a `ProposeOwner`; pass `None` to cancel a pending proposal
+pub fn propose_owner(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    new_owner_pubkey: Option<&Pubkey>,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ProposeOwner {
+        new_owner: new_owner_pubkey.cloned().unwrap_or_default(),
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn accept_ownership(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    pending_owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::AcceptOwnership.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *pending_owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_default_account_state(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    freeze_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    state: AccountState,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::SetDefaultAccountState { state }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*freeze_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn require_memo_transfers(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    enable: bool,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::RequireMemoTransfers { enable }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked_with_fee(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+    fee: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::TransferCheckedWithFee {
+        amount,
+        decimals,
+        fee,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_transfer_fee(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    transfer_fee_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    transfer_fee_basis_points: u16,
+    maximum_fee: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::SetTransferFee {
+        transfer_fee_basis_points,
+        maximum_fee,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *transfer_fee_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn withdraw_withheld_tokens_from_accounts(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    source_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let num_token_accounts =
+        u8::try_from(source_pubkeys.len()).map_err(|_| ProgramError::InvalidArgument)?;
+    let data = TokenInstruction::WithdrawWithheldTokensFromAccounts { num_token_accounts }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len() + source_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+    for source_pubkey in source_pubkeys.iter() {
+        accounts.push(AccountMeta::new(**source_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn harvest_withheld_tokens_to_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    source_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::HarvestWithheldTokensToMint.pack();
+
+    let mut accounts = Vec::with_capacity(1 + source_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    for source_pubkey in source_pubkeys.iter() {
+        accounts.push(AccountMeta::new(**source_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_rate_interest_bearing_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    rate_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    rate: i16,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::UpdateRateInterestBearingMint { rate }.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *rate_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn pause(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    pause_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Pause.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*pause_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn resume(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    pause_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Resume.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*pause_authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn reallocate(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::Reallocate.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn reallocate_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::ReallocateMint.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_immutable_owner(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeImmutableOwner.pack();
+
+    let accounts = vec![AccountMeta::new(*account_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn enable_cpi_guard(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::EnableCpiGuard.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn disable_cpi_guard(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::DisableCpiGuard.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_transfer_hook(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: Option<&Pubkey>,
+    hook_program_id: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeTransferHook {
+        authority: authority_pubkey.cloned().into(),
+        program_id: hook_program_id.cloned().into(),
+    }
+    .pack();
+
+    let accounts = vec![AccountMeta::new(*mint_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_transfer_hook(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    hook_program_id: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::UpdateTransferHook {
+        program_id: hook_program_id.cloned().into(),
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_metadata_pointer(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    metadata_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeMetadataPointer {
+        metadata_address: metadata_pubkey.cloned().into(),
+    }
+    .pack();
+
+    let accounts = vec![AccountMeta::new(*mint_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_token_metadata(
+    token_program_id: &Pubkey,
+    metadata_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    update_authority_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    name: &str,
+    symbol: &str,
+    uri: &str,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeTokenMetadata { name, symbol, uri }.pack();
+
+    let mut accounts = Vec::with_capacity(6 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*metadata_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*update_authority_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *mint_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_token_metadata_field(
+    token_program_id: &Pubkey,
+    metadata_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    update_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    field: MetadataField,
+    value: &str,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::UpdateTokenMetadataField { field, value }.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*metadata_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *update_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn initialize_associated_account(
+    token_program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::InitializeAssociatedAccount.pack();
+    let (associated_account_pubkey, _) = get_associated_token_address_with_program_id(
+        wallet_pubkey,
+        mint_pubkey,
+        token_program_id,
+    );
+
+    let accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new(associated_account_pubkey, false),
+        AccountMeta::new_readonly(*wallet_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn recover_nested(
+    token_program_id: &Pubkey,
+    nested_account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_account_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::RecoverNested.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*nested_account_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_account_pubkey, false));
+    accounts.push(AccountMeta::new(*wallet_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn wrap_sol(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    funding_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::WrapSol { lamports }.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*funding_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn unwrap_sol(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    let data = TokenInstruction::UnwrapSol { lamports }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*owner_pubkey, signer_pubkeys.is_empty()));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Borsh output is the packed instruction data, which the schema describes.
+/// There is no `BorshDeserialize` impl since the string instructions borrow
+/// from their input; [`TokenInstruction::try_from_slice`] decodes instead.
+#[cfg(feature = "borsh")]
+mod borsh_impls {
+    use super::*;
+    use borsh::{
+        io,
+        schema::{add_definition, Declaration, Definition, DiscriminantValue, Fields},
+        BorshSchema, BorshSerialize,
+    };
+    use std::collections::BTreeMap;
+
+    /// UTF-8 that takes the rest of the instruction data, with no length prefix
+    const REMAINING_STR: &str = "RemainingStr";
+    /// A one-byte count, then that many amounts
+    const BATCH_AMOUNTS: &str = "BatchAmounts";
+    /// A one-byte count, then that many entries of a `u16` length and the
+    /// inner instruction
+    const BATCH_ENTRIES: &str = "BatchEntries";
+    const BATCH_ENTRY: &str = "BatchEntry";
+
+    impl<'a> TokenInstruction<'a> {
+        /// Decodes packed instruction data, the inverse of the borsh output.
+        /// The data must hold exactly one instruction.
+        pub fn try_from_slice(data: &'a [u8]) -> io::Result<Self> {
+            Self::unpack(data)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+        }
+    }
+
+    impl BorshSerialize for TokenInstruction<'_> {
+        fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+            writer.write_all(&self.pack())
+        }
+    }
+
+    fn named(fields: &[(&str, Declaration)]) -> Fields {
+        if fields.is_empty() {
+            return Fields::Empty;
+        }
+        Fields::NamedFields(
+            fields
+                .iter()
+                .map(|(name, declaration)| (name.to_string(), declaration.clone()))
+                .collect(),
+        )
+    }
+
+    fn mint_fields() -> Fields {
+        named(&[
+            ("decimals", u8::declaration()),
+            ("mint_authority", Pubkey::declaration()),
+            ("freeze_authority", Option::<Pubkey>::declaration()),
+            ("max_supply", Option::<u64>::declaration()),
+            ("permanent_delegate", Option::<Pubkey>::declaration()),
+            ("transfer_fee_authority", Option::<Pubkey>::declaration()),
+            ("rate_authority", Option::<Pubkey>::declaration()),
+            ("pause_authority", Option::<Pubkey>::declaration()),
+        ])
+    }
+
+    impl BorshSchema for TokenInstruction<'_> {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            let amount = || named(&[("amount", u64::declaration())]);
+            let amount_decimals =
+                || named(&[("amount", u64::declaration()), ("decimals", u8::declaration())]);
+            let owner = || named(&[("owner", Pubkey::declaration())]);
+            let amounts = || named(&[("amounts", BATCH_AMOUNTS.to_string())]);
+            let entry = || named(&[("entry", Pubkey::declaration())]);
+            let lamports = || named(&[("lamports", u64::declaration())]);
+            let range = || named(&[("minimum", u64::declaration()), ("maximum", u64::declaration())]);
+            let count = || named(&[("count", u8::declaration())]);
+            let variants = vec![
+                (INITIALIZE_MINT_TAG, "InitializeMint", mint_fields()),
+                (INITIALIZE_ACCOUNT_TAG, "InitializeAccount", Fields::Empty),
+                (TRANSFER_TAG, "Transfer", amount()),
+                (APPROVE_TAG, "Approve", amount()),
+                (MINT_TO_TAG, "MintTo", amount()),
+                (BURN_TAG, "Burn", amount()),
+                (REVOKE_TAG, "Revoke", Fields::Empty),
+                (
+                    SET_AUTHORITY_TAG,
+                    "SetAuthority",
+                    named(&[
+                        ("authority_type", AuthorityType::declaration()),
+                        ("new_authority", Option::<Pubkey>::declaration()),
+                    ]),
+                ),
+                (FREEZE_ACCOUNT_TAG, "FreezeAccount", Fields::Empty),
+                (THAW_ACCOUNT_TAG, "ThawAccount", Fields::Empty),
+                (TRANSFER_CHECKED_TAG, "TransferChecked", amount_decimals()),
+                (APPROVE_CHECKED_TAG, "ApproveChecked", amount_decimals()),
+                (BURN_CHECKED_TAG, "BurnChecked", amount_decimals()),
+                (
+                    INITIALIZE_MULTISIG_TAG,
+                    "InitializeMultisig",
+                    named(&[("m", u8::declaration())]),
+                ),
+                (INITIALIZE_MINT2_TAG, "InitializeMint2", mint_fields()),
+                (INITIALIZE_ACCOUNT2_TAG, "InitializeAccount2", owner()),
+                (INITIALIZE_ACCOUNT3_TAG, "InitializeAccount3", owner()),
+                (SYNC_NATIVE_TAG, "SyncNative", Fields::Empty),
+                (GET_ACCOUNT_DATA_SIZE_TAG, "GetAccountDataSize", Fields::Empty),
+                (AMOUNT_TO_UI_AMOUNT_TAG, "AmountToUiAmount", amount()),
+                (
+                    UI_AMOUNT_TO_AMOUNT_TAG,
+                    "UiAmountToAmount",
+                    named(&[("ui_amount", REMAINING_STR.to_string())]),
+                ),
+                (CLOSE_ACCOUNT_TAG, "CloseAccount", Fields::Empty),
+                (INCREASE_DELEGATED_AMOUNT_TAG, "IncreaseDelegatedAmount", amount()),
+                (DECREASE_DELEGATED_AMOUNT_TAG, "DecreaseDelegatedAmount", amount()),
+                (
+                    APPROVE_WITH_EXPIRY_TAG,
+                    "ApproveWithExpiry",
+                    named(&[("amount", u64::declaration()), ("expiry_slot", u64::declaration())]),
+                ),
+                (TRANSFER_BATCH_TAG, "TransferBatch", amounts()),
+                (MINT_TO_BATCH_TAG, "MintToBatch", amounts()),
+                (
+                    PROPOSE_OWNER_TAG,
+                    "ProposeOwner",
+                    named(&[("new_owner", Pubkey::declaration())]),
+                ),
+                (ACCEPT_OWNERSHIP_TAG, "AcceptOwnership", Fields::Empty),
+                (
+                    SET_DEFAULT_ACCOUNT_STATE_TAG,
+                    "SetDefaultAccountState",
+                    named(&[("state", AccountState::declaration())]),
+                ),
+                (
+                    REQUIRE_MEMO_TRANSFERS_TAG,
+                    "RequireMemoTransfers",
+                    named(&[("enable", bool::declaration())]),
+                ),
+                (
+                    TRANSFER_CHECKED_WITH_FEE_TAG,
+                    "TransferCheckedWithFee",
+                    named(&[
+                        ("amount", u64::declaration()),
+                        ("decimals", u8::declaration()),
+                        ("fee", u64::declaration()),
+                    ]),
+                ),
+                (
+                    SET_TRANSFER_FEE_TAG,
+                    "SetTransferFee",
+                    named(&[
+                        ("transfer_fee_basis_points", u16::declaration()),
+                        ("maximum_fee", u64::declaration()),
+                    ]),
+                ),
+                (
+                    WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG,
+                    "WithdrawWithheldTokensFromAccounts",
+                    named(&[("num_token_accounts", u8::declaration())]),
+                ),
+                (
+                    HARVEST_WITHHELD_TOKENS_TO_MINT_TAG,
+                    "HarvestWithheldTokensToMint",
+                    Fields::Empty,
+                ),
+                (
+                    UPDATE_RATE_INTEREST_BEARING_MINT_TAG,
+                    "UpdateRateInterestBearingMint",
+                    named(&[("rate", i16::declaration())]),
+                ),
+                (PAUSE_TAG, "Pause", Fields::Empty),
+                (RESUME_TAG, "Resume", Fields::Empty),
+                (REALLOCATE_TAG, "Reallocate", Fields::Empty),
+                (REALLOCATE_MINT_TAG, "ReallocateMint", Fields::Empty),
+                (INITIALIZE_IMMUTABLE_OWNER_TAG, "InitializeImmutableOwner", Fields::Empty),
+                (ENABLE_CPI_GUARD_TAG, "EnableCpiGuard", Fields::Empty),
+                (DISABLE_CPI_GUARD_TAG, "DisableCpiGuard", Fields::Empty),
+                (
+                    INITIALIZE_TRANSFER_HOOK_TAG,
+                    "InitializeTransferHook",
+                    named(&[
+                        ("authority", Option::<Pubkey>::declaration()),
+                        ("program_id", Option::<Pubkey>::declaration()),
+                    ]),
+                ),
+                (
+                    UPDATE_TRANSFER_HOOK_TAG,
+                    "UpdateTransferHook",
+                    named(&[("program_id", Option::<Pubkey>::declaration())]),
+                ),
+                (
+                    INITIALIZE_METADATA_POINTER_TAG,
+                    "InitializeMetadataPointer",
+                    named(&[("metadata_address", Option::<Pubkey>::declaration())]),
+                ),
+                (
+                    INITIALIZE_TOKEN_METADATA_TAG,
+                    "InitializeTokenMetadata",
+                    named(&[
+                        ("name", String::declaration()),
+                        ("symbol", String::declaration()),
+                        ("uri", String::declaration()),
+                    ]),
+                ),
+                (
+                    UPDATE_TOKEN_METADATA_FIELD_TAG,
+                    "UpdateTokenMetadataField",
+                    named(&[
+                        ("field", MetadataField::declaration()),
+                        ("value", String::declaration()),
+                    ]),
+                ),
+                (
+                    INITIALIZE_ASSOCIATED_ACCOUNT_TAG,
+                    "InitializeAssociatedAccount",
+                    Fields::Empty,
+                ),
+                (RECOVER_NESTED_TAG, "RecoverNested", Fields::Empty),
+                (WRAP_SOL_TAG, "WrapSol", lamports()),
+                (UNWRAP_SOL_TAG, "UnwrapSol", lamports()),
+                (REVOKE_ALL_TAG, "RevokeAll", Fields::Empty),
+                (ASSERT_BALANCE_TAG, "AssertBalance", range()),
+                (ASSERT_SUPPLY_TAG, "AssertSupply", range()),
+                (GET_ACCOUNT_BALANCE_TAG, "GetAccountBalance", Fields::Empty),
+                (GET_MINT_SUPPLY_TAG, "GetMintSupply", Fields::Empty),
+                (SPLIT_ACCOUNT_TAG, "SplitAccount", amount()),
+                (
+                    MERGE_ACCOUNTS_TAG,
+                    "MergeAccounts",
+                    named(&[
+                        ("num_source_accounts", u8::declaration()),
+                        ("close_sources", bool::declaration()),
+                    ]),
+                ),
+                (
+                    LOCK_UNTIL_SLOT_TAG,
+                    "LockUntilSlot",
+                    named(&[("slot", u64::declaration())]),
+                ),
+                (
+                    INITIALIZE_POLICY_TAG,
+                    "InitializePolicy",
+                    named(&[("mode", PolicyMode::declaration())]),
+                ),
+                (ADD_POLICY_ENTRY_TAG, "AddPolicyEntry", entry()),
+                (REMOVE_POLICY_ENTRY_TAG, "RemovePolicyEntry", entry()),
+                (APPROVE_ESCROWED_TAG, "ApproveEscrowed", amount()),
+                (
+                    UPDATE_DECIMALS_TAG,
+                    "UpdateDecimals",
+                    named(&[("decimals", u8::declaration())]),
+                ),
+                (
+                    INITIALIZE_FLASH_MINT_TAG,
+                    "InitializeFlashMint",
+                    named(&[("fee", u64::declaration())]),
+                ),
+                (FLASH_MINT_TO_TAG, "FlashMintTo", amount()),
+                (FLASH_BURN_REPAY_TAG, "FlashBurnRepay", amount()),
+                (
+                    SET_SPENDING_LIMIT_TAG,
+                    "SetSpendingLimit",
+                    named(&[("amount", Option::<u64>::declaration())]),
+                ),
+                (
+                    TRANSFER_VESTED_TAG,
+                    "TransferVested",
+                    named(&[
+                        ("total_amount", u64::declaration()),
+                        ("start_slot", u64::declaration()),
+                        ("end_slot", u64::declaration()),
+                    ]),
+                ),
+                (CLAIM_VESTED_TAG, "ClaimVested", Fields::Empty),
+                (
+                    SWAP_TAG,
+                    "Swap",
+                    named(&[("amount_a", u64::declaration()), ("amount_b", u64::declaration())]),
+                ),
+                (
+                    BATCH_TAG,
+                    "Batch",
+                    named(&[("instructions", BATCH_ENTRIES.to_string())]),
+                ),
+                (FREEZE_ACCOUNTS_TAG, "FreezeAccounts", count()),
+                (THAW_ACCOUNTS_TAG, "ThawAccounts", count()),
+                (BURN_FROM_TAG, "BurnFrom", amount()),
+            ];
+            let variants = variants
+                .into_iter()
+                .map(|(tag, name, fields)| {
+                    let declaration = format!("TokenInstruction{}", name);
+                    add_definition(declaration.clone(), Definition::Struct { fields }, definitions);
+                    (DiscriminantValue::from(tag), name.to_string(), declaration)
+                })
+                .collect();
+            add_definition(
+                Self::declaration(),
+                Definition::Enum {
+                    tag_width: 1,
+                    variants,
+                },
+                definitions,
+            );
+            add_definition(
+                REMAINING_STR.to_string(),
+                Definition::Sequence {
+                    length_width: 0,
+                    length_range: Definition::DEFAULT_LENGTH_RANGE,
+                    elements: u8::declaration(),
+                },
+                definitions,
+            );
+            add_definition(
+                BATCH_AMOUNTS.to_string(),
+                Definition::Sequence {
+                    length_width: 1,
+                    length_range: 1..=MAX_BATCH_SIZE as u64,
+                    elements: u64::declaration(),
+                },
+                definitions,
+            );
+            add_definition(
+                BATCH_ENTRIES.to_string(),
+                Definition::Sequence {
+                    length_width: 1,
+                    length_range: 1..=MAX_BATCH_SIZE as u64,
+                    elements: BATCH_ENTRY.to_string(),
+                },
+                definitions,
+            );
+            // An inner instruction is its account count, its tag and its data
+            add_definition(
+                BATCH_ENTRY.to_string(),
+                Definition::Sequence {
+                    length_width: 2,
+                    length_range: 2..=u16::MAX as u64,
+                    elements: u8::declaration(),
+                },
+                definitions,
+            );
+            u8::add_definitions_recursively(definitions);
+            u16::add_definitions_recursively(definitions);
+            i16::add_definitions_recursively(definitions);
+            u64::add_definitions_recursively(definitions);
+            bool::add_definitions_recursively(definitions);
+            String::add_definitions_recursively(definitions);
+            Pubkey::add_definitions_recursively(definitions);
+            Option::<Pubkey>::add_definitions_recursively(definitions);
+            Option::<u64>::add_definitions_recursively(definitions);
+            AuthorityType::add_definitions_recursively(definitions);
+            AccountState::add_definitions_recursively(definitions);
+            MetadataField::add_definitions_recursively(definitions);
+            PolicyMode::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            "TokenInstruction".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mint1() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, mint);
+    }
+
+    #[test]
+    fn test_mint2() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([2u8; 32]),
+            freeze_authority: COption::Some(Pubkey::new_from_array([3u8; 32])),
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[2u8; 32]);
+        packed.push(1);
+        packed.extend_from_slice(&[3u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, mint);
+    }
+
+    #[test]
+    fn test_mint_without_freeze_authority_section() {
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(
+            unpacked,
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_mint_with_max_supply() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::Some(1_000),
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(1);
+        packed.extend_from_slice(&1_000u64.to_le_bytes());
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), mint);
+
+        // packed before supply caps
+        let unpacked = TokenInstruction::unpack(&packed[..35]).unwrap();
+        assert_eq!(
+            unpacked,
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        // an unknown option tag is rejected
+        packed[35] = 2;
+        assert_eq!(
+            TokenInstruction::unpack(&packed),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let instruction = initialize_mint_with_max_supply(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_from_array([1u8; 32]),
+            None,
+            2,
+            Some(1_000),
+        )
+        .unwrap();
+        packed[35] = 1;
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_mint_with_permanent_delegate() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::Some(Pubkey::new_from_array([4u8; 32])),
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(1);
+        packed.extend_from_slice(&[4u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), mint);
+
+        // packed before permanent delegates
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..36]).unwrap(),
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        let instruction = initialize_mint_with_permanent_delegate(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_from_array([1u8; 32]),
+            None,
+            &Pubkey::new_from_array([4u8; 32]),
+            2,
+        )
+        .unwrap();
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_mint_with_transfer_fee_authority() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::Some(Pubkey::new_from_array([5u8; 32])),
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(1);
+        packed.extend_from_slice(&[5u8; 32]);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), mint);
+
+        // packed before transfer fees
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..37]).unwrap(),
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        let instruction = initialize_mint_with_transfer_fee_authority(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_from_array([1u8; 32]),
+            None,
+            &Pubkey::new_from_array([5u8; 32]),
+            2,
+        )
+        .unwrap();
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_mint_with_rate_authority() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::Some(Pubkey::new_from_array([6u8; 32])),
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(1);
+        packed.extend_from_slice(&[6u8; 32]);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), mint);
+
+        // packed before interest-bearing mints
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..38]).unwrap(),
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        let instruction = initialize_mint_with_rate_authority(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_from_array([1u8; 32]),
+            None,
+            &Pubkey::new_from_array([6u8; 32]),
+            2,
+        )
+        .unwrap();
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_update_rate_interest_bearing_mint() {
+        let check = TokenInstruction::UpdateRateInterestBearingMint { rate: 500 };
+        let packed = Vec::from([35u8, 0xf4, 0x01]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // negative rates are two's complement
+        let check = TokenInstruction::UpdateRateInterestBearingMint { rate: -500 };
+        let packed = Vec::from([35u8, 0x0c, 0xfe]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction =
+            update_rate_interest_bearing_mint(&program_id, &mint, &multisig, &[&signer], -500)
+                .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_mint_with_pause_authority() {
+        let mint = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::Some(Pubkey::new_from_array([7u8; 32])),
+        };
+
+        let mut packed = Vec::from([0u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(1);
+        packed.extend_from_slice(&[7u8; 32]);
+
+        assert_eq!(mint.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), mint);
+
+        // packed before pausable mints
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..39]).unwrap(),
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        let instruction = initialize_mint_with_pause_authority(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_from_array([1u8; 32]),
+            None,
+            &Pubkey::new_from_array([7u8; 32]),
+            2,
+        )
+        .unwrap();
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_init_account1() {
+        let init_account = TokenInstruction::InitializeAccount;
+
+        let packed = Vec::from([1u8]);
+
+        assert_eq!(init_account.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+
+        assert_eq!(unpacked, init_account);
+    }
+
+    #[test]
+    fn test_revoke() {
+        let revoke = TokenInstruction::Revoke;
+
+        let packed = Vec::from([6u8]);
+
+        assert_eq!(revoke.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+
+        assert_eq!(unpacked, revoke);
+    }
+
+    #[test]
+    fn test_revoke_all() {
+        let check = TokenInstruction::RevokeAll;
+        let packed = check.pack();
+        assert_eq!(packed, [52u8]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_assert_balance_and_supply() {
+        let check = TokenInstruction::AssertBalance {
+            minimum: 1,
+            maximum: u64::MAX,
+        };
+        let packed = check.pack();
+        let mut expect = vec![53u8, 1, 0, 0, 0, 0, 0, 0, 0];
+        expect.extend_from_slice(&[255; 8]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        let check = TokenInstruction::AssertSupply {
+            minimum: 0,
+            maximum: 2,
+        };
+        let packed = check.pack();
+        assert_eq!(
+            packed,
+            [54u8, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..9]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        // read-only, so the runtime can run them alongside other readers
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let instruction = assert_balance(&program_id, &key, 0, 1).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new_readonly(key, false)]);
+        let instruction = assert_supply(&program_id, &key, 0, 1).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new_readonly(key, false)]);
+    }
+
+    #[test]
+    fn test_set_authority() {
+        let set_authority = TokenInstruction::SetAuthority {
+            authority_type: AuthorityType::MintTokens,
+            new_authority: COption::Some(Pubkey::new_from_array([4u8; 32])),
+        };
+
+        let mut packed = Vec::from([7u8, 0, 1]);
+        packed.extend_from_slice(&[4u8; 32]);
+
+        assert_eq!(set_authority.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, set_authority);
+
+        let set_authority = TokenInstruction::SetAuthority {
+            authority_type: AuthorityType::AccountOwner,
+            new_authority: COption::None,
+        };
+
+        let packed = Vec::from([7u8, 1, 0]);
+
+        assert_eq!(set_authority.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, set_authority);
+
+        let set_authority = TokenInstruction::SetAuthority {
+            authority_type: AuthorityType::CloseAccount,
+            new_authority: COption::None,
+        };
+        let packed = Vec::from([7u8, 2, 0]);
+        assert_eq!(set_authority.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), set_authority);
+
+        let set_authority = TokenInstruction::SetAuthority {
+            authority_type: AuthorityType::FreezeAccount,
+            new_authority: COption::Some(Pubkey::new_from_array([4u8; 32])),
+        };
+        let mut packed = Vec::from([7u8, 3, 1]);
+        packed.extend_from_slice(&[4u8; 32]);
+        assert_eq!(set_authority.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), set_authority);
+
+        // an unknown authority type is named as such
+        for authority_type in [4u8, 0xff] {
+            assert_eq!(
+                TokenInstruction::unpack(&[7u8, authority_type, 0]),
+                Err(TokenError::AuthorityTypeNotSupported.into())
+            );
+        }
+        assert_eq!(
+            TokenInstruction::unpack(&[7u8, 0, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_freeze_thaw() {
+        let freeze = TokenInstruction::FreezeAccount;
+        let packed = Vec::from([8u8]);
+        assert_eq!(freeze.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), freeze);
+
+        let thaw = TokenInstruction::ThawAccount;
+        let packed = Vec::from([9u8]);
+        assert_eq!(thaw.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), thaw);
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let pause = TokenInstruction::Pause;
+        let packed = Vec::from([36u8]);
+        assert_eq!(pause.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), pause);
+
+        let resume = TokenInstruction::Resume;
+        let packed = Vec::from([37u8]);
+        assert_eq!(resume.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), resume);
+
+        // neither takes a payload
+        assert_eq!(
+            TokenInstruction::unpack(&[36u8, 0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&[37u8, 0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_reallocate() {
+        let check = TokenInstruction::Reallocate;
+        let packed = Vec::from([38u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let account = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction =
+            reallocate(&program_id, &account, &payer, &multisig, &[&signer]).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_reallocate_mint() {
+        let check = TokenInstruction::ReallocateMint;
+        let packed = Vec::from([39u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&[39u8, 0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let instruction = reallocate_mint(&program_id, &mint, &payer).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ]
+        );
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_initialize_immutable_owner() {
+        let check = TokenInstruction::InitializeImmutableOwner;
+        let packed = Vec::from([40u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let account = Pubkey::new_unique();
+        let instruction = initialize_immutable_owner(&program_id, &account).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new(account, false)]);
+        assert_eq!(instruction.data, packed);
+    }
+
+    #[test]
+    fn test_cpi_guard() {
+        let enable = TokenInstruction::EnableCpiGuard;
+        let packed = Vec::from([41u8]);
+        assert_eq!(enable.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), enable);
+
+        let disable = TokenInstruction::DisableCpiGuard;
+        let packed = Vec::from([42u8]);
+        assert_eq!(disable.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), disable);
+
+        let program_id = crate::id();
+        let account = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction = enable_cpi_guard(&program_id, &account, &multisig, &[&signer]).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+        assert_eq!(instruction.data, [41u8]);
+        let owner = Pubkey::new_unique();
+        let instruction = disable_cpi_guard(&program_id, &account, &owner, &[]).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+        assert_eq!(instruction.data, [42u8]);
+    }
+
+    #[test]
+    fn test_transfer_hook() {
+        let check = TokenInstruction::InitializeTransferHook {
+            authority: COption::Some(Pubkey::new_from_array([1u8; 32])),
+            program_id: COption::None,
+        };
+        let mut packed = Vec::from([43u8, 1]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(0);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        // both options are required
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..34]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let check = TokenInstruction::UpdateTransferHook {
+            program_id: COption::Some(Pubkey::new_from_array([2u8; 32])),
+        };
+        let mut packed = Vec::from([44u8, 1]);
+        packed.extend_from_slice(&[2u8; 32]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&[44u8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let hook = Pubkey::new_unique();
+        let instruction =
+            initialize_transfer_hook(&program_id, &mint, Some(&authority), Some(&hook)).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new(mint, false)]);
+        assert_eq!(
+            TokenInstruction::unpack(&instruction.data).unwrap(),
+            TokenInstruction::InitializeTransferHook {
+                authority: COption::Some(authority),
+                program_id: COption::Some(hook),
+            }
+        );
+        let instruction = update_transfer_hook(&program_id, &mint, &authority, &[], None).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(authority, true),
+            ]
+        );
+        assert_eq!(instruction.data, [44u8, 0]);
+    }
+
+    #[test]
+    fn test_token_metadata() {
+        let check = TokenInstruction::InitializeMetadataPointer {
+            metadata_address: COption::Some(Pubkey::new_from_array([1u8; 32])),
+        };
+        let mut packed = Vec::from([45u8, 1]);
+        packed.extend_from_slice(&[1u8; 32]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::InitializeTokenMetadata {
+            name: "ab",
+            symbol: "",
+            uri: "c",
+        };
+        let packed = [46u8, 2, 0, 0, 0, b'a', b'b', 0, 0, 0, 0, 1, 0, 0, 0, b'c'];
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        // every string is required, and must fit its length prefix
+        for len in [11, packed.len() - 1] {
+            assert_eq!(
+                TokenInstruction::unpack(&packed[..len]),
+                Err(TokenError::InvalidInstruction.into())
+            );
+        }
+
+        let check = TokenInstruction::UpdateTokenMetadataField {
+            field: MetadataField::Uri,
+            value: "d",
+        };
+        let packed = [47u8, 2, 1, 0, 0, 0, b'd'];
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        // unknown fields and invalid UTF-8 are rejected
+        for packed in [[47u8, 3, 1, 0, 0, 0, b'd'], [47u8, 2, 1, 0, 0, 0, 0xff]] {
+            assert_eq!(
+                TokenInstruction::unpack(&packed),
+                Err(TokenError::InvalidInstruction.into())
+            );
+        }
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let metadata = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let instruction = initialize_metadata_pointer(&program_id, &mint, Some(&metadata)).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new(mint, false)]);
+        assert_eq!(
+            TokenInstruction::unpack(&instruction.data).unwrap(),
+            TokenInstruction::InitializeMetadataPointer {
+                metadata_address: COption::Some(metadata),
+            }
+        );
+        let instruction = initialize_token_metadata(
+            &program_id,
+            &metadata,
+            &payer,
+            &mint,
+            &authority,
+            &authority,
+            &[],
+            "ab",
+            "",
+            "c",
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(metadata, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(authority, false),
+                AccountMeta::new_readonly(authority, true),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            [46u8, 2, 0, 0, 0, b'a', b'b', 0, 0, 0, 0, 1, 0, 0, 0, b'c']
+        );
+        let instruction = update_token_metadata_field(
+            &program_id,
+            &metadata,
+            &payer,
+            &authority,
+            &[],
+            MetadataField::Uri,
+            "d",
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(metadata, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(authority, true),
+            ]
+        );
+        assert_eq!(instruction.data, [47u8, 2, 1, 0, 0, 0, b'd']);
+    }
+
+    #[test]
+    fn test_initialize_associated_account() {
+        let check = TokenInstruction::InitializeAssociatedAccount;
+        let packed = check.pack();
+        assert_eq!(packed, [48u8]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let payer = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let instruction = initialize_associated_account(&program_id, &payer, &wallet, &mint).unwrap();
+        let (associated_account, _) =
+            crate::associated_account::get_associated_token_address(&wallet, &mint);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(associated_account, false),
+                AccountMeta::new_readonly(wallet, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ]
+        );
+        assert_eq!(instruction.data, [48u8]);
+    }
+
+    #[test]
+    fn test_recover_nested() {
+        let check = TokenInstruction::RecoverNested;
+        let packed = check.pack();
+        assert_eq!(packed, [49u8]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let nested = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let owner_account = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let instruction =
+            recover_nested(&program_id, &nested, &destination, &owner_account, &wallet, &[])
+                .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(nested, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner_account, false),
+                AccountMeta::new(wallet, true),
+            ]
+        );
+        assert_eq!(instruction.data, [49u8]);
+    }
+
+    #[test]
+    fn test_wrap_unwrap_sol() {
+        let check = TokenInstruction::WrapSol { lamports: 1 };
+        let packed = check.pack();
+        assert_eq!(packed, [50u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        let check = TokenInstruction::UnwrapSol { lamports: 2 };
+        let packed = check.pack();
+        assert_eq!(packed, [51u8, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let account = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let instruction = wrap_sol(&program_id, &account, &other, &owner, &[], 1).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new(other, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+        let instruction = unwrap_sol(&program_id, &account, &other, &owner, &[], 2).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new(other, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+        assert_eq!(instruction.data, [51u8, 2, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_split_account() {
+        let check = TokenInstruction::SplitAccount { amount: 3 };
+        let packed = check.pack();
+        assert_eq!(packed, [57u8, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let source = Pubkey::new_unique();
+        let new_account = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let instruction =
+            split_account(&program_id, &source, &new_account, &mint, &owner, &[], 3).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(new_account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_accounts() {
+        let check = TokenInstruction::MergeAccounts {
+            num_source_accounts: 2,
+            close_sources: true,
+        };
+        let packed = check.pack();
+        assert_eq!(packed, [58u8, 2, 1]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // the count stays within 1..=MAX_BATCH_SIZE, and the flag is a bool
+        assert_eq!(
+            TokenInstruction::unpack(&[58u8, 0, 1]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&[58u8, MAX_BATCH_SIZE as u8 + 1, 1]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&[58u8, 2, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let destination = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction =
+            merge_accounts(&program_id, &destination, &owner, &[&signer], &[&source], false)
+                .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, false),
+                AccountMeta::new(source, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+        assert_eq!(
+            merge_accounts(&program_id, &destination, &owner, &[], &[], false),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            merge_accounts(
+                &program_id,
+                &destination,
+                &owner,
+                &[],
+                &[&source; MAX_BATCH_SIZE + 1],
+                false,
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_lock_until_slot() {
+        let check = TokenInstruction::LockUntilSlot { slot: 300 };
+        let packed = check.pack();
+        assert_eq!(packed, [59u8, 44, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_policy_instructions() {
+        let check = TokenInstruction::InitializePolicy {
+            mode: PolicyMode::Blocklist,
+        };
+        let packed = check.pack();
+        assert_eq!(packed, [60u8, 1]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&[60, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let entry = Pubkey::new_from_array([3; 32]);
+        let check = TokenInstruction::AddPolicyEntry { entry };
+        let packed = check.pack();
+        let mut expect = vec![61u8];
+        expect.extend_from_slice(&[3; 32]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::RemovePolicyEntry { entry };
+        let packed = check.pack();
+        expect[0] = 62;
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..32]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let policy = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let instruction =
+            initialize_policy(&program_id, &policy, &mint, PolicyMode::Allowlist).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(policy, false),
+                AccountMeta::new(mint, false),
+            ]
+        );
+        let freeze_authority = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction =
+            add_policy_entry(&program_id, &policy, &mint, &freeze_authority, &[&signer], &entry)
+                .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(policy, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(freeze_authority, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_decimals() {
+        let check = TokenInstruction::UpdateDecimals { decimals: 6 };
+        let packed = check.pack();
+        assert_eq!(packed, [64u8, 6]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..1]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_flash_mint_instructions() {
+        let check = TokenInstruction::InitializeFlashMint { fee: 2 };
+        let packed = check.pack();
+        assert_eq!(packed, [65u8, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::FlashMintTo { amount: 3 };
+        let packed = check.pack();
+        assert_eq!(packed, [66u8, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::FlashBurnRepay { amount: 3 };
+        let packed = check.pack();
+        assert_eq!(packed, [67u8, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let config = Pubkey::new_unique();
+        let instruction = flash_mint_to(&program_id, &mint, &destination, &config, 3).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new(config, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_spending_limit() {
+        let check = TokenInstruction::SetSpendingLimit {
+            amount: COption::Some(5),
+        };
+        let packed = check.pack();
+        assert_eq!(packed, [68u8, 1, 5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::SetSpendingLimit {
+            amount: COption::None,
+        };
+        let packed = check.pack();
+        assert_eq!(packed, [68u8, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_vesting_instructions() {
+        let check = TokenInstruction::TransferVested {
+            total_amount: 1,
+            start_slot: 2,
+            end_slot: 3,
+        };
+        let packed = check.pack();
+        let mut expect = vec![69u8];
+        expect.extend_from_slice(&1u64.to_le_bytes());
+        expect.extend_from_slice(&2u64.to_le_bytes());
+        expect.extend_from_slice(&3u64.to_le_bytes());
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..24]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let check = TokenInstruction::ClaimVested;
+        let packed = check.pack();
+        assert_eq!(packed, [70u8]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_swap() {
+        let check = TokenInstruction::Swap {
+            amount_a: 1,
+            amount_b: 2,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([71u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..9]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let check = TokenInstruction::Batch {
+            instructions: vec![vec![3, 9], vec![2, 3, 5, 0, 0, 0, 0, 0, 0, 0]],
+        };
+        let packed = check.pack();
+        let mut expect = Vec::from([72u8, 2, 2, 0, 3, 9, 10, 0, 2, 3]);
+        expect.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // the count must be 1 to `MAX_BATCH_SIZE` and every entry carry an
+        // account count and a tag
+        let mut too_many = vec![72u8, MAX_BATCH_SIZE as u8 + 1];
+        for _ in 0..=MAX_BATCH_SIZE {
+            too_many.extend_from_slice(&[2, 0, 3, 9]);
+        }
+        for data in [&[72u8, 0][..], &too_many, &[72, 1, 1, 0, 3], &[72, 1, 0, 0]] {
+            assert_eq!(
+                TokenInstruction::unpack(data),
+                Err(TokenError::InvalidInstruction.into())
+            );
+        }
+
+        let program_id = crate::id();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let inner = [
+            transfer(&program_id, &source, &destination, &owner, &[], 5).unwrap(),
+            revoke(&program_id, &source, &owner, &[]).unwrap(),
+        ];
+        let ix = batch(&program_id, &inner).unwrap();
+        assert_eq!(
+            ix.accounts,
+            [inner[0].accounts.clone(), inner[1].accounts.clone()].concat()
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&ix.data).unwrap(),
+            TokenInstruction::Batch {
+                instructions: vec![
+                    [&[3u8][..], &inner[0].data].concat(),
+                    [&[2u8][..], &inner[1].data].concat(),
+                ],
+            }
+        );
+
+        assert_eq!(batch(&program_id, &[]), Err(ProgramError::InvalidArgument));
+        let mut foreign = inner[1].clone();
+        foreign.program_id = Pubkey::new_unique();
+        assert_eq!(
+            batch(&program_id, &[inner[0].clone(), foreign]),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_freeze_accounts() {
+        let check = TokenInstruction::FreezeAccounts { count: 25 };
+        let packed = check.pack();
+        assert_eq!(packed, [73u8, 25]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        let check = TokenInstruction::ThawAccounts { count: 1 };
+        let packed = check.pack();
+        assert_eq!(packed, [74u8, 1]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        for data in [[73u8, 0], [74, 0]] {
+            assert_eq!(
+                TokenInstruction::unpack(&data),
+                Err(TokenError::InvalidInstruction.into())
+            );
+        }
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let ix = thaw_accounts(&program_id, &mint, &authority, &[&signer], &[&account]).unwrap();
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(authority, false),
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(signer, true),
+            ]
+        );
+        assert_eq!(ix.data, [74, 1]);
+        let accounts = vec![&account; 256];
+        for account_pubkeys in [&[][..], &accounts] {
+            assert_eq!(
+                freeze_accounts(&program_id, &mint, &authority, &[], account_pubkeys),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_burn_from() {
+        let check = TokenInstruction::BurnFrom { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([75u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    /// An instruction of each variant, with its tag
+    fn instruction_per_tag() -> Vec<(TokenInstruction<'static>, u8)> {
+        let key = Pubkey::new_from_array([1u8; 32]);
+        let mint = |decimals| TokenInstruction::InitializeMint {
+            decimals,
+            mint_authority: key,
+            freeze_authority: COption::Some(key),
+            max_supply: COption::Some(3),
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::Some(key),
+            rate_authority: COption::None,
+            pause_authority: COption::Some(key),
+        };
+        let mint2 = TokenInstruction::InitializeMint2 {
+            decimals: 2,
+            mint_authority: key,
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::Some(key),
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::Some(key),
+            pause_authority: COption::None,
+        };
+        vec![
+            (mint(2), INITIALIZE_MINT_TAG),
+            (TokenInstruction::InitializeAccount, INITIALIZE_ACCOUNT_TAG),
+            (TokenInstruction::Transfer { amount: 1 }, TRANSFER_TAG),
+            (TokenInstruction::Approve { amount: 1 }, APPROVE_TAG),
+            (TokenInstruction::MintTo { amount: 1 }, MINT_TO_TAG),
+            (TokenInstruction::Burn { amount: 1 }, BURN_TAG),
+            (TokenInstruction::Revoke, REVOKE_TAG),
+            (
+                TokenInstruction::SetAuthority {
+                    authority_type: AuthorityType::CloseAccount,
+                    new_authority: COption::Some(key),
+                },
+                SET_AUTHORITY_TAG,
+            ),
+            (TokenInstruction::FreezeAccount, FREEZE_ACCOUNT_TAG),
+            (TokenInstruction::ThawAccount, THAW_ACCOUNT_TAG),
+            (
+                TokenInstruction::TransferChecked {
+                    amount: 1,
+                    decimals: 2,
+                },
+                TRANSFER_CHECKED_TAG,
+            ),
+            (
+                TokenInstruction::ApproveChecked {
+                    amount: 1,
+                    decimals: 2,
+                },
+                APPROVE_CHECKED_TAG,
+            ),
+            (
+                TokenInstruction::BurnChecked {
+                    amount: 1,
+                    decimals: 2,
+                },
+                BURN_CHECKED_TAG,
+            ),
+            (TokenInstruction::InitializeMultisig { m: 1 }, INITIALIZE_MULTISIG_TAG),
+            (mint2, INITIALIZE_MINT2_TAG),
+            (TokenInstruction::InitializeAccount2 { owner: key }, INITIALIZE_ACCOUNT2_TAG),
+            (TokenInstruction::InitializeAccount3 { owner: key }, INITIALIZE_ACCOUNT3_TAG),
+            (TokenInstruction::SyncNative, SYNC_NATIVE_TAG),
+            (TokenInstruction::GetAccountDataSize, GET_ACCOUNT_DATA_SIZE_TAG),
+            (TokenInstruction::AmountToUiAmount { amount: 1 }, AMOUNT_TO_UI_AMOUNT_TAG),
+            (TokenInstruction::UiAmountToAmount { ui_amount: "1" }, UI_AMOUNT_TO_AMOUNT_TAG),
+            (TokenInstruction::CloseAccount, CLOSE_ACCOUNT_TAG),
+            (
+                TokenInstruction::IncreaseDelegatedAmount { amount: 1 },
+                INCREASE_DELEGATED_AMOUNT_TAG,
+            ),
+            (
+                TokenInstruction::DecreaseDelegatedAmount { amount: 1 },
+                DECREASE_DELEGATED_AMOUNT_TAG,
+            ),
+            (
+                TokenInstruction::ApproveWithExpiry {
+                    amount: 1,
+                    expiry_slot: 2,
+                },
+                APPROVE_WITH_EXPIRY_TAG,
+            ),
+            (
+                TokenInstruction::TransferBatch {
+                    amounts: vec![1, 2],
+                },
+                TRANSFER_BATCH_TAG,
+            ),
+            (
+                TokenInstruction::MintToBatch {
+                    amounts: vec![1, 2],
+                },
+                MINT_TO_BATCH_TAG,
+            ),
+            (TokenInstruction::ProposeOwner { new_owner: key }, PROPOSE_OWNER_TAG),
+            (TokenInstruction::AcceptOwnership, ACCEPT_OWNERSHIP_TAG),
+            (
+                TokenInstruction::SetDefaultAccountState {
+                    state: AccountState::Frozen,
+                },
+                SET_DEFAULT_ACCOUNT_STATE_TAG,
+            ),
+            (
+                TokenInstruction::RequireMemoTransfers { enable: true },
+                REQUIRE_MEMO_TRANSFERS_TAG,
+            ),
+            (
+                TokenInstruction::TransferCheckedWithFee {
+                    amount: 1,
+                    decimals: 2,
+                    fee: 3,
+                },
+                TRANSFER_CHECKED_WITH_FEE_TAG,
+            ),
+            (
+                TokenInstruction::SetTransferFee {
+                    transfer_fee_basis_points: 1,
+                    maximum_fee: 2,
+                },
+                SET_TRANSFER_FEE_TAG,
+            ),
+            (
+                TokenInstruction::WithdrawWithheldTokensFromAccounts {
+                    num_token_accounts: 1,
+                },
+                WITHDRAW_WITHHELD_TOKENS_FROM_ACCOUNTS_TAG,
+            ),
+            (
+                TokenInstruction::HarvestWithheldTokensToMint,
+                HARVEST_WITHHELD_TOKENS_TO_MINT_TAG,
+            ),
+            (
+                TokenInstruction::UpdateRateInterestBearingMint { rate: -1 },
+                UPDATE_RATE_INTEREST_BEARING_MINT_TAG,
+            ),
+            (TokenInstruction::Pause, PAUSE_TAG),
+            (TokenInstruction::Resume, RESUME_TAG),
+            (TokenInstruction::Reallocate, REALLOCATE_TAG),
+            (TokenInstruction::ReallocateMint, REALLOCATE_MINT_TAG),
+            (
+                TokenInstruction::InitializeImmutableOwner,
+                INITIALIZE_IMMUTABLE_OWNER_TAG,
+            ),
+            (TokenInstruction::EnableCpiGuard, ENABLE_CPI_GUARD_TAG),
+            (TokenInstruction::DisableCpiGuard, DISABLE_CPI_GUARD_TAG),
+            (
+                TokenInstruction::InitializeTransferHook {
+                    authority: COption::Some(key),
+                    program_id: COption::None,
+                },
+                INITIALIZE_TRANSFER_HOOK_TAG,
+            ),
+            (
+                TokenInstruction::UpdateTransferHook {
+                    program_id: COption::Some(key),
+                },
+                UPDATE_TRANSFER_HOOK_TAG,
+            ),
+            (
+                TokenInstruction::InitializeMetadataPointer {
+                    metadata_address: COption::Some(key),
+                },
+                INITIALIZE_METADATA_POINTER_TAG,
+            ),
+            (
+                TokenInstruction::InitializeTokenMetadata {
+                    name: "n",
+                    symbol: "s",
+                    uri: "u",
+                },
+                INITIALIZE_TOKEN_METADATA_TAG,
+            ),
+            (
+                TokenInstruction::UpdateTokenMetadataField {
+                    field: MetadataField::Uri,
+                    value: "v",
+                },
+                UPDATE_TOKEN_METADATA_FIELD_TAG,
+            ),
+            (
+                TokenInstruction::InitializeAssociatedAccount,
+                INITIALIZE_ASSOCIATED_ACCOUNT_TAG,
+            ),
+            (TokenInstruction::RecoverNested, RECOVER_NESTED_TAG),
+            (TokenInstruction::WrapSol { lamports: 1 }, WRAP_SOL_TAG),
+            (TokenInstruction::UnwrapSol { lamports: 1 }, UNWRAP_SOL_TAG),
+            (TokenInstruction::RevokeAll, REVOKE_ALL_TAG),
+            (
+                TokenInstruction::AssertBalance {
+                    minimum: 1,
+                    maximum: 2,
+                },
+                ASSERT_BALANCE_TAG,
+            ),
+            (
+                TokenInstruction::AssertSupply {
+                    minimum: 1,
+                    maximum: 2,
+                },
+                ASSERT_SUPPLY_TAG,
+            ),
+            (TokenInstruction::GetAccountBalance, GET_ACCOUNT_BALANCE_TAG),
+            (TokenInstruction::GetMintSupply, GET_MINT_SUPPLY_TAG),
+            (TokenInstruction::SplitAccount { amount: 1 }, SPLIT_ACCOUNT_TAG),
+            (
+                TokenInstruction::MergeAccounts {
+                    num_source_accounts: 2,
+                    close_sources: true,
+                },
+                MERGE_ACCOUNTS_TAG,
+            ),
+            (TokenInstruction::LockUntilSlot { slot: 1 }, LOCK_UNTIL_SLOT_TAG),
+            (
+                TokenInstruction::InitializePolicy {
+                    mode: PolicyMode::Blocklist,
+                },
+                INITIALIZE_POLICY_TAG,
+            ),
+            (TokenInstruction::AddPolicyEntry { entry: key }, ADD_POLICY_ENTRY_TAG),
+            (
+                TokenInstruction::RemovePolicyEntry { entry: key },
+                REMOVE_POLICY_ENTRY_TAG,
+            ),
+            (TokenInstruction::ApproveEscrowed { amount: 1 }, APPROVE_ESCROWED_TAG),
+            (TokenInstruction::UpdateDecimals { decimals: 3 }, UPDATE_DECIMALS_TAG),
+            (TokenInstruction::InitializeFlashMint { fee: 1 }, INITIALIZE_FLASH_MINT_TAG),
+            (TokenInstruction::FlashMintTo { amount: 1 }, FLASH_MINT_TO_TAG),
+            (TokenInstruction::FlashBurnRepay { amount: 1 }, FLASH_BURN_REPAY_TAG),
+            (
+                TokenInstruction::SetSpendingLimit {
+                    amount: COption::Some(1),
+                },
+                SET_SPENDING_LIMIT_TAG,
+            ),
+            (
+                TokenInstruction::TransferVested {
+                    total_amount: 1,
+                    start_slot: 2,
+                    end_slot: 3,
+                },
+                TRANSFER_VESTED_TAG,
+            ),
+            (TokenInstruction::ClaimVested, CLAIM_VESTED_TAG),
+            (
+                TokenInstruction::Swap {
+                    amount_a: 1,
+                    amount_b: 2,
+                },
+                SWAP_TAG,
+            ),
+            (
+                TokenInstruction::Batch {
+                    instructions: vec![vec![0, REVOKE_TAG]],
+                },
+                BATCH_TAG,
+            ),
+            (TokenInstruction::FreezeAccounts { count: 1 }, FREEZE_ACCOUNTS_TAG),
+            (TokenInstruction::ThawAccounts { count: 1 }, THAW_ACCOUNTS_TAG),
+            (TokenInstruction::BurnFrom { amount: 1 }, BURN_FROM_TAG),
+        ]
+    }
+
+    #[test]
+    fn test_instruction_tags() {
+        let instructions = instruction_per_tag();
+        for (instruction, tag) in &instructions {
+            let packed = instruction.pack();
+            assert_eq!(packed[0], *tag, "{:?}", instruction);
+            assert_eq!(
+                TokenInstruction::unpack(&packed).as_ref(),
+                Ok(instruction),
+                "{:?}",
+                instruction
+            );
+        }
+        // One instruction per tag, so no variant is missing from the list
+        let mut tags: Vec<u8> = instructions.iter().map(|(_, tag)| *tag).collect();
+        tags.sort_unstable();
+        assert_eq!(tags, (0..=BURN_FROM_TAG).collect::<Vec<_>>());
+        // The constants run in sequence up to the last tag
+        assert_eq!(
+            TokenInstruction::unpack(&[BURN_FROM_TAG + 1]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    /// Every proper prefix of `encoded` is rejected, and `encoded` followed
+    /// by trailing bytes unpacks to `expected` with the trailing bytes left
+    fn assert_unpacks<T: PartialEq + std::fmt::Debug>(
+        unpack: impl Fn(&[u8]) -> Result<(T, &[u8]), ProgramError>,
+        encoded: &[u8],
+        expected: T,
+    ) {
+        for len in 0..encoded.len() {
+            assert_eq!(
+                unpack(&encoded[..len]).map(|(value, _)| value),
+                Err(TokenError::InvalidInstruction.into()),
+                "{} of {} bytes",
+                len,
+                encoded.len()
+            );
+        }
+        let input = [encoded, &[7, 8]].concat();
+        assert_eq!(unpack(&input), Ok((expected, &[7u8, 8][..])));
+    }
+
+    #[test]
+    fn test_unpack_helpers() {
+        let key = Pubkey::new_from_array([3u8; 32]);
+        let amount = 0x0102_0304_0506_0708u64;
+
+        assert_unpacks(TokenInstruction::unpack_u16, &[0x34, 0x12], 0x1234);
+        assert_unpacks(TokenInstruction::unpack_u64, &amount.to_le_bytes(), amount);
+        assert_unpacks(
+            |input| {
+                TokenInstruction::unpack_amount_decimals(input)
+                    .map(|(amount, decimals, rest)| ((amount, decimals), rest))
+            },
+            &[&amount.to_le_bytes()[..], &[9]].concat(),
+            (amount, 9),
+        );
+        assert_unpacks(TokenInstruction::unpack_pubkey, key.as_ref(), key);
+
+        assert_unpacks(TokenInstruction::unpack_pubkey_option, &[0], COption::None);
+        assert_unpacks(
+            TokenInstruction::unpack_pubkey_option,
+            &[&[1], key.as_ref()].concat(),
+            COption::Some(key),
+        );
+        assert_unpacks(TokenInstruction::unpack_u64_option, &[0], COption::None);
+        assert_unpacks(
+            TokenInstruction::unpack_u64_option,
+            &[&[1][..], &amount.to_le_bytes()].concat(),
+            COption::Some(amount),
+        );
+        // An option tag is 0 or 1, even with a full value behind it
+        for tag in 2..=u8::MAX {
+            assert_eq!(
+                TokenInstruction::unpack_pubkey_option(&[&[tag], key.as_ref()].concat()),
+                Err(TokenError::InvalidInstruction.into())
+            );
+            assert_eq!(
+                TokenInstruction::unpack_u64_option(&[&[tag][..], &amount.to_le_bytes()].concat()),
+                Err(TokenError::InvalidInstruction.into())
+            );
+        }
+
+        assert_unpacks(
+            |input| TokenInstruction::unpack_str(input).map(|(s, rest)| (s.to_string(), rest)),
+            &[&3u32.to_le_bytes()[..], b"abc"].concat(),
+            "abc".to_string(),
+        );
+        assert_eq!(
+            TokenInstruction::unpack_str(&[&2u32.to_le_bytes()[..], &[0xc3, 0x28]].concat()),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        assert_unpacks(
+            TokenInstruction::unpack_amounts,
+            &[&[2][..], &1u64.to_le_bytes(), &amount.to_le_bytes()].concat(),
+            vec![1, amount],
+        );
+        // A batch carries at least one amount and at most `MAX_BATCH_SIZE`
+        assert_eq!(
+            TokenInstruction::unpack_amounts(&[0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        let oversized = [
+            &[MAX_BATCH_SIZE as u8 + 1][..],
+            &[0; 8 * (MAX_BATCH_SIZE + 1)],
+        ]
+        .concat();
+        assert_eq!(
+            TokenInstruction::unpack_amounts(&oversized),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_checked() {
+        let transfer = TokenInstruction::TransferChecked {
+            amount: 1,
+            decimals: 2,
+        };
+        let packed = Vec::from([10u8, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(transfer.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), transfer);
+
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..9]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_approve_checked() {
+        let approve = TokenInstruction::ApproveChecked {
+            amount: 1,
+            decimals: 2,
+        };
+        let packed = Vec::from([11u8, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(approve.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), approve);
+
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..9]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_burn_checked() {
+        let burn = TokenInstruction::BurnChecked {
+            amount: 1,
+            decimals: 2,
+        };
+        let packed = Vec::from([12u8, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(burn.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), burn);
+    }
+
+    #[test]
+    fn test_initialize_multisig() {
+        let multisig = TokenInstruction::InitializeMultisig { m: 1 };
+        let packed = Vec::from([13u8, 1]);
+        assert_eq!(multisig.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), multisig);
+    }
+
+    #[test]
+    fn test_initialize_mint2() {
+        let mint = TokenInstruction::InitializeMint2 {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::Some(Pubkey::new_from_array([2u8; 32])),
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        };
+
+        let mut packed = Vec::from([14u8, 2]);
+        packed.extend_from_slice(&[1u8; 32]);
+        packed.push(1);
+        packed.extend_from_slice(&[2u8; 32]);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+        packed.push(0);
+
+        assert_eq!(mint.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, mint);
+    }
+
+    #[test]
+    fn test_initialize_account2() {
+        let init_account = TokenInstruction::InitializeAccount2 {
+            owner: Pubkey::new_from_array([2u8; 32]),
+        };
+
+        let mut packed = Vec::from([15u8]);
+        packed.extend_from_slice(&[2u8; 32]);
+
+        assert_eq!(init_account.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, init_account);
+    }
+
+    #[test]
+    fn test_initialize_account3() {
+        let init_account = TokenInstruction::InitializeAccount3 {
+            owner: Pubkey::new_from_array([2u8; 32]),
+        };
+
+        let mut packed = Vec::from([16u8]);
+        packed.extend_from_slice(&[2u8; 32]);
+
+        assert_eq!(init_account.pack(), packed);
+
+        let unpacked = TokenInstruction::unpack(&packed).unwrap();
+        assert_eq!(unpacked, init_account);
+    }
+
+    #[test]
+    fn test_sync_native() {
+        let sync_native = TokenInstruction::SyncNative;
+        let packed = Vec::from([17u8]);
+        assert_eq!(sync_native.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), sync_native);
+    }
+
+    #[test]
+    fn test_get_account_data_size() {
+        let get_size = TokenInstruction::GetAccountDataSize;
+        let packed = Vec::from([18u8]);
+        assert_eq!(get_size.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), get_size);
+    }
+
+    #[test]
+    fn test_get_account_balance_and_mint_supply() {
+        let check = TokenInstruction::GetAccountBalance;
+        let packed = Vec::from([55u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        let check = TokenInstruction::GetMintSupply;
+        let packed = Vec::from([56u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let instruction = get_account_balance(&program_id, &key).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new_readonly(key, false)]);
+        let instruction = get_mint_supply(&program_id, &key).unwrap();
+        assert_eq!(instruction.accounts, vec![AccountMeta::new_readonly(key, false)]);
+    }
+
+    #[test]
+    fn test_amount_to_ui_amount() {
+        let check = TokenInstruction::AmountToUiAmount { amount: 42 };
+        let packed = Vec::from([19u8, 42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_ui_amount_to_amount() {
+        let check = TokenInstruction::UiAmountToAmount { ui_amount: "0.42" };
+        let packed = Vec::from([20u8, 48, 46, 52, 50]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        assert_eq!(
+            TokenInstruction::unpack(&[20u8, 0xff]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_close_account() {
+        let close_account = TokenInstruction::CloseAccount;
+        let packed = Vec::from([21u8]);
+        assert_eq!(close_account.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), close_account);
+    }
+
+    #[test]
+    fn test_change_delegated_amount() {
+        let check = TokenInstruction::IncreaseDelegatedAmount { amount: 42 };
+        let packed = Vec::from([22u8, 42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::DecreaseDelegatedAmount { amount: 42 };
+        let packed = Vec::from([23u8, 42, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_approve_with_expiry() {
+        let check = TokenInstruction::ApproveWithExpiry {
+            amount: 1,
+            expiry_slot: 2,
+        };
+        let packed = Vec::from([24u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+    }
+
+    #[test]
+    fn test_approve_escrowed() {
+        let check = TokenInstruction::ApproveEscrowed { amount: 300 };
+        let packed = check.pack();
+        assert_eq!(packed, [63u8, 44, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_batch() {
+        let check = TokenInstruction::TransferBatch {
+            amounts: vec![1, 2],
+        };
+        let packed = Vec::from([25u8, 2, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // the count has to match the amounts that follow
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..packed.len() - 8]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        // and stay within 1..=MAX_BATCH_SIZE
+        assert_eq!(
+            TokenInstruction::unpack(&[25u8, 0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        let check = TokenInstruction::TransferBatch {
+            amounts: vec![7; MAX_BATCH_SIZE],
+        };
+        let mut packed = check.pack();
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        packed[1] += 1;
+        packed.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(
+            TokenInstruction::unpack(&packed),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let destination = Pubkey::new_unique();
+        assert_eq!(
+            transfer_batch(&program_id, &destination, &destination, &[], &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            transfer_batch(
+                &program_id,
+                &destination,
+                &destination,
+                &[],
+                &[(&destination, 1); MAX_BATCH_SIZE + 1],
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_mint_to_batch() {
+        let check = TokenInstruction::MintToBatch {
+            amounts: vec![1, 2],
+        };
+        let packed = Vec::from([26u8, 2, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+        assert_eq!(
+            TokenInstruction::unpack(&[26u8, 0]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let instruction =
+            mint_to_batch(&program_id, &mint, &authority, &[], &[(&destination, 5)]).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(destination, false),
+            ]
+        );
+        assert_eq!(
+            mint_to_batch(&program_id, &mint, &authority, &[], &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            mint_to_batch(
+                &program_id,
+                &mint,
+                &authority,
+                &[],
+                &[(&destination, 1); MAX_BATCH_SIZE + 1],
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_propose_owner() {
+        let check = TokenInstruction::ProposeOwner {
+            new_owner: Pubkey::new_from_array([2u8; 32]),
+        };
+        let mut packed = Vec::from([27u8]);
+        packed.extend_from_slice(&[2u8; 32]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::AcceptOwnership;
+        let packed = Vec::from([28u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // cancelling proposes the default key
+        let program_id = crate::id();
+        let account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let instruction = propose_owner(&program_id, &account, None, &owner, &[]).unwrap();
+        assert_eq!(
+            TokenInstruction::unpack(&instruction.data).unwrap(),
+            TokenInstruction::ProposeOwner {
+                new_owner: Pubkey::default(),
+            }
+        );
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_default_account_state() {
+        let check = TokenInstruction::SetDefaultAccountState {
+            state: AccountState::Frozen,
+        };
+        let packed = Vec::from([29u8, 2]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // only known states decode
+        assert_eq!(
+            TokenInstruction::unpack(&[29u8, 3]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_require_memo_transfers() {
+        let check = TokenInstruction::RequireMemoTransfers { enable: true };
+        let packed = Vec::from([30u8, 1]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::RequireMemoTransfers { enable: false };
+        let packed = Vec::from([30u8, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // the flag is a strict boolean
+        assert_eq!(
+            TokenInstruction::unpack(&[30u8, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_fee_instructions() {
+        let check = TokenInstruction::TransferCheckedWithFee {
+            amount: 1,
+            decimals: 2,
+            fee: 3,
+        };
+        let packed = Vec::from([31u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::SetTransferFee {
+            transfer_fee_basis_points: 0x0102,
+            maximum_fee: 3,
+        };
+        let packed = Vec::from([32u8, 2, 1, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::WithdrawWithheldTokensFromAccounts {
+            num_token_accounts: 3,
+        };
+        let packed = Vec::from([33u8, 3]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        let check = TokenInstruction::HarvestWithheldTokensToMint;
+        let packed = Vec::from([34u8]);
+        assert_eq!(check.pack(), packed);
+        assert_eq!(TokenInstruction::unpack(&packed).unwrap(), check);
+
+        // the sources follow the multisig signers
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let instruction = withdraw_withheld_tokens_from_accounts(
+            &program_id,
+            &mint,
+            &destination,
+            &multisig,
+            &[&signer],
+            &[&source],
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new(source, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builders_reject_foreign_program_id() {
+        let foreign_program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        assert_eq!(
+            initialize_mint(&foreign_program_id, &mint, &owner, None, 2),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            initialize_account(&foreign_program_id, &account, &mint, &owner),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            transfer(&foreign_program_id, &account, &account, &owner, &[], 1),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert!(transfer(&crate::id(), &account, &account, &owner, &[], 1).is_ok());
+    }
+
+    #[test]
+    fn test_builder_account_metas() {
+        let program_id = crate::id();
+        let mint = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let signer1 = Pubkey::new_unique();
+        let signer2 = Pubkey::new_unique();
+
+        let instruction = initialize_mint(&program_id, &mint, &owner, None, 2).unwrap();
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ]
+        );
+
+        let instruction = initialize_account(&program_id, &source, &mint, &owner).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(owner, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ]
+        );
+
+        let instruction = transfer(&program_id, &source, &destination, &owner, &[], 1).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+
+        // a multisig authority does not sign itself, its signers do
+        let instruction = transfer(
+            &program_id,
+            &source,
+            &destination,
+            &owner,
+            &[&signer1, &signer2],
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, false),
+                AccountMeta::new_readonly(signer1, true),
+                AccountMeta::new_readonly(signer2, true),
+            ]
+        );
+
+        let instruction = approve(&program_id, &source, &destination, &owner, &[], 1).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new_readonly(destination, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+
+        let instruction = mint_to(&program_id, &mint, &destination, &owner, &[], 1).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(mint, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+
+        let instruction = burn(&program_id, &source, &mint, &owner, &[], 1).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+
+        let instruction = close_account(&program_id, &source, &destination, &owner, &[]).unwrap();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(owner, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpack_rejects_wrong_lengths() {
+        let key = Pubkey::new_unique();
+        let instructions = vec![
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: key,
+                freeze_authority: COption::Some(key),
+                max_supply: COption::Some(1),
+                permanent_delegate: COption::Some(key),
+                transfer_fee_authority: COption::Some(key),
+                rate_authority: COption::Some(key),
+                pause_authority: COption::Some(key),
+            },
+            TokenInstruction::InitializeAccount,
+            TokenInstruction::Transfer { amount: 1 },
+            TokenInstruction::Approve { amount: 1 },
+            TokenInstruction::MintTo { amount: 1 },
+            TokenInstruction::Burn { amount: 1 },
+            TokenInstruction::Revoke,
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::AccountOwner,
+                new_authority: COption::Some(key),
+            },
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::MintTokens,
+                new_authority: COption::None,
+            },
+            TokenInstruction::FreezeAccount,
+            TokenInstruction::ThawAccount,
+            TokenInstruction::TransferChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::ApproveChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::BurnChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::InitializeMultisig { m: 1 },
+            TokenInstruction::InitializeMint2 {
+                decimals: 2,
+                mint_authority: key,
+                freeze_authority: COption::Some(key),
+                max_supply: COption::Some(1),
+                permanent_delegate: COption::Some(key),
+                transfer_fee_authority: COption::Some(key),
+                rate_authority: COption::Some(key),
+                pause_authority: COption::Some(key),
+            },
+            TokenInstruction::InitializeAccount2 { owner: key },
+            TokenInstruction::InitializeAccount3 { owner: key },
+            TokenInstruction::SyncNative,
+            TokenInstruction::GetAccountDataSize,
+            TokenInstruction::AmountToUiAmount { amount: 1 },
+            TokenInstruction::CloseAccount,
+            TokenInstruction::IncreaseDelegatedAmount { amount: 1 },
+            TokenInstruction::DecreaseDelegatedAmount { amount: 1 },
+            TokenInstruction::ApproveWithExpiry {
+                amount: 1,
+                expiry_slot: 2,
+            },
+            TokenInstruction::TransferBatch {
+                amounts: vec![1, 2, 3],
+            },
+            TokenInstruction::MintToBatch {
+                amounts: vec![1, 2, 3],
+            },
+            TokenInstruction::ProposeOwner { new_owner: key },
+            TokenInstruction::SetDefaultAccountState {
+                state: AccountState::Frozen,
+            },
+            TokenInstruction::RequireMemoTransfers { enable: true },
+            TokenInstruction::TransferCheckedWithFee {
+                amount: 1,
+                decimals: 2,
+                fee: 3,
+            },
+            TokenInstruction::SetTransferFee {
+                transfer_fee_basis_points: 1,
+                maximum_fee: 2,
+            },
+            TokenInstruction::WithdrawWithheldTokensFromAccounts {
+                num_token_accounts: 1,
+            },
+            TokenInstruction::UpdateRateInterestBearingMint { rate: -1 },
+            TokenInstruction::InitializeTransferHook {
+                authority: COption::Some(key),
+                program_id: COption::Some(key),
+            },
+            TokenInstruction::UpdateTransferHook {
+                program_id: COption::Some(key),
+            },
+            TokenInstruction::InitializeMetadataPointer {
+                metadata_address: COption::Some(key),
+            },
+            TokenInstruction::InitializeTokenMetadata {
+                name: "Token",
+                symbol: "TKN",
+                uri: "https://example.com/token.json",
+            },
+            TokenInstruction::UpdateTokenMetadataField {
+                field: MetadataField::Uri,
+                value: "",
+            },
+            TokenInstruction::WrapSol { lamports: 1 },
+            TokenInstruction::UnwrapSol { lamports: 1 },
+            TokenInstruction::RevokeAll,
+            TokenInstruction::AssertBalance {
+                minimum: 1,
+                maximum: 2,
+            },
+            TokenInstruction::AssertSupply {
+                minimum: 1,
+                maximum: 2,
+            },
+            TokenInstruction::GetAccountBalance,
+            TokenInstruction::GetMintSupply,
+            TokenInstruction::SplitAccount { amount: 1 },
+            TokenInstruction::MergeAccounts {
+                num_source_accounts: 1,
+                close_sources: false,
+            },
+            TokenInstruction::LockUntilSlot { slot: 1 },
+            TokenInstruction::InitializePolicy {
+                mode: PolicyMode::Allowlist,
+            },
+            TokenInstruction::AddPolicyEntry { entry: key },
+            TokenInstruction::RemovePolicyEntry { entry: key },
+            TokenInstruction::ApproveEscrowed { amount: 1 },
+            TokenInstruction::UpdateDecimals { decimals: 1 },
+            TokenInstruction::InitializeFlashMint { fee: 1 },
+            TokenInstruction::FlashMintTo { amount: 1 },
+            TokenInstruction::FlashBurnRepay { amount: 1 },
+            TokenInstruction::SetSpendingLimit {
+                amount: COption::Some(1),
+            },
+            TokenInstruction::TransferVested {
+                total_amount: 1,
+                start_slot: 2,
+                end_slot: 3,
+            },
+            TokenInstruction::ClaimVested,
+            TokenInstruction::Swap {
+                amount_a: 1,
+                amount_b: 2,
+            },
+            TokenInstruction::Batch {
+                instructions: vec![vec![3, 9]],
+            },
+            TokenInstruction::FreezeAccounts { count: 1 },
+            TokenInstruction::ThawAccounts { count: 25 },
+            TokenInstruction::BurnFrom { amount: 1 },
+        ];
+
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(TokenInstruction::unpack(&packed).unwrap(), instruction);
+
+            // oversized
+            let mut oversized = packed.clone();
+            oversized.push(0);
+            assert_eq!(
+                TokenInstruction::unpack(&oversized),
+                Err(TokenError::InvalidInstruction.into()),
+                "{:?}",
+                instruction
+            );
+            oversized.extend_from_slice(&[0; 200]);
+            assert_eq!(
+                TokenInstruction::unpack(&oversized),
+                Err(TokenError::InvalidInstruction.into()),
+                "{:?}",
+                instruction
+            );
+
+            // undersized
+            assert_eq!(
+                TokenInstruction::unpack(&packed[..packed.len() - 1]),
+                Err(TokenError::InvalidInstruction.into()),
+                "{:?}",
+                instruction
+            );
+        }
+
+        // the trailing UI amount string can be of any length
+        let packed = TokenInstruction::UiAmountToAmount { ui_amount: "1.5" }.pack();
+        assert!(TokenInstruction::unpack(&packed[..2]).is_ok());
+        assert!(TokenInstruction::unpack(&packed[..1]).is_ok());
+
+        assert_eq!(
+            TokenInstruction::unpack(&[]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_unpack_initialize_mint_boundaries() {
+        let packed = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new_from_array([1u8; 32]),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        }
+        .pack();
+        assert_eq!(packed.len(), 40);
+
+        // 35 to 39 bytes: no max supply, permanent delegate, transfer fee
+        // authority, rate authority or pause authority section
+        for len in [35, 36, 37, 38, 39] {
+            assert_eq!(
+                TokenInstruction::unpack(&packed[..len]).unwrap(),
+                TokenInstruction::unpack(&packed).unwrap()
+            );
+        }
+
+        // 34 bytes: no freeze authority section at all
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..34]).unwrap(),
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::None,
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            }
+        );
+
+        // 33 bytes: truncated mint authority
+        assert_eq!(
+            TokenInstruction::unpack(&packed[..33]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        // a `None` freeze authority followed by a zeroed key is not accepted
+        let mut padded = packed.clone();
+        padded.extend_from_slice(&[0u8; 32]);
+        assert_eq!(
+            TokenInstruction::unpack(&padded),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_unpack_initialize_mint_freeze_authority_tag() {
+        for tag in [INITIALIZE_MINT_TAG, INITIALIZE_MINT2_TAG] {
+            let mut packed = vec![tag, 2];
+            packed.extend_from_slice(&[1u8; 32]);
+
+            // a set freeze authority, with no section after it
+            let mut with_freeze_authority = packed.clone();
+            with_freeze_authority.push(1);
+            with_freeze_authority.extend_from_slice(&[3u8; 32]);
+            let freeze_authority = match TokenInstruction::unpack(&with_freeze_authority).unwrap() {
+                TokenInstruction::InitializeMint {
+                    freeze_authority, ..
+                }
+                | TokenInstruction::InitializeMint2 {
+                    freeze_authority, ..
+                } => freeze_authority,
+                instruction => panic!("{:?}", instruction),
+            };
+            assert_eq!(
+                freeze_authority,
+                COption::Some(Pubkey::new_from_array([3u8; 32]))
+            );
+
+            // a tag other than 0 or 1
+            for bad_tag in [2u8, 0xff] {
+                let mut malformed = packed.clone();
+                malformed.push(bad_tag);
+                malformed.extend_from_slice(&[3u8; 32]);
+                assert_eq!(
+                    TokenInstruction::unpack(&malformed),
+                    Err(TokenError::InvalidInstruction.into()),
+                    "{} {}",
+                    tag,
+                    bad_tag
+                );
+            }
+
+            // a set tag without its whole key
+            for key_len in [0, 31] {
+                let mut truncated = packed.clone();
+                truncated.push(1);
+                truncated.extend_from_slice(&vec![3u8; key_len]);
+                assert_eq!(
+                    TokenInstruction::unpack(&truncated),
+                    Err(TokenError::InvalidInstruction.into()),
+                    "{} {}",
+                    tag,
+                    key_len
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_serialize() {
+        let instructions = [
+            TokenInstruction::Transfer { amount: 42 },
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new_from_array([1u8; 32]),
+                freeze_authority: COption::Some(Pubkey::new_from_array([2u8; 32])),
+                max_supply: COption::None,
+                permanent_delegate: COption::None,
+                transfer_fee_authority: COption::None,
+                rate_authority: COption::None,
+                pause_authority: COption::None,
+            },
+            TokenInstruction::UiAmountToAmount { ui_amount: "1.5" },
+        ];
+        for instruction in instructions {
+            let serialized = borsh::to_vec(&instruction).unwrap();
+            assert_eq!(serialized, instruction.pack());
+            assert_eq!(TokenInstruction::try_from_slice(&serialized).unwrap(), instruction);
+        }
+        let error = TokenInstruction::try_from_slice(&[BURN_FROM_TAG + 1]).unwrap_err();
+        assert_eq!(error.kind(), borsh::io::ErrorKind::InvalidData);
+    }
+
+    /// Reads one value of `declaration` off the front of `data` as the schema
+    /// lays it out, returning the variant names it passes through
+    #[cfg(feature = "borsh")]
+    fn read_schema(
+        schema: &borsh::schema::BorshSchemaContainer,
+        declaration: &str,
+        data: &mut &[u8],
+    ) -> Vec<String> {
+        use borsh::schema::{Definition, Fields};
+        let take = |data: &mut &[u8], len: usize| {
+            let (bytes, rest) = data.split_at(len);
+            *data = rest;
+            bytes.iter().rev().fold(0u64, |value, &byte| value << 8 | u64::from(byte))
+        };
+        match schema.get_definition(declaration).unwrap() {
+            Definition::Primitive(size) => {
+                take(data, *size as usize);
+                vec![]
+            }
+            Definition::Sequence {
+                length_width,
+                length_range,
+                elements,
+            } => {
+                let len = match length_width {
+                    0 if length_range.start() == length_range.end() => *length_range.start(),
+                    // Unprefixed, so it runs to the end of the data
+                    0 => data.len() as u64,
+                    width => take(data, *width as usize),
+                };
+                assert!(length_range.contains(&len), "{} of {}", len, declaration);
+                (0..len).flat_map(|_| read_schema(schema, elements, data)).collect()
+            }
+            Definition::Tuple { elements } => elements
+                .iter()
+                .flat_map(|element| read_schema(schema, element, data))
+                .collect(),
+            Definition::Enum {
+                tag_width,
+                variants,
+            } => {
+                let tag = take(data, *tag_width as usize) as i64;
+                let (_, name, variant) = variants
+                    .iter()
+                    .find(|(discriminant, _, _)| *discriminant == tag)
+                    .unwrap();
+                let mut names = vec![name.clone()];
+                names.extend(read_schema(schema, variant, data));
+                names
+            }
+            Definition::Struct { fields } => match fields {
+                Fields::NamedFields(fields) => fields
+                    .iter()
+                    .flat_map(|(_, field)| read_schema(schema, field, data))
+                    .collect(),
+                Fields::UnnamedFields(fields) => fields
+                    .iter()
+                    .flat_map(|field| read_schema(schema, field, data))
+                    .collect(),
+                Fields::Empty => vec![],
+            },
+        }
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_schema() {
+        use borsh::schema::BorshSchemaContainer;
+        let schema = BorshSchemaContainer::for_type::<TokenInstruction>();
+        assert_eq!(schema.validate(), Ok(()));
+        // The schema reads the packed data of each variant to the end,
+        // entering the variant of the same name
+        for (instruction, _) in instruction_per_tag() {
+            let packed = instruction.pack();
+            let mut data = &packed[..];
+            let names = read_schema(&schema, schema.declaration(), &mut data);
+            assert!(data.is_empty(), "{:?}", instruction);
+            let debug = format!("{:?}", instruction);
+            assert_eq!(debug.split(' ').next(), Some(names[0].as_str()), "{}", debug);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_snapshots() {
+        let check = [
+            (
+                TokenInstruction::InitializeMint {
+                    decimals: 2,
+                    mint_authority: Pubkey::new_from_array([1u8; 32]),
+                    freeze_authority: COption::None,
+                    max_supply: COption::None,
+                    permanent_delegate: COption::None,
+                    transfer_fee_authority: COption::None,
+                    rate_authority: COption::None,
+                    pause_authority: COption::None,
+                },
+                r#"{"InitializeMint":{"decimals":2,"mint_authority":"4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi","freeze_authority":null,"max_supply":null,"permanent_delegate":null,"transfer_fee_authority":null,"rate_authority":null,"pause_authority":null}}"#,
+            ),
+            (
+                TokenInstruction::InitializeAccount,
+                r#""InitializeAccount""#,
+            ),
+            (
+                TokenInstruction::Transfer { amount: 42 },
+                r#"{"Transfer":{"amount":42}}"#,
+            ),
+            (
+                TokenInstruction::SetAuthority {
+                    authority_type: AuthorityType::AccountOwner,
+                    new_authority: COption::Some(Pubkey::new_from_array([2u8; 32])),
+                },
+                r#"{"SetAuthority":{"authority_type":"AccountOwner","new_authority":"8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR"}}"#,
+            ),
+            (
+                TokenInstruction::InitializeAccount2 {
+                    owner: Pubkey::new_from_array([2u8; 32]),
+                },
+                r#"{"InitializeAccount2":{"owner":"8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR"}}"#,
+            ),
+            (
+                TokenInstruction::UiAmountToAmount { ui_amount: "1.5" },
+                r#"{"UiAmountToAmount":{"ui_amount":"1.5"}}"#,
+            ),
+            (
+                TokenInstruction::UpdateTokenMetadataField {
+                    field: MetadataField::Symbol,
+                    value: "TKN",
+                },
+                r#"{"UpdateTokenMetadataField":{"field":"Symbol","value":"TKN"}}"#,
+            ),
+        ];
+        for (instruction, expected) in check.iter() {
+            let json = serde_json::to_string(instruction).unwrap();
+            assert_eq!(&json, expected);
+            assert_eq!(
+                &serde_json::from_str::<TokenInstruction>(&json).unwrap(),
+                instruction
+            );
+        }
     }
 }
@@ -1,27 +1,63 @@
+pub mod amount;
 pub mod error;
 pub mod processor;
 pub mod state;
 pub mod instruction;
+pub mod native_mint;
+pub mod memo;
+pub mod transfer_hook;
+pub mod associated_account;
+pub mod cpi;
+pub mod events;
+#[cfg(feature = "serde")]
+pub mod serialization;
+#[cfg(feature = "idl")]
+pub mod idl;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "proptest")]
+mod arbitrary;
+#[cfg(not(feature = "no-entrypoint"))]
+mod entrypoint;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod test_fixtures;
 
+pub use amount::{
+    amount_to_ui_amount_string, amount_to_ui_amount_string_trimmed, try_ui_amount_into_amount,
+};
 pub use solana_program;
 
-use crate::{error::TokenError, processor::Processor};
-use solana_program::{
-    entrypoint::ProgramResult, pubkey::Pubkey,
-    account_info::AccountInfo, entrypoint, program_error::PrintProgramError,
-};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
 
-solana_program::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+solana_program::declare_id!("HS1MCU9ZqZoh9AoVgjhA1fonoAqP3Nb97Njwq7HW6gZZ");
 
-entrypoint!(process_instruction);
-fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
-        error.print::<TokenError>();
-        return Err(error);
+/// Checks that the supplied program ID is the correct one for this program
+pub fn check_program_account(program_id: &Pubkey) -> ProgramResult {
+    if program_id != &id() {
+        return Err(ProgramError::IncorrectProgramId);
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_program_account() {
+        assert_eq!(check_program_account(&id()), Ok(()));
+        assert_eq!(
+            check_program_account(&Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        // the SPL Token program id is not ours
+        assert_ne!(
+            id().to_string(),
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+    }
+}
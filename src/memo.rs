@@ -0,0 +1,12 @@
+use solana_program::{declare_id, pubkey, pubkey::Pubkey};
+
+// The SPL Memo program
+declare_id!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// The first deployment of the SPL Memo program, still accepted as a memo
+pub const ID_V1: Pubkey = pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+
+/// Checks whether the program ID belongs to either deployment of the Memo program
+pub fn is_memo_program(program_id: &Pubkey) -> bool {
+    check_id(program_id) || program_id == &ID_V1
+}
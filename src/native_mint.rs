@@ -0,0 +1,17 @@
+use solana_program::declare_id;
+
+pub const DECIMALS: u8 = 9;
+
+// The Mint for native SOL Token accounts
+declare_id!("So11111111111111111111111111111111111111112");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::native_token::LAMPORTS_PER_SOL;
+
+    #[test]
+    fn test_decimals() {
+        assert_eq!(10u64.pow(DECIMALS as u32), LAMPORTS_PER_SOL);
+    }
+}
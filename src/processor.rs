@@ -1,66 +1,536 @@
 use solana_program::{
-    pubkey::Pubkey,
+    pubkey::{Pubkey, PUBKEY_BYTES},
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
     program_error::ProgramError,
     program_option::COption,
-    msg,
+    program::{invoke, invoke_signed, set_return_data},
+    program_memory::sol_memcmp,
     program_pack::{IsInitialized, Pack},
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{
+        self,
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+        Sysvar,
+    },
 };
 use crate::{
+    amount::{amount_to_ui_amount_string_trimmed, try_ui_amount_into_amount},
+    associated_account::get_associated_token_address_with_program_id,
     error::TokenError,
-    instruction::{TokenInstruction},
-    state::{Account, AccountState, Mint},
+    events::{ApproveEvent, BurnEvent, MintEvent, TokenEvent, TransferEvent},
+    memo,
+    native_mint,
+    instruction::{
+        is_valid_signer_index, AuthorityType, MetadataField, TokenInstruction, MAX_SIGNERS,
+        TRANSFER_ALL,
+    },
+    state::{
+        pod::{PodAccount, PodCOption, PodU64},
+        Account, AccountState, FlashMintConfig, InterestBearingConfig, LayoutVersion, Mint,
+        Multisig, PolicyMode, TokenMetadata, TransferFeeConfig, TransferPolicy, MAX_DECIMALS,
+        MAX_FEE_BASIS_POINTS, MAX_POLICY_ENTRIES,
+    },
+    transfer_hook,
 };
 
+/// `msg!` for the lines only an observer wants, such as the instruction name;
+/// the `no-log` feature compiles them out to save compute units
+macro_rules! log {
+    ($($arg:tt)*) => {{
+        #[cfg(not(feature = "no-log"))]
+        solana_program::msg!($($arg)*);
+        // Still checks the arguments, so a value that is only logged does not
+        // turn into an unused variable
+        #[cfg(feature = "no-log")]
+        let _ = format_args!($($arg)*);
+    }};
+}
+pub(crate) use log;
 
 pub struct Processor {}
 impl Processor {
-    pub fn process(_program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = TokenInstruction::unpack(input)?;
+        Self::process_instruction(program_id, accounts, instruction)
+    }
 
+    fn process_instruction(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction: TokenInstruction,
+    ) -> ProgramResult {
         match instruction {
             TokenInstruction::InitializeMint {
                 decimals,
                 mint_authority,
+                freeze_authority,
+                max_supply,
+                permanent_delegate,
+                transfer_fee_authority,
+                rate_authority,
+                pause_authority,
+            } => {
+                log!("Instruction: InitializeMint");
+                Self::process_initialize_mint(
+                    program_id,
+                    accounts,
+                    decimals,
+                    mint_authority,
+                    freeze_authority,
+                    max_supply,
+                    permanent_delegate,
+                    transfer_fee_authority,
+                    rate_authority,
+                    pause_authority,
+                    true,
+                )
+            }
+            TokenInstruction::InitializeMint2 {
+                decimals,
+                mint_authority,
+                freeze_authority,
+                max_supply,
+                permanent_delegate,
+                transfer_fee_authority,
+                rate_authority,
+                pause_authority,
             } => {
-                msg!("Instruction: InitializeMint");
-                Self::process_initialize_mint(accounts, decimals, mint_authority)
+                log!("Instruction: InitializeMint2");
+                Self::process_initialize_mint(
+                    program_id,
+                    accounts,
+                    decimals,
+                    mint_authority,
+                    freeze_authority,
+                    max_supply,
+                    permanent_delegate,
+                    transfer_fee_authority,
+                    rate_authority,
+                    pause_authority,
+                    false,
+                )
             }
             TokenInstruction::InitializeAccount => {
-                msg!("Instruction: InitializeAccount");
-                Self::process_initialize_account(accounts)
+                log!("Instruction: InitializeAccount");
+                Self::process_initialize_account(program_id, accounts, None, true)
+            }
+            TokenInstruction::InitializeAccount2 { owner } => {
+                log!("Instruction: InitializeAccount2");
+                Self::process_initialize_account(program_id, accounts, Some(&owner), true)
+            }
+            TokenInstruction::InitializeAccount3 { owner } => {
+                log!("Instruction: InitializeAccount3");
+                Self::process_initialize_account(program_id, accounts, Some(&owner), false)
+            }
+            TokenInstruction::InitializeMultisig { m } => {
+                log!("Instruction: InitializeMultisig");
+                Self::process_initialize_multisig(program_id, accounts, m)
             }
             TokenInstruction::Transfer { amount } => {
-                msg!("Instruction: Transfer");
-                Self::process_transfer(accounts, amount)
+                log!("Instruction: Transfer");
+                if Self::transfer_in_place(program_id, accounts, amount) {
+                    return Ok(());
+                }
+                Self::process_transfer(program_id, accounts, amount, None, None)
             }
             TokenInstruction::Approve { amount } => {
-                msg!("Instruction: Approve");
-                Self::process_approve(accounts, amount)
+                log!("Instruction: Approve");
+                Self::process_approve(program_id, accounts, amount, None, COption::None, false)
             }
             TokenInstruction::MintTo { amount } => {
-                msg!("Instruction: MintTo");
-                Self::process_mint_to(accounts, amount)
+                log!("Instruction: MintTo");
+                Self::process_mint_to(program_id, accounts, amount)
             }
             TokenInstruction::Burn { amount } => {
-                msg!("Instruction: Burn");
-                Self::process_burn(accounts, amount)
+                log!("Instruction: Burn");
+                Self::process_burn(program_id, accounts, amount, None)
+            }
+            TokenInstruction::Revoke => {
+                log!("Instruction: Revoke");
+                Self::process_revoke(program_id, accounts)
+            }
+            TokenInstruction::SetAuthority {
+                authority_type,
+                new_authority,
+            } => {
+                log!("Instruction: SetAuthority");
+                Self::process_set_authority(program_id, accounts, authority_type, new_authority)
+            }
+            TokenInstruction::FreezeAccount => {
+                log!("Instruction: FreezeAccount");
+                Self::process_toggle_freeze_account(program_id, accounts, true)
+            }
+            TokenInstruction::ThawAccount => {
+                log!("Instruction: ThawAccount");
+                Self::process_toggle_freeze_account(program_id, accounts, false)
+            }
+            TokenInstruction::TransferChecked { amount, decimals } => {
+                log!("Instruction: TransferChecked");
+                Self::process_transfer(program_id, accounts, amount, Some(decimals), None)
+            }
+            TokenInstruction::ApproveChecked { amount, decimals } => {
+                log!("Instruction: ApproveChecked");
+                Self::process_approve(
+                    program_id,
+                    accounts,
+                    amount,
+                    Some(decimals),
+                    COption::None,
+                    false,
+                )
+            }
+            TokenInstruction::BurnChecked { amount, decimals } => {
+                log!("Instruction: BurnChecked");
+                Self::process_burn(program_id, accounts, amount, Some(decimals))
+            }
+            TokenInstruction::SyncNative => {
+                log!("Instruction: SyncNative");
+                Self::process_sync_native(program_id, accounts)
+            }
+            TokenInstruction::GetAccountDataSize => {
+                log!("Instruction: GetAccountDataSize");
+                Self::process_get_account_data_size(program_id, accounts)
+            }
+            TokenInstruction::AmountToUiAmount { amount } => {
+                log!("Instruction: AmountToUiAmount");
+                Self::process_amount_to_ui_amount(program_id, accounts, amount)
+            }
+            TokenInstruction::UiAmountToAmount { ui_amount } => {
+                log!("Instruction: UiAmountToAmount");
+                Self::process_ui_amount_to_amount(program_id, accounts, ui_amount)
+            }
+            TokenInstruction::CloseAccount => {
+                log!("Instruction: CloseAccount");
+                Self::process_close_account(program_id, accounts)
+            }
+            TokenInstruction::IncreaseDelegatedAmount { amount } => {
+                log!("Instruction: IncreaseDelegatedAmount");
+                Self::process_change_delegated_amount(program_id, accounts, amount, true)
+            }
+            TokenInstruction::DecreaseDelegatedAmount { amount } => {
+                log!("Instruction: DecreaseDelegatedAmount");
+                Self::process_change_delegated_amount(program_id, accounts, amount, false)
+            }
+            TokenInstruction::ApproveWithExpiry {
+                amount,
+                expiry_slot,
+            } => {
+                log!("Instruction: ApproveWithExpiry");
+                Self::process_approve(
+                    program_id,
+                    accounts,
+                    amount,
+                    None,
+                    COption::Some(expiry_slot),
+                    false,
+                )
+            }
+            TokenInstruction::TransferBatch { amounts } => {
+                log!("Instruction: TransferBatch");
+                Self::process_transfer_batch(program_id, accounts, &amounts)
+            }
+            TokenInstruction::MintToBatch { amounts } => {
+                log!("Instruction: MintToBatch");
+                Self::process_mint_to_batch(program_id, accounts, &amounts)
+            }
+            TokenInstruction::ProposeOwner { new_owner } => {
+                log!("Instruction: ProposeOwner");
+                Self::process_propose_owner(program_id, accounts, new_owner)
+            }
+            TokenInstruction::AcceptOwnership => {
+                log!("Instruction: AcceptOwnership");
+                Self::process_accept_ownership(program_id, accounts)
+            }
+            TokenInstruction::SetDefaultAccountState { state } => {
+                log!("Instruction: SetDefaultAccountState");
+                Self::process_set_default_account_state(program_id, accounts, state)
+            }
+            TokenInstruction::RequireMemoTransfers { enable } => {
+                log!("Instruction: RequireMemoTransfers");
+                Self::process_require_memo_transfers(program_id, accounts, enable)
+            }
+            TokenInstruction::TransferCheckedWithFee {
+                amount,
+                decimals,
+                fee,
+            } => {
+                log!("Instruction: TransferCheckedWithFee");
+                Self::process_transfer(program_id, accounts, amount, Some(decimals), Some(fee))
+            }
+            TokenInstruction::SetTransferFee {
+                transfer_fee_basis_points,
+                maximum_fee,
+            } => {
+                log!("Instruction: SetTransferFee");
+                Self::process_set_transfer_fee(
+                    program_id,
+                    accounts,
+                    transfer_fee_basis_points,
+                    maximum_fee,
+                )
+            }
+            TokenInstruction::WithdrawWithheldTokensFromAccounts { num_token_accounts } => {
+                log!("Instruction: WithdrawWithheldTokensFromAccounts");
+                Self::process_withdraw_withheld_tokens_from_accounts(
+                    program_id,
+                    accounts,
+                    num_token_accounts,
+                )
+            }
+            TokenInstruction::HarvestWithheldTokensToMint => {
+                log!("Instruction: HarvestWithheldTokensToMint");
+                Self::process_harvest_withheld_tokens_to_mint(program_id, accounts)
+            }
+            TokenInstruction::UpdateRateInterestBearingMint { rate } => {
+                log!("Instruction: UpdateRateInterestBearingMint");
+                Self::process_update_rate_interest_bearing_mint(program_id, accounts, rate)
+            }
+            TokenInstruction::Pause => {
+                log!("Instruction: Pause");
+                Self::process_toggle_pause(program_id, accounts, true)
+            }
+            TokenInstruction::Resume => {
+                log!("Instruction: Resume");
+                Self::process_toggle_pause(program_id, accounts, false)
+            }
+            TokenInstruction::Reallocate => {
+                log!("Instruction: Reallocate");
+                Self::process_reallocate(program_id, accounts)
+            }
+            TokenInstruction::ReallocateMint => {
+                log!("Instruction: ReallocateMint");
+                Self::process_reallocate_mint(program_id, accounts)
+            }
+            TokenInstruction::InitializeImmutableOwner => {
+                log!("Instruction: InitializeImmutableOwner");
+                Self::process_initialize_immutable_owner(program_id, accounts)
+            }
+            TokenInstruction::EnableCpiGuard => {
+                log!("Instruction: EnableCpiGuard");
+                Self::process_toggle_cpi_guard(program_id, accounts, true)
+            }
+            TokenInstruction::DisableCpiGuard => {
+                log!("Instruction: DisableCpiGuard");
+                Self::process_toggle_cpi_guard(program_id, accounts, false)
+            }
+            TokenInstruction::InitializeTransferHook {
+                authority,
+                program_id: hook_program_id,
+            } => {
+                log!("Instruction: InitializeTransferHook");
+                Self::process_initialize_transfer_hook(
+                    program_id,
+                    accounts,
+                    authority,
+                    hook_program_id,
+                )
+            }
+            TokenInstruction::UpdateTransferHook {
+                program_id: hook_program_id,
+            } => {
+                log!("Instruction: UpdateTransferHook");
+                Self::process_update_transfer_hook(program_id, accounts, hook_program_id)
+            }
+            TokenInstruction::InitializeMetadataPointer { metadata_address } => {
+                log!("Instruction: InitializeMetadataPointer");
+                Self::process_initialize_metadata_pointer(program_id, accounts, metadata_address)
+            }
+            TokenInstruction::InitializeTokenMetadata { name, symbol, uri } => {
+                log!("Instruction: InitializeTokenMetadata");
+                Self::process_initialize_token_metadata(program_id, accounts, name, symbol, uri)
+            }
+            TokenInstruction::UpdateTokenMetadataField { field, value } => {
+                log!("Instruction: UpdateTokenMetadataField");
+                Self::process_update_token_metadata_field(program_id, accounts, field, value)
+            }
+            TokenInstruction::InitializeAssociatedAccount => {
+                log!("Instruction: InitializeAssociatedAccount");
+                Self::process_initialize_associated_account(program_id, accounts)
+            }
+            TokenInstruction::RecoverNested => {
+                log!("Instruction: RecoverNested");
+                Self::process_recover_nested(program_id, accounts)
+            }
+            TokenInstruction::WrapSol { lamports } => {
+                log!("Instruction: WrapSol");
+                Self::process_wrap_sol(program_id, accounts, lamports)
+            }
+            TokenInstruction::UnwrapSol { lamports } => {
+                log!("Instruction: UnwrapSol");
+                Self::process_unwrap_sol(program_id, accounts, lamports)
+            }
+            TokenInstruction::RevokeAll => {
+                log!("Instruction: RevokeAll");
+                Self::process_revoke_all(program_id, accounts)
+            }
+            TokenInstruction::AssertBalance { minimum, maximum } => {
+                log!("Instruction: AssertBalance");
+                Self::process_assert_balance(program_id, accounts, minimum, maximum)
+            }
+            TokenInstruction::AssertSupply { minimum, maximum } => {
+                log!("Instruction: AssertSupply");
+                Self::process_assert_supply(program_id, accounts, minimum, maximum)
             }
+            TokenInstruction::GetAccountBalance => {
+                log!("Instruction: GetAccountBalance");
+                Self::process_get_account_balance(program_id, accounts)
+            }
+            TokenInstruction::GetMintSupply => {
+                log!("Instruction: GetMintSupply");
+                Self::process_get_mint_supply(program_id, accounts)
+            }
+            TokenInstruction::SplitAccount { amount } => {
+                log!("Instruction: SplitAccount");
+                Self::process_split_account(program_id, accounts, amount)
+            }
+            TokenInstruction::MergeAccounts {
+                num_source_accounts,
+                close_sources,
+            } => {
+                log!("Instruction: MergeAccounts");
+                Self::process_merge_accounts(
+                    program_id,
+                    accounts,
+                    num_source_accounts,
+                    close_sources,
+                )
+            }
+            TokenInstruction::LockUntilSlot { slot } => {
+                log!("Instruction: LockUntilSlot");
+                Self::process_lock_until_slot(program_id, accounts, slot)
+            }
+            TokenInstruction::ApproveEscrowed { amount } => {
+                log!("Instruction: ApproveEscrowed");
+                Self::process_approve(program_id, accounts, amount, None, COption::None, true)
+            }
+            TokenInstruction::UpdateDecimals { decimals } => {
+                log!("Instruction: UpdateDecimals");
+                Self::process_update_decimals(program_id, accounts, decimals)
+            }
+            TokenInstruction::InitializePolicy { mode } => {
+                log!("Instruction: InitializePolicy");
+                Self::process_initialize_policy(program_id, accounts, mode)
+            }
+            TokenInstruction::AddPolicyEntry { entry } => {
+                log!("Instruction: AddPolicyEntry");
+                Self::process_add_policy_entry(program_id, accounts, entry)
+            }
+            TokenInstruction::RemovePolicyEntry { entry } => {
+                log!("Instruction: RemovePolicyEntry");
+                Self::process_remove_policy_entry(program_id, accounts, entry)
+            }
+            TokenInstruction::InitializeFlashMint { fee } => {
+                log!("Instruction: InitializeFlashMint");
+                Self::process_initialize_flash_mint(program_id, accounts, fee)
+            }
+            TokenInstruction::FlashMintTo { amount } => {
+                log!("Instruction: FlashMintTo");
+                Self::process_flash_mint_to(program_id, accounts, amount)
+            }
+            TokenInstruction::FlashBurnRepay { amount } => {
+                log!("Instruction: FlashBurnRepay");
+                Self::process_flash_burn_repay(program_id, accounts, amount)
+            }
+            TokenInstruction::SetSpendingLimit { amount } => {
+                log!("Instruction: SetSpendingLimit");
+                Self::process_set_spending_limit(program_id, accounts, amount)
+            }
+            TokenInstruction::TransferVested {
+                total_amount,
+                start_slot,
+                end_slot,
+            } => {
+                log!("Instruction: TransferVested");
+                Self::process_transfer_vested(
+                    program_id,
+                    accounts,
+                    total_amount,
+                    start_slot,
+                    end_slot,
+                )
+            }
+            TokenInstruction::ClaimVested => {
+                log!("Instruction: ClaimVested");
+                Self::process_claim_vested(program_id, accounts)
+            }
+            TokenInstruction::Swap { amount_a, amount_b } => {
+                log!("Instruction: Swap");
+                Self::process_swap(program_id, accounts, amount_a, amount_b)
+            }
+            TokenInstruction::Batch { instructions } => {
+                log!("Instruction: Batch");
+                Self::process_batch(program_id, accounts, &instructions)
+            }
+            TokenInstruction::FreezeAccounts { count } => {
+                log!("Instruction: FreezeAccounts");
+                Self::process_toggle_freeze_accounts(program_id, accounts, count, true)
+            }
+            TokenInstruction::ThawAccounts { count } => {
+                log!("Instruction: ThawAccounts");
+                Self::process_toggle_freeze_accounts(program_id, accounts, count, false)
+            }
+            TokenInstruction::BurnFrom { amount } => {
+                log!("Instruction: BurnFrom");
+                Self::process_burn_from(program_id, accounts, amount)
+            }
+        }
+    }
+
+    /// Hands each inner instruction the next run of accounts. Any failure
+    /// fails the whole instruction, so the runtime discards the inner
+    /// instructions that already ran.
+    fn process_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instructions: &[Vec<u8>],
+    ) -> ProgramResult {
+        let mut remaining = accounts;
+        for entry in instructions {
+            let (&account_count, data) = entry
+                .split_first()
+                .ok_or(TokenError::InvalidInstruction)?;
+            let instruction = TokenInstruction::unpack(data)?;
+            if let TokenInstruction::Batch { .. } = instruction {
+                return Err(TokenError::InvalidInstruction.into());
+            }
+            if remaining.len() < account_count as usize {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let (inner_accounts, rest) = remaining.split_at(account_count as usize);
+            Self::process_instruction(program_id, inner_accounts, instruction)?;
+            remaining = rest;
         }
+        Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_initialize_mint(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         decimals: u8,
         mint_authority: Pubkey,
+        freeze_authority: COption<Pubkey>,
+        max_supply: COption<u64>,
+        permanent_delegate: COption<Pubkey>,
+        transfer_fee_authority: COption<Pubkey>,
+        rate_authority: COption<Pubkey>,
+        pause_authority: COption<Pubkey>,
+        rent_sysvar_account: bool,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let mint_info = next_account_info(account_info_iter)?;
         let mint_data_len = mint_info.data_len();
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = if rent_sysvar_account {
+            Rent::from_account_info(next_account_info(account_info_iter)?)?
+        } else {
+            Rent::get()?
+        };
 
+        Self::check_account_owner(program_id, mint_info)?;
         let mut mint = Mint::unpack_unchecked(&mint_info.data.borrow())?;
         if mint.is_initialized {
             return Err(TokenError::AlreadyInUse.into());
@@ -69,24 +539,81 @@ impl Processor {
         if !rent.is_exempt(mint_info.lamports(), mint_data_len) {
             return Err(TokenError::NotRentExempt.into());
         }
+        if decimals > MAX_DECIMALS {
+            return Err(TokenError::InvalidDecimals.into());
+        }
+        Self::check_authority(&mint_authority)?;
+        for authority in [
+            freeze_authority,
+            permanent_delegate,
+            transfer_fee_authority,
+            rate_authority,
+            pause_authority,
+        ] {
+            if let COption::Some(authority) = authority {
+                Self::check_authority(&authority)?;
+            }
+        }
 
         mint.mint_authority = COption::Some(mint_authority);
         mint.decimals = decimals;
         mint.is_initialized = true;
+        mint.freeze_authority = freeze_authority;
+        mint.max_supply = max_supply;
+        mint.permanent_delegate = permanent_delegate;
+        // The shorter layouts have no room for the default state, and read
+        // as `Uninitialized`, which initializes accounts all the same
+        if LayoutVersion::of_mint(&mint_info.data.borrow()) == Some(LayoutVersion::V2) {
+            mint.default_account_state = AccountState::Initialized;
+        }
+        mint.transfer_fee_config = TransferFeeConfig {
+            transfer_fee_authority,
+            transfer_fee_basis_points: 0,
+            maximum_fee: 0,
+        };
+        // Interest accrues from now, at a zero rate until one is set
+        let unix_timestamp = if rate_authority.is_some() {
+            Clock::get()?.unix_timestamp
+        } else {
+            0
+        };
+        mint.interest_bearing_config = InterestBearingConfig {
+            rate_authority,
+            initialization_timestamp: unix_timestamp,
+            pre_update_average_rate: 0,
+            last_update_timestamp: unix_timestamp,
+            current_rate: 0,
+        };
+        mint.pause_authority = pause_authority;
+        mint.paused = false;
 
         Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    fn process_initialize_account(accounts: &[AccountInfo]) -> ProgramResult {
+    fn process_initialize_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        owner: Option<&Pubkey>,
+        rent_sysvar_account: bool,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let new_account_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let owner = next_account_info(account_info_iter)?.key;
+        let owner = if let Some(owner) = owner {
+            owner
+        } else {
+            next_account_info(account_info_iter)?.key
+        };
         let new_account_info_data_len = new_account_info.data_len();
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = if rent_sysvar_account {
+            Rent::from_account_info(next_account_info(account_info_iter)?)?
+        } else {
+            Rent::get()?
+        };
 
+        Self::check_account_owner(program_id, new_account_info)?;
         let mut account = Account::unpack_unchecked(&new_account_info.data.borrow())?;
         if account.is_initialized() {
             return Err(TokenError::AlreadyInUse.into());
@@ -95,330 +622,16186 @@ impl Processor {
         if !rent.is_exempt(new_account_info.lamports(), new_account_info_data_len) {
             return Err(TokenError::NotRentExempt.into());
         }
+        Self::check_authority(owner)?;
 
-        let _ = Mint::unpack(&mint_info.data.borrow_mut())
-                .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        let is_native_mint = Self::cmp_pubkeys(mint_info.key, &native_mint::id());
+        let (state, mint_required) = if is_native_mint {
+            (AccountState::Initialized, false)
+        } else {
+            Self::check_account_owner(program_id, mint_info)?;
+            let mint = Mint::unpack(&mint_info.data.borrow_mut())
+                    .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+            let state = match mint.default_account_state {
+                AccountState::Frozen => AccountState::Frozen,
+                _ => AccountState::Initialized,
+            };
+            // The fee and hook authorities and the policy are fixed by
+            // initialization, so no account can predate a mint that may charge
+            // a fee, run a hook or check a policy
+            let mint_required = mint.transfer_fee_config.transfer_fee_authority.is_some()
+                || mint.transfer_hook_authority.is_some()
+                || mint.transfer_hook_program_id.is_some()
+                || mint.transfer_policy.is_some();
+            (state, mint_required)
+        };
 
         account.mint = *mint_info.key;
         account.owner = *owner;
         account.delegate = COption::None;
         account.delegated_amount = 0;
-        account.state = AccountState::Initialized;
-        account.amount = 0;
+        account.state = state;
+        account.mint_required = mint_required;
+        if is_native_mint {
+            let rent_exempt_reserve = rent.minimum_balance(new_account_info_data_len);
+            account.is_native = COption::Some(rent_exempt_reserve);
+            account.amount = new_account_info
+                .lamports()
+                .checked_sub(rent_exempt_reserve)
+                .ok_or(TokenError::Overflow)?;
+        } else {
+            account.is_native = COption::None;
+            account.amount = 0;
+        }
 
         Account::pack(account, &mut new_account_info.data.borrow_mut())?;
 
         Ok(())
     }
 
+    fn process_initialize_multisig(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        m: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let multisig_info = next_account_info(account_info_iter)?;
+        let multisig_info_data_len = multisig_info.data_len();
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+        Self::check_account_owner(program_id, multisig_info)?;
+        let mut multisig = Multisig::unpack_unchecked(&multisig_info.data.borrow())?;
+        if multisig.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        if !rent.is_exempt(multisig_info.lamports(), multisig_info_data_len) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        let signer_infos = account_info_iter.as_slice();
+        if !is_valid_signer_index(signer_infos.len()) {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if !is_valid_signer_index(m as usize) || m as usize > signer_infos.len() {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+
+        multisig.m = m;
+        multisig.n = signer_infos.len() as u8;
+        for (i, signer_info) in signer_infos.iter().enumerate() {
+            multisig.signers[i] = *signer_info.key;
+        }
+        multisig.is_initialized = true;
+
+        Multisig::pack(multisig, &mut multisig_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
     fn process_transfer(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        expected_decimals: Option<u8>,
+        expected_fee: Option<u64>,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
         let source_account_info = next_account_info(account_info_iter)?;
+        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
+            Some((next_account_info(account_info_iter)?, expected_decimals))
+        } else {
+            None
+        };
         let dest_account_info = next_account_info(account_info_iter)?;
-        if source_account_info.key == dest_account_info.key {
-            return Err(TokenError::SelfTransfer.into());
-        }
+        let self_transfer = Self::cmp_pubkeys(source_account_info.key, dest_account_info.key);
 
         let authority_info = next_account_info(account_info_iter)?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, dest_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let original_source_account = source_account;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
 
-        if source_account.amount < amount {
-            return Err(TokenError::InsufficientFunds.into());
+        if source_account.is_frozen() || dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
         }
-        if source_account.mint != dest_account.mint {
+        if !Self::cmp_pubkeys(&source_account.mint, &dest_account.mint) {
             return Err(TokenError::MintMismatch.into());
         }
 
-        match source_account.delegate {
-            COption::Some(ref delegate) if authority_info.key == delegate => {
-                Self::validate_owner(
-                    delegate,
-                    authority_info,
-                )?;
+        // The permanent delegate, the transfer fee, pausing, the transfer hook
+        // and the transfer policy only apply when the mint is passed in, which
+        // accounts of a mint that may charge a fee, run a hook or check a
+        // policy require
+        let (permanent_delegate, transfer_fee_config, transfer_hook, transfer_policy) =
+            if let Some((mint_info, expected_decimals)) = expected_mint_info {
+                if !Self::cmp_pubkeys(mint_info.key, &source_account.mint) {
+                    return Err(TokenError::MintMismatch.into());
+                }
 
-                if source_account.delegated_amount < amount {
-                    return Err(TokenError::InsufficientFunds.into());
+                Self::check_account_owner(program_id, mint_info)?;
+                let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+                if expected_decimals != mint.decimals {
+                    return Err(TokenError::MintDecimalsMismatch.into());
                 }
-                
-                // Remove delegated amount from transfer authority
-                source_account.delegated_amount = source_account
-                    .delegated_amount
-                    .checked_sub(amount)
-                    .ok_or(TokenError::Overflow)?;
+                if mint.paused {
+                    return Err(TokenError::MintPaused.into());
+                }
+                let transfer_hook = Option::from(mint.transfer_hook_program_id)
+                    .map(|hook_program_id| (mint_info, hook_program_id));
+                (
+                    mint.permanent_delegate,
+                    mint.transfer_fee_config,
+                    transfer_hook,
+                    mint.transfer_policy,
+                )
+            } else {
+                Self::check_mint_not_required(&source_account)?;
+                Self::check_mint_not_required(&dest_account)?;
+                (
+                    COption::None,
+                    TransferFeeConfig::default(),
+                    None,
+                    COption::None,
+                )
+            };
 
-                if source_account.delegated_amount == 0 {
-                    source_account.delegate = COption::None;
+        // Resolve a sweep against the balance at execution time
+        let amount = if amount == TRANSFER_ALL {
+            match source_account.delegate {
+                COption::Some(ref delegate)
+                    if Self::cmp_pubkeys(authority_info.key, delegate)
+                        && !matches!(
+                            permanent_delegate,
+                            COption::Some(ref permanent_delegate)
+                                if Self::cmp_pubkeys(permanent_delegate, delegate)
+                        ) =>
+                {
+                    source_account.amount.min(source_account.delegated_amount)
                 }
+                _ => source_account.amount,
             }
-            _ => Self::validate_owner(
-                &source_account.owner,
-                authority_info,
-            )?,
+        } else {
+            amount
         };
+        if source_account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
 
-        source_account.amount = source_account
+        let fee = transfer_fee_config
+            .calculate_fee(amount)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if expected_fee.is_some_and(|expected_fee| expected_fee != fee) {
+            return Err(TokenError::FeeMismatch.into());
+        }
+
+        Self::validate_debit(
+            program_id,
+            &mut source_account,
+            permanent_delegate,
+            authority_info,
+            account_info_iter.as_slice(),
+            amount,
+        )?;
+
+        let event = TokenEvent::Transfer(TransferEvent {
+            source: *source_account_info.key,
+            destination: *dest_account_info.key,
+            authority: *authority_info.key,
+            amount,
+        });
+
+        // A self-transfer is validated like any other but leaves the account
+        // untouched, including the delegated amount
+        if self_transfer {
+            event.emit();
+            return Ok(());
+        }
+        if dest_account.memo_required {
+            Self::check_memo(account_info_iter.as_slice())?;
+        }
+        Self::check_transfer_policy(
+            program_id,
+            &transfer_policy,
+            &dest_account.owner,
+            account_info_iter.as_slice(),
+        )?;
+
+        let source_amount = source_account
             .amount
             .checked_sub(amount)
             .ok_or(TokenError::Overflow)?;
-        dest_account.amount = dest_account
+        // The fee stays with the destination, withheld from its balance
+        let dest_amount = dest_account
             .amount
-            .checked_add(amount)
+            .checked_add(amount - fee)
+            .ok_or(TokenError::Overflow)?;
+        let dest_withheld_amount = dest_account
+            .withheld_amount
+            .checked_add(fee)
             .ok_or(TokenError::Overflow)?;
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
-        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        if source_account.is_native() {
+            let source_starting_lamports = source_account_info.lamports();
+            **source_account_info.lamports.borrow_mut() = source_starting_lamports
+                .checked_sub(amount)
+                .ok_or(TokenError::Overflow)?;
+
+            let dest_starting_lamports = dest_account_info.lamports();
+            **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+                .checked_add(amount)
+                .ok_or(TokenError::Overflow)?;
+        }
+
+        // Only the balances, the delegation and the spending window change, so
+        // write them in place rather than re-packing both accounts. `V1` accounts are too short
+        // for the `Pod` view and are re-packed instead. An account left as it
+        // was, such as either side of a zero-amount transfer, is not written.
+        source_account.amount = source_amount;
+        if source_account != original_source_account {
+            if source_account_info.data_len() == Account::LEN {
+                let mut source_data = source_account_info.data.borrow_mut();
+                let source = PodAccount::from_bytes_mut(&mut source_data)?;
+                source.amount = source_amount.into();
+                source.delegate = source_account.delegate.into();
+                source.delegated_amount = source_account.delegated_amount.into();
+                source.expiry_slot = source_account.expiry_slot.map(PodU64::from).into();
+                source.lock_until = source_account.lock_until.map(PodU64::from).into();
+                source.escrowed = source_account.escrowed.into();
+                source.window_start_epoch = source_account.window_start_epoch.into();
+                source.spent_in_window = source_account.spent_in_window.into();
+            } else {
+                Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+            }
+        }
+        if amount != 0 {
+            if dest_account_info.data_len() == Account::LEN {
+                let mut dest_data = dest_account_info.data.borrow_mut();
+                let dest = PodAccount::from_bytes_mut(&mut dest_data)?;
+                dest.amount = dest_amount.into();
+                dest.withheld_amount = dest_withheld_amount.into();
+            } else {
+                // Has no room for a withheld fee, so only fee-free transfers land
+                dest_account.amount = dest_amount;
+                dest_account.withheld_amount = dest_withheld_amount;
+                Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+            }
+        }
+
+        // Runs on the updated balances; if the hook fails, the whole transfer
+        // is rolled back
+        if let Some((mint_info, hook_program_id)) = transfer_hook {
+            Self::invoke_transfer_hook(
+                &hook_program_id,
+                source_account_info,
+                mint_info,
+                dest_account_info,
+                authority_info,
+                account_info_iter.as_slice(),
+                amount,
+            )?;
+        }
+        event.emit();
+
+        Ok(())
+    }
+
+    /// The common `Transfer`: the owner moving tokens between two plain
+    /// accounts of a mint, done on the account data in place rather than
+    /// unpacking both accounts. Returns whether it made the transfer.
+    /// Anything else, including every input `process_transfer` rejects, is
+    /// left untouched for `process_transfer`, which writes the same bytes for
+    /// the transfers covered here. A zero amount is left to it too, since it
+    /// writes nothing for one.
+    fn transfer_in_place(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> bool {
+        let (source_account_info, dest_account_info, authority_info) = match accounts {
+            [source, dest, authority, ..] if amount != 0 => (source, dest, authority),
+            _ => return false,
+        };
+        if Self::cmp_pubkeys(source_account_info.key, dest_account_info.key)
+            || Self::check_account_owner(program_id, source_account_info).is_err()
+            || Self::check_account_owner(program_id, dest_account_info).is_err()
+            || !authority_info.is_signer
+            || (Self::cmp_pubkeys(authority_info.owner, program_id)
+                && authority_info.data_len() == Multisig::get_packed_len())
+        {
+            return false;
+        }
+        let (mut source_data, mut dest_data) = match (
+            source_account_info.data.try_borrow_mut(),
+            dest_account_info.data.try_borrow_mut(),
+        ) {
+            (Ok(source_data), Ok(dest_data)) => (source_data, dest_data),
+            _ => return false,
+        };
+        let (source, dest) = match (
+            PodAccount::from_bytes_mut(&mut source_data),
+            PodAccount::from_bytes_mut(&mut dest_data),
+        ) {
+            (Ok(source), Ok(dest)) => (source, dest),
+            _ => return false,
+        };
+
+        // Locks, delegations, spending limits, the CPI guard, memos, native
+        // balances and accounts that require the mint all go the full way.
+        // The options `process_transfer` rewrites must already be in the form
+        // it writes them in.
+        let initialized = AccountState::Initialized as u8;
+        if !source.is_valid()
+            || !dest.is_valid()
+            || source.state != initialized
+            || dest.state != initialized
+            || !Self::cmp_pubkeys(&source.mint, &dest.mint)
+            || !Self::cmp_pubkeys(&source.owner, authority_info.key)
+            || source.delegate != PodCOption::none()
+            || source.expiry_slot != PodCOption::none()
+            || source.lock_until != PodCOption::none()
+            || !source.limit_amount.is_none()
+            || !source.is_native.is_none()
+            || bool::from(source.escrowed)
+            || bool::from(source.cpi_guard)
+            || bool::from(dest.memo_required)
+            || bool::from(source.mint_required)
+            || bool::from(dest.mint_required)
+        {
+            return false;
+        }
+
+        let source_amount = u64::from(source.amount);
+        let amount = if amount == TRANSFER_ALL {
+            source_amount
+        } else {
+            amount
+        };
+        match (
+            source_amount.checked_sub(amount),
+            u64::from(dest.amount).checked_add(amount),
+        ) {
+            (Some(source_amount), Some(dest_amount)) => {
+                source.amount = source_amount.into();
+                dest.amount = dest_amount.into();
+                TokenEvent::Transfer(TransferEvent {
+                    source: *source_account_info.key,
+                    destination: *dest_account_info.key,
+                    authority: *authority_info.key,
+                    amount,
+                })
+                .emit();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs each leg as a `Transfer` over its source, destination and
+    /// authority followed by the shared trailing accounts. A failing second
+    /// leg fails the instruction, which rolls back the first.
+    fn process_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount_a: u64,
+        amount_b: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_a_info = next_account_info(account_info_iter)?;
+        let dest_a_info = next_account_info(account_info_iter)?;
+        let source_b_info = next_account_info(account_info_iter)?;
+        let dest_b_info = next_account_info(account_info_iter)?;
+        let authority_a_info = next_account_info(account_info_iter)?;
+        let authority_b_info = next_account_info(account_info_iter)?;
+        let trailing = account_info_iter.as_slice();
+
+        for (source_info, dest_info, authority_info, amount) in [
+            (source_a_info, dest_a_info, authority_a_info, amount_a),
+            (source_b_info, dest_b_info, authority_b_info, amount_b),
+        ] {
+            let mut leg = vec![
+                source_info.clone(),
+                dest_info.clone(),
+                authority_info.clone(),
+            ];
+            leg.extend_from_slice(trailing);
+            Self::process_transfer(program_id, &leg, amount, None, None)?;
+        }
+
+        Ok(())
+    }
+
+    fn process_transfer_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let dest_account_infos = amounts
+            .iter()
+            .map(|_| next_account_info(account_info_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        Self::check_mint_not_required(&source_account)?;
+
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(TokenError::Overflow)?;
+        if source_account.amount < total {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+
+        // Every destination is checked before any balance changes
+        let mut dest_accounts = Vec::with_capacity(dest_account_infos.len());
+        for dest_account_info in dest_account_infos.iter() {
+            Self::check_account_owner(program_id, dest_account_info)?;
+            let dest_account =
+                Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+            if dest_account.is_frozen() {
+                return Err(TokenError::AccountFrozen.into());
+            }
+            if !Self::cmp_pubkeys(&dest_account.mint, &source_account.mint) {
+                return Err(TokenError::MintMismatch.into());
+            }
+            Self::check_mint_not_required(&dest_account)?;
+            dest_accounts.push(dest_account);
+        }
+        if dest_accounts.iter().any(|dest_account| dest_account.memo_required) {
+            Self::check_memo(account_info_iter.as_slice())?;
+        }
+
+        Self::validate_debit(
+            program_id,
+            &mut source_account,
+            COption::None,
+            authority_info,
+            account_info_iter.as_slice(),
+            total,
+        )?;
+        source_account.amount -= total;
+
+        // Credits are gathered per account first, so a destination listed
+        // twice, or the source itself, ends up with the right balance
+        let mut updated = vec![(source_account_info, source_account)];
+        for ((dest_account_info, dest_account), amount) in dest_account_infos
+            .iter()
+            .zip(dest_accounts)
+            .zip(amounts)
+        {
+            let position = match updated
+                .iter()
+                .position(|(info, _)| Self::cmp_pubkeys(info.key, dest_account_info.key))
+            {
+                Some(position) => position,
+                None => {
+                    updated.push((*dest_account_info, dest_account));
+                    updated.len() - 1
+                }
+            };
+            let account = &mut updated[position].1;
+            account.amount = account
+                .amount
+                .checked_add(*amount)
+                .ok_or(TokenError::Overflow)?;
+        }
+
+        // Wrapped SOL moves as lamports alongside the amount
+        if source_account.is_native() {
+            for (dest_account_info, amount) in dest_account_infos.iter().zip(amounts) {
+                let source_starting_lamports = source_account_info.lamports();
+                **source_account_info.lamports.borrow_mut() = source_starting_lamports
+                    .checked_sub(*amount)
+                    .ok_or(TokenError::Overflow)?;
+                let dest_starting_lamports = dest_account_info.lamports();
+                **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+                    .checked_add(*amount)
+                    .ok_or(TokenError::Overflow)?;
+            }
+        }
+
+        for (account_info, account) in updated {
+            Account::pack(account, &mut account_info.data.borrow_mut())?;
+        }
 
         Ok(())
     }
 
     fn process_approve(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        expected_decimals: Option<u8>,
+        expiry_slot: COption<u64>,
+        escrowed: bool,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
         let source_account_info = next_account_info(account_info_iter)?;
+        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
+            Some((next_account_info(account_info_iter)?, expected_decimals))
+        } else {
+            None
+        };
         let delegate_info = next_account_info(account_info_iter)?;
         let owner_info = next_account_info(account_info_iter)?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let original_source_account = source_account;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        if let Some((mint_info, expected_decimals)) = expected_mint_info {
+            if !Self::cmp_pubkeys(mint_info.key, &source_account.mint) {
+                return Err(TokenError::MintMismatch.into());
+            }
+
+            Self::check_account_owner(program_id, mint_info)?;
+            let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
 
         Self::validate_owner(
+            program_id,
             &source_account.owner,
             owner_info,
+            account_info_iter.as_slice(),
         )?;
+        // An owner spending as its own delegate would eat into the allowance
+        if Self::cmp_pubkeys(delegate_info.key, &source_account.owner)
+            || Self::cmp_pubkeys(delegate_info.key, source_account_info.key)
+        {
+            return Err(TokenError::InvalidDelegate.into());
+        }
 
-        source_account.delegate = COption::Some(*delegate_info.key);
+        // Approving nothing is the same as revoking
+        if amount == 0 {
+            source_account.delegate = COption::None;
+            source_account.expiry_slot = COption::None;
+        } else {
+            Self::check_cpi_guard(&source_account)?;
+            Self::check_lock(&mut source_account)?;
+            if escrowed && amount > source_account.amount {
+                return Err(TokenError::InsufficientFunds.into());
+            }
+            source_account.delegate = COption::Some(*delegate_info.key);
+            source_account.expiry_slot = expiry_slot;
+        }
         source_account.delegated_amount = amount;
+        source_account.escrowed = escrowed && amount != 0;
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        // Re-approving the same delegation writes nothing
+        Self::pack_if_changed(source_account, &original_source_account, source_account_info)?;
+        TokenEvent::Approve(ApproveEvent {
+            source: *source_account_info.key,
+            delegate: *delegate_info.key,
+            owner: *owner_info.key,
+            amount,
+        })
+        .emit();
 
         Ok(())
     }
 
-    fn process_mint_to(
+    /// Adjusts the allowance of the current delegate relative to what is left
+    /// of it, so a delegate spending ahead of the change cannot end up with
+    /// more than the owner intended
+    fn process_change_delegated_amount(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        increase: bool,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let mint_info = next_account_info(account_info_iter)?;
-        let dest_account_info = next_account_info(account_info_iter)?;
+
+        let source_account_info = next_account_info(account_info_iter)?;
         let owner_info = next_account_info(account_info_iter)?;
 
-        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
-        if mint_info.key != &dest_account.mint {
-            return Err(TokenError::MintMismatch.into());
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let original_source_account = source_account;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
         }
 
-        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
-        match mint.mint_authority {
-            COption::Some(mint_authority) => Self::validate_owner(
-                &mint_authority,
-                owner_info,
-            )?,
-            COption::None => return Err(TokenError::FixedSupply.into()),
+        Self::validate_owner(
+            program_id,
+            &source_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        if source_account.delegate.is_none() {
+            return Err(TokenError::InvalidState.into());
+        }
+        if increase {
+            Self::check_cpi_guard(&source_account)?;
+            Self::check_lock(&mut source_account)?;
+        }
+        source_account.delegated_amount = if increase {
+            source_account
+                .delegated_amount
+                .checked_add(amount)
+                .ok_or(TokenError::Overflow)?
+        } else {
+            source_account
+                .delegated_amount
+                .checked_sub(amount)
+                .ok_or(TokenError::InsufficientDelegatedFunds)?
+        };
+        if source_account.delegated_amount == 0 {
+            source_account.delegate = COption::None;
+            source_account.expiry_slot = COption::None;
+            source_account.escrowed = false;
         }
 
-        dest_account.amount = dest_account
-            .amount
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
+        Self::pack_if_changed(source_account, &original_source_account, source_account_info)?;
 
-        mint.supply = mint
-            .supply
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
+        Ok(())
+    }
 
-        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
-        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+    fn process_revoke(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let original_source_account = source_account;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        // The current delegate may renounce its own delegation, escrowed or not
+        let authority = match source_account.delegate {
+            COption::Some(delegate) if Self::cmp_pubkeys(owner_info.key, &delegate) => delegate,
+            _ => source_account.owner,
+        };
+        Self::validate_owner(
+            program_id,
+            &authority,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        source_account.delegate = COption::None;
+        source_account.delegated_amount = 0;
+        source_account.expiry_slot = COption::None;
+        source_account.escrowed = false;
+
+        Self::pack_if_changed(source_account, &original_source_account, source_account_info)?;
 
         Ok(())
     }
 
-    fn process_burn(
+    fn process_split_account(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-
         let source_account_info = next_account_info(account_info_iter)?;
+        let new_account_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        if source_account.amount < amount {
-            return Err(TokenError::InsufficientFunds.into());
-        }
-        if mint_info.key != &source_account.mint {
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        if !Self::cmp_pubkeys(&source_account.mint, mint_info.key) {
             return Err(TokenError::MintMismatch.into());
         }
+        // Moving a native balance would mean moving its lamports as well
+        if source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &source_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        Self::check_cpi_guard(&source_account)?;
+        Self::check_lock(&mut source_account)?;
+        // The owner keeps the tokens, but may since have lost the policy's
+        // permission to receive them
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        Self::check_transfer_policy(
+            program_id,
+            &mint.transfer_policy,
+            &source_account.owner,
+            account_info_iter.as_slice(),
+        )?;
+        Self::check_unlocked_funds(&source_account, amount)?;
+        Self::check_spending_limit(&mut source_account, amount)?;
+
+        Self::process_initialize_account(
+            program_id,
+            &[new_account_info.clone(), mint_info.clone()],
+            Some(&source_account.owner),
+            false,
+        )?;
+        let mut new_account = Account::unpack(&new_account_info.data.borrow())?;
+        // The mint may start new accounts frozen
+        if new_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
 
-        match source_account.delegate {
-            COption::Some(ref delegate) if authority_info.key == delegate => {
-                Self::validate_owner(
-                    delegate,
-                    authority_info,
-                )?;
+        source_account.amount = source_account
+            .amount
+            .checked_sub(amount)
+            .ok_or(TokenError::InsufficientFunds)?;
+        new_account.amount = amount;
 
-                if source_account.delegated_amount < amount {
-                    return Err(TokenError::InsufficientFunds.into());
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        Account::pack(new_account, &mut new_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_merge_accounts(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        num_source_accounts: u8,
+        close_sources: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let source_account_infos = (0..num_source_accounts)
+            .map(|_| next_account_info(account_info_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signers = account_info_iter.as_slice();
+
+        Self::check_account_owner(program_id, dest_account_info)?;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+        if dest_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        Self::check_mint_not_required(&dest_account)?;
+        Self::validate_owner(program_id, &dest_account.owner, owner_info, signers)?;
+
+        // Every source is checked before any balance changes
+        let mut source_accounts = Vec::with_capacity(source_account_infos.len());
+        for (i, source_account_info) in source_account_infos.iter().enumerate() {
+            if Self::cmp_pubkeys(source_account_info.key, dest_account_info.key)
+                || source_account_infos[..i]
+                    .iter()
+                    .any(|other| Self::cmp_pubkeys(other.key, source_account_info.key))
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Self::check_account_owner(program_id, source_account_info)?;
+            let mut source_account =
+                Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+            if !Self::cmp_pubkeys(&source_account.mint, &dest_account.mint) {
+                return Err(TokenError::MintMismatch.into());
+            }
+            if !Self::cmp_pubkeys(&source_account.owner, &dest_account.owner) {
+                return Err(TokenError::OwnerMismatch.into());
+            }
+            if source_account.is_frozen() {
+                return Err(TokenError::AccountFrozen.into());
+            }
+            Self::check_mint_not_required(&source_account)?;
+            Self::check_cpi_guard(&source_account)?;
+            Self::check_lock(&mut source_account)?;
+            let source_amount = source_account.amount;
+            Self::check_unlocked_funds(&source_account, source_amount)?;
+            Self::check_spending_limit(&mut source_account, source_amount)?;
+            if close_sources {
+                if source_account.withheld_amount != 0 {
+                    return Err(TokenError::AccountHasWithheldTransferFees.into());
                 }
-                source_account.delegated_amount = source_account
-                    .delegated_amount
-                    .checked_sub(amount)
-                    .ok_or(TokenError::Overflow)?;
-                if source_account.delegated_amount == 0 {
-                    source_account.delegate = COption::None;
+                if source_account.vesting_amount != 0 {
+                    return Err(TokenError::VestingInProgress.into());
                 }
+                Self::validate_close_authority(program_id, &source_account, owner_info, signers)?;
             }
-            _ => Self::validate_owner(
-                &source_account.owner,
-                authority_info,
-            )?,
+            dest_account.amount = dest_account
+                .amount
+                .checked_add(source_account.amount)
+                .ok_or(TokenError::Overflow)?;
+            source_accounts.push(source_account);
+        }
+
+        for (source_account_info, mut source_account) in
+            source_account_infos.into_iter().zip(source_accounts)
+        {
+            if close_sources {
+                let dest_starting_lamports = dest_account_info.lamports();
+                **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+                    .checked_add(source_account_info.lamports())
+                    .ok_or(TokenError::Overflow)?;
+                **source_account_info.lamports.borrow_mut() = 0;
+                source_account_info.data.borrow_mut().fill(0);
+            } else {
+                source_account.amount = 0;
+                Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+            }
+        }
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_lock_until_slot(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        slot: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        // A lock still running can only be pushed further out
+        if let COption::Some(lock_until) = account.lock_until {
+            if slot < lock_until && Clock::get()?.slot < lock_until {
+                return Err(TokenError::AccountLocked.into());
+            }
+        }
+        account.lock_until = COption::Some(slot);
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_set_spending_limit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: COption<u64>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        // What was spent this epoch still counts against the new limit
+        account.limit_amount = amount;
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_transfer_vested(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        total_amount: u64,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if start_slot >= end_slot
+            || Self::cmp_pubkeys(source_account_info.key, dest_account_info.key)
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, dest_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+        if source_account.is_frozen() || dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if !Self::cmp_pubkeys(&source_account.mint, &dest_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if source_account.is_native() || dest_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        Self::check_mint_not_required(&source_account)?;
+        Self::check_mint_not_required(&dest_account)?;
+        // One schedule per account, replaced only once fully claimed
+        if dest_account.vesting_amount != 0 {
+            return Err(TokenError::VestingInProgress.into());
+        }
+        if source_account.amount < total_amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+
+        Self::validate_debit(
+            program_id,
+            &mut source_account,
+            COption::None,
+            authority_info,
+            account_info_iter.as_slice(),
+            total_amount,
+        )?;
+        if dest_account.memo_required {
+            Self::check_memo(account_info_iter.as_slice())?;
         }
 
         source_account.amount = source_account
             .amount
-            .checked_sub(amount)
+            .checked_sub(total_amount)
             .ok_or(TokenError::Overflow)?;
+        dest_account.vesting_amount = total_amount;
+        dest_account.vesting_total = total_amount;
+        dest_account.vesting_start_slot = start_slot;
+        dest_account.vesting_end_slot = end_slot;
 
-        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
-        mint.supply = mint
-            .supply
-            .checked_sub(amount)
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_claim_vested(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        // Nothing left to claim is not an error; the clock is only read
+        // when there is
+        if account.vesting_amount == 0 {
+            return Ok(());
+        }
+        let claimable = account.claimable_vested_amount(Clock::get()?.slot);
+        account.vesting_amount -= claimable;
+        account.amount = account
+            .amount
+            .checked_add(claimable)
             .ok_or(TokenError::Overflow)?;
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
-        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    fn validate_owner(
-        expected_owner: &Pubkey,
-        owner_account_info: &AccountInfo
+    fn process_update_decimals(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        decimals: u8,
     ) -> ProgramResult {
-        if expected_owner != owner_account_info.key {
-            return Err(TokenError::OwnerMismatch.into());
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let mint_authority_info = next_account_info(account_info_iter)?;
+
+        if decimals > MAX_DECIMALS {
+            return Err(TokenError::InvalidDecimals.into());
         }
-        if !owner_account_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                mint_authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+        // Amounts already out would change value
+        if mint.supply != 0 {
+            return Err(TokenError::MintHasSupply.into());
         }
+
+        mint.decimals = decimals;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::instruction::*;
-    use solana_program::{
-        account_info::IntoAccountInfo, clock::Epoch, instruction::Instruction, sysvar::rent,
-    };
-    use solana_sdk::account::{
-        create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
-    };
+    fn process_initialize_policy(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        mode: PolicyMode,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let policy_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, policy_info)?;
+        let mut policy = TransferPolicy::unpack_unchecked(&policy_info.data.borrow())?;
+        if policy.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !Rent::get()?.is_exempt(policy_info.lamports(), policy_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Mint::unpack_unchecked(&mint_info.data.borrow())?;
+        if mint.is_initialized || mint.transfer_policy.is_some() {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        policy.mint = *mint_info.key;
+        policy.mode = mode;
+        policy.is_initialized = true;
+        TransferPolicy::pack(policy, &mut policy_info.data.borrow_mut())?;
+        // `InitializeMint` keeps the policy when it fills in the rest, so
+        // every account of the mint is marked as requiring it
+        mint.transfer_policy = COption::Some(*policy_info.key);
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
 
-    fn do_process_instruction(
-        instruction: Instruction,
-        accounts: Vec<&mut SolanaAccount>,
+    fn process_add_policy_entry(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        entry: Pubkey,
     ) -> ProgramResult {
-        let mut meta = instruction
-            .accounts
-            .iter()
-            .zip(accounts)
-            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
-            .collect::<Vec<_>>();
+        let (policy_info, mut policy) = Self::unpack_policy_for_update(program_id, accounts)?;
+        if policy.entries().iter().any(|listed| Self::cmp_pubkeys(listed, &entry)) {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if policy.len as usize == MAX_POLICY_ENTRIES {
+            return Err(TokenError::PolicyFull.into());
+        }
 
-        let account_infos = create_is_signer_account_infos(&mut meta);
-        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+        policy.entries[policy.len as usize] = entry;
+        policy.len += 1;
+        TransferPolicy::pack(policy, &mut policy_info.data.borrow_mut())?;
+
+        Ok(())
     }
 
-    fn do_process_instruction_dups(
-        instruction: Instruction,
-        account_infos: Vec<AccountInfo>,
+    fn process_remove_policy_entry(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        entry: Pubkey,
     ) -> ProgramResult {
-        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+        let (policy_info, mut policy) = Self::unpack_policy_for_update(program_id, accounts)?;
+        let index = policy
+            .entries()
+            .iter()
+            .position(|listed| Self::cmp_pubkeys(listed, &entry))
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        // Entries are unordered, so the last one fills the gap
+        let last = policy.len as usize - 1;
+        policy.entries[index] = policy.entries[last];
+        policy.entries[last] = Pubkey::default();
+        policy.len -= 1;
+        TransferPolicy::pack(policy, &mut policy_info.data.borrow_mut())?;
+
+        Ok(())
     }
 
-    fn rent_sysvar() -> SolanaAccount {
-        create_account_for_test(&Rent::default())
+    /// Unpacks the policy an `AddPolicyEntry` or `RemovePolicyEntry` edits,
+    /// once it is known to belong to the mint passed with it and the mint's
+    /// freeze authority has signed
+    fn unpack_policy_for_update<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<(&'a AccountInfo<'b>, TransferPolicy), ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let policy_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let freeze_authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, policy_info)?;
+        let policy = Self::unpack_initialized::<TransferPolicy>(&policy_info.data.borrow())?;
+        if !Self::cmp_pubkeys(&policy.mint, mint_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        Self::validate_freeze_authority(
+            program_id,
+            mint_info,
+            freeze_authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        Ok((policy_info, policy))
     }
 
-    fn mint_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(Mint::get_packed_len())
+    /// Fails unless the mint has a freeze authority and it signed
+    fn validate_freeze_authority(
+        program_id: &Pubkey,
+        mint_info: &AccountInfo,
+        freeze_authority_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.freeze_authority {
+            COption::Some(freeze_authority) => Self::validate_owner(
+                program_id,
+                &freeze_authority,
+                freeze_authority_info,
+                signers,
+            ),
+            COption::None => Err(TokenError::MintCannotFreeze.into()),
+        }
     }
 
-    fn account_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(Account::get_packed_len())
+    fn process_revoke_all(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        account.delegate = COption::None;
+        account.delegated_amount = 0;
+        account.expiry_slot = COption::None;
+        account.escrowed = false;
+        account.close_authority = COption::None;
+        account.pending_owner = COption::None;
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
     }
 
-    #[test]
-    fn test_pack_unpack_mint() {
-        // Mint
-        let mint = Mint {
-            mint_authority: COption::Some(Pubkey::new(&[1; 32])),
-            supply: 42,
-            decimals: 7,
+    fn process_set_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        authority_type: AuthorityType,
+        new_authority: COption<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        // Removing an authority takes `None`
+        if let COption::Some(new_authority) = new_authority {
+            Self::check_authority(&new_authority)?;
+        }
+
+        Self::check_account_owner(program_id, account_info)?;
+        if LayoutVersion::of_account(&account_info.data.borrow()).is_some() {
+            let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+            if account.is_frozen() {
+                return Err(TokenError::AccountFrozen.into());
+            }
+
+            match authority_type {
+                AuthorityType::AccountOwner => {
+                    if account.immutable_owner {
+                        return Err(TokenError::ImmutableOwner.into());
+                    }
+                    Self::validate_owner(
+                        program_id,
+                        &account.owner,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    Self::check_cpi_guard(&account)?;
+
+                    if let COption::Some(authority) = new_authority {
+                        account.owner = authority;
+                    } else {
+                        return Err(TokenError::InvalidInstruction.into());
+                    }
+
+                    // Approvals granted by the previous owner do not carry over,
+                    // and neither does a proposal they made
+                    account.delegate = COption::None;
+                    account.delegated_amount = 0;
+                    account.expiry_slot = COption::None;
+                    account.escrowed = false;
+                    account.pending_owner = COption::None;
+                }
+                AuthorityType::CloseAccount => {
+                    Self::validate_close_authority(
+                        program_id,
+                        &account,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    account.close_authority = new_authority;
+                }
+                AuthorityType::MintTokens | AuthorityType::FreezeAccount => {
+                    return Err(TokenError::AuthorityTypeNotSupported.into())
+                }
+            }
+            Account::pack(account, &mut account_info.data.borrow_mut())?;
+        } else if LayoutVersion::of_mint(&account_info.data.borrow()).is_some() {
+            let mut mint = Self::unpack_initialized::<Mint>(&account_info.data.borrow())?;
+
+            match authority_type {
+                AuthorityType::MintTokens => {
+                    // Once renounced, the mint authority can never be set again
+                    let mint_authority = mint
+                        .mint_authority
+                        .ok_or(Into::<ProgramError>::into(TokenError::FixedSupply))?;
+                    Self::validate_owner(
+                        program_id,
+                        &mint_authority,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    mint.mint_authority = new_authority;
+                }
+                AuthorityType::FreezeAccount => {
+                    // Like the mint authority, a renounced freeze authority is
+                    // gone for good
+                    let freeze_authority = mint
+                        .freeze_authority
+                        .ok_or(Into::<ProgramError>::into(TokenError::MintCannotFreeze))?;
+                    Self::validate_owner(
+                        program_id,
+                        &freeze_authority,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    mint.freeze_authority = new_authority;
+                }
+                AuthorityType::AccountOwner | AuthorityType::CloseAccount => {
+                    return Err(TokenError::AuthorityTypeNotSupported.into())
+                }
+            }
+            Mint::pack(mint, &mut account_info.data.borrow_mut())?;
+        } else {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    fn process_set_default_account_state(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        state: AccountState,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let freeze_authority_info = next_account_info(account_info_iter)?;
+
+        if state == AccountState::Uninitialized {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.freeze_authority {
+            COption::Some(freeze_authority) => Self::validate_owner(
+                program_id,
+                &freeze_authority,
+                freeze_authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::MintCannotFreeze.into()),
+        }
+
+        mint.default_account_state = state;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_set_transfer_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        transfer_fee_basis_points: u16,
+        maximum_fee: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if transfer_fee_basis_points > MAX_FEE_BASIS_POINTS {
+            return Err(TokenError::TransferFeeExceedsMaximum.into());
+        }
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.transfer_fee_config.transfer_fee_authority {
+            COption::Some(transfer_fee_authority) => Self::validate_owner(
+                program_id,
+                &transfer_fee_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::NoTransferFeeAuthority.into()),
+        }
+
+        mint.transfer_fee_config.transfer_fee_basis_points = transfer_fee_basis_points;
+        mint.transfer_fee_config.maximum_fee = maximum_fee;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_withdraw_withheld_tokens_from_accounts(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        num_token_accounts: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        // Multisig signers sit between the authority and the sources
+        let remaining = account_info_iter.as_slice();
+        let signers_len = remaining
+            .len()
+            .checked_sub(num_token_accounts as usize)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let (signers, source_account_infos) = remaining.split_at(signers_len);
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.transfer_fee_config.transfer_fee_authority {
+            COption::Some(transfer_fee_authority) => {
+                Self::validate_owner(program_id, &transfer_fee_authority, authority_info, signers)?
+            }
+            COption::None => return Err(TokenError::NoTransferFeeAuthority.into()),
+        }
+
+        Self::check_account_owner(program_id, dest_account_info)?;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+        if !Self::cmp_pubkeys(&dest_account.mint, mint_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        // Fees already harvested to the mint go out with the rest. Nothing is
+        // written until every source has been checked and the destination
+        // credited, with a repeated source giving up its fees once.
+        let mut withdrawn = mint.withheld_amount;
+        mint.withheld_amount = 0;
+        let mut cleared: Vec<(&AccountInfo, Account)> =
+            Vec::with_capacity(source_account_infos.len());
+        for source_account_info in source_account_infos {
+            // The destination may list itself, and its fees are then kept in
+            // the account already unpacked above
+            if Self::cmp_pubkeys(source_account_info.key, dest_account_info.key) {
+                withdrawn = withdrawn
+                    .checked_add(dest_account.withheld_amount)
+                    .ok_or(TokenError::Overflow)?;
+                dest_account.withheld_amount = 0;
+                continue;
+            }
+            if cleared
+                .iter()
+                .any(|(info, _)| Self::cmp_pubkeys(info.key, source_account_info.key))
+            {
+                continue;
+            }
+            Self::check_account_owner(program_id, source_account_info)?;
+            let mut source_account =
+                Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+            if !Self::cmp_pubkeys(&source_account.mint, mint_info.key) {
+                return Err(TokenError::MintMismatch.into());
+            }
+            withdrawn = withdrawn
+                .checked_add(source_account.withheld_amount)
+                .ok_or(TokenError::Overflow)?;
+            source_account.withheld_amount = 0;
+            cleared.push((source_account_info, source_account));
+        }
+
+        dest_account.amount = dest_account
+            .amount
+            .checked_add(withdrawn)
+            .ok_or(TokenError::Overflow)?;
+        for (source_account_info, source_account) in cleared {
+            Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        }
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_harvest_withheld_tokens_to_mint(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+
+        // Nothing is written until every source has been checked, with a
+        // repeated source giving up its fees once
+        let source_account_infos = account_info_iter.as_slice();
+        let mut cleared: Vec<(&AccountInfo, Account)> =
+            Vec::with_capacity(source_account_infos.len());
+        for source_account_info in source_account_infos {
+            if cleared
+                .iter()
+                .any(|(info, _)| Self::cmp_pubkeys(info.key, source_account_info.key))
+            {
+                continue;
+            }
+            Self::check_account_owner(program_id, source_account_info)?;
+            let mut source_account =
+                Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+            if !Self::cmp_pubkeys(&source_account.mint, mint_info.key) {
+                return Err(TokenError::MintMismatch.into());
+            }
+            mint.withheld_amount = mint
+                .withheld_amount
+                .checked_add(source_account.withheld_amount)
+                .ok_or(TokenError::Overflow)?;
+            source_account.withheld_amount = 0;
+            cleared.push((source_account_info, source_account));
+        }
+
+        for (source_account_info, source_account) in cleared {
+            Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        }
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_update_rate_interest_bearing_mint(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        rate: i16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        let config = &mut mint.interest_bearing_config;
+        match config.rate_authority {
+            COption::Some(rate_authority) => Self::validate_owner(
+                program_id,
+                &rate_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::NoRateAuthority.into()),
+        }
+
+        // Fold the interest accrued at the old rate into the average, so the
+        // new rate only applies from now on
+        let unix_timestamp = Clock::get()?.unix_timestamp;
+        config.pre_update_average_rate = config
+            .time_weighted_average_rate(unix_timestamp)
+            .ok_or(TokenError::Overflow)?;
+        config.last_update_timestamp = unix_timestamp;
+        config.current_rate = rate;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_toggle_pause(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        paused: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.pause_authority {
+            COption::Some(pause_authority) => Self::validate_owner(
+                program_id,
+                &pause_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::NoPauseAuthority.into()),
+        }
+
+        mint.paused = paused;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_initialize_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        authority: COption<Pubkey>,
+        hook_program_id: COption<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Mint::unpack_unchecked(&mint_info.data.borrow())?;
+        if mint.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        // `InitializeMint` keeps the hook when it fills in the rest
+        mint.transfer_hook_authority = authority;
+        mint.transfer_hook_program_id = hook_program_id;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_update_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        hook_program_id: COption<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.transfer_hook_authority {
+            COption::Some(transfer_hook_authority) => Self::validate_owner(
+                program_id,
+                &transfer_hook_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::NoTransferHookAuthority.into()),
+        }
+
+        mint.transfer_hook_program_id = hook_program_id;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_initialize_associated_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let associated_account_info = next_account_info(account_info_iter)?;
+        let wallet_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+        let rent = Rent::from_account_info(rent_sysvar_info)?;
+
+        // Deriving the address here, rather than taking a bump, leaves only
+        // the canonical one valid, so each wallet has one account per mint
+        let (associated_account_key, bump) =
+            get_associated_token_address_with_program_id(wallet_info.key, mint_info.key, program_id);
+        if !Self::cmp_pubkeys(associated_account_info.key, &associated_account_key) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if Self::cmp_pubkeys(associated_account_info.owner, program_id) {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        let signer_seeds: &[&[u8]] = &[wallet_info.key.as_ref(), mint_info.key.as_ref(), &[bump]];
+        let system_account_infos = [
+            payer_info.clone(),
+            associated_account_info.clone(),
+            system_program_info.clone(),
+        ];
+        let minimum_balance = rent.minimum_balance(Account::LEN);
+        if associated_account_info.lamports() == 0 {
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    associated_account_info.key,
+                    minimum_balance,
+                    Account::LEN as u64,
+                    program_id,
+                ),
+                &system_account_infos,
+                &[signer_seeds],
+            )?;
+        } else {
+            // `create_account` refuses an address that already holds
+            // lamports, which anyone could send to block the account
+            let shortfall = minimum_balance.saturating_sub(associated_account_info.lamports());
+            if shortfall > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        payer_info.key,
+                        associated_account_info.key,
+                        shortfall,
+                    ),
+                    &system_account_infos,
+                )?;
+            }
+            invoke_signed(
+                &system_instruction::allocate(associated_account_info.key, Account::LEN as u64),
+                &system_account_infos,
+                &[signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(associated_account_info.key, program_id),
+                &system_account_infos,
+                &[signer_seeds],
+            )?;
+        }
+
+        Self::process_initialize_account(
+            program_id,
+            &[
+                associated_account_info.clone(),
+                mint_info.clone(),
+                rent_sysvar_info.clone(),
+            ],
+            Some(wallet_info.key),
+            true,
+        )
+    }
+
+    fn process_initialize_metadata_pointer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        metadata_address: COption<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Mint::unpack_unchecked(&mint_info.data.borrow())?;
+        if mint.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        // `InitializeMint` keeps the pointer when it fills in the rest
+        mint.metadata_pointer = metadata_address;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_initialize_token_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: &str,
+        symbol: &str,
+        uri: &str,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let metadata_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let update_authority_info = next_account_info(account_info_iter)?;
+        let mint_authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, metadata_info)?;
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        if !matches!(
+            mint.metadata_pointer,
+            COption::Some(ref metadata_pointer)
+                if Self::cmp_pubkeys(metadata_pointer, metadata_info.key)
+        ) {
+            return Err(TokenError::MetadataPointerMismatch.into());
+        }
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                mint_authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+
+        // A mint keeps its own metadata after the mint fields, any other
+        // account from the start
+        let offset = if Self::cmp_pubkeys(metadata_info.key, mint_info.key) {
+            Mint::LEN
+        } else {
+            0
+        };
+        if metadata_info.data.borrow().iter().skip(offset).any(|byte| *byte != 0) {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        let metadata = TokenMetadata {
+            update_authority: *update_authority_info.key,
+            mint: *mint_info.key,
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+        };
+        Self::write_token_metadata(
+            metadata_info,
+            payer_info,
+            system_program_info,
+            offset,
+            &metadata,
+        )
+    }
+
+    fn process_update_token_metadata_field(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        field: MetadataField,
+        value: &str,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let metadata_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let update_authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, metadata_info)?;
+        let offset =
+            TokenMetadata::offset(metadata_info.data_len()).ok_or(ProgramError::InvalidAccountData)?;
+        let mut metadata = TokenMetadata::unpack(&metadata_info.data.borrow()[offset..])?;
+        if !metadata.is_initialized() {
+            return Err(TokenError::UninitializedState.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &metadata.update_authority,
+            update_authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        match field {
+            MetadataField::Name => metadata.name = value.to_string(),
+            MetadataField::Symbol => metadata.symbol = value.to_string(),
+            MetadataField::Uri => metadata.uri = value.to_string(),
+        }
+        Self::write_token_metadata(
+            metadata_info,
+            payer_info,
+            system_program_info,
+            offset,
+            &metadata,
+        )
+    }
+
+    /// Resizes `metadata_info` to hold `metadata` at `offset` and writes it,
+    /// with `payer_info` covering any rent shortfall of the new size
+    fn write_token_metadata<'a>(
+        metadata_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        offset: usize,
+        metadata: &TokenMetadata,
+    ) -> ProgramResult {
+        metadata.check_field_lengths()?;
+        let len = if offset == 0 {
+            metadata.account_len()
+        } else {
+            offset + metadata.packed_len()
+        };
+        metadata_info.realloc(len, false)?;
+        metadata.pack(&mut metadata_info.data.borrow_mut()[offset..])?;
+
+        Self::fund_rent_exemption(metadata_info, payer_info, system_program_info)
+    }
+
+    /// Has `payer_info` transfer whatever `account_info` lacks of the
+    /// rent-exempt reserve for its current size
+    fn fund_rent_exemption<'a>(
+        account_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let rent = Rent::get()?;
+        let shortfall = rent
+            .minimum_balance(account_info.data_len())
+            .saturating_sub(account_info.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, account_info.key, shortfall),
+                &[
+                    payer_info.clone(),
+                    account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn process_reallocate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let token_account_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, token_account_info)?;
+        Account::upgrade_in_place(token_account_info)?;
+        let mut account =
+            Self::unpack_initialized::<Account>(&token_account_info.data.borrow())?;
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        let rent = Rent::get()?;
+        let minimum_balance = rent.minimum_balance(Account::LEN);
+        // Wrapped SOL keeps the larger reserve out of its token amount
+        let required_lamports = if account.is_native() {
+            let required_lamports = minimum_balance
+                .checked_add(account.amount)
+                .ok_or(TokenError::Overflow)?;
+            account.is_native = COption::Some(minimum_balance);
+            Account::pack(account, &mut token_account_info.data.borrow_mut())?;
+            required_lamports
+        } else {
+            minimum_balance
+        };
+        let shortfall = required_lamports.saturating_sub(token_account_info.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, token_account_info.key, shortfall),
+                &[
+                    payer_info.clone(),
+                    token_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        if !rent.is_exempt(token_account_info.lamports(), Account::LEN) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_initialize_immutable_owner(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Account::unpack_unchecked(&account_info.data.borrow())?;
+        if account.is_initialized() {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        // `InitializeAccount` keeps the flag when it fills in the rest
+        account.immutable_owner = true;
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_toggle_cpi_guard(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        enable: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        // Otherwise the program being guarded against could lift the guard
+        if Self::invoked_by_cpi() {
+            return Err(TokenError::CpiGuarded.into());
+        }
+
+        account.cpi_guard = enable;
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_reallocate_mint(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        // Growing changes nothing the authorities control, so anyone may pay
+        // for it
+        Mint::upgrade_in_place(mint_info)?;
+        Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+
+        Self::fund_rent_exemption(mint_info, payer_info, system_program_info)
+    }
+
+    fn process_require_memo_transfers(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        enable: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        account.memo_required = enable;
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_propose_owner(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_owner: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if account.immutable_owner {
+            return Err(TokenError::ImmutableOwner.into());
+        }
+
+        if Self::cmp_pubkeys(&new_owner, &Pubkey::default()) {
+            // Either side may call off a pending proposal
+            let authority = match account.pending_owner {
+                COption::Some(pending_owner)
+                    if Self::cmp_pubkeys(authority_info.key, &pending_owner) =>
+                {
+                    pending_owner
+                }
+                _ => account.owner,
+            };
+            Self::validate_owner(
+                program_id,
+                &authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?;
+            account.pending_owner = COption::None;
+        } else {
+            // A new proposal replaces any pending one
+            Self::validate_owner(
+                program_id,
+                &account.owner,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?;
+            Self::check_cpi_guard(&account)?;
+            account.pending_owner = COption::Some(new_owner);
+        }
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_accept_ownership(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let pending_owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        let pending_owner = account
+            .pending_owner
+            .ok_or(Into::<ProgramError>::into(TokenError::InvalidState))?;
+        Self::validate_owner(
+            program_id,
+            &pending_owner,
+            pending_owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        // Same as `SetAuthority`: the previous owner's approvals do not carry
+        // over
+        account.owner = pending_owner;
+        account.pending_owner = COption::None;
+        account.delegate = COption::None;
+        account.delegated_amount = 0;
+        account.expiry_slot = COption::None;
+        account.escrowed = false;
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_mint_to(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::mint_into(
+            program_id,
+            mint_info,
+            dest_account_info,
+            owner_info.key,
+            amount,
+            |mint| match mint.mint_authority {
+                COption::Some(mint_authority) => Self::validate_owner(
+                    program_id,
+                    &mint_authority,
+                    owner_info,
+                    account_info_iter.as_slice(),
+                ),
+                COption::None => Err(TokenError::FixedSupply.into()),
+            },
+        )
+    }
+
+    /// Mints `amount` into the destination once the mint and the destination
+    /// are validated and `authorize` accepts the mint. `authority` is only
+    /// reported in the event.
+    fn mint_into(
+        program_id: &Pubkey,
+        mint_info: &AccountInfo,
+        dest_account_info: &AccountInfo,
+        authority: &Pubkey,
+        amount: u64,
+        authorize: impl FnOnce(&Mint) -> ProgramResult,
+    ) -> ProgramResult {
+        // The mint can never double as the token account it mints into
+        if Self::cmp_pubkeys(mint_info.key, dest_account_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        // The mint first, so a bogus one is named as such whatever the
+        // destination looks like. The native mint has no account to check.
+        if Self::cmp_pubkeys(mint_info.key, &native_mint::id()) {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+
+        Self::check_account_owner(program_id, dest_account_info)?;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+        if !Self::cmp_pubkeys(mint_info.key, &dest_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if dest_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+
+        if mint.paused {
+            return Err(TokenError::MintPaused.into());
+        }
+        authorize(&mint)?;
+
+        dest_account.amount = dest_account
+            .amount
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+
+        mint.supply = mint
+            .supply
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        Self::check_supply_cap(&mint)?;
+
+        // Minting nothing leaves both accounts as they were
+        if amount != 0 {
+            Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+            Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+        }
+        TokenEvent::Mint(MintEvent {
+            mint: *mint_info.key,
+            account: *dest_account_info.key,
+            authority: *authority,
+            amount,
+        })
+        .emit();
+
+        Ok(())
+    }
+
+    fn check_supply_cap(mint: &Mint) -> ProgramResult {
+        match mint.max_supply {
+            COption::Some(max_supply) if mint.supply > max_supply => {
+                Err(TokenError::SupplyCapExceeded.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn process_initialize_flash_mint(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let mint_authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, config_info)?;
+        let mut config = FlashMintConfig::unpack_unchecked(&config_info.data.borrow())?;
+        if config.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !Rent::get()?.is_exempt(config_info.lamports(), config_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                mint_authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+
+        config.mint = *mint_info.key;
+        config.is_initialized = true;
+        config.fee = fee;
+        FlashMintConfig::pack(config, &mut config_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_flash_mint_to(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+
+        let mut config = Self::unpack_flash_mint_config(program_id, config_info, mint_info.key)?;
+        // One repayment can only ever settle one flash mint
+        if config.outstanding != 0 {
+            return Err(TokenError::FlashMintOutstanding.into());
+        }
+        Self::check_flash_repay(
+            program_id,
+            instructions_info,
+            mint_info.key,
+            config_info.key,
+            amount,
+        )?;
+
+        // The config, checked against the repayment, stands in for the
+        // authority
+        Self::mint_into(
+            program_id,
+            mint_info,
+            dest_account_info,
+            config_info.key,
+            amount,
+            |mint| {
+                // A fixed supply stays fixed, even within a transaction
+                if mint.mint_authority.is_none() {
+                    return Err(TokenError::FixedSupply.into());
+                }
+                Ok(())
+            },
+        )?;
+
+        config.outstanding = amount;
+        FlashMintConfig::pack(config, &mut config_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_flash_burn_repay(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        // The mint can never double as the token account it burns from
+        if Self::cmp_pubkeys(mint_info.key, source_account_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mut config = Self::unpack_flash_mint_config(program_id, config_info, mint_info.key)?;
+        if config.outstanding != amount {
+            return Err(TokenError::FlashRepayMismatch.into());
+        }
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if !Self::cmp_pubkeys(mint_info.key, &source_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mut mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        if mint.paused {
+            return Err(TokenError::MintPaused.into());
+        }
+
+        // The fee goes to an account of the mint authority's, and is simply
+        // kept when that is the source itself
+        let fee_account = if config.fee == 0
+            || Self::cmp_pubkeys(fee_account_info.key, source_account_info.key)
+        {
+            None
+        } else {
+            Self::check_account_owner(program_id, fee_account_info)?;
+            let fee_account =
+                Self::unpack_initialized::<Account>(&fee_account_info.data.borrow())?;
+            if fee_account.is_frozen() {
+                return Err(TokenError::AccountFrozen.into());
+            }
+            if !Self::cmp_pubkeys(mint_info.key, &fee_account.mint) {
+                return Err(TokenError::MintMismatch.into());
+            }
+            if !matches!(
+                mint.mint_authority,
+                COption::Some(ref mint_authority)
+                    if Self::cmp_pubkeys(mint_authority, &fee_account.owner)
+            ) {
+                return Err(TokenError::OwnerMismatch.into());
+            }
+            Some(fee_account)
+        };
+        let debit = match fee_account {
+            Some(_) => amount.checked_add(config.fee).ok_or(TokenError::Overflow)?,
+            None => amount,
+        };
+        let fee_account = match fee_account {
+            Some(mut fee_account) => {
+                fee_account.amount = fee_account
+                    .amount
+                    .checked_add(config.fee)
+                    .ok_or(TokenError::Overflow)?;
+                Some(fee_account)
+            }
+            None => None,
+        };
+        if source_account.amount < debit {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+
+        Self::validate_debit(
+            program_id,
+            &mut source_account,
+            mint.permanent_delegate,
+            authority_info,
+            account_info_iter.as_slice(),
+            debit,
+        )?;
+
+        source_account.amount = source_account
+            .amount
+            .checked_sub(debit)
+            .ok_or(TokenError::Overflow)?;
+        mint.supply = mint
+            .supply
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+        config.outstanding = 0;
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        if let Some(fee_account) = fee_account {
+            Account::pack(fee_account, &mut fee_account_info.data.borrow_mut())?;
+        }
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+        FlashMintConfig::pack(config, &mut config_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Unpacks the initialized flash mint config of the mint keyed `mint_key`
+    fn unpack_flash_mint_config(
+        program_id: &Pubkey,
+        config_info: &AccountInfo,
+        mint_key: &Pubkey,
+    ) -> Result<FlashMintConfig, ProgramError> {
+        Self::check_account_owner(program_id, config_info)?;
+        let config = Self::unpack_initialized::<FlashMintConfig>(&config_info.data.borrow())?;
+        if !Self::cmp_pubkeys(&config.mint, mint_key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        Ok(config)
+    }
+
+    /// Succeeds if a later top-level instruction of the transaction is a
+    /// `FlashBurnRepay` of `amount` against the same mint and config, read
+    /// from the instructions sysvar
+    fn check_flash_repay(
+        program_id: &Pubkey,
+        instructions_info: &AccountInfo,
+        mint_key: &Pubkey,
+        config_key: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let repay = TokenInstruction::FlashBurnRepay { amount }.pack();
+        let current = load_current_index_checked(instructions_info)? as usize;
+        for index in current + 1.. {
+            match load_instruction_at_checked(index, instructions_info) {
+                Ok(instruction)
+                    if Self::cmp_pubkeys(&instruction.program_id, program_id)
+                        && instruction.data == repay
+                        && instruction
+                            .accounts
+                            .get(1)
+                            .is_some_and(|meta| Self::cmp_pubkeys(&meta.pubkey, mint_key))
+                        && instruction
+                            .accounts
+                            .get(2)
+                            .is_some_and(|meta| Self::cmp_pubkeys(&meta.pubkey, config_key)) =>
+                {
+                    return Ok(());
+                }
+                Ok(_) => {}
+                // Past the last instruction
+                Err(ProgramError::InvalidArgument) => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(TokenError::FlashRepayMissing.into())
+    }
+
+    /// Succeeds if the mint has no transfer policy, or if the policy it
+    /// records, passed among the trailing accounts, permits `owner`
+    fn check_transfer_policy(
+        program_id: &Pubkey,
+        transfer_policy: &COption<Pubkey>,
+        owner: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let policy_key = match transfer_policy {
+            COption::Some(policy_key) => policy_key,
+            COption::None => return Ok(()),
+        };
+        let policy_info = accounts
+            .iter()
+            .find(|account_info| Self::cmp_pubkeys(account_info.key, policy_key))
+            .ok_or(TokenError::MissingTransferPolicy)?;
+        Self::check_account_owner(program_id, policy_info)?;
+        let policy = Self::unpack_initialized::<TransferPolicy>(&policy_info.data.borrow())?;
+        if !policy.permits(owner) {
+            return Err(TokenError::TransferNotPermitted.into());
+        }
+        Ok(())
+    }
+
+    /// Succeeds if the transaction carries a Memo program instruction, read
+    /// from the instructions sysvar passed among the trailing accounts
+    fn check_memo(accounts: &[AccountInfo]) -> ProgramResult {
+        let instructions_info = accounts
+            .iter()
+            .find(|account_info| sysvar::instructions::check_id(account_info.key))
+            .ok_or(TokenError::MissingMemo)?;
+        for index in 0.. {
+            match load_instruction_at_checked(index, instructions_info) {
+                Ok(instruction) if memo::is_memo_program(&instruction.program_id) => {
+                    return Ok(());
+                }
+                Ok(_) => {}
+                // Past the last instruction
+                Err(ProgramError::InvalidArgument) => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(TokenError::MissingMemo.into())
+    }
+
+    /// Fails for an account whose transfers must pass the mint, in the
+    /// transfers that do not
+    fn check_mint_not_required(account: &Account) -> ProgramResult {
+        if account.mint_required {
+            return Err(TokenError::MintRequiredForTransfer.into());
+        }
+        Ok(())
+    }
+
+    fn process_mint_to_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let dest_account_infos = amounts
+            .iter()
+            .map(|_| next_account_info(account_info_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        if mint.paused {
+            return Err(TokenError::MintPaused.into());
+        }
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                owner_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+
+        // The supply takes the whole batch, so its overflow is caught up front
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(TokenError::Overflow)?;
+        mint.supply = mint
+            .supply
+            .checked_add(total)
+            .ok_or(TokenError::Overflow)?;
+        Self::check_supply_cap(&mint)?;
+
+        // Nothing is written until every destination has been checked and
+        // credited, with repeated destinations sharing one entry
+        let mut updated: Vec<(&AccountInfo, Account)> = Vec::with_capacity(amounts.len());
+        for (dest_account_info, amount) in dest_account_infos.into_iter().zip(amounts) {
+            let position = match updated
+                .iter()
+                .position(|(info, _)| Self::cmp_pubkeys(info.key, dest_account_info.key))
+            {
+                Some(position) => position,
+                None => {
+                    if Self::cmp_pubkeys(mint_info.key, dest_account_info.key) {
+                        return Err(TokenError::MintMismatch.into());
+                    }
+                    Self::check_account_owner(program_id, dest_account_info)?;
+                    let dest_account =
+                        Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+                    if dest_account.is_frozen() {
+                        return Err(TokenError::AccountFrozen.into());
+                    }
+                    if dest_account.is_native() {
+                        return Err(TokenError::NativeNotSupported.into());
+                    }
+                    if !Self::cmp_pubkeys(mint_info.key, &dest_account.mint) {
+                        return Err(TokenError::MintMismatch.into());
+                    }
+                    updated.push((dest_account_info, dest_account));
+                    updated.len() - 1
+                }
+            };
+            let account = &mut updated[position].1;
+            account.amount = account
+                .amount
+                .checked_add(*amount)
+                .ok_or(TokenError::Overflow)?;
+        }
+
+        for (dest_account_info, dest_account) in updated {
+            Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        }
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_burn(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        // The mint can never double as the token account it burns from
+        if Self::cmp_pubkeys(mint_info.key, source_account_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, mint_info)?;
+        let original_source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        // The native mint has no mint account to check
+        if original_source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+
+        // Everything is checked before anything is computed, and both
+        // accounts are written only once both new states are known
+        let original_mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        if !Self::cmp_pubkeys(mint_info.key, &original_source_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if let Some(expected_decimals) = expected_decimals {
+            if expected_decimals != original_mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
+        if original_mint.paused {
+            return Err(TokenError::MintPaused.into());
+        }
+
+        if original_source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if original_source_account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+
+        let mut source_account = original_source_account;
+        Self::validate_debit(
+            program_id,
+            &mut source_account,
+            original_mint.permanent_delegate,
+            authority_info,
+            account_info_iter.as_slice(),
+            amount,
+        )?;
+        source_account.amount = source_account
+            .amount
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+        let mint = Mint {
+            supply: original_mint
+                .supply
+                .checked_sub(amount)
+                .ok_or(TokenError::Overflow)?,
+            ..original_mint
+        };
+
+        Self::pack_if_changed(source_account, &original_source_account, source_account_info)?;
+        Self::pack_if_changed(mint, &original_mint, mint_info)?;
+        Self::emit_burn(source_account_info, mint_info, authority_info, amount);
+
+        Ok(())
+    }
+
+    /// Like `Burn`, but authorized by the mint authority in place of the
+    /// account owner or a delegate
+    fn process_burn_from(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if Self::cmp_pubkeys(mint_info.key, source_account_info.key) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        let original_source_account = source_account;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if source_account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+        if !Self::cmp_pubkeys(mint_info.key, &source_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mut mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        let original_mint = mint;
+        if mint.paused {
+            return Err(TokenError::MintPaused.into());
+        }
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+
+        source_account.amount = source_account
+            .amount
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+        mint.supply = mint
+            .supply
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+
+        log!("Clawback of {} from {}", amount, source_account_info.key);
+        Self::pack_if_changed(source_account, &original_source_account, source_account_info)?;
+        Self::pack_if_changed(mint, &original_mint, mint_info)?;
+        Self::emit_burn(source_account_info, mint_info, authority_info, amount);
+
+        Ok(())
+    }
+
+    fn emit_burn(
+        account_info: &AccountInfo,
+        mint_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        amount: u64,
+    ) {
+        TokenEvent::Burn(BurnEvent {
+            account: *account_info.key,
+            mint: *mint_info.key,
+            authority: *authority_info.key,
+            amount,
+        })
+        .emit();
+    }
+
+    fn process_toggle_freeze_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        freeze: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        Self::check_account_owner(program_id, mint_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        if freeze == source_account.is_frozen() {
+            return Err(TokenError::InvalidState.into());
+        }
+        if !Self::cmp_pubkeys(mint_info.key, &source_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.freeze_authority {
+            COption::Some(freeze_authority) => Self::validate_owner(
+                program_id,
+                &freeze_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::MintCannotFreeze.into()),
+        }
+
+        source_account.state = if freeze {
+            AccountState::Frozen
+        } else {
+            AccountState::Initialized
+        };
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Flips each target in order under one freeze authority signature. The
+    /// first target that cannot be flipped fails the whole instruction and
+    /// has its index among the targets logged.
+    fn process_toggle_freeze_accounts(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        count: u8,
+        freeze: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let target_infos = (0..count)
+            .map(|_| next_account_info(account_info_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        match mint.freeze_authority {
+            COption::Some(freeze_authority) => Self::validate_owner(
+                program_id,
+                &freeze_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::MintCannotFreeze.into()),
+        }
+
+        for (index, target_info) in target_infos.into_iter().enumerate() {
+            if let Err(error) =
+                Self::toggle_freeze_target(program_id, target_info, mint_info.key, freeze)
+            {
+                log!("Failed on target account {}", index);
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn toggle_freeze_target(
+        program_id: &Pubkey,
+        account_info: &AccountInfo,
+        mint_key: &Pubkey,
+        freeze: bool,
+    ) -> ProgramResult {
+        Self::check_account_owner(program_id, account_info)?;
+        let mut account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if freeze == account.is_frozen() {
+            return Err(TokenError::InvalidState.into());
+        }
+        if !Self::cmp_pubkeys(mint_key, &account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        account.state = if freeze {
+            AccountState::Frozen
+        } else {
+            AccountState::Initialized
+        };
+        Account::pack(account, &mut account_info.data.borrow_mut())
+    }
+
+    fn process_sync_native(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let native_account_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, native_account_info)?;
+        let mut native_account =
+            Self::unpack_initialized::<Account>(&native_account_info.data.borrow())?;
+
+        if let COption::Some(rent_exempt_reserve) = native_account.is_native {
+            native_account.amount = native_account_info
+                .lamports()
+                .checked_sub(rent_exempt_reserve)
+                .ok_or(TokenError::Overflow)?;
+        } else {
+            return Err(TokenError::NonNativeNotSupported.into());
+        }
+
+        Account::pack(native_account, &mut native_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_wrap_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        lamports: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let native_account_info = next_account_info(account_info_iter)?;
+        let funding_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, native_account_info)?;
+        let mut native_account =
+            Self::unpack_initialized::<Account>(&native_account_info.data.borrow())?;
+        if !native_account.is_native() {
+            return Err(TokenError::NonNativeNotSupported.into());
+        }
+        if native_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &native_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        invoke(
+            &system_instruction::transfer(funding_info.key, native_account_info.key, lamports),
+            &[
+                funding_info.clone(),
+                native_account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+        native_account.amount = native_account
+            .amount
+            .checked_add(lamports)
+            .ok_or(TokenError::Overflow)?;
+        Account::pack(native_account, &mut native_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_unwrap_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        lamports: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let native_account_info = next_account_info(account_info_iter)?;
+        let dest_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        if Self::cmp_pubkeys(native_account_info.key, dest_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::check_account_owner(program_id, native_account_info)?;
+        let mut native_account =
+            Self::unpack_initialized::<Account>(&native_account_info.data.borrow())?;
+        let rent_exempt_reserve = match native_account.is_native {
+            COption::Some(rent_exempt_reserve) => rent_exempt_reserve,
+            COption::None => return Err(TokenError::NonNativeNotSupported.into()),
+        };
+        if native_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &native_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        Self::check_cpi_guard(&native_account)?;
+        Self::check_lock(&mut native_account)?;
+        Self::check_unlocked_funds(&native_account, lamports)?;
+        Self::check_spending_limit(&mut native_account, lamports)?;
+
+        native_account.amount = native_account
+            .amount
+            .checked_sub(lamports)
+            .ok_or(TokenError::InsufficientFunds)?;
+        // The balance is what the account holds above its reserve, but a
+        // balance out of sync with the lamports must not eat into it
+        let remaining_lamports = native_account_info
+            .lamports()
+            .checked_sub(lamports)
+            .filter(|remaining| *remaining >= rent_exempt_reserve)
+            .ok_or(TokenError::InsufficientFunds)?;
+        let dest_lamports = dest_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(TokenError::Overflow)?;
+        Account::pack(native_account, &mut native_account_info.data.borrow_mut())?;
+
+        **native_account_info.lamports.borrow_mut() = remaining_lamports;
+        **dest_info.lamports.borrow_mut() = dest_lamports;
+
+        Ok(())
+    }
+
+    fn process_get_account_data_size(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+
+        let account_len = Self::account_data_size(&mint);
+        set_return_data(&(account_len as u64).to_le_bytes());
+
+        Ok(())
+    }
+
+    // Every mint currently uses the same account layout
+    fn account_data_size(_mint: &Mint) -> usize {
+        Account::get_packed_len()
+    }
+
+    fn process_assert_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        minimum: u64,
+        maximum: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        if !(minimum..=maximum).contains(&account.amount) {
+            return Err(TokenError::BalanceAssertionFailed.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_assert_supply(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        minimum: u64,
+        maximum: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        if !(minimum..=maximum).contains(&mint.supply) {
+            return Err(TokenError::BalanceAssertionFailed.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_get_account_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, account_info)?;
+        let account = Self::unpack_initialized::<Account>(&account_info.data.borrow())?;
+        set_return_data(&account.amount.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn process_get_mint_supply(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Self::unpack_initialized::<Mint>(&mint_info.data.borrow())?;
+        set_return_data(&mint.supply.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn process_amount_to_ui_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+
+        let config = &mint.interest_bearing_config;
+        let ui_amount = if config.rate_authority.is_some() {
+            config.amount_to_ui_amount(amount, mint.decimals, Clock::get()?.unix_timestamp)
+        } else {
+            amount_to_ui_amount_string_trimmed(amount, mint.decimals)
+        };
+        set_return_data(&ui_amount.into_bytes());
+
+        Ok(())
+    }
+
+    fn process_ui_amount_to_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        ui_amount: &str,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_owner(program_id, mint_info)?;
+        let mint = Mint::unpack(&mint_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+
+        let config = &mint.interest_bearing_config;
+        let amount = if config.rate_authority.is_some() {
+            config.try_ui_amount_into_amount(
+                ui_amount,
+                mint.decimals,
+                Clock::get()?.unix_timestamp,
+            )?
+        } else {
+            try_ui_amount_into_amount(ui_amount, mint.decimals)?
+        };
+        set_return_data(&amount.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn process_close_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if Self::cmp_pubkeys(source_account_info.key, dest_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::check_account_owner(program_id, source_account_info)?;
+        let mut source_account =
+            Self::unpack_initialized::<Account>(&source_account_info.data.borrow())?;
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        // The lamports of a native account are its balance, so it can be
+        // closed with one, unless that balance is locked
+        if source_account.is_native() {
+            Self::check_lock(&mut source_account)?;
+            let source_amount = source_account.amount;
+            Self::check_unlocked_funds(&source_account, source_amount)?;
+            Self::check_spending_limit(&mut source_account, source_amount)?;
+        } else if source_account.amount != 0 {
+            return Err(TokenError::NonNativeHasBalance.into());
+        }
+        // Withheld fees are still part of the supply, so they must be
+        // harvested first
+        if source_account.withheld_amount != 0 {
+            return Err(TokenError::AccountHasWithheldTransferFees.into());
+        }
+        // So are tokens still vesting, which only the owner can claim
+        if source_account.vesting_amount != 0 {
+            return Err(TokenError::VestingInProgress.into());
+        }
+
+        Self::validate_close_authority(
+            program_id,
+            &source_account,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        let dest_starting_lamports = dest_account_info.lamports();
+        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+            .checked_add(source_account_info.lamports())
+            .ok_or(TokenError::Overflow)?;
+        **source_account_info.lamports.borrow_mut() = 0;
+        source_account_info.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    fn process_recover_nested(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nested_account_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let owner_account_info = next_account_info(account_info_iter)?;
+        let wallet_info = next_account_info(account_info_iter)?;
+
+        if Self::cmp_pubkeys(nested_account_info.key, dest_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::check_account_owner(program_id, nested_account_info)?;
+        Self::check_account_owner(program_id, dest_account_info)?;
+        Self::check_account_owner(program_id, owner_account_info)?;
+        let nested_account =
+            Self::unpack_initialized::<Account>(&nested_account_info.data.borrow())?;
+        let owner_account =
+            Self::unpack_initialized::<Account>(&owner_account_info.data.borrow())?;
+        let mut dest_account =
+            Self::unpack_initialized::<Account>(&dest_account_info.data.borrow())?;
+
+        // The nested account can only be reached through the account that
+        // owns it, and that account only through its own owner, who must
+        // also own the destination
+        if !Self::cmp_pubkeys(&nested_account.owner, owner_account_info.key) {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &owner_account.owner,
+            wallet_info,
+            account_info_iter.as_slice(),
+        )?;
+        if !Self::cmp_pubkeys(&dest_account.owner, &owner_account.owner) {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if !Self::cmp_pubkeys(&dest_account.mint, &nested_account.mint) {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if nested_account.is_frozen() || dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if nested_account.withheld_amount != 0 {
+            return Err(TokenError::AccountHasWithheldTransferFees.into());
+        }
+        if nested_account.vesting_amount != 0 {
+            return Err(TokenError::VestingInProgress.into());
+        }
+
+        dest_account.amount = dest_account
+            .amount
+            .checked_add(nested_account.amount)
+            .ok_or(TokenError::Overflow)?;
+
+        // A native balance is held in lamports, which move with it; the rest
+        // is the reclaimed rent
+        let nested_lamports = nested_account_info.lamports();
+        let moved_lamports = if nested_account.is_native() {
+            nested_account.amount
+        } else {
+            0
+        };
+        let reclaimed_lamports = nested_lamports
+            .checked_sub(moved_lamports)
+            .ok_or(TokenError::Overflow)?;
+        let dest_lamports = dest_account_info
+            .lamports()
+            .checked_add(moved_lamports)
+            .ok_or(TokenError::Overflow)?;
+        let wallet_lamports = wallet_info
+            .lamports()
+            .checked_add(reclaimed_lamports)
+            .ok_or(TokenError::Overflow)?;
+
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        **dest_account_info.lamports.borrow_mut() = dest_lamports;
+        **wallet_info.lamports.borrow_mut() = wallet_lamports;
+        **nested_account_info.lamports.borrow_mut() = 0;
+        nested_account_info.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    fn unpack_initialized<T: Pack + IsInitialized>(data: &[u8]) -> Result<T, ProgramError> {
+        T::unpack(data).map_err(|error| match error {
+            ProgramError::UninitializedAccount => TokenError::UninitializedState.into(),
+            _ => TokenError::InvalidState.into(),
+        })
+    }
+
+    /// Packs `value` into the account unless it still equals `original`, the
+    /// state the account was unpacked into, so an operation that changes
+    /// nothing leaves the account data unwritten
+    fn pack_if_changed<T: Pack + PartialEq>(
+        value: T,
+        original: &T,
+        account_info: &AccountInfo,
+    ) -> ProgramResult {
+        if value != *original {
+            T::pack(value, &mut account_info.data.borrow_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `authority_info` may move `amount` out of `source_account`,
+    /// either as its delegate, whose allowance is reduced accordingly, or as
+    /// its owner. Either way the debit counts against the spending limit.
+    fn validate_debit(
+        program_id: &Pubkey,
+        source_account: &mut Account,
+        permanent_delegate: COption<Pubkey>,
+        authority_info: &AccountInfo,
+        signers: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        Self::check_lock(source_account)?;
+
+        // The mint's permanent delegate overrides the owner and any approval,
+        // and leaves the delegation as it is
+        if let COption::Some(permanent_delegate) = permanent_delegate {
+            if Self::cmp_pubkeys(authority_info.key, &permanent_delegate) {
+                Self::validate_owner(program_id, &permanent_delegate, authority_info, signers)?;
+                log!("Debit authorized by the mint's permanent delegate");
+                return Ok(());
+            }
+        }
+
+        match source_account.delegate {
+            COption::Some(delegate) if Self::cmp_pubkeys(authority_info.key, &delegate) => {
+                Self::validate_owner(program_id, &delegate, authority_info, signers)?;
+                if Self::delegation_expired(source_account)? {
+                    return Err(TokenError::DelegateExpired.into());
+                }
+                if source_account.delegated_amount < amount {
+                    return Err(TokenError::InsufficientDelegatedFunds.into());
+                }
+
+                source_account.delegated_amount = source_account
+                    .delegated_amount
+                    .checked_sub(amount)
+                    .ok_or(TokenError::Overflow)?;
+                if source_account.delegated_amount == 0 {
+                    source_account.delegate = COption::None;
+                    source_account.expiry_slot = COption::None;
+                    source_account.escrowed = false;
+                }
+            }
+            _ => {
+                Self::validate_owner(program_id, &source_account.owner, authority_info, signers)?;
+                Self::check_cpi_guard(source_account)?;
+                // A stale delegation is dropped on the owner's next operation
+                if Self::delegation_expired(source_account)? {
+                    source_account.delegate = COption::None;
+                    source_account.delegated_amount = 0;
+                    source_account.expiry_slot = COption::None;
+                    source_account.escrowed = false;
+                }
+                Self::check_unlocked_funds(source_account, amount)?;
+            }
+        }
+        Self::check_spending_limit(source_account, amount)
+    }
+
+    /// Passes every account after the transfer authority on to the hook as an
+    /// extra account, except the hook program itself
+    fn invoke_transfer_hook<'a>(
+        hook_program_id: &Pubkey,
+        source_account_info: &AccountInfo<'a>,
+        mint_info: &AccountInfo<'a>,
+        dest_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        extra_account_infos: &[AccountInfo<'a>],
+        amount: u64,
+    ) -> ProgramResult {
+        let extra_account_pubkeys = extra_account_infos
+            .iter()
+            .map(|info| info.key)
+            .filter(|key| !Self::cmp_pubkeys(key, hook_program_id))
+            .collect::<Vec<_>>();
+        let instruction = transfer_hook::execute(
+            hook_program_id,
+            source_account_info.key,
+            mint_info.key,
+            dest_account_info.key,
+            authority_info.key,
+            &extra_account_pubkeys,
+            amount,
+        );
+
+        let mut account_infos = vec![
+            source_account_info.clone(),
+            mint_info.clone(),
+            dest_account_info.clone(),
+            authority_info.clone(),
+        ];
+        account_infos.extend_from_slice(extra_account_infos);
+        invoke(&instruction, &account_infos)
+    }
+
+    /// Whether the instruction was invoked by another program rather than
+    /// directly by the transaction
+    fn invoked_by_cpi() -> bool {
+        get_stack_height() > TRANSACTION_LEVEL_STACK_HEIGHT
+    }
+
+    /// Blocks an owner action invoked by another program while the account's
+    /// CPI guard is on, since that program may be spending the owner's
+    /// signature on something the owner never agreed to
+    fn check_cpi_guard(account: &Account) -> ProgramResult {
+        if account.cpi_guard && Self::invoked_by_cpi() {
+            return Err(TokenError::CpiGuarded.into());
+        }
+        Ok(())
+    }
+
+    /// Fails while the account is locked, and drops a lock that has run out.
+    /// The clock is only read when a lock is set.
+    fn check_lock(account: &mut Account) -> ProgramResult {
+        if let COption::Some(lock_until) = account.lock_until {
+            if Clock::get()?.slot < lock_until {
+                return Err(TokenError::AccountLocked.into());
+            }
+            account.lock_until = COption::None;
+        }
+        Ok(())
+    }
+
+    /// Counts `amount` against the account's spending limit, starting a new
+    /// window when the epoch has moved on. The clock is only read when a
+    /// limit is set.
+    fn check_spending_limit(account: &mut Account, amount: u64) -> ProgramResult {
+        if let COption::Some(limit_amount) = account.limit_amount {
+            let epoch = Clock::get()?.epoch;
+            if epoch != account.window_start_epoch {
+                account.window_start_epoch = epoch;
+                account.spent_in_window = 0;
+            }
+            let spent_in_window = account
+                .spent_in_window
+                .checked_add(amount)
+                .ok_or(TokenError::Overflow)?;
+            if spent_in_window > limit_amount {
+                return Err(TokenError::SpendingLimitExceeded.into());
+            }
+            account.spent_in_window = spent_in_window;
+        }
+        Ok(())
+    }
+
+    /// Fails if the owner would spend into the amount an escrowed approval
+    /// reserves for the delegate. An expired approval reserves nothing.
+    fn check_unlocked_funds(account: &Account, amount: u64) -> ProgramResult {
+        if account.escrowed
+            && !Self::delegation_expired(account)?
+            && amount > account.amount.saturating_sub(account.delegated_amount)
+        {
+            return Err(TokenError::InsufficientUnlockedFunds.into());
+        }
+        Ok(())
+    }
+
+    /// Nobody can sign as the default pubkey, so naming it as an authority
+    /// is taken for a client bug rather than a way to give the authority up
+    fn check_authority(authority: &Pubkey) -> ProgramResult {
+        if Self::cmp_pubkeys(authority, &Pubkey::default()) {
+            return Err(TokenError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+
+    /// Whether the clock is past the expiry slot of the delegation. The clock
+    /// is only read when an expiry is set.
+    fn delegation_expired(account: &Account) -> Result<bool, ProgramError> {
+        match account.expiry_slot {
+            COption::Some(expiry_slot) => Ok(Clock::get()?.slot > expiry_slot),
+            COption::None => Ok(false),
+        }
+    }
+
+    /// Compares two keys with the `sol_memcmp` syscall, which costs fewer
+    /// compute units than the byte-by-byte loop `==` compiles to
+    fn cmp_pubkeys(a: &Pubkey, b: &Pubkey) -> bool {
+        sol_memcmp(a.as_ref(), b.as_ref(), PUBKEY_BYTES) == 0
+    }
+
+    /// Checks that the account can hold token state: owned by the program
+    /// and not itself a program
+    fn check_account_owner(program_id: &Pubkey, account_info: &AccountInfo) -> ProgramResult {
+        if !Self::cmp_pubkeys(program_id, account_info.owner) {
+            Err(ProgramError::IncorrectProgramId)
+        } else if account_info.executable {
+            Err(ProgramError::InvalidAccountData)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn validate_owner(
+        program_id: &Pubkey,
+        expected_owner: &Pubkey,
+        owner_account_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
+        if !Self::cmp_pubkeys(expected_owner, owner_account_info.key) {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if Self::cmp_pubkeys(program_id, owner_account_info.owner)
+            && owner_account_info.data_len() == Multisig::get_packed_len()
+        {
+            let multisig = Self::unpack_initialized::<Multisig>(&owner_account_info.data.borrow())?;
+            let mut num_signers = 0;
+            let mut matched = [false; MAX_SIGNERS];
+            for signer in signers.iter() {
+                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
+                    if Self::cmp_pubkeys(key, signer.key) && !matched[position] {
+                        if !signer.is_signer {
+                            return Err(ProgramError::MissingRequiredSignature);
+                        }
+                        matched[position] = true;
+                        num_signers += 1;
+                    }
+                }
+            }
+            if num_signers < multisig.m {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            return Ok(());
+        }
+        if !owner_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// The owner can always close an account; a close authority, when set,
+    /// can as well
+    fn validate_close_authority(
+        program_id: &Pubkey,
+        account: &Account,
+        authority_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
+        match account.close_authority {
+            COption::Some(ref close_authority)
+                if Self::cmp_pubkeys(authority_info.key, close_authority) =>
+            {
+                Self::validate_owner(program_id, close_authority, authority_info, signers)
+            }
+            _ => Self::validate_owner(program_id, &account.owner, authority_info, signers),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instruction::*,
+        state::{
+            ACCOUNT_FIELDS, ACCOUNT_STATE_OFFSET, LEGACY_ACCOUNT_LEN, LEGACY_MINT_LEN,
+            MAX_NAME_LENGTH, MAX_URI_LENGTH, SPL_ACCOUNT_LEN, SPL_ACCOUNT_STATE_OFFSET,
+        },
+    };
+    use crate::test_fixtures::*;
+    use crate::test_support::*;
+    use solana_program::{
+        instruction::{AccountMeta, Instruction},
+        system_program,
+        sysvar::rent,
+    };
+    use std::convert::TryInto;
+    use solana_sdk::account::{create_is_signer_account_infos, Account as SolanaAccount};
+
+    #[test]
+    fn test_pack_unpack_mint() {
+        // Mint
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            freeze_authority: COption::Some(Pubkey::new_from_array([2; 32])),
+            max_supply: COption::Some(1_000),
+            permanent_delegate: COption::Some(Pubkey::new_from_array([3; 32])),
+            default_account_state: AccountState::Frozen,
+            transfer_fee_config: TransferFeeConfig {
+                transfer_fee_authority: COption::Some(Pubkey::new_from_array([4; 32])),
+                transfer_fee_basis_points: 0x0102,
+                maximum_fee: 5,
+            },
+            withheld_amount: 6,
+            interest_bearing_config: InterestBearingConfig {
+                rate_authority: COption::Some(Pubkey::new_from_array([7; 32])),
+                initialization_timestamp: 8,
+                pre_update_average_rate: -9,
+                last_update_timestamp: 10,
+                current_rate: 11,
+            },
+            pause_authority: COption::Some(Pubkey::new_from_array([12; 32])),
+            paused: true,
+            transfer_hook_authority: COption::Some(Pubkey::new_from_array([13; 32])),
+            transfer_hook_program_id: COption::Some(Pubkey::new_from_array([14; 32])),
+            metadata_pointer: COption::Some(Pubkey::new_from_array([15; 32])),
+            transfer_policy: COption::Some(Pubkey::new_from_array([16; 32])),
+        };
+        let mut packed = vec![0; Mint::get_packed_len() - 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            Mint::pack(mint, &mut packed)
+        );
+        let mut packed = vec![0; Mint::get_packed_len()];
+        Mint::pack(mint, &mut packed).unwrap();
+        let expect = vec![
+            1, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+            1, 1, 1, 1, 1, 1, 1, 42, 0, 0, 0, 0, 0, 0, 0, 7, 1, 1, 0, 0, 0, 2, 2, 2, 2, 2, 2, 2, 2,
+            2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+            1, 0, 0, 0, 232, 3, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 2, 1, 0, 0, 0, 4, 4, 4,
+            4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+            4, 2, 1, 5, 0, 0, 0, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 7, 7, 7, 7, 7,
+            7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 8,
+            0, 0, 0, 0, 0, 0, 0, 247, 255, 10, 0, 0, 0, 0, 0, 0, 0, 11, 0, 1, 0, 0, 0, 12, 12,
+            12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+            12, 12, 12, 12, 12, 12, 12, 12, 12, 1, 1, 0, 0, 0, 13, 13, 13, 13, 13, 13, 13, 13, 13,
+            13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13,
+            13, 13, 1, 0, 0, 0, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14,
+            14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 14, 1, 0, 0, 0, 15, 15,
+            15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+            15, 15, 15, 15, 15, 15, 15, 15, 15, 1, 0, 0, 0, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+            16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+            16, 16,
+        ];
+        assert_eq!(packed, expect);
+        let unpacked = Mint::unpack(&packed).unwrap();
+        assert_eq!(unpacked, mint);
+
+        // longer data is a mint followed by its own metadata, which packing
+        // leaves alone
+        let mut packed = vec![0xff; Mint::get_packed_len() + TokenMetadata::FIXED_LEN];
+        Mint::pack(mint, &mut packed).unwrap();
+        assert_eq!(packed[..Mint::get_packed_len()], expect[..]);
+        assert_eq!(packed[Mint::get_packed_len()], 0xff);
+        assert_eq!(Mint::unpack(&packed).unwrap(), mint);
+
+        // but too short to hold any is not a mint at all
+        let mut packed = expect.clone();
+        packed.push(0);
+        assert_eq!(Err(ProgramError::InvalidAccountData), Mint::unpack(&packed));
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            Mint::unpack_from_slice(&packed)
+        );
+        let mut packed = expect.clone();
+
+        // the pause flag is a strict boolean
+        packed[277] = 2;
+        assert_eq!(Err(ProgramError::InvalidAccountData), Mint::unpack(&packed));
+    }
+
+    #[test]
+    fn test_pack_unpack_account() {
+        // Account
+        let check = Account {
+            mint: Pubkey::new_from_array([1; 32]),
+            owner: Pubkey::new_from_array([2; 32]),
+            amount: 3,
+            delegate: COption::Some(Pubkey::new_from_array([4; 32])),
+            delegated_amount: 6,
+            state: AccountState::Initialized,
+            is_native: COption::Some(7),
+            close_authority: COption::Some(Pubkey::new_from_array([8; 32])),
+            expiry_slot: COption::Some(9),
+            pending_owner: COption::Some(Pubkey::new_from_array([10; 32])),
+            memo_required: true,
+            withheld_amount: 11,
+            mint_required: true,
+            immutable_owner: true,
+            cpi_guard: true,
+            lock_until: COption::Some(12),
+            escrowed: true,
+            limit_amount: COption::Some(13),
+            window_start_epoch: 14,
+            spent_in_window: 15,
+            vesting_amount: 16,
+            vesting_total: 17,
+            vesting_start_slot: 18,
+            vesting_end_slot: 19,
+        };
+        let mut packed = vec![0; Account::get_packed_len() + 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            Account::pack(check, &mut packed)
+        );
+        let mut packed = vec![0; Account::get_packed_len() - 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            Account::pack(check, &mut packed)
+        );
+        let mut packed = vec![0; Account::get_packed_len()];
+        Account::pack(check, &mut packed).unwrap();
+        let expect = vec![
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+            1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+            2, 2, 2, 2, 2, 2, 3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 0, 0, 0, 0, 0, 0, 0, 1,
+            1, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+            8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 1, 0, 0, 0, 9, 0, 0, 0, 0, 0,
+            0, 0, 1, 0, 0, 0, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10,
+            10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 1, 11, 0, 0, 0, 0, 0, 0,
+            0, 1, 1, 1, 1, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0,
+            14, 0, 0, 0, 0, 0, 0, 0, 15, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 17, 0, 0, 0,
+            0, 0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 19, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(packed, expect);
+        let unpacked = Account::unpack(&packed).unwrap();
+        assert_eq!(unpacked, check);
+
+        // unknown state bytes are rejected rather than read as uninitialized
+        packed[ACCOUNT_STATE_OFFSET] = 3;
+        assert_eq!(
+            Account::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+        packed[ACCOUNT_STATE_OFFSET] = 1;
+        packed[213] = 2;
+        assert_eq!(
+            Account::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+        packed[213] = 1;
+        packed[222] = 2;
+        assert_eq!(
+            Account::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+        packed[222] = 1;
+        packed[223] = 2;
+        assert_eq!(
+            Account::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+        packed[223] = 1;
+        packed[224] = 2;
+        assert_eq!(
+            Account::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_approve_dups() {
+        let program_id = crate::id();
+        let account1_key = Pubkey::new_unique();
+        let mut account1_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account1_info: AccountInfo = (&account1_key, true, &mut account1_account).into();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_info: AccountInfo = (&account2_key, false, &mut account2_account).into();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint_info: AccountInfo = (&mint_key, false, &mut mint_account).into();
+        let rent_key = rent::id();
+        let mut rent_sysvar = rent_sysvar();
+        let rent_info: AccountInfo = (&rent_key, false, &mut rent_sysvar).into();
+
+        // create mint
+        do_process_instruction_dups(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![mint_info.clone(), rent_info.clone()],
+        )
+        .unwrap();
+
+        // create account, owned by itself
+        do_process_instruction_dups(
+            initialize_account(&program_id, &account1_key, &mint_key, &account1_key).unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account1_info.clone(),
+                rent_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // mint to account
+        do_process_instruction_dups(
+            mint_to(&program_id, &mint_key, &account1_key, &owner_key, &[], 1000).unwrap(),
+            vec![mint_info.clone(), account1_info.clone(), owner_info.clone()],
+        )
+        .unwrap();
+
+        // source-owner approve
+        do_process_instruction_dups(
+            approve(&program_id, &account1_key, &account2_key, &account1_key, &[], 500).unwrap(),
+            vec![
+                account1_info.clone(),
+                account2_info.clone(),
+                account1_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // source-owner approve_checked
+        do_process_instruction_dups(
+            approve_checked(
+                &program_id,
+                &account1_key,
+                &mint_key,
+                &account2_key,
+                &account1_key,
+                &[],
+                500,
+                2,
+            )
+            .unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account2_info.clone(),
+                account1_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // source-owner revoke
+        do_process_instruction_dups(
+            revoke(&program_id, &account1_key, &account1_key, &[]).unwrap(),
+            vec![account1_info.clone(), account1_info.clone()],
+        )
+        .unwrap();
+
+        // the account, its own owner, cannot be its delegate
+        assert_eq!(
+            Err(TokenError::InvalidDelegate.into()),
+            do_process_instruction_dups(
+                approve(&program_id, &account1_key, &account1_key, &account1_key, &[], 500)
+                    .unwrap(),
+                vec![
+                    account1_info.clone(),
+                    account1_info.clone(),
+                    account1_info.clone(),
+                ],
+            )
+        );
+        let account = Account::unpack(&account1_info.data.borrow()).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+    }
+
+    #[test]
+    fn test_self_transfer() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account_info: AccountInfo = (&account_key, false, &mut account_account).into();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let owner2_info: AccountInfo = (&owner2_key, true, &mut owner2_account).into();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let delegate_info: AccountInfo = (&delegate_key, true, &mut delegate_account).into();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint_info: AccountInfo = (&mint_key, false, &mut mint_account).into();
+        let rent_key = rent::id();
+        let mut rent_sysvar = rent_sysvar();
+        let rent_info: AccountInfo = (&rent_key, false, &mut rent_sysvar).into();
+
+        // create mint
+        do_process_instruction_dups(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![mint_info.clone(), rent_info.clone()],
+        )
+        .unwrap();
+
+        // create account
+        do_process_instruction_dups(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                account_info.clone(),
+                mint_info.clone(),
+                owner_info.clone(),
+                rent_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // mint to account
+        do_process_instruction_dups(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![mint_info.clone(), account_info.clone(), owner_info.clone()],
+        )
+        .unwrap();
+
+        // owner-signed self transfer
+        do_process_instruction_dups(
+            transfer(&program_id, &account_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![
+                account_info.clone(),
+                account_info.clone(),
+                owner_info.clone(),
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 1000);
+
+        // owner-signed self transfer_checked
+        do_process_instruction_dups(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account_key,
+                &owner_key,
+                &[],
+                1000,
+                2,
+            )
+            .unwrap(),
+            vec![
+                account_info.clone(),
+                mint_info.clone(),
+                account_info.clone(),
+                owner_info.clone(),
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 1000);
+
+        // self transfer with the wrong decimals
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction_dups(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    10,
+                )
+                .unwrap(),
+                vec![
+                    account_info.clone(),
+                    mint_info.clone(),
+                    account_info.clone(),
+                    owner_info.clone(),
+                ],
+            )
+        );
+
+        // self transfer with the wrong authority
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_dups(
+                transfer(&program_id, &account_key, &account_key, &owner2_key, &[], 1).unwrap(),
+                vec![
+                    account_info.clone(),
+                    account_info.clone(),
+                    owner2_info.clone(),
+                ],
+            )
+        );
+
+        // self transfer without the owner signature
+        let mut unsigned_owner_info = owner_info.clone();
+        unsigned_owner_info.is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction_dups(
+                transfer(&program_id, &account_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    account_info.clone(),
+                    account_info.clone(),
+                    unsigned_owner_info,
+                ],
+            )
+        );
+
+        // self transfer of more than the balance
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction_dups(
+                transfer(&program_id, &account_key, &account_key, &owner_key, &[], 1001).unwrap(),
+                vec![
+                    account_info.clone(),
+                    account_info.clone(),
+                    owner_info.clone(),
+                ],
+            )
+        );
+
+        // approve delegate
+        do_process_instruction_dups(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                account_info.clone(),
+                delegate_info.clone(),
+                owner_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // delegate-signed self transfer consumes no delegated amount
+        do_process_instruction_dups(
+            transfer(&program_id, &account_key, &account_key, &delegate_key, &[], 100).unwrap(),
+            vec![
+                account_info.clone(),
+                account_info.clone(),
+                delegate_info.clone(),
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 1000);
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 100);
+
+        // delegate self transfer of more than the delegated amount
+        assert_eq!(
+            Err(TokenError::InsufficientDelegatedFunds.into()),
+            do_process_instruction_dups(
+                transfer(&program_id, &account_key, &account_key, &delegate_key, &[], 101)
+                    .unwrap(),
+                vec![
+                    account_info.clone(),
+                    account_info.clone(),
+                    delegate_info.clone(),
+                ],
+            )
+        );
+        let account = Account::unpack(&account_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 1000);
+        assert_eq!(account.delegated_amount, 100);
+    }
+
+    #[test]
+    fn test_transfer_all() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .supply(1000)
+            .decimals(2)
+            .into_solana_account();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .amount(1000)
+            .delegate(delegate_key, 300)
+            .into_solana_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .into_solana_account();
+
+        // sweeping an empty account moves nothing
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner_key, &[], TRANSFER_ALL)
+                .unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 1000);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 0);
+
+        // a delegate sweep is capped by the delegated amount
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], TRANSFER_ALL)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 700);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 300);
+
+        // and by the balance when less was left than was delegated
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 900).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], TRANSFER_ALL)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 0);
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 200);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 1000);
+
+        // the owner sweeps the whole balance back
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account2_key,
+                &mint_key,
+                &account_key,
+                &owner_key,
+                &[],
+                TRANSFER_ALL,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 1000);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 0);
+    }
+
+    #[test]
+    fn test_transfer_batch() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let source_key = Pubkey::new_unique();
+        let mut source_account = new_token_account();
+        let dest_keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut dest_accounts: Vec<SolanaAccount> = (0..3).map(|_| new_token_account()).collect();
+        let mismatch_key = Pubkey::new_unique();
+        let mut mismatch_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        for (key, account) in [(&mint_key, &mut mint_account), (&mint2_key, &mut mint2_account)] {
+            do_process_instruction(
+                initialize_mint(&program_id, key, &owner_key, None, 2).unwrap(),
+                vec![account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        for (key, account) in std::iter::once((&source_key, &mut source_account))
+            .chain(dest_keys.iter().zip(dest_accounts.iter_mut()))
+        {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            initialize_account(&program_id, &mismatch_key, &mint2_key, &owner_key).unwrap(),
+            vec![
+                &mut mismatch_account,
+                &mut mint2_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &source_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut source_account, &mut owner_account],
+        )
+        .unwrap();
+
+        let [dest1, dest2, dest3] = &mut dest_accounts[..] else {
+            unreachable!()
+        };
+        do_process_instruction(
+            transfer_batch(
+                &program_id,
+                &source_key,
+                &owner_key,
+                &[],
+                &[(&dest_keys[0], 100), (&dest_keys[1], 200), (&dest_keys[2], 300)],
+            )
+            .unwrap(),
+            vec![
+                &mut source_account,
+                &mut owner_account,
+                dest1,
+                dest2,
+                dest3,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&source_account.data).unwrap().amount, 400);
+        for (dest, expected) in [(&*dest1, 100), (&*dest2, 200), (&*dest3, 300)] {
+            assert_eq!(Account::unpack(&dest.data).unwrap().amount, expected);
+        }
+
+        // a bad last destination aborts the whole batch
+        let before = (source_account.clone(), dest1.clone(), dest2.clone());
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                transfer_batch(
+                    &program_id,
+                    &source_key,
+                    &owner_key,
+                    &[],
+                    &[(&dest_keys[0], 10), (&dest_keys[1], 10), (&mismatch_key, 10)],
+                )
+                .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut owner_account,
+                    dest1,
+                    dest2,
+                    &mut mismatch_account,
+                ],
+            )
+        );
+        assert_eq!(
+            (source_account.clone(), dest1.clone(), dest2.clone()),
+            before
+        );
+
+        // the total is checked against the balance
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                transfer_batch(
+                    &program_id,
+                    &source_key,
+                    &owner_key,
+                    &[],
+                    &[(&dest_keys[0], 200), (&dest_keys[1], 201)],
+                )
+                .unwrap(),
+                vec![&mut source_account, &mut owner_account, dest1, dest2],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::Overflow.into()),
+            do_process_instruction(
+                transfer_batch(
+                    &program_id,
+                    &source_key,
+                    &owner_key,
+                    &[],
+                    &[(&dest_keys[0], u64::MAX), (&dest_keys[1], 1)],
+                )
+                .unwrap(),
+                vec![&mut source_account, &mut owner_account, dest1, dest2],
+            )
+        );
+
+        // every amount needs its destination account
+        let mut instruction = transfer_batch(
+            &program_id,
+            &source_key,
+            &owner_key,
+            &[],
+            &[(&dest_keys[0], 1), (&dest_keys[1], 1)],
+        )
+        .unwrap();
+        instruction.accounts.pop();
+        assert_eq!(
+            Err(ProgramError::NotEnoughAccountKeys),
+            do_process_instruction(
+                instruction,
+                vec![&mut source_account, &mut owner_account, dest1],
+            )
+        );
+
+        // a delegate spends the total out of its allowance
+        do_process_instruction(
+            approve(&program_id, &source_key, &delegate_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut source_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_batch(
+                &program_id,
+                &source_key,
+                &delegate_key,
+                &[],
+                &[(&dest_keys[0], 20), (&dest_keys[1], 20)],
+            )
+            .unwrap(),
+            vec![&mut source_account, &mut delegate_account, dest1, dest2],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&source_account.data).unwrap().delegated_amount,
+            10
+        );
+        assert_eq!(
+            Err(TokenError::InsufficientDelegatedFunds.into()),
+            do_process_instruction(
+                transfer_batch(
+                    &program_id,
+                    &source_key,
+                    &delegate_key,
+                    &[],
+                    &[(&dest_keys[0], 6), (&dest_keys[1], 5)],
+                )
+                .unwrap(),
+                vec![&mut source_account, &mut delegate_account, dest1, dest2],
+            )
+        );
+
+        // repeated destinations, including the source, are credited per entry
+        let source_info: AccountInfo = (&source_key, false, &mut source_account).into();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let dest_info: AccountInfo = (&dest_keys[0], false, &mut *dest1).into();
+        do_process_instruction_dups(
+            transfer_batch(
+                &program_id,
+                &source_key,
+                &owner_key,
+                &[],
+                &[(&dest_keys[0], 5), (&source_key, 7), (&dest_keys[0], 5)],
+            )
+            .unwrap(),
+            vec![
+                source_info.clone(),
+                owner_info,
+                dest_info.clone(),
+                source_info.clone(),
+                dest_info.clone(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&source_info.data.borrow()).unwrap().amount, 350);
+        assert_eq!(Account::unpack(&dest_info.data.borrow()).unwrap().amount, 130);
+    }
+
+    #[test]
+    fn test_mint_to_batch() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+        let dest_keys: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let mut dest_accounts: Vec<SolanaAccount> = (0..10).map(|_| new_token_account()).collect();
+        let mismatch_key = Pubkey::new_unique();
+        let mut mismatch_account = new_token_account();
+
+        for (key, account) in [(&mint_key, &mut mint_account), (&mint2_key, &mut mint2_account)] {
+            do_process_instruction(
+                initialize_mint(&program_id, key, &owner_key, None, 2).unwrap(),
+                vec![account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        for (key, account) in dest_keys.iter().zip(dest_accounts.iter_mut()) {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            initialize_account(&program_id, &mismatch_key, &mint2_key, &owner_key).unwrap(),
+            vec![
+                &mut mismatch_account,
+                &mut mint2_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // ten recipients in one instruction
+        let mints: Vec<(&Pubkey, u64)> = dest_keys
+            .iter()
+            .zip(1..)
+            .map(|(key, amount)| (key, amount * 10))
+            .collect();
+        let mut instruction_accounts = vec![&mut mint_account, &mut owner_account];
+        instruction_accounts.extend(dest_accounts.iter_mut());
+        do_process_instruction(
+            mint_to_batch(&program_id, &mint_key, &owner_key, &[], &mints).unwrap(),
+            instruction_accounts,
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 550);
+        for (dest_account, expected) in dest_accounts.iter().zip((1..).map(|n| n * 10)) {
+            assert_eq!(Account::unpack(&dest_account.data).unwrap().amount, expected);
+        }
+
+        // one bad destination aborts everything
+        let before = (mint_account.clone(), dest_accounts.clone());
+        let (dest1, dest2) = dest_accounts.split_at_mut(1);
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                mint_to_batch(
+                    &program_id,
+                    &mint_key,
+                    &owner_key,
+                    &[],
+                    &[(&dest_keys[0], 1), (&dest_keys[1], 1), (&mismatch_key, 1)],
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut dest1[0],
+                    &mut dest2[0],
+                    &mut mismatch_account,
+                ],
+            )
+        );
+        assert_eq!((mint_account.clone(), dest_accounts.clone()), before);
+
+        // the sum overflows even though each amount fits
+        let (dest1, dest2) = dest_accounts.split_at_mut(1);
+        assert_eq!(
+            Err(TokenError::Overflow.into()),
+            do_process_instruction(
+                mint_to_batch(
+                    &program_id,
+                    &mint_key,
+                    &owner_key,
+                    &[],
+                    &[(&dest_keys[0], u64::MAX - 1000), (&dest_keys[1], 1000)],
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut dest1[0],
+                    &mut dest2[0],
+                ],
+            )
+        );
+        assert_eq!((mint_account.clone(), dest_accounts.clone()), before);
+
+        // only the mint authority may mint
+        let other_key = Pubkey::new_unique();
+        let mut other_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                mint_to_batch(&program_id, &mint_key, &other_key, &[], &[(&dest_keys[0], 1)])
+                    .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut other_account,
+                    &mut dest_accounts[0],
+                ],
+            )
+        );
+
+        // a destination listed twice is credited for each entry
+        let mint_info: AccountInfo = (&mint_key, false, &mut mint_account).into();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let dest_info: AccountInfo = (&dest_keys[0], false, &mut dest_accounts[0]).into();
+        do_process_instruction_dups(
+            mint_to_batch(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                &[],
+                &[(&dest_keys[0], 3), (&dest_keys[0], 4)],
+            )
+            .unwrap(),
+            vec![mint_info.clone(), owner_info, dest_info.clone(), dest_info.clone()],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&dest_info.data.borrow()).unwrap().amount, 17);
+        assert_eq!(Mint::unpack(&mint_info.data.borrow()).unwrap().supply, 557);
+    }
+
+    #[test]
+    fn test_mint_to_supply_cap() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint_with_max_supply(&program_id, &mint_key, &owner_key, None, 2, Some(100))
+                .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().max_supply,
+            COption::Some(100)
+        );
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+
+        // minting up to exactly the cap is fine
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 60).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 40).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 100);
+
+        // one over is not
+        assert_eq!(
+            Err(TokenError::SupplyCapExceeded.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner_account],
+            )
+        );
+
+        // burning frees headroom
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 10).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::SupplyCapExceeded.into()),
+            do_process_instruction(
+                mint_to_batch(
+                    &program_id,
+                    &mint_key,
+                    &owner_key,
+                    &[],
+                    &[(&account_key, 5), (&account2_key, 6)],
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut account_account,
+                    &mut account2_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            mint_to_batch(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                &[],
+                &[(&account_key, 5), (&account2_key, 5)],
+            )
+            .unwrap(),
+            vec![
+                &mut mint_account,
+                &mut owner_account,
+                &mut account_account,
+                &mut account2_account,
+            ],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.supply, 100);
+        assert_eq!(mint.max_supply, COption::Some(100));
+
+        // a mint without a cap is only bounded by overflow
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint2_account.data).unwrap().max_supply,
+            COption::None
+        );
+    }
+
+    #[test]
+    fn test_two_step_ownership() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let new_owner_key = Pubkey::new_unique();
+        let mut new_owner_account = SolanaAccount::default();
+        let other_key = Pubkey::new_unique();
+        let mut other_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // nothing to accept yet
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                accept_ownership(&program_id, &account_key, &new_owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut new_owner_account],
+            )
+        );
+
+        // only the owner may propose
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                propose_owner(&program_id, &account_key, Some(&other_key), &other_key, &[])
+                    .unwrap(),
+                vec![&mut account_account, &mut other_account],
+            )
+        );
+
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&new_owner_key), &owner_key, &[])
+                .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(account.pending_owner, COption::Some(new_owner_key));
+
+        // proposing again replaces the pending proposal
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&other_key), &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().pending_owner,
+            COption::Some(other_key)
+        );
+
+        // so the first proposed owner can no longer accept
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                accept_ownership(&program_id, &account_key, &new_owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut new_owner_account],
+            )
+        );
+
+        // the pending owner may cancel
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, None, &other_key, &[]).unwrap(),
+            vec![&mut account_account, &mut other_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().pending_owner,
+            COption::None
+        );
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                accept_ownership(&program_id, &account_key, &other_key, &[]).unwrap(),
+                vec![&mut account_account, &mut other_account],
+            )
+        );
+
+        // and so may the owner, but nobody else
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&new_owner_key), &owner_key, &[])
+                .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                propose_owner(&program_id, &account_key, None, &other_key, &[]).unwrap(),
+                vec![&mut account_account, &mut other_account],
+            )
+        );
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, None, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().pending_owner,
+            COption::None
+        );
+
+        // accepting hands over the account and drops the old approvals
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&new_owner_key), &owner_key, &[])
+                .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            accept_ownership(&program_id, &account_key, &new_owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut new_owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, new_owner_key);
+        assert_eq!(account.pending_owner, COption::None);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+
+        // a direct owner change drops a pending proposal
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&other_key), &new_owner_key, &[])
+                .unwrap(),
+            vec![&mut account_account, &mut new_owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&owner_key),
+                AuthorityType::AccountOwner,
+                &new_owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut new_owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(account.pending_owner, COption::None);
+    }
+
+    #[test]
+    fn test_permanent_delegate() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let permanent_delegate_key = Pubkey::new_unique();
+        let mut permanent_delegate_account = SolanaAccount::default();
+        let other_key = Pubkey::new_unique();
+        let mut other_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint_with_permanent_delegate(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &permanent_delegate_key,
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().permanent_delegate,
+            COption::Some(permanent_delegate_key)
+        );
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // moves tokens it was never approved for, leaving the approval alone
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &permanent_delegate_key,
+                &[],
+                30,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut permanent_delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 70);
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 10);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 30);
+
+        // the mint has to be passed in
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                transfer(
+                    &program_id,
+                    &account_key,
+                    &account2_key,
+                    &permanent_delegate_key,
+                    &[],
+                    30,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut permanent_delegate_account,
+                ],
+            )
+        );
+
+        // a permanent delegate is set, but someone else signs
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &other_key,
+                    &[],
+                    30,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut other_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &other_key, &[], 10, 2).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut other_account],
+            )
+        );
+
+        // burns too
+        do_process_instruction(
+            burn_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &permanent_delegate_key,
+                &[],
+                20,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut permanent_delegate_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 50);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 80);
+
+        // and a sweep takes the whole balance
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &permanent_delegate_key,
+                &[],
+                TRANSFER_ALL,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut permanent_delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 0);
+        assert_eq!(account.delegated_amount, 10);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 80);
+
+        // the owner and the delegate keep their usual rights
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().delegate,
+            COption::None
+        );
+    }
+
+    #[test]
+    fn test_default_account_state() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let account3_key = Pubkey::new_unique();
+        let mut account3_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let freeze_key = Pubkey::new_unique();
+        let mut freeze_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&freeze_key), 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().default_account_state,
+            AccountState::Initialized
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // only the freeze authority may change it, and never to uninitialized
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_default_account_state(
+                    &program_id,
+                    &mint_key,
+                    &owner_key,
+                    &[],
+                    AccountState::Frozen,
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                set_default_account_state(
+                    &program_id,
+                    &mint_key,
+                    &freeze_key,
+                    &[],
+                    AccountState::Uninitialized,
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut freeze_account],
+            )
+        );
+        do_process_instruction(
+            set_default_account_state(&program_id, &mint_key, &freeze_key, &[], AccountState::Frozen)
+                .unwrap(),
+            vec![&mut mint_account, &mut freeze_account],
+        )
+        .unwrap();
+
+        // new accounts start frozen and cannot receive anything
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account2_account.data).unwrap().state,
+            AccountState::Frozen
+        );
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 10).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account2_key, &owner_key, &[], 10).unwrap(),
+                vec![&mut mint_account, &mut account2_account, &mut owner_account],
+            )
+        );
+
+        // until the freeze authority thaws them
+        do_process_instruction(
+            thaw_account(&program_id, &account2_key, &mint_key, &freeze_key, &[]).unwrap(),
+            vec![&mut account2_account, &mut mint_account, &mut freeze_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 10);
+
+        // switching back only affects accounts created afterwards
+        do_process_instruction(
+            set_default_account_state(
+                &program_id,
+                &mint_key,
+                &freeze_key,
+                &[],
+                AccountState::Initialized,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut freeze_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account3_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account3_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account3_account.data).unwrap().state,
+            AccountState::Initialized
+        );
+
+        // a mint without a freeze authority keeps the default
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                set_default_account_state(
+                    &program_id,
+                    &mint2_key,
+                    &owner_key,
+                    &[],
+                    AccountState::Frozen,
+                )
+                .unwrap(),
+                vec![&mut mint2_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_require_memo_transfers() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        // program-test is not available, so the instructions sysvar is built
+        // by hand from the program ids of the transaction's instructions
+        let instructions_sysvar = |program_ids: &[&Pubkey]| {
+            let instructions = program_ids
+                .iter()
+                .map(|program_id| sysvar::instructions::BorrowedInstruction {
+                    program_id,
+                    accounts: vec![],
+                    data: &[],
+                })
+                .collect::<Vec<_>>();
+            let mut account = SolanaAccount::new(0, 0, &sysvar::id());
+            account.data = sysvar::instructions::construct_instructions_data(&instructions);
+            account
+        };
+        let with_instructions_sysvar = |mut instruction: Instruction| {
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+            instruction
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner2_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner2_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // only the owner may set the flag
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                require_memo_transfers(&program_id, &account2_key, &owner_key, &[], true).unwrap(),
+                vec![&mut account2_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            require_memo_transfers(&program_id, &account2_key, &owner2_key, &[], true).unwrap(),
+            vec![&mut account2_account, &mut owner2_account],
+        )
+        .unwrap();
+        assert!(Account::unpack(&account2_account.data).unwrap().memo_required);
+
+        // no instructions sysvar
+        assert_eq!(
+            Err(TokenError::MissingMemo.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100).unwrap(),
+                vec![&mut account_account, &mut account2_account, &mut owner_account],
+            )
+        );
+
+        // a transaction without a memo
+        let mut sysvar_account = instructions_sysvar(&[&program_id]);
+        assert_eq!(
+            Err(TokenError::MissingMemo.into()),
+            do_process_instruction(
+                with_instructions_sysvar(
+                    transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100)
+                        .unwrap()
+                ),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                    &mut sysvar_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MissingMemo.into()),
+            do_process_instruction(
+                with_instructions_sysvar(
+                    transfer_batch(&program_id, &account_key, &owner_key, &[], &[(&account2_key, 100)])
+                        .unwrap()
+                ),
+                vec![
+                    &mut account_account,
+                    &mut owner_account,
+                    &mut account2_account,
+                    &mut sysvar_account,
+                ],
+            )
+        );
+
+        // a memo anywhere in the transaction, from either deployment
+        for memo_id in [memo::id(), memo::ID_V1] {
+            let mut sysvar_account = instructions_sysvar(&[&memo_id, &program_id]);
+            do_process_instruction(
+                with_instructions_sysvar(
+                    transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100)
+                        .unwrap(),
+                ),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                    &mut sysvar_account,
+                ],
+            )
+            .unwrap();
+        }
+        let mut sysvar_account = instructions_sysvar(&[&program_id, &memo::id()]);
+        do_process_instruction(
+            with_instructions_sysvar(
+                transfer_batch(&program_id, &account_key, &owner_key, &[], &[(&account2_key, 100)])
+                    .unwrap(),
+            ),
+            vec![
+                &mut account_account,
+                &mut owner_account,
+                &mut account2_account,
+                &mut sysvar_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 300);
+
+        // outgoing transfers from a flagged account need no memo
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner2_key, &[], 50).unwrap(),
+            vec![&mut account2_account, &mut account_account, &mut owner2_account],
+        )
+        .unwrap();
+
+        // once disabled, plain transfers go through again
+        do_process_instruction(
+            require_memo_transfers(&program_id, &account2_key, &owner2_key, &[], false).unwrap(),
+            vec![&mut account2_account, &mut owner2_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut account_account, &mut account2_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 350);
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let fee_authority_key = Pubkey::new_unique();
+        let mut fee_authority_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint_with_transfer_fee_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &fee_authority_key,
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().transfer_fee_config,
+            TransferFeeConfig {
+                transfer_fee_authority: COption::Some(fee_authority_key),
+                transfer_fee_basis_points: 0,
+                maximum_fee: 0,
+            }
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner2_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner2_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 10_000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // only the fee authority sets the fee, and never above the whole amount
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_transfer_fee(&program_id, &mint_key, &owner_key, &[], 100, 50).unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::TransferFeeExceedsMaximum.into()),
+            do_process_instruction(
+                set_transfer_fee(&program_id, &mint_key, &fee_authority_key, &[], 10_001, 50)
+                    .unwrap(),
+                vec![&mut mint_account, &mut fee_authority_account],
+            )
+        );
+        do_process_instruction(
+            set_transfer_fee(&program_id, &mint_key, &fee_authority_key, &[], 100, 50).unwrap(),
+            vec![&mut mint_account, &mut fee_authority_account],
+        )
+        .unwrap();
+
+        // accounts of a mint with a fee authority are marked as they start, so
+        // no transfer that leaves out the mint can skip the fee
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert!(account.mint_required);
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction(
+                transfer_batch(
+                    &program_id,
+                    &account_key,
+                    &owner_key,
+                    &[],
+                    &[(&account2_key, 1)],
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut owner_account,
+                    &mut account2_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction(
+                transfer_vested(
+                    &program_id,
+                    &account_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    1,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the caller's fee must match the computed one
+        assert_eq!(
+            Err(TokenError::FeeMismatch.into()),
+            do_process_instruction(
+                transfer_checked_with_fee(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1_000,
+                    2,
+                    9,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            transfer_checked_with_fee(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                1_000,
+                2,
+                10,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 9_000);
+        assert_eq!(account.withheld_amount, 0);
+        let account2 = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 990);
+        assert_eq!(account2.withheld_amount, 10);
+
+        // the smallest transfer still pays a whole token
+        do_process_instruction(
+            transfer_checked_with_fee(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                1,
+                2,
+                1,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account2 = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 990);
+        assert_eq!(account2.withheld_amount, 11);
+
+        // TransferChecked withholds the fee too, capped at the maximum
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                8_999,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 0);
+        let account2 = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 990 + 8_949);
+        assert_eq!(account2.withheld_amount, 61);
+        assert_eq!(
+            account2.amount + account2.withheld_amount,
+            Mint::unpack(&mint_account.data).unwrap().supply
+        );
+
+        // a mint without a fee authority can never charge a fee
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NoTransferFeeAuthority.into()),
+            do_process_instruction(
+                set_transfer_fee(&program_id, &mint2_key, &owner_key, &[], 100, 50).unwrap(),
+                vec![&mut mint2_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_withheld_fees() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let fee_account_key = Pubkey::new_unique();
+        let mut fee_account_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let fee_authority_key = Pubkey::new_unique();
+        let mut fee_authority_account = SolanaAccount::default();
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint_with_transfer_fee_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &fee_authority_key,
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account, owner) in [
+            (&account_key, &mut account_account, &owner_key),
+            (&account2_key, &mut account2_account, &owner2_key),
+            (&fee_account_key, &mut fee_account_account, &fee_authority_key),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, owner).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 10_000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_transfer_fee(&program_id, &mint_key, &fee_authority_key, &[], 100, 1_000).unwrap(),
+            vec![&mut mint_account, &mut fee_authority_account],
+        )
+        .unwrap();
+
+        // a fee withheld in account2, which then sends back its balance
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                1_000,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account2_key,
+                &mint_key,
+                &account_key,
+                &owner2_key,
+                &[],
+                990,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut account_account,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+        let account2 = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 0);
+        assert_eq!(account2.withheld_amount, 10);
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 9_980);
+        assert_eq!(account.withheld_amount, 10);
+
+        // the empty account cannot be closed until its fees are harvested
+        assert_eq!(
+            Err(TokenError::AccountHasWithheldTransferFees.into()),
+            do_process_instruction(
+                close_account(&program_id, &account2_key, &dest_key, &owner2_key, &[]).unwrap(),
+                vec![&mut account2_account, &mut dest_account, &mut owner2_account],
+            )
+        );
+
+        // anyone may harvest, and accounts without fees are skipped over
+        do_process_instruction(
+            harvest_withheld_tokens_to_mint(
+                &program_id,
+                &mint_key,
+                &[&account2_key, &account_key, &fee_account_key],
+            )
+            .unwrap(),
+            vec![
+                &mut mint_account,
+                &mut account2_account,
+                &mut account_account,
+                &mut fee_account_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().withheld_amount, 20);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().withheld_amount, 0);
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 9_980);
+        assert_eq!(account.withheld_amount, 0);
+        do_process_instruction(
+            close_account(&program_id, &account2_key, &dest_key, &owner2_key, &[]).unwrap(),
+            vec![&mut account2_account, &mut dest_account, &mut owner2_account],
+        )
+        .unwrap();
+
+        // a second fee, this time withheld in the fee authority's own account
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &fee_account_key,
+                &owner_key,
+                &[],
+                2_000,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut fee_account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let fee_account = Account::unpack(&fee_account_account.data).unwrap();
+        assert_eq!(fee_account.amount, 1_980);
+        assert_eq!(fee_account.withheld_amount, 20);
+
+        // only the fee authority withdraws
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                withdraw_withheld_tokens_from_accounts(
+                    &program_id,
+                    &mint_key,
+                    &fee_account_key,
+                    &owner_key,
+                    &[],
+                    &[&account_key],
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut fee_account_account,
+                    &mut owner_account,
+                    &mut account_account,
+                ],
+            )
+        );
+
+        // the destination may also be a source, and the harvested fees come
+        // along
+        {
+            let mint_info: AccountInfo = (&mint_key, false, &mut mint_account).into();
+            let fee_account_info: AccountInfo =
+                (&fee_account_key, false, &mut fee_account_account).into();
+            let fee_authority_info: AccountInfo =
+                (&fee_authority_key, true, &mut fee_authority_account).into();
+            let account_info: AccountInfo = (&account_key, false, &mut account_account).into();
+            do_process_instruction_dups(
+                withdraw_withheld_tokens_from_accounts(
+                    &program_id,
+                    &mint_key,
+                    &fee_account_key,
+                    &fee_authority_key,
+                    &[],
+                    &[&fee_account_key, &account_key],
+                )
+                .unwrap(),
+                vec![
+                    mint_info,
+                    fee_account_info.clone(),
+                    fee_authority_info,
+                    fee_account_info,
+                    account_info,
+                ],
+            )
+            .unwrap();
+        }
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.withheld_amount, 0);
+        let fee_account = Account::unpack(&fee_account_account.data).unwrap();
+        assert_eq!(fee_account.amount, 1_980 + 20 + 20);
+        assert_eq!(fee_account.withheld_amount, 0);
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 7_980);
+        assert_eq!(account.withheld_amount, 0);
+
+        // nothing was minted or burned along the way
+        assert_eq!(mint.supply, account.amount + fee_account.amount);
+    }
+
+    #[test]
+    fn test_interest_bearing_mint() {
+        const YEAR: i64 = crate::state::SECONDS_PER_YEAR as i64;
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let rate_authority_key = Pubkey::new_unique();
+        let mut rate_authority_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+        let ui_amount = |mint_account: &mut SolanaAccount, amount| {
+            do_process_instruction(
+                amount_to_ui_amount(&program_id, &mint_key, amount).unwrap(),
+                vec![mint_account],
+            )
+            .unwrap();
+            let (_, data) = solana_program::program::get_return_data().unwrap();
+            String::from_utf8(data).unwrap()
+        };
+        let assert_close = |ui_amount: String, expected: f64| {
+            let ui_amount = ui_amount.parse::<f64>().unwrap();
+            assert!(
+                (ui_amount - expected).abs() <= expected * 1e-12,
+                "{} != {}",
+                ui_amount,
+                expected
+            );
+        };
+
+        // interest accrues from initialization, at a zero rate
+        CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.set(1_000));
+        do_process_instruction(
+            initialize_mint_with_rate_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &rate_authority_key,
+                6,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().interest_bearing_config,
+            InterestBearingConfig {
+                rate_authority: COption::Some(rate_authority_key),
+                initialization_timestamp: 1_000,
+                pre_update_average_rate: 0,
+                last_update_timestamp: 1_000,
+                current_rate: 0,
+            }
+        );
+        CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.set(1_000 + YEAR));
+        assert_eq!(ui_amount(&mut mint_account, 1_500_000), "1.5");
+
+        // only the rate authority may change the rate
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                update_rate_interest_bearing_mint(&program_id, &mint_key, &owner_key, &[], 500)
+                    .unwrap(),
+                vec![&mut mint_account, &mut rate_authority_account],
+            )
+        );
+        let mut instruction = update_rate_interest_bearing_mint(
+            &program_id,
+            &mint_key,
+            &rate_authority_key,
+            &[],
+            500,
+        )
+        .unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![&mut mint_account, &mut rate_authority_account],
+            )
+        );
+
+        // a year at 5% from here
+        do_process_instruction(
+            update_rate_interest_bearing_mint(
+                &program_id,
+                &mint_key,
+                &rate_authority_key,
+                &[],
+                500,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rate_authority_account],
+        )
+        .unwrap();
+        assert_eq!(ui_amount(&mut mint_account, 1_000_000), "1");
+        CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.set(1_000 + 2 * YEAR));
+        assert_close(ui_amount(&mut mint_account, 1_000_000), 0.05f64.exp());
+        do_process_instruction(
+            ui_amount_to_amount(&program_id, &mint_key, &0.05f64.exp().to_string()).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        let (_, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(u64::from_le_bytes(data[..8].try_into().unwrap()), 1_000_000);
+
+        // a negative rate keeps the interest already accrued and then shrinks
+        // the UI amount
+        do_process_instruction(
+            update_rate_interest_bearing_mint(
+                &program_id,
+                &mint_key,
+                &rate_authority_key,
+                &[],
+                -1_000,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rate_authority_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().interest_bearing_config,
+            InterestBearingConfig {
+                rate_authority: COption::Some(rate_authority_key),
+                initialization_timestamp: 1_000,
+                pre_update_average_rate: 250,
+                last_update_timestamp: 1_000 + 2 * YEAR,
+                current_rate: -1_000,
+            }
+        );
+        assert_close(ui_amount(&mut mint_account, 1_000_000), 0.05f64.exp());
+        CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.set(1_000 + 3 * YEAR));
+        assert_close(ui_amount(&mut mint_account, 1_000_000), (-0.05f64).exp());
+        do_process_instruction(
+            ui_amount_to_amount(&program_id, &mint_key, &(-0.05f64).exp().to_string()).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        let (_, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(u64::from_le_bytes(data[..8].try_into().unwrap()), 1_000_000);
+
+        // mints without a rate authority never accrue interest
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 6).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NoRateAuthority.into()),
+            do_process_instruction(
+                update_rate_interest_bearing_mint(
+                    &program_id,
+                    &mint2_key,
+                    &rate_authority_key,
+                    &[],
+                    500,
+                )
+                .unwrap(),
+                vec![&mut mint2_account, &mut rate_authority_account],
+            )
+        );
+        CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.set(1_000 + 10 * YEAR));
+        do_process_instruction(
+            amount_to_ui_amount(&program_id, &mint2_key, 1_500_000).unwrap(),
+            vec![&mut mint2_account],
+        )
+        .unwrap();
+        let (_, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(std::str::from_utf8(&data).unwrap(), "1.5");
+    }
+
+    #[test]
+    fn test_pause() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let account3_key = Pubkey::new_unique();
+        let mut account3_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let pause_authority_key = Pubkey::new_unique();
+        let mut pause_authority_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint_with_pause_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &pause_authority_key,
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.pause_authority, COption::Some(pause_authority_key));
+        assert!(!mint.paused);
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![account, &mut mint_account, &mut owner_account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1_000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // only the pause authority may pause
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                pause(&program_id, &mint_key, &owner_key, &[]).unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        let mut instruction = pause(&program_id, &mint_key, &pause_authority_key, &[]).unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![&mut mint_account, &mut pause_authority_account],
+            )
+        );
+        do_process_instruction(
+            pause(&program_id, &mint_key, &pause_authority_key, &[]).unwrap(),
+            vec![&mut mint_account, &mut pause_authority_account],
+        )
+        .unwrap();
+        assert!(Mint::unpack(&mint_account.data).unwrap().paused);
+
+        // nothing moves while paused
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                mint_to_batch(&program_id, &mint_key, &owner_key, &[], &[(&account_key, 1)])
+                    .unwrap(),
+                vec![&mut mint_account, &mut owner_account, &mut account_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 1, 2)
+                    .unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintPaused.into()),
+            do_process_instruction(
+                transfer_checked_with_fee(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                    0,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 1_000);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 1_000);
+
+        // new accounts can still be opened
+        do_process_instruction(
+            initialize_account(&program_id, &account3_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account3_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // and everything works again once resumed
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                resume(&program_id, &mint_key, &owner_key, &[]).unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            resume(&program_id, &mint_key, &pause_authority_key, &[]).unwrap(),
+            vec![&mut mint_account, &mut pause_authority_account],
+        )
+        .unwrap();
+        assert!(!Mint::unpack(&mint_account.data).unwrap().paused);
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to_batch(&program_id, &mint_key, &owner_key, &[], &[(&account_key, 1)]).unwrap(),
+            vec![&mut mint_account, &mut owner_account, &mut account_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 1, 2).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                10,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 990);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 10);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 1_000);
+
+        // mints without a pause authority can never be paused
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NoPauseAuthority.into()),
+            do_process_instruction(
+                pause(&program_id, &mint2_key, &owner_key, &[]).unwrap(),
+                vec![&mut mint2_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_reallocate() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let payer_key = Pubkey::new_unique();
+        let mut payer_account = SolanaAccount::new(1_000_000_000, 0, &Pubkey::default());
+        let mut system_program_account = SolanaAccount::default();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // an account from before any field was appended, with a delegate
+        let legacy = Account {
+            mint: mint_key,
+            owner: owner_key,
+            amount: 100,
+            delegate: COption::Some(delegate_key),
+            delegated_amount: 40,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(legacy, &mut data).unwrap();
+        let legacy_balance = Rent::default().minimum_balance(LEGACY_ACCOUNT_LEN);
+        let mut account_account = SolanaAccount::new(legacy_balance, 0, &program_id);
+        account_account.data = data[..LEGACY_ACCOUNT_LEN].to_vec();
+        assert_eq!(Ok(legacy), Account::unpack(&account_account.data));
+
+        // only the owner may grow it
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_serialized(
+                reallocate(&program_id, &account_key, &payer_key, &delegate_key, &[]).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        let mut instruction =
+            reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap();
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction_serialized(
+                instruction,
+                vec![
+                    &mut account_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the payer funds the rent for the new fields
+        do_process_instruction_serialized(
+            reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut account_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(account_account.data.len(), Account::LEN);
+        assert_eq!(account_account.data[..LEGACY_ACCOUNT_LEN], data[..LEGACY_ACCOUNT_LEN]);
+        assert_eq!(Account::unpack(&account_account.data).unwrap(), legacy);
+        assert_eq!(account_account.lamports, account_minimum_balance());
+        assert_eq!(
+            payer_account.lamports,
+            1_000_000_000 - (account_minimum_balance() - legacy_balance)
+        );
+
+        // and the delegate can still spend its allowance
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 40).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 60);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 40);
+
+        // growing a current account changes nothing, and costs nothing
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut account_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap(), account);
+        assert_eq!(payer_account.lamports, payer_lamports);
+
+        // accounts never shrink, nor grow from shorter than the first layout
+        for len in [LEGACY_ACCOUNT_LEN - 1, Account::LEN + 1] {
+            let mut account_account = account_account.clone();
+            account_account.data.resize(len, 0);
+            assert_eq!(
+                Err(ProgramError::InvalidAccountData),
+                do_process_instruction_serialized(
+                    reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap(),
+                    vec![
+                        &mut account_account,
+                        &mut payer_account,
+                        &mut system_program_account,
+                        &mut owner_account,
+                    ],
+                )
+            );
+        }
+
+        // an account in SPL Token's layout grows into this crate's order
+        let spl_balance = Rent::default().minimum_balance(SPL_ACCOUNT_LEN);
+        let mut spl_account = SolanaAccount::new(spl_balance, SPL_ACCOUNT_LEN, &program_id);
+        Account::pack(legacy, &mut spl_account.data).unwrap();
+        assert_eq!(
+            spl_account.data[SPL_ACCOUNT_STATE_OFFSET],
+            AccountState::Initialized as u8
+        );
+        do_process_instruction_serialized(
+            reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut spl_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(spl_account.data, data);
+        assert_eq!(spl_account.lamports, account_minimum_balance());
+
+        // wrapped SOL moves the larger reserve out of the token amount
+        let native_len = LEGACY_ACCOUNT_LEN + 12;
+        let native_reserve = Rent::default().minimum_balance(native_len);
+        let native = Account {
+            mint: crate::native_mint::id(),
+            owner: owner_key,
+            amount: 50,
+            state: AccountState::Initialized,
+            is_native: COption::Some(native_reserve),
+            ..Account::default()
+        };
+        Account::pack(native, &mut data).unwrap();
+        let mut native_account = SolanaAccount::new(native_reserve + 50, 0, &program_id);
+        native_account.data = data[..native_len].to_vec();
+        do_process_instruction_serialized(
+            reallocate(&program_id, &account_key, &payer_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut native_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&native_account.data).unwrap(),
+            Account {
+                is_native: COption::Some(account_minimum_balance()),
+                ..native
+            }
+        );
+        assert_eq!(native_account.lamports, account_minimum_balance() + 50);
+    }
+
+    #[test]
+    fn test_legacy_layouts() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let new_owner_key = Pubkey::new_unique();
+        let mut new_owner_account = SolanaAccount::default();
+        let payer_key = Pubkey::new_unique();
+        let mut payer_account = SolanaAccount::new(1_000_000_000, 0, &Pubkey::default());
+        let mut system_program_account = SolanaAccount::default();
+        let account_key = Pubkey::new_unique();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+
+        // a mint and an account written by the first release
+        let legacy_mint_balance = Rent::default().minimum_balance(LEGACY_MINT_LEN);
+        let mut mint_account =
+            SolanaAccount::new(legacy_mint_balance, LEGACY_MINT_LEN, &program_id);
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(owner_key),
+                supply: 100,
+                decimals: 2,
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut mint_account.data,
+        )
+        .unwrap();
+        let mut account_account = SolanaAccount::new(
+            Rent::default().minimum_balance(LEGACY_ACCOUNT_LEN),
+            LEGACY_ACCOUNT_LEN,
+            &program_id,
+        );
+        Account::pack(
+            Account {
+                mint: mint_key,
+                owner: owner_key,
+                amount: 100,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut account_account.data,
+        )
+        .unwrap();
+
+        // they keep working alongside current ones
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 50).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 30).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(account_account.data.len(), LEGACY_ACCOUNT_LEN);
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 130);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 20);
+        assert_eq!(mint_account.data.len(), LEGACY_MINT_LEN);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 150);
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&new_owner_key),
+                AuthorityType::AccountOwner,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // fields appended since have nowhere to go until the account grows
+        let instruction = set_authority(
+            &program_id,
+            &account_key,
+            Some(&owner_key),
+            AuthorityType::CloseAccount,
+            &new_owner_key,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                instruction.clone(),
+                vec![&mut account_account, &mut new_owner_account],
+            )
+        );
+        do_process_instruction_serialized(
+            reallocate(&program_id, &account_key, &payer_key, &new_owner_key, &[]).unwrap(),
+            vec![
+                &mut account_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut new_owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            instruction.clone(),
+            vec![&mut account_account, &mut new_owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().close_authority,
+            COption::Some(owner_key)
+        );
+
+        // an uninitialized mint is not upgraded
+        let mut uninitialized_account =
+            SolanaAccount::new(legacy_mint_balance, LEGACY_MINT_LEN, &program_id);
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction_serialized(
+                reallocate_mint(&program_id, &mint_key, &payer_key).unwrap(),
+                vec![
+                    &mut uninitialized_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                ],
+            )
+        );
+
+        // anyone can grow the mint, paying for the larger reserve
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            reallocate_mint(&program_id, &mint_key, &payer_key).unwrap(),
+            vec![
+                &mut mint_account,
+                &mut payer_account,
+                &mut system_program_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(mint_account.data.len(), Mint::LEN);
+        assert_eq!(mint_account.lamports, mint_minimum_balance());
+        assert_eq!(
+            payer_account.lamports,
+            payer_lamports - (mint_minimum_balance() - legacy_mint_balance)
+        );
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.supply, 150);
+        assert_eq!(mint.mint_authority, COption::Some(owner_key));
+        assert_eq!(mint.freeze_authority, COption::None);
+
+        // growing again changes nothing
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            reallocate_mint(&program_id, &mint_key, &payer_key).unwrap(),
+            vec![
+                &mut mint_account,
+                &mut payer_account,
+                &mut system_program_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap(), mint);
+        assert_eq!(payer_account.lamports, payer_lamports);
+
+        // mints never shrink, nor grow from shorter than the first layout
+        for len in [LEGACY_MINT_LEN - 1, Mint::LEN + 1] {
+            let mut mint_account = mint_account.clone();
+            mint_account.data.resize(len, 0);
+            assert_eq!(
+                Err(ProgramError::InvalidAccountData),
+                do_process_instruction_serialized(
+                    reallocate_mint(&program_id, &mint_key, &payer_key).unwrap(),
+                    vec![
+                        &mut mint_account,
+                        &mut payer_account,
+                        &mut system_program_account,
+                    ],
+                )
+            );
+        }
+
+        // a mint account not owned by the program is rejected
+        let mut foreign_account =
+            SolanaAccount::new(legacy_mint_balance, LEGACY_MINT_LEN, &Pubkey::new_unique());
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction_serialized(
+                reallocate_mint(&program_id, &mint_key, &payer_key).unwrap(),
+                vec![
+                    &mut foreign_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_immutable_owner() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let new_owner_key = Pubkey::new_unique();
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // the flag is set first, and survives initialization
+        do_process_instruction(
+            initialize_immutable_owner(&program_id, &account_key).unwrap(),
+            vec![&mut account_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert!(account.immutable_owner);
+        assert_eq!(account.owner, owner_key);
+
+        // the owner cannot be reassigned, directly or through a proposal
+        assert_eq!(
+            Err(TokenError::ImmutableOwner.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &account_key,
+                    Some(&new_owner_key),
+                    AuthorityType::AccountOwner,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::ImmutableOwner.into()),
+            do_process_instruction(
+                propose_owner(&program_id, &account_key, Some(&new_owner_key), &owner_key, &[])
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        // other authorities can still change
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&new_owner_key),
+                AuthorityType::CloseAccount,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(account.close_authority, COption::Some(new_owner_key));
+
+        // too late once the account is initialized
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_immutable_owner(&program_id, &account_key).unwrap(),
+                vec![&mut account2_account],
+            )
+        );
+        assert!(!Account::unpack(&account2_account.data).unwrap().immutable_owner);
+
+        // the flag has no room in the V1 layout
+        let mut legacy_account =
+            SolanaAccount::new(account_minimum_balance(), LEGACY_ACCOUNT_LEN, &program_id);
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                initialize_immutable_owner(&program_id, &account_key).unwrap(),
+                vec![&mut legacy_account],
+            )
+        );
+
+        let mut foreign_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &Pubkey::new_unique(),
+        );
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                initialize_immutable_owner(&program_id, &account_key).unwrap(),
+                vec![&mut foreign_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_cpi_guard() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+        let in_cpi = |invoked: bool| {
+            STACK_HEIGHT.with(|height| {
+                height.set(TRANSACTION_LEVEL_STACK_HEIGHT + invoked as usize)
+            })
+        };
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![account, &mut mint_account, &mut owner_account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // without the guard the owner can act through a CPI
+        in_cpi(true);
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // the guard can only be set from a top-level instruction
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                enable_cpi_guard(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        in_cpi(false);
+        let mut instruction = enable_cpi_guard(&program_id, &account_key, &owner_key, &[]).unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(instruction, vec![&mut account_account, &mut owner_account])
+        );
+        do_process_instruction(
+            enable_cpi_guard(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert!(Account::unpack(&account_account.data).unwrap().cpi_guard);
+
+        // now the owner's signature is refused inside a CPI
+        in_cpi(true);
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 10).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 10).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 60).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut delegate_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                increase_delegated_amount(&program_id, &account_key, &owner_key, &[], 10)
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &account_key,
+                    Some(&delegate_key),
+                    AuthorityType::AccountOwner,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                propose_owner(&program_id, &account_key, Some(&delegate_key), &owner_key, &[])
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::CpiGuarded.into()),
+            do_process_instruction(
+                disable_cpi_guard(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        // while the delegate keeps its allowance, and the owner can still
+        // shrink it
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 20).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            decrease_delegated_amount(&program_id, &account_key, &owner_key, &[], 10).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 70);
+        assert_eq!(account.delegated_amount, 20);
+
+        // a top-level instruction is unaffected
+        in_cpi(false);
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            disable_cpi_guard(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 60);
+        assert!(!account.cpi_guard);
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 40);
+
+        in_cpi(true);
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 10).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        in_cpi(false);
+    }
+
+    #[test]
+    fn test_transfer_hook_authority() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let owner_key = Pubkey::new_unique();
+        let authority_key = Pubkey::new_unique();
+        let mut authority_account = SolanaAccount::default();
+        let hook_key = Pubkey::new_unique();
+        let new_hook_key = Pubkey::new_unique();
+        let mut rent_sysvar = rent_sysvar();
+
+        // the hook is set first, and survives initialization
+        do_process_instruction(
+            initialize_transfer_hook(&program_id, &mint_key, Some(&authority_key), Some(&hook_key))
+                .unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.transfer_hook_authority, COption::Some(authority_key));
+        assert_eq!(mint.transfer_hook_program_id, COption::Some(hook_key));
+
+        // too late once the mint is initialized
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_transfer_hook(&program_id, &mint_key, None, None).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        // only the authority may change the hook
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                update_transfer_hook(&program_id, &mint_key, &owner_key, &[], None).unwrap(),
+                vec![&mut mint_account, &mut authority_account],
+            )
+        );
+        let mut instruction =
+            update_transfer_hook(&program_id, &mint_key, &authority_key, &[], None).unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(instruction, vec![&mut mint_account, &mut authority_account])
+        );
+        do_process_instruction(
+            update_transfer_hook(&program_id, &mint_key, &authority_key, &[], Some(&new_hook_key))
+                .unwrap(),
+            vec![&mut mint_account, &mut authority_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().transfer_hook_program_id,
+            COption::Some(new_hook_key)
+        );
+        do_process_instruction(
+            update_transfer_hook(&program_id, &mint_key, &authority_key, &[], None).unwrap(),
+            vec![&mut mint_account, &mut authority_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().transfer_hook_program_id,
+            COption::None
+        );
+
+        // a hook set without an authority is fixed
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_transfer_hook(&program_id, &mint2_key, None, Some(&hook_key)).unwrap(),
+            vec![&mut mint2_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NoTransferHookAuthority.into()),
+            do_process_instruction(
+                update_transfer_hook(&program_id, &mint2_key, &authority_key, &[], None).unwrap(),
+                vec![&mut mint2_account, &mut authority_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_token_metadata() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let metadata_key = Pubkey::new_unique();
+        let mut metadata_account = SolanaAccount::new(0, 0, &program_id);
+        let payer_key = Pubkey::new_unique();
+        let mut payer_account =
+            SolanaAccount::new(1_000_000_000, 0, &solana_program::system_program::id());
+        let mut system_program_account = SolanaAccount::default();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let authority_key = Pubkey::new_unique();
+        let mut authority_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+        let rent = Rent::default();
+
+        // the pointer is set first, and survives initialization
+        do_process_instruction(
+            initialize_metadata_pointer(&program_id, &mint_key, Some(&metadata_key)).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().metadata_pointer,
+            COption::Some(metadata_key)
+        );
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_metadata_pointer(&program_id, &mint_key, None).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        let initialize = |metadata_key: &Pubkey, mint_authority_key: &Pubkey, name: &str| {
+            initialize_token_metadata(
+                &program_id,
+                metadata_key,
+                &payer_key,
+                &mint_key,
+                &authority_key,
+                mint_authority_key,
+                &[],
+                name,
+                "TKN",
+                "https://example.com/a",
+            )
+            .unwrap()
+        };
+
+        // metadata only goes where the mint points
+        let mut other_account = SolanaAccount::new(0, 0, &program_id);
+        assert_eq!(
+            Err(TokenError::MetadataPointerMismatch.into()),
+            do_process_instruction_serialized(
+                initialize(&Pubkey::new_unique(), &owner_key, "Token"),
+                vec![
+                    &mut other_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut mint_account,
+                    &mut authority_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the mint authority must sign
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_serialized(
+                initialize(&metadata_key, &authority_key, "Token"),
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut mint_account,
+                    &mut authority_account.clone(),
+                    &mut authority_account,
+                ],
+            )
+        );
+
+        // strings are bounded
+        assert_eq!(
+            Err(TokenError::MetadataFieldTooLong.into()),
+            do_process_instruction_serialized(
+                initialize(&metadata_key, &owner_key, &"a".repeat(MAX_NAME_LENGTH + 1)),
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut mint_account,
+                    &mut authority_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the account is sized to fit, with the payer funding its reserve
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            initialize(&metadata_key, &owner_key, "Token"),
+            vec![
+                &mut metadata_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut mint_account,
+                &mut authority_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let metadata = TokenMetadata::unpack_from_account_data(&metadata_account.data).unwrap();
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                update_authority: authority_key,
+                mint: mint_key,
+                name: "Token".to_string(),
+                symbol: "TKN".to_string(),
+                uri: "https://example.com/a".to_string(),
+            }
+        );
+        assert_eq!(metadata_account.data.len(), metadata.account_len());
+        let minimum_balance = rent.minimum_balance(metadata_account.data.len());
+        assert_eq!(metadata_account.lamports, minimum_balance);
+        assert_eq!(payer_account.lamports, payer_lamports - minimum_balance);
+
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction_serialized(
+                initialize(&metadata_key, &owner_key, "Token"),
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut mint_account,
+                    &mut authority_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // only the update authority may change a field
+        let update = |authority_key: &Pubkey, field: MetadataField, value: &str| {
+            update_token_metadata_field(
+                &program_id,
+                &metadata_key,
+                &payer_key,
+                authority_key,
+                &[],
+                field,
+                value,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_serialized(
+                update(&owner_key, MetadataField::Uri, "https://example.com/b"),
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let mut instruction = update(&authority_key, MetadataField::Uri, "https://example.com/b");
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction_serialized(
+                instruction,
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut authority_account,
+                ],
+            )
+        );
+
+        // a longer URI grows the account, a shorter one shrinks it
+        let long_uri = format!("https://example.com/{}", "b".repeat(100));
+        do_process_instruction_serialized(
+            update(&authority_key, MetadataField::Uri, &long_uri),
+            vec![
+                &mut metadata_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut authority_account,
+            ],
+        )
+        .unwrap();
+        let metadata = TokenMetadata::unpack_from_account_data(&metadata_account.data).unwrap();
+        assert_eq!(metadata.uri, long_uri);
+        assert_eq!(metadata.name, "Token");
+        assert_eq!(metadata_account.data.len(), metadata.account_len());
+        assert_eq!(
+            metadata_account.lamports,
+            rent.minimum_balance(metadata_account.data.len())
+        );
+        do_process_instruction_serialized(
+            update(&authority_key, MetadataField::Uri, "https://example.com/c"),
+            vec![
+                &mut metadata_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut authority_account,
+            ],
+        )
+        .unwrap();
+        let metadata = TokenMetadata::unpack_from_account_data(&metadata_account.data).unwrap();
+        assert_eq!(metadata.uri, "https://example.com/c");
+        assert_eq!(metadata_account.data.len(), metadata.account_len());
+        assert_eq!(
+            Err(TokenError::MetadataFieldTooLong.into()),
+            do_process_instruction_serialized(
+                update(&authority_key, MetadataField::Uri, &"c".repeat(MAX_URI_LENGTH + 1)),
+                vec![
+                    &mut metadata_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut authority_account,
+                ],
+            )
+        );
+
+        // the mint is not metadata
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction_serialized(
+                update(&authority_key, MetadataField::Uri, "https://example.com/d"),
+                vec![
+                    &mut mint_account,
+                    &mut payer_account,
+                    &mut system_program_account,
+                    &mut authority_account,
+                ],
+            )
+        );
+
+        // a mint pointing at itself keeps its metadata after the mint fields
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_metadata_pointer(&program_id, &mint2_key, Some(&mint2_key)).unwrap(),
+            vec![&mut mint2_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        let mint2 = Mint::unpack(&mint2_account.data).unwrap();
+        let instruction = initialize_token_metadata(
+            &program_id,
+            &mint2_key,
+            &payer_key,
+            &mint2_key,
+            &authority_key,
+            &owner_key,
+            &[],
+            "Token",
+            "TKN",
+            "https://example.com/a",
+        )
+        .unwrap();
+        let mut mint2_copy = mint2_account.clone();
+        do_process_instruction_serialized(
+            instruction,
+            vec![
+                &mut mint2_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut mint2_copy,
+                &mut authority_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint2_account.data).unwrap(), mint2);
+        let metadata = TokenMetadata::unpack_from_account_data(&mint2_account.data).unwrap();
+        assert_eq!(metadata.mint, mint2_key);
+        assert_eq!(
+            mint2_account.data.len(),
+            Mint::LEN + metadata.packed_len()
+        );
+        assert_eq!(
+            mint2_account.lamports,
+            rent.minimum_balance(mint2_account.data.len())
+        );
+        let mut instruction = update(&authority_key, MetadataField::Name, "Renamed");
+        instruction.accounts[0].pubkey = mint2_key;
+        do_process_instruction_serialized(
+            instruction,
+            vec![
+                &mut mint2_account,
+                &mut payer_account,
+                &mut system_program_account,
+                &mut authority_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint2_account.data).unwrap(), mint2);
+        assert_eq!(
+            TokenMetadata::unpack_from_account_data(&mint2_account.data)
+                .unwrap()
+                .name,
+            "Renamed"
+        );
+    }
+
+    #[test]
+    fn test_initialize_associated_account() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let wallet_key = Pubkey::new_unique();
+        let mut wallet_account = SolanaAccount::default();
+        let payer_key = Pubkey::new_unique();
+        let mut payer_account = SolanaAccount::new(1_000_000_000, 0, &system_program::id());
+        let mut system_program_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &Pubkey::new_unique(), None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // the client derives the same address the program checks
+        let (associated_key, bump) =
+            crate::associated_account::get_associated_token_address(&wallet_key, &mint_key);
+        let instruction =
+            initialize_associated_account(&program_id, &payer_key, &wallet_key, &mint_key).unwrap();
+        assert_eq!(instruction.accounts[1].pubkey, associated_key);
+        let mut associated_account = SolanaAccount::new(0, 0, &system_program::id());
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            instruction.clone(),
+            vec![
+                &mut payer_account,
+                &mut associated_account,
+                &mut wallet_account,
+                &mut mint_account,
+                &mut system_program_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(associated_account.owner, program_id);
+        assert_eq!(associated_account.lamports, account_minimum_balance());
+        assert_eq!(payer_account.lamports, payer_lamports - account_minimum_balance());
+        let account = Account::unpack(&associated_account.data).unwrap();
+        assert_eq!(account.owner, wallet_key);
+        assert_eq!(account.mint, mint_key);
+        assert_eq!(account.state, AccountState::Initialized);
+
+        // there is only one per wallet and mint
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction_serialized(
+                instruction.clone(),
+                vec![
+                    &mut payer_account,
+                    &mut associated_account,
+                    &mut wallet_account,
+                    &mut mint_account,
+                    &mut system_program_account,
+                    &mut rent_sysvar,
+                ],
+            )
+        );
+
+        // other addresses are rejected, including those of non-canonical bumps
+        let non_canonical_key = (0..bump)
+            .rev()
+            .find_map(|bump| {
+                Pubkey::create_program_address(
+                    &[wallet_key.as_ref(), mint_key.as_ref(), &[bump]],
+                    &program_id,
+                )
+                .ok()
+            })
+            .unwrap();
+        for key in [non_canonical_key, Pubkey::new_unique()] {
+            let mut instruction = instruction.clone();
+            instruction.accounts[1].pubkey = key;
+            let mut account = SolanaAccount::new(0, 0, &system_program::id());
+            assert_eq!(
+                Err(ProgramError::InvalidSeeds),
+                do_process_instruction_serialized(
+                    instruction,
+                    vec![
+                        &mut payer_account,
+                        &mut account,
+                        &mut wallet_account,
+                        &mut mint_account,
+                        &mut system_program_account,
+                        &mut rent_sysvar,
+                    ],
+                )
+            );
+        }
+
+        // lamports sent to the address beforehand do not block it, and count
+        // towards the reserve
+        let wallet2_key = Pubkey::new_unique();
+        let instruction =
+            initialize_associated_account(&program_id, &payer_key, &wallet2_key, &mint_key)
+                .unwrap();
+        let mut associated_account = SolanaAccount::new(1_000, 0, &system_program::id());
+        let payer_lamports = payer_account.lamports;
+        do_process_instruction_serialized(
+            instruction,
+            vec![
+                &mut payer_account,
+                &mut associated_account,
+                &mut wallet_account,
+                &mut mint_account,
+                &mut system_program_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(associated_account.owner, program_id);
+        assert_eq!(associated_account.lamports, account_minimum_balance());
+        assert_eq!(
+            payer_account.lamports,
+            payer_lamports - (account_minimum_balance() - 1_000)
+        );
+        assert_eq!(
+            Account::unpack(&associated_account.data).unwrap().owner,
+            wallet2_key
+        );
+    }
+
+    #[test]
+    fn test_recover_nested() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let wallet_key = Pubkey::new_unique();
+        let mut wallet_account = SolanaAccount::default();
+        let attacker_key = Pubkey::new_unique();
+        let mut attacker_account = SolanaAccount::default();
+        let token_account = |mint: &Pubkey, owner: &Pubkey, amount: u64| {
+            let mut account = SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            );
+            Account {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                state: AccountState::Initialized,
+                ..Account::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+
+        // the wallet owns `owner`, which was sent tokens in `nested`
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = token_account(&mint_key, &wallet_key, 0);
+        let nested_key = Pubkey::new_unique();
+        let mut nested_account = token_account(&mint_key, &owner_key, 100);
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = token_account(&mint_key, &wallet_key, 5);
+
+        // an attacker cannot sign for the wallet
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                recover_nested(
+                    &program_id,
+                    &nested_key,
+                    &dest_key,
+                    &owner_key,
+                    &attacker_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut nested_account,
+                    &mut dest_account,
+                    &mut owner_account,
+                    &mut attacker_account,
+                ],
+            )
+        );
+
+        // nor pass off their own account as the owner of the nested one
+        let attacker_owner_key = Pubkey::new_unique();
+        let mut attacker_owner_account = token_account(&mint_key, &attacker_key, 0);
+        let attacker_dest_key = Pubkey::new_unique();
+        let mut attacker_dest_account = token_account(&mint_key, &attacker_key, 0);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                recover_nested(
+                    &program_id,
+                    &nested_key,
+                    &attacker_dest_key,
+                    &attacker_owner_key,
+                    &attacker_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut nested_account,
+                    &mut attacker_dest_account,
+                    &mut attacker_owner_account,
+                    &mut attacker_account,
+                ],
+            )
+        );
+
+        // and the wallet's signature only sends the tokens to the wallet
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                recover_nested(
+                    &program_id,
+                    &nested_key,
+                    &attacker_dest_key,
+                    &owner_key,
+                    &wallet_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut nested_account,
+                    &mut attacker_dest_account,
+                    &mut owner_account,
+                    &mut wallet_account,
+                ],
+            )
+        );
+        let mut instruction =
+            recover_nested(&program_id, &nested_key, &dest_key, &owner_key, &wallet_key, &[])
+                .unwrap();
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut nested_account,
+                    &mut dest_account,
+                    &mut owner_account,
+                    &mut wallet_account,
+                ],
+            )
+        );
+        let other_dest_key = Pubkey::new_unique();
+        let mut other_dest_account = token_account(&Pubkey::new_unique(), &wallet_key, 0);
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                recover_nested(
+                    &program_id,
+                    &nested_key,
+                    &other_dest_key,
+                    &owner_key,
+                    &wallet_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut nested_account,
+                    &mut other_dest_account,
+                    &mut owner_account,
+                    &mut wallet_account,
+                ],
+            )
+        );
+
+        // the wallet gets the balance and the nested account's rent
+        do_process_instruction(
+            recover_nested(&program_id, &nested_key, &dest_key, &owner_key, &wallet_key, &[])
+                .unwrap(),
+            vec![
+                &mut nested_account,
+                &mut dest_account,
+                &mut owner_account,
+                &mut wallet_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&dest_account.data).unwrap().amount, 105);
+        assert_eq!(nested_account.lamports, 0);
+        assert!(nested_account.data.iter().all(|byte| *byte == 0));
+        assert_eq!(wallet_account.lamports, account_minimum_balance());
+        assert_eq!(dest_account.lamports, account_minimum_balance());
+
+        // a native balance brings its lamports along
+        let native_mint_key = crate::native_mint::id();
+        let mut nested_account = token_account(&native_mint_key, &owner_key, 40);
+        nested_account.lamports += 40;
+        let mut nested = Account::unpack(&nested_account.data).unwrap();
+        nested.is_native = COption::Some(account_minimum_balance());
+        Account::pack(nested, &mut nested_account.data).unwrap();
+        let mut dest_account = token_account(&native_mint_key, &wallet_key, 0);
+        let mut dest = Account::unpack(&dest_account.data).unwrap();
+        dest.is_native = COption::Some(account_minimum_balance());
+        Account::pack(dest, &mut dest_account.data).unwrap();
+        let mut wallet_account = SolanaAccount::default();
+        do_process_instruction(
+            recover_nested(&program_id, &nested_key, &dest_key, &owner_key, &wallet_key, &[])
+                .unwrap(),
+            vec![
+                &mut nested_account,
+                &mut dest_account,
+                &mut owner_account,
+                &mut wallet_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&dest_account.data).unwrap().amount, 40);
+        assert_eq!(dest_account.lamports, account_minimum_balance() + 40);
+        assert_eq!(wallet_account.lamports, account_minimum_balance());
+        assert_eq!(nested_account.lamports, 0);
+    }
+
+    #[test]
+    fn test_wrap_unwrap_sol() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut native_mint_account = SolanaAccount::default();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let funding_key = Pubkey::new_unique();
+        let mut funding_account = SolanaAccount::new(1_000, 0, &system_program::id());
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = SolanaAccount::default();
+        let mut system_program_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &native_mint::id(), &owner_key)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut native_mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // wrapping moves the lamports in and counts them, in one step
+        do_process_instruction(
+            wrap_sol(&program_id, &account_key, &funding_key, &owner_key, &[], 600).unwrap(),
+            vec![
+                &mut account_account,
+                &mut funding_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 600);
+        assert_eq!(account_account.lamports, account_minimum_balance() + 600);
+        assert_eq!(funding_account.lamports, 400);
+        assert_eq!(
+            Err(ProgramError::InsufficientFunds),
+            do_process_instruction(
+                wrap_sol(&program_id, &account_key, &funding_key, &owner_key, &[], 401).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut funding_account,
+                    &mut system_program_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // both need the owner
+        let mut instruction =
+            wrap_sol(&program_id, &account_key, &funding_key, &owner_key, &[], 1).unwrap();
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut account_account,
+                    &mut funding_account,
+                    &mut system_program_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let mut not_owner_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                unwrap_sol(&program_id, &account_key, &dest_key, &dest_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut dest_account, &mut not_owner_account],
+            )
+        );
+
+        // no more than the wrapped balance comes out
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                unwrap_sol(&program_id, &account_key, &dest_key, &owner_key, &[], 601).unwrap(),
+                vec![&mut account_account, &mut dest_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            unwrap_sol(&program_id, &account_key, &dest_key, &owner_key, &[], 200).unwrap(),
+            vec![&mut account_account, &mut dest_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 400);
+        assert_eq!(account_account.lamports, account_minimum_balance() + 400);
+        assert_eq!(dest_account.lamports, 200);
+
+        // the reserve stays, even when the balance claims more than is there
+        account_account.lamports -= 1;
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                unwrap_sol(&program_id, &account_key, &dest_key, &owner_key, &[], 400).unwrap(),
+                vec![&mut account_account, &mut dest_account, &mut owner_account],
+            )
+        );
+        account_account.lamports += 1;
+        do_process_instruction(
+            unwrap_sol(&program_id, &account_key, &dest_key, &owner_key, &[], 400).unwrap(),
+            vec![&mut account_account, &mut dest_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 0);
+        assert_eq!(account_account.lamports, account_minimum_balance());
+        assert_eq!(dest_account.lamports, 600);
+
+        // only native accounts hold lamports as their balance
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let token_key = Pubkey::new_unique();
+        let mut token_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &token_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut token_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NonNativeNotSupported.into()),
+            do_process_instruction(
+                wrap_sol(&program_id, &token_key, &funding_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut token_account,
+                    &mut funding_account,
+                    &mut system_program_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::NonNativeNotSupported.into()),
+            do_process_instruction(
+                unwrap_sol(&program_id, &token_key, &dest_key, &owner_key, &[], 0).unwrap(),
+                vec![&mut token_account, &mut dest_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_revoke_all() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let other_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // hand out every permission there is
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&other_key),
+                AuthorityType::CloseAccount,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            propose_owner(&program_id, &account_key, Some(&other_key), &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // the delegate cannot strip them
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                revoke_all(&program_id, &account_key, &delegate_key, &[]).unwrap(),
+                vec![&mut account_account, &mut delegate_account],
+            )
+        );
+        let mut instruction = revoke_all(&program_id, &account_key, &owner_key, &[]).unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(instruction, vec![&mut account_account, &mut owner_account])
+        );
+
+        // the owner can, and doing it again changes nothing
+        for _ in 0..2 {
+            do_process_instruction(
+                revoke_all(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+            .unwrap();
+            let account = Account::unpack(&account_account.data).unwrap();
+            assert_eq!(account.delegate, COption::None);
+            assert_eq!(account.delegated_amount, 0);
+            assert_eq!(account.close_authority, COption::None);
+            assert_eq!(account.pending_owner, COption::None);
+            assert_eq!(account.owner, owner_key);
+        }
+    }
+
+    #[test]
+    fn test_assert_balance_and_supply() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // neither asserts on state that is not there yet
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction(
+                assert_supply(&program_id, &mint_key, 0, u64::MAX).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction(
+                assert_balance(&program_id, &account_key, 0, u64::MAX).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account_data = account_account.data.clone();
+        let mint_data = mint_account.data.clone();
+
+        // bounds are inclusive, and open at u64::MIN and u64::MAX
+        for (minimum, maximum) in [(100, 100), (0, 100), (100, u64::MAX), (u64::MIN, u64::MAX)] {
+            do_process_instruction(
+                assert_balance(&program_id, &account_key, minimum, maximum).unwrap(),
+                vec![&mut account_account],
+            )
+            .unwrap();
+            do_process_instruction(
+                assert_supply(&program_id, &mint_key, minimum, maximum).unwrap(),
+                vec![&mut mint_account],
+            )
+            .unwrap();
+        }
+        for (minimum, maximum) in [(101, u64::MAX), (0, 99), (101, 99)] {
+            assert_eq!(
+                Err(TokenError::BalanceAssertionFailed.into()),
+                do_process_instruction(
+                    assert_balance(&program_id, &account_key, minimum, maximum).unwrap(),
+                    vec![&mut account_account],
+                )
+            );
+            assert_eq!(
+                Err(TokenError::BalanceAssertionFailed.into()),
+                do_process_instruction(
+                    assert_supply(&program_id, &mint_key, minimum, maximum).unwrap(),
+                    vec![&mut mint_account],
+                )
+            );
+        }
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(mint_account.data, mint_data);
+
+        // each reads only its own kind of state, and only from this program
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                assert_balance(&program_id, &mint_key, 0, u64::MAX).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                assert_supply(&program_id, &account_key, 0, u64::MAX).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+        account_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                assert_balance(&program_id, &account_key, 0, u64::MAX).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_split_account() {
+        let program_id = crate::id();
+        let source_key = Pubkey::new_unique();
+        let mut source_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let new_key = Pubkey::new_unique();
+        let mut new_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        for (key, account) in [(&mint_key, &mut mint_account), (&mint2_key, &mut mint2_account)] {
+            do_process_instruction(
+                initialize_mint(&program_id, key, &owner_key, None, 2).unwrap(),
+                vec![account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            initialize_account(&program_id, &source_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut source_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &source_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut source_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &source_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut source_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // only the owner splits, even with an allowance covering the amount
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                split_account(
+                    &program_id,
+                    &source_key,
+                    &new_key,
+                    &mint_key,
+                    &delegate_key,
+                    &[],
+                    40,
+                )
+                .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+
+        // the new account has to be of the source's mint
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                split_account(&program_id, &source_key, &new_key, &mint2_key, &owner_key, &[], 40)
+                    .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // and rent-exempt
+        new_account.lamports -= 1;
+        assert_eq!(
+            Err(TokenError::NotRentExempt.into()),
+            do_process_instruction(
+                split_account(&program_id, &source_key, &new_key, &mint_key, &owner_key, &[], 40)
+                    .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        new_account.lamports += 1;
+
+        // no more than the balance moves
+        let mut new_copy = new_account.clone();
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                split_account(&program_id, &source_key, &new_key, &mint_key, &owner_key, &[], 101)
+                    .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut new_copy,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        do_process_instruction(
+            split_account(&program_id, &source_key, &new_key, &mint_key, &owner_key, &[], 40)
+                .unwrap(),
+            vec![
+                &mut source_account,
+                &mut new_account,
+                &mut mint_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let source = Account::unpack(&source_account.data).unwrap();
+        assert_eq!(source.amount, 60);
+        let new = Account::unpack(&new_account.data).unwrap();
+        assert_eq!(new.mint, mint_key);
+        assert_eq!(new.owner, owner_key);
+        assert_eq!(new.amount, 40);
+        assert_eq!(new.delegate, COption::None);
+
+        // an account already in use cannot be split into
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                split_account(&program_id, &source_key, &new_key, &mint_key, &owner_key, &[], 10)
+                    .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_accounts() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let token_account = |mint: &Pubkey, owner: &Pubkey, amount: u64| {
+            let mut account = SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            );
+            Account {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                state: AccountState::Initialized,
+                ..Account::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = token_account(&mint_key, &owner_key, 5);
+        let source1_key = Pubkey::new_unique();
+        let mut source1_account = token_account(&mint_key, &owner_key, 10);
+        let source2_key = Pubkey::new_unique();
+        let mut source2_account = token_account(&mint_key, &owner_key, 20);
+        let other_mint_key = Pubkey::new_unique();
+        let mut other_mint_account = token_account(&Pubkey::new_unique(), &owner_key, 30);
+        let other_owner_key = Pubkey::new_unique();
+        let mut other_owner_account = token_account(&mint_key, &Pubkey::new_unique(), 40);
+
+        // one bad source fails the whole merge, wherever it is in the list
+        let (dest_data, source1_data, source2_data) = (
+            dest_account.data.clone(),
+            source1_account.data.clone(),
+            source2_account.data.clone(),
+        );
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                merge_accounts(
+                    &program_id,
+                    &dest_key,
+                    &owner_key,
+                    &[],
+                    &[&source1_key, &source2_key, &other_mint_key],
+                    true,
+                )
+                .unwrap(),
+                vec![
+                    &mut dest_account,
+                    &mut owner_account,
+                    &mut source1_account,
+                    &mut source2_account,
+                    &mut other_mint_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                merge_accounts(
+                    &program_id,
+                    &dest_key,
+                    &owner_key,
+                    &[],
+                    &[&source1_key, &other_owner_key],
+                    false,
+                )
+                .unwrap(),
+                vec![
+                    &mut dest_account,
+                    &mut owner_account,
+                    &mut source1_account,
+                    &mut other_owner_account,
+                ],
+            )
+        );
+        let mut source1_copy = source1_account.clone();
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                merge_accounts(
+                    &program_id,
+                    &dest_key,
+                    &owner_key,
+                    &[],
+                    &[&source1_key, &source1_key],
+                    false,
+                )
+                .unwrap(),
+                vec![
+                    &mut dest_account,
+                    &mut owner_account,
+                    &mut source1_account,
+                    &mut source1_copy,
+                ],
+            )
+        );
+        assert_eq!(dest_account.data, dest_data);
+        assert_eq!(source1_account.data, source1_data);
+        assert_eq!(source2_account.data, source2_data);
+
+        // the owner signs once for every account
+        let mut instruction = merge_accounts(
+            &program_id,
+            &dest_key,
+            &owner_key,
+            &[],
+            &[&source1_key],
+            false,
+        )
+        .unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![&mut dest_account, &mut owner_account, &mut source1_account],
+            )
+        );
+
+        // kept open, a drained source stays usable
+        do_process_instruction(
+            merge_accounts(
+                &program_id,
+                &dest_key,
+                &owner_key,
+                &[],
+                &[&source1_key],
+                false,
+            )
+            .unwrap(),
+            vec![&mut dest_account, &mut owner_account, &mut source1_account],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&dest_account.data).unwrap().amount, 15);
+        assert_eq!(Account::unpack(&source1_account.data).unwrap().amount, 0);
+
+        // closed, its rent goes to the destination
+        do_process_instruction(
+            merge_accounts(
+                &program_id,
+                &dest_key,
+                &owner_key,
+                &[],
+                &[&source1_key, &source2_key],
+                true,
+            )
+            .unwrap(),
+            vec![
+                &mut dest_account,
+                &mut owner_account,
+                &mut source1_account,
+                &mut source2_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&dest_account.data).unwrap().amount, 35);
+        assert_eq!(dest_account.lamports, 3 * account_minimum_balance());
+        for source_account in [&source1_account, &source2_account] {
+            assert_eq!(source_account.lamports, 0);
+            assert!(source_account.data.iter().all(|byte| *byte == 0));
+        }
+    }
+
+    #[test]
+    fn test_approve_escrowed() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // only what the account holds can be escrowed
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                approve_escrowed(&program_id, &account_key, &delegate_key, &owner_key, &[], 101)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut delegate_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            approve_escrowed(&program_id, &account_key, &delegate_key, &owner_key, &[], 60)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 60);
+        assert!(account.escrowed);
+
+        // the owner spends down to the escrowed amount and no further
+        assert_eq!(
+            Err(TokenError::InsufficientUnlockedFunds.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 41).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 40).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 60);
+        assert_eq!(
+            Err(TokenError::InsufficientUnlockedFunds.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InsufficientUnlockedFunds.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // the delegate spends from the escrow, which shrinks with it
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::InsufficientUnlockedFunds.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // the delegate can release the escrow
+        do_process_instruction(
+            revoke(&program_id, &account_key, &delegate_key, &[]).unwrap(),
+            vec![&mut account_account, &mut delegate_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert!(!account.escrowed);
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // so can the owner
+        do_process_instruction(
+            approve_escrowed(&program_id, &account_key, &delegate_key, &owner_key, &[], 49)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert!(!Account::unpack(&account_account.data).unwrap().escrowed);
+
+        // and it ends when the delegate has spent it all
+        do_process_instruction(
+            approve_escrowed(&program_id, &account_key, &delegate_key, &owner_key, &[], 49)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 49).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 0);
+        assert_eq!(account.delegate, COption::None);
+        assert!(!account.escrowed);
+
+        // anyone else cannot revoke
+        let mut other_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                revoke(&program_id, &account_key, &Pubkey::new_unique(), &[]).unwrap(),
+                vec![&mut account_account, &mut other_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_transfer_policy() {
+        let program_id = crate::id();
+        let mint_authority_key = Pubkey::new_unique();
+        let mut mint_authority_account = SolanaAccount::default();
+        let freeze_authority_key = Pubkey::new_unique();
+        let mut freeze_authority_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let other_mint_key = Pubkey::new_unique();
+        let mut other_mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+        let policy_minimum_balance = Rent::default().minimum_balance(TransferPolicy::LEN);
+        let policy_key = Pubkey::new_unique();
+        let mut policy_account =
+            SolanaAccount::new(policy_minimum_balance, TransferPolicy::LEN, &program_id);
+        let other_policy_key = Pubkey::new_unique();
+        let mut other_policy_account =
+            SolanaAccount::new(policy_minimum_balance, TransferPolicy::LEN, &program_id);
+
+        // the policy must be rent-exempt
+        let mut poor_account = SolanaAccount::new(0, TransferPolicy::LEN, &program_id);
+        assert_eq!(
+            Err(TokenError::NotRentExempt.into()),
+            do_process_instruction(
+                initialize_policy(&program_id, &policy_key, &mint_key, PolicyMode::Allowlist)
+                    .unwrap(),
+                vec![&mut poor_account, &mut mint_account],
+            )
+        );
+
+        // a mint records a single policy, before it is initialized
+        do_process_instruction(
+            initialize_policy(&program_id, &policy_key, &mint_key, PolicyMode::Allowlist).unwrap(),
+            vec![&mut policy_account, &mut mint_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_policy(
+                    &program_id,
+                    &other_policy_key,
+                    &mint_key,
+                    PolicyMode::Blocklist,
+                )
+                .unwrap(),
+                vec![&mut other_policy_account, &mut mint_account],
+            )
+        );
+        do_process_instruction(
+            initialize_mint(
+                &program_id,
+                &mint_key,
+                &mint_authority_key,
+                Some(&freeze_authority_key),
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().transfer_policy,
+            COption::Some(policy_key)
+        );
+        let policy = TransferPolicy::unpack(&policy_account.data).unwrap();
+        assert_eq!(policy.mint, mint_key);
+        assert_eq!(policy.mode, PolicyMode::Allowlist);
+        assert!(policy.entries().is_empty());
+        do_process_instruction(
+            initialize_policy(
+                &program_id,
+                &other_policy_key,
+                &other_mint_key,
+                PolicyMode::Blocklist,
+            )
+            .unwrap(),
+            vec![&mut other_policy_account, &mut other_mint_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &other_mint_key, &mint_authority_key, None, 2).unwrap(),
+            vec![&mut other_mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // an initialized mint takes no policy
+        let late_policy_key = Pubkey::new_unique();
+        let mut late_policy_account =
+            SolanaAccount::new(policy_minimum_balance, TransferPolicy::LEN, &program_id);
+        let plain_mint_key = Pubkey::new_unique();
+        let mut plain_mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &plain_mint_key, &mint_authority_key, None, 2).unwrap(),
+            vec![&mut plain_mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_policy(
+                    &program_id,
+                    &late_policy_key,
+                    &plain_mint_key,
+                    PolicyMode::Allowlist,
+                )
+                .unwrap(),
+                vec![&mut late_policy_account, &mut plain_mint_account],
+            )
+        );
+
+        // the policy is edited through its own mint only, by the mint's
+        // freeze authority
+        let alice_key = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                add_policy_entry(
+                    &program_id,
+                    &policy_key,
+                    &other_mint_key,
+                    &freeze_authority_key,
+                    &[],
+                    &alice_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut policy_account,
+                    &mut other_mint_account,
+                    &mut freeze_authority_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                add_policy_entry(
+                    &program_id,
+                    &policy_key,
+                    &mint_key,
+                    &mint_authority_key,
+                    &[],
+                    &alice_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut policy_account,
+                    &mut mint_account,
+                    &mut mint_authority_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                add_policy_entry(
+                    &program_id,
+                    &other_policy_key,
+                    &other_mint_key,
+                    &mint_authority_key,
+                    &[],
+                    &alice_key,
+                )
+                .unwrap(),
+                vec![
+                    &mut other_policy_account,
+                    &mut other_mint_account,
+                    &mut mint_authority_account,
+                ],
+            )
+        );
+        let add = |entry: &Pubkey| {
+            add_policy_entry(
+                &program_id,
+                &policy_key,
+                &mint_key,
+                &freeze_authority_key,
+                &[],
+                entry,
+            )
+            .unwrap()
+        };
+        let remove = |entry: &Pubkey| {
+            remove_policy_entry(
+                &program_id,
+                &policy_key,
+                &mint_key,
+                &freeze_authority_key,
+                &[],
+                entry,
+            )
+            .unwrap()
+        };
+        do_process_instruction(
+            add(&alice_key),
+            vec![
+                &mut policy_account,
+                &mut mint_account,
+                &mut freeze_authority_account,
+            ],
+        )
+        .unwrap();
+
+        // an entry is listed once
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                add(&alice_key),
+                vec![
+                    &mut policy_account,
+                    &mut mint_account,
+                    &mut freeze_authority_account,
+                ],
+            )
+        );
+
+        // up to the capacity
+        let others = (1..MAX_POLICY_ENTRIES)
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        for other in &others {
+            do_process_instruction(
+                add(other),
+                vec![
+                    &mut policy_account,
+                    &mut mint_account,
+                    &mut freeze_authority_account,
+                ],
+            )
+            .unwrap();
+        }
+        let bob_key = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::PolicyFull.into()),
+            do_process_instruction(
+                add(&bob_key),
+                vec![
+                    &mut policy_account,
+                    &mut mint_account,
+                    &mut freeze_authority_account,
+                ],
+            )
+        );
+
+        // removing makes room, and the rest stay listed
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                remove(&bob_key),
+                vec![
+                    &mut policy_account,
+                    &mut mint_account,
+                    &mut freeze_authority_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            remove(&alice_key),
+            vec![
+                &mut policy_account,
+                &mut mint_account,
+                &mut freeze_authority_account,
+            ],
+        )
+        .unwrap();
+        let policy = TransferPolicy::unpack(&policy_account.data).unwrap();
+        assert_eq!(policy.len as usize, MAX_POLICY_ENTRIES - 1);
+        assert!(!policy.entries().contains(&alice_key));
+        assert!(others.iter().all(|other| policy.entries().contains(other)));
+
+        // every account of the mint requires the mint, and so the policy
+        let token_account = |owner: &Pubkey, amount: u64| {
+            let mut account = SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            );
+            Account {
+                mint: mint_key,
+                owner: *owner,
+                amount,
+                state: AccountState::Initialized,
+                mint_required: true,
+                ..Account::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let bob_account_key = Pubkey::new_unique();
+        let mut bob_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut bob_owner_account = SolanaAccount::default();
+        do_process_instruction(
+            initialize_account(&program_id, &bob_account_key, &mint_key, &bob_key).unwrap(),
+            vec![
+                &mut bob_account,
+                &mut mint_account,
+                &mut bob_owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert!(Account::unpack(&bob_account.data).unwrap().mint_required);
+        let source_key = Pubkey::new_unique();
+        let mut source_account = token_account(&owner_key, 100);
+
+        // transfers that pass the policy only reach listed owners
+        let transfer_with_policy = |policy_key: &Pubkey| {
+            let mut instruction = transfer_checked(
+                &program_id,
+                &source_key,
+                &mint_key,
+                &bob_account_key,
+                &owner_key,
+                &[],
+                1,
+                2,
+            )
+            .unwrap();
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(*policy_key, false));
+            instruction
+        };
+        assert_eq!(
+            Err(TokenError::TransferNotPermitted.into()),
+            do_process_instruction(
+                transfer_with_policy(&policy_key),
+                vec![
+                    &mut source_account,
+                    &mut mint_account,
+                    &mut bob_account,
+                    &mut owner_account,
+                    &mut policy_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            add(&bob_key),
+            vec![
+                &mut policy_account,
+                &mut mint_account,
+                &mut freeze_authority_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_with_policy(&policy_key),
+            vec![
+                &mut source_account,
+                &mut mint_account,
+                &mut bob_account,
+                &mut owner_account,
+                &mut policy_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&bob_account.data).unwrap().amount, 1);
+
+        // a blocklist turns it around
+        let mut blocklist = TransferPolicy::unpack(&policy_account.data).unwrap();
+        blocklist.mode = PolicyMode::Blocklist;
+        TransferPolicy::pack(blocklist, &mut policy_account.data).unwrap();
+        assert_eq!(
+            Err(TokenError::TransferNotPermitted.into()),
+            do_process_instruction(
+                transfer_with_policy(&policy_key),
+                vec![
+                    &mut source_account,
+                    &mut mint_account,
+                    &mut bob_account,
+                    &mut owner_account,
+                    &mut policy_account,
+                ],
+            )
+        );
+        blocklist.mode = PolicyMode::Allowlist;
+        TransferPolicy::pack(blocklist, &mut policy_account.data).unwrap();
+
+        // only the policy the mint records counts, however permissive another
+        // is
+        let mut permissive = TransferPolicy::unpack(&policy_account.data).unwrap();
+        permissive.mode = PolicyMode::Blocklist;
+        permissive.len = 0;
+        TransferPolicy::pack(permissive, &mut other_policy_account.data).unwrap();
+        assert_eq!(
+            Err(TokenError::MissingTransferPolicy.into()),
+            do_process_instruction(
+                transfer_with_policy(&other_policy_key),
+                vec![
+                    &mut source_account,
+                    &mut mint_account,
+                    &mut bob_account,
+                    &mut owner_account,
+                    &mut other_policy_account,
+                ],
+            )
+        );
+
+        // transfers that leave the policy out fail
+        assert_eq!(
+            Err(TokenError::MissingTransferPolicy.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &source_key,
+                    &mint_key,
+                    &bob_account_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut mint_account,
+                    &mut bob_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // and so do those that leave out the mint as well
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction(
+                transfer(
+                    &program_id,
+                    &source_key,
+                    &bob_account_key,
+                    &owner_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![&mut source_account, &mut bob_account, &mut owner_account],
+            )
+        );
+        let other_source_key = Pubkey::new_unique();
+        let mut other_source_account = token_account(&owner_key, 0);
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction(
+                merge_accounts(
+                    &program_id,
+                    &source_key,
+                    &owner_key,
+                    &[],
+                    &[&other_source_key],
+                    false,
+                )
+                .unwrap(),
+                vec![
+                    &mut source_account,
+                    &mut owner_account,
+                    &mut other_source_account,
+                ],
+            )
+        );
+        assert_eq!(Account::unpack(&source_account.data).unwrap().amount, 99);
+        assert_eq!(Account::unpack(&bob_account.data).unwrap().amount, 1);
+
+        // splitting an account keeps the tokens with the owner, who must
+        // still be permitted
+        let new_key = Pubkey::new_unique();
+        let mut new_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let split = |policy_key: Option<&Pubkey>| {
+            let mut instruction = split_account(
+                &program_id,
+                &source_key,
+                &new_key,
+                &mint_key,
+                &owner_key,
+                &[],
+                10,
+            )
+            .unwrap();
+            instruction
+                .accounts
+                .extend(policy_key.map(|policy_key| AccountMeta::new_readonly(*policy_key, false)));
+            instruction
+        };
+        assert_eq!(
+            Err(TokenError::MissingTransferPolicy.into()),
+            do_process_instruction(
+                split(None),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::TransferNotPermitted.into()),
+            do_process_instruction(
+                split(Some(&policy_key)),
+                vec![
+                    &mut source_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut policy_account,
+                ],
+            )
+        );
+
+        // the policy is full, so bob makes way for the owner
+        do_process_instruction(
+            remove(&bob_key),
+            vec![
+                &mut policy_account,
+                &mut mint_account,
+                &mut freeze_authority_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            add(&owner_key),
+            vec![
+                &mut policy_account,
+                &mut mint_account,
+                &mut freeze_authority_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            split(Some(&policy_key)),
+            vec![
+                &mut source_account,
+                &mut new_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut policy_account,
+            ],
+        )
+        .unwrap();
+        let new = Account::unpack(&new_account.data).unwrap();
+        assert_eq!(new.amount, 10);
+        assert!(new.mint_required);
+        assert_eq!(Account::unpack(&source_account.data).unwrap().amount, 89);
+    }
+
+    #[test]
+    fn test_malformed_accounts_rejected() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let source_key = Pubkey::new_unique();
+        let dest_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        Mint {
+            mint_authority: COption::Some(owner_key),
+            supply: 100,
+            decimals: 2,
+            is_initialized: true,
+            freeze_authority: COption::Some(owner_key),
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut mint_account.data);
+        let token_account = |amount: u64| {
+            let mut account = SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            );
+            Account {
+                mint: mint_key,
+                owner: owner_key,
+                amount,
+                state: AccountState::Initialized,
+                ..Account::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let accounts = [
+            (mint_key, mint_account),
+            (source_key, token_account(100)),
+            (dest_key, token_account(0)),
+        ];
+
+        let cases = [
+            (
+                transfer(&program_id, &source_key, &dest_key, &owner_key, &[], 1),
+                vec![source_key, dest_key],
+            ),
+            (
+                transfer_checked(
+                    &program_id,
+                    &source_key,
+                    &mint_key,
+                    &dest_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                ),
+                vec![mint_key],
+            ),
+            (
+                mint_to(&program_id, &mint_key, &dest_key, &owner_key, &[], 1),
+                vec![mint_key, dest_key],
+            ),
+            (
+                burn(&program_id, &source_key, &mint_key, &owner_key, &[], 1),
+                vec![source_key, mint_key],
+            ),
+            (
+                approve(&program_id, &source_key, &dest_key, &owner_key, &[], 1),
+                vec![source_key],
+            ),
+            (
+                revoke(&program_id, &source_key, &owner_key, &[]),
+                vec![source_key],
+            ),
+            (
+                set_authority(
+                    &program_id,
+                    &source_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &owner_key,
+                    &[],
+                ),
+                vec![source_key],
+            ),
+            (
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    None,
+                    AuthorityType::MintTokens,
+                    &owner_key,
+                    &[],
+                ),
+                vec![mint_key],
+            ),
+            (
+                freeze_account(&program_id, &source_key, &mint_key, &owner_key, &[]),
+                vec![source_key, mint_key],
+            ),
+            (
+                close_account(&program_id, &dest_key, &source_key, &owner_key, &[]),
+                vec![dest_key],
+            ),
+            (get_account_balance(&program_id, &source_key), vec![source_key]),
+            (get_mint_supply(&program_id, &mint_key), vec![mint_key]),
+        ];
+        for (instruction, state_keys) in cases {
+            let instruction = instruction.unwrap();
+            let run = |malformed_key: Option<&Pubkey>, malform: fn(&mut SolanaAccount)| {
+                let mut instruction_accounts = instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| {
+                        let mut account = accounts
+                            .iter()
+                            .find(|(key, _)| *key == meta.pubkey)
+                            .map(|(_, account)| account.clone())
+                            .unwrap_or_default();
+                        if Some(&meta.pubkey) == malformed_key {
+                            malform(&mut account);
+                        }
+                        account
+                    })
+                    .collect::<Vec<_>>();
+                do_process_instruction(
+                    instruction.clone(),
+                    instruction_accounts.iter_mut().collect(),
+                )
+            };
+
+            // fine as it is, refused one byte over or marked executable
+            assert_eq!(run(None, |_| {}), Ok(()), "{:?}", instruction.data);
+            for state_key in &state_keys {
+                assert!(
+                    run(Some(state_key), |account| account.data.push(0)).is_err(),
+                    "{:?}",
+                    instruction.data
+                );
+                assert_eq!(
+                    run(Some(state_key), |account| account.executable = true),
+                    Err(ProgramError::InvalidAccountData),
+                    "{:?}",
+                    instruction.data
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_initialize_checks_rent_sysvar_and_executable() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let signer_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        let mut signer_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+
+        // a rent sysvar passed under any other key is refused
+        let with_wrong_rent_key = |mut instruction: Instruction, rent_index: usize| {
+            instruction.accounts[rent_index].pubkey = Pubkey::new_unique();
+            instruction
+        };
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                with_wrong_rent_key(
+                    initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+                    1
+                ),
+                vec![&mut mint_account, &mut rent_sysvar],
+            )
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                with_wrong_rent_key(
+                    initialize_multisig(&program_id, &multisig_key, &[&signer_key], 1).unwrap(),
+                    1
+                ),
+                vec![&mut multisig_account, &mut rent_sysvar, &mut signer_account],
+            )
+        );
+
+        // an executable account cannot become a mint
+        mint_account.executable = true;
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+                vec![&mut mint_account, &mut rent_sysvar],
+            )
+        );
+        mint_account.executable = false;
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                with_wrong_rent_key(
+                    initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                    3
+                ),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                with_wrong_rent_key(
+                    initialize_account2(&program_id, &account_key, &mint_key, &owner_key)
+                        .unwrap(),
+                    2
+                ),
+                vec![&mut account_account, &mut mint_account, &mut rent_sysvar],
+            )
+        );
+
+        // nor a token account, nor can an executable mint back one
+        account_account.executable = true;
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account],
+            )
+        );
+        account_account.executable = false;
+        mint_account.executable = true;
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account],
+            )
+        );
+        mint_account.executable = false;
+        do_process_instruction(
+            initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![&mut account_account, &mut mint_account],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mint_to_validation_order() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mint = Mint {
+            mint_authority: COption::Some(owner_key),
+            decimals: 2,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        let account = Account {
+            mint: mint_key,
+            owner: owner_key,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut mint_to_with = |mint_account: &SolanaAccount, account_account: &SolanaAccount| {
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 42).unwrap(),
+                vec![
+                    &mut mint_account.clone(),
+                    &mut account_account.clone(),
+                    &mut owner_account,
+                ],
+            )
+        };
+
+        // an uninitialized mint is named even when the destination is broken too
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            mint_to_with(&mint_account, &account_account)
+        );
+        let mut foreign_mint_account = mint_account.clone();
+        foreign_mint_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            mint_to_with(&foreign_mint_account, &account_account)
+        );
+        Mint::pack(mint, &mut mint_account.data).unwrap();
+
+        // then the destination
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            mint_to_with(&mint_account, &account_account)
+        );
+        let mut foreign_account_account = account_account.clone();
+        foreign_account_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            mint_to_with(&mint_account, &foreign_account_account)
+        );
+        let mut other_mint_account = account_account.clone();
+        Account::pack(
+            Account {
+                mint: Pubkey::new_unique(),
+                ..account
+            },
+            &mut other_mint_account.data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            mint_to_with(&mint_account, &other_mint_account)
+        );
+        let mut frozen_account = account_account.clone();
+        Account::pack(
+            Account {
+                state: AccountState::Frozen,
+                ..account
+            },
+            &mut frozen_account.data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            mint_to_with(&mint_account, &frozen_account)
+        );
+
+        Account::pack(account, &mut account_account.data).unwrap();
+        assert_eq!(Ok(()), mint_to_with(&mint_account, &account_account));
+    }
+
+    #[test]
+    fn test_flash_mint() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let fee_account_key = Pubkey::new_unique();
+        let config_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mint_authority_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mut mint_authority_account = SolanaAccount::default();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let mut account_account = new_token_account();
+        let mut fee_account = new_token_account();
+        let mut config_account = SolanaAccount::new(
+            Rent::default().minimum_balance(FlashMintConfig::LEN),
+            FlashMintConfig::LEN,
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+        // program-test is not available, so the instructions sysvar is built
+        // by hand, positioned at `current`
+        let instructions_sysvar = |instructions: &[&Instruction], current: u16| {
+            let instructions = instructions
+                .iter()
+                .map(|instruction| sysvar::instructions::BorrowedInstruction {
+                    program_id: &instruction.program_id,
+                    accounts: instruction
+                        .accounts
+                        .iter()
+                        .map(|meta| sysvar::instructions::BorrowedAccountMeta {
+                            pubkey: &meta.pubkey,
+                            is_signer: meta.is_signer,
+                            is_writable: meta.is_writable,
+                        })
+                        .collect(),
+                    data: &instruction.data,
+                })
+                .collect::<Vec<_>>();
+            let mut account = SolanaAccount::new(0, 0, &sysvar::id());
+            account.data = sysvar::instructions::construct_instructions_data(&instructions);
+            sysvar::instructions::store_current_index(&mut account.data, current);
+            account
+        };
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &mint_authority_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &fee_account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut fee_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // only the mint authority opens a mint to flash minting
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                initialize_flash_mint(&program_id, &config_key, &mint_key, &owner_key, &[], 5)
+                    .unwrap(),
+                vec![&mut config_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            initialize_flash_mint(&program_id, &config_key, &mint_key, &mint_authority_key, &[], 5)
+                .unwrap(),
+            vec![
+                &mut config_account,
+                &mut mint_account,
+                &mut mint_authority_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            FlashMintConfig::unpack(&config_account.data).unwrap(),
+            FlashMintConfig {
+                mint: mint_key,
+                is_initialized: true,
+                fee: 5,
+                outstanding: 0,
+            }
+        );
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_flash_mint(
+                    &program_id,
+                    &config_key,
+                    &mint_key,
+                    &mint_authority_key,
+                    &[],
+                    5
+                )
+                .unwrap(),
+                vec![
+                    &mut config_account,
+                    &mut mint_account,
+                    &mut mint_authority_account,
+                ],
+            )
+        );
+
+        let flash_mint =
+            flash_mint_to(&program_id, &mint_key, &account_key, &config_key, 100).unwrap();
+        let repay = flash_burn_repay(
+            &program_id,
+            &account_key,
+            &mint_key,
+            &config_key,
+            &fee_account_key,
+            &owner_key,
+            &[],
+            100,
+        )
+        .unwrap();
+
+        // a repay earlier in the transaction does not count
+        assert_eq!(
+            Err(TokenError::FlashRepayMissing.into()),
+            do_process_instruction(
+                flash_mint.clone(),
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut config_account,
+                    &mut instructions_sysvar(&[&repay, &flash_mint], 1),
+                ],
+            )
+        );
+
+        // nor is any other account read as the instructions sysvar
+        let mut wrong_sysvar_key = flash_mint.clone();
+        wrong_sysvar_key.accounts[3].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::UnsupportedSysvar),
+            do_process_instruction(
+                wrong_sysvar_key,
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut config_account,
+                    &mut instructions_sysvar(&[&flash_mint, &repay], 0),
+                ],
+            )
+        );
+
+        // the config must be the mint's
+        let mut other_config_account = config_account.clone();
+        let mut other_config = FlashMintConfig::unpack(&other_config_account.data).unwrap();
+        other_config.mint = Pubkey::new_unique();
+        FlashMintConfig::pack(other_config, &mut other_config_account.data).unwrap();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                flash_mint.clone(),
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut other_config_account,
+                    &mut instructions_sysvar(&[&flash_mint, &repay], 0),
+                ],
+            )
+        );
+
+        do_process_instruction(
+            flash_mint.clone(),
+            vec![
+                &mut mint_account,
+                &mut account_account,
+                &mut config_account,
+                &mut instructions_sysvar(&[&flash_mint, &repay], 0),
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 100);
+        assert_eq!(
+            FlashMintConfig::unpack(&config_account.data)
+                .unwrap()
+                .outstanding,
+            100
+        );
+
+        // the fee goes to the mint authority, and the balance covers it
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                repay.clone(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut config_account,
+                    &mut fee_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let mut account = Account::unpack(&fee_account.data).unwrap();
+        account.owner = mint_authority_key;
+        Account::pack(account, &mut fee_account.data).unwrap();
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                repay.clone(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut config_account,
+                    &mut fee_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let mut account = Account::unpack(&account_account.data).unwrap();
+        account.amount += 5;
+        Account::pack(account, &mut account_account.data).unwrap();
+        do_process_instruction(
+            repay.clone(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut config_account,
+                &mut fee_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 0);
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 0);
+        assert_eq!(Account::unpack(&fee_account.data).unwrap().amount, 5);
+        assert_eq!(
+            FlashMintConfig::unpack(&config_account.data)
+                .unwrap()
+                .outstanding,
+            0
+        );
+
+        // nothing is left to repay
+        assert_eq!(
+            Err(TokenError::FlashRepayMismatch.into()),
+            do_process_instruction(
+                repay,
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut config_account,
+                    &mut fee_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_spending_limit() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 500).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // only the owner sets a limit
+        CLOCK_EPOCH.with(|epoch| epoch.set(5));
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_spending_limit(
+                    &program_id,
+                    &account_key,
+                    &delegate_key,
+                    &[],
+                    COption::Some(300)
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut delegate_account],
+            )
+        );
+        do_process_instruction(
+            set_spending_limit(&program_id, &account_key, &owner_key, &[], COption::Some(300))
+                .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // the owner and the delegate draw on the same limit
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 200).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.window_start_epoch, 5);
+        assert_eq!(account.spent_in_window, 300);
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 1)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // nor do the balance-moving instructions get around it
+        let new_key = Pubkey::new_unique();
+        let mut new_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                split_account(&program_id, &account_key, &new_key, &mint_key, &owner_key, &[], 1)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut new_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        for close_sources in [false, true] {
+            assert_eq!(
+                Err(TokenError::SpendingLimitExceeded.into()),
+                do_process_instruction(
+                    merge_accounts(
+                        &program_id,
+                        &account2_key,
+                        &owner_key,
+                        &[],
+                        &[&account_key],
+                        close_sources,
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut account2_account,
+                        &mut owner_account,
+                        &mut account_account,
+                    ],
+                )
+            );
+        }
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 700);
+        assert_eq!(account.spent_in_window, 300);
+
+        // the window follows the epoch, however many slots pass
+        CLOCK_SLOT.with(|slot| slot.set(1_000));
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the next epoch starts a new window
+        CLOCK_EPOCH.with(|epoch| epoch.set(6));
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 250).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 51).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.window_start_epoch, 6);
+        assert_eq!(account.spent_in_window, 250);
+        assert_eq!(account.amount, 450);
+
+        // lowering the limit keeps what was spent
+        do_process_instruction(
+            set_spending_limit(&program_id, &account_key, &owner_key, &[], COption::Some(260))
+                .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 11).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // lifting it frees the rest of the balance
+        do_process_instruction(
+            set_spending_limit(&program_id, &account_key, &owner_key, &[], COption::None).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 450).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 0);
+
+        // unwrapping spends the native balance like any other debit
+        let native_key = Pubkey::new_unique();
+        let mut native_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut native_mint_account = SolanaAccount::default();
+        let funding_key = Pubkey::new_unique();
+        let mut funding_account = SolanaAccount::new(1_000, 0, &system_program::id());
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = SolanaAccount::default();
+        let mut system_program_account = SolanaAccount::default();
+        do_process_instruction(
+            initialize_account(&program_id, &native_key, &native_mint::id(), &owner_key).unwrap(),
+            vec![
+                &mut native_account,
+                &mut native_mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            wrap_sol(&program_id, &native_key, &funding_key, &owner_key, &[], 600).unwrap(),
+            vec![
+                &mut native_account,
+                &mut funding_account,
+                &mut system_program_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_spending_limit(&program_id, &native_key, &owner_key, &[], COption::Some(200))
+                .unwrap(),
+            vec![&mut native_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            unwrap_sol(&program_id, &native_key, &dest_key, &owner_key, &[], 200).unwrap(),
+            vec![&mut native_account, &mut dest_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                unwrap_sol(&program_id, &native_key, &dest_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut native_account, &mut dest_account, &mut owner_account],
+            )
+        );
+        let native = Account::unpack(&native_account.data).unwrap();
+        assert_eq!(native.amount, 400);
+        assert_eq!(native.spent_in_window, 200);
+        assert_eq!(dest_account.lamports, 200);
+
+        // and closing pays the rest of it out, so it counts too
+        assert_eq!(
+            Err(TokenError::SpendingLimitExceeded.into()),
+            do_process_instruction(
+                close_account(&program_id, &native_key, &dest_key, &owner_key, &[]).unwrap(),
+                vec![&mut native_account, &mut dest_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            set_spending_limit(&program_id, &native_key, &owner_key, &[], COption::Some(600))
+                .unwrap(),
+            vec![&mut native_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            close_account(&program_id, &native_key, &dest_key, &owner_key, &[]).unwrap(),
+            vec![&mut native_account, &mut dest_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(native_account.lamports, 0);
+        assert_eq!(dest_account.lamports, account_minimum_balance() + 600);
+    }
+
+    #[test]
+    fn test_transfer_vested() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account, owner) in [
+            (&account_key, &mut account_account, &owner_key),
+            (&account2_key, &mut account2_account, &owner2_key),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, owner).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        let vest = |total_amount, start_slot, end_slot| {
+            transfer_vested(
+                &program_id,
+                &account_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                total_amount,
+                start_slot,
+                end_slot,
+            )
+            .unwrap()
+        };
+        let claim = || claim_vested(&program_id, &account2_key, &owner2_key, &[]).unwrap();
+
+        // the schedule must run forward
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                vest(100, 40, 40),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            vest(100, 10, 40),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 900);
+        let account = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account.amount, 0);
+        assert_eq!(account.vesting_amount, 100);
+        assert_eq!(
+            Err(TokenError::VestingInProgress.into()),
+            do_process_instruction(
+                vest(100, 10, 40),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // unvested tokens cannot be spent
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                transfer(&program_id, &account2_key, &account_key, &owner2_key, &[], 1).unwrap(),
+                vec![
+                    &mut account2_account,
+                    &mut account_account,
+                    &mut owner2_account,
+                ],
+            )
+        );
+
+        // only the owner claims, and nothing before the start
+        CLOCK_SLOT.with(|slot| slot.set(5));
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                claim_vested(&program_id, &account2_key, &owner_key, &[]).unwrap(),
+                vec![&mut account2_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(claim(), vec![&mut account2_account, &mut owner2_account])
+            .unwrap();
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 0);
+
+        // claiming twice at the same slot releases nothing more
+        CLOCK_SLOT.with(|slot| slot.set(25));
+        for _ in 0..2 {
+            do_process_instruction(claim(), vec![&mut account2_account, &mut owner2_account])
+                .unwrap();
+            let account = Account::unpack(&account2_account.data).unwrap();
+            assert_eq!(account.amount, 50);
+            assert_eq!(account.vesting_amount, 50);
+        }
+
+        // the claimed part is spendable, but the account cannot be closed
+        // on the rest
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner2_key, &[], 50).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::VestingInProgress.into()),
+            do_process_instruction(
+                close_account(&program_id, &account2_key, &account_key, &owner2_key, &[]).unwrap(),
+                vec![
+                    &mut account2_account,
+                    &mut account_account,
+                    &mut owner2_account,
+                ],
+            )
+        );
+
+        // rounded down in between
+        CLOCK_SLOT.with(|slot| slot.set(26));
+        do_process_instruction(claim(), vec![&mut account2_account, &mut owner2_account])
+            .unwrap();
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 3);
+
+        // everything after the end, once
+        CLOCK_SLOT.with(|slot| slot.set(100));
+        for _ in 0..2 {
+            do_process_instruction(claim(), vec![&mut account2_account, &mut owner2_account])
+                .unwrap();
+            let account = Account::unpack(&account2_account.data).unwrap();
+            assert_eq!(account.amount, 50);
+            assert_eq!(account.vesting_amount, 0);
+        }
+
+        // a fully claimed schedule can be replaced
+        do_process_instruction(
+            vest(10, 100, 200),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account.vesting_amount, 10);
+        assert_eq!(account.vesting_total, 10);
+        assert_eq!(account.vesting_start_slot, 100);
+        assert_eq!(account.vesting_end_slot, 200);
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 940);
+    }
+
+    #[test]
+    fn test_swap() {
+        let program_id = crate::id();
+        let alice_key = Pubkey::new_unique();
+        let mut alice_account = SolanaAccount::default();
+        let bob_key = Pubkey::new_unique();
+        let mut bob_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_x_key = Pubkey::new_unique();
+        let mut mint_x_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint_y_key = Pubkey::new_unique();
+        let mut mint_y_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let alice_x_key = Pubkey::new_unique();
+        let mut alice_x_account = new_token_account();
+        let bob_x_key = Pubkey::new_unique();
+        let mut bob_x_account = new_token_account();
+        let bob_y_key = Pubkey::new_unique();
+        let mut bob_y_account = new_token_account();
+        let alice_y_key = Pubkey::new_unique();
+        let mut alice_y_account = new_token_account();
+        let mut rent_sysvar = rent_sysvar();
+
+        for (key, account) in [
+            (&mint_x_key, &mut mint_x_account),
+            (&mint_y_key, &mut mint_y_account),
+        ] {
+            do_process_instruction(
+                initialize_mint(&program_id, key, &alice_key, None, 2).unwrap(),
+                vec![account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        for (key, account, in_mint_x, owner_key) in [
+            (&alice_x_key, &mut alice_x_account, true, &alice_key),
+            (&bob_x_key, &mut bob_x_account, true, &bob_key),
+            (&bob_y_key, &mut bob_y_account, false, &bob_key),
+            (&alice_y_key, &mut alice_y_account, false, &alice_key),
+        ] {
+            let (mint_key, mint_account) = if in_mint_x {
+                (&mint_x_key, &mut mint_x_account)
+            } else {
+                (&mint_y_key, &mut mint_y_account)
+            };
+            let mut owner_account = SolanaAccount::default();
+            do_process_instruction(
+                initialize_account(&program_id, key, mint_key, owner_key).unwrap(),
+                vec![account, mint_account, &mut owner_account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_x_key, &alice_x_key, &alice_key, &[], 1000).unwrap(),
+            vec![&mut mint_x_account, &mut alice_x_account, &mut alice_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_y_key, &bob_y_key, &alice_key, &[], 1000).unwrap(),
+            vec![&mut mint_y_account, &mut bob_y_account, &mut alice_account],
+        )
+        .unwrap();
+        let amount_of = |account: &SolanaAccount| Account::unpack(&account.data).unwrap().amount;
+
+        // both parties must sign
+        let mut ix = swap(
+            &program_id,
+            &alice_x_key,
+            &bob_x_key,
+            &bob_y_key,
+            &alice_y_key,
+            &alice_key,
+            &bob_key,
+            100,
+            200,
+        )
+        .unwrap();
+        ix.accounts[5].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction_serialized(
+                ix,
+                vec![
+                    &mut alice_x_account,
+                    &mut bob_x_account,
+                    &mut bob_y_account,
+                    &mut alice_y_account,
+                    &mut alice_account,
+                    &mut bob_account,
+                ],
+            )
+        );
+        assert_eq!(amount_of(&alice_x_account), 1000);
+
+        do_process_instruction_serialized(
+            swap(
+                &program_id,
+                &alice_x_key,
+                &bob_x_key,
+                &bob_y_key,
+                &alice_y_key,
+                &alice_key,
+                &bob_key,
+                100,
+                200,
+            )
+            .unwrap(),
+            vec![
+                &mut alice_x_account,
+                &mut bob_x_account,
+                &mut bob_y_account,
+                &mut alice_y_account,
+                &mut alice_account,
+                &mut bob_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(amount_of(&alice_x_account), 900);
+        assert_eq!(amount_of(&bob_x_account), 100);
+        assert_eq!(amount_of(&bob_y_account), 800);
+        assert_eq!(amount_of(&alice_y_account), 200);
+
+        // a failing second leg leaves the first undone
+        let mut bob_x_account_dup = bob_x_account.clone();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction_serialized(
+                swap(
+                    &program_id,
+                    &alice_x_key,
+                    &bob_x_key,
+                    &bob_y_key,
+                    &bob_x_key,
+                    &alice_key,
+                    &bob_key,
+                    100,
+                    200,
+                )
+                .unwrap(),
+                vec![
+                    &mut alice_x_account,
+                    &mut bob_x_account,
+                    &mut bob_y_account,
+                    &mut bob_x_account_dup,
+                    &mut alice_account,
+                    &mut bob_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction_serialized(
+                swap(
+                    &program_id,
+                    &alice_x_key,
+                    &bob_x_key,
+                    &bob_y_key,
+                    &alice_y_key,
+                    &alice_key,
+                    &bob_key,
+                    100,
+                    801,
+                )
+                .unwrap(),
+                vec![
+                    &mut alice_x_account,
+                    &mut bob_x_account,
+                    &mut bob_y_account,
+                    &mut alice_y_account,
+                    &mut alice_account,
+                    &mut bob_account,
+                ],
+            )
+        );
+        assert_eq!(amount_of(&alice_x_account), 900);
+        assert_eq!(amount_of(&bob_x_account), 100);
+        assert_eq!(amount_of(&bob_y_account), 800);
+        assert_eq!(amount_of(&alice_y_account), 200);
+
+        // a leg into an account that requires the mint fails, as `Transfer` does
+        let mut alice_y_required = alice_y_account.clone();
+        let mut alice_y = Account::unpack(&alice_y_account.data).unwrap();
+        alice_y.mint_required = true;
+        Account::pack(alice_y, &mut alice_y_required.data).unwrap();
+        assert_eq!(
+            Err(TokenError::MintRequiredForTransfer.into()),
+            do_process_instruction_serialized(
+                swap(
+                    &program_id,
+                    &alice_x_key,
+                    &bob_x_key,
+                    &bob_y_key,
+                    &alice_y_key,
+                    &alice_key,
+                    &bob_key,
+                    100,
+                    200,
+                )
+                .unwrap(),
+                vec![
+                    &mut alice_x_account,
+                    &mut bob_x_account,
+                    &mut bob_y_account,
+                    &mut alice_y_required,
+                    &mut alice_account,
+                    &mut bob_account,
+                ],
+            )
+        );
+        assert_eq!(amount_of(&alice_x_account), 900);
+
+        // a delegate may sign one leg while the owner signs the other
+        do_process_instruction(
+            approve(&program_id, &bob_y_key, &delegate_key, &bob_key, &[], 300).unwrap(),
+            vec![&mut bob_y_account, &mut delegate_account, &mut bob_account],
+        )
+        .unwrap();
+        do_process_instruction_serialized(
+            swap(
+                &program_id,
+                &alice_x_key,
+                &bob_x_key,
+                &bob_y_key,
+                &alice_y_key,
+                &alice_key,
+                &delegate_key,
+                50,
+                300,
+            )
+            .unwrap(),
+            vec![
+                &mut alice_x_account,
+                &mut bob_x_account,
+                &mut bob_y_account,
+                &mut alice_y_account,
+                &mut alice_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(amount_of(&alice_x_account), 850);
+        assert_eq!(amount_of(&alice_y_account), 500);
+        let bob_y = Account::unpack(&bob_y_account.data).unwrap();
+        assert_eq!(bob_y.amount, 500);
+        assert_eq!(bob_y.delegate, COption::None);
+        assert_eq!(bob_y.delegated_amount, 0);
+
+        // the spent delegation cannot sign a second swap
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_serialized(
+                swap(
+                    &program_id,
+                    &alice_x_key,
+                    &bob_x_key,
+                    &bob_y_key,
+                    &alice_y_key,
+                    &alice_key,
+                    &delegate_key,
+                    50,
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut alice_x_account,
+                    &mut bob_x_account,
+                    &mut bob_y_account,
+                    &mut alice_y_account,
+                    &mut alice_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(amount_of(&alice_x_account), 850);
+
+        // self-dealing: one owner on both sides still moves both legs
+        let mut alice_account_dup = alice_account.clone();
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &bob_y_key,
+                Some(&alice_key),
+                AuthorityType::AccountOwner,
+                &bob_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut bob_y_account, &mut bob_account],
+        )
+        .unwrap();
+        do_process_instruction_serialized(
+            swap(
+                &program_id,
+                &alice_x_key,
+                &bob_x_key,
+                &bob_y_key,
+                &alice_y_key,
+                &alice_key,
+                &alice_key,
+                10,
+                20,
+            )
+            .unwrap(),
+            vec![
+                &mut alice_x_account,
+                &mut bob_x_account,
+                &mut bob_y_account,
+                &mut alice_y_account,
+                &mut alice_account,
+                &mut alice_account_dup,
+            ],
+        )
+        .unwrap();
+        assert_eq!(amount_of(&alice_x_account), 840);
+        assert_eq!(amount_of(&bob_x_account), 160);
+        assert_eq!(amount_of(&bob_y_account), 480);
+        assert_eq!(amount_of(&alice_y_account), 520);
+
+        // and swapping each account with itself nets out
+        {
+            let alice_x_info: AccountInfo =
+                (&alice_x_key, false, &mut alice_x_account).into();
+            let alice_y_info: AccountInfo =
+                (&alice_y_key, false, &mut alice_y_account).into();
+            let alice_info: AccountInfo = (&alice_key, true, &mut alice_account).into();
+            do_process_instruction_dups(
+                swap(
+                    &program_id,
+                    &alice_x_key,
+                    &alice_x_key,
+                    &alice_y_key,
+                    &alice_y_key,
+                    &alice_key,
+                    &alice_key,
+                    10,
+                    20,
+                )
+                .unwrap(),
+                vec![
+                    alice_x_info.clone(),
+                    alice_x_info,
+                    alice_y_info.clone(),
+                    alice_y_info,
+                    alice_info.clone(),
+                    alice_info,
+                ],
+            )
+            .unwrap();
+        }
+        assert_eq!(amount_of(&alice_x_account), 840);
+        assert_eq!(amount_of(&alice_y_account), 520);
+    }
+
+    #[test]
+    fn test_freeze_accounts() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let freezer_key = Pubkey::new_unique();
+        let mut freezer_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        for (key, account) in [
+            (&mint_key, &mut mint_account),
+            (&mint2_key, &mut mint2_account),
+        ] {
+            do_process_instruction(
+                initialize_mint(&program_id, key, &owner_key, Some(&freezer_key), 2).unwrap(),
+                vec![account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        let target_keys: Vec<Pubkey> = (0..25).map(|_| Pubkey::new_unique()).collect();
+        let mut target_accounts: Vec<SolanaAccount> = (0..25)
+            .map(|_| {
+                SolanaAccount::new(
+                    account_minimum_balance(),
+                    Account::get_packed_len(),
+                    &program_id,
+                )
+            })
+            .collect();
+        // the target at index 17 belongs to another mint
+        for (index, (key, account)) in target_keys
+            .iter()
+            .zip(target_accounts.iter_mut())
+            .enumerate()
+        {
+            let (mint_key, mint_account) = if index == 17 {
+                (&mint2_key, &mut mint2_account)
+            } else {
+                (&mint_key, &mut mint_account)
+            };
+            do_process_instruction(
+                initialize_account(&program_id, key, mint_key, &owner_key).unwrap(),
+                vec![account, mint_account, &mut owner_account, &mut rent_sysvar],
+            )
+            .unwrap();
+        }
+        let target_refs: Vec<&Pubkey> = target_keys.iter().collect();
+        let is_frozen = |account: &SolanaAccount| Account::unpack(&account.data).unwrap().is_frozen();
+
+        // the mismatched target fails the batch and none is frozen
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        let mut accounts = vec![&mut mint_account, &mut freezer_account];
+        accounts.extend(target_accounts.iter_mut());
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction_serialized(
+                freeze_accounts(&program_id, &mint_key, &freezer_key, &[], &target_refs).unwrap(),
+                accounts,
+            )
+        );
+        assert_eq!(
+            LOGS.with(|logs| logs.borrow().contains(&"Failed on target account 17".to_string())),
+            !cfg!(feature = "no-log")
+        );
+        assert!(!target_accounts.iter().any(is_frozen));
+
+        // only the freeze authority may sign
+        let mut accounts = vec![&mut mint_account, &mut owner_account];
+        accounts.extend(target_accounts.iter_mut());
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction_serialized(
+                freeze_accounts(&program_id, &mint_key, &owner_key, &[], &target_refs).unwrap(),
+                accounts,
+            )
+        );
+
+        // without the stray target all 24 freeze at once
+        let mut good_refs = target_refs.clone();
+        good_refs.remove(17);
+        let stray_account = target_accounts.remove(17);
+        let mut accounts = vec![&mut mint_account, &mut freezer_account];
+        accounts.extend(target_accounts.iter_mut());
+        do_process_instruction_serialized(
+            freeze_accounts(&program_id, &mint_key, &freezer_key, &[], &good_refs).unwrap(),
+            accounts,
+        )
+        .unwrap();
+        assert!(target_accounts.iter().all(is_frozen));
+        assert!(!is_frozen(&stray_account));
+
+        // an account listed twice fails on its second appearance
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        let mut first_account = target_accounts[0].clone();
+        let first_info: AccountInfo = (good_refs[0], true, &mut first_account).into();
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction_dups(
+                thaw_accounts(
+                    &program_id,
+                    &mint_key,
+                    &freezer_key,
+                    &[],
+                    &[good_refs[0], good_refs[0]],
+                )
+                .unwrap(),
+                vec![
+                    (&mint_key, false, &mut mint_account).into(),
+                    (&freezer_key, true, &mut freezer_account).into(),
+                    first_info.clone(),
+                    first_info,
+                ],
+            )
+        );
+        assert_eq!(
+            LOGS.with(|logs| logs.borrow().contains(&"Failed on target account 1".to_string())),
+            !cfg!(feature = "no-log")
+        );
+
+        // and thawing them all restores the accounts
+        let mut accounts = vec![&mut mint_account, &mut freezer_account];
+        accounts.extend(target_accounts.iter_mut());
+        do_process_instruction_serialized(
+            thaw_accounts(&program_id, &mint_key, &freezer_key, &[], &good_refs).unwrap(),
+            accounts,
+        )
+        .unwrap();
+        assert!(!target_accounts.iter().any(is_frozen));
+    }
+
+    #[test]
+    fn test_burn_from() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let authority_key = Pubkey::new_unique();
+        let mut authority_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &authority_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &authority_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut authority_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 500).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // neither the owner nor a delegate can claw back
+        for (key, account) in [
+            (&owner_key, &mut owner_account),
+            (&delegate_key, &mut delegate_account),
+        ] {
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    burn_from(&program_id, &account_key, &mint_key, key, &[], 100).unwrap(),
+                    vec![&mut account_account, &mut mint_account, account],
+                )
+            );
+        }
+
+        // the mint authority burns without the owner
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                burn_from(&program_id, &account_key, &mint_key, &authority_key, &[], 1001)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut authority_account,
+                ],
+            )
+        );
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        do_process_instruction(
+            burn_from(&program_id, &account_key, &mint_key, &authority_key, &[], 300).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut authority_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            LOGS.with(|logs| logs
+                .borrow()
+                .contains(&format!("Clawback of 300 from {}", account_key))),
+            !cfg!(feature = "no-log")
+        );
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.amount, 700);
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 700);
+
+        // and loses the power with its authority
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                None,
+                AuthorityType::MintTokens,
+                &authority_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut authority_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::FixedSupply.into()),
+            do_process_instruction(
+                burn_from(&program_id, &account_key, &mint_key, &authority_key, &[], 100)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut authority_account,
+                ],
+            )
+        );
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 700);
+    }
+
+    #[test]
+    fn test_cmp_pubkeys() {
+        let key = Pubkey::new_unique();
+        assert!(Processor::cmp_pubkeys(&key, &key));
+        assert!(Processor::cmp_pubkeys(&key, &Pubkey::new_from_array(key.to_bytes())));
+        assert!(Processor::cmp_pubkeys(&Pubkey::default(), &Pubkey::default()));
+        assert!(!Processor::cmp_pubkeys(&key, &Pubkey::new_unique()));
+        // a difference in any one byte is seen
+        for index in 0..PUBKEY_BYTES {
+            let mut bytes = key.to_bytes();
+            bytes[index] ^= 1;
+            assert!(!Processor::cmp_pubkeys(&key, &Pubkey::new_from_array(bytes)));
+            assert!(!Processor::cmp_pubkeys(&Pubkey::new_from_array(bytes), &key));
+        }
+    }
+
+    /// Runs `transfer_in_place` and the full `process_transfer` over copies
+    /// of the same accounts and returns whether the in-place path made the
+    /// transfer. When it did, both paths must leave the same accounts; when it
+    /// did not, it must have left them untouched.
+    fn compare_transfer_paths(accounts: &[(Pubkey, bool, SolanaAccount)], amount: u64) -> bool {
+        let program_id = crate::id();
+        let run = |in_place: bool| {
+            let mut accounts = accounts.to_vec();
+            let result = {
+                let mut metas: Vec<(&Pubkey, bool, &mut SolanaAccount)> = accounts
+                    .iter_mut()
+                    .map(|(key, is_signer, account)| (&*key, *is_signer, account))
+                    .collect();
+                let account_infos = create_is_signer_account_infos(&mut metas);
+                if in_place {
+                    Ok(Processor::transfer_in_place(&program_id, &account_infos, amount))
+                } else {
+                    Processor::process_transfer(&program_id, &account_infos, amount, None, None)
+                        .map(|()| true)
+                }
+            };
+            (result, accounts)
+        };
+
+        let (in_place, in_place_accounts) = run(true);
+        if in_place != Ok(true) {
+            assert_eq!(in_place_accounts, accounts);
+            return false;
+        }
+        let (full, full_accounts) = run(false);
+        assert_eq!(full, Ok(true));
+        assert_eq!(in_place_accounts, full_accounts);
+        true
+    }
+
+    #[test]
+    fn test_transfer_in_place() {
+        set_stubs();
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let delegate_key = Pubkey::new_unique();
+        let source_key = Pubkey::new_unique();
+        let dest_key = Pubkey::new_unique();
+        let token_account = |owner: &Pubkey, amount: u64| Account {
+            mint: mint_key,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let packed = |account: Account| {
+            let mut data = vec![0; Account::LEN];
+            Account::pack(account, &mut data).unwrap();
+            SolanaAccount {
+                lamports: account_minimum_balance(),
+                data,
+                owner: program_id,
+                ..SolanaAccount::default()
+            }
+        };
+        let source = token_account(&owner_key, 1_000);
+        let dest = token_account(&Pubkey::new_unique(), 10);
+        let accounts = |source: SolanaAccount, dest: SolanaAccount| {
+            vec![
+                (source_key, false, source),
+                (dest_key, false, dest),
+                (owner_key, true, SolanaAccount::default()),
+            ]
+        };
+
+        // a plain transfer by the owner is made in place
+        let plain = accounts(packed(source), packed(dest));
+        assert!(compare_transfer_paths(&plain, 300));
+        assert!(compare_transfer_paths(&plain, 1_000));
+        assert!(compare_transfer_paths(&plain, TRANSFER_ALL));
+        // but a zero amount is left to the full path, which writes nothing
+        assert!(!compare_transfer_paths(&plain, 0));
+        // as is one over stale fields the full path does not rewrite
+        let stale = Account {
+            delegated_amount: 5,
+            close_authority: COption::Some(Pubkey::new_unique()),
+            pending_owner: COption::Some(Pubkey::new_unique()),
+            window_start_epoch: 3,
+            spent_in_window: 4,
+            vesting_amount: 6,
+            ..source
+        };
+        let dest_with_options = Account {
+            delegate: COption::Some(delegate_key),
+            delegated_amount: 1,
+            cpi_guard: true,
+            lock_until: COption::Some(u64::MAX),
+            ..dest
+        };
+        assert!(compare_transfer_paths(
+            &accounts(packed(stale), packed(dest_with_options)),
+            300
+        ));
+
+        // anything else is left to the full path
+        assert!(!compare_transfer_paths(&plain, 1_001));
+        let mut unsigned = plain.clone();
+        unsigned[2].1 = false;
+        assert!(!compare_transfer_paths(&unsigned, 300));
+        let mut self_transfer = plain.clone();
+        self_transfer[1].0 = source_key;
+        assert!(!compare_transfer_paths(&self_transfer, 300));
+        let mut foreign = plain.clone();
+        foreign[1].2.owner = Pubkey::new_unique();
+        assert!(!compare_transfer_paths(&foreign, 300));
+        let mut legacy = plain.clone();
+        legacy[0].2.data.truncate(LEGACY_ACCOUNT_LEN);
+        assert!(!compare_transfer_paths(&legacy, 300));
+        let full = accounts(packed(source), packed(token_account(&owner_key, u64::MAX)));
+        assert!(!compare_transfer_paths(&full, 1));
+        for source in [
+            Account {
+                delegate: COption::Some(delegate_key),
+                delegated_amount: 100,
+                ..source
+            },
+            Account {
+                expiry_slot: COption::Some(u64::MAX),
+                ..source
+            },
+            Account {
+                lock_until: COption::Some(u64::MAX),
+                ..source
+            },
+            Account {
+                limit_amount: COption::Some(u64::MAX),
+                ..source
+            },
+            Account {
+                is_native: COption::Some(0),
+                ..source
+            },
+            Account {
+                escrowed: true,
+                ..source
+            },
+            Account {
+                cpi_guard: true,
+                ..source
+            },
+            Account {
+                mint_required: true,
+                ..source
+            },
+            Account {
+                state: AccountState::Frozen,
+                ..source
+            },
+            Account {
+                mint: Pubkey::new_unique(),
+                ..source
+            },
+            token_account(&Pubkey::new_unique(), 1_000),
+        ] {
+            assert!(!compare_transfer_paths(&accounts(packed(source), packed(dest)), 300));
+        }
+        for dest in [
+            Account {
+                memo_required: true,
+                ..dest
+            },
+            Account {
+                mint_required: true,
+                ..dest
+            },
+            Account {
+                state: AccountState::Frozen,
+                ..dest
+            },
+            Account {
+                state: AccountState::Uninitialized,
+                ..dest
+            },
+        ] {
+            assert!(!compare_transfer_paths(&accounts(packed(source), packed(dest)), 300));
+        }
+
+        // a delegate goes the full way
+        let delegated = Account {
+            delegate: COption::Some(delegate_key),
+            delegated_amount: 100,
+            ..source
+        };
+        let mut by_delegate = accounts(packed(delegated), packed(dest));
+        by_delegate[2].0 = delegate_key;
+        assert!(!compare_transfer_paths(&by_delegate, 50));
+
+        // so does an owner that is a multisig
+        let mut multisig = plain.clone();
+        multisig[2].2 = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        assert!(!compare_transfer_paths(&multisig, 300));
+
+        // and bytes the full path would rewrite or reject
+        let offset = |name: &str| {
+            ACCOUNT_FIELDS
+                .iter()
+                .find(|(field, _, _)| *field == name)
+                .unwrap()
+                .1
+        };
+        let mut non_canonical = plain.clone();
+        non_canonical[0].2.data[offset("delegate") + 4] = 1;
+        assert!(!compare_transfer_paths(&non_canonical, 300));
+        let mut bad_tag = plain.clone();
+        bad_tag[1].2.data[offset("close_authority")] = 2;
+        assert!(!compare_transfer_paths(&bad_tag, 300));
+        let mut bad_flag = plain;
+        bad_flag[1].2.data[offset("immutable_owner")] = 2;
+        assert!(!compare_transfer_paths(&bad_flag, 300));
+    }
+
+    #[test]
+    fn test_no_op_writes_nothing() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1_000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        let account_data = account_account.data.clone();
+        let account2_data = account2_account.data.clone();
+        let mint_data = mint_account.data.clone();
+
+        // each no-op succeeds with the data of the accounts it leaves alone
+        // borrowed, so a write would panic, and leaves their bytes as they were
+        do_process_instruction_holding(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 0).unwrap(),
+            vec![&mut account_account, &mut account2_account, &mut owner_account],
+            &[0, 1],
+        )
+        .unwrap();
+        do_process_instruction_holding(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                0,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+            &[0, 1, 2],
+        )
+        .unwrap();
+        do_process_instruction_holding(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+            &[0],
+        )
+        .unwrap();
+        do_process_instruction_holding(
+            revoke(&program_id, &account2_key, &owner_key, &[]).unwrap(),
+            vec![&mut account2_account, &mut owner_account],
+            &[0],
+        )
+        .unwrap();
+        do_process_instruction_holding(
+            mint_to(&program_id, &mint_key, &account2_key, &owner_key, &[], 0).unwrap(),
+            vec![&mut mint_account, &mut account2_account, &mut owner_account],
+            &[0, 1],
+        )
+        .unwrap();
+        do_process_instruction_holding(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 0).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+            &[0, 1],
+        )
+        .unwrap();
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(account2_account.data, account2_data);
+        assert_eq!(mint_account.data, mint_data);
+
+        // a change is still written
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_ne!(account_account.data, account_data);
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().delegated_amount,
+            50
+        );
+    }
+
+    #[test]
+    fn test_failure_writes_nothing() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let fee_authority_key = Pubkey::new_unique();
+        let mut fee_authority_account = SolanaAccount::default();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(fee_authority_key),
+                supply: u64::MAX,
+                is_initialized: true,
+                transfer_fee_config: TransferFeeConfig {
+                    transfer_fee_authority: COption::Some(fee_authority_key),
+                    ..TransferFeeConfig::default()
+                },
+                ..Mint::default()
+            },
+            &mut mint_account.data,
+        )
+        .unwrap();
+        let token_account = |amount: u64, withheld_amount: u64| {
+            let mut account = SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            );
+            Account::pack(
+                Account {
+                    mint: mint_key,
+                    owner: fee_authority_key,
+                    amount,
+                    state: AccountState::Initialized,
+                    withheld_amount,
+                    ..Account::default()
+                },
+                &mut account.data,
+            )
+            .unwrap();
+            account
+        };
+        let source_key = Pubkey::new_unique();
+        let mut source_account = token_account(0, 5);
+        let source2_key = Pubkey::new_unique();
+        let mut source2_account = token_account(0, 5);
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = token_account(u64::MAX - 5, 0);
+        let source_data = source_account.data.clone();
+        let source2_data = source2_account.data.clone();
+
+        // the fees do not fit in the destination, and no source gives them up
+        assert_eq!(
+            Err(TokenError::Overflow.into()),
+            do_process_instruction(
+                withdraw_withheld_tokens_from_accounts(
+                    &program_id,
+                    &mint_key,
+                    &dest_key,
+                    &fee_authority_key,
+                    &[],
+                    &[&source_key, &source2_key],
+                )
+                .unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut dest_account,
+                    &mut fee_authority_account,
+                    &mut source_account,
+                    &mut source2_account,
+                ],
+            )
+        );
+        assert_eq!(source_account.data, source_data);
+        assert_eq!(source2_account.data, source2_data);
+
+        // a bad source after a good one leaves the good one unharvested
+        let mut other_account = token_account(0, 5);
+        Account::pack(
+            Account {
+                mint: Pubkey::new_unique(),
+                ..Account::unpack(&other_account.data).unwrap()
+            },
+            &mut other_account.data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                harvest_withheld_tokens_to_mint(
+                    &program_id,
+                    &mint_key,
+                    &[&source_key, &Pubkey::new_unique()],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut source_account, &mut other_account],
+            )
+        );
+        assert_eq!(source_account.data, source_data);
+    }
+
+    #[test]
+    fn test_events() {
+        let program_id = crate::id();
+        let new_token_account = || {
+            SolanaAccount::new(
+                account_minimum_balance(),
+                Account::get_packed_len(),
+                &program_id,
+            )
+        };
+        let account_key = Pubkey::new_unique();
+        let mut account_account = new_token_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = new_token_account();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        let events = || {
+            LOG_DATA
+                .with(|log_data| log_data.replace(vec![]))
+                .iter()
+                .map(|data| crate::events::parse_event(data).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let expect = |expected: Vec<TokenEvent>| {
+            if cfg!(feature = "events") {
+                expected
+            } else {
+                vec![]
+            }
+        };
+        // initializing emits nothing
+        assert_eq!(events(), vec![]);
+
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1_000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            events(),
+            expect(vec![TokenEvent::Mint(MintEvent {
+                mint: mint_key,
+                account: account_key,
+                authority: owner_key,
+                amount: 1_000,
+            })])
+        );
+
+        // the in-place path and the full path report a transfer alike
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 300).unwrap(),
+            vec![&mut account_account, &mut account2_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                TRANSFER_ALL,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let transfer_event = |amount| {
+            TokenEvent::Transfer(TransferEvent {
+                source: account_key,
+                destination: account2_key,
+                authority: owner_key,
+                amount,
+            })
+        };
+        // a sweep reports the amount it resolved to
+        assert_eq!(
+            events(),
+            expect(vec![transfer_event(300), transfer_event(700)])
+        );
+
+        do_process_instruction(
+            approve(&program_id, &account2_key, &delegate_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            burn(&program_id, &account2_key, &mint_key, &delegate_key, &[], 10).unwrap(),
+            vec![&mut account2_account, &mut mint_account, &mut delegate_account],
+        )
+        .unwrap();
+        assert_eq!(
+            events(),
+            expect(vec![
+                TokenEvent::Approve(ApproveEvent {
+                    source: account2_key,
+                    delegate: delegate_key,
+                    owner: owner_key,
+                    amount: 50,
+                }),
+                TokenEvent::Burn(BurnEvent {
+                    account: account2_key,
+                    mint: mint_key,
+                    authority: delegate_key,
+                    amount: 10,
+                }),
+            ])
+        );
+
+        // a failed instruction emits nothing
+        assert_eq!(
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![&mut account_account, &mut account2_account, &mut owner_account],
+            ),
+            Err(TokenError::InsufficientFunds.into())
+        );
+        assert_eq!(events(), vec![]);
+    }
+
+    #[test]
+    fn test_approve_invalid_delegate() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = MintFixture::new()
+            .supply(1000)
+            .decimals(2)
+            .into_solana_account();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .amount(1000)
+            .into_solana_account();
+
+        // the owner as its own delegate
+        assert_eq!(
+            Err(TokenError::InvalidDelegate.into()),
+            do_process_instruction(
+                approve(&program_id, &account_key, &owner_key, &owner_key, &[], 100).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut owner_account.clone(),
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidDelegate.into()),
+            do_process_instruction(
+                approve_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &owner_key,
+                    &owner_key,
+                    &[],
+                    100,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut owner_account.clone(),
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // the account as its own delegate
+        assert_eq!(
+            Err(TokenError::InvalidDelegate.into()),
+            do_process_instruction(
+                approve(&program_id, &account_key, &account_key, &owner_key, &[], 100).unwrap(),
+                vec![
+                    &mut account_account.clone(),
+                    &mut account_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidDelegate.into()),
+            do_process_instruction(
+                approve_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account_key,
+                    &owner_key,
+                    &[],
+                    100,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account.clone(),
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+    }
+
+    #[test]
+    fn test_owner_as_delegate_transfer() {
+        // Approve no longer creates this state, but accounts approved before
+        // it was rejected can still hold it: an owner transfer is taken for
+        // a delegate transfer and spends the allowance
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .amount(1000)
+            .delegate(owner_key, 50)
+            .into_solana_account();
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .into_solana_account();
+
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 30).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 970);
+        assert_eq!(account.delegate, COption::Some(owner_key));
+        assert_eq!(account.delegated_amount, 20);
+
+        // and the owner cannot move more than what is left of it
+        assert_eq!(
+            Err(TokenError::InsufficientDelegatedFunds.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 30).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_freeze_authority() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let authority_key = Pubkey::new_unique();
+        let mut authority_account = SolanaAccount::default();
+        let authority2_key = Pubkey::new_unique();
+        let mut authority2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .freeze_authority(authority_key)
+            .into_solana_account();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .into_solana_account();
+
+        // mint-level authorities are not an account's
+        for authority_type in [AuthorityType::FreezeAccount, AuthorityType::MintTokens] {
+            assert_eq!(
+                Err(TokenError::AuthorityTypeNotSupported.into()),
+                do_process_instruction(
+                    set_authority(
+                        &program_id,
+                        &account_key,
+                        Some(&authority2_key),
+                        authority_type,
+                        &owner_key,
+                        &[],
+                    )
+                    .unwrap(),
+                    vec![&mut account_account, &mut owner_account],
+                )
+            );
+        }
+        // and account-level ones are not a mint's
+        for authority_type in [AuthorityType::AccountOwner, AuthorityType::CloseAccount] {
+            assert_eq!(
+                Err(TokenError::AuthorityTypeNotSupported.into()),
+                do_process_instruction(
+                    set_authority(
+                        &program_id,
+                        &mint_key,
+                        Some(&authority2_key),
+                        authority_type,
+                        &owner_key,
+                        &[],
+                    )
+                    .unwrap(),
+                    vec![&mut mint_account, &mut owner_account],
+                )
+            );
+        }
+
+        // only the freeze authority hands it over
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&authority2_key),
+                    AuthorityType::FreezeAccount,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                Some(&authority2_key),
+                AuthorityType::FreezeAccount,
+                &authority_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut authority_account],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.freeze_authority, COption::Some(authority2_key));
+        assert_eq!(mint.mint_authority, COption::Some(owner_key));
+
+        // the previous authority can no longer freeze, the new one can
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &authority_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut authority_account],
+            )
+        );
+        do_process_instruction(
+            freeze_account(&program_id, &account_key, &mint_key, &authority2_key, &[]).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut authority2_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            thaw_account(&program_id, &account_key, &mint_key, &authority2_key, &[]).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut authority2_account],
+        )
+        .unwrap();
+
+        // once renounced, it cannot freeze nor be set again
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                None,
+                AuthorityType::FreezeAccount,
+                &authority2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut authority2_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().freeze_authority,
+            COption::None
+        );
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &authority2_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut authority2_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&authority2_key),
+                    AuthorityType::FreezeAccount,
+                    &authority2_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut authority2_account],
+            )
+        );
+
+        // a mint created without one never had it
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .into_solana_account();
+        let mint2_data = mint2_account.data.clone();
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint2_key,
+                    Some(&authority_key),
+                    AuthorityType::FreezeAccount,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint2_account, &mut owner_account],
+            )
+        );
+        assert_eq!(mint2_account.data, mint2_data);
+    }
+
+    #[test]
+    fn test_mint_to_dups() {
+        let program_id = crate::id();
+        let account1_key = Pubkey::new_unique();
+        let mut account1_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account1_info: AccountInfo = (&account1_key, true, &mut account1_account).into();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint_info: AccountInfo = (&mint_key, true, &mut mint_account).into();
+        let rent_key = rent::id();
+        let mut rent_sysvar = rent_sysvar();
+        let rent_info: AccountInfo = (&rent_key, false, &mut rent_sysvar).into();
+
+        // create mint, its own mint authority
+        do_process_instruction_dups(
+            initialize_mint(&program_id, &mint_key, &mint_key, None, 2).unwrap(),
+            vec![mint_info.clone(), rent_info.clone()],
+        )
+        .unwrap();
+
+        // create account
+        do_process_instruction_dups(
+            initialize_account(&program_id, &account1_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                owner_info.clone(),
+                rent_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // mint_to when mint_authority is self
+        do_process_instruction_dups(
+            mint_to(&program_id, &mint_key, &account1_key, &mint_key, &[], 42).unwrap(),
+            vec![mint_info.clone(), account1_info.clone(), mint_info.clone()],
+        )
+        .unwrap();
+        let account = Account::unpack(&account1_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 42);
+        let mint = Mint::unpack(&mint_info.data.borrow()).unwrap();
+        assert_eq!(mint.supply, 42);
+
+        // the mint passed as the destination account
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction_dups(
+                mint_to(&program_id, &mint_key, &mint_key, &mint_key, &[], 42).unwrap(),
+                vec![mint_info.clone(), mint_info.clone(), mint_info.clone()],
+            )
+        );
+        let mint = Mint::unpack(&mint_info.data.borrow()).unwrap();
+        assert_eq!(mint.supply, 42);
+    }
+
+    #[test]
+    fn test_burn_dups() {
+        let program_id = crate::id();
+        let account1_key = Pubkey::new_unique();
+        let mut account1_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account1_info: AccountInfo = (&account1_key, true, &mut account1_account).into();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner_info: AccountInfo = (&owner_key, true, &mut owner_account).into();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint_info: AccountInfo = (&mint_key, true, &mut mint_account).into();
+        let rent_key = rent::id();
+        let mut rent_sysvar = rent_sysvar();
+        let rent_info: AccountInfo = (&rent_key, false, &mut rent_sysvar).into();
+
+        // create mint
+        do_process_instruction_dups(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![mint_info.clone(), rent_info.clone()],
+        )
+        .unwrap();
+
+        // create account, owned by itself
+        do_process_instruction_dups(
+            initialize_account(&program_id, &account1_key, &mint_key, &account1_key).unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account1_info.clone(),
+                rent_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // mint to account
+        do_process_instruction_dups(
+            mint_to(&program_id, &mint_key, &account1_key, &owner_key, &[], 1000).unwrap(),
+            vec![mint_info.clone(), account1_info.clone(), owner_info.clone()],
+        )
+        .unwrap();
+
+        // source-owner burn
+        do_process_instruction_dups(
+            burn(&program_id, &account1_key, &mint_key, &account1_key, &[], 500).unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account1_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // source-owner burn_checked
+        do_process_instruction_dups(
+            burn_checked(&program_id, &account1_key, &mint_key, &account1_key, &[], 100, 2)
+                .unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account1_info.clone(),
+            ],
+        )
+        .unwrap();
+
+        // source-delegate burn, the mint delegated as an authority
+        do_process_instruction_dups(
+            approve(&program_id, &account1_key, &mint_key, &account1_key, &[], 100).unwrap(),
+            vec![
+                account1_info.clone(),
+                mint_info.clone(),
+                account1_info.clone(),
+            ],
+        )
+        .unwrap();
+        do_process_instruction_dups(
+            burn(&program_id, &account1_key, &mint_key, &mint_key, &[], 100).unwrap(),
+            vec![account1_info.clone(), mint_info.clone(), mint_info.clone()],
+        )
+        .unwrap();
+        let account = Account::unpack(&account1_info.data.borrow()).unwrap();
+        assert_eq!(account.amount, 300);
+        assert_eq!(account.delegate, COption::None);
+        let mint = Mint::unpack(&mint_info.data.borrow()).unwrap();
+        assert_eq!(mint.supply, 300);
+
+        // the mint passed as the source account
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction_dups(
+                burn(&program_id, &mint_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![mint_info.clone(), mint_info.clone(), owner_info.clone()],
+            )
+        );
+        let mint = Mint::unpack(&mint_info.data.borrow()).unwrap();
+        assert_eq!(mint.supply, 300);
+    }
+
+    #[test]
+    fn test_burn_invalid_mint() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .supply(1000)
+            .decimals(2)
+            .into_solana_account();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .amount(1000)
+            .into_solana_account();
+        let account_data = account_account.data.clone();
+
+        // a short account under the mint key whose bytes are arbitrary
+        let mut fake_mint_account = SolanaAccount::new(1, 46, &program_id);
+        fake_mint_account.data = vec![1; 46];
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 42).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut fake_mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, account_data);
+
+        // an uninitialized mint of the right size
+        let mut uninitialized_mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 42, 2)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut uninitialized_mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(
+            uninitialized_mint_account.data,
+            vec![0; Mint::get_packed_len()]
+        );
+
+        // the real mint still burns
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 42).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 958);
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.supply, 958);
+    }
+
+    #[test]
+    fn test_burn_invalid_mint_by_delegate() {
+        // A delegate burn spends the allowance, which must not happen when
+        // the mint turns out to be wrong
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .supply(1000)
+            .decimals(2)
+            .into_solana_account();
+        let mint_data = mint_account.data.clone();
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account = MintFixture::new()
+            .mint_authority(owner_key)
+            .supply(1000)
+            .decimals(2)
+            .into_solana_account();
+        let mint2_data = mint2_account.data.clone();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = AccountFixture::new(mint_key)
+            .owner(owner_key)
+            .amount(1000)
+            .delegate(delegate_key, 100)
+            .into_solana_account();
+        let account_data = account_account.data.clone();
+
+        // garbage under the mint key
+        let mut fake_mint_account = SolanaAccount::new(1, Mint::get_packed_len(), &program_id);
+        fake_mint_account.data = vec![0xff; Mint::get_packed_len()];
+        let fake_mint_data = fake_mint_account.data.clone();
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &delegate_key, &[], 42).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut fake_mint_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(fake_mint_account.data, fake_mint_data);
+
+        // a valid mint, but not the account's
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint2_key, &delegate_key, &[], 42).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(mint2_account.data, mint2_data);
+
+        // the account's own mint at the wrong decimals
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &delegate_key, &[], 42, 9)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, account_data);
+        assert_eq!(mint_account.data, mint_data);
+
+        // the delegate burn itself goes through
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &delegate_key, &[], 42).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 958);
+        assert_eq!(account.delegated_amount, 58);
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 958);
+    }
+
+    #[test]
+    fn test_approve() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let delegate2_key = Pubkey::new_unique();
+        let mut delegate2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // approve delegate
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 100);
+
+        // re-approving another delegate replaces both the key and the amount
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate2_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate2_key));
+        assert_eq!(account.delegated_amount, 50);
+
+        // the previous delegate can no longer transfer
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &delegate_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+
+        // approving zero clears the delegate
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate2_key, &owner_key, &[], 0).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+
+        // approve_checked with zero clears it too
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &delegate_key,
+                &owner_key,
+                &[],
+                0,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+    }
+
+    #[test]
+    fn test_lock_until_slot() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // only the owner locks
+        CLOCK_SLOT.with(|slot| slot.set(10));
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                lock_until_slot(&program_id, &account_key, &delegate_key, &[], 20).unwrap(),
+                vec![&mut account_account, &mut delegate_account],
+            )
+        );
+        do_process_instruction(
+            lock_until_slot(&program_id, &account_key, &owner_key, &[], 20).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Account::unpack(&account_account.data).unwrap().lock_until,
+            COption::Some(20)
+        );
+
+        // a running lock can be extended but not shortened
+        assert_eq!(
+            Err(TokenError::AccountLocked.into()),
+            do_process_instruction(
+                lock_until_slot(&program_id, &account_key, &owner_key, &[], 19).unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            lock_until_slot(&program_id, &account_key, &owner_key, &[], 30).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // while locked, nothing is spent or approved, by owner or delegate
+        CLOCK_SLOT.with(|slot| slot.set(29));
+        for authority_key in [&owner_key, &delegate_key] {
+            let mut authority_account = SolanaAccount::default();
+            assert_eq!(
+                Err(TokenError::AccountLocked.into()),
+                do_process_instruction(
+                    transfer(&program_id, &account_key, &account2_key, authority_key, &[], 1)
+                        .unwrap(),
+                    vec![
+                        &mut account_account,
+                        &mut account2_account,
+                        &mut authority_account,
+                    ],
+                )
+            );
+            assert_eq!(
+                Err(TokenError::AccountLocked.into()),
+                do_process_instruction(
+                    burn(&program_id, &account_key, &mint_key, authority_key, &[], 1).unwrap(),
+                    vec![
+                        &mut account_account,
+                        &mut mint_account,
+                        &mut authority_account,
+                    ],
+                )
+            );
+        }
+        assert_eq!(
+            Err(TokenError::AccountLocked.into()),
+            do_process_instruction(
+                approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 200).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut delegate_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // but tokens can still come in
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 50).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 0).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 1050);
+        assert_eq!(account.lock_until, COption::Some(30));
+
+        // from the lock slot on, the first debit goes through and clears it
+        CLOCK_SLOT.with(|slot| slot.set(30));
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 1000);
+        assert_eq!(account.lock_until, COption::None);
+
+        // once a lock has run out, a new one may end earlier than it did
+        do_process_instruction(
+            lock_until_slot(&program_id, &account_key, &owner_key, &[], 40).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        CLOCK_SLOT.with(|slot| slot.set(40));
+        do_process_instruction(
+            lock_until_slot(&program_id, &account_key, &owner_key, &[], 35).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 200).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegated_amount, 200);
+        assert_eq!(account.lock_until, COption::None);
+    }
+
+    #[test]
+    fn test_approve_with_expiry() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        CLOCK_SLOT.with(|slot| slot.set(10));
+        do_process_instruction(
+            approve_with_expiry(&program_id, &account_key, &delegate_key, &owner_key, &[], 100, 20)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.expiry_slot, COption::Some(20));
+
+        // the expiry slot itself is still usable
+        CLOCK_SLOT.with(|slot| slot.set(20));
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 30).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+
+        CLOCK_SLOT.with(|slot| slot.set(21));
+        assert_eq!(
+            Err(TokenError::DelegateExpired.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 1)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::DelegateExpired.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &delegate_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 970);
+        assert_eq!(account.delegated_amount, 70);
+
+        // the owner's next transfer drops the stale delegation
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 969);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+        assert_eq!(account.expiry_slot, COption::None);
+
+        // a plain approval never expires
+        do_process_instruction(
+            approve_with_expiry(&program_id, &account_key, &delegate_key, &owner_key, &[], 100, 5)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.expiry_slot, COption::None);
+        do_process_instruction(
+            burn(&program_id, &account_key, &mint_key, &delegate_key, &[], 1).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+
+        // revoking clears the expiry with the delegate
+        do_process_instruction(
+            approve_with_expiry(&program_id, &account_key, &delegate_key, &owner_key, &[], 100, 50)
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.expiry_slot, COption::None);
+    }
+
+    #[test]
+    fn test_change_delegated_amount() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // nothing to adjust without a delegate
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                increase_delegated_amount(&program_id, &account_key, &owner_key, &[], 10)
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // only the owner may adjust the allowance
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                increase_delegated_amount(&program_id, &account_key, &delegate_key, &[], 10)
+                    .unwrap(),
+                vec![&mut account_account, &mut delegate_account],
+            )
+        );
+
+        do_process_instruction(
+            increase_delegated_amount(&program_id, &account_key, &owner_key, &[], 50).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 150);
+
+        assert_eq!(
+            Err(TokenError::Overflow.into()),
+            do_process_instruction(
+                increase_delegated_amount(&program_id, &account_key, &owner_key, &[], u64::MAX)
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        // the owner means to cut the allowance from 150 to 50, but the
+        // delegate spends 120 first
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 120).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+
+        // the decrease is relative, so it cannot hand the delegate a fresh 50
+        assert_eq!(
+            Err(TokenError::InsufficientDelegatedFunds.into()),
+            do_process_instruction(
+                decrease_delegated_amount(&program_id, &account_key, &owner_key, &[], 100)
+                    .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            decrease_delegated_amount(&program_id, &account_key, &owner_key, &[], 30).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 880);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+
+        // in total the delegate moved no more than the owner ever allowed
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 1)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+        assert_eq!(Account::unpack(&account2_account.data).unwrap().amount, 120);
+    }
+
+    #[test]
+    fn test_uninitialized_and_invalid_state() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let uninitialized_key = Pubkey::new_unique();
+        let mut uninitialized_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // transfer to an account that was created but never initialized
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &uninitialized_key, &owner_key, &[], 0)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut uninitialized_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // corrupt account state
+        let mut corrupt_account = account_account.clone();
+        corrupt_account.data[ACCOUNT_STATE_OFFSET] = 42;
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut corrupt_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_revoke() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create accounts
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // mint to account
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // revoke without a delegate is a no-op
+        do_process_instruction(
+            revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // approve delegate
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // partial transfer by delegate
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 40).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate_key));
+        assert_eq!(account.delegated_amount, 60);
+
+        // a signer that is neither owner nor delegate cannot revoke
+        let stranger_key = Pubkey::new_unique();
+        let mut stranger_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                revoke(&program_id, &account_key, &stranger_key, &[]).unwrap(),
+                vec![&mut account_account, &mut stranger_account],
+            )
+        );
+
+        // owner revokes
+        do_process_instruction(
+            revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+        assert_eq!(account.amount, 960);
+
+        // revoked delegate can no longer transfer
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &delegate_key, &[], 10).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+
+        // the delegate may renounce its own delegation
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            revoke(&program_id, &account_key, &delegate_key, &[]).unwrap(),
+            vec![&mut account_account, &mut delegate_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+
+        // but a replaced delegate no longer can
+        let delegate2_key = Pubkey::new_unique();
+        let mut delegate2_account = SolanaAccount::default();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate2_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                revoke(&program_id, &account_key, &delegate_key, &[]).unwrap(),
+                vec![&mut account_account, &mut delegate_account],
+            )
+        );
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate2_key));
+        assert_eq!(account.delegated_amount, 50);
+    }
+
+    #[test]
+    fn test_set_authority() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create accounts
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // wrong mint authority
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&owner2_key),
+                    AuthorityType::MintTokens,
+                    &owner2_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner2_account],
+            )
+        );
+
+        // account owner type on a mint
+        assert_eq!(
+            Err(TokenError::AuthorityTypeNotSupported.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&owner2_key),
+                    AuthorityType::AccountOwner,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+
+        // rotate mint authority
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                Some(&owner2_key),
+                AuthorityType::MintTokens,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 42).unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner2_key, &[], 42).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner2_account],
+        )
+        .unwrap();
+
+        // renounce mint authority
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                None,
+                AuthorityType::MintTokens,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut owner2_account],
+        )
+        .unwrap();
+        let mint = Mint::unpack_unchecked(&mint_account.data).unwrap();
+        assert_eq!(mint.mint_authority, COption::None);
+        assert_eq!(mint.supply, 42);
+        assert_eq!(
+            Err(TokenError::FixedSupply.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner2_key, &[], 42).unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner2_account],
+            )
+        );
+
+        // renounced authority cannot be restored
+        assert_eq!(
+            Err(TokenError::FixedSupply.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&owner2_key),
+                    AuthorityType::MintTokens,
+                    &owner2_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner2_account],
+            )
+        );
+
+        // mint authority type on an account
+        assert_eq!(
+            Err(TokenError::AuthorityTypeNotSupported.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &account_key,
+                    Some(&owner2_key),
+                    AuthorityType::MintTokens,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        // account owner cannot be removed
+        assert_eq!(
+            Err(TokenError::InvalidInstruction.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &account_key,
+                    None,
+                    AuthorityType::AccountOwner,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut owner_account],
+            )
+        );
+
+        // hand account ownership over, clearing the delegate
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 10).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&owner2_key),
+                AuthorityType::AccountOwner,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner2_key);
+        assert_eq!(account.delegate, COption::None);
+        assert_eq!(account.delegated_amount, 0);
+
+        // previous owner can no longer transfer
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner2_key, &[], 1).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_freeze_thaw_account() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let freezer_key = Pubkey::new_unique();
+        let mut freezer_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mints, only the first one can freeze
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&freezer_key), 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create accounts
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // thawing an account that is not frozen
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                thaw_account(&program_id, &account_key, &mint_key, &freezer_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut freezer_account],
+            )
+        );
+
+        // wrong mint
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint2_key, &freezer_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint2_account, &mut freezer_account],
+            )
+        );
+
+        // wrong freeze authority
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // freeze
+        do_process_instruction(
+            freeze_account(&program_id, &account_key, &mint_key, &freezer_key, &[]).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut freezer_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.state, AccountState::Frozen);
+
+        // frozen account cannot send
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // frozen account cannot receive
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account2_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account2_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 100).unwrap(),
+                vec![
+                    &mut account2_account,
+                    &mut account_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner_account],
+            )
+        );
+
+        // frozen account cannot burn or approve
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 100).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(
+                approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut delegate_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // freezing twice
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &freezer_key, &[]).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut freezer_account],
+            )
+        );
+
+        // thaw restores normal behavior
+        do_process_instruction(
+            thaw_account(&program_id, &account_key, &mint_key, &freezer_key, &[]).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut freezer_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.state, AccountState::Initialized);
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 50).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut account_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.amount, 950);
+
+        // mint without a freeze authority
+        let account3_key = Pubkey::new_unique();
+        let mut account3_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account3_key, &mint2_key, &owner_key).unwrap(),
+            vec![
+                &mut account3_account,
+                &mut mint2_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                freeze_account(&program_id, &account3_key, &mint2_key, &owner_key, &[]).unwrap(),
+                vec![&mut account3_account, &mut mint2_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_transfer_checked() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mints
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create accounts
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // wrong decimals
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    100,
+                    6,)
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // wrong mint account
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint2_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    100,
+                    2,)
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint2_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // owner transfer
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                100,
+                2,)
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // delegate transfer
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &account2_key,
+                &delegate_key,
+                &[],
+                60,
+                2,)
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut account2_account,
+                &mut delegate_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::InsufficientDelegatedFunds.into()),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &delegate_key,
+                    &[],
+                    60,
+                    2,)
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut account2_account,
+                    &mut delegate_account,
+                ],
+            )
+        );
+
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.amount, 840);
+        assert_eq!(account.delegated_amount, 40);
+        let account2 = Account::unpack_unchecked(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 160);
+    }
+
+    #[test]
+    fn test_approve_checked() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let delegate2_key = Pubkey::new_unique();
+        let mut delegate2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mint2_key = Pubkey::new_unique();
+        let mut mint2_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mints
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_mint(&program_id, &mint2_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint2_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create account
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // approve delegate
+        do_process_instruction(
+            approve_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &delegate_key,
+                &owner_key,
+                &[],
+                100,
+                2,)
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+
+        // wrong decimals leaves the existing delegation untouched
+        let before = account_account.data.clone();
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                approve_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &delegate2_key,
+                    &owner_key,
+                    &[],
+                    50,
+                    6,)
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut delegate2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+        assert_eq!(account_account.data, before);
+
+        // wrong mint
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(
+                approve_checked(
+                    &program_id,
+                    &account_key,
+                    &mint2_key,
+                    &delegate2_key,
+                    &owner_key,
+                    &[],
+                    50,
+                    2,)
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint2_account,
+                    &mut delegate2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // approving a different delegate overwrites the previous one
+        do_process_instruction(
+            approve_checked(
+                &program_id,
+                &account_key,
+                &mint_key,
+                &delegate2_key,
+                &owner_key,
+                &[],
+                50,
+                2,)
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut delegate2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegate, COption::Some(delegate2_key));
+        assert_eq!(account.delegated_amount, 50);
+    }
+
+    #[test]
+    fn test_burn_checked() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let delegate_key = Pubkey::new_unique();
+        let mut delegate_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint and account
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // owner burn with wrong decimals
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 10, 3).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // owner burn
+        do_process_instruction(
+            burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 10, 2).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // delegate burn with wrong decimals leaves everything untouched
+        do_process_instruction(
+            approve(&program_id, &account_key, &delegate_key, &owner_key, &[], 100).unwrap(),
+            vec![
+                &mut account_account,
+                &mut delegate_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let account_before = account_account.data.clone();
+        let mint_before = mint_account.data.clone();
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &delegate_key, &[], 40, 9).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut delegate_account],
+            )
+        );
+        assert_eq!(account_account.data, account_before);
+        assert_eq!(mint_account.data, mint_before);
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.delegated_amount, 100);
+
+        // delegate burn
+        do_process_instruction(
+            burn_checked(&program_id, &account_key, &mint_key, &delegate_key, &[], 40, 2).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut delegate_account],
+        )
+        .unwrap();
+        let account = Account::unpack_unchecked(&account_account.data).unwrap();
+        assert_eq!(account.amount, 950);
+        assert_eq!(account.delegated_amount, 60);
+        let mint = Mint::unpack_unchecked(&mint_account.data).unwrap();
+        assert_eq!(mint.supply, 950);
+    }
+
+    #[test]
+    fn test_pack_unpack_multisig() {
+        let mut check = Multisig {
+            m: 2,
+            n: 3,
             is_initialized: true,
+            ..Multisig::default()
         };
-        let mut packed = vec![0; Mint::get_packed_len() + 1];
+        check.signers[0] = Pubkey::new_from_array([1; 32]);
+        check.signers[2] = Pubkey::new_from_array([3; 32]);
+        let mut packed = vec![0; Multisig::get_packed_len()];
+        Multisig::pack(check, &mut packed).unwrap();
+        assert_eq!(&packed[..3], &[2, 3, 1]);
+        assert_eq!(&packed[3..35], &[1; 32]);
+        assert_eq!(&packed[35..67], &[0; 32]);
+        assert_eq!(&packed[67..99], &[3; 32]);
+        let unpacked = Multisig::unpack(&packed).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn test_initialize_multisig() {
+        let program_id = crate::id();
+        let multisig_key = Pubkey::new_unique();
+        let mut multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        let signer_keys = vec![Pubkey::new_unique(); MAX_SIGNERS + 1];
+        let signer_key_refs: Vec<&Pubkey> = signer_keys.iter().collect();
+        let mut signer_accounts = vec![SolanaAccount::default(); MAX_SIGNERS + 1];
+        let mut rent_sysvar = rent_sysvar();
+
+        // not rent exempt
+        let mut poor_multisig_account = SolanaAccount::new(
+            multisig_minimum_balance() - 1,
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        {
+            let mut accounts = vec![&mut poor_multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            assert_eq!(
+                Err(TokenError::NotRentExempt.into()),
+                do_process_instruction(
+                    initialize_multisig(&program_id, &multisig_key, &signer_key_refs[..2], 1)
+                        .unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        // too many signers
+        {
+            let mut accounts = vec![&mut multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut());
+            assert_eq!(
+                Err(TokenError::InvalidNumberOfProvidedSigners.into()),
+                do_process_instruction(
+                    initialize_multisig(&program_id, &multisig_key, &signer_key_refs, 1).unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        // m out of range
+        for m in [0, 3] {
+            let mut accounts = vec![&mut multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            assert_eq!(
+                Err(TokenError::InvalidNumberOfRequiredSigners.into()),
+                do_process_instruction(
+                    initialize_multisig(&program_id, &multisig_key, &signer_key_refs[..2], m)
+                        .unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        // success
+        {
+            let mut accounts = vec![&mut multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            do_process_instruction(
+                initialize_multisig(&program_id, &multisig_key, &signer_key_refs[..2], 2).unwrap(),
+                accounts,
+            )
+            .unwrap();
+        }
+        let multisig = Multisig::unpack(&multisig_account.data).unwrap();
+        assert_eq!(multisig.m, 2);
+        assert_eq!(multisig.n, 2);
+
+        // already initialized
+        {
+            let mut accounts = vec![&mut multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            assert_eq!(
+                Err(TokenError::AlreadyInUse.into()),
+                do_process_instruction(
+                    initialize_multisig(&program_id, &multisig_key, &signer_key_refs[..2], 2)
+                        .unwrap(),
+                    accounts,
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_multisig_authorities() {
+        let program_id = crate::id();
+        let mut rent_sysvar = rent_sysvar();
+        let signer_keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let signer_key_refs: Vec<&Pubkey> = signer_keys.iter().collect();
+        let mut signer_accounts = vec![SolanaAccount::default(); 3];
+
+        // 1-of-2 multisig as mint authority
+        let mint_multisig_key = Pubkey::new_unique();
+        let mut mint_multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        {
+            let mut accounts = vec![&mut mint_multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            do_process_instruction(
+                initialize_multisig(&program_id, &mint_multisig_key, &signer_key_refs[..2], 1)
+                    .unwrap(),
+                accounts,
+            )
+            .unwrap();
+        }
+
+        // 2-of-3 multisig as account owner
+        let owner_multisig_key = Pubkey::new_unique();
+        let mut owner_multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        {
+            let mut accounts = vec![&mut owner_multisig_account, &mut rent_sysvar];
+            accounts.extend(signer_accounts.iter_mut());
+            do_process_instruction(
+                initialize_multisig(&program_id, &owner_multisig_key, &signer_key_refs, 2)
+                    .unwrap(),
+                accounts,
+            )
+            .unwrap();
+        }
+
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &mint_multisig_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_multisig_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_multisig_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_multisig_key)
+                .unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_multisig_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // 1-of-2 mint with the second signer
+        {
+            let mut accounts = vec![
+                &mut mint_account,
+                &mut account_account,
+                &mut mint_multisig_account,
+            ];
+            accounts.extend(signer_accounts.iter_mut().skip(1).take(1));
+            do_process_instruction(
+                mint_to(
+                    &program_id,
+                    &mint_key,
+                    &account_key,
+                    &mint_multisig_key,
+                    &[signer_key_refs[1]],
+                    1000,
+                )
+                .unwrap(),
+                accounts,
+            )
+            .unwrap();
+        }
+
+        // 1-of-2 rejects a signer outside the set
+        {
+            let mut accounts = vec![
+                &mut mint_account,
+                &mut account_account,
+                &mut mint_multisig_account,
+            ];
+            accounts.extend(signer_accounts.iter_mut().skip(2).take(1));
+            assert_eq!(
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    mint_to(
+                        &program_id,
+                        &mint_key,
+                        &account_key,
+                        &mint_multisig_key,
+                        &[signer_key_refs[2]],
+                        1000,
+                    )
+                    .unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        // 2-of-3 transfer with two signatures
+        {
+            let mut accounts = vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_multisig_account,
+            ];
+            accounts.extend(signer_accounts.iter_mut().take(2));
+            do_process_instruction(
+                transfer(
+                    &program_id,
+                    &account_key,
+                    &account2_key,
+                    &owner_multisig_key,
+                    &signer_key_refs[..2],
+                    100,
+                )
+                .unwrap(),
+                accounts,
+            )
+            .unwrap();
+        }
+
+        // 2-of-3 transfer with only one signature
+        {
+            let mut accounts = vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_multisig_account,
+            ];
+            accounts.extend(signer_accounts.iter_mut().take(1));
+            assert_eq!(
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    transfer(
+                        &program_id,
+                        &account_key,
+                        &account2_key,
+                        &owner_multisig_key,
+                        &signer_key_refs[..1],
+                        100,
+                    )
+                    .unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        // the same signer listed twice only counts once
+        {
+            let mut accounts = vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_multisig_account,
+            ];
+            let (first, _) = signer_accounts.split_at_mut(1);
+            let mut duplicate = first[0].clone();
+            accounts.push(&mut first[0]);
+            accounts.push(&mut duplicate);
+            assert_eq!(
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    transfer(
+                        &program_id,
+                        &account_key,
+                        &account2_key,
+                        &owner_multisig_key,
+                        &[signer_key_refs[0], signer_key_refs[0]],
+                        100,
+                    )
+                    .unwrap(),
+                    accounts,
+                )
+            );
+        }
+
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 900);
+        let account2 = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account2.amount, 100);
+    }
+
+    #[test]
+    fn test_initialize_foreign_owned_accounts() {
+        let program_id = crate::id();
+        let foreign_program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let multisig_key = Pubkey::new_unique();
+        let mut rent_sysvar = rent_sysvar();
+
+        // mint owned by another program
+        let mut foreign_mint_account = SolanaAccount::new(
+            mint_minimum_balance(),
+            Mint::get_packed_len(),
+            &foreign_program_id,
+        );
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+                vec![&mut foreign_mint_account, &mut rent_sysvar],
+            )
+        );
+
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // token account owned by another program
+        let mut foreign_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &foreign_program_id,
+        );
+        let mut owner_account = SolanaAccount::default();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    &mut foreign_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar
+                ],
+            )
+        );
+
+        // multisig owned by another program
+        let mut foreign_multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &foreign_program_id,
+        );
+        let mut signer_account = SolanaAccount::default();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                initialize_multisig(&program_id, &multisig_key, &[&owner_key], 1).unwrap(),
+                vec![
+                    &mut foreign_multisig_account,
+                    &mut rent_sysvar,
+                    &mut signer_account
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_foreign_owned_accounts() {
+        let program_id = crate::id();
+        let foreign_program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let new_account_key = Pubkey::new_unique();
+        let mut new_account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&owner_key), 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // same data as the valid accounts, but owned by another program
+        let mut foreign_mint_account = mint_account.clone();
+        foreign_mint_account.owner = foreign_program_id;
+        let mut foreign_account_account = account_account.clone();
+        foreign_account_account.owner = foreign_program_id;
+
+        // initialize account with a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                initialize_account(&program_id, &new_account_key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    &mut new_account_account,
+                    &mut foreign_mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+        );
+
+        // transfer from a foreign source
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut foreign_account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // transfer to a foreign destination
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                transfer(&program_id, &account2_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut account2_account,
+                    &mut foreign_account_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // transfer checked against a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                transfer_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut foreign_mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // approve on a foreign source
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                approve(&program_id, &account_key, &account2_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut foreign_account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // approve checked against a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                approve_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    2,
+                )
+                .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut foreign_mint_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // revoke on a foreign source
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                revoke(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut foreign_account_account, &mut owner_account],
+            )
+        );
+
+        // set authority on a foreign account
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &account_key,
+                    Some(&account2_key),
+                    AuthorityType::AccountOwner,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut foreign_account_account, &mut owner_account],
+            )
+        );
+
+        // set authority on a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    None,
+                    AuthorityType::MintTokens,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut foreign_mint_account, &mut owner_account],
+            )
+        );
+
+        // mint_to from a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut foreign_mint_account,
+                    &mut account_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // mint_to a foreign destination
         assert_eq!(
-            Err(ProgramError::InvalidAccountData),
-            Mint::pack(mint, &mut packed)
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut mint_account,
+                    &mut foreign_account_account,
+                    &mut owner_account,
+                ],
+            )
         );
-        let mut packed = vec![0; Mint::get_packed_len() - 1];
+
+        // burn from a foreign account
         assert_eq!(
-            Err(ProgramError::InvalidAccountData),
-            Mint::pack(mint, &mut packed)
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+                vec![
+                    &mut foreign_account_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
         );
-        let mut packed = vec![0; Mint::get_packed_len()];
-        Mint::pack(mint, &mut packed).unwrap();
-        let expect = vec![
-            1, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-            1, 1, 1, 1, 1, 1, 1, 42, 0, 0, 0, 0, 0, 0, 0, 7, 1,
-        ];
-        assert_eq!(packed, expect);
-        let unpacked = Mint::unpack(&packed).unwrap();
-        assert_eq!(unpacked, mint);
+
+        // burn checked against a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 1, 2)
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut foreign_mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // freeze a foreign account
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &owner_key, &[]).unwrap(),
+                vec![
+                    &mut foreign_account_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // freeze against a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                freeze_account(&program_id, &account_key, &mint_key, &owner_key, &[]).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut foreign_mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // thaw a foreign account
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                thaw_account(&program_id, &account_key, &mint_key, &owner_key, &[]).unwrap(),
+                vec![
+                    &mut foreign_account_account,
+                    &mut mint_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // sync a foreign account
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                sync_native(&program_id, &account_key).unwrap(),
+                vec![&mut foreign_account_account],
+            )
+        );
+
+        // read-only queries against a foreign mint
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                get_account_data_size(&program_id, &mint_key).unwrap(),
+                vec![&mut foreign_mint_account],
+            )
+        );
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                amount_to_ui_amount(&program_id, &mint_key, 1).unwrap(),
+                vec![&mut foreign_mint_account],
+            )
+        );
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                ui_amount_to_amount(&program_id, &mint_key, "1").unwrap(),
+                vec![&mut foreign_mint_account],
+            )
+        );
+
+        // nothing changed on the valid accounts
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 1000);
+        assert_eq!(account.state, AccountState::Initialized);
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.supply, 1000);
     }
 
     #[test]
-    fn test_pack_unpack_account() {
-        // Account
-        let check = Account {
-            mint: Pubkey::new(&[1; 32]),
-            owner: Pubkey::new(&[2; 32]),
-            amount: 3,
-            delegate: COption::Some(Pubkey::new(&[4; 32])),
-            delegated_amount: 6,
-            state: AccountState::Initialized,
-        };
-        let mut packed = vec![0; Account::get_packed_len() + 1];
+    fn test_initialize_mint2() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account = SolanaAccount::new(42, Mint::get_packed_len(), &program_id);
+
+        // mint is not rent exempt
         assert_eq!(
-            Err(ProgramError::InvalidAccountData),
-            Account::pack(check, &mut packed)
+            Err(TokenError::NotRentExempt.into()),
+            do_process_instruction(
+                initialize_mint2(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+                vec![&mut mint_account]
+            )
         );
-        let mut packed = vec![0; Account::get_packed_len() - 1];
+
+        mint_account.lamports = mint_minimum_balance();
+
+        // create new mint with a single account
+        do_process_instruction(
+            initialize_mint2(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.mint_authority, COption::Some(owner_key));
+        assert_eq!(mint.decimals, 2);
+
+        // create twice
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_mint2(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+                vec![&mut mint_account]
+            )
+        );
+    }
+
+    #[test]
+    fn test_update_decimals() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+
+        // at most `MAX_DECIMALS`, however the mint is initialized
+        assert_eq!(
+            Err(TokenError::InvalidDecimals.into()),
+            do_process_instruction(
+                initialize_mint(&program_id, &mint_key, &owner_key, None, MAX_DECIMALS + 1)
+                    .unwrap(),
+                vec![&mut mint_account, &mut rent_sysvar],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidDecimals.into()),
+            do_process_instruction(
+                initialize_mint2(&program_id, &mint_key, &owner_key, None, u8::MAX).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, MAX_DECIMALS).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // only the mint authority corrects the decimals, within the maximum
+        let mut other_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                update_decimals(&program_id, &mint_key, &Pubkey::new_unique(), &[], 2).unwrap(),
+                vec![&mut mint_account, &mut other_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidDecimals.into()),
+            do_process_instruction(
+                update_decimals(&program_id, &mint_key, &owner_key, &[], MAX_DECIMALS + 1)
+                    .unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            update_decimals(&program_id, &mint_key, &owner_key, &[], 2).unwrap(),
+            vec![&mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().decimals, 2);
+
+        // checked instructions see the new decimals
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintDecimalsMismatch.into()),
+            do_process_instruction(
+                burn_checked(
+                    &program_id,
+                    &account_key,
+                    &mint_key,
+                    &owner_key,
+                    &[],
+                    1,
+                    MAX_DECIMALS,
+                )
+                .unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+        do_process_instruction(
+            burn_checked(&program_id, &account_key, &mint_key, &owner_key, &[], 1, 2).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // but not once tokens exist
+        assert_eq!(
+            Err(TokenError::MintHasSupply.into()),
+            do_process_instruction(
+                update_decimals(&program_id, &mint_key, &owner_key, &[], 6).unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_reject_default_authorities() {
+        let program_id = crate::id();
+        let default_key = Pubkey::default();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_sysvar = rent_sysvar();
+
+        // as the mint authority or any optional mint authority
+        for instruction in [
+            initialize_mint(&program_id, &mint_key, &default_key, None, 2),
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&default_key), 2),
+            initialize_mint_with_permanent_delegate(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &default_key,
+                2,
+            ),
+            initialize_mint_with_transfer_fee_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &default_key,
+                2,
+            ),
+            initialize_mint_with_rate_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &default_key,
+                2,
+            ),
+            initialize_mint_with_pause_authority(
+                &program_id,
+                &mint_key,
+                &owner_key,
+                None,
+                &default_key,
+                2,
+            ),
+        ] {
+            assert_eq!(
+                Err(TokenError::InvalidAuthority.into()),
+                do_process_instruction(
+                    instruction.unwrap(),
+                    vec![&mut mint_account, &mut rent_sysvar],
+                )
+            );
+        }
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&owner_key), 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // as the owner of a new account
+        let mut default_account = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::InvalidAuthority.into()),
+            do_process_instruction(
+                initialize_account(&program_id, &account_key, &mint_key, &default_key).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut default_account,
+                    &mut rent_sysvar,
+                ],
+            )
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // as a new authority, where `None` is the way to remove one
+        assert_eq!(
+            Err(TokenError::InvalidAuthority.into()),
+            do_process_instruction(
+                set_authority(
+                    &program_id,
+                    &mint_key,
+                    Some(&default_key),
+                    AuthorityType::MintTokens,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut mint_account, &mut owner_account],
+            )
+        );
+        for authority_type in [AuthorityType::AccountOwner, AuthorityType::CloseAccount] {
+            assert_eq!(
+                Err(TokenError::InvalidAuthority.into()),
+                do_process_instruction(
+                    set_authority(
+                        &program_id,
+                        &account_key,
+                        Some(&default_key),
+                        authority_type,
+                        &owner_key,
+                        &[],
+                    )
+                    .unwrap(),
+                    vec![&mut account_account, &mut owner_account],
+                )
+            );
+        }
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                None,
+                AuthorityType::MintTokens,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Mint::unpack(&mint_account.data).unwrap().mint_authority,
+            COption::None
+        );
+    }
+
+    #[test]
+    fn test_initialize_account2() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance() - 1,
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // account is not rent exempt
+        assert_eq!(
+            Err(TokenError::NotRentExempt.into()),
+            do_process_instruction(
+                initialize_account2(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut rent_sysvar],
+            )
+        );
+
+        account_account.lamports = account_minimum_balance();
+
+        // mint is not valid (not initialized)
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                initialize_account2(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut rent_sysvar],
+            )
+        );
+
+        // create mint
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create account without passing the owner account
+        do_process_instruction(
+            initialize_account2(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(account.mint, mint_key);
+        assert_eq!(account.state, AccountState::Initialized);
+
+        // create twice
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_account2(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut rent_sysvar],
+            )
+        );
+    }
+
+    #[test]
+    fn test_initialize_account3() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+
+        // mint is not initialized
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account],
+            )
+        );
+
+        // create mint
+        do_process_instruction(
+            initialize_mint2(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+
+        // create account with exactly two accounts
+        do_process_instruction(
+            initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![&mut account_account, &mut mint_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner_key);
+        assert_eq!(account.mint, mint_key);
+
+        // create twice
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_account3(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+                vec![&mut account_account, &mut mint_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_native_token() {
+        let program_id = crate::id();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance() + 40,
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+
+        // wrap: the amount is everything above the rent-exempt reserve
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &native_mint::id(), &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert!(account.is_native());
+        assert_eq!(account.is_native, COption::Some(account_minimum_balance()));
+        assert_eq!(account.amount, 40);
+
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &native_mint::id(), &owner_key)
+                .unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&account2_account.data).unwrap();
+        assert!(account.is_native());
+        assert_eq!(account.amount, 0);
+
+        // mint_to unsupported
+        assert_eq!(
+            Err(TokenError::NativeNotSupported.into()),
+            do_process_instruction(
+                mint_to(&program_id, &native_mint::id(), &account_key, &owner_key, &[], 42)
+                    .unwrap(),
+                vec![&mut mint_account, &mut account_account, &mut owner_account],
+            )
+        );
+
+        // burn unsupported
+        assert_eq!(
+            Err(TokenError::NativeNotSupported.into()),
+            do_process_instruction(
+                burn(&program_id, &account_key, &native_mint::id(), &owner_key, &[], 42).unwrap(),
+                vec![&mut account_account, &mut mint_account, &mut owner_account],
+            )
+        );
+
+        // cannot transfer more than the wrapped amount
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(
+                transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 41).unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut account2_account,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // transfer moves lamports along with the amount
+        do_process_instruction(
+            transfer(&program_id, &account_key, &account2_key, &owner_key, &[], 40).unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(account_account.lamports, account_minimum_balance());
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.amount, 0);
+        assert_eq!(account2_account.lamports, account_minimum_balance() + 40);
+        let account = Account::unpack(&account2_account.data).unwrap();
+        assert_eq!(account.amount, 40);
+
+        // a native account closes with its balance, which leaves as lamports
+        let mut dest_account = SolanaAccount::default();
+        do_process_instruction(
+            close_account(&program_id, &account2_key, &owner_key, &owner_key, &[]).unwrap(),
+            vec![&mut account2_account, &mut dest_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(account2_account.lamports, 0);
+        assert_eq!(dest_account.lamports, account_minimum_balance() + 40);
+    }
+
+    #[test]
+    fn test_close_account() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let close_authority_key = Pubkey::new_unique();
+        let mut close_authority_account = SolanaAccount::default();
+        let dest_key = Pubkey::new_unique();
+        let mut dest_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        for (key, account) in [
+            (&account_key, &mut account_account),
+            (&account2_key, &mut account2_account),
+        ] {
+            do_process_instruction(
+                initialize_account(&program_id, key, &mint_key, &owner_key).unwrap(),
+                vec![
+                    account,
+                    &mut mint_account,
+                    &mut owner_account,
+                    &mut rent_sysvar,
+                ],
+            )
+            .unwrap();
+        }
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account2_key, &owner_key, &[], 42).unwrap(),
+            vec![&mut mint_account, &mut account2_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // an account holding tokens cannot be closed
+        assert_eq!(
+            Err(TokenError::NonNativeHasBalance.into()),
+            do_process_instruction(
+                close_account(&program_id, &account2_key, &dest_key, &owner_key, &[]).unwrap(),
+                vec![&mut account2_account, &mut dest_account, &mut owner_account],
+            )
+        );
+
+        // without a close authority, nobody but the owner can close
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                close_account(&program_id, &account_key, &dest_key, &close_authority_key, &[])
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut dest_account,
+                    &mut close_authority_account,
+                ],
+            )
+        );
+
+        // the owner sets a close authority
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&close_authority_key),
+                AuthorityType::CloseAccount,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.close_authority, COption::Some(close_authority_key));
+
+        // the close authority survives a change of owner, the old owner does
+        // not keep any rights
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &account_key,
+                Some(&owner2_key),
+                AuthorityType::AccountOwner,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&account_account.data).unwrap();
+        assert_eq!(account.owner, owner2_key);
+        assert_eq!(account.close_authority, COption::Some(close_authority_key));
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                close_account(&program_id, &account_key, &dest_key, &owner_key, &[]).unwrap(),
+                vec![&mut account_account, &mut dest_account, &mut owner_account],
+            )
+        );
+
+        // the new owner can close as well
+        let mut closed_by_owner = account_account.clone();
+        do_process_instruction(
+            close_account(&program_id, &account_key, &dest_key, &owner2_key, &[]).unwrap(),
+            vec![&mut closed_by_owner, &mut dest_account, &mut owner2_account],
+        )
+        .unwrap();
+        assert_eq!(closed_by_owner.lamports, 0);
+        dest_account.lamports = 0;
+
+        // the close authority has to sign
+        let mut instruction =
+            close_account(&program_id, &account_key, &dest_key, &close_authority_key, &[])
+                .unwrap();
+        instruction.accounts[2].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut account_account,
+                    &mut dest_account,
+                    &mut close_authority_account,
+                ],
+            )
+        );
+
+        // the destination cannot be the account itself
+        let mut same_account = account_account.clone();
         assert_eq!(
             Err(ProgramError::InvalidAccountData),
-            Account::pack(check, &mut packed)
+            do_process_instruction(
+                close_account(&program_id, &account_key, &account_key, &close_authority_key, &[])
+                    .unwrap(),
+                vec![
+                    &mut account_account,
+                    &mut same_account,
+                    &mut close_authority_account,
+                ],
+            )
         );
-        let mut packed = vec![0; Account::get_packed_len()];
-        Account::pack(check, &mut packed).unwrap();
-        let expect = vec![
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-            1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
-            2, 2, 2, 2, 2, 2, 3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 0, 0, 0, 0, 0, 0, 0, 1
+
+        // the close authority closes, moving the rent to the destination
+        do_process_instruction(
+            close_account(&program_id, &account_key, &dest_key, &close_authority_key, &[])
+                .unwrap(),
+            vec![
+                &mut account_account,
+                &mut dest_account,
+                &mut close_authority_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(account_account.lamports, 0);
+        assert_eq!(dest_account.lamports, account_minimum_balance());
+        assert!(account_account.data.iter().all(|byte| *byte == 0));
+        assert_eq!(
+            Account::unpack_unchecked(&account_account.data).unwrap(),
+            Account::default()
+        );
+    }
+
+    #[test]
+    fn test_sync_native() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut native_mint_account = SolanaAccount::default();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let native_account_key = Pubkey::new_unique();
+        let lamports = 40;
+        let mut native_account = SolanaAccount::new(
+            account_minimum_balance() + lamports,
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+
+        // non-native account
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NonNativeNotSupported.into()),
+            do_process_instruction(
+                sync_native(&program_id, &account_key).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+
+        // native account
+        do_process_instruction(
+            initialize_account(&program_id, &native_account_key, &native_mint::id(), &owner_key)
+                .unwrap(),
+            vec![
+                &mut native_account,
+                &mut native_mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        let account = Account::unpack(&native_account.data).unwrap();
+        assert_eq!(account.amount, lamports);
+
+        // nothing changed, idempotent
+        do_process_instruction(
+            sync_native(&program_id, &native_account_key).unwrap(),
+            vec![&mut native_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&native_account.data).unwrap();
+        assert_eq!(account.amount, lamports);
+
+        // lamports added externally
+        let new_lamports = lamports + 1_000;
+        native_account.lamports += 1_000;
+        do_process_instruction(
+            sync_native(&program_id, &native_account_key).unwrap(),
+            vec![&mut native_account],
+        )
+        .unwrap();
+        let account = Account::unpack(&native_account.data).unwrap();
+        assert_eq!(account.amount, new_lamports);
+
+        // lamports below the reserve
+        native_account.lamports = account_minimum_balance() - 1;
+        assert_eq!(
+            Err(TokenError::Overflow.into()),
+            do_process_instruction(
+                sync_native(&program_id, &native_account_key).unwrap(),
+                vec![&mut native_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_account_data_size() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // uninitialized mint
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                get_account_data_size(&program_id, &mint_key).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        do_process_instruction(
+            get_account_data_size(&program_id, &mint_key).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        let (returning_program_id, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(returning_program_id, program_id);
+        assert_eq!(
+            u64::from_le_bytes(data.try_into().unwrap()),
+            Account::get_packed_len() as u64
+        );
+    }
+
+    #[test]
+    fn test_get_account_balance_and_mint_supply() {
+        let program_id = crate::id();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // uninitialized state has nothing to report
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction(
+                get_mint_supply(&program_id, &mint_key).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::UninitializedState.into()),
+            do_process_instruction(
+                get_account_balance(&program_id, &account_key).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 42).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        do_process_instruction(
+            get_account_balance(&program_id, &account_key).unwrap(),
+            vec![&mut account_account],
+        )
+        .unwrap();
+        let (returning_program_id, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(returning_program_id, program_id);
+        assert_eq!(data, 42u64.to_le_bytes());
+
+        do_process_instruction(
+            get_mint_supply(&program_id, &mint_key).unwrap(),
+            vec![&mut mint_account],
+        )
+        .unwrap();
+        let (returning_program_id, data) = solana_program::program::get_return_data().unwrap();
+        assert_eq!(returning_program_id, program_id);
+        assert_eq!(data, 42u64.to_le_bytes());
+
+        // a mint is not an account, nor the other way round
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                get_account_balance(&program_id, &mint_key).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+        assert_eq!(
+            Err(TokenError::InvalidState.into()),
+            do_process_instruction(
+                get_mint_supply(&program_id, &account_key).unwrap(),
+                vec![&mut account_account],
+            )
+        );
+    }
+
+    #[test]
+    fn test_amount_to_ui_amount() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // fail if an invalid mint is passed in
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                amount_to_ui_amount(&program_id, &mint_key, 110).unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 6).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        for (amount, expected) in [
+            (1_500_000, "1.5"),
+            (1_000_000, "1"),
+            (0, "0"),
+            (1, "0.000001"),
+            (u64::MAX, "18446744073709.551615"),
+        ] {
+            do_process_instruction(
+                amount_to_ui_amount(&program_id, &mint_key, amount).unwrap(),
+                vec![&mut mint_account],
+            )
+            .unwrap();
+            let (_, data) = solana_program::program::get_return_data().unwrap();
+            assert_eq!(std::str::from_utf8(&data).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_ui_amount_to_amount() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // fail if an invalid mint is passed in
+        assert_eq!(
+            Err(TokenError::InvalidMint.into()),
+            do_process_instruction(
+                ui_amount_to_amount(&program_id, &mint_key, "1.1").unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 6).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        for (ui_amount, expected) in [
+            ("1.5", 1_500_000),
+            ("1", 1_000_000),
+            ("0", 0),
+            ("0.000001", 1),
+            ("18446744073709.551615", u64::MAX),
+        ] {
+            do_process_instruction(
+                ui_amount_to_amount(&program_id, &mint_key, ui_amount).unwrap(),
+                vec![&mut mint_account],
+            )
+            .unwrap();
+            let (_, data) = solana_program::program::get_return_data().unwrap();
+            assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), expected);
+        }
+
+        // more fractional digits than decimals
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                ui_amount_to_amount(&program_id, &mint_key, "0.0000001").unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+
+        // overflow
+        assert_eq!(
+            Err(ProgramError::InvalidArgument),
+            do_process_instruction(
+                ui_amount_to_amount(&program_id, &mint_key, "18446744073709.551616").unwrap(),
+                vec![&mut mint_account],
+            )
+        );
+    }
+
+    /// One operation of a random run against `TokenScenario`, naming
+    /// accounts by index
+    #[derive(Clone, Debug)]
+    enum SupplyStep {
+        MintTo { to: usize, amount: u64 },
+        Transfer { from: usize, to: usize, amount: u64, by_delegate: bool },
+        Burn { from: usize, amount: u64, by_delegate: bool },
+        Approve { source: usize, amount: u64 },
+    }
+
+    fn arb_supply_step() -> impl proptest::strategy::Strategy<Value = SupplyStep> {
+        use proptest::prelude::*;
+        let amount = prop_oneof![0..2_000u64, any::<u64>()];
+        prop_oneof![
+            (0..4usize, amount.clone()).prop_map(|(to, amount)| SupplyStep::MintTo { to, amount }),
+            (0..4usize, 0..4usize, amount.clone(), any::<bool>()).prop_map(
+                |(from, to, amount, by_delegate)| SupplyStep::Transfer {
+                    from,
+                    to,
+                    amount,
+                    by_delegate,
+                }
+            ),
+            (0..4usize, amount.clone(), any::<bool>()).prop_map(|(from, amount, by_delegate)| {
+                SupplyStep::Burn {
+                    from,
+                    amount,
+                    by_delegate,
+                }
+            }),
+            (0..4usize, amount).prop_map(|(source, amount)| SupplyStep::Approve { source, amount }),
+        ]
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_supply_invariant(
+            steps in proptest::collection::vec(arb_supply_step(), 1..40),
+        ) {
+            let program_id = crate::id();
+            let mut scenario = TokenScenario::new();
+            let authority = Pubkey::new_unique();
+            let delegate = Pubkey::new_unique();
+            let mint = scenario.create_mint(&authority, 2);
+            let other_mint = scenario.create_mint(&authority, 2);
+            // three accounts of one mint and a fourth of another, so some
+            // transfers cross mints and must fail
+            let owners: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+            let accounts: Vec<(Pubkey, Pubkey)> = owners
+                .iter()
+                .enumerate()
+                .map(|(index, owner)| {
+                    let mint = if index == 3 { &other_mint } else { &mint };
+                    (scenario.create_account(mint, owner), *mint)
+                })
+                .collect();
+
+            for step in steps {
+                let instruction = match step {
+                    SupplyStep::MintTo { to, amount } => mint_to(
+                        &program_id,
+                        &accounts[to].1,
+                        &accounts[to].0,
+                        &authority,
+                        &[],
+                        amount,
+                    ),
+                    SupplyStep::Transfer { from, to, amount, by_delegate } => transfer(
+                        &program_id,
+                        &accounts[from].0,
+                        &accounts[to].0,
+                        if by_delegate { &delegate } else { &owners[from] },
+                        &[],
+                        amount,
+                    ),
+                    SupplyStep::Burn { from, amount, by_delegate } => burn(
+                        &program_id,
+                        &accounts[from].0,
+                        &accounts[from].1,
+                        if by_delegate { &delegate } else { &owners[from] },
+                        &[],
+                        amount,
+                    ),
+                    SupplyStep::Approve { source, amount } => approve(
+                        &program_id,
+                        &accounts[source].0,
+                        &delegate,
+                        &owners[source],
+                        &[],
+                        amount,
+                    ),
+                }
+                .unwrap();
+                // failures are expected; `process` checks the invariant after
+                // every success and a failure changes nothing
+                let _ = scenario.process(instruction);
+            }
+            scenario.assert_supply_invariant();
+        }
+    }
+
+    /// Mostly the seed of the key owning most accounts
+    fn arb_transfer_authority() -> impl proptest::strategy::Strategy<Value = u8> {
+        use proptest::prelude::*;
+        prop_oneof![3 => Just(1u8), 1 => 2..4u8]
+    }
+
+    /// A packed account biased towards the transfers the in-place path
+    /// makes, with the occasional byte overwritten
+    fn arb_transfer_account() -> impl proptest::strategy::Strategy<Value = Vec<u8>> {
+        use proptest::prelude::*;
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let rare_key = proptest::option::weighted(0.05, (1..4u8).prop_map(key));
+        let rare_u64 = proptest::option::weighted(0.05, prop_oneof![0..10u64, Just(u64::MAX)]);
+        let rare_flag = proptest::bool::weighted(0.05);
+        let amount = prop_oneof![9 => 0..2_000u64, 1 => Just(u64::MAX)];
+        let state = prop_oneof![
+            18 => Just(AccountState::Initialized),
+            1 => Just(AccountState::Frozen),
+            1 => Just(AccountState::Uninitialized),
         ];
-        assert_eq!(packed, expect);
-        let unpacked = Account::unpack(&packed).unwrap();
-        assert_eq!(unpacked, check);
+        (
+            (
+                prop_oneof![4 => Just(1u8), 1 => Just(2u8)],
+                arb_transfer_authority(),
+                amount,
+                rare_key.clone(),
+                0..2_000u64,
+                state,
+            ),
+            (
+                rare_u64.clone(),
+                rare_key.clone(),
+                rare_u64.clone(),
+                rare_key,
+                rare_flag,
+                rare_flag,
+            ),
+            (rare_flag, rare_u64.clone(), rare_flag, rare_u64),
+            prop_oneof![
+                4 => Just(vec![]),
+                1 => proptest::collection::vec((0..Account::LEN, any::<u8>()), 1..3),
+            ],
+        )
+            .prop_map(
+                move |(
+                    (mint, owner, amount, delegate, delegated_amount, state),
+                    (is_native, close_authority, expiry_slot, pending_owner, memo_required, cpi_guard),
+                    (escrowed, lock_until, immutable_owner, limit_amount),
+                    overwrites,
+                )| {
+                    let account = Account {
+                        mint: key(mint),
+                        owner: key(owner),
+                        amount,
+                        delegate: delegate.into(),
+                        delegated_amount,
+                        state,
+                        is_native: is_native.into(),
+                        close_authority: close_authority.into(),
+                        expiry_slot: expiry_slot.into(),
+                        pending_owner: pending_owner.into(),
+                        memo_required,
+                        cpi_guard,
+                        escrowed,
+                        lock_until: lock_until.into(),
+                        immutable_owner,
+                        limit_amount: limit_amount.into(),
+                        ..Account::default()
+                    };
+                    let mut data = vec![0; Account::LEN];
+                    Account::pack(account, &mut data).unwrap();
+                    for (offset, value) in overwrites {
+                        data[offset] = value;
+                    }
+                    data
+                },
+            )
+    }
+
+    proptest::proptest! {
+        // few random inputs reach the in-place path, so run more of them
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(1024))]
+
+        #[test]
+        fn test_transfer_in_place_matches_full_path(
+            source in arb_transfer_account(),
+            dest in arb_transfer_account(),
+            authority in arb_transfer_authority(),
+            is_signer in proptest::bool::weighted(0.9),
+            amount in proptest::prop_oneof![
+                0..2_000u64,
+                proptest::prelude::Just(TRANSFER_ALL),
+                proptest::prelude::any::<u64>(),
+            ],
+        ) {
+            set_stubs();
+            let program_id = crate::id();
+            let token_account = |data: Vec<u8>| SolanaAccount {
+                lamports: account_minimum_balance(),
+                data,
+                owner: program_id,
+                ..SolanaAccount::default()
+            };
+            compare_transfer_paths(
+                &[
+                    (Pubkey::new_unique(), false, token_account(source)),
+                    (Pubkey::new_unique(), false, token_account(dest)),
+                    (
+                        Pubkey::new_from_array([authority; 32]),
+                        is_signer,
+                        SolanaAccount::default(),
+                    ),
+                ],
+                amount,
+            );
+        }
     }
 }
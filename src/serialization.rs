@@ -0,0 +1,98 @@
+//! Serde helpers that render keys as base58 strings and `COption`s as
+//! nullable values, for off-chain tooling.
+
+/// `Pubkey` as a base58 string
+pub mod pubkey {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use solana_program::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(pubkey)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Pubkey::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// `COption<Pubkey>` as a nullable base58 string
+pub mod coption_pubkey {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use solana_program::{program_option::COption, pubkey::Pubkey};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(
+        value: &COption<Pubkey>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            COption::Some(pubkey) => serializer.collect_str(pubkey),
+            COption::None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<COption<Pubkey>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => Pubkey::from_str(&s)
+                .map(COption::Some)
+                .map_err(serde::de::Error::custom),
+            None => Ok(COption::None),
+        }
+    }
+}
+
+/// `COption<u64>` as a nullable number
+pub mod coption_u64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use solana_program::program_option::COption;
+
+    pub fn serialize<S: Serializer>(
+        value: &COption<u64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            COption::Some(value) => serializer.serialize_some(value),
+            COption::None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<COption<u64>, D::Error> {
+        Ok(Option::<u64>::deserialize(deserializer)?.into())
+    }
+}
+
+/// Fixed-size array of `Pubkey`s as a list of base58 strings
+pub mod pubkey_array {
+    use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+    use solana_program::pubkey::Pubkey;
+    use std::{convert::TryInto, str::FromStr};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        pubkeys: &[Pubkey; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(N))?;
+        for pubkey in pubkeys.iter() {
+            seq.serialize_element(&pubkey.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[Pubkey; N], D::Error> {
+        let pubkeys = Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| Pubkey::from_str(s).map_err(serde::de::Error::custom))
+            .collect::<Result<Vec<_>, _>>()?;
+        pubkeys
+            .try_into()
+            .map_err(|_| serde::de::Error::invalid_length(N, &"a fixed number of keys"))
+    }
+}
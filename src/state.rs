@@ -1,23 +1,77 @@
+pub mod coption;
+pub mod pod;
+
+use std::convert::TryFrom;
 use solana_program::{
+    account_info::AccountInfo,
     program_error::ProgramError,
     program_option::COption,
     pubkey::Pubkey,
     program_pack::{IsInitialized, Pack, Sealed},
 };
+use crate::{error::TokenError, instruction::MAX_SIGNERS};
+use coption::{pack_coption_key, pack_coption_u64, unpack_coption_key, unpack_coption_u64};
 use num_enum::TryFromPrimitive;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mint {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
     pub mint_authority: COption<Pubkey>,
     pub supply: u64,
     pub decimals: u8,
     pub is_initialized: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub freeze_authority: COption<Pubkey>,
+    /// Supply the mint can never exceed, set once at initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+    pub max_supply: COption<u64>,
+    /// May transfer or burn from any account of the mint, set once at
+    /// initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub permanent_delegate: COption<Pubkey>,
+    /// State new accounts of the mint start in
+    pub default_account_state: AccountState,
+    /// Fee withheld from checked transfers of the mint's tokens
+    pub transfer_fee_config: TransferFeeConfig,
+    /// Transfer fees harvested from the mint's accounts, awaiting withdrawal
+    pub withheld_amount: u64,
+    /// Interest the mint's amounts accrue when shown as UI amounts
+    pub interest_bearing_config: InterestBearingConfig,
+    /// May pause and resume the mint, set once at initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub pause_authority: COption<Pubkey>,
+    /// Whether minting, burning and checked transfers are halted
+    pub paused: bool,
+    /// May change the transfer hook program, set once before initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub transfer_hook_authority: COption<Pubkey>,
+    /// Program invoked after every checked transfer of the mint's tokens,
+    /// which can veto the transfer by failing
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub transfer_hook_program_id: COption<Pubkey>,
+    /// Account holding the mint's `TokenMetadata`, which may be the mint
+    /// itself, set once before initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub metadata_pointer: COption<Pubkey>,
+    /// `TransferPolicy` every transfer of the mint's tokens must pass, set
+    /// once before initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub transfer_policy: COption<Pubkey>,
 }
 
 impl Sealed for Mint {}
 
+impl Mint {
+    /// Grows a `V1` or `Spl` mint to the current layout, zero-filling the
+    /// appended fields. The caller funds the larger rent-exempt reserve.
+    pub fn upgrade_in_place(mint_info: &AccountInfo) -> Result<(), ProgramError> {
+        upgrade_in_place::<Self>(mint_info)
+    }
+}
+
 impl IsInitialized for Mint {
     fn is_initialized(&self) -> bool {
         self.is_initialized
@@ -25,12 +79,44 @@ impl IsInitialized for Mint {
 }
 
 impl Pack for Mint {
-    const LEN: usize = 46;
+    const LEN: usize = 422;
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        unpack_versioned(input)
+    }
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        pack_versioned(&src, dst)
+    }
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 46];
+        if src.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, 422];
 
-        let (mint_authority, supply, decimals, is_initialized) =
-            array_refs![src, 36, 8, 1, 1];
+        let (
+            mint_authority,
+            supply,
+            decimals,
+            is_initialized,
+            freeze_authority,
+            max_supply,
+            permanent_delegate,
+            default_account_state,
+            transfer_fee_authority,
+            transfer_fee_basis_points,
+            maximum_fee,
+            withheld_amount,
+            rate_authority,
+            initialization_timestamp,
+            pre_update_average_rate,
+            last_update_timestamp,
+            current_rate,
+            pause_authority,
+            paused,
+            transfer_hook_authority,
+            transfer_hook_program_id,
+            metadata_pointer,
+            transfer_policy,
+        ) = array_refs![src, 36, 8, 1, 1, 36, 12, 36, 1, 36, 2, 8, 8, 36, 8, 2, 8, 2, 36, 1, 36, 36, 36, 36];
 
         let mint_authority = unpack_coption_key(mint_authority)?;
         let supply = u64::from_le_bytes(*supply);
@@ -40,51 +126,350 @@ impl Pack for Mint {
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
+        let freeze_authority = unpack_coption_key(freeze_authority)?;
+        let max_supply = unpack_coption_u64(max_supply)?;
+        let permanent_delegate = unpack_coption_key(permanent_delegate)?;
+        let default_account_state = AccountState::try_from_primitive(default_account_state[0])
+            .or(Err(ProgramError::InvalidAccountData))?;
+        let transfer_fee_config = TransferFeeConfig {
+            transfer_fee_authority: unpack_coption_key(transfer_fee_authority)?,
+            transfer_fee_basis_points: u16::from_le_bytes(*transfer_fee_basis_points),
+            maximum_fee: u64::from_le_bytes(*maximum_fee),
+        };
+        let interest_bearing_config = InterestBearingConfig {
+            rate_authority: unpack_coption_key(rate_authority)?,
+            initialization_timestamp: i64::from_le_bytes(*initialization_timestamp),
+            pre_update_average_rate: i16::from_le_bytes(*pre_update_average_rate),
+            last_update_timestamp: i64::from_le_bytes(*last_update_timestamp),
+            current_rate: i16::from_le_bytes(*current_rate),
+        };
+        let paused = match paused {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
 
         Ok(Mint {
             mint_authority,
             supply,
             decimals,
             is_initialized,
+            freeze_authority,
+            max_supply,
+            permanent_delegate,
+            default_account_state,
+            transfer_fee_config,
+            withheld_amount: u64::from_le_bytes(*withheld_amount),
+            interest_bearing_config,
+            pause_authority: unpack_coption_key(pause_authority)?,
+            paused,
+            transfer_hook_authority: unpack_coption_key(transfer_hook_authority)?,
+            transfer_hook_program_id: unpack_coption_key(transfer_hook_program_id)?,
+            metadata_pointer: unpack_coption_key(metadata_pointer)?,
+            transfer_policy: unpack_coption_key(transfer_policy)?,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 46];
+        let dst = array_mut_ref![dst, 0, 422];
 
         let (
             mint_authority_dst,
             supply_dst,
             decimals_dst,
             is_initialized_dst,
-        ) = mut_array_refs![dst, 36, 8, 1, 1];
+            freeze_authority_dst,
+            max_supply_dst,
+            permanent_delegate_dst,
+            default_account_state_dst,
+            transfer_fee_authority_dst,
+            transfer_fee_basis_points_dst,
+            maximum_fee_dst,
+            withheld_amount_dst,
+            rate_authority_dst,
+            initialization_timestamp_dst,
+            pre_update_average_rate_dst,
+            last_update_timestamp_dst,
+            current_rate_dst,
+            pause_authority_dst,
+            paused_dst,
+            transfer_hook_authority_dst,
+            transfer_hook_program_id_dst,
+            metadata_pointer_dst,
+            transfer_policy_dst,
+        ) = mut_array_refs![dst, 36, 8, 1, 1, 36, 12, 36, 1, 36, 2, 8, 8, 36, 8, 2, 8, 2, 36, 1, 36, 36, 36, 36];
 
         let &Mint {
             ref mint_authority,
             supply,
             decimals,
             is_initialized,
+            ref freeze_authority,
+            ref max_supply,
+            ref permanent_delegate,
+            default_account_state,
+            ref transfer_fee_config,
+            withheld_amount,
+            ref interest_bearing_config,
+            ref pause_authority,
+            paused,
+            ref transfer_hook_authority,
+            ref transfer_hook_program_id,
+            ref metadata_pointer,
+            ref transfer_policy,
         } = self;
 
         pack_coption_key(mint_authority, mint_authority_dst);
         *supply_dst = supply.to_le_bytes();
         decimals_dst[0] = decimals;
         is_initialized_dst[0] = is_initialized as u8;
+        pack_coption_key(freeze_authority, freeze_authority_dst);
+        pack_coption_u64(max_supply, max_supply_dst);
+        pack_coption_key(permanent_delegate, permanent_delegate_dst);
+        default_account_state_dst[0] = default_account_state as u8;
+        pack_coption_key(
+            &transfer_fee_config.transfer_fee_authority,
+            transfer_fee_authority_dst,
+        );
+        *transfer_fee_basis_points_dst = transfer_fee_config.transfer_fee_basis_points.to_le_bytes();
+        *maximum_fee_dst = transfer_fee_config.maximum_fee.to_le_bytes();
+        *withheld_amount_dst = withheld_amount.to_le_bytes();
+        pack_coption_key(&interest_bearing_config.rate_authority, rate_authority_dst);
+        *initialization_timestamp_dst = interest_bearing_config.initialization_timestamp.to_le_bytes();
+        *pre_update_average_rate_dst = interest_bearing_config.pre_update_average_rate.to_le_bytes();
+        *last_update_timestamp_dst = interest_bearing_config.last_update_timestamp.to_le_bytes();
+        *current_rate_dst = interest_bearing_config.current_rate.to_le_bytes();
+        pack_coption_key(pause_authority, pause_authority_dst);
+        paused_dst[0] = paused as u8;
+        pack_coption_key(transfer_hook_authority, transfer_hook_authority_dst);
+        pack_coption_key(transfer_hook_program_id, transfer_hook_program_id_dst);
+        pack_coption_key(metadata_pointer, metadata_pointer_dst);
+        pack_coption_key(transfer_policy, transfer_policy_dst);
+    }
+}
+
+/// Most decimals a mint may have, as many as a lamport has below a SOL
+pub const MAX_DECIMALS: u8 = 9;
+
+/// Largest transfer fee, as a whole transfer
+pub const MAX_FEE_BASIS_POINTS: u16 = 10_000;
+
+/// Fee withheld from checked transfers, in basis points of the amount and
+/// capped at `maximum_fee`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransferFeeConfig {
+    /// May change the fee, set once at initialization
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub transfer_fee_authority: COption<Pubkey>,
+    pub transfer_fee_basis_points: u16,
+    pub maximum_fee: u64,
+}
+
+impl TransferFeeConfig {
+    /// Fee on a transfer of `amount`, rounded up to the next whole token.
+    /// `None` if the basis points are above `MAX_FEE_BASIS_POINTS`.
+    pub fn calculate_fee(&self, amount: u64) -> Option<u64> {
+        let basis_points = self.transfer_fee_basis_points;
+        if basis_points > MAX_FEE_BASIS_POINTS {
+            return None;
+        }
+        // Cannot overflow: the product of a u64 and a u16 fits in a u128
+        let fee = (amount as u128 * basis_points as u128).div_ceil(MAX_FEE_BASIS_POINTS as u128);
+        // and the fee never exceeds the amount, so it fits back in a u64
+        Some((fee as u64).min(self.maximum_fee))
     }
 }
 
+/// Seconds in an average Gregorian year, over which interest rates apply
+pub const SECONDS_PER_YEAR: f64 = 60.0 * 60.0 * 24.0 * 365.24;
+
+/// Interest accrued on UI amounts, compounded continuously at a rate in basis
+/// points per year. The raw amounts held in accounts never change.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterestBearingConfig {
+    /// May change the rate, set once at initialization. Interest only accrues
+    /// on mints that have one.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub rate_authority: COption<Pubkey>,
+    /// Unix timestamp interest accrues from
+    pub initialization_timestamp: i64,
+    /// Average rate from initialization to the last update, weighted by time
+    pub pre_update_average_rate: i16,
+    /// Unix timestamp of the last rate change
+    pub last_update_timestamp: i64,
+    /// Rate applied since the last update
+    pub current_rate: i16,
+}
+
+impl InterestBearingConfig {
+    /// Average rate from initialization to `unix_timestamp`, weighted by the
+    /// time each rate applied
+    pub fn time_weighted_average_rate(&self, unix_timestamp: i64) -> Option<i16> {
+        let total_timespan =
+            (unix_timestamp as i128).checked_sub(self.initialization_timestamp as i128)?;
+        if total_timespan <= 0 {
+            return Some(self.current_rate);
+        }
+        let pre_update_timespan =
+            self.last_update_timestamp as i128 - self.initialization_timestamp as i128;
+        let post_update_timespan = unix_timestamp as i128 - self.last_update_timestamp as i128;
+        // Cannot overflow: each product is an i16 times a span under 2^65
+        let weighted = self.pre_update_average_rate as i128 * pre_update_timespan
+            + self.current_rate as i128 * post_update_timespan;
+        i16::try_from(weighted / total_timespan).ok()
+    }
+
+    /// Growth of an amount from initialization to `unix_timestamp`
+    fn interest_scale(&self, unix_timestamp: i64) -> f64 {
+        let exp = |rate: i16, timespan: f64| {
+            (rate as f64 * timespan / SECONDS_PER_YEAR / 10_000.0).exp()
+        };
+        let pre_update_timespan =
+            self.last_update_timestamp as f64 - self.initialization_timestamp as f64;
+        let post_update_timespan = unix_timestamp as f64 - self.last_update_timestamp as f64;
+        exp(self.pre_update_average_rate, pre_update_timespan)
+            * exp(self.current_rate, post_update_timespan)
+    }
+
+    /// UI amount of `amount` with the interest accrued by `unix_timestamp`
+    pub fn amount_to_ui_amount(&self, amount: u64, decimals: u8, unix_timestamp: i64) -> String {
+        let ui_amount = amount as f64 * self.interest_scale(unix_timestamp);
+        (ui_amount / 10_f64.powi(decimals as i32)).to_string()
+    }
+
+    /// Raw amount whose UI amount at `unix_timestamp` is `ui_amount`, rounded
+    /// to the nearest token
+    pub fn try_ui_amount_into_amount(
+        &self,
+        ui_amount: &str,
+        decimals: u8,
+        unix_timestamp: i64,
+    ) -> Result<u64, ProgramError> {
+        let ui_amount = ui_amount
+            .parse::<f64>()
+            .map_err(|_| ProgramError::InvalidArgument)?;
+        let amount = (ui_amount * 10_f64.powi(decimals as i32) / self.interest_scale(unix_timestamp))
+            .round();
+        // `u64::MAX as f64` rounds up to 2^64, which is already out of range.
+        // NaN fails the range check too.
+        if !(0.0..u64::MAX as f64).contains(&amount) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(amount as u64)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Account {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
     pub mint: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
     pub owner: Pubkey,
     pub amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
     pub delegate: COption<Pubkey>,
     pub delegated_amount: u64,
     pub state: AccountState,
+    /// For wrapped SOL accounts, the rent-exempt reserve that is not part of
+    /// the token amount
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+    pub is_native: COption<u64>,
+    /// May close the account in addition to the owner
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub close_authority: COption<Pubkey>,
+    /// Last slot at which the delegate may still spend. Stored after the
+    /// other fields so existing offsets do not move.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+    pub expiry_slot: COption<u64>,
+    /// Proposed owner, who becomes the owner once they accept
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_pubkey"))]
+    pub pending_owner: COption<Pubkey>,
+    /// Incoming transfers must be accompanied by a memo instruction
+    pub memo_required: bool,
+    /// Transfer fees withheld from incoming transfers, not spendable by the
+    /// owner
+    pub withheld_amount: u64,
+    /// Transfers to or from the account must pass the mint. Set at
+    /// initialization when the mint has a transfer fee authority or a
+    /// transfer hook.
+    pub mint_required: bool,
+    /// The owner can never be changed. Set before the account is initialized.
+    pub immutable_owner: bool,
+    /// The owner can only spend, approve or hand over the account from a
+    /// top-level instruction, never through a cross-program invocation
+    pub cpi_guard: bool,
+    /// Nothing can be spent or approved before this slot. Cleared by the
+    /// first debit or approval after it.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+    pub lock_until: COption<u64>,
+    /// The delegated amount is reserved for the delegate, so the owner can
+    /// only spend the rest of the balance. Cleared with the delegation.
+    pub escrowed: bool,
+    /// Most the owner and its delegate together may move out of the account
+    /// per epoch, by transfer, burn, split, merge, unwrap or close
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::coption_u64"))]
+    pub limit_amount: COption<u64>,
+    /// Epoch `spent_in_window` was counted from
+    pub window_start_epoch: u64,
+    /// Amount transferred or burned since `window_start_epoch` began
+    pub spent_in_window: u64,
+    /// Tokens received through `TransferVested` and not claimed yet. Not
+    /// part of `amount`, so not spendable.
+    pub vesting_amount: u64,
+    /// Amount the vesting schedule releases in all
+    pub vesting_total: u64,
+    /// Slot the vesting schedule starts releasing from
+    pub vesting_start_slot: u64,
+    /// Slot by which the whole `vesting_total` is released
+    pub vesting_end_slot: u64,
 }
 
 impl Sealed for Account {}
 
+impl Account {
+    pub fn is_frozen(&self) -> bool {
+        self.state == AccountState::Frozen
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.is_native.is_some()
+    }
+
+    /// Part of the vesting schedule released by `slot`, rounded down: none
+    /// until the start, all of it from the end and linearly in between
+    pub fn vested_amount(&self, slot: u64) -> u64 {
+        if slot >= self.vesting_end_slot {
+            self.vesting_total
+        } else if slot <= self.vesting_start_slot {
+            0
+        } else {
+            let elapsed = (slot - self.vesting_start_slot) as u128;
+            let duration = (self.vesting_end_slot - self.vesting_start_slot) as u128;
+            (self.vesting_total as u128 * elapsed / duration) as u64
+        }
+    }
+
+    /// Part of `vesting_amount` released by `slot`
+    pub fn claimable_vested_amount(&self, slot: u64) -> u64 {
+        let claimed = self.vesting_total.saturating_sub(self.vesting_amount);
+        self.vested_amount(slot)
+            .saturating_sub(claimed)
+            .min(self.vesting_amount)
+    }
+
+    /// Grows a `V1` or `Spl` account to the current layout, moving the fields
+    /// SPL Token keeps in another order and zero-filling the appended ones.
+    /// The caller funds the larger rent-exempt reserve.
+    pub fn upgrade_in_place(account_info: &AccountInfo) -> Result<(), ProgramError> {
+        upgrade_in_place::<Self>(account_info)
+    }
+}
+
 impl IsInitialized for Account {
     fn is_initialized(&self) -> bool {
         self.state != AccountState::Uninitialized
@@ -92,83 +477,1942 @@ impl IsInitialized for Account {
 }
 
 impl Pack for Account {
-    const LEN: usize = 117;
+    const LEN: usize = 298;
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        unpack_versioned(input)
+    }
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        pack_versioned(&src, dst)
+    }
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 117];
-        
-        let (mint, owner, amount, delegate, delegated_amount, state) =
-            array_refs![src, 32, 32, 8, 36, 8, 1];
+        if src.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, 298];
+        let key_at = |offset: usize| Pubkey::new_from_array(*array_ref![src, offset, 32]);
+        let u64_at = |offset: usize| u64::from_le_bytes(*array_ref![src, offset, 8]);
+        let coption_key_at = |offset: usize| unpack_coption_key(array_ref![src, offset, 36]);
+        let coption_u64_at = |offset: usize| unpack_coption_u64(array_ref![src, offset, 12]);
+        let bool_at = |offset: usize| match src[offset] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
 
         Ok(Account {
-            mint: Pubkey::new_from_array(*mint),
-            owner: Pubkey::new_from_array(*owner),
-            amount: u64::from_le_bytes(*amount),
-            delegate: unpack_coption_key(delegate)?,
-            delegated_amount: u64::from_le_bytes(*delegated_amount),
-            state: AccountState::try_from_primitive(state[0])
+            mint: key_at(ACCOUNT_MINT_OFFSET),
+            owner: key_at(ACCOUNT_OWNER_OFFSET),
+            amount: u64_at(ACCOUNT_AMOUNT_OFFSET),
+            delegate: coption_key_at(ACCOUNT_DELEGATE_OFFSET)?,
+            state: AccountState::try_from_primitive(src[ACCOUNT_STATE_OFFSET])
                 .or(Err(ProgramError::InvalidAccountData))?,
+            is_native: coption_u64_at(ACCOUNT_IS_NATIVE_OFFSET)?,
+            delegated_amount: u64_at(ACCOUNT_DELEGATED_AMOUNT_OFFSET),
+            close_authority: coption_key_at(ACCOUNT_CLOSE_AUTHORITY_OFFSET)?,
+            expiry_slot: coption_u64_at(ACCOUNT_EXPIRY_SLOT_OFFSET)?,
+            pending_owner: coption_key_at(ACCOUNT_PENDING_OWNER_OFFSET)?,
+            memo_required: bool_at(ACCOUNT_MEMO_REQUIRED_OFFSET)?,
+            withheld_amount: u64_at(ACCOUNT_WITHHELD_AMOUNT_OFFSET),
+            mint_required: bool_at(ACCOUNT_MINT_REQUIRED_OFFSET)?,
+            immutable_owner: bool_at(ACCOUNT_IMMUTABLE_OWNER_OFFSET)?,
+            cpi_guard: bool_at(ACCOUNT_CPI_GUARD_OFFSET)?,
+            lock_until: coption_u64_at(ACCOUNT_LOCK_UNTIL_OFFSET)?,
+            escrowed: bool_at(ACCOUNT_ESCROWED_OFFSET)?,
+            limit_amount: coption_u64_at(ACCOUNT_LIMIT_AMOUNT_OFFSET)?,
+            window_start_epoch: u64_at(ACCOUNT_WINDOW_START_EPOCH_OFFSET),
+            spent_in_window: u64_at(ACCOUNT_SPENT_IN_WINDOW_OFFSET),
+            vesting_amount: u64_at(ACCOUNT_VESTING_AMOUNT_OFFSET),
+            vesting_total: u64_at(ACCOUNT_VESTING_TOTAL_OFFSET),
+            vesting_start_slot: u64_at(ACCOUNT_VESTING_START_SLOT_OFFSET),
+            vesting_end_slot: u64_at(ACCOUNT_VESTING_END_SLOT_OFFSET),
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 117];
-        let (
-            mint_dst,
-            owner_dst,
-            amount_dst,
-            delegate_dst,
-            delegated_amount_dst,
-            state_dst,
-        ) = mut_array_refs![dst, 32, 32, 8, 36, 8, 1];
-
+        let dst = array_mut_ref![dst, 0, 298];
         let &Account {
             ref mint,
             ref owner,
             amount,
             ref delegate,
-            delegated_amount,
             state,
+            ref is_native,
+            delegated_amount,
+            ref close_authority,
+            ref expiry_slot,
+            ref pending_owner,
+            memo_required,
+            withheld_amount,
+            mint_required,
+            immutable_owner,
+            cpi_guard,
+            ref lock_until,
+            escrowed,
+            ref limit_amount,
+            window_start_epoch,
+            spent_in_window,
+            vesting_amount,
+            vesting_total,
+            vesting_start_slot,
+            vesting_end_slot,
         } = self;
 
-        mint_dst.copy_from_slice(mint.as_ref());
-        owner_dst.copy_from_slice(owner.as_ref());
-        *amount_dst = amount.to_le_bytes();
-        pack_coption_key(delegate, delegate_dst);
-        state_dst[0] = state as u8;
-        *delegated_amount_dst = delegated_amount.to_le_bytes();
+        array_mut_ref![dst, ACCOUNT_MINT_OFFSET, 32].copy_from_slice(mint.as_ref());
+        array_mut_ref![dst, ACCOUNT_OWNER_OFFSET, 32].copy_from_slice(owner.as_ref());
+        *array_mut_ref![dst, ACCOUNT_AMOUNT_OFFSET, 8] = amount.to_le_bytes();
+        pack_coption_key(delegate, array_mut_ref![dst, ACCOUNT_DELEGATE_OFFSET, 36]);
+        dst[ACCOUNT_STATE_OFFSET] = state as u8;
+        pack_coption_u64(is_native, array_mut_ref![dst, ACCOUNT_IS_NATIVE_OFFSET, 12]);
+        *array_mut_ref![dst, ACCOUNT_DELEGATED_AMOUNT_OFFSET, 8] = delegated_amount.to_le_bytes();
+        pack_coption_key(
+            close_authority,
+            array_mut_ref![dst, ACCOUNT_CLOSE_AUTHORITY_OFFSET, 36],
+        );
+        pack_coption_u64(
+            expiry_slot,
+            array_mut_ref![dst, ACCOUNT_EXPIRY_SLOT_OFFSET, 12],
+        );
+        pack_coption_key(
+            pending_owner,
+            array_mut_ref![dst, ACCOUNT_PENDING_OWNER_OFFSET, 36],
+        );
+        dst[ACCOUNT_MEMO_REQUIRED_OFFSET] = memo_required as u8;
+        *array_mut_ref![dst, ACCOUNT_WITHHELD_AMOUNT_OFFSET, 8] = withheld_amount.to_le_bytes();
+        dst[ACCOUNT_MINT_REQUIRED_OFFSET] = mint_required as u8;
+        dst[ACCOUNT_IMMUTABLE_OWNER_OFFSET] = immutable_owner as u8;
+        dst[ACCOUNT_CPI_GUARD_OFFSET] = cpi_guard as u8;
+        pack_coption_u64(
+            lock_until,
+            array_mut_ref![dst, ACCOUNT_LOCK_UNTIL_OFFSET, 12],
+        );
+        dst[ACCOUNT_ESCROWED_OFFSET] = escrowed as u8;
+        pack_coption_u64(
+            limit_amount,
+            array_mut_ref![dst, ACCOUNT_LIMIT_AMOUNT_OFFSET, 12],
+        );
+        *array_mut_ref![dst, ACCOUNT_WINDOW_START_EPOCH_OFFSET, 8] =
+            window_start_epoch.to_le_bytes();
+        *array_mut_ref![dst, ACCOUNT_SPENT_IN_WINDOW_OFFSET, 8] = spent_in_window.to_le_bytes();
+        *array_mut_ref![dst, ACCOUNT_VESTING_AMOUNT_OFFSET, 8] = vesting_amount.to_le_bytes();
+        *array_mut_ref![dst, ACCOUNT_VESTING_TOTAL_OFFSET, 8] = vesting_total.to_le_bytes();
+        *array_mut_ref![dst, ACCOUNT_VESTING_START_SLOT_OFFSET, 8] =
+            vesting_start_slot.to_le_bytes();
+        *array_mut_ref![dst, ACCOUNT_VESTING_END_SLOT_OFFSET, 8] = vesting_end_slot.to_le_bytes();
+    }
+}
+
+/// Length of the `V1` mint layout: authority, supply, decimals and the
+/// initialized flag
+pub const LEGACY_MINT_LEN: usize = 46;
+/// Length of the `V1` account layout: mint, owner, amount, delegate,
+/// delegated amount and state
+pub const LEGACY_ACCOUNT_LEN: usize = 117;
+/// Length of SPL Token's mint, the `Spl` mint layout
+pub const SPL_MINT_LEN: usize = 82;
+/// Length of SPL Token's account, the `Spl` account layout
+pub const SPL_ACCOUNT_LEN: usize = 165;
+/// Offset of the `AccountState` byte in `Spl` account data
+pub const SPL_ACCOUNT_STATE_OFFSET: usize = 108;
+/// Bytes of account data whose fields SPL Token keeps in another order: the
+/// delegated amount comes after the state and the native reserve
+const SPL_REORDERED_ACCOUNT_BYTES: std::ops::Range<usize> =
+    ACCOUNT_DELEGATED_AMOUNT_OFFSET..ACCOUNT_CLOSE_AUTHORITY_OFFSET;
+
+/// Layout of packed `Mint` or `Account` data, told apart by its length.
+/// Every field since `V1` was appended, so `V1` data reads as the current
+/// layout with the appended fields unset. `Spl` data does too, once its
+/// account fields are put in this crate's order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayoutVersion {
+    /// The original layout
+    V1,
+    /// The current layout, `Pack::LEN` bytes long
+    V2,
+    /// SPL Token's layout, `SPL_MINT_LEN` or `SPL_ACCOUNT_LEN` bytes long, so
+    /// wallets and RPC parsers that only know SPL Token read the data. A mint
+    /// is the start of `V2`; an account keeps the delegated amount after the
+    /// state and the native reserve.
+    Spl,
+}
+
+impl LayoutVersion {
+    fn from_len<T: Pack>(len: usize, legacy_len: usize, spl_len: usize) -> Option<Self> {
+        if len == T::LEN {
+            Some(Self::V2)
+        } else if len == legacy_len {
+            Some(Self::V1)
+        } else if len == spl_len {
+            Some(Self::Spl)
+        } else {
+            None
+        }
+    }
+
+    /// Layout of packed `Mint` data, or `None` for any other length. `V2`
+    /// data may be followed by the mint's own `TokenMetadata`.
+    pub fn of_mint(data: &[u8]) -> Option<Self> {
+        // Longer data has to have room for at least the fixed part of the
+        // metadata, which also rules out a policy
+        if data.len() >= Mint::LEN + TokenMetadata::FIXED_LEN {
+            return Some(Self::V2);
+        }
+        Self::from_len::<Mint>(data.len(), LEGACY_MINT_LEN, SPL_MINT_LEN)
+    }
+
+    /// Layout of packed `Account` data, or `None` for any other length
+    pub fn of_account(data: &[u8]) -> Option<Self> {
+        Self::from_len::<Account>(data.len(), LEGACY_ACCOUNT_LEN, SPL_ACCOUNT_LEN)
+    }
+}
+
+/// A packed type's older and shorter layouts, which convert to the current
+/// one by zero-extending the data and moving fields that sit elsewhere
+trait Versioned: Pack {
+    fn version(data: &[u8]) -> Option<LayoutVersion>;
+
+    /// Length of data in `version`
+    fn version_len(version: LayoutVersion) -> usize;
+
+    /// Moves the fields of zero-extended data in `version` to where the
+    /// current layout has them
+    fn from_version(_version: LayoutVersion, _data: &mut [u8]) {}
+
+    /// Moves the fields of current-layout data to where `version` has them
+    fn to_version(_version: LayoutVersion, _data: &mut [u8]) {}
+}
+
+impl Versioned for Mint {
+    fn version(data: &[u8]) -> Option<LayoutVersion> {
+        LayoutVersion::of_mint(data)
+    }
+
+    fn version_len(version: LayoutVersion) -> usize {
+        match version {
+            LayoutVersion::V1 => LEGACY_MINT_LEN,
+            LayoutVersion::V2 => Self::LEN,
+            LayoutVersion::Spl => SPL_MINT_LEN,
+        }
+    }
+}
+
+impl Versioned for Account {
+    fn version(data: &[u8]) -> Option<LayoutVersion> {
+        LayoutVersion::of_account(data)
+    }
+
+    fn version_len(version: LayoutVersion) -> usize {
+        match version {
+            LayoutVersion::V1 => LEGACY_ACCOUNT_LEN,
+            LayoutVersion::V2 => Self::LEN,
+            LayoutVersion::Spl => SPL_ACCOUNT_LEN,
+        }
+    }
+
+    fn from_version(version: LayoutVersion, data: &mut [u8]) {
+        if version == LayoutVersion::Spl {
+            data[SPL_REORDERED_ACCOUNT_BYTES].rotate_right(8);
+        }
+    }
+
+    fn to_version(version: LayoutVersion, data: &mut [u8]) {
+        if version == LayoutVersion::Spl {
+            data[SPL_REORDERED_ACCOUNT_BYTES].rotate_left(8);
+        }
+    }
+}
+
+fn unpack_versioned<T: Versioned>(src: &[u8]) -> Result<T, ProgramError> {
+    match T::version(src) {
+        // `V2` mint data may run on into the mint's metadata
+        Some(LayoutVersion::V2) => T::unpack_from_slice(&src[..T::LEN]),
+        Some(version) => {
+            let mut data = vec![0; T::LEN];
+            data[..src.len()].copy_from_slice(src);
+            T::from_version(version, &mut data);
+            T::unpack_from_slice(&data)
+        }
+        None => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Writes an older layout only if every field it lacks is unset, since it
+/// has nowhere to store them. Like `V2` data, the bytes behind a `None` are
+/// left as they were.
+fn pack_versioned<T: Versioned>(src: &T, dst: &mut [u8]) -> Result<(), ProgramError> {
+    match T::version(dst) {
+        Some(LayoutVersion::V2) => src.pack_into_slice(dst),
+        Some(version) => {
+            let mut data = vec![0; T::LEN];
+            data[..dst.len()].copy_from_slice(dst);
+            T::from_version(version, &mut data);
+            src.pack_into_slice(&mut data);
+            T::to_version(version, &mut data);
+            if data[dst.len()..].iter().any(|byte| *byte != 0) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            dst.copy_from_slice(&data[..dst.len()]);
+        }
+        None => return Err(ProgramError::InvalidAccountData),
+    }
+    Ok(())
+}
+
+/// Also accepts the lengths in between, left by layouts that only ever
+/// shipped before a release and kept the `V1` field order
+fn upgrade_in_place<T: Versioned>(info: &AccountInfo) -> Result<(), ProgramError> {
+    let len = info.data_len();
+    // Data only ever grows, and never past the current layout
+    if !(T::version_len(LayoutVersion::V1)..=T::LEN).contains(&len) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if len < T::LEN {
+        let version = T::version(&info.data.borrow()).unwrap_or(LayoutVersion::V1);
+        info.realloc(T::LEN, true)?;
+        T::from_version(version, &mut info.data.borrow_mut());
+    }
+    Ok(())
+}
+
+/// Name, offset and length of each field of packed `Mint` data, in order
+pub const MINT_FIELDS: [(&str, usize, usize); 23] = [
+    ("mint_authority", 0, 36),
+    ("supply", 36, 8),
+    ("decimals", 44, 1),
+    ("is_initialized", 45, 1),
+    ("freeze_authority", 46, 36),
+    ("max_supply", 82, 12),
+    ("permanent_delegate", 94, 36),
+    ("default_account_state", 130, 1),
+    ("transfer_fee_authority", 131, 36),
+    ("transfer_fee_basis_points", 167, 2),
+    ("maximum_fee", 169, 8),
+    ("withheld_amount", 177, 8),
+    ("rate_authority", 185, 36),
+    ("initialization_timestamp", 221, 8),
+    ("pre_update_average_rate", 229, 2),
+    ("last_update_timestamp", 231, 8),
+    ("current_rate", 239, 2),
+    ("pause_authority", 241, 36),
+    ("paused", 277, 1),
+    ("transfer_hook_authority", 278, 36),
+    ("transfer_hook_program_id", 314, 36),
+    ("metadata_pointer", 350, 36),
+    ("transfer_policy", 386, 36),
+];
+
+/// Name, offset and length of each field of packed `Account` data, in order
+pub const ACCOUNT_FIELDS: [(&str, usize, usize); 24] = [
+    ("mint", ACCOUNT_MINT_OFFSET, 32),
+    ("owner", ACCOUNT_OWNER_OFFSET, 32),
+    ("amount", ACCOUNT_AMOUNT_OFFSET, 8),
+    ("delegate", ACCOUNT_DELEGATE_OFFSET, 36),
+    ("delegated_amount", ACCOUNT_DELEGATED_AMOUNT_OFFSET, 8),
+    ("state", ACCOUNT_STATE_OFFSET, 1),
+    ("is_native", ACCOUNT_IS_NATIVE_OFFSET, 12),
+    ("close_authority", ACCOUNT_CLOSE_AUTHORITY_OFFSET, 36),
+    ("expiry_slot", ACCOUNT_EXPIRY_SLOT_OFFSET, 12),
+    ("pending_owner", ACCOUNT_PENDING_OWNER_OFFSET, 36),
+    ("memo_required", ACCOUNT_MEMO_REQUIRED_OFFSET, 1),
+    ("withheld_amount", ACCOUNT_WITHHELD_AMOUNT_OFFSET, 8),
+    ("mint_required", ACCOUNT_MINT_REQUIRED_OFFSET, 1),
+    ("immutable_owner", ACCOUNT_IMMUTABLE_OWNER_OFFSET, 1),
+    ("cpi_guard", ACCOUNT_CPI_GUARD_OFFSET, 1),
+    ("lock_until", ACCOUNT_LOCK_UNTIL_OFFSET, 12),
+    ("escrowed", ACCOUNT_ESCROWED_OFFSET, 1),
+    ("limit_amount", ACCOUNT_LIMIT_AMOUNT_OFFSET, 12),
+    ("window_start_epoch", ACCOUNT_WINDOW_START_EPOCH_OFFSET, 8),
+    ("spent_in_window", ACCOUNT_SPENT_IN_WINDOW_OFFSET, 8),
+    ("vesting_amount", ACCOUNT_VESTING_AMOUNT_OFFSET, 8),
+    ("vesting_total", ACCOUNT_VESTING_TOTAL_OFFSET, 8),
+    ("vesting_start_slot", ACCOUNT_VESTING_START_SLOT_OFFSET, 8),
+    ("vesting_end_slot", ACCOUNT_VESTING_END_SLOT_OFFSET, 8),
+];
+
+// Integers are little-endian, and a `COption` is a four-byte tag before its
+// value. `Spl` data moves the delegated amount behind the native reserve.
+/// Offset of the mint in packed `Account` data
+pub const ACCOUNT_MINT_OFFSET: usize = 0;
+/// Offset of the owner in packed `Account` data
+pub const ACCOUNT_OWNER_OFFSET: usize = 32;
+/// Offset of the little-endian amount in packed `Account` data
+pub const ACCOUNT_AMOUNT_OFFSET: usize = 64;
+/// Offset of the delegate in packed `Account` data
+pub const ACCOUNT_DELEGATE_OFFSET: usize = 72;
+/// Offset of the delegated amount in packed `Account` data
+pub const ACCOUNT_DELEGATED_AMOUNT_OFFSET: usize = 108;
+/// Offset of the `AccountState` byte in packed `Account` data
+pub const ACCOUNT_STATE_OFFSET: usize = 116;
+/// Offset of the native reserve in packed `Account` data
+pub const ACCOUNT_IS_NATIVE_OFFSET: usize = 117;
+/// Offset of the close authority in packed `Account` data
+pub const ACCOUNT_CLOSE_AUTHORITY_OFFSET: usize = 129;
+/// Offset of the delegation's expiry slot in packed `Account` data
+pub const ACCOUNT_EXPIRY_SLOT_OFFSET: usize = 165;
+/// Offset of the pending owner in packed `Account` data
+pub const ACCOUNT_PENDING_OWNER_OFFSET: usize = 177;
+/// Offset of the memo-required flag in packed `Account` data
+pub const ACCOUNT_MEMO_REQUIRED_OFFSET: usize = 213;
+/// Offset of the withheld fees in packed `Account` data
+pub const ACCOUNT_WITHHELD_AMOUNT_OFFSET: usize = 214;
+/// Offset of the mint-required flag in packed `Account` data
+pub const ACCOUNT_MINT_REQUIRED_OFFSET: usize = 222;
+/// Offset of the immutable-owner flag in packed `Account` data
+pub const ACCOUNT_IMMUTABLE_OWNER_OFFSET: usize = 223;
+/// Offset of the CPI guard flag in packed `Account` data
+pub const ACCOUNT_CPI_GUARD_OFFSET: usize = 224;
+/// Offset of the lock's end slot in packed `Account` data
+pub const ACCOUNT_LOCK_UNTIL_OFFSET: usize = 225;
+/// Offset of the escrow flag in packed `Account` data
+pub const ACCOUNT_ESCROWED_OFFSET: usize = 237;
+/// Offset of the spending limit in packed `Account` data
+pub const ACCOUNT_LIMIT_AMOUNT_OFFSET: usize = 238;
+/// Offset of the spending window's start epoch in packed `Account` data
+pub const ACCOUNT_WINDOW_START_EPOCH_OFFSET: usize = 250;
+/// Offset of the amount spent in the window in packed `Account` data
+pub const ACCOUNT_SPENT_IN_WINDOW_OFFSET: usize = 258;
+/// Offset of the unclaimed vesting amount in packed `Account` data
+pub const ACCOUNT_VESTING_AMOUNT_OFFSET: usize = 266;
+/// Offset of the vesting total in packed `Account` data
+pub const ACCOUNT_VESTING_TOTAL_OFFSET: usize = 274;
+/// Offset of the vesting start slot in packed `Account` data
+pub const ACCOUNT_VESTING_START_SLOT_OFFSET: usize = 282;
+/// Offset of the vesting end slot in packed `Account` data
+pub const ACCOUNT_VESTING_END_SLOT_OFFSET: usize = 290;
+
+/// Checks that `data` has a packed `Account` length and an initialized or
+/// frozen state, without unpacking anything else
+pub fn valid_account_data(data: &[u8]) -> bool {
+    let state = match LayoutVersion::of_account(data) {
+        Some(LayoutVersion::V1) | Some(LayoutVersion::V2) => data[ACCOUNT_STATE_OFFSET],
+        Some(LayoutVersion::Spl) => data[SPL_ACCOUNT_STATE_OFFSET],
+        None => return false,
+    };
+    state == AccountState::Initialized as u8 || state == AccountState::Frozen as u8
+}
+
+pub fn unpack_account_mint(data: &[u8]) -> Option<&Pubkey> {
+    if valid_account_data(data) {
+        Some(bytemuck::from_bytes(&data[ACCOUNT_MINT_OFFSET..ACCOUNT_MINT_OFFSET + 32]))
+    } else {
+        None
+    }
+}
+
+pub fn unpack_account_owner(data: &[u8]) -> Option<&Pubkey> {
+    if valid_account_data(data) {
+        Some(bytemuck::from_bytes(&data[ACCOUNT_OWNER_OFFSET..ACCOUNT_OWNER_OFFSET + 32]))
+    } else {
+        None
+    }
+}
+
+pub fn unpack_account_amount(data: &[u8]) -> Option<u64> {
+    if valid_account_data(data) {
+        Some(u64::from_le_bytes(*array_ref![data, ACCOUNT_AMOUNT_OFFSET, 8]))
+    } else {
+        None
     }
 }
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize, borsh::BorshSchema)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum AccountState {
     Uninitialized,
     Initialized,
+    Frozen,
 }
 
+// Not derived: num_enum treats a `#[default]` variant as the fallback for
+// unknown bytes, which would decode a corrupt state as `Uninitialized`
+#[allow(clippy::derivable_impls)]
 impl Default for AccountState {
     fn default() -> Self {
         AccountState::Uninitialized
     }
 }
 
-fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
-    let (tag, body) = mut_array_refs![dst, 4, 32];
-    match src {
-        COption::Some(key) => {
-            *tag = [1, 0, 0, 0];
-            body.copy_from_slice(key.as_ref());
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Multisig {
+    pub m: u8,
+    pub n: u8,
+    pub is_initialized: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey_array"))]
+    pub signers: [Pubkey; MAX_SIGNERS],
+}
+
+impl Sealed for Multisig {}
+
+impl IsInitialized for Multisig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Multisig {
+    const LEN: usize = 355;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, 355];
+
+        let (m, n, is_initialized, signers_flat) = array_refs![src, 1, 1, 1, 32 * MAX_SIGNERS];
+
+        let mut result = Multisig {
+            m: m[0],
+            n: n[0],
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            signers: [Pubkey::new_from_array([0u8; 32]); MAX_SIGNERS],
+        };
+        for (src, dst) in signers_flat.chunks(32).zip(result.signers.iter_mut()) {
+            *dst = Pubkey::try_from(src).map_err(|_| ProgramError::InvalidAccountData)?;
+        }
+
+        Ok(result)
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 355];
+
+        let (m, n, is_initialized, signers_flat) = mut_array_refs![dst, 1, 1, 1, 32 * MAX_SIGNERS];
+
+        *m = [self.m];
+        *n = [self.n];
+        *is_initialized = [self.is_initialized as u8];
+        for (i, src) in self.signers.iter().enumerate() {
+            let dst_array = array_mut_ref![signers_flat, 32 * i, 32];
+            dst_array.copy_from_slice(src.as_ref());
+        }
+    }
+}
+
+/// Most owners a `TransferPolicy` can list
+pub const MAX_POLICY_ENTRIES: usize = 12;
+
+/// Whether a `TransferPolicy` lists the owners that may receive tokens or
+/// the owners that may not
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize, borsh::BorshSchema)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum PolicyMode {
+    Allowlist,
+    Blocklist,
+}
+
+// Not derived, as for `AccountState`
+#[allow(clippy::derivable_impls)]
+impl Default for PolicyMode {
+    fn default() -> Self {
+        PolicyMode::Allowlist
+    }
+}
+
+/// Owners a mint's tokens may or may not be transferred to, kept by the
+/// mint's freeze authority and checked by `TransferChecked` when passed in
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransferPolicy {
+    /// Mint whose transfers the policy applies to
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+    pub mint: Pubkey,
+    pub mode: PolicyMode,
+    pub is_initialized: bool,
+    /// Number of `entries` in use, from the start
+    pub len: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey_array"))]
+    pub entries: [Pubkey; MAX_POLICY_ENTRIES],
+}
+
+impl TransferPolicy {
+    /// The listed owners
+    pub fn entries(&self) -> &[Pubkey] {
+        &self.entries[..self.len as usize]
+    }
+
+    /// Whether tokens may be transferred to an account owned by `owner`
+    pub fn permits(&self, owner: &Pubkey) -> bool {
+        let listed = self.entries().contains(owner);
+        match self.mode {
+            PolicyMode::Allowlist => listed,
+            PolicyMode::Blocklist => !listed,
         }
-        COption::None => {
-            *tag = [0; 4];
+    }
+}
+
+impl Sealed for TransferPolicy {}
+
+impl IsInitialized for TransferPolicy {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for TransferPolicy {
+    const LEN: usize = 419;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, 419];
+
+        let (mint, mode, is_initialized, len, entries_flat) =
+            array_refs![src, 32, 1, 1, 1, 32 * MAX_POLICY_ENTRIES];
+        if len[0] as usize > MAX_POLICY_ENTRIES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut result = TransferPolicy {
+            mint: Pubkey::new_from_array(*mint),
+            mode: PolicyMode::try_from_primitive(mode[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            len: len[0],
+            entries: [Pubkey::new_from_array([0u8; 32]); MAX_POLICY_ENTRIES],
+        };
+        for (src, dst) in entries_flat.chunks(32).zip(result.entries.iter_mut()) {
+            *dst = Pubkey::try_from(src).map_err(|_| ProgramError::InvalidAccountData)?;
         }
+
+        Ok(result)
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 419];
+
+        let (mint, mode, is_initialized, len, entries_flat) =
+            mut_array_refs![dst, 32, 1, 1, 1, 32 * MAX_POLICY_ENTRIES];
+
+        mint.copy_from_slice(self.mint.as_ref());
+        *mode = [self.mode as u8];
+        *is_initialized = [self.is_initialized as u8];
+        *len = [self.len];
+        for (i, src) in self.entries.iter().enumerate() {
+            let dst_array = array_mut_ref![entries_flat, 32 * i, 32];
+            dst_array.copy_from_slice(src.as_ref());
+        }
+    }
+}
+
+/// Lets anyone mint a mint's tokens for the length of a transaction, on
+/// condition that `FlashBurnRepay` burns them again later in it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlashMintConfig {
+    /// Mint the config applies to
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+    pub mint: Pubkey,
+    pub is_initialized: bool,
+    /// Flat fee the repayer pays the mint authority on top of the repayment
+    pub fee: u64,
+    /// Amount flash minted and not yet repaid
+    pub outstanding: u64,
+}
+
+impl Sealed for FlashMintConfig {}
+
+impl IsInitialized for FlashMintConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
     }
 }
 
-fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
-    let (tag, body) = array_refs![src, 4, 32];
-    match *tag {
-        [0, 0, 0, 0] => Ok(COption::None),
-        [1, 0, 0, 0] => Ok(COption::Some(Pubkey::new_from_array(*body))),
-        _ => Err(ProgramError::InvalidAccountData),
+impl Pack for FlashMintConfig {
+    const LEN: usize = 49;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, 49];
+
+        let (mint, is_initialized, fee, outstanding) = array_refs![src, 32, 1, 8, 8];
+        Ok(FlashMintConfig {
+            mint: Pubkey::new_from_array(*mint),
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            fee: u64::from_le_bytes(*fee),
+            outstanding: u64::from_le_bytes(*outstanding),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 49];
+
+        let (mint, is_initialized, fee, outstanding) = mut_array_refs![dst, 32, 1, 8, 8];
+
+        mint.copy_from_slice(self.mint.as_ref());
+        *is_initialized = [self.is_initialized as u8];
+        *fee = self.fee.to_le_bytes();
+        *outstanding = self.outstanding.to_le_bytes();
+    }
+}
+
+/// Longest `TokenMetadata::name`, in bytes
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Longest `TokenMetadata::symbol`, in bytes
+pub const MAX_SYMBOL_LENGTH: usize = 10;
+/// Longest `TokenMetadata::uri`, in bytes
+pub const MAX_URI_LENGTH: usize = 200;
+
+/// Name, symbol and URI of a mint, kept in the account its `metadata_pointer`
+/// names. Packed as the update authority and mint, then each string as a
+/// little-endian `u32` byte length followed by its UTF-8 bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenMetadata {
+    /// May change the other fields
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+    pub update_authority: Pubkey,
+    /// Mint the metadata describes
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::pubkey"))]
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+impl IsInitialized for TokenMetadata {
+    fn is_initialized(&self) -> bool {
+        self.mint != Pubkey::default()
+    }
+}
+
+impl TokenMetadata {
+    /// Packed length of the authority, mint and string length prefixes
+    pub(crate) const FIXED_LEN: usize = 32 + 32 + 3 * 4;
+
+    /// Packed length of the metadata
+    pub fn packed_len(&self) -> usize {
+        Self::FIXED_LEN + self.name.len() + self.symbol.len() + self.uri.len()
+    }
+
+    /// Length of an account holding only the metadata. Padded by a zero byte
+    /// when it would equal the length of a mint, account, multisig or
+    /// transfer policy, so the account is never read as one of those.
+    pub fn account_len(&self) -> usize {
+        let len = self.packed_len();
+        if Self::offset(len).is_none() {
+            len + 1
+        } else {
+            len
+        }
+    }
+
+    /// Where the metadata starts in account data of `data_len` bytes: after
+    /// the mint when it lives in the mint itself, otherwise at the start.
+    /// `None` for the lengths of accounts that cannot hold metadata.
+    pub fn offset(data_len: usize) -> Option<usize> {
+        if [
+            LEGACY_MINT_LEN,
+            LEGACY_ACCOUNT_LEN,
+            SPL_MINT_LEN,
+            SPL_ACCOUNT_LEN,
+            Account::LEN,
+            Multisig::LEN,
+            Mint::LEN,
+            TransferPolicy::LEN,
+        ]
+        .contains(&data_len)
+        {
+            None
+        } else if data_len > Mint::LEN {
+            Some(Mint::LEN)
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Reads the metadata out of a metadata account, or out of a mint that
+    /// holds its own
+    pub fn unpack_from_account_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let offset = Self::offset(data.len()).ok_or(ProgramError::InvalidAccountData)?;
+        Self::unpack(&data[offset..])
+    }
+
+    /// Fails with `MetadataFieldTooLong` if any string is over its maximum
+    pub fn check_field_lengths(&self) -> Result<(), TokenError> {
+        if self.name.len() > MAX_NAME_LENGTH
+            || self.symbol.len() > MAX_SYMBOL_LENGTH
+            || self.uri.len() > MAX_URI_LENGTH
+        {
+            return Err(TokenError::MetadataFieldTooLong);
+        }
+        Ok(())
+    }
+
+    /// Unpacks the metadata from the start of `src`, ignoring what follows
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::FIXED_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (update_authority, rest) = src.split_at(32);
+        let (mint, rest) = rest.split_at(32);
+        let (name, rest) = unpack_string(rest, MAX_NAME_LENGTH)?;
+        let (symbol, rest) = unpack_string(rest, MAX_SYMBOL_LENGTH)?;
+        let (uri, _) = unpack_string(rest, MAX_URI_LENGTH)?;
+        Ok(Self {
+            update_authority: Pubkey::try_from(update_authority)
+                .map_err(|_| ProgramError::InvalidAccountData)?,
+            mint: Pubkey::try_from(mint).map_err(|_| ProgramError::InvalidAccountData)?,
+            name,
+            symbol,
+            uri,
+        })
+    }
+
+    /// Packs the metadata into the start of `dst`, zeroing the rest
+    pub fn pack(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        self.check_field_lengths()?;
+        if dst.len() < self.packed_len() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut offset = 0;
+        for bytes in [self.update_authority.as_ref(), self.mint.as_ref()] {
+            dst[offset..offset + 32].copy_from_slice(bytes);
+            offset += 32;
+        }
+        for string in [&self.name, &self.symbol, &self.uri] {
+            dst[offset..offset + 4].copy_from_slice(&(string.len() as u32).to_le_bytes());
+            offset += 4;
+            dst[offset..offset + string.len()].copy_from_slice(string.as_bytes());
+            offset += string.len();
+        }
+        dst[offset..].fill(0);
+        Ok(())
+    }
+}
+
+fn unpack_string(src: &[u8], max_len: usize) -> Result<(String, &[u8]), ProgramError> {
+    let len = src
+        .get(..4)
+        .and_then(|len| <[u8; 4]>::try_from(len).ok())
+        .map(u32::from_le_bytes)
+        .ok_or(ProgramError::InvalidAccountData)? as usize;
+    if len > max_len {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let bytes = src.get(4..4 + len).ok_or(ProgramError::InvalidAccountData)?;
+    let string = std::str::from_utf8(bytes).map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok((string.to_string(), &src[4 + len..]))
+}
+
+/// Borsh encodes `Mint` and `Account` exactly like `Pack`, including the 4-byte
+/// `COption` tags, so either codec can read data written by the other.
+#[cfg(feature = "borsh")]
+mod borsh_impls {
+    use super::*;
+    use borsh::{
+        io,
+        schema::{add_definition, Declaration, Definition, Fields},
+        BorshDeserialize, BorshSchema, BorshSerialize,
+    };
+    use std::collections::BTreeMap;
+
+    /// Schema of a packed `COption<Pubkey>`; the key is zeroed when the tag is 0
+    #[derive(BorshSchema)]
+    #[allow(dead_code)]
+    struct COptionPubkey {
+        tag: u32,
+        value: Pubkey,
+    }
+
+    /// Schema of a packed `COption<u64>`; the value is zeroed when the tag is 0
+    #[derive(BorshSchema)]
+    #[allow(dead_code)]
+    struct COptionU64 {
+        tag: u32,
+        value: u64,
+    }
+
+    fn serialize_packed<T: Pack, W: io::Write>(value: &T, writer: &mut W) -> io::Result<()> {
+        let mut data = vec![0; T::LEN];
+        value.pack_into_slice(&mut data);
+        writer.write_all(&data)
+    }
+
+    fn deserialize_packed<T: Pack, R: io::Read>(reader: &mut R) -> io::Result<T> {
+        let mut data = vec![0; T::LEN];
+        reader.read_exact(&mut data)?;
+        T::unpack_from_slice(&data)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+    }
+
+    impl BorshSerialize for Mint {
+        fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+            serialize_packed(self, writer)
+        }
+    }
+
+    impl BorshDeserialize for Mint {
+        fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+            deserialize_packed(reader)
+        }
+    }
+
+    impl BorshSchema for Mint {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            let fields = Fields::NamedFields(vec![
+                ("mint_authority".to_string(), COptionPubkey::declaration()),
+                ("supply".to_string(), u64::declaration()),
+                ("decimals".to_string(), u8::declaration()),
+                ("is_initialized".to_string(), bool::declaration()),
+                ("freeze_authority".to_string(), COptionPubkey::declaration()),
+                ("max_supply".to_string(), COptionU64::declaration()),
+                ("permanent_delegate".to_string(), COptionPubkey::declaration()),
+                ("default_account_state".to_string(), AccountState::declaration()),
+                ("transfer_fee_config".to_string(), TransferFeeConfig::declaration()),
+                ("withheld_amount".to_string(), u64::declaration()),
+                ("interest_bearing_config".to_string(), InterestBearingConfig::declaration()),
+                ("pause_authority".to_string(), COptionPubkey::declaration()),
+                ("paused".to_string(), bool::declaration()),
+                ("transfer_hook_authority".to_string(), COptionPubkey::declaration()),
+                ("transfer_hook_program_id".to_string(), COptionPubkey::declaration()),
+                ("metadata_pointer".to_string(), COptionPubkey::declaration()),
+                ("transfer_policy".to_string(), COptionPubkey::declaration()),
+            ]);
+            add_definition(Self::declaration(), Definition::Struct { fields }, definitions);
+            COptionPubkey::add_definitions_recursively(definitions);
+            u64::add_definitions_recursively(definitions);
+            u8::add_definitions_recursively(definitions);
+            bool::add_definitions_recursively(definitions);
+            COptionU64::add_definitions_recursively(definitions);
+            AccountState::add_definitions_recursively(definitions);
+            TransferFeeConfig::add_definitions_recursively(definitions);
+            InterestBearingConfig::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            "Mint".to_string()
+        }
+    }
+
+    impl BorshSchema for TransferFeeConfig {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            let fields = Fields::NamedFields(vec![
+                ("transfer_fee_authority".to_string(), COptionPubkey::declaration()),
+                ("transfer_fee_basis_points".to_string(), u16::declaration()),
+                ("maximum_fee".to_string(), u64::declaration()),
+            ]);
+            add_definition(Self::declaration(), Definition::Struct { fields }, definitions);
+            COptionPubkey::add_definitions_recursively(definitions);
+            u16::add_definitions_recursively(definitions);
+            u64::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            "TransferFeeConfig".to_string()
+        }
+    }
+
+    impl BorshSchema for InterestBearingConfig {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            let fields = Fields::NamedFields(vec![
+                ("rate_authority".to_string(), COptionPubkey::declaration()),
+                ("initialization_timestamp".to_string(), i64::declaration()),
+                ("pre_update_average_rate".to_string(), i16::declaration()),
+                ("last_update_timestamp".to_string(), i64::declaration()),
+                ("current_rate".to_string(), i16::declaration()),
+            ]);
+            add_definition(Self::declaration(), Definition::Struct { fields }, definitions);
+            COptionPubkey::add_definitions_recursively(definitions);
+            i64::add_definitions_recursively(definitions);
+            i16::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            "InterestBearingConfig".to_string()
+        }
+    }
+
+    impl BorshSerialize for Account {
+        fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+            serialize_packed(self, writer)
+        }
+    }
+
+    impl BorshDeserialize for Account {
+        fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+            deserialize_packed(reader)
+        }
+    }
+
+    impl BorshSchema for Account {
+        fn add_definitions_recursively(definitions: &mut BTreeMap<Declaration, Definition>) {
+            let fields = Fields::NamedFields(vec![
+                ("mint".to_string(), Pubkey::declaration()),
+                ("owner".to_string(), Pubkey::declaration()),
+                ("amount".to_string(), u64::declaration()),
+                ("delegate".to_string(), COptionPubkey::declaration()),
+                ("delegated_amount".to_string(), u64::declaration()),
+                ("state".to_string(), AccountState::declaration()),
+                ("is_native".to_string(), COptionU64::declaration()),
+                ("close_authority".to_string(), COptionPubkey::declaration()),
+                ("expiry_slot".to_string(), COptionU64::declaration()),
+                ("pending_owner".to_string(), COptionPubkey::declaration()),
+                ("memo_required".to_string(), bool::declaration()),
+                ("withheld_amount".to_string(), u64::declaration()),
+                ("mint_required".to_string(), bool::declaration()),
+                ("immutable_owner".to_string(), bool::declaration()),
+                ("cpi_guard".to_string(), bool::declaration()),
+                ("lock_until".to_string(), COptionU64::declaration()),
+                ("escrowed".to_string(), bool::declaration()),
+                ("limit_amount".to_string(), COptionU64::declaration()),
+                ("window_start_epoch".to_string(), u64::declaration()),
+                ("spent_in_window".to_string(), u64::declaration()),
+                ("vesting_amount".to_string(), u64::declaration()),
+                ("vesting_total".to_string(), u64::declaration()),
+                ("vesting_start_slot".to_string(), u64::declaration()),
+                ("vesting_end_slot".to_string(), u64::declaration()),
+            ]);
+            add_definition(Self::declaration(), Definition::Struct { fields }, definitions);
+            Pubkey::add_definitions_recursively(definitions);
+            u64::add_definitions_recursively(definitions);
+            COptionPubkey::add_definitions_recursively(definitions);
+            AccountState::add_definitions_recursively(definitions);
+            COptionU64::add_definitions_recursively(definitions);
+            bool::add_definitions_recursively(definitions);
+        }
+
+        fn declaration() -> Declaration {
+            "Account".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed_account(state: AccountState) -> (Account, Vec<u8>) {
+        let account = Account {
+            mint: Pubkey::new_from_array([1; 32]),
+            owner: Pubkey::new_from_array([2; 32]),
+            amount: 1_000_000,
+            delegate: COption::Some(Pubkey::new_from_array([3; 32])),
+            delegated_amount: 4,
+            state,
+            is_native: COption::None,
+            close_authority: COption::None,
+            expiry_slot: COption::None,
+            pending_owner: COption::Some(Pubkey::new_from_array([5; 32])),
+            memo_required: true,
+            withheld_amount: 6,
+            mint_required: true,
+            immutable_owner: true,
+            cpi_guard: true,
+            lock_until: COption::Some(7),
+            escrowed: true,
+            limit_amount: COption::Some(8),
+            window_start_epoch: 9,
+            spent_in_window: 10,
+            vesting_amount: 11,
+            vesting_total: 12,
+            vesting_start_slot: 13,
+            vesting_end_slot: 14,
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack_into_slice(&account, &mut data);
+        (account, data)
+    }
+
+    #[test]
+    fn test_calculate_fee() {
+        let config = |transfer_fee_basis_points, maximum_fee| TransferFeeConfig {
+            transfer_fee_authority: COption::None,
+            transfer_fee_basis_points,
+            maximum_fee,
+        };
+
+        // no fee
+        assert_eq!(config(0, u64::MAX).calculate_fee(u64::MAX), Some(0));
+        assert_eq!(config(100, u64::MAX).calculate_fee(0), Some(0));
+
+        // any fraction of a token rounds up
+        assert_eq!(config(1, u64::MAX).calculate_fee(1), Some(1));
+        assert_eq!(config(1, u64::MAX).calculate_fee(9_999), Some(1));
+        assert_eq!(config(1, u64::MAX).calculate_fee(10_000), Some(1));
+        assert_eq!(config(1, u64::MAX).calculate_fee(10_001), Some(2));
+        assert_eq!(config(50, u64::MAX).calculate_fee(10_000), Some(50));
+        assert_eq!(config(50, u64::MAX).calculate_fee(10_001), Some(51));
+        assert_eq!(config(9_999, u64::MAX).calculate_fee(1), Some(1));
+        assert_eq!(config(9_999, u64::MAX).calculate_fee(10_000), Some(9_999));
+        assert_eq!(
+            config(9_999, u64::MAX).calculate_fee(u64::MAX),
+            Some(u64::MAX - u64::MAX / 10_000)
+        );
+
+        // the top of the range takes the whole amount
+        assert_eq!(config(10_000, u64::MAX).calculate_fee(1), Some(1));
+        assert_eq!(config(10_000, u64::MAX).calculate_fee(u64::MAX), Some(u64::MAX));
+
+        // the cap applies after rounding
+        assert_eq!(config(100, 5).calculate_fee(1_000), Some(5));
+        assert_eq!(config(100, 10).calculate_fee(1_000), Some(10));
+        assert_eq!(config(100, 10).calculate_fee(1_001), Some(10));
+        assert_eq!(config(100, 0).calculate_fee(1_000), Some(0));
+
+        // out of range
+        assert_eq!(config(10_001, u64::MAX).calculate_fee(1), None);
+        assert_eq!(config(u16::MAX, u64::MAX).calculate_fee(0), None);
+
+        // every rate rounds up to the smallest fee covering the exact share,
+        // and never takes more than the amount
+        for basis_points in 0..=MAX_FEE_BASIS_POINTS {
+            for amount in [0, 1, 2, 9_999, 10_000, 10_001, 123_456_789, u64::MAX] {
+                let fee = config(basis_points, u64::MAX).calculate_fee(amount).unwrap();
+                let share = amount as u128 * basis_points as u128;
+                assert!(fee <= amount);
+                assert!(fee as u128 * 10_000 >= share);
+                if fee > 0 {
+                    assert!((fee as u128 - 1) * 10_000 < share);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interest_bearing_config() {
+        const YEAR: i64 = SECONDS_PER_YEAR as i64;
+        let config = |current_rate| InterestBearingConfig {
+            rate_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            initialization_timestamp: 1_000,
+            pre_update_average_rate: 0,
+            last_update_timestamp: 1_000,
+            current_rate,
+        };
+        let close = |ui_amount: String, expected: f64| {
+            let ui_amount = ui_amount.parse::<f64>().unwrap();
+            assert!(
+                (ui_amount - expected).abs() <= expected.abs() * 1e-12,
+                "{} != {}",
+                ui_amount,
+                expected
+            );
+        };
+
+        // no time passed, or a zero rate, leaves the decimal shift only
+        assert_eq!(config(500).amount_to_ui_amount(12_345, 2, 1_000), "123.45");
+        assert_eq!(config(0).amount_to_ui_amount(12_345, 2, 1_000 + YEAR), "123.45");
+        assert_eq!(config(500).try_ui_amount_into_amount("123.45", 2, 1_000), Ok(12_345));
+
+        // one year at 5% compounds continuously
+        close(
+            config(500).amount_to_ui_amount(1_000_000, 6, 1_000 + YEAR),
+            0.05f64.exp(),
+        );
+        assert_eq!(
+            config(500).try_ui_amount_into_amount(&0.05f64.exp().to_string(), 6, 1_000 + YEAR),
+            Ok(1_000_000)
+        );
+        close(
+            config(500).amount_to_ui_amount(1_000_000, 6, 1_000 + 2 * YEAR),
+            0.1f64.exp(),
+        );
+
+        // negative rates shrink the UI amount
+        close(
+            config(-500).amount_to_ui_amount(1_000_000, 6, 1_000 + YEAR),
+            (-0.05f64).exp(),
+        );
+        assert_eq!(
+            config(-500).try_ui_amount_into_amount(&(-0.05f64).exp().to_string(), 6, 1_000 + YEAR),
+            Ok(1_000_000)
+        );
+
+        // the average rate is weighted by how long each rate applied
+        let mut config = config(1_000);
+        assert_eq!(config.time_weighted_average_rate(1_000), Some(1_000));
+        assert_eq!(config.time_weighted_average_rate(1_000 + YEAR), Some(1_000));
+        // a change after one year keeps the interest already accrued
+        config.pre_update_average_rate = config.time_weighted_average_rate(1_000 + YEAR).unwrap();
+        config.last_update_timestamp = 1_000 + YEAR;
+        config.current_rate = -1_000;
+        close(config.amount_to_ui_amount(1_000_000, 6, 1_000 + YEAR), 0.1f64.exp());
+        assert_eq!(config.time_weighted_average_rate(1_000 + 3 * YEAR), Some(-333));
+        // and a second year at the opposite rate cancels it out
+        assert_eq!(config.time_weighted_average_rate(1_000 + 2 * YEAR), Some(0));
+        close(config.amount_to_ui_amount(1_000_000, 6, 1_000 + 2 * YEAR), 1.0);
+        assert_eq!(config.try_ui_amount_into_amount("1", 6, 1_000 + 2 * YEAR), Ok(1_000_000));
+
+        // rounds to the nearest token
+        assert_eq!(config.try_ui_amount_into_amount("1.0000004", 6, 1_000 + 2 * YEAR), Ok(1_000_000));
+        assert_eq!(config.try_ui_amount_into_amount("1.0000006", 6, 1_000 + 2 * YEAR), Ok(1_000_001));
+
+        // out of range
+        for ui_amount in ["", "abc", "-1", "NaN", "inf", "1e30"] {
+            assert_eq!(
+                config.try_ui_amount_into_amount(ui_amount, 6, 1_000 + 2 * YEAR),
+                Err(ProgramError::InvalidArgument),
+                "{}",
+                ui_amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_partial_unpack_matches_unpack() {
+        for state in [AccountState::Initialized, AccountState::Frozen] {
+            let (account, data) = packed_account(state);
+            let unpacked = Account::unpack(&data).unwrap();
+            assert_eq!(unpacked, account);
+            assert_eq!(unpack_account_mint(&data), Some(&unpacked.mint));
+            assert_eq!(unpack_account_owner(&data), Some(&unpacked.owner));
+            assert_eq!(unpack_account_amount(&data), Some(unpacked.amount));
+        }
+    }
+
+    #[test]
+    fn test_partial_unpack_uninitialized() {
+        let (_, data) = packed_account(AccountState::Uninitialized);
+        assert!(!valid_account_data(&data));
+        assert_eq!(unpack_account_mint(&data), None);
+        assert_eq!(unpack_account_owner(&data), None);
+        assert_eq!(unpack_account_amount(&data), None);
+
+        let zeroed = vec![0; Account::LEN];
+        assert_eq!(unpack_account_owner(&zeroed), None);
+    }
+
+    #[test]
+    fn test_partial_unpack_wrong_length() {
+        let (_, data) = packed_account(AccountState::Initialized);
+        let mut long = data.clone();
+        long.push(0);
+        for buffer in [&data[..Account::LEN - 1], &long[..], &[][..]] {
+            assert!(!valid_account_data(buffer));
+            assert_eq!(unpack_account_mint(buffer), None);
+            assert_eq!(unpack_account_owner(buffer), None);
+            assert_eq!(unpack_account_amount(buffer), None);
+        }
+    }
+
+    #[test]
+    fn test_account_v1_round_trip() {
+        let (mut account, _) = packed_account(AccountState::Initialized);
+        account.pending_owner = COption::None;
+        account.memo_required = false;
+        account.withheld_amount = 0;
+        account.mint_required = false;
+        account.immutable_owner = false;
+        account.cpi_guard = false;
+        account.lock_until = COption::None;
+        account.escrowed = false;
+        account.limit_amount = COption::None;
+        account.window_start_epoch = 0;
+        account.spent_in_window = 0;
+        account.vesting_amount = 0;
+        account.vesting_total = 0;
+        account.vesting_start_slot = 0;
+        account.vesting_end_slot = 0;
+
+        let mut v1 = vec![0; LEGACY_ACCOUNT_LEN];
+        Account::pack(account, &mut v1).unwrap();
+        assert_eq!(LayoutVersion::of_account(&v1), Some(LayoutVersion::V1));
+        assert_eq!(Account::unpack(&v1).unwrap(), account);
+        assert_eq!(unpack_account_owner(&v1), Some(&account.owner));
+
+        // The V1 bytes are a prefix of the V2 encoding of the same account
+        let mut v2 = vec![0; Account::LEN];
+        Account::pack(account, &mut v2).unwrap();
+        assert_eq!(LayoutVersion::of_account(&v2), Some(LayoutVersion::V2));
+        assert_eq!(v1[..], v2[..LEGACY_ACCOUNT_LEN]);
+        assert!(v2[LEGACY_ACCOUNT_LEN..].iter().all(|byte| *byte == 0));
+
+        // Zero-extending V1 data upgrades it without changing what it reads as
+        v1.resize(Account::LEN, 0);
+        assert_eq!(Account::unpack(&v1).unwrap(), account);
+    }
+
+    #[test]
+    fn test_account_spl_round_trip() {
+        let account = Account {
+            mint: Pubkey::new_from_array([1; 32]),
+            owner: Pubkey::new_from_array([2; 32]),
+            amount: 3,
+            delegate: COption::Some(Pubkey::new_from_array([4; 32])),
+            delegated_amount: 5,
+            state: AccountState::Frozen,
+            is_native: COption::Some(6),
+            close_authority: COption::Some(Pubkey::new_from_array([7; 32])),
+            ..Account::default()
+        };
+        let mut spl = vec![0; SPL_ACCOUNT_LEN];
+        Account::pack(account, &mut spl).unwrap();
+        assert_eq!(LayoutVersion::of_account(&spl), Some(LayoutVersion::Spl));
+        assert_eq!(Account::unpack(&spl).unwrap(), account);
+        assert_eq!(unpack_account_owner(&spl), Some(&account.owner));
+        assert_eq!(spl[SPL_ACCOUNT_STATE_OFFSET], AccountState::Frozen as u8);
+
+        // SPL Token keeps the delegated amount after the state and the native
+        // reserve, and everything else where V2 has it
+        let mut v2 = vec![0; Account::LEN];
+        Account::pack(account, &mut v2).unwrap();
+        assert_eq!(spl[..108], v2[..108]);
+        assert_eq!(spl[108], v2[ACCOUNT_STATE_OFFSET]);
+        assert_eq!(spl[109..121], v2[ACCOUNT_IS_NATIVE_OFFSET..129]);
+        assert_eq!(spl[121..129], v2[ACCOUNT_DELEGATED_AMOUNT_OFFSET..116]);
+        assert_eq!(spl[129..], v2[129..SPL_ACCOUNT_LEN]);
+        assert!(v2[SPL_ACCOUNT_LEN..].iter().all(|byte| *byte == 0));
+
+        let mut upgraded = spl.clone();
+        upgraded.resize(Account::LEN, 0);
+        Account::from_version(LayoutVersion::Spl, &mut upgraded);
+        assert_eq!(upgraded, v2);
+
+        // Fields SPL Token lacks have nowhere to go
+        let locked = Account {
+            lock_until: COption::Some(8),
+            ..account
+        };
+        let mut spl = vec![0; SPL_ACCOUNT_LEN];
+        assert_eq!(
+            Account::pack(locked, &mut spl),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert!(spl.iter().all(|byte| *byte == 0));
+
+        // As SPL Token does, clearing an option leaves its value bytes
+        Account::pack(account, &mut spl).unwrap();
+        let revoked = Account {
+            delegate: COption::None,
+            delegated_amount: 0,
+            ..account
+        };
+        Account::pack(revoked, &mut spl).unwrap();
+        assert_eq!(Account::unpack(&spl).unwrap(), revoked);
+        assert_eq!(spl[ACCOUNT_DELEGATE_OFFSET..108][..4], [0; 4]);
+        assert_eq!(spl[ACCOUNT_DELEGATE_OFFSET + 4..108], [4; 32]);
+    }
+
+    #[test]
+    fn test_account_v1_rejects_appended_fields() {
+        let (account, _) = packed_account(AccountState::Initialized);
+        let mut v1 = vec![0; LEGACY_ACCOUNT_LEN];
+        assert_eq!(
+            Account::pack(account, &mut v1),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert!(v1.iter().all(|byte| *byte == 0));
+
+        for len in [LEGACY_ACCOUNT_LEN - 1, LEGACY_ACCOUNT_LEN + 1, Account::LEN + 1] {
+            assert_eq!(LayoutVersion::of_account(&vec![0; len]), None);
+            assert_eq!(
+                Account::unpack_unchecked(&vec![0; len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+            assert_eq!(
+                Account::pack(Account::default(), &mut vec![0; len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+
+    #[test]
+    fn test_mint_v1_round_trip() {
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            ..Mint::default()
+        };
+
+        let mut v1 = vec![0; LEGACY_MINT_LEN];
+        Mint::pack(mint, &mut v1).unwrap();
+        assert_eq!(LayoutVersion::of_mint(&v1), Some(LayoutVersion::V1));
+        assert_eq!(Mint::unpack(&v1).unwrap(), mint);
+
+        let mut v2 = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut v2).unwrap();
+        assert_eq!(LayoutVersion::of_mint(&v2), Some(LayoutVersion::V2));
+        assert_eq!(v1[..], v2[..LEGACY_MINT_LEN]);
+
+        v1.resize(Mint::LEN, 0);
+        assert_eq!(Mint::unpack(&v1).unwrap(), mint);
+
+        // A freeze authority has nowhere to go in V1
+        let frozen = Mint {
+            freeze_authority: COption::Some(Pubkey::new_from_array([2; 32])),
+            ..mint
+        };
+        let mut v1 = vec![0; LEGACY_MINT_LEN];
+        assert_eq!(
+            Mint::pack(frozen, &mut v1),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Mint::unpack_unchecked(&[0; LEGACY_MINT_LEN + 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_mint_spl_round_trip() {
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            freeze_authority: COption::Some(Pubkey::new_from_array([2; 32])),
+            ..Mint::default()
+        };
+
+        // The SPL bytes are a prefix of the V2 encoding of the same mint
+        let mut spl = vec![0; SPL_MINT_LEN];
+        Mint::pack(mint, &mut spl).unwrap();
+        assert_eq!(LayoutVersion::of_mint(&spl), Some(LayoutVersion::Spl));
+        assert_eq!(Mint::unpack(&spl).unwrap(), mint);
+        let mut v2 = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut v2).unwrap();
+        assert_eq!(spl[..], v2[..SPL_MINT_LEN]);
+
+        let capped = Mint {
+            max_supply: COption::Some(100),
+            ..mint
+        };
+        assert_eq!(
+            Mint::pack(capped, &mut [0; SPL_MINT_LEN]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_transfer_policy_pack() {
+        let mut policy = TransferPolicy {
+            mint: Pubkey::new_from_array([1; 32]),
+            mode: PolicyMode::Blocklist,
+            is_initialized: true,
+            len: 1,
+            ..TransferPolicy::default()
+        };
+        policy.entries[0] = Pubkey::new_from_array([2; 32]);
+        let mut packed = vec![0; TransferPolicy::LEN];
+        TransferPolicy::pack(policy, &mut packed).unwrap();
+        assert_eq!(&packed[..35], &[&[1; 32][..], &[1, 1, 1]].concat()[..]);
+        assert_eq!(TransferPolicy::unpack(&packed).unwrap(), policy);
+        assert!(!policy.permits(&Pubkey::new_from_array([2; 32])));
+        assert!(policy.permits(&Pubkey::new_from_array([3; 32])));
+
+        // never mistaken for a mint, which with its metadata is longer
+        const { assert!(Mint::LEN + TokenMetadata::FIXED_LEN > TransferPolicy::LEN) };
+        assert_eq!(
+            Mint::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // a count past the capacity, or an unknown mode, is corrupt
+        let mut corrupt = packed.clone();
+        corrupt[34] = MAX_POLICY_ENTRIES as u8 + 1;
+        assert_eq!(
+            TransferPolicy::unpack(&corrupt),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut corrupt = packed;
+        corrupt[32] = 2;
+        assert_eq!(
+            TransferPolicy::unpack(&corrupt),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_vested_amount() {
+        let mut account = Account {
+            vesting_amount: 100,
+            vesting_total: 100,
+            vesting_start_slot: 10,
+            vesting_end_slot: 40,
+            ..Account::default()
+        };
+        for (slot, vested) in [
+            (0, 0),
+            (10, 0),
+            // rounded down
+            (11, 3),
+            (25, 50),
+            (39, 96),
+            (40, 100),
+            (1_000, 100),
+        ] {
+            assert_eq!(account.vested_amount(slot), vested, "slot {}", slot);
+            assert_eq!(account.claimable_vested_amount(slot), vested, "slot {}", slot);
+        }
+
+        // what was claimed is not claimable again
+        account.vesting_amount = 50;
+        assert_eq!(account.claimable_vested_amount(25), 0);
+        assert_eq!(account.claimable_vested_amount(31), 20);
+        assert_eq!(account.claimable_vested_amount(40), 50);
+
+        // no overflow on the largest amounts
+        account.vesting_total = u64::MAX;
+        account.vesting_amount = u64::MAX;
+        account.vesting_start_slot = 0;
+        account.vesting_end_slot = u64::MAX;
+        assert_eq!(account.vested_amount(u64::MAX - 1), u64::MAX - 1);
+        assert_eq!(account.vested_amount(u64::MAX / 2), u64::MAX / 2);
+    }
+
+    #[test]
+    fn test_flash_mint_config_pack() {
+        let config = FlashMintConfig {
+            mint: Pubkey::new_from_array([1; 32]),
+            is_initialized: true,
+            fee: 2,
+            outstanding: 3,
+        };
+        let mut packed = vec![0; FlashMintConfig::LEN];
+        FlashMintConfig::pack(config, &mut packed).unwrap();
+        let expect = [
+            &[1; 32][..],
+            &[1],
+            &[2, 0, 0, 0, 0, 0, 0, 0],
+            &[3, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(packed, expect);
+        assert_eq!(FlashMintConfig::unpack(&packed).unwrap(), config);
+
+        // shorter than any metadata, so never read as some
+        const { assert!(TokenMetadata::FIXED_LEN > FlashMintConfig::LEN) };
+
+        let mut corrupt = packed;
+        corrupt[32] = 2;
+        assert_eq!(
+            FlashMintConfig::unpack(&corrupt),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_token_metadata_pack() {
+        let metadata = TokenMetadata {
+            update_authority: Pubkey::new_from_array([1; 32]),
+            mint: Pubkey::new_from_array([2; 32]),
+            name: "ab".to_string(),
+            symbol: String::new(),
+            uri: "c".to_string(),
+        };
+        let mut expect = vec![1; 32];
+        expect.extend_from_slice(&[2; 32]);
+        expect.extend_from_slice(&[2, 0, 0, 0, b'a', b'b', 0, 0, 0, 0, 1, 0, 0, 0, b'c']);
+        assert_eq!(metadata.packed_len(), expect.len());
+        let mut packed = vec![0xff; metadata.account_len()];
+        metadata.pack(&mut packed).unwrap();
+        assert_eq!(packed, expect);
+        assert_eq!(TokenMetadata::unpack(&packed).unwrap(), metadata);
+        assert!(metadata.is_initialized());
+        assert!(!TokenMetadata::unpack(&[0; 80]).unwrap().is_initialized());
+
+        // trailing bytes are zeroed, and ignored when reading
+        let mut packed = vec![0xff; metadata.packed_len() + 2];
+        metadata.pack(&mut packed).unwrap();
+        assert_eq!(packed[metadata.packed_len()..], [0, 0]);
+        assert_eq!(TokenMetadata::unpack(&packed).unwrap(), metadata);
+        assert_eq!(
+            metadata.pack(&mut vec![0; metadata.packed_len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            TokenMetadata::unpack(&expect[..expect.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // each string has a maximum length, on both sides
+        let long = TokenMetadata {
+            symbol: "a".repeat(MAX_SYMBOL_LENGTH + 1),
+            ..metadata.clone()
+        };
+        assert_eq!(
+            long.check_field_lengths(),
+            Err(TokenError::MetadataFieldTooLong)
+        );
+        assert_eq!(
+            long.pack(&mut vec![0; long.packed_len()]),
+            Err(TokenError::MetadataFieldTooLong.into())
+        );
+        let mut packed = expect.clone();
+        packed[64..68].copy_from_slice(&(MAX_NAME_LENGTH as u32 + 1).to_le_bytes());
+        packed.resize(packed.len() + MAX_NAME_LENGTH, b'a');
+        assert_eq!(
+            TokenMetadata::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut packed = expect.clone();
+        packed[68] = 0xff;
+        assert_eq!(
+            TokenMetadata::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_token_metadata_account_data() {
+        // a standalone account is never the length of another account type
+        let metadata = TokenMetadata {
+            name: "a".repeat(LEGACY_ACCOUNT_LEN - TokenMetadata::FIXED_LEN),
+            ..TokenMetadata::default()
+        };
+        assert_eq!(metadata.packed_len(), LEGACY_ACCOUNT_LEN);
+        assert_eq!(metadata.account_len(), LEGACY_ACCOUNT_LEN + 1);
+        let metadata = TokenMetadata {
+            name: "a".repeat(MAX_NAME_LENGTH),
+            ..TokenMetadata::default()
+        };
+        assert_eq!(metadata.account_len(), metadata.packed_len());
+        for len in [
+            LEGACY_MINT_LEN,
+            LEGACY_ACCOUNT_LEN,
+            SPL_MINT_LEN,
+            SPL_ACCOUNT_LEN,
+            Account::LEN,
+            Multisig::LEN,
+            Mint::LEN,
+            TransferPolicy::LEN,
+        ] {
+            assert_eq!(TokenMetadata::offset(len), None);
+            assert_eq!(
+                TokenMetadata::unpack_from_account_data(&vec![0; len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+
+        // a mint holds its own metadata after the mint fields
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            is_initialized: true,
+            metadata_pointer: COption::Some(Pubkey::new_from_array([2; 32])),
+            ..Mint::default()
+        };
+        let metadata = TokenMetadata {
+            update_authority: Pubkey::new_from_array([1; 32]),
+            mint: Pubkey::new_from_array([2; 32]),
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            uri: "https://example.com".to_string(),
+        };
+        let mut data = vec![0; Mint::LEN + metadata.packed_len()];
+        Mint::pack(mint, &mut data).unwrap();
+        metadata.pack(&mut data[Mint::LEN..]).unwrap();
+        assert_eq!(LayoutVersion::of_mint(&data), Some(LayoutVersion::V2));
+        assert_eq!(TokenMetadata::offset(data.len()), Some(Mint::LEN));
+        assert_eq!(Mint::unpack(&data).unwrap(), mint);
+        assert_eq!(TokenMetadata::unpack_from_account_data(&data).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_partial_unpack_invalid_state() {
+        let (_, mut data) = packed_account(AccountState::Initialized);
+        data[ACCOUNT_STATE_OFFSET] = 3;
+        assert_eq!(unpack_account_owner(&data), None);
+    }
+
+    /// Name and length of each field the schema of `T` lays out, in order,
+    /// reading through the nested config structs
+    #[cfg(feature = "borsh")]
+    fn schema_fields<T: borsh::BorshSchema>() -> Vec<(String, usize)> {
+        use borsh::schema::{BorshSchemaContainer, Definition, Fields};
+
+        fn len(schema: &BorshSchemaContainer, declaration: &str) -> usize {
+            match schema.get_definition(declaration).unwrap() {
+                Definition::Primitive(size) => *size as usize,
+                Definition::Sequence {
+                    length_range,
+                    elements,
+                    ..
+                } => *length_range.end() as usize * len(schema, elements),
+                Definition::Enum {
+                    tag_width,
+                    variants,
+                } => {
+                    *tag_width as usize
+                        + variants
+                            .iter()
+                            .map(|(_, _, variant)| len(schema, variant))
+                            .max()
+                            .unwrap_or(0)
+                }
+                Definition::Tuple { elements } => {
+                    elements.iter().map(|element| len(schema, element)).sum()
+                }
+                Definition::Struct { fields } => match fields {
+                    Fields::NamedFields(fields) => {
+                        fields.iter().map(|(_, field)| len(schema, field)).sum()
+                    }
+                    Fields::UnnamedFields(fields) => {
+                        fields.iter().map(|field| len(schema, field)).sum()
+                    }
+                    Fields::Empty => 0,
+                },
+            }
+        }
+
+        fn flatten(
+            schema: &BorshSchemaContainer,
+            declaration: &str,
+            fields: &mut Vec<(String, usize)>,
+        ) {
+            let named = match schema.get_definition(declaration) {
+                Some(Definition::Struct {
+                    fields: Fields::NamedFields(named),
+                }) => named,
+                _ => unreachable!("{} is not a struct", declaration),
+            };
+            for (name, field) in named {
+                match schema.get_definition(field) {
+                    Some(Definition::Struct {
+                        fields: Fields::NamedFields(_),
+                    }) if !field.starts_with("COption") => flatten(schema, field, fields),
+                    _ => fields.push((name.clone(), len(schema, field))),
+                }
+            }
+        }
+
+        let schema = BorshSchemaContainer::for_type::<T>();
+        let mut fields = vec![];
+        flatten(&schema, schema.declaration(), &mut fields);
+        fields
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_matches_pack() {
+        use borsh::{BorshDeserialize, BorshSerialize};
+
+        let (account, data) = packed_account(AccountState::Frozen);
+        let serialized = borsh::to_vec(&account).unwrap();
+        assert_eq!(serialized, data);
+        assert_eq!(Account::try_from_slice(&data).unwrap(), account);
+        assert_eq!(borsh::max_serialized_size::<Account>().unwrap(), Account::LEN);
+
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            freeze_authority: COption::None,
+            max_supply: COption::Some(1_000),
+            permanent_delegate: COption::None,
+            default_account_state: AccountState::Frozen,
+            transfer_fee_config: TransferFeeConfig::default(),
+            withheld_amount: 0,
+            interest_bearing_config: InterestBearingConfig::default(),
+            pause_authority: COption::None,
+            paused: false,
+            transfer_hook_authority: COption::None,
+            transfer_hook_program_id: COption::None,
+            metadata_pointer: COption::None,
+            transfer_policy: COption::None,
+        };
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        let mut serialized = vec![];
+        mint.serialize(&mut serialized).unwrap();
+        assert_eq!(serialized, data);
+        assert_eq!(Mint::try_from_slice(&data).unwrap(), mint);
+        assert_eq!(borsh::max_serialized_size::<Mint>().unwrap(), Mint::LEN);
+
+        assert_eq!(borsh::to_vec(&AccountState::Frozen).unwrap(), vec![2]);
+
+        // The schemas list the packed fields in their packed order
+        let packed_fields = |fields: &[(&str, usize, usize)]| -> Vec<(String, usize)> {
+            fields
+                .iter()
+                .map(|(name, _, len)| (name.to_string(), *len))
+                .collect()
+        };
+        assert_eq!(schema_fields::<Account>(), packed_fields(&ACCOUNT_FIELDS));
+        assert_eq!(schema_fields::<Mint>(), packed_fields(&MINT_FIELDS));
+
+        // the 4-byte option tag is enforced
+        let mut bad_tag = data.clone();
+        bad_tag[0] = 2;
+        assert!(Mint::try_from_slice(&bad_tag).is_err());
+        // so is the exact length
+        assert!(Mint::try_from_slice(&data[..Mint::LEN - 1]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_snapshots() {
+        let (account, _) = packed_account(AccountState::Frozen);
+        let json = serde_json::to_string(&account).unwrap();
+        assert_eq!(
+            json,
+            r#"{"mint":"4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi","owner":"8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR","amount":1000000,"delegate":"CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8","delegated_amount":4,"state":"frozen","is_native":null,"close_authority":null,"expiry_slot":null,"pending_owner":"LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY","memo_required":true,"withheld_amount":6,"mint_required":true,"immutable_owner":true,"cpi_guard":true,"lock_until":7,"escrowed":true,"limit_amount":8,"window_start_epoch":9,"spent_in_window":10,"vesting_amount":11,"vesting_total":12,"vesting_start_slot":13,"vesting_end_slot":14}"#
+        );
+        assert_eq!(serde_json::from_str::<Account>(&json).unwrap(), account);
+
+        let native = Account {
+            is_native: COption::Some(2_039_280),
+            state: AccountState::Initialized,
+            ..account
+        };
+        let json = serde_json::to_string(&native).unwrap();
+        assert!(json.contains(r#""state":"initialized","is_native":2039280"#));
+        assert_eq!(serde_json::from_str::<Account>(&json).unwrap(), native);
+
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            freeze_authority: COption::None,
+            max_supply: COption::Some(1_000),
+            permanent_delegate: COption::None,
+            default_account_state: AccountState::Frozen,
+            transfer_fee_config: TransferFeeConfig::default(),
+            withheld_amount: 0,
+            interest_bearing_config: InterestBearingConfig::default(),
+            pause_authority: COption::None,
+            paused: false,
+            transfer_hook_authority: COption::None,
+            transfer_hook_program_id: COption::None,
+            metadata_pointer: COption::None,
+            transfer_policy: COption::None,
+        };
+        let json = serde_json::to_string(&mint).unwrap();
+        assert_eq!(
+            json,
+            r#"{"mint_authority":"4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi","supply":42,"decimals":7,"is_initialized":true,"freeze_authority":null,"max_supply":1000,"permanent_delegate":null,"default_account_state":"frozen","transfer_fee_config":{"transfer_fee_authority":null,"transfer_fee_basis_points":0,"maximum_fee":0},"withheld_amount":0,"interest_bearing_config":{"rate_authority":null,"initialization_timestamp":0,"pre_update_average_rate":0,"last_update_timestamp":0,"current_rate":0},"pause_authority":null,"paused":false,"transfer_hook_authority":null,"transfer_hook_program_id":null,"metadata_pointer":null,"transfer_policy":null}"#
+        );
+        assert_eq!(serde_json::from_str::<Mint>(&json).unwrap(), mint);
+
+        let mut multisig = Multisig {
+            m: 1,
+            n: 1,
+            is_initialized: true,
+            ..Multisig::default()
+        };
+        multisig.signers[0] = Pubkey::new_from_array([1; 32]);
+        let json = serde_json::to_string(&multisig).unwrap();
+        assert!(json.starts_with(
+            r#"{"m":1,"n":1,"is_initialized":true,"signers":["4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi","11111111111111111111111111111111","#
+        ));
+        assert_eq!(serde_json::from_str::<Multisig>(&json).unwrap(), multisig);
+
+        assert!(serde_json::from_str::<Account>(&json).is_err());
+    }
+
+    #[test]
+    fn test_field_tables() {
+        for (fields, len) in [(&MINT_FIELDS[..], Mint::LEN), (&ACCOUNT_FIELDS[..], Account::LEN)] {
+            let end = fields.iter().fold(0, |offset, &(name, field_offset, field_len)| {
+                assert_eq!(field_offset, offset, "{}", name);
+                offset + field_len
+            });
+            assert_eq!(end, len);
+        }
+        let offset_of = |name| {
+            ACCOUNT_FIELDS
+                .iter()
+                .find(|field| field.0 == name)
+                .unwrap()
+                .1
+        };
+        assert_eq!(offset_of("mint"), ACCOUNT_MINT_OFFSET);
+        assert_eq!(offset_of("owner"), ACCOUNT_OWNER_OFFSET);
+        assert_eq!(offset_of("amount"), ACCOUNT_AMOUNT_OFFSET);
+        assert_eq!(offset_of("state"), ACCOUNT_STATE_OFFSET);
+    }
+
+    #[test]
+    fn test_account_field_offsets() {
+        let (mut account, _) = packed_account(AccountState::Frozen);
+        account.is_native = COption::Some(15);
+        account.close_authority = COption::Some(Pubkey::new_from_array([16; 32]));
+        account.expiry_slot = COption::Some(17);
+        let mut data = vec![0; Account::LEN];
+        Account::pack(account, &mut data).unwrap();
+
+        let key = |offset: usize| Pubkey::new_from_array(*array_ref![data, offset, 32]);
+        let int = |offset: usize| u64::from_le_bytes(*array_ref![data, offset, 8]);
+        // a set `COption` is the tag 1 before its value
+        let some = |offset: usize| {
+            assert_eq!(data[offset..offset + 4], [1, 0, 0, 0]);
+            offset + 4
+        };
+        assert_eq!(key(ACCOUNT_MINT_OFFSET), account.mint);
+        assert_eq!(key(ACCOUNT_OWNER_OFFSET), account.owner);
+        assert_eq!(int(ACCOUNT_AMOUNT_OFFSET), account.amount);
+        assert_eq!(
+            COption::Some(key(some(ACCOUNT_DELEGATE_OFFSET))),
+            account.delegate
+        );
+        assert_eq!(data[ACCOUNT_STATE_OFFSET], account.state as u8);
+        assert_eq!(
+            COption::Some(int(some(ACCOUNT_IS_NATIVE_OFFSET))),
+            account.is_native
+        );
+        assert_eq!(int(ACCOUNT_DELEGATED_AMOUNT_OFFSET), account.delegated_amount);
+        assert_eq!(
+            COption::Some(key(some(ACCOUNT_CLOSE_AUTHORITY_OFFSET))),
+            account.close_authority
+        );
+        assert_eq!(
+            COption::Some(int(some(ACCOUNT_EXPIRY_SLOT_OFFSET))),
+            account.expiry_slot
+        );
+        assert_eq!(
+            COption::Some(key(some(ACCOUNT_PENDING_OWNER_OFFSET))),
+            account.pending_owner
+        );
+        assert_eq!(data[ACCOUNT_MEMO_REQUIRED_OFFSET], account.memo_required as u8);
+        assert_eq!(int(ACCOUNT_WITHHELD_AMOUNT_OFFSET), account.withheld_amount);
+        assert_eq!(data[ACCOUNT_MINT_REQUIRED_OFFSET], account.mint_required as u8);
+        assert_eq!(data[ACCOUNT_IMMUTABLE_OWNER_OFFSET], account.immutable_owner as u8);
+        assert_eq!(data[ACCOUNT_CPI_GUARD_OFFSET], account.cpi_guard as u8);
+        assert_eq!(
+            COption::Some(int(some(ACCOUNT_LOCK_UNTIL_OFFSET))),
+            account.lock_until
+        );
+        assert_eq!(data[ACCOUNT_ESCROWED_OFFSET], account.escrowed as u8);
+        assert_eq!(
+            COption::Some(int(some(ACCOUNT_LIMIT_AMOUNT_OFFSET))),
+            account.limit_amount
+        );
+        assert_eq!(int(ACCOUNT_WINDOW_START_EPOCH_OFFSET), account.window_start_epoch);
+        assert_eq!(int(ACCOUNT_SPENT_IN_WINDOW_OFFSET), account.spent_in_window);
+        assert_eq!(int(ACCOUNT_VESTING_AMOUNT_OFFSET), account.vesting_amount);
+        assert_eq!(int(ACCOUNT_VESTING_TOTAL_OFFSET), account.vesting_total);
+        assert_eq!(int(ACCOUNT_VESTING_START_SLOT_OFFSET), account.vesting_start_slot);
+        assert_eq!(int(ACCOUNT_VESTING_END_SLOT_OFFSET), account.vesting_end_slot);
+
+        // and writing a field at its offset is what unpacking reads back
+        data[ACCOUNT_STATE_OFFSET] = AccountState::Initialized as u8;
+        data[ACCOUNT_AMOUNT_OFFSET..ACCOUNT_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&42u64.to_le_bytes());
+        let unpacked = Account::unpack(&data).unwrap();
+        assert_eq!(unpacked.state, AccountState::Initialized);
+        assert_eq!(unpacked.amount, 42);
+        assert_eq!(
+            Account {
+                state: account.state,
+                amount: account.amount,
+                ..unpacked
+            },
+            account
+        );
     }
 }
@@ -0,0 +1,158 @@
+//! The `COption` encoding of the packed state, for programs and clients that
+//! parse the layouts themselves.
+//!
+//! An optional value is a four-byte tag followed by the value: `[0, 0, 0, 0]`
+//! for `None`, `[1, 0, 0, 0]` for `Some`. A key takes 36 bytes and a `u64`,
+//! stored little-endian, 12. The value bytes of a `None` are not read, and
+//! packing a `None` leaves them as they were. Any other tag is invalid data.
+//!
+//! Reading the delegate of a token account straight from its data:
+//!
+//! ```
+//! use solana_program::{
+//!     program_error::ProgramError, program_option::COption, program_pack::Pack,
+//!     pubkey::Pubkey,
+//! };
+//! use std::convert::TryInto;
+//! use test_token_solana::state::{
+//!     coption::unpack_coption_key, Account, AccountState, ACCOUNT_DELEGATE_OFFSET,
+//! };
+//!
+//! fn delegate(data: &[u8]) -> Result<COption<Pubkey>, ProgramError> {
+//!     let field = data
+//!         .get(ACCOUNT_DELEGATE_OFFSET..ACCOUNT_DELEGATE_OFFSET + 36)
+//!         .ok_or(ProgramError::InvalidAccountData)?;
+//!     unpack_coption_key(field.try_into().unwrap())
+//! }
+//!
+//! let account = Account {
+//!     delegate: COption::Some(Pubkey::new_unique()),
+//!     state: AccountState::Initialized,
+//!     ..Account::default()
+//! };
+//! let mut data = vec![0; Account::LEN];
+//! Account::pack(account, &mut data).unwrap();
+//! assert_eq!(delegate(&data), Ok(account.delegate));
+//! assert_eq!(delegate(&data[..100]), Err(ProgramError::InvalidAccountData));
+//! ```
+
+use arrayref::{array_refs, mut_array_refs};
+use solana_program::{program_error::ProgramError, program_option::COption, pubkey::Pubkey};
+
+/// Packs an optional key into its 36-byte encoding
+pub fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
+    let (tag, body) = mut_array_refs![dst, 4, 32];
+    match src {
+        COption::Some(key) => {
+            *tag = [1, 0, 0, 0];
+            body.copy_from_slice(key.as_ref());
+        }
+        COption::None => {
+            *tag = [0; 4];
+        }
+    }
+}
+
+/// Unpacks an optional key, failing with `InvalidAccountData` on an unknown
+/// tag
+pub fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
+    let (tag, body) = array_refs![src, 4, 32];
+    match *tag {
+        [0, 0, 0, 0] => Ok(COption::None),
+        [1, 0, 0, 0] => Ok(COption::Some(Pubkey::new_from_array(*body))),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Packs an optional `u64` into its 12-byte encoding
+pub fn pack_coption_u64(src: &COption<u64>, dst: &mut [u8; 12]) {
+    let (tag, body) = mut_array_refs![dst, 4, 8];
+    match src {
+        COption::Some(amount) => {
+            *tag = [1, 0, 0, 0];
+            *body = amount.to_le_bytes();
+        }
+        COption::None => {
+            *tag = [0; 4];
+        }
+    }
+}
+
+/// Unpacks an optional `u64`, failing with `InvalidAccountData` on an
+/// unknown tag
+pub fn unpack_coption_u64(src: &[u8; 12]) -> Result<COption<u64>, ProgramError> {
+    let (tag, body) = array_refs![src, 4, 8];
+    match *tag {
+        [0, 0, 0, 0] => Ok(COption::None),
+        [1, 0, 0, 0] => Ok(COption::Some(u64::from_le_bytes(*body))),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVALID_TAGS: [[u8; 4]; 6] = [
+        [2, 0, 0, 0],
+        [1, 1, 0, 0],
+        [0, 1, 0, 0],
+        [0, 0, 0, 1],
+        [1, 0, 0, 1],
+        [255, 255, 255, 255],
+    ];
+
+    #[test]
+    fn test_coption_key() {
+        let key = Pubkey::new_from_array([7; 32]);
+
+        let mut dst = [0xff; 36];
+        pack_coption_key(&COption::Some(key), &mut dst);
+        assert_eq!(dst[..4], [1, 0, 0, 0]);
+        assert_eq!(dst[4..], key.to_bytes());
+        assert_eq!(unpack_coption_key(&dst), Ok(COption::Some(key)));
+
+        // only the tag of a `None` is written or read
+        pack_coption_key(&COption::None, &mut dst);
+        assert_eq!(dst[..4], [0, 0, 0, 0]);
+        assert_eq!(dst[4..], key.to_bytes());
+        assert_eq!(unpack_coption_key(&dst), Ok(COption::None));
+        assert_eq!(unpack_coption_key(&[0; 36]), Ok(COption::None));
+
+        for tag in INVALID_TAGS {
+            dst[..4].copy_from_slice(&tag);
+            assert_eq!(
+                unpack_coption_key(&dst),
+                Err(ProgramError::InvalidAccountData),
+                "{:?}",
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn test_coption_u64() {
+        for value in [0, 1, 0x0102_0304_0506_0708, u64::MAX] {
+            let mut dst = [0xff; 12];
+            pack_coption_u64(&COption::Some(value), &mut dst);
+            assert_eq!(dst[..4], [1, 0, 0, 0]);
+            assert_eq!(dst[4..], value.to_le_bytes());
+            assert_eq!(unpack_coption_u64(&dst), Ok(COption::Some(value)));
+
+            pack_coption_u64(&COption::None, &mut dst);
+            assert_eq!(dst[..4], [0, 0, 0, 0]);
+            assert_eq!(dst[4..], value.to_le_bytes());
+            assert_eq!(unpack_coption_u64(&dst), Ok(COption::None));
+
+            for tag in INVALID_TAGS {
+                dst[..4].copy_from_slice(&tag);
+                assert_eq!(
+                    unpack_coption_u64(&dst),
+                    Err(ProgramError::InvalidAccountData),
+                    "{:?}",
+                    tag
+                );
+            }
+        }
+    }
+}
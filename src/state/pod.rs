@@ -0,0 +1,484 @@
+//! Zero-copy views over the packed `Mint` and `Account` layouts.
+//!
+//! Every field is made of byte arrays, so the structs have an alignment of one
+//! and can be cast directly from account data. The layouts are identical to
+//! the `Pack` implementations in the parent module.
+
+use super::AccountState;
+use bytemuck::{Pod, Zeroable};
+use num_enum::TryFromPrimitive;
+use solana_program::{program_error::ProgramError, program_option::COption, pubkey::Pubkey};
+
+/// Little-endian `u64` with an alignment of one
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodU64([u8; 8]);
+
+impl From<u64> for PodU64 {
+    fn from(value: u64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU64> for u64 {
+    fn from(value: PodU64) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `u16` with an alignment of one
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodU16([u8; 2]);
+
+impl From<u16> for PodU16 {
+    fn from(value: u16) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU16> for u16 {
+    fn from(value: PodU16) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `i64` with an alignment of one
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodI64([u8; 8]);
+
+impl From<i64> for PodI64 {
+    fn from(value: i64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodI64> for i64 {
+    fn from(value: PodI64) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `i16` with an alignment of one
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodI16([u8; 2]);
+
+impl From<i16> for PodI16 {
+    fn from(value: i16) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodI16> for i16 {
+    fn from(value: PodI16) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Boolean stored as a single byte
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodBool(u8);
+
+impl From<bool> for PodBool {
+    fn from(value: bool) -> Self {
+        Self(value as u8)
+    }
+}
+
+impl From<PodBool> for bool {
+    fn from(value: PodBool) -> Self {
+        value.0 != 0
+    }
+}
+
+impl PodBool {
+    /// Whether the byte is one `Pack` accepts
+    pub fn is_valid(&self) -> bool {
+        self.0 <= 1
+    }
+}
+
+/// `COption` with the same 4-byte tag used by the packed state
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PodCOption<T> {
+    option: [u8; 4],
+    value: T,
+}
+
+// Only implemented for alignment-one payloads, which keeps the struct free of
+// padding
+unsafe impl Zeroable for PodCOption<Pubkey> {}
+unsafe impl Pod for PodCOption<Pubkey> {}
+unsafe impl Zeroable for PodCOption<PodU64> {}
+unsafe impl Pod for PodCOption<PodU64> {}
+
+impl<T: Copy + Default> PodCOption<T> {
+    const NONE: [u8; 4] = [0; 4];
+    const SOME: [u8; 4] = [1, 0, 0, 0];
+
+    pub fn none() -> Self {
+        Self {
+            option: Self::NONE,
+            value: T::default(),
+        }
+    }
+
+    pub fn some(value: T) -> Self {
+        Self {
+            option: Self::SOME,
+            value,
+        }
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.option == Self::SOME
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.option == Self::NONE
+    }
+
+    /// Whether the tag is one `Pack` accepts
+    pub fn is_valid(&self) -> bool {
+        self.is_some() || self.is_none()
+    }
+
+    pub fn get(&self) -> Option<T> {
+        if self.is_some() {
+            Some(self.value)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Copy + Default> From<COption<T>> for PodCOption<T> {
+    fn from(value: COption<T>) -> Self {
+        match value {
+            COption::Some(value) => Self::some(value),
+            COption::None => Self::none(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodMint {
+    pub mint_authority: PodCOption<Pubkey>,
+    pub supply: PodU64,
+    pub decimals: u8,
+    pub is_initialized: PodBool,
+    pub freeze_authority: PodCOption<Pubkey>,
+    pub max_supply: PodCOption<PodU64>,
+    pub permanent_delegate: PodCOption<Pubkey>,
+    pub default_account_state: u8,
+    pub transfer_fee_config: PodTransferFeeConfig,
+    pub withheld_amount: PodU64,
+    pub interest_bearing_config: PodInterestBearingConfig,
+    pub pause_authority: PodCOption<Pubkey>,
+    pub paused: PodBool,
+    pub transfer_hook_authority: PodCOption<Pubkey>,
+    pub transfer_hook_program_id: PodCOption<Pubkey>,
+    pub metadata_pointer: PodCOption<Pubkey>,
+    pub transfer_policy: PodCOption<Pubkey>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodTransferFeeConfig {
+    pub transfer_fee_authority: PodCOption<Pubkey>,
+    pub transfer_fee_basis_points: PodU16,
+    pub maximum_fee: PodU64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodInterestBearingConfig {
+    pub rate_authority: PodCOption<Pubkey>,
+    pub initialization_timestamp: PodI64,
+    pub pre_update_average_rate: PodI16,
+    pub last_update_timestamp: PodI64,
+    pub current_rate: PodI16,
+}
+
+impl PodMint {
+    pub fn from_bytes(data: &[u8]) -> Result<&Self, ProgramError> {
+        pod_from_bytes(data)
+    }
+
+    pub fn from_bytes_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        pod_from_bytes_mut(data)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: PodU64,
+    pub delegate: PodCOption<Pubkey>,
+    pub delegated_amount: PodU64,
+    pub state: u8,
+    pub is_native: PodCOption<PodU64>,
+    pub close_authority: PodCOption<Pubkey>,
+    pub expiry_slot: PodCOption<PodU64>,
+    pub pending_owner: PodCOption<Pubkey>,
+    pub memo_required: PodBool,
+    pub withheld_amount: PodU64,
+    pub mint_required: PodBool,
+    pub immutable_owner: PodBool,
+    pub cpi_guard: PodBool,
+    pub lock_until: PodCOption<PodU64>,
+    pub escrowed: PodBool,
+    pub limit_amount: PodCOption<PodU64>,
+    pub window_start_epoch: PodU64,
+    pub spent_in_window: PodU64,
+    pub vesting_amount: PodU64,
+    pub vesting_total: PodU64,
+    pub vesting_start_slot: PodU64,
+    pub vesting_end_slot: PodU64,
+}
+
+impl PodAccount {
+    pub fn from_bytes(data: &[u8]) -> Result<&Self, ProgramError> {
+        pod_from_bytes(data)
+    }
+
+    /// Whether `Account::unpack_unchecked` accepts the bytes: the state is
+    /// known and every option tag and flag is well-formed
+    pub fn is_valid(&self) -> bool {
+        AccountState::try_from_primitive(self.state).is_ok()
+            && self.delegate.is_valid()
+            && self.is_native.is_valid()
+            && self.close_authority.is_valid()
+            && self.expiry_slot.is_valid()
+            && self.pending_owner.is_valid()
+            && self.memo_required.is_valid()
+            && self.mint_required.is_valid()
+            && self.immutable_owner.is_valid()
+            && self.cpi_guard.is_valid()
+            && self.lock_until.is_valid()
+            && self.escrowed.is_valid()
+            && self.limit_amount.is_valid()
+    }
+
+    pub fn from_bytes_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        pod_from_bytes_mut(data)
+    }
+}
+
+fn pod_from_bytes<T: Pod>(data: &[u8]) -> Result<&T, ProgramError> {
+    bytemuck::try_from_bytes(data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+fn pod_from_bytes_mut<T: Pod>(data: &mut [u8]) -> Result<&mut T, ProgramError> {
+    bytemuck::try_from_bytes_mut(data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Account, AccountState, InterestBearingConfig, Mint, TransferFeeConfig};
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn test_pod_sizes() {
+        assert_eq!(std::mem::size_of::<PodMint>(), Mint::LEN);
+        assert_eq!(std::mem::size_of::<PodAccount>(), Account::LEN);
+        assert_eq!(std::mem::align_of::<PodMint>(), 1);
+        assert_eq!(std::mem::align_of::<PodAccount>(), 1);
+    }
+
+    #[test]
+    fn test_pod_mint_matches_pack() {
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 42,
+            decimals: 7,
+            is_initialized: true,
+            freeze_authority: COption::None,
+            max_supply: COption::Some(1_000),
+            permanent_delegate: COption::Some(Pubkey::new_from_array([3; 32])),
+            default_account_state: AccountState::Frozen,
+            transfer_fee_config: TransferFeeConfig {
+                transfer_fee_authority: COption::Some(Pubkey::new_from_array([4; 32])),
+                transfer_fee_basis_points: 50,
+                maximum_fee: 5_000,
+            },
+            withheld_amount: 6,
+            interest_bearing_config: InterestBearingConfig {
+                rate_authority: COption::Some(Pubkey::new_from_array([5; 32])),
+                initialization_timestamp: -7,
+                pre_update_average_rate: -8,
+                last_update_timestamp: 9,
+                current_rate: 10,
+            },
+            pause_authority: COption::Some(Pubkey::new_from_array([11; 32])),
+            paused: true,
+            transfer_hook_authority: COption::Some(Pubkey::new_from_array([12; 32])),
+            transfer_hook_program_id: COption::Some(Pubkey::new_from_array([13; 32])),
+            metadata_pointer: COption::Some(Pubkey::new_from_array([14; 32])),
+            transfer_policy: COption::Some(Pubkey::new_from_array([15; 32])),
+        };
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+
+        let pod = PodMint::from_bytes(&data).unwrap();
+        assert_eq!(pod.mint_authority.get(), Some(Pubkey::new_from_array([1; 32])));
+        assert_eq!(u64::from(pod.supply), 42);
+        assert_eq!(pod.decimals, 7);
+        assert!(bool::from(pod.is_initialized));
+        assert!(pod.freeze_authority.is_none());
+        assert_eq!(pod.max_supply.get().map(u64::from), Some(1_000));
+        assert_eq!(pod.permanent_delegate.get(), Some(Pubkey::new_from_array([3; 32])));
+        assert_eq!(pod.default_account_state, AccountState::Frozen as u8);
+        assert_eq!(
+            pod.transfer_fee_config.transfer_fee_authority.get(),
+            Some(Pubkey::new_from_array([4; 32]))
+        );
+        assert_eq!(u16::from(pod.transfer_fee_config.transfer_fee_basis_points), 50);
+        assert_eq!(u64::from(pod.transfer_fee_config.maximum_fee), 5_000);
+        assert_eq!(u64::from(pod.withheld_amount), 6);
+        let interest = &pod.interest_bearing_config;
+        assert_eq!(interest.rate_authority.get(), Some(Pubkey::new_from_array([5; 32])));
+        assert_eq!(i64::from(interest.initialization_timestamp), -7);
+        assert_eq!(i16::from(interest.pre_update_average_rate), -8);
+        assert_eq!(i64::from(interest.last_update_timestamp), 9);
+        assert_eq!(i16::from(interest.current_rate), 10);
+        assert_eq!(pod.pause_authority.get(), Some(Pubkey::new_from_array([11; 32])));
+        assert!(bool::from(pod.paused));
+        assert_eq!(pod.transfer_hook_authority.get(), Some(Pubkey::new_from_array([12; 32])));
+        assert_eq!(pod.transfer_hook_program_id.get(), Some(Pubkey::new_from_array([13; 32])));
+        assert_eq!(pod.metadata_pointer.get(), Some(Pubkey::new_from_array([14; 32])));
+        assert_eq!(pod.transfer_policy.get(), Some(Pubkey::new_from_array([15; 32])));
+
+        // writes through the view are seen by `Pack`
+        let pod = PodMint::from_bytes_mut(&mut data).unwrap();
+        pod.supply = 100.into();
+        pod.freeze_authority = COption::Some(Pubkey::new_from_array([2; 32])).into();
+        let unpacked = Mint::unpack(&data).unwrap();
+        assert_eq!(unpacked.supply, 100);
+        assert_eq!(
+            unpacked.freeze_authority,
+            COption::Some(Pubkey::new_from_array([2; 32]))
+        );
+    }
+
+    #[test]
+    fn test_pod_account_matches_pack() {
+        let account = Account {
+            mint: Pubkey::new_from_array([1; 32]),
+            owner: Pubkey::new_from_array([2; 32]),
+            amount: 3,
+            delegate: COption::Some(Pubkey::new_from_array([4; 32])),
+            delegated_amount: 5,
+            state: AccountState::Frozen,
+            is_native: COption::Some(6),
+            close_authority: COption::Some(Pubkey::new_from_array([7; 32])),
+            expiry_slot: COption::Some(8),
+            pending_owner: COption::Some(Pubkey::new_from_array([9; 32])),
+            memo_required: true,
+            withheld_amount: 10,
+            mint_required: true,
+            immutable_owner: true,
+            cpi_guard: true,
+            lock_until: COption::Some(11),
+            escrowed: true,
+            limit_amount: COption::Some(12),
+            window_start_epoch: 13,
+            spent_in_window: 14,
+            vesting_amount: 15,
+            vesting_total: 16,
+            vesting_start_slot: 17,
+            vesting_end_slot: 18,
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(account, &mut data).unwrap();
+
+        let pod = PodAccount::from_bytes(&data).unwrap();
+        assert_eq!(pod.mint, account.mint);
+        assert_eq!(pod.owner, account.owner);
+        assert_eq!(u64::from(pod.amount), 3);
+        assert_eq!(pod.delegate.get(), Some(Pubkey::new_from_array([4; 32])));
+        assert_eq!(u64::from(pod.delegated_amount), 5);
+        assert_eq!(pod.state, AccountState::Frozen as u8);
+        assert_eq!(pod.is_native.get().map(u64::from), Some(6));
+        assert_eq!(pod.close_authority.get(), Some(Pubkey::new_from_array([7; 32])));
+        assert_eq!(pod.expiry_slot.get().map(u64::from), Some(8));
+        assert_eq!(pod.pending_owner.get(), Some(Pubkey::new_from_array([9; 32])));
+        assert!(bool::from(pod.memo_required));
+        assert_eq!(u64::from(pod.withheld_amount), 10);
+        assert!(bool::from(pod.mint_required));
+        assert!(bool::from(pod.immutable_owner));
+        assert!(bool::from(pod.cpi_guard));
+        assert_eq!(pod.lock_until.get().map(u64::from), Some(11));
+        assert!(bool::from(pod.escrowed));
+        assert_eq!(pod.limit_amount.get().map(u64::from), Some(12));
+        assert_eq!(u64::from(pod.window_start_epoch), 13);
+        assert_eq!(u64::from(pod.spent_in_window), 14);
+        assert_eq!(u64::from(pod.vesting_amount), 15);
+        assert_eq!(u64::from(pod.vesting_total), 16);
+        assert_eq!(u64::from(pod.vesting_start_slot), 17);
+        assert_eq!(u64::from(pod.vesting_end_slot), 18);
+
+        // writes through the view are seen by `Pack`
+        let pod = PodAccount::from_bytes_mut(&mut data).unwrap();
+        pod.amount = 7.into();
+        pod.delegate = PodCOption::none();
+        pod.delegated_amount = 0.into();
+        pod.is_native = PodCOption::none();
+        let unpacked = Account::unpack(&data).unwrap();
+        assert_eq!(unpacked.amount, 7);
+        assert_eq!(unpacked.delegate, COption::None);
+        assert_eq!(unpacked.delegated_amount, 0);
+        assert_eq!(unpacked.is_native, COption::None);
+
+        // a view built from scratch packs to the same bytes
+        let pod = PodAccount {
+            mint: account.mint,
+            owner: account.owner,
+            amount: 3.into(),
+            delegate: account.delegate.into(),
+            delegated_amount: 5.into(),
+            state: AccountState::Frozen as u8,
+            is_native: COption::Some(PodU64::from(6)).into(),
+            close_authority: account.close_authority.into(),
+            expiry_slot: COption::Some(PodU64::from(8)).into(),
+            pending_owner: account.pending_owner.into(),
+            memo_required: true.into(),
+            withheld_amount: 10.into(),
+            mint_required: true.into(),
+            immutable_owner: true.into(),
+            cpi_guard: true.into(),
+            lock_until: COption::Some(PodU64::from(11)).into(),
+            escrowed: true.into(),
+            limit_amount: COption::Some(PodU64::from(12)).into(),
+            window_start_epoch: 13.into(),
+            spent_in_window: 14.into(),
+            vesting_amount: 15.into(),
+            vesting_total: 16.into(),
+            vesting_start_slot: 17.into(),
+            vesting_end_slot: 18.into(),
+        };
+        let mut packed = vec![0; Account::LEN];
+        Account::pack(account, &mut packed).unwrap();
+        assert_eq!(bytemuck::bytes_of(&pod), &packed[..]);
+    }
+
+    #[test]
+    fn test_pod_wrong_length() {
+        assert_eq!(
+            PodAccount::from_bytes(&[0; Account::LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            PodMint::from_bytes(&[0; Mint::LEN + 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Builders for the mints and token accounts unit tests start from. Each
+//! packs into a rent-exempt `SolanaAccount` owned by the program, ready for
+//! `do_process_instruction`, so a test names only the fields it relies on
+//! instead of running the initialize instructions.
+
+use crate::{
+    state::{Account, AccountState, Mint},
+    test_support::{account_minimum_balance, mint_minimum_balance},
+};
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::account::Account as SolanaAccount;
+
+/// An initialized mint with no authorities, supply or decimals
+pub(crate) struct MintFixture {
+    mint: Mint,
+}
+
+impl MintFixture {
+    pub(crate) fn new() -> Self {
+        Self {
+            mint: Mint {
+                is_initialized: true,
+                default_account_state: AccountState::Initialized,
+                ..Mint::default()
+            },
+        }
+    }
+
+    pub(crate) fn mint_authority(mut self, mint_authority: Pubkey) -> Self {
+        self.mint.mint_authority = COption::Some(mint_authority);
+        self
+    }
+
+    pub(crate) fn freeze_authority(mut self, freeze_authority: Pubkey) -> Self {
+        self.mint.freeze_authority = COption::Some(freeze_authority);
+        self
+    }
+
+    /// The supply has to add up to what the test's accounts hold
+    pub(crate) fn supply(mut self, supply: u64) -> Self {
+        self.mint.supply = supply;
+        self
+    }
+
+    pub(crate) fn decimals(mut self, decimals: u8) -> Self {
+        self.mint.decimals = decimals;
+        self
+    }
+
+    pub(crate) fn into_solana_account(self) -> SolanaAccount {
+        let mut account = SolanaAccount::new(mint_minimum_balance(), Mint::LEN, &crate::id());
+        Mint::pack(self.mint, &mut account.data).unwrap();
+        account
+    }
+}
+
+/// An initialized, empty account of a mint, owned by a unique key
+pub(crate) struct AccountFixture {
+    account: Account,
+}
+
+impl AccountFixture {
+    pub(crate) fn new(mint: Pubkey) -> Self {
+        Self {
+            account: Account {
+                mint,
+                owner: Pubkey::new_unique(),
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+        }
+    }
+
+    pub(crate) fn owner(mut self, owner: Pubkey) -> Self {
+        self.account.owner = owner;
+        self
+    }
+
+    pub(crate) fn amount(mut self, amount: u64) -> Self {
+        self.account.amount = amount;
+        self
+    }
+
+    pub(crate) fn delegate(mut self, delegate: Pubkey, delegated_amount: u64) -> Self {
+        self.account.delegate = COption::Some(delegate);
+        self.account.delegated_amount = delegated_amount;
+        self
+    }
+
+    pub(crate) fn into_solana_account(self) -> SolanaAccount {
+        let mut account = SolanaAccount::new(account_minimum_balance(), Account::LEN, &crate::id());
+        Account::pack(self.account, &mut account.data).unwrap();
+        account
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::{
+        instruction::{initialize_account, initialize_mint},
+        test_support::{do_process_instruction, rent_sysvar},
+    };
+
+    #[test]
+    fn test_fixtures_match_initialize() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let freeze_key = Pubkey::new_unique();
+        let mut rent_sysvar = rent_sysvar();
+
+        let mut mint_account = SolanaAccount::new(mint_minimum_balance(), Mint::LEN, &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&freeze_key), 6).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        let fixture = MintFixture::new()
+            .mint_authority(owner_key)
+            .freeze_authority(freeze_key)
+            .decimals(6)
+            .into_solana_account();
+        assert_eq!(fixture, mint_account);
+
+        let account_key = Pubkey::new_unique();
+        let mut account_account =
+            SolanaAccount::new(account_minimum_balance(), Account::LEN, &program_id);
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            AccountFixture::new(mint_key)
+                .owner(owner_key)
+                .into_solana_account(),
+            account_account
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Harness shared by the unit tests: syscall stubs, helpers that run an
+//! instruction over test accounts, and `TokenScenario`, which checks the
+//! supply invariant after every instruction it runs.
+
+use crate::{
+    processor::Processor,
+    state::{Account, Mint, Multisig},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use solana_sdk::account::{
+    create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    convert::TryInto,
+    sync::Once,
+};
+
+thread_local! {
+    pub(crate) static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> =
+        const { RefCell::new(None) };
+    pub(crate) static CLOCK_SLOT: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static CLOCK_UNIX_TIMESTAMP: Cell<i64> = const { Cell::new(0) };
+    pub(crate) static CLOCK_EPOCH: Cell<u64> = const { Cell::new(0) };
+    pub(crate) static LOGS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    pub(crate) static LOG_DATA: RefCell<Vec<Vec<u8>>> = const { RefCell::new(vec![]) };
+    pub(crate) static STACK_HEIGHT: Cell<usize> =
+        const { Cell::new(TRANSACTION_LEVEL_STACK_HEIGHT) };
+}
+
+struct SyscallStubs {}
+impl solana_sdk::program_stubs::SyscallStubs for SyscallStubs {
+    fn sol_log(&self, message: &str) {
+        LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        LOG_DATA.with(|log_data| {
+            log_data
+                .borrow_mut()
+                .extend(fields.iter().map(|field| field.to_vec()))
+        });
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        RETURN_DATA.with(|return_data| {
+            *return_data.borrow_mut() = Some((crate::id(), data.to_vec()))
+        });
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RETURN_DATA.with(|return_data| return_data.borrow().clone())
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut _ as *mut Rent) = Rent::default();
+        }
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut _ as *mut Clock) = Clock {
+                slot: CLOCK_SLOT.with(|slot| slot.get()),
+                unix_timestamp: CLOCK_UNIX_TIMESTAMP.with(|timestamp| timestamp.get()),
+                epoch: CLOCK_EPOCH.with(|epoch| epoch.get()),
+                ..Clock::default()
+            };
+        }
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        STACK_HEIGHT.with(|height| height.get()) as u64
+    }
+
+    // Only the system program's account creation, assignment, transfer
+    // and allocation are emulated. Resizing needs the accounts laid out
+    // by `do_process_instruction_serialized`.
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        assert_eq!(instruction.program_id, system_program::id());
+        let find = |meta: &AccountMeta| {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            // the caller signs for the addresses its seeds derive
+            let is_signer = info.is_signer
+                || signers_seeds.iter().any(|seeds| {
+                    Pubkey::create_program_address(seeds, &crate::id()) == Ok(*info.key)
+                });
+            if meta.is_signer && !is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            Ok(info)
+        };
+        let data = &instruction.data;
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let pubkey_at =
+            |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| {
+            let from_lamports = from
+                .lamports()
+                .checked_sub(lamports)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            **from.lamports.borrow_mut() = from_lamports;
+            **to.lamports.borrow_mut() += lamports;
+            Ok::<_, ProgramError>(())
+        };
+        match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+            // CreateAccount
+            0 => {
+                let from = find(&instruction.accounts[0])?;
+                let to = find(&instruction.accounts[1])?;
+                if to.lamports() > 0 || !to.data_is_empty() {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                transfer(from, to, u64_at(4))?;
+                to.realloc(u64_at(12) as usize, true)?;
+                to.assign(&pubkey_at(20));
+            }
+            // Assign
+            1 => find(&instruction.accounts[0])?.assign(&pubkey_at(4)),
+            // Transfer
+            2 => transfer(
+                find(&instruction.accounts[0])?,
+                find(&instruction.accounts[1])?,
+                u64_at(4),
+            )?,
+            // Allocate
+            8 => find(&instruction.accounts[0])?.realloc(u64_at(4) as usize, true)?,
+            tag => panic!("unexpected system instruction {}", tag),
+        }
+        Ok(())
+    }
+}
+
+/// Routes syscalls to `SyscallStubs` for the rest of the test binary
+pub(crate) fn set_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        solana_sdk::program_stubs::set_syscall_stubs(Box::new(SyscallStubs {}));
+    });
+}
+
+pub(crate) fn do_process_instruction(
+    instruction: Instruction,
+    accounts: Vec<&mut SolanaAccount>,
+) -> ProgramResult {
+    set_stubs();
+
+    let mut meta = instruction
+        .accounts
+        .iter()
+        .zip(accounts)
+        .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+        .collect::<Vec<_>>();
+
+    let account_infos = create_is_signer_account_infos(&mut meta);
+    Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+}
+
+/// Like `do_process_instruction`, but with the data of the accounts at
+/// `held` borrowed for the whole instruction, so writing to any of them
+/// panics
+pub(crate) fn do_process_instruction_holding(
+    instruction: Instruction,
+    accounts: Vec<&mut SolanaAccount>,
+    held: &[usize],
+) -> ProgramResult {
+    set_stubs();
+
+    let mut meta = instruction
+        .accounts
+        .iter()
+        .zip(accounts)
+        .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+        .collect::<Vec<_>>();
+
+    let account_infos = create_is_signer_account_infos(&mut meta);
+    let _borrows: Vec<_> = held
+        .iter()
+        .map(|&index| account_infos[index].data.borrow())
+        .collect();
+    Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+}
+
+/// Like `do_process_instruction`, but lays the accounts out the way the
+/// runtime serializes them, which `AccountInfo::realloc` relies on. As in
+/// the runtime, the accounts are only updated if the instruction succeeds.
+pub(crate) fn do_process_instruction_serialized(
+    instruction: Instruction,
+    accounts: Vec<&mut SolanaAccount>,
+) -> ProgramResult {
+    let mut input = vec![];
+    input.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+    for (account_meta, account) in instruction.accounts.iter().zip(accounts.iter()) {
+        input.push(solana_program::entrypoint::NON_DUP_MARKER);
+        input.push(account_meta.is_signer as u8);
+        input.push(account_meta.is_writable as u8);
+        input.push(account.executable as u8);
+        input.extend_from_slice(&[0; 4]);
+        input.extend_from_slice(account_meta.pubkey.as_ref());
+        input.extend_from_slice(account.owner.as_ref());
+        input.extend_from_slice(&account.lamports.to_le_bytes());
+        input.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+        input.extend_from_slice(&account.data);
+        input.resize(
+            (input.len() + solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE)
+                .next_multiple_of(8),
+            0,
+        );
+        input.extend_from_slice(&account.rent_epoch.to_le_bytes());
+    }
+    input.extend_from_slice(&(instruction.data.len() as u64).to_le_bytes());
+    input.extend_from_slice(&instruction.data);
+    input.extend_from_slice(instruction.program_id.as_ref());
+
+    // The entrypoint reads the input as aligned `u64`s
+    let mut aligned = vec![0u64; input.len().div_ceil(8)];
+    bytemuck::cast_slice_mut::<u64, u8>(&mut aligned)[..input.len()].copy_from_slice(&input);
+    let (program_id, account_infos, data) =
+        unsafe { solana_program::entrypoint::deserialize(aligned.as_mut_ptr() as *mut u8) };
+    Processor::process(program_id, &account_infos, data)?;
+
+    for (account, account_info) in accounts.into_iter().zip(account_infos.iter()) {
+        account.lamports = account_info.lamports();
+        account.data = account_info.data.borrow().to_vec();
+        account.owner = *account_info.owner;
+    }
+    Ok(())
+}
+
+pub(crate) fn do_process_instruction_dups(
+    instruction: Instruction,
+    account_infos: Vec<AccountInfo>,
+) -> ProgramResult {
+    Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+}
+
+pub(crate) fn rent_sysvar() -> SolanaAccount {
+    create_account_for_test(&Rent::default())
+}
+
+pub(crate) fn mint_minimum_balance() -> u64 {
+    Rent::default().minimum_balance(Mint::get_packed_len())
+}
+
+pub(crate) fn account_minimum_balance() -> u64 {
+    Rent::default().minimum_balance(Account::get_packed_len())
+}
+
+pub(crate) fn multisig_minimum_balance() -> u64 {
+    Rent::default().minimum_balance(Multisig::get_packed_len())
+}
+
+/// Runs instructions over accounts it keeps by key, the way the runtime
+/// would: an account listed twice is the same account, and a failed
+/// instruction leaves every account as it was. After each instruction that
+/// succeeds it asserts the supply invariant.
+pub(crate) struct TokenScenario {
+    accounts: HashMap<Pubkey, SolanaAccount>,
+    mints: Vec<Pubkey>,
+    token_accounts: Vec<Pubkey>,
+}
+
+impl TokenScenario {
+    pub(crate) fn new() -> Self {
+        set_stubs();
+        let mut accounts = HashMap::new();
+        accounts.insert(sysvar::rent::id(), rent_sysvar());
+        Self {
+            accounts,
+            mints: vec![],
+            token_accounts: vec![],
+        }
+    }
+
+    pub(crate) fn create_mint(&mut self, mint_authority: &Pubkey, decimals: u8) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.accounts.insert(
+            key,
+            SolanaAccount::new(mint_minimum_balance(), Mint::LEN, &crate::id()),
+        );
+        self.process(
+            crate::instruction::initialize_mint(
+                &crate::id(),
+                &key,
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        self.mints.push(key);
+        key
+    }
+
+    pub(crate) fn create_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.accounts.insert(
+            key,
+            SolanaAccount::new(account_minimum_balance(), Account::LEN, &crate::id()),
+        );
+        self.process(
+            crate::instruction::initialize_account(&crate::id(), &key, mint, owner).unwrap(),
+        )
+        .unwrap();
+        self.token_accounts.push(key);
+        key
+    }
+
+    pub(crate) fn account(&self, key: &Pubkey) -> Account {
+        Account::unpack(&self.accounts[key].data).unwrap()
+    }
+
+    pub(crate) fn mint(&self, key: &Pubkey) -> Mint {
+        Mint::unpack(&self.accounts[key].data).unwrap()
+    }
+
+    /// Runs `instruction` with its metas' signer flags taken as signatures
+    pub(crate) fn process(&mut self, instruction: Instruction) -> ProgramResult {
+        let mut keys: Vec<Pubkey> = vec![];
+        for meta in instruction.accounts.iter() {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let mut accounts: Vec<SolanaAccount> = keys
+            .iter()
+            .map(|key| self.accounts.get(key).cloned().unwrap_or_default())
+            .collect();
+
+        {
+            let account_infos: Vec<AccountInfo> = keys
+                .iter()
+                .zip(accounts.iter_mut())
+                .map(|(key, account)| {
+                    let is_signer = instruction
+                        .accounts
+                        .iter()
+                        .any(|meta| meta.pubkey == *key && meta.is_signer);
+                    let is_writable = instruction
+                        .accounts
+                        .iter()
+                        .any(|meta| meta.pubkey == *key && meta.is_writable);
+                    AccountInfo::new(
+                        key,
+                        is_signer,
+                        is_writable,
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        account.executable,
+                        account.rent_epoch,
+                    )
+                })
+                .collect();
+            let ordered_infos: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let position = keys.iter().position(|key| *key == meta.pubkey).unwrap();
+                    account_infos[position].clone()
+                })
+                .collect();
+            Processor::process(&instruction.program_id, &ordered_infos, &instruction.data)?;
+        }
+
+        for (key, account) in keys.into_iter().zip(accounts) {
+            self.accounts.insert(key, account);
+        }
+        self.assert_supply_invariant();
+        Ok(())
+    }
+
+    /// Every mint's supply is what its accounts hold, counting fees withheld
+    /// in the accounts or the mint and tokens still vesting
+    pub(crate) fn assert_supply_invariant(&self) {
+        for mint_key in self.mints.iter() {
+            let mint = self.mint(mint_key);
+            let held = self
+                .token_accounts
+                .iter()
+                .map(|key| self.account(key))
+                .filter(|account| account.mint == *mint_key)
+                .map(|account| {
+                    account.amount as u128
+                        + account.withheld_amount as u128
+                        + account.vesting_amount as u128
+                })
+                .sum::<u128>();
+            assert_eq!(
+                mint.supply as u128,
+                held + mint.withheld_amount as u128,
+                "supply of {} does not match its accounts",
+                mint_key
+            );
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! The instruction a mint's transfer hook program receives after each
+//! checked transfer of the mint's tokens
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+/// Builds the instruction that runs a transfer hook. Data: the transferred
+/// amount as a little-endian `u64`. Accounts, all read-only so the hook
+/// cannot touch the balances it is judging: source, mint, destination,
+/// transfer authority, then the extra accounts passed to the transfer.
+pub fn execute(
+    hook_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    extra_account_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Instruction {
+    let mut accounts = Vec::with_capacity(4 + extra_account_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(*source_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*authority_pubkey, false));
+    for extra_account_pubkey in extra_account_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**extra_account_pubkey, false));
+    }
+
+    Instruction {
+        program_id: *hook_program_id,
+        accounts,
+        data: amount.to_le_bytes().to_vec(),
+    }
+}
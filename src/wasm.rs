@@ -0,0 +1,92 @@
+//! JS bindings for building instruction data and reading state off-chain,
+//! through `wasm-bindgen`.
+//!
+//! Values cross the boundary as the JSON the `serde` feature defines: an
+//! instruction is `{"Transfer":{"amount":42}}`, keys are base58 strings and
+//! unset options are `null`. Build with
+//! `wasm-pack build --no-default-features --features wasm`.
+
+use crate::{
+    instruction::TokenInstruction,
+    state::{Account, Mint},
+};
+use solana_program::program_pack::Pack;
+use std::fmt::Display;
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: impl Display) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Packs the instruction given as JSON into its instruction data
+#[wasm_bindgen(js_name = packInstruction)]
+pub fn pack_instruction(instruction: &str) -> Result<Vec<u8>, JsError> {
+    let instruction: TokenInstruction = serde_json::from_str(instruction).map_err(js_error)?;
+    Ok(instruction.pack())
+}
+
+/// Unpacks instruction data into the instruction as JSON
+#[wasm_bindgen(js_name = unpackInstruction)]
+pub fn unpack_instruction(data: &[u8]) -> Result<String, JsError> {
+    let instruction = TokenInstruction::unpack(data).map_err(js_error)?;
+    serde_json::to_string(&instruction).map_err(js_error)
+}
+
+/// Unpacks an initialized mint's data into the mint as JSON
+#[wasm_bindgen(js_name = unpackMint)]
+pub fn unpack_mint(data: &[u8]) -> Result<String, JsError> {
+    let mint = Mint::unpack(data).map_err(js_error)?;
+    serde_json::to_string(&mint).map_err(js_error)
+}
+
+/// Unpacks an initialized token account's data into the account as JSON
+#[wasm_bindgen(js_name = unpackAccount)]
+pub fn unpack_account(data: &[u8]) -> Result<String, JsError> {
+    let account = Account::unpack(data).map_err(js_error)?;
+    serde_json::to_string(&account).map_err(js_error)
+}
+
+// A `JsError` can only be created on a wasm target, so the native tests
+// cover the successful paths and `tests/wasm.rs` the rest under
+// `wasm-pack test`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AccountState;
+    use solana_program::{program_option::COption, pubkey::Pubkey};
+
+    #[test]
+    fn test_instruction_round_trip() {
+        let json = r#"{"Transfer":{"amount":42}}"#;
+        let data = pack_instruction(json).unwrap();
+        assert_eq!(data, TokenInstruction::Transfer { amount: 42 }.pack());
+        assert_eq!(unpack_instruction(&data).unwrap(), json);
+    }
+
+    #[test]
+    fn test_unpack_state() {
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_from_array([1; 32])),
+            supply: 1_000,
+            decimals: 2,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        let json = unpack_mint(&data).unwrap();
+        assert_eq!(serde_json::from_str::<Mint>(&json).unwrap(), mint);
+
+        let account = Account {
+            mint: Pubkey::new_from_array([2; 32]),
+            owner: Pubkey::new_from_array([3; 32]),
+            amount: 7,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(account, &mut data).unwrap();
+        let json = unpack_account(&data).unwrap();
+        assert_eq!(serde_json::from_str::<Account>(&json).unwrap(), account);
+    }
+}
@@ -0,0 +1,147 @@
+//! The async client against a bank running the native build of the program.
+//!
+//! Every step goes through `Token`, so the flow builds no instruction of its
+//! own.
+
+#![cfg(feature = "client")]
+
+use solana_program::{instruction::InstructionError, program_option::COption, pubkey::Pubkey};
+use solana_program_test::{processor, tokio, BanksClient, ProgramTest};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use test_token_solana::{
+    client::{ClientError, Token},
+    error::TokenError,
+    id,
+    processor::Processor,
+    state::AccountState,
+};
+
+async fn start() -> (BanksClient, Keypair) {
+    let program_test = ProgramTest::new("test_token_solana", id(), processor!(Processor::process));
+    let (banks_client, payer, _) = program_test.start().await;
+    (banks_client, payer)
+}
+
+#[tokio::test]
+async fn test_token_flow() {
+    let (banks_client, payer) = start().await;
+    let mint_authority = Keypair::new();
+    let token = Token::create_mint(
+        banks_client,
+        payer,
+        &Keypair::new(),
+        &mint_authority.pubkey(),
+        None,
+        2,
+    )
+    .await
+    .unwrap();
+    let mint = token.get_mint_info().await.unwrap();
+    assert_eq!(mint.mint_authority, COption::Some(mint_authority.pubkey()));
+    assert_eq!(mint.decimals, 2);
+
+    let (alice, bob, delegate) = (Keypair::new(), Keypair::new(), Keypair::new());
+    let (alice_account, bob_account) = (Keypair::new(), Keypair::new());
+    token
+        .create_account(&alice_account, &alice.pubkey())
+        .await
+        .unwrap();
+    token
+        .create_account(&bob_account, &bob.pubkey())
+        .await
+        .unwrap();
+    let account = token
+        .get_account_info(&alice_account.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(account.mint, *token.mint());
+    assert_eq!(account.owner, alice.pubkey());
+    assert_eq!(account.state, AccountState::Initialized);
+
+    token
+        .mint_to(&alice_account.pubkey(), &mint_authority, 1_000)
+        .await
+        .unwrap();
+    token
+        .transfer(&alice_account.pubkey(), &bob_account.pubkey(), &alice, 300)
+        .await
+        .unwrap();
+    token
+        .approve(&bob_account.pubkey(), &delegate.pubkey(), &bob, 100)
+        .await
+        .unwrap();
+    token
+        .transfer(
+            &bob_account.pubkey(),
+            &alice_account.pubkey(),
+            &delegate,
+            40,
+        )
+        .await
+        .unwrap();
+    token
+        .burn(&alice_account.pubkey(), &alice, 140)
+        .await
+        .unwrap();
+
+    let alice_account = token
+        .get_account_info(&alice_account.pubkey())
+        .await
+        .unwrap();
+    let bob_account = token.get_account_info(&bob_account.pubkey()).await.unwrap();
+    assert_eq!(alice_account.amount, 600);
+    assert_eq!(bob_account.amount, 260);
+    assert_eq!(bob_account.delegate, COption::Some(delegate.pubkey()));
+    assert_eq!(bob_account.delegated_amount, 60);
+    assert_eq!(token.get_mint_info().await.unwrap().supply, 860);
+}
+
+#[tokio::test]
+async fn test_token_errors() {
+    let (banks_client, payer) = start().await;
+    let owner = Keypair::new();
+    let token = Token::create_mint(
+        banks_client,
+        payer,
+        &Keypair::new(),
+        &owner.pubkey(),
+        None,
+        0,
+    )
+    .await
+    .unwrap();
+    let (source, destination) = (Keypair::new(), Keypair::new());
+    token
+        .create_account(&source, &owner.pubkey())
+        .await
+        .unwrap();
+    token
+        .create_account(&destination, &owner.pubkey())
+        .await
+        .unwrap();
+
+    // the program's error comes back as the failed transaction's
+    assert!(matches!(
+        token
+            .transfer(&source.pubkey(), &destination.pubkey(), &owner, 1)
+            .await,
+        Err(ClientError::Transaction(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code),
+        ))) if code == TokenError::InsufficientFunds as u32
+    ));
+
+    let missing = Pubkey::new_unique();
+    assert!(matches!(
+        token.get_account_info(&missing).await,
+        Err(ClientError::AccountNotFound(address)) if address == missing
+    ));
+    // a system account is not a token account
+    assert!(matches!(
+        token.get_account_info(&token.payer().pubkey()).await,
+        Err(ClientError::Program(_))
+    ));
+}
@@ -0,0 +1,469 @@
+//! Compute-unit budgets of the common instructions.
+//!
+//! Each instruction runs alone in a fresh bank against the SBF build of the
+//! program, and the units it consumed must stay within the budget recorded
+//! below. A new check that pushes an instruction over its budget fails here;
+//! an optimisation should lower the budget with it, so the next regression
+//! is caught as early.
+//!
+//! The units are only meaningful for the SBF build, so the test runs under
+//! `cargo test-sbf`, which enables the `test-sbf` feature, and is ignored
+//! otherwise. Pass `-- --nocapture` to print the per-instruction report.
+//! Running it again with `--features no-log` measures the build without log
+//! lines against the same budgets, and checks that it prints no instruction
+//! names.
+//!
+//! `test_no_log_saves_units` runs every instruction on both builds and checks
+//! that dropping the log lines saves at least one line's cost. It needs the
+//! stripped build next to the default one, so it only runs when asked:
+//!
+//! ```text
+//! cargo build-sbf --features no-log --sbf-out-dir target/deploy/no-log
+//! cargo test-sbf --test compute_units -- --include-ignored
+//! ```
+
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{read_file, tokio, ProgramTest};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    bpf_loader,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use std::path::PathBuf;
+use test_token_solana::{
+    id,
+    instruction::{
+        approve, burn, close_account, freeze_account, initialize_account, initialize_mint,
+        mint_to, revoke, set_authority, thaw_account, transfer, transfer_checked, AuthorityType,
+        MAX_SIGNERS,
+    },
+    state::{Account, AccountState, Mint, Multisig},
+};
+
+/// Ceiling for a whole transaction, well above any budget
+const MAX_UNITS: u64 = 200_000;
+
+/// File name of the SBF build in `SBF_OUT_DIR`
+const PROGRAM_FILE: &str = "test_token_solana.so";
+/// Directory under `SBF_OUT_DIR` holding the build with `--features no-log`
+const NO_LOG_DIR: &str = "no-log";
+/// Least a log line costs: `sol_log` charges at least the syscall base cost,
+/// and every instruction logs at least its name
+const LOG_LINE_UNITS: u64 = 100;
+
+const INITIALIZE_MINT_BUDGET: u64 = 6_500;
+const INITIALIZE_ACCOUNT_BUDGET: u64 = 9_000;
+const MINT_TO_BUDGET: u64 = 9_500;
+const TRANSFER_BUDGET: u64 = 11_000;
+const TRANSFER_CHECKED_BUDGET: u64 = 13_000;
+const TRANSFER_MULTISIG_BUDGET: u64 = 16_000;
+const APPROVE_BUDGET: u64 = 6_500;
+const REVOKE_BUDGET: u64 = 6_000;
+const BURN_BUDGET: u64 = 9_500;
+const FREEZE_ACCOUNT_BUDGET: u64 = 8_500;
+const THAW_ACCOUNT_BUDGET: u64 = 8_500;
+const CLOSE_ACCOUNT_BUDGET: u64 = 6_500;
+const SET_AUTHORITY_BUDGET: u64 = 6_500;
+
+const DECIMALS: u8 = 2;
+
+struct Case {
+    name: &'static str,
+    budget: u64,
+    instruction: Instruction,
+    /// Program accounts the bank starts with, besides the payer's
+    accounts: Vec<(Pubkey, SolanaAccount)>,
+}
+
+struct Measurement {
+    name: &'static str,
+    budget: u64,
+    consumed: u64,
+}
+
+struct Outcome {
+    consumed: u64,
+    /// Whether the program logged the instruction's name
+    logged_name: bool,
+}
+
+/// The keys every case shares; `owner` signs as every authority, except
+/// that `multisig` needs all of `multisig_signers`
+struct Keys {
+    owner: Keypair,
+    multisig: Pubkey,
+    multisig_signers: Vec<Keypair>,
+    mint: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    delegate: Pubkey,
+}
+
+fn program_account<T: Pack>(value: T) -> SolanaAccount {
+    let mut account = SolanaAccount::new(Rent::default().minimum_balance(T::LEN), T::LEN, &id());
+    T::pack(value, &mut account.data).unwrap();
+    account
+}
+
+fn blank_account(len: usize) -> SolanaAccount {
+    SolanaAccount::new(Rent::default().minimum_balance(len), len, &id())
+}
+
+fn mint(keys: &Keys) -> Mint {
+    Mint {
+        mint_authority: COption::Some(keys.owner.pubkey()),
+        supply: 1_000,
+        decimals: DECIMALS,
+        is_initialized: true,
+        freeze_authority: COption::Some(keys.owner.pubkey()),
+        ..Mint::default()
+    }
+}
+
+fn account(keys: &Keys, amount: u64) -> Account {
+    Account {
+        mint: keys.mint,
+        owner: keys.owner.pubkey(),
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    }
+}
+
+/// The mint with its whole supply in `source` and an empty `destination`
+fn funded(keys: &Keys) -> Vec<(Pubkey, SolanaAccount)> {
+    vec![
+        (keys.mint, program_account(mint(keys))),
+        (keys.source, program_account(account(keys, 1_000))),
+        (keys.destination, program_account(account(keys, 0))),
+    ]
+}
+
+fn cases(keys: &Keys) -> Vec<Case> {
+    let owner = keys.owner.pubkey();
+    let multisig_signers: Vec<Pubkey> = keys
+        .multisig_signers
+        .iter()
+        .map(|signer| signer.pubkey())
+        .collect();
+    let mut signers = [Pubkey::default(); MAX_SIGNERS];
+    signers.copy_from_slice(&multisig_signers);
+    vec![
+        Case {
+            name: "InitializeMint",
+            budget: INITIALIZE_MINT_BUDGET,
+            instruction: initialize_mint(&id(), &keys.mint, &owner, Some(&owner), DECIMALS)
+                .unwrap(),
+            accounts: vec![(keys.mint, blank_account(Mint::LEN))],
+        },
+        Case {
+            name: "InitializeAccount",
+            budget: INITIALIZE_ACCOUNT_BUDGET,
+            instruction: initialize_account(&id(), &keys.source, &keys.mint, &owner).unwrap(),
+            accounts: vec![
+                (keys.mint, program_account(mint(keys))),
+                (keys.source, blank_account(Account::LEN)),
+            ],
+        },
+        Case {
+            name: "MintTo",
+            budget: MINT_TO_BUDGET,
+            instruction: mint_to(&id(), &keys.mint, &keys.destination, &owner, &[], 100).unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "Transfer",
+            budget: TRANSFER_BUDGET,
+            instruction: transfer(&id(), &keys.source, &keys.destination, &owner, &[], 100)
+                .unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "TransferChecked",
+            budget: TRANSFER_CHECKED_BUDGET,
+            instruction: transfer_checked(
+                &id(),
+                &keys.source,
+                &keys.mint,
+                &keys.destination,
+                &owner,
+                &[],
+                100,
+                DECIMALS,
+            )
+            .unwrap(),
+            accounts: funded(keys),
+        },
+        // Every signer is matched against every multisig key, which makes this
+        // the case most sensitive to the cost of comparing keys
+        Case {
+            name: "TransferMultisig",
+            budget: TRANSFER_MULTISIG_BUDGET,
+            instruction: transfer(
+                &id(),
+                &keys.source,
+                &keys.destination,
+                &keys.multisig,
+                &multisig_signers.iter().collect::<Vec<_>>(),
+                100,
+            )
+            .unwrap(),
+            accounts: vec![
+                (keys.mint, program_account(mint(keys))),
+                (
+                    keys.source,
+                    program_account(Account {
+                        owner: keys.multisig,
+                        ..account(keys, 1_000)
+                    }),
+                ),
+                (keys.destination, program_account(account(keys, 0))),
+                (
+                    keys.multisig,
+                    program_account(Multisig {
+                        m: MAX_SIGNERS as u8,
+                        n: MAX_SIGNERS as u8,
+                        is_initialized: true,
+                        signers,
+                    }),
+                ),
+            ],
+        },
+        Case {
+            name: "Approve",
+            budget: APPROVE_BUDGET,
+            instruction: approve(&id(), &keys.source, &keys.delegate, &owner, &[], 100).unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "Revoke",
+            budget: REVOKE_BUDGET,
+            instruction: revoke(&id(), &keys.source, &owner, &[]).unwrap(),
+            accounts: vec![
+                (keys.mint, program_account(mint(keys))),
+                (
+                    keys.source,
+                    program_account(Account {
+                        delegate: COption::Some(keys.delegate),
+                        delegated_amount: 100,
+                        ..account(keys, 1_000)
+                    }),
+                ),
+            ],
+        },
+        Case {
+            name: "Burn",
+            budget: BURN_BUDGET,
+            instruction: burn(&id(), &keys.source, &keys.mint, &owner, &[], 100).unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "FreezeAccount",
+            budget: FREEZE_ACCOUNT_BUDGET,
+            instruction: freeze_account(&id(), &keys.source, &keys.mint, &owner, &[]).unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "ThawAccount",
+            budget: THAW_ACCOUNT_BUDGET,
+            instruction: thaw_account(&id(), &keys.source, &keys.mint, &owner, &[]).unwrap(),
+            accounts: vec![
+                (keys.mint, program_account(mint(keys))),
+                (
+                    keys.source,
+                    program_account(Account {
+                        state: AccountState::Frozen,
+                        ..account(keys, 1_000)
+                    }),
+                ),
+            ],
+        },
+        Case {
+            name: "CloseAccount",
+            budget: CLOSE_ACCOUNT_BUDGET,
+            instruction: close_account(&id(), &keys.destination, &keys.source, &owner, &[])
+                .unwrap(),
+            accounts: funded(keys),
+        },
+        Case {
+            name: "SetAuthority",
+            budget: SET_AUTHORITY_BUDGET,
+            instruction: set_authority(
+                &id(),
+                &keys.mint,
+                Some(&keys.delegate),
+                AuthorityType::MintTokens,
+                &owner,
+                &[],
+            )
+            .unwrap(),
+            accounts: funded(keys),
+        },
+    ]
+}
+
+fn new_keys() -> Keys {
+    Keys {
+        owner: Keypair::new(),
+        multisig: Pubkey::new_unique(),
+        multisig_signers: (0..MAX_SIGNERS).map(|_| Keypair::new()).collect(),
+        mint: Pubkey::new_unique(),
+        source: Pubkey::new_unique(),
+        destination: Pubkey::new_unique(),
+        delegate: Pubkey::new_unique(),
+    }
+}
+
+fn keypairs(keys: &Keys) -> Vec<&Keypair> {
+    let mut keypairs = vec![&keys.owner];
+    keypairs.extend(keys.multisig_signers.iter());
+    keypairs
+}
+
+fn sbf_out_dir() -> PathBuf {
+    std::env::var("SBF_OUT_DIR")
+        .or_else(|_| std::env::var("BPF_OUT_DIR"))
+        .map(PathBuf::from)
+        .expect("SBF_OUT_DIR is set by `cargo test-sbf`")
+}
+
+/// Runs the case's instruction alone in a fresh bank with `program`, an SBF
+/// build, deployed as the token program, signed by whichever of `keypairs`
+/// it needs
+async fn measure(case: &Case, keypairs: &[&Keypair], program: &[u8]) -> Outcome {
+    let mut program_test = ProgramTest::default();
+    program_test.set_compute_max_units(MAX_UNITS);
+    program_test.add_account(
+        id(),
+        SolanaAccount {
+            lamports: Rent::default().minimum_balance(program.len()).max(1),
+            data: program.to_vec(),
+            owner: bpf_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+    for (key, account) in case.accounts.iter() {
+        program_test.add_account(*key, account.clone());
+    }
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut signers = vec![&payer];
+    signers.extend(keypairs.iter().filter(|keypair| {
+        case.instruction
+            .accounts
+            .iter()
+            .any(|meta| meta.is_signer && meta.pubkey == keypair.pubkey())
+    }));
+    let transaction = Transaction::new_signed_with_payer(
+        std::slice::from_ref(&case.instruction),
+        Some(&payer.pubkey()),
+        &signers,
+        recent_blockhash,
+    );
+    let outcome = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert_eq!(outcome.result, Ok(()), "{} failed", case.name);
+    let metadata = outcome.metadata.unwrap();
+    Outcome {
+        consumed: metadata.compute_units_consumed,
+        logged_name: metadata
+            .log_messages
+            .iter()
+            .any(|line| line.starts_with("Program log: Instruction: ")),
+    }
+}
+
+/// Prints one line per instruction: units consumed, budget, and the share of
+/// the budget used
+fn report(measurements: &[Measurement]) {
+    println!(
+        "{:<20} {:>10} {:>10} {:>6}",
+        "instruction", "consumed", "budget", "used"
+    );
+    for measurement in measurements {
+        println!(
+            "{:<20} {:>10} {:>10} {:>5}%",
+            measurement.name,
+            measurement.consumed,
+            measurement.budget,
+            measurement.consumed * 100 / measurement.budget
+        );
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-sbf"),
+    ignore = "compute units are measured under `cargo test-sbf`"
+)]
+async fn test_compute_units() {
+    let program = read_file(sbf_out_dir().join(PROGRAM_FILE));
+    let keys = new_keys();
+    let mut measurements = vec![];
+    for case in cases(&keys) {
+        let outcome = measure(&case, &keypairs(&keys), &program).await;
+        assert_eq!(
+            outcome.logged_name,
+            !cfg!(feature = "no-log"),
+            "{} logged its name under the wrong feature set",
+            case.name
+        );
+        measurements.push(Measurement {
+            name: case.name,
+            budget: case.budget,
+            consumed: outcome.consumed,
+        });
+    }
+    report(&measurements);
+
+    let over: Vec<&str> = measurements
+        .iter()
+        .filter(|measurement| measurement.consumed > measurement.budget)
+        .map(|measurement| measurement.name)
+        .collect();
+    assert!(over.is_empty(), "over budget: {:?}", over);
+}
+
+#[tokio::test]
+#[ignore = "needs the no-log SBF build as well, see the module docs"]
+async fn test_no_log_saves_units() {
+    let out_dir = sbf_out_dir();
+    let logging = read_file(out_dir.join(PROGRAM_FILE));
+    let stripped = read_file(out_dir.join(NO_LOG_DIR).join(PROGRAM_FILE));
+    let keys = new_keys();
+
+    println!(
+        "{:<20} {:>10} {:>10} {:>6}",
+        "instruction", "logging", "no-log", "saved"
+    );
+    let mut short = vec![];
+    for case in cases(&keys) {
+        let with_logs = measure(&case, &keypairs(&keys), &logging).await;
+        let without_logs = measure(&case, &keypairs(&keys), &stripped).await;
+        assert!(
+            with_logs.logged_name && !without_logs.logged_name,
+            "{}: the default and no-log builds are swapped",
+            case.name
+        );
+        let saved = with_logs.consumed.saturating_sub(without_logs.consumed);
+        println!(
+            "{:<20} {:>10} {:>10} {:>6}",
+            case.name, with_logs.consumed, without_logs.consumed, saved
+        );
+        if saved < LOG_LINE_UNITS {
+            short.push(case.name);
+        }
+    }
+    assert!(
+        short.is_empty(),
+        "no-log build not cheaper by a log line: {:?}",
+        short
+    );
+}
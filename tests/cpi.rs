@@ -0,0 +1,321 @@
+//! A tiny caller program that moves tokens out of an account owned by its own
+//! program-derived address through `cpi::transfer`.
+//!
+//! Cross-program invocations are routed to `Processor::process` by syscall
+//! stubs, which also grant the signature of any address derived from the
+//! caller's seeds and track the invocation stack height.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{Instruction, TRANSACTION_LEVEL_STACK_HEIGHT},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+};
+use solana_sdk::account::Account as SolanaAccount;
+use std::{cell::Cell, convert::TryInto, sync::Once};
+use test_token_solana::{
+    cpi,
+    error::TokenError,
+    instruction,
+    processor::Processor,
+    state::{Account, AccountState, InterestBearingConfig, Mint, TransferFeeConfig},
+};
+
+const CALLER_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+const AUTHORITY_SEED: &[u8] = b"authority";
+
+thread_local! {
+    static CPI_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CpiStubs;
+
+impl SyscallStubs for CpiStubs {
+    fn sol_get_stack_height(&self) -> u64 {
+        (TRANSACTION_LEVEL_STACK_HEIGHT + CPI_DEPTH.with(Cell::get)) as u64
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id != test_token_solana::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &CALLER_ID))
+            .collect::<Result<Vec<_>, _>>()?;
+        let infos = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                info.is_signer |= pda_signers.contains(info.key);
+                if meta.is_signer && !info.is_signer {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        CPI_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        let result = Processor::process(&instruction.program_id, &infos, &instruction.data);
+        CPI_DEPTH.with(|depth| depth.set(depth.get() - 1));
+        result
+    }
+}
+
+fn set_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(CpiStubs));
+    });
+}
+
+/// Accounts: token program, source, destination, source owner (PDA).
+/// Data: amount as a little-endian `u64`.
+fn process_caller(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount = u64::from_le_bytes(data.try_into().unwrap());
+    let (_, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], program_id);
+    cpi::transfer(
+        &accounts[0],
+        &accounts[1],
+        &accounts[2],
+        &accounts[3],
+        amount,
+        &[&[AUTHORITY_SEED, &[bump]]],
+    )
+}
+
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> SolanaAccount {
+    let mut account = SolanaAccount::new(0, Account::LEN, &test_token_solana::id());
+    Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    }
+    .pack_into_slice(&mut account.data);
+    account
+}
+
+#[test]
+fn test_cpi_transfer_signed_by_pda() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let (authority_key, _) = Pubkey::find_program_address(&[AUTHORITY_SEED], &CALLER_ID);
+    let mint_key = Pubkey::new_unique();
+    let source_key = Pubkey::new_unique();
+    let destination_key = Pubkey::new_unique();
+
+    let mut mint = SolanaAccount::new(0, Mint::LEN, &token_program_id);
+    Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply: 100,
+        decimals: 2,
+        is_initialized: true,
+        freeze_authority: COption::None,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        default_account_state: AccountState::Initialized,
+        transfer_fee_config: TransferFeeConfig::default(),
+        withheld_amount: 0,
+        interest_bearing_config: InterestBearingConfig::default(),
+        pause_authority: COption::None,
+        paused: false,
+        transfer_hook_authority: COption::None,
+        transfer_hook_program_id: COption::None,
+        metadata_pointer: COption::None,
+        transfer_policy: COption::None,
+    }
+    .pack_into_slice(&mut mint.data);
+    let mut token_program = SolanaAccount::default();
+    let mut authority = SolanaAccount::default();
+    let mut source = token_account(&mint_key, &authority_key, 100);
+    let mut destination = token_account(&mint_key, &Pubkey::new_unique(), 0);
+    let wrong_program_key = Pubkey::new_unique();
+    let mut wrong_program = SolanaAccount::default();
+
+    {
+        let accounts = [
+            (&token_program_id, false, &mut token_program).into(),
+            (&source_key, false, &mut source).into(),
+            (&destination_key, false, &mut destination).into(),
+            (&authority_key, false, &mut authority).into(),
+        ];
+        process_caller(&CALLER_ID, &accounts, &40u64.to_le_bytes()).unwrap();
+
+        // an impostor token program is refused before anything is invoked
+        let mut accounts = accounts;
+        accounts[0] = (&wrong_program_key, false, &mut wrong_program).into();
+        assert_eq!(
+            process_caller(&CALLER_ID, &accounts, &40u64.to_le_bytes()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    assert_eq!(Account::unpack(&source.data).unwrap().amount, 60);
+    assert_eq!(Account::unpack(&destination.data).unwrap().amount, 40);
+}
+
+#[test]
+fn test_cpi_transfer_without_pda_signature() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let owner_key = Pubkey::new_unique();
+    let mint_key = Pubkey::new_unique();
+    let source_key = Pubkey::new_unique();
+    let destination_key = Pubkey::new_unique();
+
+    let mut token_program = SolanaAccount::default();
+    let mut owner = SolanaAccount::default();
+    let mut source = token_account(&mint_key, &owner_key, 100);
+    let mut destination = token_account(&mint_key, &owner_key, 0);
+
+    // the caller's seeds do not derive the owner, so nobody signs for it
+    let accounts = [
+        (&token_program_id, false, &mut token_program).into(),
+        (&source_key, false, &mut source).into(),
+        (&destination_key, false, &mut destination).into(),
+        (&owner_key, false, &mut owner).into(),
+    ];
+    assert_eq!(
+        process_caller(&CALLER_ID, &accounts, &40u64.to_le_bytes()),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+}
+
+#[test]
+fn test_cpi_transfer_with_wrong_seeds() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    // the caller's own address, but from other seeds than it signs with
+    let (owner_key, _) = Pubkey::find_program_address(&[b"other"], &CALLER_ID);
+    assert!(!owner_key.is_on_curve());
+    let mint_key = Pubkey::new_unique();
+    let source_key = Pubkey::new_unique();
+    let destination_key = Pubkey::new_unique();
+
+    let mut token_program = SolanaAccount::default();
+    let mut owner = SolanaAccount::default();
+    let mut source = token_account(&mint_key, &owner_key, 100);
+    let mut destination = token_account(&mint_key, &Pubkey::new_unique(), 0);
+
+    {
+        let accounts = [
+            (&token_program_id, false, &mut token_program).into(),
+            (&source_key, false, &mut source).into(),
+            (&destination_key, false, &mut destination).into(),
+            (&owner_key, false, &mut owner).into(),
+        ];
+        assert_eq!(
+            process_caller(&CALLER_ID, &accounts, &40u64.to_le_bytes()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    assert_eq!(Account::unpack(&source.data).unwrap().amount, 100);
+}
+
+#[test]
+fn test_cpi_guard_blocks_owner_signature() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let owner_key = Pubkey::new_unique();
+    let delegate_key = Pubkey::new_unique();
+    let mint_key = Pubkey::new_unique();
+    let source_key = Pubkey::new_unique();
+    let destination_key = Pubkey::new_unique();
+
+    let mut token_program = SolanaAccount::default();
+    let mut owner = SolanaAccount::default();
+    let mut delegate = SolanaAccount::default();
+    let mut source = token_account(&mint_key, &owner_key, 100);
+    let mut destination = token_account(&mint_key, &Pubkey::new_unique(), 0);
+
+    // the wallet turns the guard on and approves a delegate, both top-level
+    {
+        let instruction =
+            instruction::enable_cpi_guard(&token_program_id, &source_key, &owner_key, &[])
+                .unwrap();
+        let infos = [
+            (&source_key, false, &mut source).into(),
+            (&owner_key, true, &mut owner).into(),
+        ];
+        Processor::process(&token_program_id, &infos, &instruction.data).unwrap();
+    }
+    {
+        let instruction = instruction::approve(
+            &token_program_id,
+            &source_key,
+            &delegate_key,
+            &owner_key,
+            &[],
+            30,
+        )
+        .unwrap();
+        let infos = [
+            (&source_key, false, &mut source).into(),
+            (&delegate_key, false, &mut delegate).into(),
+            (&owner_key, true, &mut owner).into(),
+        ];
+        Processor::process(&token_program_id, &infos, &instruction.data).unwrap();
+    }
+
+    // a program handed the owner's signature cannot spend with it
+    {
+        let accounts = [
+            (&token_program_id, false, &mut token_program).into(),
+            (&source_key, false, &mut source).into(),
+            (&destination_key, false, &mut destination).into(),
+            (&owner_key, true, &mut owner).into(),
+        ];
+        assert_eq!(
+            process_caller(&CALLER_ID, &accounts, &40u64.to_le_bytes()),
+            Err(TokenError::CpiGuarded.into())
+        );
+    }
+
+    // but a delegate's signature still goes through
+    {
+        let accounts = [
+            (&token_program_id, false, &mut token_program).into(),
+            (&source_key, false, &mut source).into(),
+            (&destination_key, false, &mut destination).into(),
+            (&delegate_key, true, &mut delegate).into(),
+        ];
+        process_caller(&CALLER_ID, &accounts, &30u64.to_le_bytes()).unwrap();
+    }
+
+    // and so does the owner's, in a top-level instruction
+    {
+        let instruction = instruction::transfer(
+            &token_program_id,
+            &source_key,
+            &destination_key,
+            &owner_key,
+            &[],
+            40,
+        )
+        .unwrap();
+        let infos = [
+            (&source_key, false, &mut source).into(),
+            (&destination_key, false, &mut destination).into(),
+            (&owner_key, true, &mut owner).into(),
+        ];
+        Processor::process(&token_program_id, &infos, &instruction.data).unwrap();
+    }
+
+    assert_eq!(Account::unpack(&source.data).unwrap().amount, 30);
+    assert_eq!(Account::unpack(&destination.data).unwrap().amount, 70);
+}
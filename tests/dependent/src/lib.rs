@@ -0,0 +1,51 @@
+//! A tiny program that depends on the token program as a library, with
+//! `no-entrypoint`, and moves tokens out of an account owned by its own
+//! program-derived address through `cpi::transfer`.
+//!
+//! Without the feature the SBF build would have two `entrypoint` symbols and
+//! fail to link. Check it with
+//! `cargo build-sbf --manifest-path tests/dependent/Cargo.toml`.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+use test_token_solana::{cpi, error::TokenError, state::Account};
+
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+entrypoint!(process_instruction);
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let amount = instruction_data
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    let (authority, bump) = Pubkey::find_program_address(&[AUTHORITY_SEED], program_id);
+    let source = Account::unpack(&source_info.data.borrow())?;
+    if source.owner != authority || authority_info.key != &authority {
+        return Err(TokenError::OwnerMismatch.into());
+    }
+    cpi::transfer(
+        token_program_info,
+        source_info,
+        destination_info,
+        authority_info,
+        amount,
+        &[&[AUTHORITY_SEED, &[bump]]],
+    )
+}
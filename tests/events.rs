@@ -0,0 +1,151 @@
+//! Events as an indexer sees them: `Program data:` lines in the transaction
+//! metadata, decoded with `parse_event`.
+//!
+//! The native `ProgramTest` stubs do not record `sol_log_data`, so the test
+//! runs the SBF build under `cargo test-sbf`, which enables the `test-sbf`
+//! feature, and is ignored otherwise. The processor's unit tests cover the
+//! events without it.
+
+#![cfg(feature = "events")]
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{tokio, BanksClient, ProgramTest};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    hash::Hash,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use test_token_solana::{
+    events::{parse_event, ApproveEvent, BurnEvent, MintEvent, TokenEvent, TransferEvent},
+    id,
+    instruction::{approve, burn, mint_to, transfer},
+    state::{Account, AccountState, Mint},
+};
+
+fn program_account<T: Pack>(value: T) -> SolanaAccount {
+    let mut account = SolanaAccount::new(Rent::default().minimum_balance(T::LEN), T::LEN, &id());
+    T::pack(value, &mut account.data).unwrap();
+    account
+}
+
+/// Sends `instruction` signed by `owner` and decodes the events it logged
+async fn events(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    owner: &Keypair,
+    recent_blockhash: Hash,
+    instruction: Instruction,
+) -> Vec<TokenEvent> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer, owner],
+        recent_blockhash,
+    );
+    let outcome = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert_eq!(outcome.result, Ok(()));
+    outcome
+        .metadata
+        .unwrap()
+        .log_messages
+        .iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .map(|data| parse_event(&STANDARD.decode(data).unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test-sbf"),
+    ignore = "events are logged by the SBF build under `cargo test-sbf`"
+)]
+async fn test_events() {
+    let owner = Keypair::new();
+    let (mint, source, destination, delegate) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let account = |amount| Account {
+        mint,
+        owner: owner.pubkey(),
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    };
+    let mut program_test = ProgramTest::new("test_token_solana", id(), None);
+    program_test.add_account(
+        mint,
+        program_account(Mint {
+            mint_authority: COption::Some(owner.pubkey()),
+            supply: 1_000,
+            decimals: 2,
+            is_initialized: true,
+            ..Mint::default()
+        }),
+    );
+    program_test.add_account(source, program_account(account(1_000)));
+    program_test.add_account(destination, program_account(account(0)));
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let cases = vec![
+        (
+            transfer(&id(), &source, &destination, &owner.pubkey(), &[], 300).unwrap(),
+            TokenEvent::Transfer(TransferEvent {
+                source,
+                destination,
+                authority: owner.pubkey(),
+                amount: 300,
+            }),
+        ),
+        (
+            mint_to(&id(), &mint, &destination, &owner.pubkey(), &[], 50).unwrap(),
+            TokenEvent::Mint(MintEvent {
+                mint,
+                account: destination,
+                authority: owner.pubkey(),
+                amount: 50,
+            }),
+        ),
+        (
+            burn(&id(), &source, &mint, &owner.pubkey(), &[], 20).unwrap(),
+            TokenEvent::Burn(BurnEvent {
+                account: source,
+                mint,
+                authority: owner.pubkey(),
+                amount: 20,
+            }),
+        ),
+        (
+            approve(&id(), &source, &delegate, &owner.pubkey(), &[], 10).unwrap(),
+            TokenEvent::Approve(ApproveEvent {
+                source,
+                delegate,
+                owner: owner.pubkey(),
+                amount: 10,
+            }),
+        ),
+    ];
+    for (instruction, expected) in cases {
+        assert_eq!(
+            events(
+                &mut banks_client,
+                &payer,
+                &owner,
+                recent_blockhash,
+                instruction
+            )
+            .await,
+            vec![expected]
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! End-to-end flows driven through the public instruction builders.
+//!
+//! Instructions go through a small harness that enforces the runtime rules
+//! the processor unit tests skip: every account marked as a signer must have
+//! signed, read-only accounts must come back unchanged, only accounts owned by
+//! the program may have their data changed, and lamports are conserved.
+//! Transactions of several instructions see them in the instructions sysvar
+//! and apply all or nothing.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::{
+        self,
+        instructions::{
+            construct_instructions_data, store_current_index, BorrowedAccountMeta,
+            BorrowedInstruction,
+        },
+    },
+};
+use solana_sdk::account::{create_account_for_test, Account as SolanaAccount};
+use std::{collections::HashMap, sync::Once};
+use test_token_solana::{
+    error::TokenError,
+    instruction::{
+        approve, batch, burn, flash_burn_repay, flash_mint_to, initialize_account,
+        initialize_flash_mint, initialize_mint, mint_to, revoke, transfer,
+    },
+    processor::Processor,
+    state::{Account, FlashMintConfig, Mint},
+};
+
+/// Serves the rent sysvar to handlers that read it with `Rent::get`
+struct RentStubs;
+
+impl SyscallStubs for RentStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        solana_program::entrypoint::SUCCESS
+    }
+}
+
+struct TestRuntime {
+    accounts: HashMap<Pubkey, SolanaAccount>,
+}
+
+impl TestRuntime {
+    fn new() -> Self {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| {
+            set_syscall_stubs(Box::new(RentStubs));
+        });
+        let mut accounts = HashMap::new();
+        accounts.insert(sysvar::rent::id(), create_account_for_test(&Rent::default()));
+        Self { accounts }
+    }
+
+    fn create_account(&mut self, key: &Pubkey, space: usize, owner: &Pubkey) {
+        let lamports = Rent::default().minimum_balance(space);
+        self.accounts.insert(*key, SolanaAccount::new(lamports, space, owner));
+    }
+
+    fn get_packed<T: Pack>(&self, key: &Pubkey) -> T {
+        T::unpack_unchecked(&self.accounts[key].data).unwrap()
+    }
+
+    /// Processes a single-instruction transaction signed by `signers`. Account
+    /// changes are only kept when the instruction succeeds.
+    fn process(&mut self, instruction: Instruction, signers: &[&Pubkey]) -> ProgramResult {
+        let mut keys: Vec<Pubkey> = vec![];
+        for meta in instruction.accounts.iter() {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let is_signer: Vec<bool> = keys
+            .iter()
+            .map(|key| {
+                instruction
+                    .accounts
+                    .iter()
+                    .any(|meta| meta.pubkey == *key && meta.is_signer)
+            })
+            .collect();
+        let is_writable: Vec<bool> = keys
+            .iter()
+            .map(|key| {
+                instruction
+                    .accounts
+                    .iter()
+                    .any(|meta| meta.pubkey == *key && meta.is_writable)
+            })
+            .collect();
+        for (key, is_signer) in keys.iter().zip(is_signer.iter()) {
+            if *is_signer && !signers.contains(&key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+
+        let mut accounts: Vec<SolanaAccount> = keys
+            .iter()
+            .map(|key| self.accounts.get(key).cloned().unwrap_or_default())
+            .collect();
+        let before = accounts.clone();
+
+        {
+            let account_infos: Vec<AccountInfo> = keys
+                .iter()
+                .zip(accounts.iter_mut())
+                .enumerate()
+                .map(|(i, (key, account))| {
+                    AccountInfo::new(
+                        key,
+                        is_signer[i],
+                        is_writable[i],
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        account.executable,
+                        account.rent_epoch,
+                    )
+                })
+                .collect();
+            let ordered_infos: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let position = keys.iter().position(|key| *key == meta.pubkey).unwrap();
+                    account_infos[position].clone()
+                })
+                .collect();
+            Processor::process(&instruction.program_id, &ordered_infos, &instruction.data)?;
+        }
+
+        for (i, (after, before)) in accounts.iter().zip(before.iter()).enumerate() {
+            if !is_writable[i] {
+                assert_eq!(after, before, "read-only account {} modified", keys[i]);
+            }
+            if after.owner != instruction.program_id {
+                assert_eq!(after.data, before.data, "foreign account {} modified", keys[i]);
+            }
+        }
+        assert_eq!(
+            accounts.iter().map(|a| a.lamports).sum::<u64>(),
+            before.iter().map(|a| a.lamports).sum::<u64>(),
+            "lamports not conserved"
+        );
+
+        for (key, account) in keys.into_iter().zip(accounts) {
+            self.accounts.insert(key, account);
+        }
+        Ok(())
+    }
+
+    /// Processes `instructions` as one transaction signed by `signers`, with
+    /// the instructions sysvar describing them. Account changes are only kept
+    /// when every instruction succeeds.
+    fn process_transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Pubkey],
+    ) -> ProgramResult {
+        let borrowed = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect::<Vec<_>>();
+        let mut instructions_data = construct_instructions_data(&borrowed);
+
+        let before = self.accounts.clone();
+        let mut result = Ok(());
+        for (index, instruction) in instructions.iter().enumerate() {
+            store_current_index(&mut instructions_data, index as u16);
+            let mut instructions_sysvar = SolanaAccount::new(0, 0, &sysvar::id());
+            instructions_sysvar.data = instructions_data.clone();
+            self.accounts
+                .insert(sysvar::instructions::id(), instructions_sysvar);
+            result = self.process(instruction.clone(), signers);
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_err() {
+            self.accounts = before;
+        } else {
+            self.accounts.remove(&sysvar::instructions::id());
+        }
+        result
+    }
+}
+
+#[test]
+fn test_token_lifecycle() {
+    let program_id = test_token_solana::id();
+    let mut runtime = TestRuntime::new();
+
+    let mint_authority = Pubkey::new_unique();
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let delegate = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let alice_account = Pubkey::new_unique();
+    let bob_account = Pubkey::new_unique();
+
+    runtime.create_account(&mint, Mint::LEN, &program_id);
+    runtime.create_account(&alice_account, Account::LEN, &program_id);
+    runtime.create_account(&bob_account, Account::LEN, &program_id);
+
+    runtime
+        .process(
+            initialize_mint(&program_id, &mint, &mint_authority, None, 2).unwrap(),
+            &[],
+        )
+        .unwrap();
+    runtime
+        .process(
+            initialize_account(&program_id, &alice_account, &mint, &alice).unwrap(),
+            &[],
+        )
+        .unwrap();
+    runtime
+        .process(
+            initialize_account(&program_id, &bob_account, &mint, &bob).unwrap(),
+            &[],
+        )
+        .unwrap();
+
+    runtime
+        .process(
+            mint_to(&program_id, &mint, &alice_account, &mint_authority, &[], 1000).unwrap(),
+            &[&mint_authority],
+        )
+        .unwrap();
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 1000);
+    assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 1000);
+
+    runtime
+        .process(
+            transfer(&program_id, &alice_account, &bob_account, &alice, &[], 100).unwrap(),
+            &[&alice],
+        )
+        .unwrap();
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 900);
+    assert_eq!(runtime.get_packed::<Account>(&bob_account).amount, 100);
+
+    runtime
+        .process(
+            approve(&program_id, &alice_account, &delegate, &alice, &[], 200).unwrap(),
+            &[&alice],
+        )
+        .unwrap();
+    let account = runtime.get_packed::<Account>(&alice_account);
+    assert_eq!(account.delegate, COption::Some(delegate));
+    assert_eq!(account.delegated_amount, 200);
+
+    runtime
+        .process(
+            transfer(&program_id, &alice_account, &bob_account, &delegate, &[], 150).unwrap(),
+            &[&delegate],
+        )
+        .unwrap();
+    let account = runtime.get_packed::<Account>(&alice_account);
+    assert_eq!(account.amount, 750);
+    assert_eq!(account.delegated_amount, 50);
+    assert_eq!(runtime.get_packed::<Account>(&bob_account).amount, 250);
+
+    // the delegate cannot exceed what is left of the approval
+    assert_eq!(
+        runtime.process(
+            transfer(&program_id, &alice_account, &bob_account, &delegate, &[], 51).unwrap(),
+            &[&delegate],
+        ),
+        Err(TokenError::InsufficientDelegatedFunds.into())
+    );
+
+    runtime
+        .process(
+            burn(&program_id, &bob_account, &mint, &bob, &[], 50).unwrap(),
+            &[&bob],
+        )
+        .unwrap();
+    assert_eq!(runtime.get_packed::<Account>(&bob_account).amount, 200);
+    assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 950);
+}
+
+#[test]
+fn test_runtime_checks() {
+    let program_id = test_token_solana::id();
+    let mut runtime = TestRuntime::new();
+
+    let mint_authority = Pubkey::new_unique();
+    let alice = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let alice_account = Pubkey::new_unique();
+    let foreign_mint = Pubkey::new_unique();
+    let foreign_account = Pubkey::new_unique();
+
+    runtime.create_account(&mint, Mint::LEN, &program_id);
+    runtime.create_account(&alice_account, Account::LEN, &program_id);
+    runtime
+        .process(
+            initialize_mint(&program_id, &mint, &mint_authority, None, 2).unwrap(),
+            &[],
+        )
+        .unwrap();
+    runtime
+        .process(
+            initialize_account(&program_id, &alice_account, &mint, &alice).unwrap(),
+            &[],
+        )
+        .unwrap();
+
+    // the transaction is not signed by the mint authority
+    assert_eq!(
+        runtime.process(
+            mint_to(&program_id, &mint, &alice_account, &mint_authority, &[], 1).unwrap(),
+            &[],
+        ),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    // accounts owned by another program are rejected before being read
+    let other_program = Pubkey::new_unique();
+    runtime.create_account(&foreign_mint, Mint::LEN, &other_program);
+    runtime.create_account(&foreign_account, Account::LEN, &program_id);
+    runtime.accounts.get_mut(&foreign_mint).unwrap().data = runtime.accounts[&mint].data.clone();
+    assert_eq!(
+        runtime.process(
+            initialize_account(&program_id, &foreign_account, &foreign_mint, &alice).unwrap(),
+            &[],
+        ),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    assert_eq!(
+        runtime.process(
+            mint_to(&program_id, &foreign_mint, &alice_account, &mint_authority, &[], 1).unwrap(),
+            &[&mint_authority],
+        ),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 0);
+}
+
+#[test]
+fn test_flash_mint() {
+    let program_id = test_token_solana::id();
+    let mut runtime = TestRuntime::new();
+
+    let mint_authority = Pubkey::new_unique();
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let config = Pubkey::new_unique();
+    let alice_account = Pubkey::new_unique();
+    let bob_account = Pubkey::new_unique();
+    let fee_account = Pubkey::new_unique();
+
+    runtime.create_account(&mint, Mint::LEN, &program_id);
+    runtime.create_account(&config, FlashMintConfig::LEN, &program_id);
+    runtime
+        .process(
+            initialize_mint(&program_id, &mint, &mint_authority, None, 2).unwrap(),
+            &[],
+        )
+        .unwrap();
+    for (account, owner) in [
+        (&alice_account, &alice),
+        (&bob_account, &bob),
+        (&fee_account, &mint_authority),
+    ] {
+        runtime.create_account(account, Account::LEN, &program_id);
+        runtime
+            .process(
+                initialize_account(&program_id, account, &mint, owner).unwrap(),
+                &[],
+            )
+            .unwrap();
+    }
+    runtime
+        .process(
+            mint_to(&program_id, &mint, &alice_account, &mint_authority, &[], 100).unwrap(),
+            &[&mint_authority],
+        )
+        .unwrap();
+    runtime
+        .process(
+            initialize_flash_mint(&program_id, &config, &mint, &mint_authority, &[], 5).unwrap(),
+            &[&mint_authority],
+        )
+        .unwrap();
+
+    let flash_mint = |amount| flash_mint_to(&program_id, &mint, &alice_account, &config, amount);
+    let repay = |amount| {
+        flash_burn_repay(
+            &program_id,
+            &alice_account,
+            &mint,
+            &config,
+            &fee_account,
+            &alice,
+            &[],
+            amount,
+        )
+    };
+    let unchanged = |runtime: &TestRuntime| {
+        assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 100);
+        assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 100);
+        assert_eq!(runtime.get_packed::<FlashMintConfig>(&config).outstanding, 0);
+    };
+
+    // missing repay
+    assert_eq!(
+        runtime.process_transaction(&[flash_mint(1000).unwrap()], &[]),
+        Err(TokenError::FlashRepayMissing.into())
+    );
+    unchanged(&runtime);
+
+    // partial repay
+    assert_eq!(
+        runtime.process_transaction(&[flash_mint(1000).unwrap(), repay(999).unwrap()], &[&alice]),
+        Err(TokenError::FlashRepayMissing.into())
+    );
+    unchanged(&runtime);
+
+    // repay before mint
+    assert_eq!(
+        runtime.process_transaction(&[repay(1000).unwrap(), flash_mint(1000).unwrap()], &[&alice]),
+        Err(TokenError::FlashRepayMismatch.into())
+    );
+    unchanged(&runtime);
+
+    // one repay cannot settle two flash mints
+    assert_eq!(
+        runtime.process_transaction(
+            &[
+                flash_mint(1000).unwrap(),
+                flash_mint(1000).unwrap(),
+                repay(1000).unwrap(),
+            ],
+            &[&alice],
+        ),
+        Err(TokenError::FlashMintOutstanding.into())
+    );
+    unchanged(&runtime);
+
+    // the minted tokens cannot leave before the repay
+    assert_eq!(
+        runtime.process_transaction(
+            &[
+                flash_mint(1000).unwrap(),
+                transfer(&program_id, &alice_account, &bob_account, &alice, &[], 1000).unwrap(),
+                repay(1000).unwrap(),
+            ],
+            &[&alice],
+        ),
+        Err(TokenError::InsufficientFunds.into())
+    );
+    unchanged(&runtime);
+
+    // repaid in full, with the fee
+    runtime
+        .process_transaction(
+            &[
+                flash_mint(1000).unwrap(),
+                transfer(&program_id, &alice_account, &bob_account, &alice, &[], 1000).unwrap(),
+                transfer(&program_id, &bob_account, &alice_account, &bob, &[], 1000).unwrap(),
+                repay(1000).unwrap(),
+            ],
+            &[&alice, &bob],
+        )
+        .unwrap();
+    assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 100);
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 95);
+    assert_eq!(runtime.get_packed::<Account>(&fee_account).amount, 5);
+    assert_eq!(runtime.get_packed::<FlashMintConfig>(&config).outstanding, 0);
+}
+
+#[test]
+fn test_batch() {
+    let program_id = test_token_solana::id();
+    let mut runtime = TestRuntime::new();
+
+    let mint_authority = Pubkey::new_unique();
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let delegate = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let alice_account = Pubkey::new_unique();
+    let bob_account = Pubkey::new_unique();
+
+    runtime.create_account(&mint, Mint::LEN, &program_id);
+    runtime.create_account(&alice_account, Account::LEN, &program_id);
+    runtime.create_account(&bob_account, Account::LEN, &program_id);
+    runtime
+        .process(
+            initialize_mint(&program_id, &mint, &mint_authority, None, 2).unwrap(),
+            &[],
+        )
+        .unwrap();
+    for (account, owner) in [(&alice_account, &alice), (&bob_account, &bob)] {
+        runtime
+            .process(
+                initialize_account(&program_id, account, &mint, owner).unwrap(),
+                &[],
+            )
+            .unwrap();
+    }
+    runtime
+        .process(
+            mint_to(&program_id, &mint, &alice_account, &mint_authority, &[], 1000).unwrap(),
+            &[&mint_authority],
+        )
+        .unwrap();
+
+    // approve, spend through the delegate, burn and revoke in one instruction
+    runtime
+        .process(
+            batch(
+                &program_id,
+                &[
+                    approve(&program_id, &alice_account, &delegate, &alice, &[], 300).unwrap(),
+                    transfer(&program_id, &alice_account, &bob_account, &delegate, &[], 200)
+                        .unwrap(),
+                    burn(&program_id, &alice_account, &mint, &alice, &[], 100).unwrap(),
+                    revoke(&program_id, &alice_account, &alice, &[]).unwrap(),
+                ],
+            )
+            .unwrap(),
+            &[&alice, &delegate],
+        )
+        .unwrap();
+    let account = runtime.get_packed::<Account>(&alice_account);
+    assert_eq!(account.amount, 700);
+    assert_eq!(account.delegate, COption::None);
+    assert_eq!(account.delegated_amount, 0);
+    assert_eq!(runtime.get_packed::<Account>(&bob_account).amount, 200);
+    assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 900);
+
+    // a failing inner instruction undoes the ones before it
+    assert_eq!(
+        runtime.process(
+            batch(
+                &program_id,
+                &[
+                    transfer(&program_id, &alice_account, &bob_account, &alice, &[], 100).unwrap(),
+                    burn(&program_id, &bob_account, &mint, &bob, &[], 301).unwrap(),
+                ],
+            )
+            .unwrap(),
+            &[&alice, &bob],
+        ),
+        Err(TokenError::InsufficientFunds.into())
+    );
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 700);
+    assert_eq!(runtime.get_packed::<Account>(&bob_account).amount, 200);
+    assert_eq!(runtime.get_packed::<Mint>(&mint).supply, 900);
+
+    // batches do not nest
+    let inner = batch(
+        &program_id,
+        &[transfer(&program_id, &alice_account, &bob_account, &alice, &[], 1).unwrap()],
+    )
+    .unwrap();
+    assert_eq!(
+        runtime.process(batch(&program_id, &[inner]).unwrap(), &[&alice]),
+        Err(TokenError::InvalidInstruction.into())
+    );
+
+    // every inner instruction must get all the accounts it claims
+    let mut short = batch(
+        &program_id,
+        &[transfer(&program_id, &alice_account, &bob_account, &alice, &[], 1).unwrap()],
+    )
+    .unwrap();
+    short.accounts.pop();
+    assert_eq!(
+        runtime.process(short, &[]),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    assert_eq!(runtime.get_packed::<Account>(&alice_account).amount, 700);
+}
@@ -0,0 +1,487 @@
+//! The generated IDL against the program it describes.
+//!
+//! `tests/fixtures/idl.json` must match what `idl()` generates; after a
+//! deliberate change to the instructions, errors or layouts, run the tests
+//! with `UPDATE_SNAPSHOTS=1` to rewrite it. Each instruction's discriminator
+//! must be the tag its variant packs with. Instruction data encoded from the
+//! IDL the way Anchor's coder encodes it must equal what the builders produce,
+//! and decode back to the same arguments, and the state types must cover the
+//! packed layouts byte for byte.
+
+#![cfg(feature = "idl")]
+
+use serde_json::{json, Value};
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+use std::{collections::BTreeSet, convert::TryInto, env, fs, path::PathBuf, str::FromStr};
+use test_token_solana::{
+    id,
+    idl::{idl, IdlType, ACCOUNT_TYPES, INSTRUCTIONS, MINT_TYPES},
+    instruction::{
+        approve, burn, initialize_mint, mint_to, set_authority, transfer, transfer_checked,
+        AuthorityType, MetadataField, TokenInstruction, BURN_FROM_TAG, MAX_SIGNERS, REVOKE_TAG,
+    },
+    state::{Account, AccountState, Mint, Multisig, PolicyMode, ACCOUNT_FIELDS, MINT_FIELDS},
+};
+
+fn types() -> Vec<Value> {
+    idl()["types"].as_array().unwrap().clone()
+}
+
+fn defined<'a>(types: &'a [Value], ty: &Value) -> &'a Value {
+    let name = &ty["defined"]["name"];
+    &types.iter().find(|defined| &defined["name"] == name).unwrap()["type"]
+}
+
+/// Borsh-encodes `value` as `ty`, as Anchor's coder does
+fn encode(types: &[Value], ty: &Value, value: &Value, data: &mut Vec<u8>) {
+    match ty.as_str() {
+        Some("u8") => data.push(value.as_u64().unwrap() as u8),
+        Some("u64") => data.extend_from_slice(&value.as_u64().unwrap().to_le_bytes()),
+        Some("bool") => data.push(value.as_bool().unwrap() as u8),
+        Some("pubkey") => {
+            data.extend_from_slice(Pubkey::from_str(value.as_str().unwrap()).unwrap().as_ref())
+        }
+        Some(other) => panic!("no encoding for {}", other),
+        None if ty.get("option").is_some() => {
+            data.push(!value.is_null() as u8);
+            if !value.is_null() {
+                encode(types, &ty["option"], value, data);
+            }
+        }
+        None => {
+            let variants = defined(types, ty)["variants"].as_array().unwrap();
+            let index = variants
+                .iter()
+                .position(|variant| variant["name"] == *value)
+                .unwrap();
+            data.push(index as u8);
+        }
+    }
+}
+
+/// Borsh-decodes a `ty` from the front of `data`
+fn decode(types: &[Value], ty: &Value, data: &mut &[u8]) -> Value {
+    let mut take = |len: usize| {
+        let (head, tail) = data.split_at(len);
+        *data = tail;
+        head.to_vec()
+    };
+    match ty.as_str() {
+        Some("u8") => json!(take(1)[0]),
+        Some("u64") => json!(u64::from_le_bytes(take(8).try_into().unwrap())),
+        Some("bool") => json!(take(1)[0] != 0),
+        Some("pubkey") => json!(Pubkey::new_from_array(take(32).try_into().unwrap()).to_string()),
+        Some(other) => panic!("no decoding for {}", other),
+        None if ty.get("option").is_some() => match take(1)[0] {
+            0 => Value::Null,
+            _ => decode(types, &ty["option"], data),
+        },
+        None => {
+            let index = take(1)[0] as usize;
+            defined(types, ty)["variants"][index]["name"].clone()
+        }
+    }
+}
+
+/// Checks the instruction the builder made against the IDL entry its tag
+/// picks: the accounts in order with their flags and addresses, and the data
+/// against `args` encoded from the IDL
+fn assert_matches_idl(instruction: Instruction, args: Value) {
+    let idl = idl();
+    let types = types();
+    let entry = idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["discriminator"] == json!([instruction.data[0]]))
+        .unwrap();
+    let name = entry["name"].as_str().unwrap();
+
+    let accounts = entry["accounts"].as_array().unwrap();
+    assert_eq!(instruction.accounts.len(), accounts.len(), "{}", name);
+    for (meta, account) in instruction.accounts.iter().zip(accounts) {
+        assert_eq!(meta.is_writable, account["writable"] == true, "{}", name);
+        assert_eq!(meta.is_signer, account["signer"] == true, "{}", name);
+        if let Some(address) = account["address"].as_str() {
+            assert_eq!(meta.pubkey.to_string(), address, "{}", name);
+        }
+    }
+
+    let mut data = entry["discriminator"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect::<Vec<_>>();
+    for arg in entry["args"].as_array().unwrap() {
+        encode(&types, &arg["type"], &args[arg["name"].as_str().unwrap()], &mut data);
+    }
+    assert_eq!(data, instruction.data, "{}", name);
+    assert!(TokenInstruction::unpack(&data).is_ok(), "{}", name);
+
+    let mut rest = &instruction.data[1..];
+    let decoded: serde_json::Map<String, Value> = entry["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|arg| {
+            let name = arg["name"].as_str().unwrap().to_string();
+            (name, decode(&types, &arg["type"], &mut rest))
+        })
+        .collect();
+    assert!(rest.is_empty(), "{}", name);
+    assert_eq!(Value::Object(decoded), args, "{}", name);
+}
+
+/// Byte size of a state field
+fn size(ty: &IdlType) -> usize {
+    match ty {
+        IdlType::U8 | IdlType::Bool => 1,
+        IdlType::U16 | IdlType::I16 => 2,
+        IdlType::U32 => 4,
+        IdlType::U64 | IdlType::I64 => 8,
+        IdlType::Pubkey => 32,
+        IdlType::Array(inner, len) => size(inner) * len,
+        IdlType::Defined("COptionPubkey") => 36,
+        IdlType::Defined("COptionU64") => 12,
+        IdlType::Defined("AccountState") | IdlType::Defined("PolicyMode") => 1,
+        other => panic!("{:?} has no fixed size", other),
+    }
+}
+
+#[test]
+fn test_idl_fixture() {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "idl.json"]
+        .iter()
+        .collect();
+    let generated = serde_json::to_string_pretty(&idl()).unwrap() + "\n";
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, generated).unwrap();
+        return;
+    }
+    assert!(
+        fs::read_to_string(&path).unwrap() == generated,
+        "tests/fixtures/idl.json is stale, rerun with UPDATE_SNAPSHOTS=1"
+    );
+}
+
+#[test]
+fn test_idl_instructions() {
+    for (tag, instruction) in INSTRUCTIONS.iter().enumerate() {
+        assert_eq!(instruction.tag as usize, tag, "{}", instruction.name);
+    }
+    assert_eq!(INSTRUCTIONS.len(), BURN_FROM_TAG as usize + 1);
+    let names: BTreeSet<&str> = INSTRUCTIONS.iter().map(|instruction| instruction.name).collect();
+    assert_eq!(names.len(), INSTRUCTIONS.len());
+    for tag in INSTRUCTIONS.len()..=u8::MAX as usize {
+        assert!(TokenInstruction::unpack(&[tag as u8]).is_err());
+    }
+}
+
+/// The IDL name of an instruction, its variant name in snake case
+fn idl_name(instruction: &TokenInstruction) -> String {
+    let variant = format!("{:?}", instruction);
+    let mut name = String::new();
+    for c in variant.chars().take_while(char::is_ascii_alphanumeric) {
+        if c.is_ascii_uppercase() && !name.is_empty() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+#[test]
+fn test_idl_discriminators() {
+    use TokenInstruction::*;
+    let key = Pubkey::new_unique();
+    let mint = InitializeMint {
+        decimals: 2,
+        mint_authority: key,
+        freeze_authority: COption::None,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::None,
+    };
+    let mint2 = InitializeMint2 {
+        decimals: 2,
+        mint_authority: key,
+        freeze_authority: COption::None,
+        max_supply: COption::None,
+        permanent_delegate: COption::None,
+        transfer_fee_authority: COption::None,
+        rate_authority: COption::None,
+        pause_authority: COption::None,
+    };
+    let instructions = vec![
+        mint,
+        InitializeAccount,
+        Transfer { amount: 1 },
+        Approve { amount: 1 },
+        MintTo { amount: 1 },
+        Burn { amount: 1 },
+        Revoke,
+        SetAuthority {
+            authority_type: AuthorityType::CloseAccount,
+            new_authority: COption::None,
+        },
+        FreezeAccount,
+        ThawAccount,
+        TransferChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        ApproveChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        BurnChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        InitializeMultisig { m: 1 },
+        mint2,
+        InitializeAccount2 { owner: key },
+        InitializeAccount3 { owner: key },
+        SyncNative,
+        GetAccountDataSize,
+        AmountToUiAmount { amount: 1 },
+        UiAmountToAmount { ui_amount: "1" },
+        CloseAccount,
+        IncreaseDelegatedAmount { amount: 1 },
+        DecreaseDelegatedAmount { amount: 1 },
+        ApproveWithExpiry {
+            amount: 1,
+            expiry_slot: 2,
+        },
+        TransferBatch { amounts: vec![1] },
+        MintToBatch { amounts: vec![1] },
+        ProposeOwner { new_owner: key },
+        AcceptOwnership,
+        SetDefaultAccountState {
+            state: AccountState::Frozen,
+        },
+        RequireMemoTransfers { enable: true },
+        TransferCheckedWithFee {
+            amount: 1,
+            decimals: 2,
+            fee: 0,
+        },
+        SetTransferFee {
+            transfer_fee_basis_points: 1,
+            maximum_fee: 2,
+        },
+        WithdrawWithheldTokensFromAccounts {
+            num_token_accounts: 1,
+        },
+        HarvestWithheldTokensToMint,
+        UpdateRateInterestBearingMint { rate: 1 },
+        Pause,
+        Resume,
+        Reallocate,
+        ReallocateMint,
+        InitializeImmutableOwner,
+        EnableCpiGuard,
+        DisableCpiGuard,
+        InitializeTransferHook {
+            authority: COption::None,
+            program_id: COption::None,
+        },
+        UpdateTransferHook {
+            program_id: COption::None,
+        },
+        InitializeMetadataPointer {
+            metadata_address: COption::None,
+        },
+        InitializeTokenMetadata {
+            name: "n",
+            symbol: "s",
+            uri: "u",
+        },
+        UpdateTokenMetadataField {
+            field: MetadataField::Name,
+            value: "v",
+        },
+        InitializeAssociatedAccount,
+        RecoverNested,
+        WrapSol { lamports: 1 },
+        UnwrapSol { lamports: 1 },
+        RevokeAll,
+        AssertBalance {
+            minimum: 1,
+            maximum: 2,
+        },
+        AssertSupply {
+            minimum: 1,
+            maximum: 2,
+        },
+        GetAccountBalance,
+        GetMintSupply,
+        SplitAccount { amount: 1 },
+        MergeAccounts {
+            num_source_accounts: 1,
+            close_sources: false,
+        },
+        LockUntilSlot { slot: 1 },
+        InitializePolicy {
+            mode: PolicyMode::Allowlist,
+        },
+        AddPolicyEntry { entry: key },
+        RemovePolicyEntry { entry: key },
+        ApproveEscrowed { amount: 1 },
+        UpdateDecimals { decimals: 3 },
+        InitializeFlashMint { fee: 1 },
+        FlashMintTo { amount: 1 },
+        FlashBurnRepay { amount: 1 },
+        SetSpendingLimit {
+            amount: COption::None,
+        },
+        TransferVested {
+            total_amount: 1,
+            start_slot: 2,
+            end_slot: 3,
+        },
+        ClaimVested,
+        Swap {
+            amount_a: 1,
+            amount_b: 2,
+        },
+        Batch {
+            instructions: vec![vec![0, REVOKE_TAG]],
+        },
+        FreezeAccounts { count: 1 },
+        ThawAccounts { count: 1 },
+        BurnFrom { amount: 1 },
+    ];
+
+    let idl = idl();
+    let entries = idl["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), entries.len());
+    for instruction in &instructions {
+        let name = idl_name(instruction);
+        let entry = entries
+            .iter()
+            .find(|entry| entry["name"] == name.as_str())
+            .unwrap_or_else(|| panic!("{} is not in the IDL", name));
+        assert_eq!(
+            entry["discriminator"],
+            json!([instruction.pack()[0]]),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_idl_matches_builders() {
+    let (source, destination, mint, owner, delegate) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+
+    assert_matches_idl(
+        transfer(&id(), &source, &destination, &owner, &[], 42).unwrap(),
+        json!({ "amount": 42 }),
+    );
+    assert_matches_idl(
+        mint_to(&id(), &mint, &destination, &owner, &[], u64::MAX).unwrap(),
+        json!({ "amount": u64::MAX }),
+    );
+    assert_matches_idl(
+        transfer_checked(&id(), &source, &mint, &destination, &owner, &[], 42, 6).unwrap(),
+        json!({ "amount": 42, "decimals": 6 }),
+    );
+    assert_matches_idl(
+        approve(&id(), &source, &delegate, &owner, &[], 7).unwrap(),
+        json!({ "amount": 7 }),
+    );
+    assert_matches_idl(
+        burn(&id(), &source, &mint, &owner, &[], 7).unwrap(),
+        json!({ "amount": 7 }),
+    );
+    assert_matches_idl(
+        set_authority(
+            &id(),
+            &mint,
+            Some(&delegate),
+            AuthorityType::MintTokens,
+            &owner,
+            &[],
+        )
+        .unwrap(),
+        json!({ "authority_type": "MintTokens", "new_authority": delegate.to_string() }),
+    );
+    assert_matches_idl(
+        set_authority(&id(), &source, None, AuthorityType::CloseAccount, &owner, &[]).unwrap(),
+        json!({ "authority_type": "CloseAccount", "new_authority": null }),
+    );
+    assert_matches_idl(
+        initialize_mint(&id(), &mint, &owner, Some(&delegate), 2).unwrap(),
+        json!({
+            "decimals": 2,
+            "mint_authority": owner.to_string(),
+            "freeze_authority": delegate.to_string(),
+            "max_supply": null,
+            "permanent_delegate": null,
+            "transfer_fee_authority": null,
+            "rate_authority": null,
+            "pause_authority": null,
+        }),
+    );
+}
+
+#[test]
+fn test_idl_multisig_authority() {
+    let signers: Vec<Pubkey> = (0..MAX_SIGNERS).map(|_| Pubkey::new_unique()).collect();
+    let signer_refs: Vec<&Pubkey> = signers.iter().collect();
+    let (source, destination, multisig) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let instruction =
+        transfer(&id(), &source, &destination, &multisig, &signer_refs, 42).unwrap();
+    let entry = &INSTRUCTIONS[instruction.data[0] as usize];
+    let listed = entry.accounts.len();
+    let authority = entry.accounts[listed - 1];
+    assert!(authority.multisig);
+
+    // The multisig itself does not sign; its signers follow it
+    assert!(!instruction.accounts[listed - 1].is_signer);
+    assert_eq!(instruction.accounts.len(), listed + MAX_SIGNERS);
+    assert!(instruction.accounts[listed..]
+        .iter()
+        .all(|meta| meta.is_signer && !meta.is_writable));
+}
+
+#[test]
+fn test_idl_state_layouts() {
+    for (fields, types, len) in [
+        (&MINT_FIELDS[..], &MINT_TYPES[..], Mint::LEN),
+        (&ACCOUNT_FIELDS[..], &ACCOUNT_TYPES[..], Account::LEN),
+    ] {
+        assert_eq!(fields.len(), types.len());
+        let mut offset = 0;
+        for (&(name, field_offset, field_len), ty) in fields.iter().zip(types) {
+            assert_eq!(field_offset, offset, "{}", name);
+            assert_eq!(size(ty), field_len, "{}", name);
+            offset += field_len;
+        }
+        assert_eq!(offset, len);
+    }
+
+    let multisig = types()
+        .into_iter()
+        .find(|ty| ty["name"] == "Multisig")
+        .unwrap();
+    let fields = multisig["type"]["fields"].as_array().unwrap();
+    assert_eq!(fields[3]["type"], json!({ "array": ["pubkey", MAX_SIGNERS] }));
+    assert_eq!(3 + 32 * MAX_SIGNERS, Multisig::LEN);
+}
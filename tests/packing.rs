@@ -0,0 +1,167 @@
+//! Property tests for the hand-rolled instruction and state encodings. The
+//! mint, account and instruction strategies are the crate's `Arbitrary`
+//! impls, enabled through the `proptest` feature.
+
+use proptest::prelude::*;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use test_token_solana::{
+    instruction::{MetadataField, TokenInstruction},
+    state::{
+        Account, FlashMintConfig, Mint, Multisig, PolicyMode, TokenMetadata, TransferPolicy,
+        MAX_NAME_LENGTH, MAX_POLICY_ENTRIES, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
+    },
+};
+
+fn arb_pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn arb_metadata_field() -> impl Strategy<Value = MetadataField> {
+    prop_oneof![
+        Just(MetadataField::Name),
+        Just(MetadataField::Symbol),
+        Just(MetadataField::Uri),
+    ]
+}
+
+/// Strings of at most `max_len` bytes, cut at a character boundary
+fn arb_bounded_string(max_len: usize) -> impl Strategy<Value = String> {
+    ".*".prop_map(move |string: String| {
+        let mut len = string.len().min(max_len);
+        while !string.is_char_boundary(len) {
+            len -= 1;
+        }
+        string[..len].to_string()
+    })
+}
+
+fn arb_token_metadata() -> impl Strategy<Value = TokenMetadata> {
+    (
+        arb_pubkey(),
+        arb_pubkey(),
+        arb_bounded_string(MAX_NAME_LENGTH),
+        arb_bounded_string(MAX_SYMBOL_LENGTH),
+        arb_bounded_string(MAX_URI_LENGTH),
+    )
+        .prop_map(|(update_authority, mint, name, symbol, uri)| TokenMetadata {
+            update_authority,
+            mint,
+            name,
+            symbol,
+            uri,
+        })
+}
+
+fn arb_transfer_policy() -> impl Strategy<Value = TransferPolicy> {
+    (
+        arb_pubkey(),
+        any::<PolicyMode>(),
+        any::<bool>(),
+        prop::collection::vec(arb_pubkey(), 0..=MAX_POLICY_ENTRIES),
+    )
+        .prop_map(|(mint, mode, is_initialized, listed)| {
+            let mut policy = TransferPolicy {
+                mint,
+                mode,
+                is_initialized,
+                len: listed.len() as u8,
+                ..TransferPolicy::default()
+            };
+            policy.entries[..listed.len()].copy_from_slice(&listed);
+            policy
+        })
+}
+
+fn arb_flash_mint_config() -> impl Strategy<Value = FlashMintConfig> {
+    (arb_pubkey(), any::<bool>(), any::<u64>(), any::<u64>()).prop_map(
+        |(mint, is_initialized, fee, outstanding)| FlashMintConfig {
+            mint,
+            is_initialized,
+            fee,
+            outstanding,
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn test_instruction_round_trip(instruction in any::<TokenInstruction<'static>>()) {
+        let packed = instruction.pack();
+        prop_assert_eq!(TokenInstruction::unpack(&packed).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_ui_amount_to_amount_round_trip(ui_amount in ".*") {
+        let instruction = TokenInstruction::UiAmountToAmount { ui_amount: &ui_amount };
+        let packed = instruction.pack();
+        prop_assert_eq!(TokenInstruction::unpack(&packed).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_token_metadata_instructions_round_trip(
+        name in ".*",
+        symbol in ".*",
+        uri in ".*",
+        field in arb_metadata_field(),
+    ) {
+        let instruction = TokenInstruction::InitializeTokenMetadata {
+            name: &name,
+            symbol: &symbol,
+            uri: &uri,
+        };
+        let packed = instruction.pack();
+        prop_assert_eq!(TokenInstruction::unpack(&packed).unwrap(), instruction);
+        let instruction = TokenInstruction::UpdateTokenMetadataField { field, value: &uri };
+        let packed = instruction.pack();
+        prop_assert_eq!(TokenInstruction::unpack(&packed).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_token_metadata_round_trip(metadata in arb_token_metadata()) {
+        let mut packed = vec![0; metadata.account_len()];
+        metadata.pack(&mut packed).unwrap();
+        prop_assert_eq!(TokenMetadata::unpack_from_account_data(&packed).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_mint_round_trip(mint in any::<Mint>()) {
+        let mut packed = vec![0; Mint::LEN];
+        Mint::pack_into_slice(&mint, &mut packed);
+        prop_assert_eq!(Mint::unpack_unchecked(&packed).unwrap(), mint);
+    }
+
+    #[test]
+    fn test_account_round_trip(account in any::<Account>()) {
+        let mut packed = vec![0; Account::LEN];
+        Account::pack_into_slice(&account, &mut packed);
+        prop_assert_eq!(Account::unpack_unchecked(&packed).unwrap(), account);
+    }
+
+    #[test]
+    fn test_transfer_policy_round_trip(policy in arb_transfer_policy()) {
+        let mut packed = vec![0; TransferPolicy::LEN];
+        TransferPolicy::pack_into_slice(&policy, &mut packed);
+        prop_assert_eq!(TransferPolicy::unpack_unchecked(&packed).unwrap(), policy);
+    }
+
+    #[test]
+    fn test_flash_mint_config_round_trip(config in arb_flash_mint_config()) {
+        let mut packed = vec![0; FlashMintConfig::LEN];
+        FlashMintConfig::pack_into_slice(&config, &mut packed);
+        prop_assert_eq!(FlashMintConfig::unpack_unchecked(&packed).unwrap(), config);
+    }
+
+    #[test]
+    fn test_unpack_arbitrary_bytes(data in prop::collection::vec(any::<u8>(), 0..200)) {
+        // errors are fine, panics are not
+        let _ = TokenInstruction::unpack(&data);
+        let _ = Mint::unpack_from_slice(&data);
+        let _ = Account::unpack_from_slice(&data);
+        let _ = Multisig::unpack_from_slice(&data);
+        let _ = TransferPolicy::unpack_from_slice(&data);
+        let _ = FlashMintConfig::unpack_from_slice(&data);
+        let _ = Mint::unpack_unchecked(&data);
+        let _ = Account::unpack_unchecked(&data);
+        let _ = TokenMetadata::unpack(&data);
+    }
+}
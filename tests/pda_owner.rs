@@ -0,0 +1,194 @@
+//! A token account owned by a program-derived address, spent by the program
+//! that derives it.
+//!
+//! Unlike `tests/cpi.rs`, which stubs the invocation, the vault program here
+//! runs next to the token program in a bank, so the address is marked as a
+//! signer by the runtime itself, from the seeds the vault signs with. Seeds
+//! that derive another address make the runtime refuse the invocation as a
+//! privilege escalation, which the native bank turns into a panic, so that
+//! case is tested against the stubs in `tests/cpi.rs`.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta,
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::{processor, tokio, BanksClient, ProgramTest};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use std::convert::TryInto;
+use test_token_solana::{
+    cpi, id, instruction,
+    processor::Processor,
+    state::{Account, Mint},
+};
+
+const VAULT_ID: Pubkey = Pubkey::new_from_array([9; 32]);
+const VAULT_SEED: &[u8] = b"vault";
+
+/// Accounts: token program, vault token account, destination, vault
+/// authority. Data: the amount as a little-endian `u64`.
+fn process_vault(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (_, bump) = Pubkey::find_program_address(&[VAULT_SEED], program_id);
+    cpi::transfer(
+        &accounts[0],
+        &accounts[1],
+        &accounts[2],
+        &accounts[3],
+        u64::from_le_bytes(data.try_into().unwrap()),
+        &[&[VAULT_SEED, &[bump]]],
+    )
+}
+
+fn withdraw(vault: &Pubkey, destination: &Pubkey, authority: &Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: VAULT_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(id(), false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, false),
+        ],
+        data: amount.to_le_bytes().to_vec(),
+    }
+}
+
+async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut keypairs = vec![payer];
+    keypairs.extend(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &keypairs,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+/// Creates a program account of `len` bytes at `keypair`'s address,
+/// initialized by `initialize`, in one transaction the owner does not sign
+async fn create(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    keypair: &Keypair,
+    len: usize,
+    initialize: Instruction,
+) {
+    let rent = banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+        &payer.pubkey(),
+        &keypair.pubkey(),
+        rent.minimum_balance(len),
+        len as u64,
+        &id(),
+    );
+    process(banks_client, payer, &[create, initialize], &[keypair])
+        .await
+        .unwrap();
+}
+
+async fn amount(banks_client: &mut BanksClient, address: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*address).await.unwrap().unwrap();
+    Account::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_pda_owner() {
+    let mut program_test =
+        ProgramTest::new("test_token_solana", id(), processor!(Processor::process));
+    program_test.add_program("vault", VAULT_ID, processor!(process_vault));
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let (authority, _) = Pubkey::find_program_address(&[VAULT_SEED], &VAULT_ID);
+    assert!(!authority.is_on_curve());
+
+    let mint = Keypair::new();
+    create(
+        &mut banks_client,
+        &payer,
+        &mint,
+        Mint::LEN,
+        instruction::initialize_mint(&id(), &mint.pubkey(), &payer.pubkey(), None, 0).unwrap(),
+    )
+    .await;
+
+    // the address has no key to sign with, and initialization needs none
+    let vault = Keypair::new();
+    create(
+        &mut banks_client,
+        &payer,
+        &vault,
+        Account::LEN,
+        instruction::initialize_account(&id(), &vault.pubkey(), &mint.pubkey(), &authority)
+            .unwrap(),
+    )
+    .await;
+    let vault_account = banks_client
+        .get_account(vault.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        Account::unpack(&vault_account.data).unwrap().owner,
+        authority
+    );
+
+    let destination = Keypair::new();
+    create(
+        &mut banks_client,
+        &payer,
+        &destination,
+        Account::LEN,
+        instruction::initialize_account(
+            &id(),
+            &destination.pubkey(),
+            &mint.pubkey(),
+            &payer.pubkey(),
+        )
+        .unwrap(),
+    )
+    .await;
+    process(
+        &mut banks_client,
+        &payer,
+        &[instruction::mint_to(
+            &id(),
+            &mint.pubkey(),
+            &vault.pubkey(),
+            &payer.pubkey(),
+            &[],
+            100,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    // the vault signs for its address with the seeds that derive it
+    process(
+        &mut banks_client,
+        &payer,
+        &[withdraw(
+            &vault.pubkey(),
+            &destination.pubkey(),
+            &authority,
+            40,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(amount(&mut banks_client, &vault.pubkey()).await, 60);
+    assert_eq!(amount(&mut banks_client, &destination.pubkey()).await, 40);
+}
@@ -0,0 +1,191 @@
+//! A tiny caller program that learns a balance and a supply mid-instruction by
+//! invoking `GetAccountBalance` and `GetMintSupply` and reading the return
+//! data.
+//!
+//! Cross-program invocations are routed to `Processor::process` by syscall
+//! stubs, which keep return data per invocation as the runtime does and hold
+//! the data of read-only accounts borrowed, so that any write panics.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program::{get_return_data, invoke},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+};
+use solana_sdk::account::Account as SolanaAccount;
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+    sync::Once,
+};
+use test_token_solana::{
+    error::TokenError,
+    instruction,
+    processor::Processor,
+    state::{Account, AccountState, Mint},
+};
+
+const CALLER_ID: Pubkey = Pubkey::new_from_array([8; 32]);
+
+thread_local! {
+    /// Program currently executing
+    static RUNNING_PROGRAM: Cell<Pubkey> = const { Cell::new(CALLER_ID) };
+    static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+}
+
+struct ReturnDataStubs;
+
+impl SyscallStubs for ReturnDataStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let program_id = RUNNING_PROGRAM.with(Cell::get);
+        RETURN_DATA.with(|return_data| {
+            *return_data.borrow_mut() = Some((program_id, data.to_vec()))
+        });
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RETURN_DATA.with(|return_data| return_data.borrow().clone())
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id != test_token_solana::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let infos = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                info.is_writable &= meta.is_writable;
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        let _read_only = infos
+            .iter()
+            .filter(|info| !info.is_writable)
+            .map(|info| info.data.borrow())
+            .collect::<Vec<_>>();
+
+        // the callee starts without return data and owns whatever it sets
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = None);
+        RUNNING_PROGRAM.with(|running| running.set(instruction.program_id));
+        let result = Processor::process(&instruction.program_id, &infos, &instruction.data);
+        RUNNING_PROGRAM.with(|running| running.set(CALLER_ID));
+        result
+    }
+}
+
+fn set_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(ReturnDataStubs));
+    });
+}
+
+/// Invokes `ix` on `target` and decodes the `u64` the token program returned
+fn query<'a>(
+    token_program: &AccountInfo<'a>,
+    target: &AccountInfo<'a>,
+    ix: Instruction,
+) -> Result<u64, ProgramError> {
+    invoke(&ix, &[target.clone(), token_program.clone()])?;
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *token_program.key => Ok(u64::from_le_bytes(
+            data.try_into().map_err(|_| ProgramError::InvalidAccountData)?,
+        )),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Accounts: token program, token account, mint. Returns the balance and the
+/// supply as the token program reported them.
+fn process_caller(accounts: &[AccountInfo]) -> Result<(u64, u64), ProgramError> {
+    let token_program = &accounts[0];
+    let balance = query(
+        token_program,
+        &accounts[1],
+        instruction::get_account_balance(token_program.key, accounts[1].key)?,
+    )?;
+    let supply = query(
+        token_program,
+        &accounts[2],
+        instruction::get_mint_supply(token_program.key, accounts[2].key)?,
+    )?;
+    Ok((balance, supply))
+}
+
+#[test]
+fn test_cpi_reads_balance_and_supply() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let mint_key = Pubkey::new_unique();
+    let account_key = Pubkey::new_unique();
+
+    let mut mint = SolanaAccount::new(0, Mint::LEN, &token_program_id);
+    Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply: 500,
+        decimals: 2,
+        is_initialized: true,
+        default_account_state: AccountState::Initialized,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint.data);
+    let mut account = SolanaAccount::new(0, Account::LEN, &token_program_id);
+    Account {
+        mint: mint_key,
+        owner: Pubkey::new_unique(),
+        amount: 123,
+        state: AccountState::Initialized,
+        ..Account::default()
+    }
+    .pack_into_slice(&mut account.data);
+    let mut token_program = SolanaAccount::default();
+
+    let accounts = [
+        (&token_program_id, false, &mut token_program).into(),
+        (&account_key, true, &mut account).into(),
+        (&mint_key, true, &mut mint).into(),
+    ];
+    assert_eq!(process_caller(&accounts), Ok((123, 500)));
+}
+
+#[test]
+fn test_cpi_query_of_uninitialized_account() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let mint_key = Pubkey::new_unique();
+    let account_key = Pubkey::new_unique();
+
+    let mut mint = SolanaAccount::new(0, Mint::LEN, &token_program_id);
+    let mut account = SolanaAccount::new(0, Account::LEN, &token_program_id);
+    let mut token_program = SolanaAccount::default();
+
+    // the query fails outright rather than leaving stale return data behind
+    let accounts = [
+        (&token_program_id, false, &mut token_program).into(),
+        (&account_key, false, &mut account).into(),
+        (&mint_key, false, &mut mint).into(),
+    ];
+    assert_eq!(
+        process_caller(&accounts),
+        Err(TokenError::UninitializedState.into())
+    );
+    assert_eq!(get_return_data(), None);
+}
@@ -0,0 +1,474 @@
+//! Golden bytes for the packed state layouts and every instruction.
+//!
+//! The fixtures under `tests/fixtures` hold one `label hex` line per packed
+//! value. A mismatch reports the fields that changed, using the field tables
+//! exported next to the state types. After a deliberate layout change, run
+//! the tests with `UPDATE_SNAPSHOTS=1` to rewrite the fixtures.
+
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use std::{collections::BTreeSet, env, fmt::Write, fs, path::PathBuf};
+use test_token_solana::{
+    instruction::{AuthorityType, MetadataField, TokenInstruction},
+    state::{
+        Account, AccountState, InterestBearingConfig, Mint, PolicyMode, TransferFeeConfig,
+        ACCOUNT_FIELDS, MINT_FIELDS,
+    },
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Lists each field of `fields` whose bytes differ, or every differing byte
+/// when there is no field table
+fn describe_diff(expected: &[u8], actual: &[u8], fields: &[(&str, usize, usize)]) -> String {
+    let mut report = String::new();
+    if expected.len() != actual.len() {
+        writeln!(report, "  length {} != {}", actual.len(), expected.len()).unwrap();
+    }
+    if fields.is_empty() {
+        for (offset, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+            if expected != actual {
+                writeln!(report, "  byte {}: {:02x} != {:02x}", offset, actual, expected).unwrap();
+            }
+        }
+    }
+    for &(name, offset, len) in fields {
+        let expected = expected.get(offset..offset + len).unwrap_or_default();
+        let actual = actual.get(offset..offset + len).unwrap_or_default();
+        if expected != actual {
+            writeln!(
+                report,
+                "  {} at {}: {} != {}",
+                name,
+                offset,
+                to_hex(actual),
+                to_hex(expected)
+            )
+            .unwrap();
+        }
+    }
+    report
+}
+
+/// Compares `values` against the fixture `name`, or rewrites the fixture
+/// when `UPDATE_SNAPSHOTS` is set
+fn assert_snapshot(name: &str, values: &[(String, Vec<u8>)], fields: &[(&str, usize, usize)]) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect();
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let contents = values.iter().fold(String::new(), |mut contents, (label, bytes)| {
+            writeln!(contents, "{} {}", label, to_hex(bytes)).unwrap();
+            contents
+        });
+        fs::write(&path, contents).unwrap();
+        return;
+    }
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let expected: Vec<(&str, Vec<u8>)> = contents
+        .lines()
+        .map(|line| {
+            let (label, hex) = line.split_once(' ').unwrap();
+            (label, from_hex(hex))
+        })
+        .collect();
+    let mut report = String::new();
+    for (label, bytes) in values {
+        match expected.iter().find(|(expected_label, _)| expected_label == label) {
+            Some((_, expected)) if expected == bytes => {}
+            Some((_, expected)) => {
+                writeln!(report, "{} changed (actual != expected):", label).unwrap();
+                report.push_str(&describe_diff(expected, bytes, fields));
+            }
+            None => writeln!(report, "{} has no snapshot", label).unwrap(),
+        }
+    }
+    for (label, _) in expected.iter() {
+        if !values.iter().any(|(value_label, _)| value_label == label) {
+            writeln!(report, "{} is no longer packed", label).unwrap();
+        }
+    }
+    assert!(report.is_empty(), "{} does not match:\n{}", name, report);
+}
+
+fn packed<T: Pack>(value: T) -> Vec<u8> {
+    let mut data = vec![0; T::LEN];
+    T::pack(value, &mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_state_lengths() {
+    assert_eq!(Mint::LEN, 422);
+    assert_eq!(Account::LEN, 298);
+}
+
+#[test]
+fn test_mint_snapshot() {
+    let mint = Mint {
+        mint_authority: COption::Some(key(1)),
+        supply: 0x0102_0304_0506_0708,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::Some(key(2)),
+        max_supply: COption::Some(u64::MAX),
+        permanent_delegate: COption::Some(key(3)),
+        default_account_state: AccountState::Frozen,
+        transfer_fee_config: TransferFeeConfig {
+            transfer_fee_authority: COption::Some(key(4)),
+            transfer_fee_basis_points: 250,
+            maximum_fee: 5,
+        },
+        withheld_amount: 6,
+        interest_bearing_config: InterestBearingConfig {
+            rate_authority: COption::Some(key(7)),
+            initialization_timestamp: 8,
+            pre_update_average_rate: -9,
+            last_update_timestamp: 10,
+            current_rate: 11,
+        },
+        pause_authority: COption::Some(key(12)),
+        paused: true,
+        transfer_hook_authority: COption::Some(key(13)),
+        transfer_hook_program_id: COption::Some(key(14)),
+        metadata_pointer: COption::Some(key(15)),
+        transfer_policy: COption::Some(key(16)),
+    };
+    let empty = Mint {
+        mint_authority: COption::Some(key(1)),
+        supply: 100,
+        decimals: 2,
+        is_initialized: true,
+        ..Mint::default()
+    };
+    assert_snapshot(
+        "mint.txt",
+        &[
+            ("full".to_string(), packed(mint)),
+            ("minimal".to_string(), packed(empty)),
+        ],
+        &MINT_FIELDS,
+    );
+}
+
+#[test]
+fn test_account_snapshot() {
+    let account = Account {
+        mint: key(1),
+        owner: key(2),
+        amount: 0x0102_0304_0506_0708,
+        state: AccountState::Initialized,
+        ..Account::default()
+    };
+    let delegated = Account {
+        delegate: COption::Some(key(3)),
+        delegated_amount: 4,
+        state: AccountState::Frozen,
+        is_native: COption::Some(5),
+        close_authority: COption::Some(key(6)),
+        expiry_slot: COption::Some(7),
+        pending_owner: COption::Some(key(8)),
+        memo_required: true,
+        withheld_amount: 9,
+        mint_required: true,
+        immutable_owner: true,
+        cpi_guard: true,
+        lock_until: COption::Some(10),
+        escrowed: true,
+        limit_amount: COption::Some(11),
+        window_start_epoch: 12,
+        spent_in_window: 13,
+        vesting_amount: 14,
+        vesting_total: 15,
+        vesting_start_slot: 16,
+        vesting_end_slot: 17,
+        ..account
+    };
+    assert_snapshot(
+        "account.txt",
+        &[
+            ("without_delegate".to_string(), packed(account)),
+            ("with_delegate".to_string(), packed(delegated)),
+        ],
+        &ACCOUNT_FIELDS,
+    );
+}
+
+/// Labels each instruction by its variant. The match is exhaustive, so a new
+/// variant does not compile until it is named here and given a snapshot.
+fn variant_name(instruction: &TokenInstruction) -> &'static str {
+    use TokenInstruction::*;
+    match instruction {
+        InitializeMint { .. } => "InitializeMint",
+        InitializeAccount => "InitializeAccount",
+        Transfer { .. } => "Transfer",
+        Approve { .. } => "Approve",
+        MintTo { .. } => "MintTo",
+        Burn { .. } => "Burn",
+        Revoke => "Revoke",
+        SetAuthority { .. } => "SetAuthority",
+        FreezeAccount => "FreezeAccount",
+        ThawAccount => "ThawAccount",
+        TransferChecked { .. } => "TransferChecked",
+        ApproveChecked { .. } => "ApproveChecked",
+        BurnChecked { .. } => "BurnChecked",
+        InitializeMultisig { .. } => "InitializeMultisig",
+        InitializeMint2 { .. } => "InitializeMint2",
+        InitializeAccount2 { .. } => "InitializeAccount2",
+        InitializeAccount3 { .. } => "InitializeAccount3",
+        SyncNative => "SyncNative",
+        GetAccountDataSize => "GetAccountDataSize",
+        AmountToUiAmount { .. } => "AmountToUiAmount",
+        UiAmountToAmount { .. } => "UiAmountToAmount",
+        CloseAccount => "CloseAccount",
+        IncreaseDelegatedAmount { .. } => "IncreaseDelegatedAmount",
+        DecreaseDelegatedAmount { .. } => "DecreaseDelegatedAmount",
+        ApproveWithExpiry { .. } => "ApproveWithExpiry",
+        TransferBatch { .. } => "TransferBatch",
+        MintToBatch { .. } => "MintToBatch",
+        ProposeOwner { .. } => "ProposeOwner",
+        AcceptOwnership => "AcceptOwnership",
+        SetDefaultAccountState { .. } => "SetDefaultAccountState",
+        RequireMemoTransfers { .. } => "RequireMemoTransfers",
+        TransferCheckedWithFee { .. } => "TransferCheckedWithFee",
+        SetTransferFee { .. } => "SetTransferFee",
+        WithdrawWithheldTokensFromAccounts { .. } => "WithdrawWithheldTokensFromAccounts",
+        HarvestWithheldTokensToMint => "HarvestWithheldTokensToMint",
+        UpdateRateInterestBearingMint { .. } => "UpdateRateInterestBearingMint",
+        Pause => "Pause",
+        Resume => "Resume",
+        Reallocate => "Reallocate",
+        ReallocateMint => "ReallocateMint",
+        InitializeImmutableOwner => "InitializeImmutableOwner",
+        EnableCpiGuard => "EnableCpiGuard",
+        DisableCpiGuard => "DisableCpiGuard",
+        InitializeTransferHook { .. } => "InitializeTransferHook",
+        UpdateTransferHook { .. } => "UpdateTransferHook",
+        InitializeMetadataPointer { .. } => "InitializeMetadataPointer",
+        InitializeTokenMetadata { .. } => "InitializeTokenMetadata",
+        UpdateTokenMetadataField { .. } => "UpdateTokenMetadataField",
+        InitializeAssociatedAccount => "InitializeAssociatedAccount",
+        RecoverNested => "RecoverNested",
+        WrapSol { .. } => "WrapSol",
+        UnwrapSol { .. } => "UnwrapSol",
+        RevokeAll => "RevokeAll",
+        AssertBalance { .. } => "AssertBalance",
+        AssertSupply { .. } => "AssertSupply",
+        GetAccountBalance => "GetAccountBalance",
+        GetMintSupply => "GetMintSupply",
+        SplitAccount { .. } => "SplitAccount",
+        MergeAccounts { .. } => "MergeAccounts",
+        LockUntilSlot { .. } => "LockUntilSlot",
+        ApproveEscrowed { .. } => "ApproveEscrowed",
+        UpdateDecimals { .. } => "UpdateDecimals",
+        InitializePolicy { .. } => "InitializePolicy",
+        AddPolicyEntry { .. } => "AddPolicyEntry",
+        RemovePolicyEntry { .. } => "RemovePolicyEntry",
+        InitializeFlashMint { .. } => "InitializeFlashMint",
+        FlashMintTo { .. } => "FlashMintTo",
+        FlashBurnRepay { .. } => "FlashBurnRepay",
+        SetSpendingLimit { .. } => "SetSpendingLimit",
+        TransferVested { .. } => "TransferVested",
+        ClaimVested => "ClaimVested",
+        Swap { .. } => "Swap",
+        Batch { .. } => "Batch",
+        FreezeAccounts { .. } => "FreezeAccounts",
+        ThawAccounts { .. } => "ThawAccounts",
+        BurnFrom { .. } => "BurnFrom",
+    }
+}
+
+#[test]
+fn test_instruction_snapshots() {
+    use TokenInstruction::*;
+    let instructions = vec![
+        InitializeMint {
+            decimals: 2,
+            mint_authority: key(1),
+            freeze_authority: COption::Some(key(2)),
+            max_supply: COption::Some(3),
+            permanent_delegate: COption::Some(key(4)),
+            transfer_fee_authority: COption::Some(key(5)),
+            rate_authority: COption::Some(key(6)),
+            pause_authority: COption::Some(key(7)),
+        },
+        InitializeAccount,
+        Transfer { amount: 1 },
+        Approve { amount: 2 },
+        MintTo { amount: 3 },
+        Burn { amount: 4 },
+        Revoke,
+        SetAuthority {
+            authority_type: AuthorityType::CloseAccount,
+            new_authority: COption::Some(key(1)),
+        },
+        FreezeAccount,
+        ThawAccount,
+        TransferChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        ApproveChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        BurnChecked {
+            amount: 1,
+            decimals: 2,
+        },
+        InitializeMultisig { m: 2 },
+        InitializeMint2 {
+            decimals: 2,
+            mint_authority: key(1),
+            freeze_authority: COption::None,
+            max_supply: COption::None,
+            permanent_delegate: COption::None,
+            transfer_fee_authority: COption::None,
+            rate_authority: COption::None,
+            pause_authority: COption::None,
+        },
+        InitializeAccount2 { owner: key(1) },
+        InitializeAccount3 { owner: key(1) },
+        SyncNative,
+        GetAccountDataSize,
+        AmountToUiAmount { amount: 1 },
+        UiAmountToAmount { ui_amount: "1.5" },
+        CloseAccount,
+        IncreaseDelegatedAmount { amount: 1 },
+        DecreaseDelegatedAmount { amount: 1 },
+        ApproveWithExpiry {
+            amount: 1,
+            expiry_slot: 2,
+        },
+        TransferBatch {
+            amounts: vec![1, 2],
+        },
+        MintToBatch {
+            amounts: vec![1, 2],
+        },
+        ProposeOwner { new_owner: key(1) },
+        AcceptOwnership,
+        SetDefaultAccountState {
+            state: AccountState::Frozen,
+        },
+        RequireMemoTransfers { enable: true },
+        TransferCheckedWithFee {
+            amount: 1,
+            decimals: 2,
+            fee: 3,
+        },
+        SetTransferFee {
+            transfer_fee_basis_points: 1,
+            maximum_fee: 2,
+        },
+        WithdrawWithheldTokensFromAccounts {
+            num_token_accounts: 2,
+        },
+        HarvestWithheldTokensToMint,
+        UpdateRateInterestBearingMint { rate: -1 },
+        Pause,
+        Resume,
+        Reallocate,
+        ReallocateMint,
+        InitializeImmutableOwner,
+        EnableCpiGuard,
+        DisableCpiGuard,
+        InitializeTransferHook {
+            authority: COption::Some(key(1)),
+            program_id: COption::Some(key(2)),
+        },
+        UpdateTransferHook {
+            program_id: COption::None,
+        },
+        InitializeMetadataPointer {
+            metadata_address: COption::Some(key(1)),
+        },
+        InitializeTokenMetadata {
+            name: "Name",
+            symbol: "SYM",
+            uri: "uri",
+        },
+        UpdateTokenMetadataField {
+            field: MetadataField::Uri,
+            value: "value",
+        },
+        InitializeAssociatedAccount,
+        RecoverNested,
+        WrapSol { lamports: 1 },
+        UnwrapSol { lamports: 1 },
+        RevokeAll,
+        AssertBalance {
+            minimum: 1,
+            maximum: 2,
+        },
+        AssertSupply {
+            minimum: 1,
+            maximum: 2,
+        },
+        GetAccountBalance,
+        GetMintSupply,
+        SplitAccount { amount: 1 },
+        MergeAccounts {
+            num_source_accounts: 2,
+            close_sources: true,
+        },
+        LockUntilSlot { slot: 1 },
+        ApproveEscrowed { amount: 1 },
+        UpdateDecimals { decimals: 3 },
+        InitializePolicy {
+            mode: PolicyMode::Blocklist,
+        },
+        AddPolicyEntry { entry: key(1) },
+        RemovePolicyEntry { entry: key(1) },
+        InitializeFlashMint { fee: 1 },
+        FlashMintTo { amount: 1 },
+        FlashBurnRepay { amount: 1 },
+        SetSpendingLimit {
+            amount: COption::Some(1),
+        },
+        TransferVested {
+            total_amount: 1,
+            start_slot: 2,
+            end_slot: 3,
+        },
+        ClaimVested,
+        Swap {
+            amount_a: 1,
+            amount_b: 2,
+        },
+        Batch {
+            instructions: vec![[&[3u8][..], &Transfer { amount: 1 }.pack()].concat()],
+        },
+        FreezeAccounts { count: 2 },
+        ThawAccounts { count: 2 },
+        BurnFrom { amount: 1 },
+    ];
+
+    // Tags are assigned in sequence, so covering every tag up to the
+    // highest covers every variant
+    let tags: BTreeSet<u8> = instructions.iter().map(|ix| ix.pack()[0]).collect();
+    assert_eq!(tags, (0..=*tags.last().unwrap()).collect());
+    assert_eq!(tags.len(), instructions.len());
+
+    let values: Vec<(String, Vec<u8>)> = instructions
+        .iter()
+        .map(|ix| (variant_name(ix).to_string(), ix.pack()))
+        .collect();
+    assert_snapshot("instructions.txt", &values, &[]);
+}
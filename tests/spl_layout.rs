@@ -0,0 +1,690 @@
+//! The `Spl` layout against SPL Token itself.
+//!
+//! A mint of `SPL_MINT_LEN` bytes and an account of `SPL_ACCOUNT_LEN` bytes
+//! are SPL Token's layouts, so wallets and RPC parsers that only know SPL
+//! Token read them. `spl_token`'s own `Pack` implementations must agree with
+//! ours both ways, and so must the bytes the deployed SPL Token program
+//! writes. `tests/fixtures/spl_token.txt` holds those bytes, captured from
+//! the SPL Token binary `solana-program-test` deploys, one `label hex` line
+//! per account; run the tests with `UPDATE_SNAPSHOTS=1` to capture them
+//! again. The same instructions run through this program must write the
+//! same bytes.
+
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent, system_instruction,
+};
+use solana_program_test::{processor, tokio, BanksClient, ProgramTest};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    signer::keypair::keypair_from_seed,
+    transaction::Transaction,
+};
+use spl_token::state::{self as spl, GenericTokenAccount};
+use std::{env, fmt::Write, fs, path::PathBuf};
+use test_token_solana::{
+    id, instruction,
+    instruction::AuthorityType,
+    native_mint,
+    processor::Processor,
+    state::{
+        unpack_account_mint, unpack_account_owner, Account, AccountState, LayoutVersion, Mint,
+        SPL_ACCOUNT_LEN, SPL_MINT_LEN,
+    },
+};
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+fn spl_state(state: AccountState) -> spl::AccountState {
+    match state {
+        AccountState::Uninitialized => spl::AccountState::Uninitialized,
+        AccountState::Initialized => spl::AccountState::Initialized,
+        AccountState::Frozen => spl::AccountState::Frozen,
+    }
+}
+
+#[test]
+fn test_spl_lengths() {
+    assert_eq!(spl::Mint::LEN, SPL_MINT_LEN);
+    assert_eq!(spl::Account::LEN, SPL_ACCOUNT_LEN);
+}
+
+#[test]
+fn test_mint_matches_spl() {
+    for (mint_authority, freeze_authority) in [
+        (COption::Some(key(1)), COption::Some(key(2))),
+        (COption::Some(key(1)), COption::None),
+        (COption::None, COption::None),
+    ] {
+        let mint = Mint {
+            mint_authority,
+            supply: 0x0102_0304_0506_0708,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority,
+            ..Mint::default()
+        };
+        let spl_mint = spl::Mint {
+            mint_authority,
+            supply: mint.supply,
+            decimals: mint.decimals,
+            is_initialized: mint.is_initialized,
+            freeze_authority,
+        };
+
+        let mut data = vec![0; SPL_MINT_LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        let mut spl_data = vec![0; SPL_MINT_LEN];
+        spl::Mint::pack(spl_mint, &mut spl_data).unwrap();
+        assert_eq!(data, spl_data);
+        assert_eq!(spl::Mint::unpack(&data).unwrap(), spl_mint);
+        assert_eq!(Mint::unpack(&spl_data).unwrap(), mint);
+
+        // and the current layout starts with it
+        let mut v2 = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut v2).unwrap();
+        assert_eq!(v2[..SPL_MINT_LEN], spl_data[..]);
+    }
+}
+
+#[test]
+fn test_account_matches_spl() {
+    let cases = [
+        Account {
+            mint: key(1),
+            owner: key(2),
+            amount: 1_000,
+            state: AccountState::Initialized,
+            ..Account::default()
+        },
+        // every optional field set, with distinct bytes so a field read
+        // from the wrong offset shows
+        Account {
+            mint: key(1),
+            owner: key(2),
+            amount: 0x0102_0304_0506_0708,
+            delegate: COption::Some(key(3)),
+            state: AccountState::Frozen,
+            is_native: COption::Some(0x1112_1314_1516_1718),
+            delegated_amount: 0x2122_2324_2526_2728,
+            close_authority: COption::Some(key(4)),
+            ..Account::default()
+        },
+    ];
+    for account in cases {
+        let spl_account = spl::Account {
+            mint: account.mint,
+            owner: account.owner,
+            amount: account.amount,
+            delegate: account.delegate,
+            state: spl_state(account.state),
+            is_native: account.is_native,
+            delegated_amount: account.delegated_amount,
+            close_authority: account.close_authority,
+        };
+
+        let mut data = vec![0; SPL_ACCOUNT_LEN];
+        Account::pack(account, &mut data).unwrap();
+        let mut spl_data = vec![0; SPL_ACCOUNT_LEN];
+        spl::Account::pack(spl_account, &mut spl_data).unwrap();
+        assert_eq!(data, spl_data);
+        assert_eq!(spl::Account::unpack(&data).unwrap(), spl_account);
+        assert_eq!(Account::unpack(&spl_data).unwrap(), account);
+    }
+
+    // SPL Token has nowhere to keep this crate's fields
+    let mut data = vec![0; SPL_ACCOUNT_LEN];
+    assert!(Account::pack(
+        Account {
+            memo_required: true,
+            ..cases[0]
+        },
+        &mut data
+    )
+    .is_err());
+}
+
+#[test]
+fn test_account_partial_unpack_matches_spl() {
+    let account = Account {
+        mint: key(1),
+        owner: key(2),
+        amount: 42,
+        state: AccountState::Frozen,
+        ..Account::default()
+    };
+    let mut data = vec![0; SPL_ACCOUNT_LEN];
+    Account::pack(account, &mut data).unwrap();
+
+    assert!(spl::Account::valid_account_data(&data));
+    assert_eq!(
+        spl::Account::unpack_account_mint(&data),
+        unpack_account_mint(&data)
+    );
+    assert_eq!(
+        spl::Account::unpack_account_owner(&data),
+        unpack_account_owner(&data)
+    );
+}
+
+/// The signers and addresses of `capture`, the same in every bank
+struct Keys {
+    mint_authority: Keypair,
+    freeze_authority: Keypair,
+    owner: Keypair,
+    delegate: Pubkey,
+    close_authority: Pubkey,
+    mint: Keypair,
+    fixed_mint: Keypair,
+    account: Keypair,
+    delegated: Keypair,
+    frozen: Keypair,
+    native: Keypair,
+}
+
+impl Keys {
+    fn new() -> Self {
+        let keypair = |byte| keypair_from_seed(&[byte; 32]).unwrap();
+        Self {
+            mint_authority: keypair(1),
+            freeze_authority: keypair(2),
+            owner: keypair(3),
+            delegate: keypair(4).pubkey(),
+            close_authority: keypair(5).pubkey(),
+            mint: keypair(6),
+            fixed_mint: keypair(7),
+            account: keypair(8),
+            delegated: keypair(9),
+            frozen: keypair(10),
+            native: keypair(11),
+        }
+    }
+}
+
+const NATIVE_AMOUNT: u64 = 1_000_000;
+
+/// What each mint captured by `capture` holds
+fn expected_mints(keys: &Keys) -> Vec<(&'static str, Mint)> {
+    let mint = Mint {
+        mint_authority: COption::Some(keys.mint_authority.pubkey()),
+        supply: 1_000 + 500 + 5,
+        decimals: 6,
+        is_initialized: true,
+        freeze_authority: COption::Some(keys.freeze_authority.pubkey()),
+        ..Mint::default()
+    };
+    let fixed_mint = Mint {
+        decimals: 0,
+        is_initialized: true,
+        ..Mint::default()
+    };
+    vec![("mint", mint), ("fixed_mint", fixed_mint)]
+}
+
+/// What each token account captured by `capture` holds
+fn expected_accounts(keys: &Keys) -> Vec<(&'static str, Account)> {
+    let account = Account {
+        mint: keys.mint.pubkey(),
+        owner: keys.owner.pubkey(),
+        amount: 1_000,
+        state: AccountState::Initialized,
+        ..Account::default()
+    };
+    let delegated = Account {
+        amount: 500,
+        delegate: COption::Some(keys.delegate),
+        delegated_amount: 200,
+        close_authority: COption::Some(keys.close_authority),
+        ..account
+    };
+    let frozen = Account {
+        amount: 5,
+        state: AccountState::Frozen,
+        ..account
+    };
+    let native = Account {
+        mint: native_mint::id(),
+        amount: NATIVE_AMOUNT,
+        is_native: COption::Some(Rent::default().minimum_balance(SPL_ACCOUNT_LEN)),
+        ..account
+    };
+    vec![
+        ("account", account),
+        ("delegated", delegated),
+        ("frozen", frozen),
+        ("native", native),
+    ]
+}
+
+/// A token program `capture` runs against
+#[derive(Clone, Copy)]
+enum Program {
+    /// The SPL Token binary `solana-program-test` deploys
+    SplToken,
+    /// This crate's processor
+    TokenSolana,
+}
+
+impl Program {
+    fn id(self) -> Pubkey {
+        match self {
+            Self::SplToken => spl_token::id(),
+            Self::TokenSolana => id(),
+        }
+    }
+
+    fn program_test(self) -> ProgramTest {
+        match self {
+            Self::SplToken => ProgramTest::default(),
+            Self::TokenSolana => {
+                ProgramTest::new("test_token_solana", id(), processor!(Processor::process))
+            }
+        }
+    }
+
+    fn initialize_mint(
+        self,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        freeze: Option<&Pubkey>,
+    ) -> Instruction {
+        let decimals = if freeze.is_some() { 6 } else { 0 };
+        match self {
+            Self::SplToken => spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                mint,
+                authority,
+                freeze,
+                decimals,
+            ),
+            Self::TokenSolana => {
+                instruction::initialize_mint(&id(), mint, authority, freeze, decimals)
+            }
+        }
+        .unwrap()
+    }
+
+    fn initialize_account(self, account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+        match self {
+            Self::SplToken => {
+                spl_token::instruction::initialize_account(&spl_token::id(), account, mint, owner)
+            }
+            Self::TokenSolana => instruction::initialize_account(&id(), account, mint, owner),
+        }
+        .unwrap()
+    }
+
+    fn mint_to(
+        self,
+        mint: &Pubkey,
+        account: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        match self {
+            Self::SplToken => spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                account,
+                authority,
+                &[],
+                amount,
+            ),
+            Self::TokenSolana => instruction::mint_to(&id(), mint, account, authority, &[], amount),
+        }
+        .unwrap()
+    }
+
+    fn approve(
+        self,
+        account: &Pubkey,
+        delegate: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        match self {
+            Self::SplToken => spl_token::instruction::approve(
+                &spl_token::id(),
+                account,
+                delegate,
+                owner,
+                &[],
+                amount,
+            ),
+            Self::TokenSolana => instruction::approve(&id(), account, delegate, owner, &[], amount),
+        }
+        .unwrap()
+    }
+
+    fn set_authority(
+        self,
+        owned: &Pubkey,
+        new_authority: Option<&Pubkey>,
+        authority_type: AuthorityType,
+        authority: &Pubkey,
+    ) -> Instruction {
+        match self {
+            Self::SplToken => {
+                let authority_type = match authority_type {
+                    AuthorityType::MintTokens => spl_token::instruction::AuthorityType::MintTokens,
+                    AuthorityType::FreezeAccount => {
+                        spl_token::instruction::AuthorityType::FreezeAccount
+                    }
+                    AuthorityType::AccountOwner => {
+                        spl_token::instruction::AuthorityType::AccountOwner
+                    }
+                    AuthorityType::CloseAccount => {
+                        spl_token::instruction::AuthorityType::CloseAccount
+                    }
+                };
+                spl_token::instruction::set_authority(
+                    &spl_token::id(),
+                    owned,
+                    new_authority,
+                    authority_type,
+                    authority,
+                    &[],
+                )
+            }
+            Self::TokenSolana => instruction::set_authority(
+                &id(),
+                owned,
+                new_authority,
+                authority_type,
+                authority,
+                &[],
+            ),
+        }
+        .unwrap()
+    }
+
+    fn freeze_account(self, account: &Pubkey, mint: &Pubkey, authority: &Pubkey) -> Instruction {
+        match self {
+            Self::SplToken => spl_token::instruction::freeze_account(
+                &spl_token::id(),
+                account,
+                mint,
+                authority,
+                &[],
+            ),
+            Self::TokenSolana => instruction::freeze_account(&id(), account, mint, authority, &[]),
+        }
+        .unwrap()
+    }
+}
+
+async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut keypairs = vec![payer];
+    keypairs.extend(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &keypairs,
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+/// Creates an account of `len` bytes owned by `program`, holding `extra`
+/// lamports over its rent-exempt reserve, and runs `initialize` on it
+async fn create(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program: Program,
+    keypair: &Keypair,
+    len: usize,
+    extra: u64,
+    initialize: Instruction,
+) {
+    let rent = banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+        &payer.pubkey(),
+        &keypair.pubkey(),
+        rent.minimum_balance(len) + extra,
+        len as u64,
+        &program.id(),
+    );
+    process(banks_client, payer, &[create, initialize], &[keypair]).await;
+}
+
+/// Runs the same instructions against `program` in a new bank and returns
+/// the data of every mint and account they write, labelled as in
+/// `expected_mints` and `expected_accounts`
+async fn capture(program: Program, keys: &Keys) -> Vec<(String, Vec<u8>)> {
+    let (mut banks_client, payer, _) = program.program_test().start().await;
+    let banks_client = &mut banks_client;
+    let mint = keys.mint.pubkey();
+    let fixed_mint = keys.fixed_mint.pubkey();
+    let mint_authority = keys.mint_authority.pubkey();
+    let owner = keys.owner.pubkey();
+
+    let initialize = program.initialize_mint(
+        &mint,
+        &mint_authority,
+        Some(&keys.freeze_authority.pubkey()),
+    );
+    create(
+        banks_client,
+        &payer,
+        program,
+        &keys.mint,
+        SPL_MINT_LEN,
+        0,
+        initialize,
+    )
+    .await;
+    let initialize = program.initialize_mint(&fixed_mint, &mint_authority, None);
+    create(
+        banks_client,
+        &payer,
+        program,
+        &keys.fixed_mint,
+        SPL_MINT_LEN,
+        0,
+        initialize,
+    )
+    .await;
+    process(
+        banks_client,
+        &payer,
+        &[program.set_authority(
+            &fixed_mint,
+            None,
+            AuthorityType::MintTokens,
+            &mint_authority,
+        )],
+        &[&keys.mint_authority],
+    )
+    .await;
+
+    for (keypair, amount) in [
+        (&keys.account, 1_000),
+        (&keys.delegated, 500),
+        (&keys.frozen, 5),
+    ] {
+        let initialize = program.initialize_account(&keypair.pubkey(), &mint, &owner);
+        create(
+            banks_client,
+            &payer,
+            program,
+            keypair,
+            SPL_ACCOUNT_LEN,
+            0,
+            initialize,
+        )
+        .await;
+        process(
+            banks_client,
+            &payer,
+            &[program.mint_to(&mint, &keypair.pubkey(), &mint_authority, amount)],
+            &[&keys.mint_authority],
+        )
+        .await;
+    }
+    let delegated = keys.delegated.pubkey();
+    process(
+        banks_client,
+        &payer,
+        &[
+            program.approve(&delegated, &keys.delegate, &owner, 200),
+            program.set_authority(
+                &delegated,
+                Some(&keys.close_authority),
+                AuthorityType::CloseAccount,
+                &owner,
+            ),
+        ],
+        &[&keys.owner],
+    )
+    .await;
+    process(
+        banks_client,
+        &payer,
+        &[program.freeze_account(
+            &keys.frozen.pubkey(),
+            &mint,
+            &keys.freeze_authority.pubkey(),
+        )],
+        &[&keys.freeze_authority],
+    )
+    .await;
+    let initialize = program.initialize_account(&keys.native.pubkey(), &native_mint::id(), &owner);
+    create(
+        banks_client,
+        &payer,
+        program,
+        &keys.native,
+        SPL_ACCOUNT_LEN,
+        NATIVE_AMOUNT,
+        initialize,
+    )
+    .await;
+
+    let mut captured = vec![];
+    for (label, keypair) in [
+        ("mint", &keys.mint),
+        ("fixed_mint", &keys.fixed_mint),
+        ("account", &keys.account),
+        ("delegated", &keys.delegated),
+        ("frozen", &keys.frozen),
+        ("native", &keys.native),
+    ] {
+        let account = banks_client
+            .get_account(keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.owner, program.id());
+        captured.push((label.to_string(), account.data));
+    }
+    captured
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn fixture_path() -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "spl_token.txt",
+    ]
+    .iter()
+    .collect()
+}
+
+fn read_fixture() -> Vec<(String, Vec<u8>)> {
+    fs::read_to_string(fixture_path())
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let (label, hex) = line.split_once(' ').unwrap();
+            (label.to_string(), from_hex(hex))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_deployed_spl_token_matches_fixture() {
+    let captured = capture(Program::SplToken, &Keys::new()).await;
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let contents = captured
+            .iter()
+            .fold(String::new(), |mut contents, (label, data)| {
+                writeln!(contents, "{} {}", label, to_hex(data)).unwrap();
+                contents
+            });
+        fs::write(fixture_path(), contents).unwrap();
+        return;
+    }
+    assert_eq!(
+        captured,
+        read_fixture(),
+        "tests/fixtures/spl_token.txt is stale, rerun with UPDATE_SNAPSHOTS=1"
+    );
+}
+
+#[tokio::test]
+async fn test_processor_writes_spl_token_bytes() {
+    let keys = Keys::new();
+    let captured = capture(Program::TokenSolana, &keys).await;
+    for ((label, data), (spl_label, spl_data)) in captured.iter().zip(read_fixture()) {
+        assert_eq!(*label, spl_label);
+        assert_eq!(*data, spl_data, "{} differs from SPL Token's", label);
+    }
+}
+
+#[test]
+fn test_fixture_unpacks() {
+    let fixture = read_fixture();
+    let data = |label: &str| {
+        &fixture
+            .iter()
+            .find(|(fixture_label, _)| fixture_label == label)
+            .unwrap()
+            .1
+    };
+    let keys = Keys::new();
+    let (mints, accounts) = (expected_mints(&keys), expected_accounts(&keys));
+    assert_eq!(fixture.len(), mints.len() + accounts.len());
+
+    for (label, mint) in mints {
+        let data = data(label);
+        assert_eq!(LayoutVersion::of_mint(data), Some(LayoutVersion::Spl));
+        assert_eq!(Mint::unpack(data).unwrap(), mint, "{}", label);
+        // packing over what SPL Token wrote changes nothing, down to the
+        // stale key a renounced authority leaves
+        let mut packed = data.clone();
+        Mint::pack(mint, &mut packed).unwrap();
+        assert_eq!(packed, *data, "{}", label);
+    }
+    for (label, account) in accounts {
+        let data = data(label);
+        assert_eq!(LayoutVersion::of_account(data), Some(LayoutVersion::Spl));
+        assert_eq!(Account::unpack(data).unwrap(), account, "{}", label);
+        assert_eq!(unpack_account_owner(data), Some(&account.owner));
+        let mut packed = data.clone();
+        Account::pack(account, &mut packed).unwrap();
+        assert_eq!(packed, *data, "{}", label);
+    }
+}
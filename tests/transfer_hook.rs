@@ -0,0 +1,303 @@
+//! A tiny transfer hook program that vetoes checked transfers above a
+//! threshold kept in an extra account, which transfers that leave out the
+//! mint cannot get around.
+//!
+//! The hook's invocation is routed to it by syscall stubs, which pass on only
+//! the privileges the instruction grants, as the runtime does.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use solana_sdk::account::{create_account_for_test, Account as SolanaAccount};
+use std::{cell::Cell, convert::TryInto, sync::Once};
+use test_token_solana::{
+    error::TokenError,
+    instruction::{initialize_account, transfer, transfer_checked},
+    processor::Processor,
+    state::{Account, AccountState, Mint},
+};
+
+const HOOK_ID: Pubkey = Pubkey::new_from_array([9; 32]);
+const OVER_THRESHOLD: u32 = 0;
+
+thread_local! {
+    /// Destination balance the hook last saw
+    static SEEN_DESTINATION_AMOUNT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+struct HookStubs;
+
+impl SyscallStubs for HookStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id != HOOK_ID {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // the callee must be among the accounts, as on-chain
+        if !account_infos.iter().any(|info| *info.key == HOOK_ID) {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let infos = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                info.is_signer &= meta.is_signer;
+                info.is_writable &= meta.is_writable;
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        process_hook(&infos, &instruction.data)
+    }
+}
+
+fn set_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(HookStubs));
+    });
+}
+
+/// Accounts: source, mint, destination, authority, then the threshold
+/// account. Data: amount as a little-endian `u64`.
+fn process_hook(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if accounts.iter().any(|info| info.is_writable) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let amount = u64::from_le_bytes(data.try_into().unwrap());
+    let threshold = u64::from_le_bytes(accounts[4].data.borrow()[..8].try_into().unwrap());
+    let destination = Account::unpack(&accounts[2].data.borrow())?;
+    SEEN_DESTINATION_AMOUNT.with(|seen| seen.set(Some(destination.amount)));
+    if amount > threshold {
+        return Err(ProgramError::Custom(OVER_THRESHOLD));
+    }
+    Ok(())
+}
+
+/// Marked as `InitializeAccount` marks the accounts of a mint with a hook
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> SolanaAccount {
+    let mut account = SolanaAccount::new(0, Account::LEN, &test_token_solana::id());
+    Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        mint_required: true,
+        ..Account::default()
+    }
+    .pack_into_slice(&mut account.data);
+    account
+}
+
+/// A checked transfer of `amount`, with the threshold account and, if
+/// `with_hook_program`, the hook program appended
+fn transfer_through_hook(
+    mint: &mut SolanaAccount,
+    source: &mut SolanaAccount,
+    destination: &mut SolanaAccount,
+    threshold: &mut SolanaAccount,
+    amount: u64,
+    with_hook_program: bool,
+) -> ProgramResult {
+    let token_program_id = test_token_solana::id();
+    let mint_key = Pubkey::new_from_array([1; 32]);
+    let source_key = Pubkey::new_from_array([2; 32]);
+    let destination_key = Pubkey::new_from_array([3; 32]);
+    let owner_key = Pubkey::new_from_array([4; 32]);
+    let threshold_key = Pubkey::new_from_array([5; 32]);
+    let instruction = transfer_checked(
+        &token_program_id,
+        &source_key,
+        &mint_key,
+        &destination_key,
+        &owner_key,
+        &[],
+        amount,
+        2,
+    )
+    .unwrap();
+
+    let mut owner = SolanaAccount::default();
+    let mut hook_program = SolanaAccount::default();
+    let mut infos = vec![
+        (&source_key, false, &mut *source).into(),
+        (&mint_key, false, &mut *mint).into(),
+        (&destination_key, false, &mut *destination).into(),
+        (&owner_key, true, &mut owner).into(),
+        (&threshold_key, false, &mut *threshold).into(),
+    ];
+    if with_hook_program {
+        infos.push((&HOOK_ID, false, &mut hook_program).into());
+    }
+    Processor::process(&token_program_id, &infos, &instruction.data)
+}
+
+#[test]
+fn test_transfer_hook_vetoes_large_transfers() {
+    set_stubs();
+    let mint_key = Pubkey::new_from_array([1; 32]);
+    let owner_key = Pubkey::new_from_array([4; 32]);
+
+    let mut mint = SolanaAccount::new(0, Mint::LEN, &test_token_solana::id());
+    Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply: 200,
+        decimals: 2,
+        is_initialized: true,
+        default_account_state: AccountState::Initialized,
+        transfer_hook_program_id: COption::Some(HOOK_ID),
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint.data);
+    let mut threshold = SolanaAccount::new(0, 8, &HOOK_ID);
+    threshold.data = 50u64.to_le_bytes().to_vec();
+    let mut source = token_account(&mint_key, &owner_key, 200);
+    let mut destination = token_account(&mint_key, &Pubkey::new_unique(), 0);
+
+    // at the threshold the hook lets the transfer through, having seen the
+    // balances it produced
+    transfer_through_hook(
+        &mut mint,
+        &mut source,
+        &mut destination,
+        &mut threshold,
+        50,
+        true,
+    )
+    .unwrap();
+    assert_eq!(SEEN_DESTINATION_AMOUNT.with(Cell::get), Some(50));
+    assert_eq!(Account::unpack(&source.data).unwrap().amount, 150);
+    assert_eq!(Account::unpack(&destination.data).unwrap().amount, 50);
+
+    // above it the hook fails the transfer, which the runtime then rolls back
+    assert_eq!(
+        transfer_through_hook(
+            &mut mint,
+            &mut source,
+            &mut destination,
+            &mut threshold,
+            51,
+            true,
+        ),
+        Err(ProgramError::Custom(OVER_THRESHOLD))
+    );
+
+    // and the hook cannot be skipped by leaving its program out
+    let mut source = token_account(&mint_key, &owner_key, 100);
+    let mut destination = token_account(&mint_key, &Pubkey::new_unique(), 0);
+    assert_eq!(
+        transfer_through_hook(
+            &mut mint,
+            &mut source,
+            &mut destination,
+            &mut threshold,
+            10,
+            false,
+        ),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+}
+
+#[test]
+fn test_transfer_hook_cannot_be_skipped_by_leaving_out_the_mint() {
+    set_stubs();
+    let token_program_id = test_token_solana::id();
+    let mint_key = Pubkey::new_from_array([1; 32]);
+    let source_key = Pubkey::new_from_array([2; 32]);
+    let destination_key = Pubkey::new_from_array([3; 32]);
+    let owner_key = Pubkey::new_from_array([4; 32]);
+    let threshold_key = Pubkey::new_from_array([5; 32]);
+
+    let mut mint = SolanaAccount::new(0, Mint::LEN, &token_program_id);
+    Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply: 100,
+        decimals: 2,
+        is_initialized: true,
+        default_account_state: AccountState::Initialized,
+        transfer_hook_program_id: COption::Some(HOOK_ID),
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut mint.data);
+    let mut threshold = SolanaAccount::new(0, 8, &HOOK_ID);
+    threshold.data = 50u64.to_le_bytes().to_vec();
+    let mut source = token_account(&mint_key, &owner_key, 100);
+
+    // an account of a mint with a hook is marked as it starts
+    let mut destination = SolanaAccount::new(
+        Rent::default().minimum_balance(Account::LEN),
+        Account::LEN,
+        &token_program_id,
+    );
+    let destination_owner_key = Pubkey::new_unique();
+    let mut destination_owner = SolanaAccount::default();
+    let mut rent_sysvar = create_account_for_test(&Rent::default());
+    let instruction = initialize_account(
+        &token_program_id,
+        &destination_key,
+        &mint_key,
+        &destination_owner_key,
+    )
+    .unwrap();
+    Processor::process(
+        &token_program_id,
+        &[
+            (&destination_key, false, &mut destination).into(),
+            (&mint_key, false, &mut mint).into(),
+            (&destination_owner_key, false, &mut destination_owner).into(),
+            (&sysvar::rent::id(), false, &mut rent_sysvar).into(),
+        ],
+        &instruction.data,
+    )
+    .unwrap();
+    assert!(Account::unpack(&destination.data).unwrap().mint_required);
+
+    // so a `Transfer`, which never invokes the hook, cannot move more than
+    // the threshold
+    let instruction = transfer(
+        &token_program_id,
+        &source_key,
+        &destination_key,
+        &owner_key,
+        &[],
+        51,
+    )
+    .unwrap();
+    let mut owner = SolanaAccount::default();
+    let mut hook_program = SolanaAccount::default();
+    assert_eq!(
+        Processor::process(
+            &token_program_id,
+            &[
+                (&source_key, false, &mut source).into(),
+                (&destination_key, false, &mut destination).into(),
+                (&owner_key, true, &mut owner).into(),
+                (&threshold_key, false, &mut threshold).into(),
+                (&HOOK_ID, false, &mut hook_program).into(),
+            ],
+            &instruction.data,
+        ),
+        Err(TokenError::MintRequiredForTransfer.into())
+    );
+    assert_eq!(Account::unpack(&source.data).unwrap().amount, 100);
+    assert_eq!(Account::unpack(&destination.data).unwrap().amount, 0);
+}
@@ -0,0 +1,25 @@
+//! The JS bindings as a browser sees them, run with
+//! `wasm-pack test --node -- --no-default-features --features wasm`.
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use test_token_solana::{
+    instruction::TokenInstruction,
+    wasm::{pack_instruction, unpack_account, unpack_instruction},
+};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_transfer_round_trip() {
+    let json = r#"{"Transfer":{"amount":42}}"#;
+    let data = pack_instruction(json).unwrap();
+    assert_eq!(data, TokenInstruction::Transfer { amount: 42 }.pack());
+    assert_eq!(unpack_instruction(&data).unwrap(), json);
+}
+
+#[wasm_bindgen_test]
+fn test_errors() {
+    assert!(pack_instruction(r#"{"Transfer":{}}"#).is_err());
+    assert!(unpack_instruction(&[u8::MAX]).is_err());
+    assert!(unpack_account(&[0; 3]).is_err());
+}